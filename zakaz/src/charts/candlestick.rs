@@ -1,55 +1,203 @@
-use plotters::coord::Shift;
+use std::ops::Range;
+use std::path::Path;
+
+use plotters::coord::{ReverseCoordTranslate, Shift};
+use plotters::coord::types::RangedCoordf64;
+use plotters::element::DashedPathElement;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
 use plotters::prelude::*;
+use slint::{Rgb8Pixel, SharedPixelBuffer};
 
 use crate::error::AppError;
 use crate::ib::types::HistoricalBar;
 use super::theme::ChartTheme;
-use super::types::{ChartViewport, VolumeBar};
+use crate::ib::analytics;
+use crate::ib::indicators::{Indicator, Macd, Rsi};
+use crate::ib::types::{AtrSmoothing, ExcludedBar, OrderSide};
+use super::layout::PaneLayout;
+use super::time_axis;
+use super::transforms::heikin_ashi;
+use super::types::{anchored_vwap, is_intraday, AtrFilter, ChartIndicators, ChartKind, ComparisonSeries, ChartViewport, Drawing, DrawingShape, ExportFormat, IndicatorPane, TradeMarkers, VolumeBar};
+
+/// A rendered chart without its crosshair. Cursor moves only change the crosshair, so it is
+/// drawn over a copy of the frame instead of rendering the chart again. Cloning shares the
+/// pixels.
+#[derive(Clone)]
+pub struct ChartFrame {
+    image: SharedPixelBuffer<Rgb8Pixel>,
+    price_coords: Cartesian2d<RangedCoordf64, RangedCoordf64>,
+}
+
+impl ChartFrame {
+    /// The rendered chart, without a crosshair
+    pub fn image(&self) -> &SharedPixelBuffer<Rgb8Pixel> {
+        &self.image
+    }
+}
+
+impl std::fmt::Debug for ChartFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChartFrame")
+            .field("width", &self.image.width())
+            .field("height", &self.image.height())
+            .finish_non_exhaustive()
+    }
+}
 
 pub struct CandlestickChart {
     width: u32,
     height: u32,
     theme: ChartTheme,
+    kind: ChartKind,
+    indicators: ChartIndicators,
+    trades: TradeMarkers,
+    drawings: Vec<Drawing>,
+    comparisons: Vec<ComparisonSeries>,
+    atr_exclusions: Vec<ExcludedBar>, // Daily bars the filtered ATR left out
+    crosshair: Option<(i32, i32)>, // Cursor position in chart pixels
+    caption: Option<String>,       // Drawn above the price pane
+    countdown: Option<chrono::Duration>, // Time left in the last bar, shown under its price
 }
 
 impl CandlestickChart {
     pub fn new(width: u32, height: u32, theme: ChartTheme) -> Self {
-        Self { width, height, theme }
+        Self {
+            width,
+            height,
+            theme,
+            kind: ChartKind::default(),
+            indicators: ChartIndicators::default(),
+            trades: TradeMarkers::default(),
+            drawings: Vec::new(),
+            comparisons: Vec::new(),
+            atr_exclusions: Vec::new(),
+            crosshair: None,
+            caption: None,
+            countdown: None,
+        }
+    }
+    
+    pub fn with_kind(mut self, kind: ChartKind) -> Self {
+        self.kind = kind;
+        self
+    }
+    
+    pub fn with_indicators(mut self, indicators: ChartIndicators) -> Self {
+        self.indicators = indicators;
+        self
+    }
+    
+    pub fn with_trades(mut self, trades: TradeMarkers) -> Self {
+        self.trades = trades;
+        self
+    }
+    
+    pub fn with_drawings(mut self, drawings: Vec<Drawing>) -> Self {
+        self.drawings = drawings;
+        self
+    }
+    
+    pub fn with_comparisons(mut self, comparisons: Vec<ComparisonSeries>) -> Self {
+        self.comparisons = comparisons;
+        self
+    }
+    
+    pub fn with_atr_exclusions(mut self, exclusions: Vec<ExcludedBar>) -> Self {
+        self.atr_exclusions = exclusions;
+        self
+    }
+    
+    pub fn with_crosshair(mut self, cursor: Option<(i32, i32)>) -> Self {
+        self.crosshair = cursor;
+        self
+    }
+    
+    pub fn with_caption(mut self, caption: Option<String>) -> Self {
+        self.caption = caption;
+        self
     }
     
+    pub fn with_countdown(mut self, countdown: Option<chrono::Duration>) -> Self {
+        self.countdown = countdown;
+        self
+    }
+    
+    /// Rendered straight into a pixel buffer for `slint::Image::from_rgb8`
     pub fn render_to_buffer(
         &self,
         bars: &[HistoricalBar],
         viewport: &ChartViewport,
-    ) -> Result<Vec<u8>, AppError> {
-        let mut buffer = vec![0u8; (self.width * self.height * 3) as usize];
+    ) -> Result<SharedPixelBuffer<Rgb8Pixel>, AppError> {
+        let frame = self.render_frame(bars, viewport)?;
+        self.render_crosshair(&frame, bars)
+    }
+    
+    /// Everything but the crosshair, for `render_crosshair` to draw over
+    pub fn render_frame(
+        &self,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+    ) -> Result<ChartFrame, AppError> {
+        let mut image = SharedPixelBuffer::<Rgb8Pixel>::new(self.width, self.height);
         
-        {
-            let root = BitMapBackend::with_buffer(&mut buffer, (self.width, self.height))
+        let price_coords = {
+            let root = BitMapBackend::with_buffer(image.make_mut_bytes(), (self.width, self.height))
                 .into_drawing_area();
-            
-            // Fill background
-            root.fill(&ChartTheme::parse_color(&self.theme.colors.background))?;
-            
-            // Calculate layout
-            let main_height = (self.height as f64 * (1.0 - self.theme.volume_height_ratio)) as i32;
-            let volume_height = (self.height as f64 * self.theme.volume_height_ratio) as i32;
-            
-            // Main price chart area
-            let (upper, lower) = root.split_vertically(main_height);
-            
-            // Draw price chart
-            self.draw_price_chart(&upper, bars, viewport)?;
-            
-            // Draw volume chart
-            self.draw_volume_chart(&lower, bars, viewport)?;
-            
+            let price_coords = self.draw_layers(&root, bars, viewport)?;
+            root.present()?;
+            price_coords
+        };
+        
+        Ok(ChartFrame { image, price_coords })
+    }
+    
+    /// The frame with this chart's crosshair drawn over a copy of it; the frame's own pixels
+    /// without one. `bars` are the ones the frame was rendered from.
+    pub fn render_crosshair(
+        &self,
+        frame: &ChartFrame,
+        bars: &[HistoricalBar],
+    ) -> Result<SharedPixelBuffer<Rgb8Pixel>, AppError> {
+        let mut buffer = frame.image.clone();
+        
+        if let Some(cursor) = self.crosshair {
+            let (width, height) = (buffer.width(), buffer.height());
+            let root = BitMapBackend::with_buffer(buffer.make_mut_bytes(), (width, height))
+                .into_drawing_area();
+            self.draw_crosshair(&root, &frame.price_coords, &self.kind.transform(bars), cursor)?;
             root.present()?;
         }
         
         Ok(buffer)
     }
     
+    /// Render to a PNG or SVG file at `path`
+    pub fn save(
+        &self,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+        path: &Path,
+        format: ExportFormat,
+    ) -> Result<(), AppError> {
+        match format {
+            ExportFormat::Png => {
+                let image = self.render_to_buffer(bars, viewport)?;
+                image::save_buffer_with_format(
+                    path,
+                    image.as_bytes(),
+                    image.width(),
+                    image.height(),
+                    image::ColorType::Rgb8,
+                    image::ImageFormat::Png,
+                ).map_err(|e| AppError::ChartError(format!("Failed to write {}: {}", path.display(), e)))
+            }
+            ExportFormat::Svg => {
+                std::fs::write(path, self.render_to_svg(bars, viewport)?)?;
+                Ok(())
+            }
+        }
+    }
+    
     pub fn render_to_svg(
         &self,
         bars: &[HistoricalBar],
@@ -60,35 +208,85 @@ impl CandlestickChart {
         {
             let root = SVGBackend::with_string(&mut svg_string, (self.width, self.height))
                 .into_drawing_area();
-            
-            // Fill background
-            root.fill(&ChartTheme::parse_color(&self.theme.colors.background))?;
-            
-            // Calculate layout
-            let main_height = (self.height as f64 * (1.0 - self.theme.volume_height_ratio)) as i32;
-            let volume_height = (self.height as f64 * self.theme.volume_height_ratio) as i32;
-            
-            // Main price chart area
-            let (upper, lower) = root.split_vertically(main_height);
-            
-            // Draw price chart
-            self.draw_price_chart(&upper, bars, viewport)?;
-            
-            // Draw volume chart
-            self.draw_volume_chart(&lower, bars, viewport)?;
-            
-            root.present()?;
+            self.draw(&root, bars, viewport)?;
         }
         
         Ok(svg_string)
     }
     
+    fn draw<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let price_coords = self.draw_layers(root, bars, viewport)?;
+        if let Some(cursor) = self.crosshair {
+            self.draw_crosshair(root, &price_coords, &self.kind.transform(bars), cursor)?;
+        }
+        
+        root.present()?;
+        Ok(())
+    }
+    
+    /// Every pane, under the crosshair. Returns the price pane's coordinates for it.
+    fn draw_layers<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+    ) -> Result<Cartesian2d<RangedCoordf64, RangedCoordf64>, AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        // Fill background
+        root.fill(&ChartTheme::parse_color(&self.theme.colors.background))?;
+        
+        // Renko and range bars stand in for the time bars everywhere
+        let bars = &*self.kind.transform(bars);
+        
+        // Price on top, then volume, then one pane per indicator
+        let mut lower_ratios = vec![self.theme.volume_height_ratio];
+        lower_ratios.extend(self.indicators.panes.iter().map(|_| self.theme.indicator_pane_height_ratio));
+        let areas = PaneLayout::new(self.height, &lower_ratios).split(root);
+        
+        let price_coords = self.draw_price_chart(&areas[0], bars, viewport)?;
+        self.draw_volume_chart(&areas[1], bars, viewport)?;
+        for (pane, area) in self.indicators.panes.iter().zip(&areas[2..]) {
+            match pane {
+                IndicatorPane::Rsi { period, overbought, oversold } => {
+                    self.draw_rsi_pane(area, bars, viewport, *period, *overbought, *oversold)?;
+                }
+                IndicatorPane::Macd { fast, slow, signal } => {
+                    self.draw_macd_pane(area, bars, viewport, Macd::new(*fast, *slow, *signal))?;
+                }
+                IndicatorPane::Atr { period, smoothing, filter } => {
+                    self.draw_atr_pane(area, bars, viewport, *period, *smoothing, filter.as_ref())?;
+                }
+            }
+        }
+        
+        self.draw_last_price(root, &price_coords, bars)?;
+        
+        if let Some(caption) = &self.caption {
+            let font = self.theme.legend_font()
+                .into_font()
+                .color(&ChartTheme::parse_color(&self.theme.colors.axis_text));
+            root.draw(&Text::new(caption.clone(), (self.theme.padding.left as i32, 4), font))?;
+        }
+        
+        Ok(price_coords)
+    }
+    
     fn draw_price_chart<DB: DrawingBackend>(
         &self,
         area: &DrawingArea<DB, Shift>,
         bars: &[HistoricalBar],
         viewport: &ChartViewport,
-    ) -> Result<(), AppError> 
+    ) -> Result<Cartesian2d<RangedCoordf64, RangedCoordf64>, AppError> 
     where 
         DB::ErrorType: 'static
     {
@@ -109,23 +307,17 @@ impl CandlestickChart {
                 viewport.y_min..viewport.y_max,
             )?;
         
-        // Draw grid
+        // Draw grid; the time axis is labelled below, from the trading calendar
+        let (family, size) = self.theme.axis_font();
         chart.configure_mesh()
-            .x_labels(10)
+            .disable_x_mesh()
+            .x_labels(0)
             .y_labels(10)
-            .x_label_formatter(&|x| {
-                let idx = (*x as usize).min(bars.len().saturating_sub(1));
-                if idx < bars.len() {
-                    bars[idx].timestamp.format("%m/%d").to_string()
-                } else {
-                    String::new()
-                }
-            })
             .y_label_formatter(&|y| format!("{:.2}", y))
             .axis_style(ChartTheme::parse_color(&self.theme.colors.axis_line))
             .label_style((
-                self.theme.font_family.as_str(),
-                self.theme.font_size,
+                family,
+                size,
                 &ChartTheme::parse_color(&self.theme.colors.axis_text)
             ))
             .light_line_style(ChartTheme::parse_color(&self.theme.colors.grid_minor))
@@ -136,17 +328,103 @@ impl CandlestickChart {
         let start_idx = viewport.x_min.floor().max(0.0) as usize;
         let end_idx = (viewport.x_max.ceil() as usize).min(bars.len());
         
-        // Draw candlesticks
-        for i in start_idx..end_idx {
-            if i >= bars.len() {
-                break;
+        self.draw_time_axis(area, &mut chart, bars, viewport, start_idx..end_idx)?;
+        self.draw_atr_exclusions(&mut chart, bars, viewport, start_idx..end_idx)?;
+        let has_profile = self.draw_volume_profile(&mut chart, bars, viewport, start_idx..end_idx)?;
+        
+        // Heikin-Ashi only changes the candles; overlays, panes and markers use the real bars
+        match self.kind {
+            ChartKind::Candlestick | ChartKind::Renko { .. } | ChartKind::RangeBars { .. } => self.draw_candles(&mut chart, &chart_area, bars, viewport, start_idx..end_idx)?,
+            ChartKind::HeikinAshi => self.draw_candles(&mut chart, &chart_area, &heikin_ashi(bars), viewport, start_idx..end_idx)?,
+            ChartKind::OhlcBars => self.draw_ohlc_bars(&mut chart, bars, start_idx..end_idx)?,
+            ChartKind::Line | ChartKind::Area => self.draw_close_line(&mut chart, bars, viewport, start_idx..end_idx)?,
+        }
+        
+        // Moving averages run over every bar so they are warmed up at the left edge
+        for (index, overlay) in self.indicators.moving_averages.iter().enumerate() {
+            let color = overlay.color.as_deref().unwrap_or(self.theme.colors.ma_color(index));
+            let points = visible_line(overlay.values(bars), start_idx, end_idx);
+            chart.draw_series(std::iter::once(PathElement::new(
+                points,
+                ChartTheme::parse_color(color).stroke_width(2),
+            )))?
+            .label(overlay.label())
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], ChartTheme::parse_color(color).stroke_width(2)));
+        }
+        
+        // VWAP restarts each session, so each session's line is drawn on its own
+        let mut has_vwap = false;
+        if let Some(vwap) = &self.indicators.vwap && self.kind.is_time_based() && is_intraday(bars) {
+            let line_color = ChartTheme::parse_color(&self.theme.colors.vwap_line);
+            let band_color = ChartTheme::parse_color(&self.theme.colors.vwap_band);
+            let first = start_idx.saturating_sub(1);
+            let mut sessions: Vec<Vec<(f64, f64, f64)>> = Vec::new();
+            let mut previous_date = None;
+            for (i, point) in vwap.values(bars).into_iter().enumerate().skip(first).take((end_idx + 1).saturating_sub(first)) {
+                let Some(point) = point else {
+                    previous_date = None;
+                    continue;
+                };
+                let date = crate::ib::market_hours::session_date(bars[i].timestamp);
+                if previous_date != Some(date) {
+                    sessions.push(Vec::new());
+                    previous_date = Some(date);
+                }
+                if let Some(session) = sessions.last_mut() {
+                    session.push((i as f64, point.vwap, point.std_dev));
+                }
             }
             
-            let bar = &bars[i];
-            let x = i as f64;
-            
+            for session in &sessions {
+                for &band in &vwap.bands {
+                    for side in [1.0, -1.0] {
+                        chart.draw_series(std::iter::once(DashedPathElement::new(
+                            session.iter().map(|&(x, v, sd)| (x, v + side * band * sd)).collect::<Vec<_>>(),
+                            4,
+                            4,
+                            band_color.stroke_width(1),
+                        )))?;
+                    }
+                }
+                let series = chart.draw_series(std::iter::once(PathElement::new(
+                    session.iter().map(|&(x, v, _)| (x, v)).collect::<Vec<_>>(),
+                    line_color.stroke_width(2),
+                )))?;
+                if !has_vwap {
+                    series
+                        .label("VWAP")
+                        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], line_color.stroke_width(2)));
+                    has_vwap = true;
+                }
+            }
+        }
+        
+        let has_comparisons = self.draw_comparisons(&mut chart, bars, start_idx..end_idx)?;
+        self.draw_drawings(&mut chart, bars, viewport)?;
+        self.draw_trades(&mut chart, bars, viewport)?;
+        
+        if !self.indicators.moving_averages.is_empty() || has_vwap || has_comparisons || has_profile {
+            self.draw_legend(&mut chart, 1.0)?;
+        }
+        
+        Ok(chart.as_coord_spec().clone())
+    }
+    
+    fn draw_candles<'a, DB: DrawingBackend + 'a>(
+        &self,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        chart_area: &DrawingArea<DB, Shift>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+        visible: Range<usize>,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let per_column = bars_per_column(viewport, chart_area.dim_in_pixel().0);
+        for (x, bar) in pixel_columns(bars, visible, per_column) {
             // Calculate candle width
-            let bar_width = 1.0 / (viewport.x_max - viewport.x_min) * chart_area.dim_in_pixel().0 as f64;
+            let bar_width = per_column as f64 / (viewport.x_max - viewport.x_min) * chart_area.dim_in_pixel().0 as f64;
             let candle_width = bar_width * self.theme.candle_width_ratio;
             let half_width = candle_width / 2.0;
             
@@ -185,6 +463,508 @@ impl CandlestickChart {
         Ok(())
     }
     
+    /// A high-low bar per bar, with the open ticked to the left and the close to the right
+    fn draw_ohlc_bars<'a, DB: DrawingBackend + 'a>(
+        &self,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        bars: &[HistoricalBar],
+        visible: Range<usize>,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let tick = self.theme.candle_width_ratio / 2.0;
+        let bullish = ChartTheme::parse_color(&self.theme.colors.candle_bullish_wick);
+        let bearish = ChartTheme::parse_color(&self.theme.colors.candle_bearish_wick);
+        for (i, bar) in bars.iter().enumerate().take(visible.end).skip(visible.start) {
+            let x = i as f64;
+            let color = if bar.close >= bar.open { bullish } else { bearish };
+            let style = color.stroke_width((self.theme.wick_width as u32).max(1));
+            chart.draw_series([
+                PathElement::new(vec![(x, bar.low), (x, bar.high)], style),
+                PathElement::new(vec![(x - tick, bar.open), (x, bar.open)], style),
+                PathElement::new(vec![(x, bar.close), (x + tick, bar.close)], style),
+            ])?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Closes joined up in `price_line`; on area charts, filled down to the bottom of the
+    /// pane in `price_area`
+    fn draw_close_line<'a, DB: DrawingBackend + 'a>(
+        &self,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+        visible: Range<usize>,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let points = visible_line(bars.iter().map(|bar| Some(bar.close)).collect(), visible.start, visible.end);
+        if let (ChartKind::Area, Some(&(first_x, _)), Some(&(last_x, _))) = (self.kind, points.first(), points.last()) {
+            let mut outline = points.clone();
+            outline.push((last_x, viewport.y_min));
+            outline.push((first_x, viewport.y_min));
+            chart.draw_series(std::iter::once(Polygon::new(
+                outline,
+                ChartTheme::parse_color(&self.theme.colors.price_area).filled(),
+            )))?;
+        }
+        chart.draw_series(std::iter::once(PathElement::new(
+            points,
+            ChartTheme::parse_color(&self.theme.colors.price_line).stroke_width(2),
+        )))?;
+        
+        Ok(())
+    }
+    
+    /// Vertical grid lines and date labels where the calendar turns over. On intraday charts,
+    /// extended-hours bars are tinted and each regular-session open gets a dashed separator.
+    /// Bars are evenly spaced whatever the time between them, so nights, weekends and
+    /// holidays take no room.
+    fn draw_time_axis<'a, DB: DrawingBackend + 'a>(
+        &self,
+        area: &DrawingArea<DB, Shift>,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+        visible: Range<usize>,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let grid = ChartTheme::parse_color(&self.theme.colors.grid_minor);
+        let extended_hours = ChartTheme::parse_color(&self.theme.colors.extended_hours_bg);
+        let separator = ChartTheme::parse_color(&self.theme.colors.session_separator);
+        let font = self.theme.axis_font()
+            .into_font()
+            .color(&ChartTheme::parse_color(&self.theme.colors.axis_text))
+            .pos(Pos::new(HPos::Center, VPos::Top));
+        let (plot_width, _) = chart.plotting_area().dim_in_pixel();
+        let (base_x, base_y) = area.get_base_pixel();
+        
+        // Renko and range bars don't follow the clock, so they have no sessions to mark
+        if self.kind.is_time_based() {
+            for run in time_axis::extended_hours_runs(bars, visible.clone()) {
+                chart.draw_series(std::iter::once(Rectangle::new(
+                    [(run.start as f64 - 0.5, viewport.y_min), (run.end as f64 - 0.5, viewport.y_max)],
+                    extended_hours.filled(),
+                )))?;
+            }
+            
+            for idx in time_axis::session_opens(bars, visible.clone()) {
+                let x = idx as f64 - 0.5;
+                chart.draw_series(std::iter::once(DashedPathElement::new(
+                    vec![(x, viewport.y_min), (x, viewport.y_max)],
+                    4,
+                    4,
+                    separator.stroke_width(1),
+                )))?;
+            }
+        }
+        
+        for (idx, text) in time_axis::axis_labels(bars, visible, (plot_width / 80).max(2) as usize) {
+            let x = idx as f64;
+            chart.draw_series(std::iter::once(PathElement::new(
+                vec![(x, viewport.y_min), (x, viewport.y_max)],
+                grid.stroke_width(1),
+            )))?;
+            let (px, py) = chart.backend_coord(&(x, viewport.y_min));
+            area.draw(&Text::new(text, (px - base_x, py - base_y + 6), font.clone()))?;
+        }
+        
+        Ok(())
+    }
+    
+    /// A tinted column behind each visible bar the filtered ATR left out
+    fn draw_atr_exclusions<'a, DB: DrawingBackend + 'a>(
+        &self,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+        visible: Range<usize>,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let tint = ChartTheme::parse_color(&self.theme.colors.atr_excluded_bg);
+        for (i, bar) in bars.iter().enumerate().take(visible.end).skip(visible.start) {
+            if self.atr_exclusion(bar).is_some() {
+                chart.draw_series(std::iter::once(Rectangle::new(
+                    [(i as f64 - 0.5, viewport.y_min), (i as f64 + 0.5, viewport.y_max)],
+                    tint.filled(),
+                )))?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// The filtered ATR's exclusion of a daily bar, if it left the bar out. Exclusions are
+    /// daily, so bars that aren't time based have none.
+    fn atr_exclusion(&self, bar: &HistoricalBar) -> Option<&ExcludedBar> {
+        if !self.kind.is_time_based() {
+            return None;
+        }
+        self.atr_exclusions.iter().find(|e| e.date.date_naive() == bar.timestamp.date_naive())
+    }
+    
+    /// Volume at price over the visible bars, or the lookback ending at the last visible one,
+    /// as rows reaching left from the right edge over a quarter of the pane. Value-area rows
+    /// are highlighted and the point of control gets a dashed level across the pane and a
+    /// legend entry. Whether a profile was drawn.
+    fn draw_volume_profile<'a, DB: DrawingBackend + 'a>(
+        &self,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+        visible: Range<usize>,
+    ) -> Result<bool, AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let Some(overlay) = &self.indicators.volume_profile else {
+            return Ok(false);
+        };
+        let end = visible.end.min(bars.len());
+        let start = overlay.lookback.map_or(visible.start, |lookback| end.saturating_sub(lookback));
+        let Some(profile) = overlay.profile(&bars[start.min(end)..end]) else {
+            return Ok(false);
+        };
+        
+        let max_volume = profile.volumes.iter().copied().fold(0.0, f64::max);
+        let width = (viewport.x_max - viewport.x_min) * 0.25;
+        let colors = &self.theme.colors;
+        let (row_color, value_color, poc_color) = (
+            ChartTheme::parse_color(&colors.volume_profile),
+            ChartTheme::parse_color(&colors.volume_profile_value_area),
+            ChartTheme::parse_color(&colors.volume_profile_poc),
+        );
+        chart.draw_series(profile.volumes.iter().enumerate().map(|(row, volume)| {
+            let bottom = profile.low + row as f64 * profile.row_height;
+            let color = if row == profile.poc {
+                poc_color.mix(0.5)
+            } else if profile.value_area.contains(&row) {
+                value_color
+            } else {
+                row_color
+            };
+            Rectangle::new(
+                [(viewport.x_max - width * volume / max_volume, bottom), (viewport.x_max, bottom + profile.row_height)],
+                color.filled(),
+            )
+        }))?;
+        
+        let poc = profile.price(profile.poc);
+        chart.draw_series(std::iter::once(DashedPathElement::new(
+            vec![(viewport.x_min, poc), (viewport.x_max, poc)],
+            6,
+            4,
+            poc_color.stroke_width(1),
+        )))?
+        .label(format!("POC {:.2}", poc))
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], poc_color.stroke_width(2)));
+        
+        Ok(true)
+    }
+    
+    /// Each comparison symbol rebased to the charted close at the first visible bar both
+    /// have, so the lines part by relative performance from there; the legend gives each
+    /// symbol's percent change across the visible bars. Whether any line was drawn.
+    fn draw_comparisons<'a, DB: DrawingBackend + 'a>(
+        &self,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        bars: &[HistoricalBar],
+        visible: Range<usize>,
+    ) -> Result<bool, AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let mut drawn = false;
+        for (index, comparison) in self.comparisons.iter().enumerate() {
+            let closes = comparison.aligned_closes(bars);
+            let Some((base_idx, base)) = closes.iter()
+                .enumerate()
+                .take(visible.end)
+                .skip(visible.start)
+                .find_map(|(i, close)| close.filter(|c| *c > 0.0).map(|c| (i, c)))
+            else {
+                continue;
+            };
+            let last = closes.iter().take(visible.end).rev().flatten().next().copied().unwrap_or(base);
+            let scale = bars[base_idx].close / base;
+            let rebased = closes.iter().map(|close| close.map(|c| c * scale)).collect();
+            
+            let color = ChartTheme::parse_color(self.theme.colors.compare_color(index));
+            chart.draw_series(std::iter::once(PathElement::new(
+                visible_line(rebased, visible.start, visible.end),
+                color.stroke_width(2),
+            )))?
+            .label(format!("{} {:+.2}%", comparison.symbol, (last / base - 1.0) * 100.0))
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color.stroke_width(2)));
+            drawn = true;
+        }
+        
+        Ok(drawn)
+    }
+    
+    /// Horizontal levels across the pane, trendlines between their anchors, zones as shaded
+    /// boxes and anchored VWAPs from their anchor bar, under the trade markers
+    fn draw_drawings<'a, DB: DrawingBackend + 'a>(
+        &self,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let x_of = |time| time_axis::bar_position(bars, time);
+        for drawing in &self.drawings {
+            let line = ChartTheme::parse_color(drawing.color.as_deref().unwrap_or(&self.theme.colors.drawing_line));
+            match drawing.shape {
+                DrawingShape::HorizontalLine { price } => {
+                    chart.draw_series(std::iter::once(PathElement::new(
+                        vec![(viewport.x_min, price), (viewport.x_max, price)],
+                        line.stroke_width(1),
+                    )))?;
+                }
+                DrawingShape::Trendline { start, end } => {
+                    let (Some(x0), Some(x1)) = (x_of(start.time), x_of(end.time)) else {
+                        continue;
+                    };
+                    chart.draw_series(std::iter::once(PathElement::new(
+                        vec![(x0, start.price), (x1, end.price)],
+                        line.stroke_width(2),
+                    )))?;
+                }
+                DrawingShape::Zone { start, end } => {
+                    let (Some(x0), Some(x1)) = (x_of(start.time), x_of(end.time)) else {
+                        continue;
+                    };
+                    let fill = match &drawing.color {
+                        Some(color) => ChartTheme::parse_color(color).mix(0.2),
+                        None => ChartTheme::parse_color(&self.theme.colors.drawing_zone),
+                    };
+                    let corners = [(x0, start.price), (x1, end.price)];
+                    chart.draw_series(std::iter::once(Rectangle::new(corners, fill.filled())))?;
+                    chart.draw_series(std::iter::once(Rectangle::new(corners, line.stroke_width(1))))?;
+                }
+                DrawingShape::AnchoredVwap { time } => {
+                    let start_idx = viewport.x_min.floor().max(0.0) as usize;
+                    let end_idx = (viewport.x_max.ceil() as usize).min(bars.len());
+                    chart.draw_series(std::iter::once(PathElement::new(
+                        visible_line(anchored_vwap(bars, time), start_idx, end_idx),
+                        line.stroke_width(2),
+                    )))?;
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// The open position's average cost as a labelled line, and a triangle at each fill's bar
+    /// and price: pointing up from below for buys, down from above for sells
+    fn draw_trades<'a, DB: DrawingBackend + 'a>(
+        &self,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        if let Some(position) = &self.trades.position {
+            let color = ChartTheme::parse_color(&self.theme.colors.position_line);
+            let cost = position.average_cost;
+            chart.draw_series(std::iter::once(DashedPathElement::new(
+                vec![(viewport.x_min, cost), (viewport.x_max, cost)],
+                6,
+                4,
+                color.stroke_width(1),
+            )))?;
+            let side = if position.quantity > 0.0 { "Long" } else { "Short" };
+            let font = self.theme.tooltip_font()
+                .into_font()
+                .color(&color)
+                .pos(Pos::new(HPos::Right, VPos::Bottom));
+            chart.draw_series(std::iter::once(
+                EmptyElement::at((viewport.x_max, cost))
+                    + Text::new(format!("{} {} @ {:.2}", side, position.quantity.abs(), cost), (-4, -2), font),
+            ))?;
+        }
+        
+        let buy = ChartTheme::parse_color(&self.theme.colors.fill_buy);
+        let sell = ChartTheme::parse_color(&self.theme.colors.fill_sell);
+        for fill in &self.trades.fills {
+            // The bar the fill happened in: the last one starting at or before it
+            let Some(idx) = bars.partition_point(|b| b.timestamp <= fill.time).checked_sub(1) else {
+                continue;
+            };
+            if (idx as f64) < viewport.x_min || (idx as f64) > viewport.x_max {
+                continue;
+            }
+            let (shape, color) = match fill.side {
+                OrderSide::Long => (vec![(0, 0), (-5, 9), (5, 9)], buy),
+                OrderSide::Short => (vec![(0, 0), (-5, -9), (5, -9)], sell),
+            };
+            chart.draw_series(std::iter::once(
+                EmptyElement::at((idx as f64, fill.price)) + Polygon::new(shape, color.filled()),
+            ))?;
+        }
+        
+        Ok(())
+    }
+    
+    /// A dashed line across the price pane at the last close, in the last candle's color,
+    /// with a tag over the price axis holding the price and the countdown to the bar's close
+    fn draw_last_price<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        price_coords: &Cartesian2d<RangedCoordf64, RangedCoordf64>,
+        bars: &[HistoricalBar],
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let Some(last) = bars.last() else {
+            return Ok(());
+        };
+        let x_range = price_coords.get_x_range();
+        if !price_coords.get_y_range().contains(&last.close) {
+            return Ok(());
+        }
+        let (left, y) = price_coords.translate(&(x_range.start, last.close));
+        let (right, _) = price_coords.translate(&(x_range.end, last.close));
+        
+        let color = ChartTheme::parse_color(if last.close >= last.open {
+            &self.theme.colors.candle_bullish_wick
+        } else {
+            &self.theme.colors.candle_bearish_wick
+        });
+        root.draw(&DashedPathElement::new(vec![(left, y), (right, y)], 4, 4, color.stroke_width(1)))?;
+        
+        let font = self.theme.tooltip_font()
+            .into_font()
+            .color(&ChartTheme::parse_color(&self.theme.colors.crosshair_text));
+        let mut lines = vec![format!("{:.2}", last.close)];
+        lines.extend(self.countdown.map(format_countdown));
+        let mut tag_width = 0;
+        let mut line_height = 0;
+        for line in &lines {
+            let (w, h) = root.estimate_text_size(line, &font)?;
+            tag_width = tag_width.max(w as i32 + 8);
+            line_height = line_height.max(h as i32 + 2);
+        }
+        // Over the price axis like the crosshair's price, the price centred on the line
+        let (tag_x, tag_y) = ((left - tag_width).max(0), y - line_height / 2 - 1);
+        let tag_height = line_height * lines.len() as i32 + 2;
+        root.draw(&Rectangle::new([(tag_x, tag_y), (tag_x + tag_width, tag_y + tag_height)], color.filled()))?;
+        for (i, line) in lines.iter().enumerate() {
+            root.draw(&Text::new(line.clone(), (tag_x + 4, tag_y + 2 + line_height * i as i32), font.clone()))?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Lines through the hovered bar and cursor, with date and price labels on the price
+    /// pane's axes and a tooltip of the bar's date, OHLCV and change beside the cursor; the
+    /// vertical line runs through every pane
+    fn draw_crosshair<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        price_coords: &Cartesian2d<RangedCoordf64, RangedCoordf64>,
+        bars: &[HistoricalBar],
+        cursor: (i32, i32),
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let x_range = price_coords.get_x_range();
+        let y_range = price_coords.get_y_range();
+        let (left, top) = price_coords.translate(&(x_range.start, y_range.end));
+        let (right, bottom) = price_coords.translate(&(x_range.end, y_range.start));
+        if bars.is_empty() || cursor.0 < left || cursor.0 > right || cursor.1 < 0 || cursor.1 > self.height as i32 {
+            return Ok(());
+        }
+        let Some((x, price)) = price_coords.reverse_translate(cursor) else {
+            return Ok(());
+        };
+        
+        // Snap to the nearest bar
+        let idx = (x.round().max(0.0) as usize).min(bars.len() - 1);
+        let bar = &bars[idx];
+        let (bar_x, _) = price_coords.translate(&(idx as f64, y_range.start));
+        
+        let line_color = ChartTheme::parse_color(&self.theme.colors.crosshair);
+        let label_bg = ChartTheme::parse_color(&self.theme.colors.crosshair_text_bg);
+        let font = self.theme.tooltip_font()
+            .into_font()
+            .color(&ChartTheme::parse_color(&self.theme.colors.crosshair_text));
+        let label = |text: &str, x: i32, y: i32| -> Result<(), AppError> {
+            let (w, h) = root.estimate_text_size(text, &font)?;
+            let (w, h) = (w as i32 + 8, h as i32 + 4);
+            root.draw(&Rectangle::new([(x, y), (x + w, y + h)], label_bg.filled()))?;
+            root.draw(&Text::new(text.to_string(), (x + 4, y + 2), font.clone()))?;
+            Ok(())
+        };
+        
+        root.draw(&PathElement::new(vec![(bar_x, 0), (bar_x, self.height as i32)], line_color.stroke_width(1)))?;
+        
+        // Price only while over the price pane
+        if (top..=bottom).contains(&cursor.1) {
+            root.draw(&PathElement::new(vec![(left, cursor.1), (right, cursor.1)], line_color.stroke_width(1)))?;
+            let text = format!("{:.2}", price);
+            let (w, h) = root.estimate_text_size(&text, &font)?;
+            label(&text, (left - w as i32 - 8).max(0), cursor.1 - h as i32 / 2 - 2)?;
+        }
+        
+        let date = if is_intraday(bars) {
+            bar.timestamp.with_timezone(&chrono_tz::America::New_York).format("%Y-%m-%d %H:%M").to_string()
+        } else {
+            bar.timestamp.format("%Y-%m-%d").to_string()
+        };
+        let (w, _) = root.estimate_text_size(&date, &font)?;
+        label(&date, (bar_x - w as i32 / 2 - 4).clamp(0, right), bottom + 2)?;
+        
+        // Tooltip beside the cursor; the change is from the previous close, or the open on the first bar
+        let reference = idx.checked_sub(1).map_or(bar.open, |previous| bars[previous].close);
+        let change = bar.close - reference;
+        let change_pct = if reference != 0.0 { change / reference * 100.0 } else { 0.0 };
+        let mut lines = vec![
+            date,
+            format!("O {:.2}", bar.open),
+            format!("H {:.2}", bar.high),
+            format!("L {:.2}", bar.low),
+            format!("C {:.2}", bar.close),
+            format!("Chg {:+.2} ({:+.2}%)", change, change_pct),
+            format!("V {}", compact_volume(bar.volume)),
+        ];
+        if let Some(exclusion) = self.atr_exclusion(bar) {
+            lines.push(format!("Excluded from ATR: {}", exclusion.reason));
+        }
+        let mut box_width = 0;
+        let mut line_height = 0;
+        for line in &lines {
+            let (w, h) = root.estimate_text_size(line, &font)?;
+            box_width = box_width.max(w as i32);
+            line_height = line_height.max(h as i32 + 2);
+        }
+        // Right of and below the cursor, flipped to the other side where it would run off the image
+        let (box_w, box_h) = (box_width + 8, line_height * lines.len() as i32 + 6);
+        let box_x = if cursor.0 + 16 + box_w <= self.width as i32 { cursor.0 + 16 } else { (cursor.0 - 16 - box_w).max(0) };
+        let box_y = if cursor.1 + 16 + box_h <= self.height as i32 { cursor.1 + 16 } else { (cursor.1 - 16 - box_h).max(0) };
+        root.draw(&Rectangle::new([(box_x, box_y), (box_x + box_w, box_y + box_h)], label_bg.filled()))?;
+        for (i, line) in lines.iter().enumerate() {
+            root.draw(&Text::new(line.clone(), (box_x + 4, box_y + 4 + line_height * i as i32), font.clone()))?;
+        }
+        
+        Ok(())
+    }
+    
     fn draw_volume_chart<DB: DrawingBackend>(
         &self,
         area: &DrawingArea<DB, Shift>,
@@ -206,9 +986,11 @@ impl CandlestickChart {
         let start_idx = viewport.x_min.floor().max(0.0) as usize;
         let end_idx = (viewport.x_max.ceil() as usize).min(bars.len());
         
-        let max_volume = bars[start_idx..end_idx]
+        let per_column = bars_per_column(viewport, chart_area.dim_in_pixel().0);
+        let columns = pixel_columns(bars, start_idx..end_idx, per_column);
+        let max_volume = columns
             .iter()
-            .map(|b| b.volume)
+            .map(|(_, b)| b.volume)
             .max()
             .unwrap_or(0) as f64;
         
@@ -225,29 +1007,25 @@ impl CandlestickChart {
             )?;
         
         // Configure volume chart
+        let (family, size) = self.theme.axis_font();
         chart.configure_mesh()
             .disable_x_mesh()
             .y_labels(3)
             .y_label_formatter(&|y| format!("{:.0}", y / 1000.0) + "K")
             .axis_style(ChartTheme::parse_color(&self.theme.colors.axis_line))
             .label_style((
-                self.theme.font_family.as_str(),
-                self.theme.font_size * 0.8,
+                family,
+                size * 0.8,
                 &ChartTheme::parse_color(&self.theme.colors.axis_text)
             ))
             .draw()?;
         
         // Draw volume bars
-        for i in start_idx..end_idx {
-            if i >= bars.len() {
-                break;
-            }
-            
-            let bar = &bars[i];
-            let x = i as f64;
+        for (x, bar) in &columns {
+            let x = *x;
             let volume_bar = VolumeBar::from_historical_bar(bar);
             
-            let bar_width = 1.0 / (viewport.x_max - viewport.x_min) * chart_area.dim_in_pixel().0 as f64;
+            let bar_width = per_column as f64 / (viewport.x_max - viewport.x_min) * chart_area.dim_in_pixel().0 as f64;
             let volume_width = bar_width * self.theme.candle_width_ratio;
             let half_width = volume_width / 2.0;
             
@@ -268,4 +1046,295 @@ impl CandlestickChart {
         
         Ok(())
     }
+    
+    fn draw_rsi_pane<DB: DrawingBackend>(
+        &self,
+        area: &DrawingArea<DB, Shift>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+        period: usize,
+        overbought: f64,
+        oversold: f64,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let mut chart = self.build_indicator_pane(area, viewport, 0.0..100.0, &|y| format!("{:.0}", y))?;
+        
+        // Overbought/oversold guides
+        let guide_color = ChartTheme::parse_color(&self.theme.colors.rsi_guide);
+        for level in [overbought, oversold] {
+            chart.draw_series(std::iter::once(DashedPathElement::new(
+                vec![(viewport.x_min, level), (viewport.x_max, level)],
+                4,
+                4,
+                guide_color.stroke_width(1),
+            )))?;
+        }
+        
+        let start_idx = viewport.x_min.floor().max(0.0) as usize;
+        let end_idx = (viewport.x_max.ceil() as usize).min(bars.len());
+        let values = Rsi::new(period).series(bars).points.into_iter().map(|(_, value)| value).collect();
+        let line_color = ChartTheme::parse_color(&self.theme.colors.rsi_line);
+        chart.draw_series(std::iter::once(PathElement::new(
+            visible_line(values, start_idx, end_idx),
+            line_color.stroke_width(2),
+        )))?
+        .label(format!("RSI({})", period))
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], line_color.stroke_width(2)));
+        
+        self.draw_legend(&mut chart, 0.8)
+    }
+    
+    fn draw_macd_pane<DB: DrawingBackend>(
+        &self,
+        area: &DrawingArea<DB, Shift>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+        mut macd: Macd,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let (fast, slow, signal) = macd.periods();
+        let start_idx = viewport.x_min.floor().max(0.0) as usize;
+        let end_idx = (viewport.x_max.ceil() as usize).min(bars.len());
+        let values: Vec<_> = macd.series(bars).points.into_iter().map(|(_, value)| value).collect();
+        
+        // Centred on zero, scaled to the visible values
+        let extent = values.iter()
+            .take(end_idx)
+            .skip(start_idx)
+            .flatten()
+            .map(|v| v.macd.abs().max(v.signal.abs()).max(v.histogram.abs()))
+            .fold(0.0, f64::max);
+        let extent = if extent > 0.0 { extent * 1.1 } else { 1.0 };
+        let mut chart = self.build_indicator_pane(area, viewport, -extent..extent, &|y| format!("{:.2}", y))?;
+        
+        // Histogram
+        let half_width = self.theme.candle_width_ratio / 2.0;
+        let positive = ChartTheme::parse_color(&self.theme.colors.macd_histogram_positive);
+        let negative = ChartTheme::parse_color(&self.theme.colors.macd_histogram_negative);
+        chart.draw_series(
+            values.iter()
+                .enumerate()
+                .take(end_idx)
+                .skip(start_idx)
+                .filter_map(|(i, value)| value.map(|v| (i as f64, v.histogram)))
+                .map(|(x, histogram)| {
+                    let color = if histogram >= 0.0 { positive } else { negative };
+                    Rectangle::new([(x - half_width, 0.0), (x + half_width, histogram)], color.filled())
+                }),
+        )?;
+        
+        let macd_color = ChartTheme::parse_color(&self.theme.colors.macd_line);
+        let signal_color = ChartTheme::parse_color(&self.theme.colors.macd_signal);
+        chart.draw_series(std::iter::once(PathElement::new(
+            visible_line(values.iter().map(|v| v.map(|v| v.macd)).collect(), start_idx, end_idx),
+            macd_color.stroke_width(2),
+        )))?
+        .label(format!("MACD({}, {}, {})", fast, slow, signal))
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], macd_color.stroke_width(2)));
+        chart.draw_series(std::iter::once(PathElement::new(
+            visible_line(values.iter().map(|v| v.map(|v| v.signal)).collect(), start_idx, end_idx),
+            signal_color.stroke_width(1),
+        )))?
+        .label("Signal")
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], signal_color.stroke_width(1)));
+        
+        self.draw_legend(&mut chart, 0.8)
+    }
+    
+    fn draw_atr_pane<DB: DrawingBackend>(
+        &self,
+        area: &DrawingArea<DB, Shift>,
+        bars: &[HistoricalBar],
+        viewport: &ChartViewport,
+        period: usize,
+        smoothing: AtrSmoothing,
+        filter: Option<&AtrFilter>,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let start_idx = viewport.x_min.floor().max(0.0) as usize;
+        let end_idx = (viewport.x_max.ceil() as usize).min(bars.len());
+        let true_ranges = analytics::true_ranges(bars);
+        let regular = analytics::rolling_atr(
+            &true_ranges.iter().map(|range| Some(*range)).collect::<Vec<_>>(),
+            period,
+            smoothing,
+        );
+        // Outliers and earnings days drop out of the filtered average
+        let filtered = filter.map(|filter| {
+            let kept: Vec<Option<f64>> = bars.iter()
+                .zip(&true_ranges)
+                .map(|(bar, range)| filter.keeps(bar, *range).then_some(*range))
+                .collect();
+            analytics::rolling_atr(&kept, period, smoothing)
+        });
+        
+        let visible_max = |values: &[Option<f64>]| {
+            values.iter().take(end_idx).skip(start_idx).flatten().fold(0.0, |max: f64, v| max.max(*v))
+        };
+        let highest = visible_max(&regular).max(filtered.as_deref().map(visible_max).unwrap_or(0.0));
+        let top = if highest > 0.0 { highest * 1.1 } else { 1.0 };
+        let mut chart = self.build_indicator_pane(area, viewport, 0.0..top, &|y| format!("{:.2}", y))?;
+        
+        let regular_color = ChartTheme::parse_color(&self.theme.colors.atr_regular_line);
+        chart.draw_series(std::iter::once(DashedPathElement::new(
+            visible_line(regular, start_idx, end_idx),
+            4,
+            3,
+            regular_color.stroke_width(1),
+        )))?
+        .label(format!("ATR({})", period))
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], regular_color.stroke_width(1)));
+        
+        if let Some(filtered) = filtered {
+            let filtered_color = ChartTheme::parse_color(&self.theme.colors.atr_line);
+            chart.draw_series(std::iter::once(PathElement::new(
+                visible_line(filtered, start_idx, end_idx),
+                filtered_color.stroke_width(2),
+            )))?
+            .label(format!("Filtered ATR({})", period))
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], filtered_color.stroke_width(2)));
+        }
+        
+        self.draw_legend(&mut chart, 0.8)
+    }
+    
+    /// Chart for an indicator pane: same x range and label width as the price pane so the
+    /// bars line up, and no x labels of its own
+    fn build_indicator_pane<'a, DB: DrawingBackend>(
+        &self,
+        area: &DrawingArea<DB, Shift>,
+        viewport: &ChartViewport,
+        y_range: Range<f64>,
+        y_label_formatter: &dyn Fn(&f64) -> String,
+    ) -> Result<ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>, AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let chart_area = area.margin(
+            5,
+            5,
+            self.theme.padding.left as i32,
+            self.theme.padding.right as i32,
+        );
+        
+        let mut chart = ChartBuilder::on(&chart_area)
+            .y_label_area_size(50)
+            .build_cartesian_2d(viewport.x_min..viewport.x_max, y_range)?;
+        
+        let (family, size) = self.theme.axis_font();
+        
+        chart.configure_mesh()
+            .disable_x_mesh()
+            .y_labels(3)
+            .y_label_formatter(y_label_formatter)
+            .axis_style(ChartTheme::parse_color(&self.theme.colors.axis_line))
+            .label_style((
+                family,
+                size * 0.8,
+                &ChartTheme::parse_color(&self.theme.colors.axis_text)
+            ))
+            .light_line_style(ChartTheme::parse_color(&self.theme.colors.grid_minor))
+            .bold_line_style(ChartTheme::parse_color(&self.theme.colors.grid_major))
+            .draw()?;
+        
+        Ok(chart)
+    }
+    
+    /// Labels of a pane's series, top left, in the legend font at `scale` of its size
+    fn draw_legend<'a, DB: DrawingBackend + 'a>(
+        &self,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        scale: f64,
+    ) -> Result<(), AppError>
+    where
+        DB::ErrorType: 'static
+    {
+        let (family, size) = self.theme.legend_font();
+        chart.configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .background_style(ChartTheme::parse_color(&self.theme.colors.crosshair_text_bg))
+            .border_style(ChartTheme::parse_color(&self.theme.colors.axis_line))
+            .label_font((
+                family,
+                size * scale,
+                &ChartTheme::parse_color(&self.theme.colors.crosshair_text),
+            ))
+            .draw()?;
+        Ok(())
+    }
+}
+
+/// Volume in thousands, millions or billions past four digits, e.g. 1.25M
+fn compact_volume(volume: i64) -> String {
+    let v = volume as f64;
+    match volume.unsigned_abs() {
+        0..10_000 => volume.to_string(),
+        10_000..1_000_000 => format!("{:.1}K", v / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.2}M", v / 1e6),
+        _ => format!("{:.2}B", v / 1e9),
+    }
+}
+
+/// A bar countdown as `m:ss`, or `h:mm:ss` from an hour up
+fn format_countdown(left: chrono::Duration) -> String {
+    let seconds = left.num_seconds().max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// How many bars share a pixel column at this zoom: 1 while bars are at least a pixel wide
+fn bars_per_column(viewport: &ChartViewport, plot_width: u32) -> usize {
+    let bar_pixels = plot_width as f64 / (viewport.x_max - viewport.x_min);
+    if bar_pixels >= 1.0 || bar_pixels <= 0.0 {
+        1
+    } else {
+        (1.0 / bar_pixels).ceil() as usize
+    }
+}
+
+/// Bars within `visible` at their x positions, `per_column` of them merged into one bar
+/// centred on the group. Groups are aligned on bar index so they don't shift while panning.
+fn pixel_columns(bars: &[HistoricalBar], visible: Range<usize>, per_column: usize) -> Vec<(f64, HistoricalBar)> {
+    let end = visible.end.min(bars.len());
+    if per_column <= 1 {
+        return (visible.start..end).map(|i| (i as f64, bars[i].clone())).collect();
+    }
+    let first = visible.start - visible.start % per_column;
+    (first..end)
+        .step_by(per_column)
+        .map(|start| {
+            let group = &bars[start..(start + per_column).min(end)];
+            let mut merged = group[0].clone();
+            for bar in &group[1..] {
+                merged.high = merged.high.max(bar.high);
+                merged.low = merged.low.min(bar.low);
+                merged.volume += bar.volume;
+                merged.count += bar.count;
+            }
+            merged.close = group[group.len() - 1].close;
+            (start as f64 + (group.len() - 1) as f64 / 2.0, merged)
+        })
+        .collect()
+}
+
+/// Points of a per-bar series within `start_idx..end_idx`, plus one bar either side so the
+/// line reaches the edges of the pane; bars without a value are skipped
+fn visible_line(values: Vec<Option<f64>>, start_idx: usize, end_idx: usize) -> Vec<(f64, f64)> {
+    let first = start_idx.saturating_sub(1);
+    values.into_iter()
+        .enumerate()
+        .skip(first)
+        .take((end_idx + 1).saturating_sub(first))
+        .filter_map(|(i, value)| value.map(|v| (i as f64, v)))
+        .collect()
 }
\ No newline at end of file
@@ -0,0 +1,113 @@
+use plotters::prelude::*;
+use slint::{Rgb8Pixel, SharedPixelBuffer};
+
+use crate::error::AppError;
+use crate::ib::performance::EquityPoint;
+use super::theme::ChartTheme;
+
+/// Cumulative closed P&L per day, with the drawdown underneath
+pub struct EquityCurveChart {
+    width: u32,
+    height: u32,
+    theme: ChartTheme,
+}
+
+impl EquityCurveChart {
+    pub fn new(width: u32, height: u32, theme: ChartTheme) -> Self {
+        Self { width, height, theme }
+    }
+
+    pub fn render_to_buffer(&self, curve: &[EquityPoint]) -> Result<SharedPixelBuffer<Rgb8Pixel>, AppError> {
+        if curve.is_empty() {
+            return Err(AppError::ChartError("No closed trades to chart".to_string()));
+        }
+        let mut buffer = SharedPixelBuffer::<Rgb8Pixel>::new(self.width, self.height);
+
+        {
+            let root = BitMapBackend::with_buffer(buffer.make_mut_bytes(), (self.width, self.height))
+                .into_drawing_area();
+            root.fill(&ChartTheme::parse_color(&self.theme.colors.background))?;
+
+            let main_height = (self.height as f64 * (1.0 - self.theme.volume_height_ratio)) as i32;
+            let (upper, lower) = root.split_vertically(main_height);
+
+            // The x axis runs over curve indices, one per trading day
+            let x_max = (curve.len() as f64 - 1.0).max(1.0);
+            let (family, size) = self.theme.axis_font();
+            let label_style = (
+                family,
+                size,
+                &ChartTheme::parse_color(&self.theme.colors.axis_text),
+            );
+
+            let low = curve.iter().map(|p| p.cumulative).fold(0.0, f64::min);
+            let high = curve.iter().map(|p| p.cumulative).fold(0.0, f64::max);
+            let margin = ((high - low) * 0.05).max(1.0);
+            let chart_area = upper.margin(
+                self.theme.padding.top as i32,
+                self.theme.padding.bottom as i32,
+                self.theme.padding.left as i32,
+                self.theme.padding.right as i32,
+            );
+            let mut chart = ChartBuilder::on(&chart_area)
+                .x_label_area_size(30)
+                .y_label_area_size(60)
+                .build_cartesian_2d(0.0..x_max, (low - margin)..(high + margin))?;
+            chart.configure_mesh()
+                .x_labels(10)
+                .y_labels(10)
+                .x_label_formatter(&|x| {
+                    curve.get(x.round().max(0.0) as usize)
+                        .map(|p| p.date.format("%m/%d").to_string())
+                        .unwrap_or_default()
+                })
+                .y_label_formatter(&|y| format!("{:.0}", y))
+                .axis_style(ChartTheme::parse_color(&self.theme.colors.axis_line))
+                .label_style(label_style)
+                .light_line_style(ChartTheme::parse_color(&self.theme.colors.grid_minor))
+                .bold_line_style(ChartTheme::parse_color(&self.theme.colors.grid_major))
+                .draw()?;
+            chart.draw_series(std::iter::once(PathElement::new(
+                vec![(0.0, 0.0), (x_max, 0.0)],
+                ChartTheme::parse_color(&self.theme.colors.axis_line),
+            )))?;
+            chart.draw_series(std::iter::once(PathElement::new(
+                curve.iter().enumerate().map(|(i, p)| (i as f64, p.cumulative)).collect::<Vec<_>>(),
+                ChartTheme::parse_color(&self.theme.colors.ma_line).stroke_width(2),
+            )))?;
+
+            let deepest = curve.iter().map(|p| p.drawdown).fold(0.0, f64::max).max(1.0);
+            let drawdown_area = lower.margin(
+                5,
+                self.theme.padding.bottom as i32,
+                self.theme.padding.left as i32,
+                self.theme.padding.right as i32,
+            );
+            let mut drawdown = ChartBuilder::on(&drawdown_area)
+                .y_label_area_size(60)
+                .build_cartesian_2d(0.0..x_max, -deepest * 1.1..0.0)?;
+            drawdown.configure_mesh()
+                .disable_x_mesh()
+                .y_labels(3)
+                .y_label_formatter(&|y| format!("{:.0}", y))
+                .axis_style(ChartTheme::parse_color(&self.theme.colors.axis_line))
+                .label_style((
+                    family,
+                    size * 0.8,
+                    &ChartTheme::parse_color(&self.theme.colors.axis_text),
+                ))
+                .draw()?;
+            let mut outline: Vec<(f64, f64)> = curve.iter().enumerate().map(|(i, p)| (i as f64, -p.drawdown)).collect();
+            outline.push((x_max, 0.0));
+            outline.push((0.0, 0.0));
+            drawdown.draw_series(std::iter::once(Polygon::new(
+                outline,
+                ChartTheme::parse_color(&self.theme.colors.volume_bearish).filled(),
+            )))?;
+
+            root.present()?;
+        }
+
+        Ok(buffer)
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use plotters::style::{register_font, FontStyle};
+
+use crate::wrn;
+
+/// Family charts fall back to when a theme's font isn't installed
+pub const FALLBACK_FAMILY: &str = "DejaVu Sans";
+
+/// Bundled so charts always have glyphs, whatever fonts the system has
+static FALLBACK_FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+
+/// The system's fonts, scanned the first time a theme asks for a family
+static SYSTEM_FONTS: LazyLock<fontdb::Database> = LazyLock::new(|| {
+    let mut db = fontdb::Database::new();
+    db.load_system_fonts();
+    db
+});
+
+/// Family each requested one is drawn with, once looked up
+static RESOLVED: LazyLock<Mutex<HashMap<String, &'static str>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The family to draw `family` with: itself, registered with plotters from the system
+/// fonts, or the bundled fallback when it isn't installed. Each family is looked up once.
+pub fn resolve(family: &str) -> &'static str {
+    let mut resolved = RESOLVED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(name) = resolved.get(family) {
+        return name;
+    }
+    let name = if family != FALLBACK_FAMILY && register_system_font(family) {
+        &*String::leak(family.to_string())
+    } else {
+        if family != FALLBACK_FAMILY {
+            wrn!("Font {} isn't installed, charts use {}", family, FALLBACK_FAMILY);
+        }
+        register_fallback();
+        FALLBACK_FAMILY
+    };
+    resolved.insert(family.to_string(), name);
+    name
+}
+
+/// Register the system's regular face of `family` under its name; false when there is none
+/// that can be drawn with
+fn register_system_font(family: &str) -> bool {
+    let query = fontdb::Query {
+        families: &[fontdb::Family::Name(family)],
+        ..Default::default()
+    };
+    let Some(id) = SYSTEM_FONTS.query(&query) else {
+        return false;
+    };
+    // Plotters reads the first face of a file, so faces further into a collection are skipped
+    let data = SYSTEM_FONTS.with_face_data(id, |data, index| (index == 0).then(|| data.to_vec()));
+    let Some(data) = data.flatten() else {
+        return false;
+    };
+    register_font(family, FontStyle::Normal, Vec::leak(data)).is_ok()
+}
+
+fn register_fallback() {
+    static REGISTERED: std::sync::Once = std::sync::Once::new();
+    REGISTERED.call_once(|| {
+        if register_font(FALLBACK_FAMILY, FontStyle::Normal, FALLBACK_FONT).is_err() {
+            wrn!("The bundled chart font couldn't be read");
+        }
+    });
+}
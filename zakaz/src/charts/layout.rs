@@ -0,0 +1,138 @@
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use serde::{Deserialize, Serialize};
+use slint::{Rgb8Pixel, SharedPixelBuffer};
+
+/// Smallest share of the height left to the price pane
+const MIN_PRICE_RATIO: f64 = 0.4;
+
+/// Pixels between the charts of a grid
+const GRID_GUTTER: u32 = 2;
+
+/// Vertical stack of panes sharing the chart width: the price pane on top, then the lower
+/// panes (volume, indicators) in order, each a fixed share of the total height
+#[derive(Debug, Clone)]
+pub struct PaneLayout {
+    heights: Vec<u32>, // Price pane first
+}
+
+impl PaneLayout {
+    /// Lower panes are scaled down together if they would leave the price pane less than
+    /// `MIN_PRICE_RATIO` of the height
+    pub fn new(total_height: u32, lower_ratios: &[f64]) -> Self {
+        let requested: f64 = lower_ratios.iter().map(|r| r.max(0.0)).sum();
+        let scale = if requested > 1.0 - MIN_PRICE_RATIO { (1.0 - MIN_PRICE_RATIO) / requested } else { 1.0 };
+        let lower: Vec<u32> = lower_ratios.iter()
+            .map(|r| (total_height as f64 * r.max(0.0) * scale) as u32)
+            .collect();
+        let price = total_height.saturating_sub(lower.iter().sum());
+
+        let mut heights = vec![price];
+        heights.extend(lower);
+        Self { heights }
+    }
+
+    pub fn pane_count(&self) -> usize {
+        self.heights.len()
+    }
+
+    /// One drawing area per pane, top to bottom
+    pub fn split<DB: DrawingBackend>(&self, area: &DrawingArea<DB, Shift>) -> Vec<DrawingArea<DB, Shift>> {
+        let breakpoints: Vec<i32> = self.heights.iter()
+            .take(self.heights.len() - 1)
+            .scan(0, |top, height| {
+                *top += *height as i32;
+                Some(*top)
+            })
+            .collect();
+        area.split_by_breakpoints([] as [i32; 0], breakpoints)
+    }
+}
+
+/// How many charts are shown at once and how they tile the chart view
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChartGrid {
+    #[default]
+    Single,
+    Pair, // Side by side
+    Quad, // Two by two
+}
+
+/// A chart's place in the chart view, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChartCell {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ChartCell {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x as i32 && x < (self.x + self.width) as i32 && y >= self.y as i32 && y < (self.y + self.height) as i32
+    }
+}
+
+impl ChartGrid {
+    pub fn slot_count(&self) -> usize {
+        match self {
+            Self::Single => 1,
+            Self::Pair => 2,
+            Self::Quad => 4,
+        }
+    }
+
+    /// Each slot's cell in a view of `width` by `height`, left to right then top to bottom,
+    /// with `GRID_GUTTER` pixels between them
+    pub fn cells(&self, width: u32, height: u32) -> Vec<ChartCell> {
+        let (columns, rows) = match self {
+            Self::Single => (1, 1),
+            Self::Pair => (2, 1),
+            Self::Quad => (2, 2),
+        };
+        // Start and length of each of `count` spans across `total`
+        let spans = |total: u32, count: u32| -> Vec<(u32, u32)> {
+            let usable = total.saturating_sub(GRID_GUTTER * (count - 1));
+            (0..count)
+                .map(|i| {
+                    let start = usable * i / count;
+                    let end = usable * (i + 1) / count;
+                    (start + GRID_GUTTER * i, end - start)
+                })
+                .collect()
+        };
+        let (xs, ys) = (spans(width, columns), spans(height, rows));
+        ys.iter()
+            .flat_map(|&(y, height)| xs.iter().map(move |&(x, width)| ChartCell { x, y, width, height }))
+            .collect()
+    }
+}
+
+/// Fill `cell` of `target` with one color
+pub fn fill_cell(target: &mut SharedPixelBuffer<Rgb8Pixel>, cell: &ChartCell, color: Rgb8Pixel) {
+    let stride = target.width() as usize;
+    let pixels = target.make_mut_slice();
+    for row in cell.y..cell.y + cell.height {
+        let start = row as usize * stride + cell.x as usize;
+        if let Some(line) = pixels.get_mut(start..start + cell.width as usize) {
+            line.fill(color);
+        }
+    }
+}
+
+/// Copy `source` into `cell` of `target`, cropped to whichever is smaller
+pub fn blit_cell(target: &mut SharedPixelBuffer<Rgb8Pixel>, source: &SharedPixelBuffer<Rgb8Pixel>, cell: &ChartCell) {
+    let (stride, source_stride) = (target.width() as usize, source.width() as usize);
+    let width = cell.width.min(source.width()) as usize;
+    let pixels = target.make_mut_slice();
+    for row in 0..cell.height.min(source.height()) as usize {
+        let start = (cell.y as usize + row) * stride + cell.x as usize;
+        let source_start = row * source_stride;
+        if let (Some(line), Some(source_line)) = (
+            pixels.get_mut(start..start + width),
+            source.as_slice().get(source_start..source_start + width),
+        ) {
+            line.copy_from_slice(source_line);
+        }
+    }
+}
@@ -1,9 +1,18 @@
 pub mod types;
 pub mod candlestick;
+pub mod equity;
 pub mod viewport;
 pub mod theme;
+pub mod layout;
+pub mod time_axis;
+pub mod transforms;
+pub mod replay;
+pub mod fonts;
 
 pub use types::ChartViewport;
-pub use candlestick::CandlestickChart;
+pub use candlestick::{CandlestickChart, ChartFrame};
+pub use equity::EquityCurveChart;
 pub use viewport::ViewportController;
-pub use theme::ChartTheme;
\ No newline at end of file
+pub use theme::ChartTheme;
+pub use replay::ReplayController;
+pub use layout::{ChartCell, ChartGrid};
\ No newline at end of file
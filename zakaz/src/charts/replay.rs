@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::ib::backtest::{self, BacktestConfig, ExitReason, Replay};
+use crate::ib::fx::DEFAULT_CURRENCY;
+use crate::ib::orphans::PositionInfo;
+use crate::ib::types::{Fill, HistoricalBar, OrderSide, OrderTemplate};
+use super::types::TradeMarkers;
+
+/// Bars already shown when a replay starts without a start time
+const DEFAULT_REPLAY_BARS: usize = 50;
+
+/// A template entered during a replay, working from the first bar after it was entered
+#[derive(Debug, Clone)]
+pub struct SimulatedEntry {
+    pub template: OrderTemplate,
+    pub start: usize, // Index of the bar the entry starts working on
+}
+
+/// Steps through a chart's bars one at a time, as if they were arriving live
+#[derive(Debug, Clone)]
+pub struct ReplayController {
+    bars: Vec<HistoricalBar>, // Every bar of the replay, oldest first
+    revealed: usize,          // Bars shown so far
+    playing: bool,
+    speed: f64,               // Bars per second while playing
+    generation: u64,          // Changes whenever playback starts, stops or changes speed
+    entries: Vec<SimulatedEntry>,
+}
+
+impl ReplayController {
+    /// A paused replay showing the bars before `from`'s session, or all but the last
+    /// `DEFAULT_REPLAY_BARS` without it. At least one bar is always shown.
+    pub fn new(bars: Vec<HistoricalBar>, from: Option<DateTime<Utc>>) -> Self {
+        let start = match from {
+            Some(from) => backtest::start_index(&bars, from),
+            None => bars.len().saturating_sub(DEFAULT_REPLAY_BARS),
+        };
+        Self {
+            revealed: start.max(1).min(bars.len()),
+            bars,
+            playing: false,
+            speed: 1.0,
+            generation: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Every bar of the replay, for when it ends
+    pub fn all_bars(&self) -> &[HistoricalBar] {
+        &self.bars
+    }
+
+    pub fn revealed_bars(&self) -> &[HistoricalBar] {
+        &self.bars[..self.revealed]
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.revealed >= self.bars.len()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Ticks scheduled under an older generation are stale
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Time between bars while playing
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.speed)
+    }
+
+    /// Show the next bar; false when there are none left
+    pub fn step(&mut self) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        self.revealed += 1;
+        true
+    }
+
+    /// Hide the latest bar, keeping at least one. Entries made after it are dropped.
+    pub fn step_back(&mut self) -> bool {
+        if self.revealed <= 1 {
+            return false;
+        }
+        self.revealed -= 1;
+        let revealed = self.revealed;
+        self.entries.retain(|entry| entry.start <= revealed);
+        true
+    }
+
+    /// Start playing, returning the generation to schedule ticks under
+    pub fn play(&mut self) -> u64 {
+        self.playing = true;
+        self.generation += 1;
+        self.generation
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+        self.generation += 1;
+    }
+
+    /// Bars per second, between 0.1 and 50. Returns the generation to schedule ticks under.
+    pub fn set_speed(&mut self, bars_per_second: f64) -> u64 {
+        self.speed = if bars_per_second.is_finite() { bars_per_second.clamp(0.1, 50.0) } else { 1.0 };
+        self.generation += 1;
+        self.generation
+    }
+
+    /// Simulate `template` from the next bar, as if it were activated now
+    pub fn enter(&mut self, template: OrderTemplate) {
+        self.entries.push(SimulatedEntry { template, start: self.revealed });
+    }
+
+    /// Each simulated entry replayed over the bars shown so far; None while it has no bar
+    /// to work on yet
+    pub fn simulated_trades(&self) -> Vec<(&OrderTemplate, Option<Result<Replay, String>>)> {
+        let config = BacktestConfig::default();
+        self.entries.iter()
+            .map(|entry| {
+                let replay = (entry.start < self.revealed)
+                    .then(|| backtest::replay(&entry.template, self.revealed_bars(), entry.start, &config));
+                (&entry.template, replay)
+            })
+            .collect()
+    }
+
+    /// Trade markers as of the latest bar shown: the real fills up to it, plus the simulated
+    /// entries' fills and the open simulated position, if any
+    pub fn markers(&self, real: &TradeMarkers) -> TradeMarkers {
+        let cutoff = self.bars.get(self.revealed).map(|bar| bar.timestamp);
+        let mut fills: Vec<Fill> = real.fills.iter()
+            .filter(|fill| cutoff.is_none_or(|cutoff| fill.time < cutoff))
+            .cloned()
+            .collect();
+        let mut position = None;
+
+        let revealed = self.revealed_bars();
+        let last_date = revealed.last().map(|bar| bar.timestamp.date_naive());
+        let time_of = |date: NaiveDate| revealed.iter()
+            .find(|bar| bar.timestamp.date_naive() == date)
+            .map(|bar| bar.timestamp);
+        for (template, replay) in self.simulated_trades() {
+            let Some(Ok(Replay::Traded(trade))) = replay else {
+                continue;
+            };
+            let simulated_fill = |n: usize, side: OrderSide, quantity: f64, price: f64, time: Option<DateTime<Utc>>| {
+                time.map(|time| Fill {
+                    exec_id: format!("replay-{}-{}", template.id, n),
+                    order_id: 0,
+                    template_id: Some(template.id.clone()),
+                    symbol: template.symbol.clone(),
+                    side,
+                    quantity,
+                    price,
+                    time,
+                    commission: None,
+                })
+            };
+            fills.extend(simulated_fill(0, trade.side, trade.quantity, trade.entry_price, time_of(trade.entry_date)));
+
+            // Closing at the end of the bars only means they ran out, so the position is open
+            let exit_side = match trade.side {
+                OrderSide::Long => OrderSide::Short,
+                OrderSide::Short => OrderSide::Long,
+            };
+            let mut open = trade.quantity;
+            for (n, exit) in trade.exits.iter().enumerate() {
+                if exit.reason == ExitReason::EndOfTest && Some(exit.date) == last_date {
+                    continue;
+                }
+                open -= exit.quantity;
+                fills.extend(simulated_fill(n + 1, exit_side, exit.quantity, exit.price, time_of(exit.date)));
+            }
+            if open > 0.0 {
+                position = Some(PositionInfo {
+                    symbol: template.symbol.clone(),
+                    currency: DEFAULT_CURRENCY.to_string(),
+                    quantity: if trade.side == OrderSide::Long { open } else { -open },
+                    average_cost: trade.entry_price,
+                });
+            }
+        }
+        fills.sort_by_key(|fill| fill.time);
+
+        TradeMarkers { fills, position }
+    }
+}
@@ -1,11 +1,15 @@
 use serde::{Deserialize, Serialize};
 
+use super::fonts;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChartColors {
     // Background
     pub background: String,
     pub grid_major: String,
     pub grid_minor: String,
+    pub extended_hours_bg: String,  // Tint over premarket and after-hours bars
+    pub session_separator: String,  // Line at each regular-session open
     
     // Candles
     pub candle_bullish_body: String,
@@ -13,6 +17,10 @@ pub struct ChartColors {
     pub candle_bearish_body: String,
     pub candle_bearish_wick: String,
     
+    // Line and area charts
+    pub price_line: String,
+    pub price_area: String,
+    
     // Volume
     pub volume_bullish: String,
     pub volume_bearish: String,
@@ -28,7 +36,50 @@ pub struct ChartColors {
     
     // Indicators
     pub atr_line: String,
+    pub atr_regular_line: String,
+    pub atr_excluded_bg: String,  // Tint behind bars the filtered ATR left out
     pub ma_line: String,
+    pub ma_line_2: String,
+    pub ma_line_3: String,
+    pub vwap_line: String,
+    pub vwap_band: String,
+    pub volume_profile: String,
+    pub volume_profile_value_area: String,
+    pub volume_profile_poc: String,
+    pub rsi_line: String,
+    pub rsi_guide: String,
+    pub macd_line: String,
+    pub macd_signal: String,
+    pub macd_histogram_positive: String,
+    pub macd_histogram_negative: String,
+    
+    // Comparison symbols
+    pub compare_line: String,
+    pub compare_line_2: String,
+    pub compare_line_3: String,
+    
+    // Trades
+    pub fill_buy: String,
+    pub fill_sell: String,
+    pub position_line: String,
+    
+    // Drawings
+    pub drawing_line: String,
+    pub drawing_zone: String,
+}
+
+impl ChartColors {
+    /// Color for the `index`th moving average, cycling through the MA colors
+    pub fn ma_color(&self, index: usize) -> &str {
+        let colors = [&self.ma_line, &self.ma_line_2, &self.ma_line_3];
+        colors[index % colors.len()]
+    }
+    
+    /// Color for the `index`th comparison symbol, cycling through the comparison colors
+    pub fn compare_color(&self, index: usize) -> &str {
+        let colors = [&self.compare_line, &self.compare_line_2, &self.compare_line_3];
+        colors[index % colors.len()]
+    }
 }
 
 impl Default for ChartColors {
@@ -38,12 +89,17 @@ impl Default for ChartColors {
             background: "#1a1a1a".to_string(),
             grid_major: "#333333".to_string(),
             grid_minor: "#262626".to_string(),
+            extended_hours_bg: "#3f51b51f".to_string(),  // 12% opacity
+            session_separator: "#ffffff4d".to_string(),  // 30% opacity
             
             candle_bullish_body: "#26a69a".to_string(),
             candle_bullish_wick: "#26a69a".to_string(),
             candle_bearish_body: "#ef5350".to_string(),
             candle_bearish_wick: "#ef5350".to_string(),
             
+            price_line: "#2196f3".to_string(),
+            price_area: "#2196f333".to_string(),  // 20% opacity
+            
             volume_bullish: "#26a69a80".to_string(),  // 50% opacity
             volume_bearish: "#ef535080".to_string(),  // 50% opacity
             
@@ -55,7 +111,32 @@ impl Default for ChartColors {
             crosshair_text: "#ffffff".to_string(),
             
             atr_line: "#ff9800".to_string(),
+            atr_regular_line: "#ffffff99".to_string(),  // 60% opacity
+            atr_excluded_bg: "#ff98002e".to_string(),  // 18% opacity
             ma_line: "#2196f3".to_string(),
+            ma_line_2: "#ab47bc".to_string(),
+            ma_line_3: "#ffeb3b".to_string(),
+            vwap_line: "#00bcd4".to_string(),
+            vwap_band: "#00bcd480".to_string(),  // 50% opacity
+            volume_profile: "#9e9e9e33".to_string(),  // 20% opacity
+            volume_profile_value_area: "#90caf94d".to_string(),  // 30% opacity
+            volume_profile_poc: "#ffc107".to_string(),
+            rsi_line: "#7e57c2".to_string(),
+            rsi_guide: "#ffffff66".to_string(),  // 40% opacity
+            macd_line: "#2196f3".to_string(),
+            macd_signal: "#ff9800".to_string(),
+            macd_histogram_positive: "#26a69a99".to_string(),  // 60% opacity
+            macd_histogram_negative: "#ef535099".to_string(),  // 60% opacity
+            compare_line: "#ff7043".to_string(),
+            compare_line_2: "#66bb6a".to_string(),
+            compare_line_3: "#ec407a".to_string(),
+            
+            fill_buy: "#00e676".to_string(),
+            fill_sell: "#ff1744".to_string(),
+            position_line: "#ffd600".to_string(),
+            
+            drawing_line: "#e0e0e0".to_string(),
+            drawing_zone: "#e0e0e033".to_string(),  // 20% opacity
         }
     }
 }
@@ -65,12 +146,29 @@ pub struct ChartTheme {
     pub colors: ChartColors,
     pub font_family: String,
     pub font_size: f64,
+    #[serde(default)]
+    pub fonts: ChartFonts,  // Per-part overrides of font_family and font_size
     pub candle_width_ratio: f64,  // 0.0 to 1.0, portion of bar width
     pub wick_width: f64,
     pub volume_height_ratio: f64,  // Portion of chart height for volume
+    pub indicator_pane_height_ratio: f64,  // Portion of chart height for each indicator pane
     pub padding: ChartPadding,
 }
 
+/// Fonts for parts of a chart; unset families and sizes are the theme's
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChartFonts {
+    pub axis: FontOverride,     // Axis and pane scale labels
+    pub tooltip: FontOverride,  // Crosshair labels, hover tooltip and price tags
+    pub legend: FontOverride,   // Series legends and the export caption
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FontOverride {
+    pub family: Option<String>,
+    pub size: Option<f64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChartPadding {
     pub top: f64,
@@ -85,9 +183,11 @@ impl Default for ChartTheme {
             colors: ChartColors::default(),
             font_family: "Arial".to_string(),
             font_size: 12.0,
+            fonts: ChartFonts::default(),
             candle_width_ratio: 0.8,
             wick_width: 1.0,
             volume_height_ratio: 0.2,
+            indicator_pane_height_ratio: 0.15,
             padding: ChartPadding {
                 top: 20.0,
                 right: 60.0,
@@ -98,33 +198,143 @@ impl Default for ChartTheme {
     }
 }
 
+/// Names of the themes that come with the app; custom themes can't take them
+pub const BUILTIN_THEMES: [&str; 2] = ["dark", "light"];
+
+/// Whether the UI and built-in chart themes follow the OS's light or dark preference, or
+/// stay on one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorSchemePreference {
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl ColorSchemePreference {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::Dark => "dark",
+            Self::Light => "light",
+        }
+    }
+    
+    /// The preference as stored by `as_str`; None for anything else
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "system" => Some(Self::System),
+            "dark" => Some(Self::Dark),
+            "light" => Some(Self::Light),
+            _ => None,
+        }
+    }
+}
+
 impl ChartTheme {
+    /// A theme that comes with the app, by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::default()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+    
     pub fn light() -> Self {
-        let mut theme = Self::default();
-        theme.colors = ChartColors {
-            background: "#ffffff".to_string(),
-            grid_major: "#e0e0e0".to_string(),
-            grid_minor: "#f0f0f0".to_string(),
-            
-            candle_bullish_body: "#4caf50".to_string(),
-            candle_bullish_wick: "#4caf50".to_string(),
-            candle_bearish_body: "#f44336".to_string(),
-            candle_bearish_wick: "#f44336".to_string(),
-            
-            volume_bullish: "#4caf5080".to_string(),
-            volume_bearish: "#f4433680".to_string(),
-            
-            axis_text: "#333333".to_string(),
-            axis_line: "#999999".to_string(),
-            
-            crosshair: "#00000066".to_string(),
-            crosshair_text_bg: "#ffffffcc".to_string(),
-            crosshair_text: "#000000".to_string(),
-            
-            atr_line: "#ff6f00".to_string(),
-            ma_line: "#1976d2".to_string(),
+        Self {
+            colors: ChartColors {
+                background: "#ffffff".to_string(),
+                grid_major: "#e0e0e0".to_string(),
+                grid_minor: "#f0f0f0".to_string(),
+                extended_hours_bg: "#3f51b514".to_string(),
+                session_separator: "#0000004d".to_string(),
+                
+                candle_bullish_body: "#4caf50".to_string(),
+                candle_bullish_wick: "#4caf50".to_string(),
+                candle_bearish_body: "#f44336".to_string(),
+                candle_bearish_wick: "#f44336".to_string(),
+                
+                price_line: "#1976d2".to_string(),
+                price_area: "#1976d233".to_string(),
+                
+                volume_bullish: "#4caf5080".to_string(),
+                volume_bearish: "#f4433680".to_string(),
+                
+                axis_text: "#333333".to_string(),
+                axis_line: "#999999".to_string(),
+                
+                crosshair: "#00000066".to_string(),
+                crosshair_text_bg: "#ffffffcc".to_string(),
+                crosshair_text: "#000000".to_string(),
+                
+                atr_line: "#ff6f00".to_string(),
+                atr_regular_line: "#00000099".to_string(),
+                atr_excluded_bg: "#ff6f0024".to_string(),
+                ma_line: "#1976d2".to_string(),
+                ma_line_2: "#8e24aa".to_string(),
+                ma_line_3: "#f9a825".to_string(),
+                vwap_line: "#00838f".to_string(),
+                vwap_band: "#00838f80".to_string(),
+                volume_profile: "#75757533".to_string(),
+                volume_profile_value_area: "#1976d240".to_string(),
+                volume_profile_poc: "#ff6f00".to_string(),
+                rsi_line: "#5e35b1".to_string(),
+                rsi_guide: "#00000066".to_string(),
+                macd_line: "#1976d2".to_string(),
+                macd_signal: "#ef6c00".to_string(),
+                macd_histogram_positive: "#4caf5099".to_string(),
+                macd_histogram_negative: "#f4433699".to_string(),
+                compare_line: "#e64a19".to_string(),
+                compare_line_2: "#388e3c".to_string(),
+                compare_line_3: "#c2185b".to_string(),
+                
+                fill_buy: "#00a152".to_string(),
+                fill_sell: "#d50000".to_string(),
+                position_line: "#ff8f00".to_string(),
+                
+                drawing_line: "#424242".to_string(),
+                drawing_zone: "#42424233".to_string(),
+            },
+            ..Self::default()
+        }
+    }
+    
+    /// Font sizes, line widths and padding multiplied by `factor`, for rendering at a
+    /// display's scale factor
+    pub fn scaled(mut self, factor: f64) -> Self {
+        self.font_size *= factor;
+        for part in [&mut self.fonts.axis, &mut self.fonts.tooltip, &mut self.fonts.legend] {
+            part.size = part.size.map(|size| size * factor);
+        }
+        self.wick_width *= factor;
+        self.padding = ChartPadding {
+            top: self.padding.top * factor,
+            right: self.padding.right * factor,
+            bottom: self.padding.bottom * factor,
+            left: self.padding.left * factor,
         };
-        theme
+        self
+    }
+    
+    /// Family and size of axis labels, the family as it can be drawn (`fonts::resolve`)
+    pub fn axis_font(&self) -> (&'static str, f64) {
+        self.font(&self.fonts.axis)
+    }
+    
+    /// Family and size of the crosshair's labels and tooltip and of price tags
+    pub fn tooltip_font(&self) -> (&'static str, f64) {
+        self.font(&self.fonts.tooltip)
+    }
+    
+    /// Family and size of legends and captions
+    pub fn legend_font(&self) -> (&'static str, f64) {
+        self.font(&self.fonts.legend)
+    }
+    
+    fn font(&self, part: &FontOverride) -> (&'static str, f64) {
+        let family = part.family.as_deref().unwrap_or(&self.font_family);
+        (fonts::resolve(family), part.size.unwrap_or(self.font_size))
     }
     
     pub fn parse_color(color: &str) -> plotters::style::RGBAColor {
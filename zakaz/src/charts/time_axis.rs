@@ -0,0 +1,154 @@
+use std::ops::Range;
+
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use chrono_tz::America::New_York;
+
+use crate::ib::market_hours;
+use crate::ib::types::HistoricalBar;
+use super::types::is_intraday;
+
+/// Bar size in seconds: the shortest step between bars, so gaps don't stretch it.
+/// A day when there is only one bar.
+pub fn bar_seconds(bars: &[HistoricalBar]) -> f64 {
+    bars.windows(2)
+        .map(|pair| (pair[1].timestamp - pair[0].timestamp).num_seconds())
+        .filter(|seconds| *seconds > 0)
+        .min()
+        .unwrap_or(86_400) as f64
+}
+
+/// The date a bar is labelled with: its own date for daily bars, the New York session date
+/// for intraday ones
+fn bar_date(bar: &HistoricalBar, intraday: bool) -> NaiveDate {
+    if intraday {
+        market_hours::session_date(bar.timestamp)
+    } else {
+        bar.timestamp.date_naive()
+    }
+}
+
+/// Fractional bar index of `time`, counting trading time only so nights, weekends and
+/// holidays take no room. Between two bars the position moves toward the later one at the
+/// bar size and reaches it once a gap starts. Before the first bar or after the last it
+/// steps by trading days on daily bars and by regular-session time on intraday ones.
+pub fn bar_position(bars: &[HistoricalBar], time: DateTime<Utc>) -> Option<f64> {
+    let (first, last) = (bars.first()?, bars.last()?);
+    let intraday = is_intraday(bars);
+    let seconds_per_bar = bar_seconds(bars);
+    // Trading time from one time to a later one, in bars
+    let elapsed = |from: DateTime<Utc>, to: DateTime<Utc>| if intraday {
+        market_hours::session_seconds_between(from, to) as f64 / seconds_per_bar
+    } else {
+        market_hours::trading_days_between(from.date_naive(), to.date_naive()) as f64
+    };
+
+    let Some(idx) = bars.partition_point(|b| b.timestamp <= time).checked_sub(1) else {
+        return Some(-elapsed(time, first.timestamp));
+    };
+    if idx == bars.len() - 1 {
+        return Some(idx as f64 + elapsed(last.timestamp, time));
+    }
+    let into = (time - bars[idx].timestamp).num_seconds() as f64;
+    Some(idx as f64 + (into / seconds_per_bar).min(1.0))
+}
+
+/// Bars in `range` at a regular-session open on an intraday chart: the first regular-hours
+/// bar of each session. None on daily charts.
+pub fn session_opens(bars: &[HistoricalBar], range: Range<usize>) -> Vec<usize> {
+    if !is_intraday(bars) {
+        return Vec::new();
+    }
+    let regular = |i: usize| market_hours::in_regular_session(bars[i].timestamp);
+    range.filter(|&i| i > 0 && i < bars.len())
+        .filter(|&i| regular(i) && (!regular(i - 1) || bar_date(&bars[i], true) != bar_date(&bars[i - 1], true)))
+        .collect()
+}
+
+/// Runs of consecutive bars in `range` outside the regular session (premarket, after hours,
+/// the afternoon of an early close) on an intraday chart. None on daily charts.
+pub fn extended_hours_runs(bars: &[HistoricalBar], range: Range<usize>) -> Vec<Range<usize>> {
+    if !is_intraday(bars) {
+        return Vec::new();
+    }
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (i, bar) in bars.iter().enumerate().take(range.end).skip(range.start) {
+        if market_hours::in_regular_session(bar.timestamp) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.end == i => run.end = i + 1,
+            _ => runs.push(i..i + 1),
+        }
+    }
+    runs
+}
+
+/// X-axis labels for the bars in `range`, at most `max_labels` of them, placed where the
+/// calendar turns over: years, months and weeks on daily charts, sessions and hours on
+/// intraday ones. The finest boundaries that fit are used, labelled by the coarsest
+/// boundary each bar starts.
+pub fn axis_labels(bars: &[HistoricalBar], range: Range<usize>, max_labels: usize) -> Vec<(usize, String)> {
+    let intraday = is_intraday(bars);
+    let end = range.end.min(bars.len());
+    // Boundary level per bar: 0 is the coarsest
+    let levels: Vec<(usize, u8)> = (range.start..end)
+        .map(|i| {
+            let level = match i.checked_sub(1).map(|p| &bars[p]) {
+                None => 0,
+                Some(previous) if intraday => {
+                    let (at, before) = (bars[i].timestamp.with_timezone(&New_York), previous.timestamp.with_timezone(&New_York));
+                    if at.date_naive() != before.date_naive() {
+                        0
+                    } else if at.hour() != before.hour() {
+                        1
+                    } else {
+                        2
+                    }
+                }
+                Some(previous) => {
+                    let (at, before) = (bar_date(&bars[i], false), bar_date(previous, false));
+                    if at.year() != before.year() {
+                        0
+                    } else if at.month() != before.month() {
+                        1
+                    } else if at.iso_week() != before.iso_week() {
+                        2
+                    } else {
+                        3
+                    }
+                }
+            };
+            (i, level)
+        })
+        .collect();
+
+    let finest = if intraday { 2 } else { 3 };
+    let picked = (0..=finest)
+        .rev()
+        .map(|depth| levels.iter().filter(|(_, level)| *level <= depth).copied().collect::<Vec<_>>())
+        .find(|picked| picked.len() <= max_labels.max(1))
+        .unwrap_or_else(|| {
+            // Even year or session starts are too dense: every nth of them
+            let coarsest: Vec<_> = levels.iter().filter(|(_, level)| *level == 0).copied().collect();
+            let step = coarsest.len().div_ceil(max_labels.max(1));
+            coarsest.into_iter().step_by(step.max(1)).collect()
+        });
+
+    picked.into_iter()
+        .map(|(i, level)| {
+            let bar = &bars[i];
+            let text = if intraday {
+                let local = bar.timestamp.with_timezone(&New_York);
+                if level == 0 { local.format("%m/%d").to_string() } else { local.format("%H:%M").to_string() }
+            } else {
+                let date = bar_date(bar, false);
+                match level {
+                    0 => date.format("%Y").to_string(),
+                    1 => date.format("%b").to_string(),
+                    _ => date.format("%m/%d").to_string(),
+                }
+            };
+            (i, text)
+        })
+        .collect()
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ib::analytics;
+use crate::ib::types::{AtrSmoothing, HistoricalBar};
+
+/// Brick size for Renko and range bars
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BrickSize {
+    Fixed(f64),                           // In price
+    Atr { multiple: f64, period: usize }, // Times the charted bars' Wilder ATR at the last bar
+}
+
+impl BrickSize {
+    /// `multiple` × ATR(14)
+    pub fn atr(multiple: f64) -> Self {
+        Self::Atr { multiple, period: 14 }
+    }
+
+    /// Size in price for these bars; None without enough bars for the ATR, or when the size
+    /// isn't positive
+    pub fn resolve(&self, bars: &[HistoricalBar]) -> Option<f64> {
+        let size = match *self {
+            Self::Fixed(size) => size,
+            Self::Atr { multiple, period } => analytics::atr(bars, period, AtrSmoothing::Wilder)? * multiple,
+        };
+        (size.is_finite() && size > 0.0).then_some(size)
+    }
+}
+
+/// Heikin-Ashi bars: each close is the bar's OHLC average and each open the midpoint of the
+/// previous Heikin-Ashi body; highs and lows stretch to cover the new body. Time, volume,
+/// WAP and count are the original bar's.
+pub fn heikin_ashi(bars: &[HistoricalBar]) -> Vec<HistoricalBar> {
+    let mut previous: Option<(f64, f64)> = None; // Open and close
+    bars.iter()
+        .map(|bar| {
+            let close = (bar.open + bar.high + bar.low + bar.close) / 4.0;
+            let open = match previous {
+                Some((open, close)) => (open + close) / 2.0,
+                None => (bar.open + bar.close) / 2.0,
+            };
+            previous = Some((open, close));
+            HistoricalBar {
+                open,
+                high: bar.high.max(open).max(close),
+                low: bar.low.min(open).min(close),
+                close,
+                ..bar.clone()
+            }
+        })
+        .collect()
+}
+
+/// Renko bricks from closes: a brick of `size` each time the close moves a full brick past
+/// the last one, in either direction; turning around takes two bricks' move. Bricks are
+/// stamped with the bar that completed them and carry the volume since the previous brick.
+pub fn renko(bars: &[HistoricalBar], size: f64) -> Vec<HistoricalBar> {
+    let Some(first) = bars.first() else {
+        return Vec::new();
+    };
+    let (mut low, mut high) = (first.close, first.close); // Last brick's body
+    let (mut volume, mut count) = (0, 0);
+    let mut bricks = Vec::new();
+    for bar in bars {
+        volume += bar.volume;
+        count += bar.count;
+        while bar.close >= high + size {
+            bricks.push(brick(bar, high, high + size, std::mem::take(&mut volume), std::mem::take(&mut count)));
+            (low, high) = (high, high + size);
+        }
+        while bar.close <= low - size {
+            bricks.push(brick(bar, low, low - size, std::mem::take(&mut volume), std::mem::take(&mut count)));
+            (low, high) = (low - size, low);
+        }
+    }
+    bricks
+}
+
+/// Bars spanning `size` from high to low each. Within a time bar, price is taken to visit
+/// the low before the high on up bars and the high before the low on down bars. Each range
+/// bar opens where the last one closed; the last one is still forming.
+pub fn range_bars(bars: &[HistoricalBar], size: f64) -> Vec<HistoricalBar> {
+    let Some(first) = bars.first() else {
+        return Vec::new();
+    };
+    let mut forming = brick(first, first.open, first.open, 0, 0);
+    let mut result = Vec::new();
+    for bar in bars {
+        forming.volume += bar.volume;
+        forming.count += bar.count;
+        let path = if bar.close >= bar.open {
+            [bar.open, bar.low, bar.high, bar.close]
+        } else {
+            [bar.open, bar.high, bar.low, bar.close]
+        };
+        for price in path {
+            loop {
+                let close = if price > forming.low + size {
+                    forming.low + size
+                } else if price < forming.high - size {
+                    forming.high - size
+                } else {
+                    forming.high = forming.high.max(price);
+                    forming.low = forming.low.min(price);
+                    forming.close = price;
+                    break;
+                };
+                let done = brick(&forming, forming.open, close, forming.volume, forming.count);
+                result.push(HistoricalBar { high: forming.high.max(close), low: forming.low.min(close), ..done });
+                forming = brick(bar, close, close, 0, 0);
+            }
+        }
+    }
+    result.push(forming);
+    result
+}
+
+/// A bar with no wicks from `open` to `close`, at `bar`'s time
+fn brick(bar: &HistoricalBar, open: f64, close: f64, volume: i64, count: i64) -> HistoricalBar {
+    HistoricalBar {
+        timestamp: bar.timestamp,
+        open,
+        high: open.max(close),
+        low: open.min(close),
+        close,
+        volume,
+        wap: (open + close) / 2.0,
+        count,
+    }
+}
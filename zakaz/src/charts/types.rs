@@ -1,5 +1,13 @@
+use crate::ib::indicators::{Ema, Indicator, Sma};
+use crate::ib::market_hours;
+use crate::ib::orphans::PositionInfo;
+use crate::ib::types::{AtrSmoothing, Fill};
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
 use crate::ib::types::HistoricalBar;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::ops::Range;
+use super::transforms::{range_bars, renko, BrickSize};
 
 #[derive(Debug, Clone)]
 pub struct ChartData {
@@ -8,6 +16,20 @@ pub struct ChartData {
     pub viewport: ChartViewport,
 }
 
+/// Size of the chart image in physical pixels, and the display's scale factor
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChartSize {
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64, // Physical pixels per logical pixel
+}
+
+impl Default for ChartSize {
+    fn default() -> Self {
+        Self { width: 800, height: 600, scale_factor: 1.0 }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ChartViewport {
     pub x_min: f64,  // Index of first visible bar
@@ -16,6 +38,33 @@ pub struct ChartViewport {
     pub y_max: f64,  // Max price in viewport
 }
 
+/// Named spans of bars to show, each ending at the latest bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ViewportPreset {
+    Last20,
+    Last60,
+    YearToDate, // From the first session of the latest bar's year
+    All,
+}
+
+impl ViewportPreset {
+    /// Index of the first bar the preset shows
+    pub fn first_bar(self, bars: &[HistoricalBar]) -> usize {
+        match self {
+            Self::Last20 => bars.len().saturating_sub(20),
+            Self::Last60 => bars.len().saturating_sub(60),
+            Self::YearToDate => {
+                let Some(last) = bars.last() else {
+                    return 0;
+                };
+                let year = market_hours::session_date(last.timestamp).year();
+                bars.partition_point(|bar| market_hours::session_date(bar.timestamp).year() < year)
+            }
+            Self::All => 0,
+        }
+    }
+}
+
 impl ChartViewport {
     pub fn new() -> Self {
         Self {
@@ -150,6 +199,452 @@ impl ChartInteraction {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MovingAverageKind {
+    Simple,
+    Exponential,
+}
+
+/// A moving average of closes drawn over the candles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MovingAverageOverlay {
+    pub kind: MovingAverageKind,
+    pub period: usize,
+    pub color: Option<String>, // None takes the theme's MA colors in turn
+}
+
+impl MovingAverageOverlay {
+    pub fn sma(period: usize) -> Self {
+        Self { kind: MovingAverageKind::Simple, period, color: None }
+    }
+
+    pub fn ema(period: usize) -> Self {
+        Self { kind: MovingAverageKind::Exponential, period, color: None }
+    }
+
+    /// Average per bar, None while warming up
+    pub fn values(&self, bars: &[HistoricalBar]) -> Vec<Option<f64>> {
+        let series = match self.kind {
+            MovingAverageKind::Simple => Sma::new(self.period).series(bars),
+            MovingAverageKind::Exponential => Ema::new(self.period).series(bars),
+        };
+        series.points.into_iter().map(|(_, value)| value).collect()
+    }
+
+    pub fn label(&self) -> String {
+        match self.kind {
+            MovingAverageKind::Simple => format!("SMA {}", self.period),
+            MovingAverageKind::Exponential => format!("EMA {}", self.period),
+        }
+    }
+}
+
+/// Session-anchored VWAP, drawn only on intraday charts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VwapOverlay {
+    pub bands: Vec<f64>, // Standard deviations either side, e.g. [1.0, 2.0]; empty for the line alone
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VwapPoint {
+    pub vwap: f64,
+    pub std_dev: f64, // Volume-weighted deviation of price from the VWAP so far this session
+}
+
+impl VwapOverlay {
+    /// VWAP per bar from each bar's `wap` and volume, restarting at every regular-session open.
+    /// None for pre/post-market bars and until the session has traded
+    pub fn values(&self, bars: &[HistoricalBar]) -> Vec<Option<VwapPoint>> {
+        let mut session = None;
+        let (mut volume, mut price_volume, mut square_volume) = (0.0, 0.0, 0.0);
+        bars.iter()
+            .map(|bar| {
+                if !market_hours::in_regular_session(bar.timestamp) {
+                    return None;
+                }
+                let date = market_hours::session_date(bar.timestamp);
+                if session != Some(date) {
+                    session = Some(date);
+                    (volume, price_volume, square_volume) = (0.0, 0.0, 0.0);
+                }
+                let price = vwap_price(bar);
+                let bar_volume = bar.volume.max(0) as f64;
+                volume += bar_volume;
+                price_volume += price * bar_volume;
+                square_volume += price * price * bar_volume;
+                if volume <= 0.0 {
+                    return None;
+                }
+                let vwap = price_volume / volume;
+                let variance = (square_volume / volume - vwap * vwap).max(0.0);
+                Some(VwapPoint { vwap, std_dev: variance.sqrt() })
+            })
+            .collect()
+    }
+}
+
+/// A bar's average traded price: its `wap`, or the typical price where IB leaves that at zero
+fn vwap_price(bar: &HistoricalBar) -> f64 {
+    if bar.wap > 0.0 { bar.wap } else { (bar.high + bar.low + bar.close) / 3.0 }
+}
+
+/// VWAP per bar from the first bar at or after `anchor` onward, never restarting. None
+/// before the anchor and until volume has traded; all None when the anchor is before the
+/// first bar, since the volume before it isn't loaded.
+pub fn anchored_vwap(bars: &[HistoricalBar], anchor: DateTime<Utc>) -> Vec<Option<f64>> {
+    let start = bars.partition_point(|bar| bar.timestamp < anchor);
+    if start == 0 && bars.first().is_some_and(|bar| bar.timestamp > anchor) {
+        return vec![None; bars.len()];
+    }
+    let (mut volume, mut price_volume) = (0.0, 0.0);
+    bars.iter()
+        .enumerate()
+        .map(|(i, bar)| {
+            if i < start {
+                return None;
+            }
+            let bar_volume = bar.volume.max(0) as f64;
+            volume += bar_volume;
+            price_volume += vwap_price(bar) * bar_volume;
+            (volume > 0.0).then(|| price_volume / volume)
+        })
+        .collect()
+}
+
+/// Volume at price, drawn as a translucent histogram against the right edge of the price pane
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VolumeProfileOverlay {
+    pub rows: usize,             // Price rows the bars' range is split into
+    pub lookback: Option<usize>, // Bars ending at the last visible one; None for the visible bars
+    pub value_area: f64,         // Share of the volume in the value area, 0.0 to 1.0
+}
+
+impl Default for VolumeProfileOverlay {
+    fn default() -> Self {
+        Self { rows: 24, lookback: None, value_area: 0.7 }
+    }
+}
+
+/// Volume traded in each price row of a range of bars
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeProfile {
+    pub low: f64,                 // Bottom of the lowest row
+    pub row_height: f64,
+    pub volumes: Vec<f64>,        // Per row, lowest first
+    pub poc: usize,               // Point of control: the row with the most volume
+    pub value_area: Range<usize>, // Rows around the point of control holding the value-area share
+}
+
+impl VolumeProfile {
+    /// Middle of a row's prices
+    pub fn price(&self, row: usize) -> f64 {
+        self.low + (row as f64 + 0.5) * self.row_height
+    }
+}
+
+impl VolumeProfileOverlay {
+    /// Profile of `bars`. Each bar's volume is spread evenly over its high-low range; a bar
+    /// without one puts it all in its close's row. None without volume or a price range.
+    pub fn profile(&self, bars: &[HistoricalBar]) -> Option<VolumeProfile> {
+        let low = bars.iter().map(|bar| bar.low).fold(f64::INFINITY, f64::min);
+        let high = bars.iter().map(|bar| bar.high).fold(f64::NEG_INFINITY, f64::max);
+        if high <= low {
+            return None;
+        }
+        let rows = self.rows.max(1);
+        let row_height = (high - low) / rows as f64;
+        let row_of = |price: f64| (((price - low) / row_height) as usize).min(rows - 1);
+
+        let mut volumes = vec![0.0; rows];
+        for bar in bars {
+            let volume = bar.volume.max(0) as f64;
+            if bar.high <= bar.low {
+                volumes[row_of(bar.close)] += volume;
+                continue;
+            }
+            for (row, row_volume) in volumes.iter_mut().enumerate().take(row_of(bar.high) + 1).skip(row_of(bar.low)) {
+                let bottom = low + row as f64 * row_height;
+                let overlap = (bar.high.min(bottom + row_height) - bar.low.max(bottom)).max(0.0);
+                *row_volume += volume * overlap / (bar.high - bar.low);
+            }
+        }
+        let total: f64 = volumes.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let poc = volumes.iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(row, _)| row)
+            .unwrap_or(0);
+
+        // Grow from the point of control toward the busier neighbouring row until the
+        // value-area share is covered
+        let target = total * self.value_area.clamp(0.0, 1.0);
+        let (mut start, mut end) = (poc, poc + 1);
+        let mut covered = volumes[poc];
+        while covered < target && (start > 0 || end < rows) {
+            let below = start.checked_sub(1).map(|row| volumes[row]);
+            let above = volumes.get(end).copied();
+            if above.is_none_or(|above| below.is_some_and(|below| below > above)) {
+                start -= 1;
+                covered += volumes[start];
+            } else {
+                covered += volumes[end];
+                end += 1;
+            }
+        }
+
+        Some(VolumeProfile { low, row_height, volumes, poc, value_area: start..end })
+    }
+}
+
+/// Whether bars are shorter than a day, i.e. two consecutive bars share a session
+pub fn is_intraday(bars: &[HistoricalBar]) -> bool {
+    bars.windows(2).any(|pair| {
+        market_hours::session_date(pair[0].timestamp) == market_hours::session_date(pair[1].timestamp)
+    })
+}
+
+/// What a streamed bar did to the charted bars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveBarUpdate {
+    Unchanged, // Older than the last bar, or it added nothing
+    Updated,   // The last bar took it in
+    Appended,  // It started a new bar
+}
+
+/// Length of an intraday `bar_size` in seconds; None for daily bars
+fn intraday_seconds(bar_size: &str) -> Option<i64> {
+    match bar_size {
+        "1 hour" => Some(3600),
+        "5 mins" => Some(300),
+        _ => None,
+    }
+}
+
+/// Start of the `bar_size` bar ("1 day", "1 hour" or "5 mins") that `time` falls in: the
+/// session date at midnight UTC for daily bars, like IB stamps them, or the clock boundary
+/// in New York time for intraday ones
+fn bar_interval_start(time: DateTime<Utc>, bar_size: &str) -> DateTime<Utc> {
+    let Some(seconds) = intraday_seconds(bar_size) else {
+        return market_hours::session_date(time).and_time(chrono::NaiveTime::MIN).and_utc();
+    };
+    let local = time.with_timezone(&chrono_tz::America::New_York);
+    let into = local.num_seconds_from_midnight() as i64 % seconds;
+    time - chrono::Duration::seconds(into) - chrono::Duration::nanoseconds(local.nanosecond() as i64)
+}
+
+/// When the `bar_size` bar stamped `start` closes: daily bars at their session's close
+/// (13:00 on early-close days), intraday ones at the end of their interval, or at the close
+/// if that comes first for a bar that started in the session
+pub fn bar_close_time(start: DateTime<Utc>, bar_size: &str) -> DateTime<Utc> {
+    let Some(seconds) = intraday_seconds(bar_size) else {
+        return market_hours::market_close_on(start.date_naive());
+    };
+    let end = bar_interval_start(start, bar_size) + chrono::Duration::seconds(seconds);
+    let close = market_hours::market_close_on(market_hours::session_date(start));
+    if start < close { end.min(close) } else { end }
+}
+
+/// Fold a streamed bar (IB's 5-second real-time bars) into `bars` of `bar_size`: into the
+/// last bar while it falls in that bar's interval, or as a new bar from the start of its
+/// interval once it doesn't. The last bar's interval may start before the clock boundary,
+/// like IB's first hourly bar of a session at 09:30, so anything up to its start counts.
+pub fn merge_live_bar(bars: &mut Vec<HistoricalBar>, live: &HistoricalBar, bar_size: &str) -> LiveBarUpdate {
+    let start = bar_interval_start(live.timestamp, bar_size);
+    let Some(last) = bars.last_mut() else {
+        bars.push(HistoricalBar { timestamp: start, ..live.clone() });
+        return LiveBarUpdate::Appended;
+    };
+    if live.timestamp < last.timestamp {
+        return LiveBarUpdate::Unchanged;
+    }
+    if start > last.timestamp {
+        bars.push(HistoricalBar { timestamp: start, ..live.clone() });
+        return LiveBarUpdate::Appended;
+    }
+
+    let volume = live.volume.max(0);
+    if volume == 0 && live.high <= last.high && live.low >= last.low && live.close == last.close {
+        return LiveBarUpdate::Unchanged;
+    }
+    let total = last.volume.max(0) + volume;
+    if total > 0 {
+        last.wap = (last.wap * last.volume.max(0) as f64 + live.wap * volume as f64) / total as f64;
+    }
+    last.high = last.high.max(live.high);
+    last.low = last.low.min(live.low);
+    last.close = live.close;
+    last.volume = total;
+    last.count += live.count;
+    LiveBarUpdate::Updated
+}
+
+/// File format for `CandlestickChart::save`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    Png,
+    Svg,
+}
+
+/// How the price pane draws bars
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ChartKind {
+    #[default]
+    Candlestick,
+    Line,                           // Closes joined up
+    Area,                           // Line of closes, filled below
+    OhlcBars,                       // High-low bar with an open tick left and a close tick right
+    HeikinAshi,                     // Candles of `heikin_ashi` bars
+    Renko { brick: BrickSize },     // Candles of `renko` bricks
+    RangeBars { range: BrickSize }, // Candles of `range_bars`
+}
+
+impl ChartKind {
+    /// Renko and range bars replace the time bars for the whole chart: every pane, overlay
+    /// and marker is drawn from them. Other kinds chart the bars as they come.
+    pub fn transform<'a>(&self, bars: &'a [HistoricalBar]) -> Cow<'a, [HistoricalBar]> {
+        match self {
+            Self::Renko { brick } => Cow::Owned(brick.resolve(bars).map(|size| renko(bars, size)).unwrap_or_default()),
+            Self::RangeBars { range } => Cow::Owned(range.resolve(bars).map(|size| range_bars(bars, size)).unwrap_or_default()),
+            _ => Cow::Borrowed(bars),
+        }
+    }
+
+    /// Whether bars are spaced by time, so sessions and VWAP mean something
+    pub fn is_time_based(&self) -> bool {
+        !matches!(self, Self::Renko { .. } | Self::RangeBars { .. })
+    }
+}
+
+/// An indicator drawn in its own pane below the volume pane, sharing the price pane's bars
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum IndicatorPane {
+    Rsi {
+        period: usize,
+        overbought: f64,
+        oversold: f64,
+    },
+    Macd {
+        fast: usize,
+        slow: usize,
+        signal: usize,
+    },
+    Atr {
+        period: usize,
+        smoothing: AtrSmoothing,
+        filter: Option<AtrFilter>, // Filled in from the symbol's filtered ATR when the chart loads
+    },
+}
+
+/// Which daily bars the filtered ATR keeps: True Range within the outlier bounds, outside
+/// earnings reactions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtrFilter {
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+    pub excluded_dates: Vec<NaiveDate>, // Earnings reaction days
+}
+
+impl AtrFilter {
+    pub fn keeps(&self, bar: &HistoricalBar, range: f64) -> bool {
+        range >= self.lower_bound
+            && range <= self.upper_bound
+            && !self.excluded_dates.contains(&bar.timestamp.date_naive())
+    }
+}
+
+impl IndicatorPane {
+    /// RSI(14) with guide lines at 70 and 30
+    pub fn rsi() -> Self {
+        Self::Rsi { period: 14, overbought: 70.0, oversold: 30.0 }
+    }
+    
+    /// MACD 12/26/9
+    pub fn macd() -> Self {
+        Self::Macd { fast: 12, slow: 26, signal: 9 }
+    }
+    
+    /// Regular and filtered ATR over `period` bars with the default smoothing
+    pub fn atr(period: usize) -> Self {
+        Self::Atr { period, smoothing: AtrSmoothing::default(), filter: None }
+    }
+}
+
+/// Indicators drawn on a chart; kept across pan and zoom until the next update changes them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChartIndicators {
+    pub moving_averages: Vec<MovingAverageOverlay>,
+    pub vwap: Option<VwapOverlay>,
+    pub volume_profile: Option<VolumeProfileOverlay>,
+    pub panes: Vec<IndicatorPane>, // Top to bottom
+}
+
+/// Another symbol's bars, drawn on the price pane as a line of relative performance
+#[derive(Debug, Clone)]
+pub struct ComparisonSeries {
+    pub symbol: String,
+    pub bars: Vec<HistoricalBar>, // Oldest first
+}
+
+impl ComparisonSeries {
+    /// Close as of each of the charted bars: the last comparison bar starting at or before
+    /// it, None before the comparison's first bar
+    pub fn aligned_closes(&self, bars: &[HistoricalBar]) -> Vec<Option<f64>> {
+        bars.iter()
+            .map(|bar| {
+                let idx = self.bars.partition_point(|b| b.timestamp <= bar.timestamp).checked_sub(1)?;
+                Some(self.bars[idx].close)
+            })
+            .collect()
+    }
+}
+
+/// Trade history on the price pane, loaded with the chart's bars
+#[derive(Debug, Clone, Default)]
+pub struct TradeMarkers {
+    pub fills: Vec<Fill>,                // Oldest first
+    pub position: Option<PositionInfo>,  // Open position, drawn at its average cost
+}
+
+/// A point a drawing is pinned to. Pinned by time rather than bar index so drawings stay put
+/// across bar sizes and as new bars arrive.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DrawingAnchor {
+    pub time: DateTime<Utc>,
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum DrawingShape {
+    HorizontalLine { price: f64 },                          // Across the whole chart
+    Trendline { start: DrawingAnchor, end: DrawingAnchor },
+    Zone { start: DrawingAnchor, end: DrawingAnchor },      // Opposite corners of a price/time box
+    AnchoredVwap { time: DateTime<Utc> },                   // VWAP from the bar at or after `time`
+}
+
+/// A user's drawing on a symbol's price pane, kept in the database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Drawing {
+    pub id: String,
+    pub symbol: String,
+    pub shape: DrawingShape,
+    pub color: Option<String>, // None: the theme's drawing colors
+}
+
+impl Drawing {
+    pub fn new(symbol: &str, shape: DrawingShape) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            symbol: symbol.to_string(),
+            shape,
+            color: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct VolumeBar {
     pub volume: i64,
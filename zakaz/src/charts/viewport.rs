@@ -61,6 +61,29 @@ impl ViewportController {
         }
     }
     
+    /// Show from bar `first` to the latest, narrowed from the left to the zoom limits
+    pub fn show_latest_from(&mut self, first: usize) {
+        if self.data_length == 0 {
+            return;
+        }
+        let x_max = self.data_length as f64 - 1.0;
+        let span = (x_max - first as f64).clamp(self.min_zoom_bars, self.max_zoom_bars);
+        self.viewport.x_min = x_max - span;
+        self.viewport.x_max = x_max;
+        self.constrain_viewport();
+    }
+    
+    /// Scroll by `pages` visible spans, negative towards older bars
+    pub fn page(&mut self, pages: f64) {
+        let span = self.viewport.x_max - self.viewport.x_min;
+        self.pan(span * pages, 0.0);
+    }
+    
+    /// Scroll to the latest bar, keeping the span
+    pub fn jump_to_latest(&mut self) {
+        self.pan(self.data_length as f64 - 1.0 - self.viewport.x_max, 0.0);
+    }
+    
     pub fn fit_y_axis(&mut self, visible_y_min: f64, visible_y_max: f64) {
         let padding = (visible_y_max - visible_y_min) * 0.1;
         self.viewport.y_min = visible_y_min - padding;
@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::{ConnectOptions, Row};
+
+/// Backups are named `<database stem>-<time>.db` with this time format, UTC
+const TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// The file behind the pool's main database; None in memory
+pub async fn database_file(pool: &SqlitePool) -> Result<Option<PathBuf>, sqlx::Error> {
+    let row = sqlx::query("SELECT file FROM pragma_database_list WHERE name = 'main'")
+        .fetch_one(pool)
+        .await?;
+    let file: String = row.get("file");
+    Ok((!file.is_empty()).then(|| PathBuf::from(file)))
+}
+
+/// A consistent copy of the database at `path`, taken while it stays in use. An older file
+/// there is replaced.
+pub async fn vacuum_into(pool: &SqlitePool, path: &Path) -> Result<(), sqlx::Error> {
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(dir)?;
+    }
+    // VACUUM INTO refuses to overwrite
+    let _ = std::fs::remove_file(path);
+    sqlx::query("VACUUM INTO ?").bind(path.to_string_lossy().into_owned()).execute(pool).await?;
+    Ok(())
+}
+
+/// Automatic backups of `database` go in a `backups` folder next to it
+pub fn backup_dir(database: &Path) -> PathBuf {
+    database.parent().unwrap_or(Path::new("")).join("backups")
+}
+
+/// Where an automatic backup of `database` taken at `at` goes
+pub fn backup_path(database: &Path, at: DateTime<Utc>) -> PathBuf {
+    backup_dir(database).join(format!("{}-{}.db", stem(database), at.format(TIME_FORMAT)))
+}
+
+/// Automatic backups of `database` with the time each was taken, oldest first. Other
+/// profiles' backups share the folder and are left out.
+pub fn list_backups(database: &Path) -> Vec<(DateTime<Utc>, PathBuf)> {
+    let prefix = format!("{}-", stem(database));
+    let Ok(entries) = std::fs::read_dir(backup_dir(database)) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<(DateTime<Utc>, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let time = name.strip_prefix(&prefix)?.strip_suffix(".db")?;
+            let time = NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok()?;
+            Some((time.and_utc(), entry.path()))
+        })
+        .collect();
+    backups.sort();
+    backups
+}
+
+/// Delete all but the newest `keep` automatic backups, returning how many went
+pub fn prune_backups(database: &Path, keep: usize) -> std::io::Result<usize> {
+    let backups = list_backups(database);
+    let excess = backups.len().saturating_sub(keep);
+    for (_, path) in &backups[..excess] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(excess)
+}
+
+/// Whether an automatic backup is due `interval_hours` after the last one; never with 0
+pub fn backup_due(last: Option<DateTime<Utc>>, now: DateTime<Utc>, interval_hours: u32) -> bool {
+    interval_hours > 0 && last.is_none_or(|last| now - last >= chrono::Duration::hours(interval_hours as i64))
+}
+
+/// Whether the database at `path` is damaged: SQLite can't read it as a database, or its
+/// quick check finds problems. A missing file isn't.
+pub async fn is_corrupt(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+    let options = match SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display())) {
+        Ok(options) => options.read_only(true),
+        Err(_) => return false,
+    };
+    let mut conn = match options.connect().await {
+        Ok(conn) => conn,
+        Err(e) => return is_corruption(&e),
+    };
+    match sqlx::query_scalar::<_, String>("PRAGMA quick_check").fetch_one(&mut conn).await {
+        Ok(result) => result != "ok",
+        Err(e) => is_corruption(&e),
+    }
+}
+
+/// SQLITE_CORRUPT or SQLITE_NOTADB, extended codes included. Anything else, like a locked
+/// file, says nothing about the data.
+fn is_corruption(e: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(e) = e else {
+        return false;
+    };
+    let code = e.code().and_then(|code| code.parse::<i32>().ok()).unwrap_or(0);
+    matches!(code & 0xff, 11 | 26)
+}
+
+/// Put `backup` in place of the damaged database at `path`. The damaged file, and any
+/// journal files beside it, are kept as `<file>.corrupt-<time>`, which is returned.
+pub fn restore(path: &Path, backup: &Path, now: DateTime<Utc>) -> std::io::Result<PathBuf> {
+    let kept = PathBuf::from(format!("{}.corrupt-{}", path.display(), now.format(TIME_FORMAT)));
+    std::fs::rename(path, &kept)?;
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let sidecar = PathBuf::from(format!("{}{}", path.display(), suffix));
+        if sidecar.exists() {
+            std::fs::rename(&sidecar, format!("{}{}", kept.display(), suffix))?;
+        }
+    }
+    std::fs::copy(backup, path)?;
+    Ok(kept)
+}
+
+fn stem(database: &Path) -> String {
+    database.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn day(d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 7, d, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_backup_retention() {
+        let temp_dir = TempDir::new().unwrap();
+        let database = temp_dir.path().join("zakaz.db");
+        std::fs::create_dir_all(backup_dir(&database)).unwrap();
+        for d in [3, 1, 2] {
+            std::fs::write(backup_path(&database, day(d)), b"").unwrap();
+        }
+        // Another profile's backup and stray files are ignored
+        std::fs::write(backup_dir(&database).join("swing-20250701-120000.db"), b"").unwrap();
+        std::fs::write(backup_dir(&database).join("zakaz-notes.db"), b"").unwrap();
+
+        let times: Vec<_> = list_backups(&database).into_iter().map(|(time, _)| time).collect();
+        assert_eq!(times, [day(1), day(2), day(3)]);
+
+        assert_eq!(prune_backups(&database, 2).unwrap(), 1);
+        assert_eq!(list_backups(&database)[0].0, day(2));
+        assert!(backup_dir(&database).join("swing-20250701-120000.db").exists());
+        assert_eq!(prune_backups(&database, 5).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_backup_due() {
+        assert!(backup_due(None, day(2), 24));
+        assert!(backup_due(Some(day(1)), day(2), 24));
+        assert!(!backup_due(Some(day(1)), day(2), 48));
+        assert!(!backup_due(None, day(2), 0));
+    }
+
+    #[tokio::test]
+    async fn test_restore_corrupt_database() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("zakaz.db");
+        assert!(!is_corrupt(&path).await);
+
+        let pool = SqlitePool::connect_with(SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display())).unwrap().create_if_missing(true))
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE kept (value TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO kept VALUES ('journal')").execute(&pool).await.unwrap();
+        assert_eq!(database_file(&pool).await.unwrap().unwrap().file_name(), path.file_name());
+        let backup = backup_path(&path, day(1));
+        vacuum_into(&pool, &backup).await.unwrap();
+        pool.close().await;
+        assert!(!is_corrupt(&path).await);
+
+        std::fs::write(&path, b"not a database, after a bad write").unwrap();
+        assert!(is_corrupt(&path).await);
+
+        let kept = restore(&path, &backup, day(2)).unwrap();
+        assert_eq!(std::fs::read(&kept).unwrap(), b"not a database, after a bad write");
+        assert!(!is_corrupt(&path).await);
+        let pool = SqlitePool::connect(&format!("sqlite:{}", path.display())).await.unwrap();
+        let value: String = sqlx::query_scalar("SELECT value FROM kept").fetch_one(&pool).await.unwrap();
+        assert_eq!(value, "journal");
+    }
+}
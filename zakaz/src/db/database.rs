@@ -1,27 +1,72 @@
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use crate::inf;
-use super::schema::{create_schema, init_default_settings, DATABASE_URL};
-use super::models::{DbOrderTemplate, DbActiveOrder, DbPosition, OrderStatus};
+use crate::{err, inf, wrn};
+use super::backup;
+use super::export::{self, CsvTable};
+use super::search::{self, SearchHit};
+use crate::ib::position_sizing::{ModelDefaults, StopRules};
+use crate::ib::types::{AuditFilter, TradingModel};
+use super::migrations;
+use super::location::DatabaseLocation;
+use super::settings::Settings;
+use super::schema::init_default_settings;
+use super::models::{DbActiveOrder, DbAtrCache, DbAuditEntry, DbBar, DbBasket, DbChecklistItem, DbDrawing, DbEarningsEvent, DbExecution, DbOrderTemplate, DbSector, DbSymbolSettings, DbTheme, DbTrade, DbTradeOutcome, DbPosition, DbTemplateRevision, DbWatchlist, DbWatchlistEntry, OrderStatus, model_to_str};
 
-#[derive(Clone)]
+/// How long a connection waits on another's write lock before failing with `database is locked`
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_CONNECTIONS: u32 = 5;
+
+#[derive(Debug, Clone)]
 pub struct Database {
     pool: SqlitePool,
 }
 
 impl Database {
+    /// Open the database of the profile picked at startup, see `DatabaseLocation::from_startup`
     pub async fn new() -> Result<Arc<Mutex<Self>>, sqlx::Error> {
-        inf!("Initializing database connection");
+        let location = DatabaseLocation::from_startup().map_err(|e| sqlx::Error::Configuration(e.into()))?;
+        location.prepare()?;
+        inf!("Using profile {} at {}", location.profile, location.path.display());
+        if backup::is_corrupt(&location.path).await {
+            Self::restore_latest_backup(&location.path);
+        }
+        Self::open(&location.url()).await
+    }
+
+    /// Replace a damaged database with its newest automatic backup, keeping the damaged file
+    /// beside it. Without a backup the file is left for SQLite to report on.
+    fn restore_latest_backup(path: &Path) {
+        let Some((taken_at, latest)) = backup::list_backups(path).pop() else {
+            err!("Database {} is damaged and there is no backup to restore", path.display());
+            return;
+        };
+        match backup::restore(path, &latest, Utc::now()) {
+            Ok(kept) => wrn!(
+                "Database {} was damaged; restored the backup from {} and kept the damaged file as {}",
+                path.display(), taken_at.format("%Y-%m-%d %H:%M UTC"), kept.display()
+            ),
+            Err(e) => err!("Database {} is damaged and restoring {} failed: {}", path.display(), latest.display(), e),
+        }
+    }
+
+    pub async fn open(url: &str) -> Result<Arc<Mutex<Self>>, sqlx::Error> {
+        inf!("Initializing database connection: {}", url);
         
         // Create connection pool
         let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect(DATABASE_URL)
+            .max_connections(MAX_CONNECTIONS)
+            .min_connections(1)
+            .acquire_timeout(BUSY_TIMEOUT * 2)
+            .connect_with(Self::connect_options(url)?)
             .await?;
         
-        // Create schema if needed
-        create_schema(&pool).await?;
+        // Create or upgrade the schema
+        migrations::run(&pool).await?;
         
         // Initialize default settings
         init_default_settings(&pool).await?;
@@ -31,30 +76,206 @@ impl Database {
         Ok(Arc::new(Mutex::new(Self { pool })))
     }
 
+    /// Options every pooled connection opens with. WAL lets the runtime and background tasks
+    /// read while one of them writes, and writers queue for up to `BUSY_TIMEOUT` instead of
+    /// failing at once. SQLite only enforces the schema's foreign keys when asked to.
+    pub fn connect_options(url: &str) -> Result<SqliteConnectOptions, sqlx::Error> {
+        Ok(SqliteConnectOptions::from_str(url)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal)
+            .busy_timeout(BUSY_TIMEOUT)
+            .foreign_keys(true))
+    }
+
+    // Backups
+    /// Copy the whole database to `path` while it stays in use, replacing a file there
+    pub async fn backup(&self, path: &Path) -> Result<(), sqlx::Error> {
+        backup::vacuum_into(&self.pool, path).await?;
+        inf!("Backed up the database to {}", path.display());
+        Ok(())
+    }
+
+    /// The database's file; None in memory
+    pub async fn file_path(&self) -> Result<Option<PathBuf>, sqlx::Error> {
+        backup::database_file(&self.pool).await
+    }
+
+    // CSV export
+    /// Write `table` to a CSV file at `path` in its fixed column layout (see `db::export`),
+    /// oldest first, returning the rows written. `from` and `to` bound when templates were
+    /// created, trades opened and executions filled.
+    pub async fn export_csv(
+        &self,
+        table: CsvTable,
+        path: &Path,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<usize, sqlx::Error> {
+        let (from, to) = (from.map(|t| t.to_rfc3339()), to.map(|t| t.to_rfc3339()));
+        let (columns, rows) = match table {
+            CsvTable::Templates => {
+                let templates = sqlx::query_as::<_, DbOrderTemplate>(
+                    "SELECT * FROM templates WHERE deleted_at IS NULL AND (?1 IS NULL OR created_at >= ?1) AND (?2 IS NULL OR created_at < ?2) ORDER BY created_at, id"
+                )
+                .bind(&from)
+                .bind(&to)
+                .fetch_all(&self.pool)
+                .await?;
+                (export::TEMPLATE_COLUMNS, templates.iter().map(export::template_row).collect::<Vec<_>>())
+            }
+            CsvTable::Trades => {
+                let trades = sqlx::query_as::<_, DbTrade>(
+                    "SELECT * FROM trades WHERE (?1 IS NULL OR opened_at >= ?1) AND (?2 IS NULL OR opened_at < ?2) ORDER BY opened_at, id"
+                )
+                .bind(&from)
+                .bind(&to)
+                .fetch_all(&self.pool)
+                .await?;
+                (export::TRADE_COLUMNS, trades.iter().map(export::trade_row).collect())
+            }
+            CsvTable::Executions => {
+                let executions = sqlx::query_as::<_, DbExecution>(
+                    "SELECT * FROM executions WHERE (?1 IS NULL OR executed_at >= ?1) AND (?2 IS NULL OR executed_at < ?2) ORDER BY executed_at, exec_id"
+                )
+                .bind(&from)
+                .bind(&to)
+                .fetch_all(&self.pool)
+                .await?;
+                (export::EXECUTION_COLUMNS, executions.iter().map(export::execution_row).collect())
+            }
+        };
+        export::write_csv(path, columns, &rows)?;
+        inf!("Exported {} {:?} row(s) to {}", rows.len(), table, path.display());
+        Ok(rows.len())
+    }
+
     // Template operations
     pub async fn create_template(&self, template: DbOrderTemplate) -> Result<(), sqlx::Error> {
         sqlx::query(
             r#"
             INSERT INTO templates (
-                id, name, symbol, side, quantity, limit_price, stop_price, 
-                technical_stop_price, time_in_force, model, status, is_read_only, 
-                risk_per_trade, created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                id, name, symbol, currency, side, quantity, limit_price, stop_price, 
+                technical_stop_price, time_in_force, time_in_force_expires_at, entry_type, entry_limit_offset, outside_rth, exit_tranches, oca_group, filled_quantity, avg_fill_price, filled_at, model, status,
+                is_read_only, risk_per_trade, sizing_strategy, stop_rules, checklist, short_availability, notes, activated_at, activate_at, expires_at, archived_at, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&template.id)
+        .bind(&template.name)
+        .bind(&template.symbol)
+        .bind(&template.currency)
+        .bind(&template.side)
+        .bind(template.quantity)
+        .bind(template.limit_price)
+        .bind(template.stop_price)
+        .bind(template.technical_stop_price)
+        .bind(&template.time_in_force)
+        .bind(&template.time_in_force_expires_at)
+        .bind(&template.entry_type)
+        .bind(template.entry_limit_offset)
+        .bind(template.outside_rth)
+        .bind(&template.exit_tranches)
+        .bind(&template.oca_group)
+        .bind(template.filled_quantity)
+        .bind(template.avg_fill_price)
+        .bind(&template.filled_at)
+        .bind(&template.model)
+        .bind(&template.status)
+        .bind(template.is_read_only)
+        .bind(template.risk_per_trade)
+        .bind(&template.sizing_strategy)
+        .bind(&template.stop_rules)
+        .bind(&template.checklist)
+        .bind(&template.short_availability)
+        .bind(&template.notes)
+        .bind(&template.activated_at)
+        .bind(&template.activate_at)
+        .bind(&template.expires_at)
+        .bind(&template.archived_at)
+        .bind(&template.created_at)
+        .bind(&template.updated_at)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+
+    /// Insert the template or overwrite every mutable column of an existing row
+    pub async fn save_template(&self, template: DbOrderTemplate) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO templates (
+                id, name, symbol, currency, side, quantity, limit_price, stop_price, 
+                technical_stop_price, time_in_force, time_in_force_expires_at, entry_type, entry_limit_offset, outside_rth, exit_tranches, oca_group, filled_quantity, avg_fill_price, filled_at, model, status,
+                is_read_only, risk_per_trade, sizing_strategy, stop_rules, checklist, short_availability, notes, activated_at, activate_at, expires_at, archived_at, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                symbol = excluded.symbol,
+                currency = excluded.currency,
+                side = excluded.side,
+                quantity = excluded.quantity,
+                limit_price = excluded.limit_price,
+                stop_price = excluded.stop_price,
+                technical_stop_price = excluded.technical_stop_price,
+                time_in_force = excluded.time_in_force,
+                time_in_force_expires_at = excluded.time_in_force_expires_at,
+                entry_type = excluded.entry_type,
+                entry_limit_offset = excluded.entry_limit_offset,
+                outside_rth = excluded.outside_rth,
+                exit_tranches = excluded.exit_tranches,
+                oca_group = excluded.oca_group,
+                filled_quantity = excluded.filled_quantity,
+                avg_fill_price = excluded.avg_fill_price,
+                filled_at = excluded.filled_at,
+                model = excluded.model,
+                status = excluded.status,
+                is_read_only = excluded.is_read_only,
+                risk_per_trade = excluded.risk_per_trade,
+                sizing_strategy = excluded.sizing_strategy,
+                stop_rules = excluded.stop_rules,
+                checklist = excluded.checklist,
+                short_availability = excluded.short_availability,
+                notes = excluded.notes,
+                activated_at = excluded.activated_at,
+                activate_at = excluded.activate_at,
+                expires_at = excluded.expires_at,
+                archived_at = excluded.archived_at
             "#
         )
         .bind(&template.id)
         .bind(&template.name)
         .bind(&template.symbol)
+        .bind(&template.currency)
         .bind(&template.side)
         .bind(template.quantity)
         .bind(template.limit_price)
         .bind(template.stop_price)
         .bind(template.technical_stop_price)
         .bind(&template.time_in_force)
+        .bind(&template.time_in_force_expires_at)
+        .bind(&template.entry_type)
+        .bind(template.entry_limit_offset)
+        .bind(template.outside_rth)
+        .bind(&template.exit_tranches)
+        .bind(&template.oca_group)
+        .bind(template.filled_quantity)
+        .bind(template.avg_fill_price)
+        .bind(&template.filled_at)
         .bind(&template.model)
         .bind(&template.status)
         .bind(template.is_read_only)
         .bind(template.risk_per_trade)
+        .bind(&template.sizing_strategy)
+        .bind(&template.stop_rules)
+        .bind(&template.checklist)
+        .bind(&template.short_availability)
+        .bind(&template.notes)
+        .bind(&template.activated_at)
+        .bind(&template.activate_at)
+        .bind(&template.expires_at)
+        .bind(&template.archived_at)
         .bind(&template.created_at)
         .bind(&template.updated_at)
         .execute(&self.pool)
@@ -65,7 +286,7 @@ impl Database {
 
     pub async fn get_template(&self, id: &str) -> Result<Option<DbOrderTemplate>, sqlx::Error> {
         let template = sqlx::query_as::<_, DbOrderTemplate>(
-            "SELECT * FROM templates WHERE id = ?"
+            "SELECT * FROM templates WHERE id = ? AND deleted_at IS NULL"
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -74,9 +295,20 @@ impl Database {
         Ok(template)
     }
 
+    /// The working set: neither archived nor deleted
     pub async fn get_all_templates(&self) -> Result<Vec<DbOrderTemplate>, sqlx::Error> {
         let templates = sqlx::query_as::<_, DbOrderTemplate>(
-            "SELECT * FROM templates ORDER BY created_at DESC"
+            "SELECT * FROM templates WHERE archived_at IS NULL AND deleted_at IS NULL ORDER BY created_at DESC"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(templates)
+    }
+
+    pub async fn get_archived_templates(&self) -> Result<Vec<DbOrderTemplate>, sqlx::Error> {
+        let templates = sqlx::query_as::<_, DbOrderTemplate>(
+            "SELECT * FROM templates WHERE archived_at IS NOT NULL AND deleted_at IS NULL ORDER BY archived_at DESC"
         )
         .fetch_all(&self.pool)
         .await?;
@@ -86,7 +318,7 @@ impl Database {
 
     pub async fn get_templates_by_status(&self, status: OrderStatus) -> Result<Vec<DbOrderTemplate>, sqlx::Error> {
         let templates = sqlx::query_as::<_, DbOrderTemplate>(
-            "SELECT * FROM templates WHERE status = ? ORDER BY created_at DESC"
+            "SELECT * FROM templates WHERE status = ? AND deleted_at IS NULL ORDER BY created_at DESC"
         )
         .bind(status.as_str())
         .fetch_all(&self.pool)
@@ -95,8 +327,90 @@ impl Database {
         Ok(templates)
     }
 
+    /// Overwrite every mutable column of an existing template; `RowNotFound` when there is none.
+    /// Unlike `save_template` a template deleted meanwhile isn't brought back.
+    pub async fn update_template(&self, template: DbOrderTemplate) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE templates SET
+                name = ?, symbol = ?, currency = ?, side = ?, quantity = ?, limit_price = ?, stop_price = ?,
+                technical_stop_price = ?, time_in_force = ?, time_in_force_expires_at = ?, entry_type = ?, entry_limit_offset = ?, outside_rth = ?, exit_tranches = ?, oca_group = ?, filled_quantity = ?, avg_fill_price = ?, filled_at = ?, model = ?, status = ?,
+                is_read_only = ?, risk_per_trade = ?, sizing_strategy = ?, stop_rules = ?, checklist = ?, short_availability = ?, notes = ?, activated_at = ?, activate_at = ?, expires_at = ?, archived_at = ?
+            WHERE id = ? AND deleted_at IS NULL
+            "#
+        )
+        .bind(&template.name)
+        .bind(&template.symbol)
+        .bind(&template.currency)
+        .bind(&template.side)
+        .bind(template.quantity)
+        .bind(template.limit_price)
+        .bind(template.stop_price)
+        .bind(template.technical_stop_price)
+        .bind(&template.time_in_force)
+        .bind(&template.time_in_force_expires_at)
+        .bind(&template.entry_type)
+        .bind(template.entry_limit_offset)
+        .bind(template.outside_rth)
+        .bind(&template.exit_tranches)
+        .bind(&template.oca_group)
+        .bind(template.filled_quantity)
+        .bind(template.avg_fill_price)
+        .bind(&template.filled_at)
+        .bind(&template.model)
+        .bind(&template.status)
+        .bind(template.is_read_only)
+        .bind(template.risk_per_trade)
+        .bind(&template.sizing_strategy)
+        .bind(&template.stop_rules)
+        .bind(&template.checklist)
+        .bind(&template.short_availability)
+        .bind(&template.notes)
+        .bind(&template.activated_at)
+        .bind(&template.activate_at)
+        .bind(&template.expires_at)
+        .bind(&template.archived_at)
+        .bind(&template.id)
+        .execute(&self.pool)
+        .await?;
+        
+        found(result.rows_affected())
+    }
+
+    /// Move a template's entry and stop, leaving the rest of the row alone
+    pub async fn update_template_prices(&self, id: &str, limit_price: f64, stop_price: f64) -> Result<(), sqlx::Error> {
+        let result = sqlx::query("UPDATE templates SET limit_price = ?, stop_price = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(limit_price)
+            .bind(stop_price)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        
+        found(result.rows_affected())
+    }
+
+    pub async fn update_template_quantity(&self, id: &str, quantity: i64) -> Result<(), sqlx::Error> {
+        let result = sqlx::query("UPDATE templates SET quantity = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(quantity)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        
+        found(result.rows_affected())
+    }
+
+    pub async fn update_template_notes(&self, id: &str, notes: Option<&str>) -> Result<(), sqlx::Error> {
+        let result = sqlx::query("UPDATE templates SET notes = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(notes)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        
+        found(result.rows_affected())
+    }
+
     pub async fn update_template_status(&self, id: &str, status: OrderStatus) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE templates SET status = ? WHERE id = ?")
+        sqlx::query("UPDATE templates SET status = ? WHERE id = ? AND deleted_at IS NULL")
             .bind(status.as_str())
             .bind(id)
             .execute(&self.pool)
@@ -105,46 +419,197 @@ impl Database {
         Ok(())
     }
 
+    /// Soft delete: the template drops out of every query but its revisions and audit trail stay
+    /// until `purge_deleted_templates`. It leaves its baskets and any live order row at once.
     pub async fn delete_template(&self, id: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("DELETE FROM templates WHERE id = ?")
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE templates SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM basket_templates WHERE template_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM active_orders WHERE template_id = ?")
             .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Remove templates deleted before `before` for good, with their history; returns how many
+    pub async fn purge_deleted_templates(&self, before: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM templates WHERE deleted_at IS NOT NULL AND deleted_at < ?")
+            .bind(before.to_rfc3339())
             .execute(&self.pool)
             .await?;
         
-        Ok(())
+        Ok(result.rows_affected())
     }
 
-    // Active order operations
-    pub async fn create_active_order(&self, active_order: DbActiveOrder) -> Result<(), sqlx::Error> {
+    // Template revision operations
+    pub async fn record_template_revision(&self, template_id: &str, changes_json: &str) -> Result<i64, sqlx::Error> {
+        let (revision,) = sqlx::query_as::<_, (i64,)>(
+            r#"
+            INSERT INTO template_revisions (template_id, revision, changes, changed_at)
+            VALUES (
+                ?,
+                (SELECT COALESCE(MAX(revision), 0) + 1 FROM template_revisions WHERE template_id = ?),
+                ?,
+                ?
+            )
+            RETURNING revision
+            "#
+        )
+        .bind(template_id)
+        .bind(template_id)
+        .bind(changes_json)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .fetch_one(&self.pool)
+        .await?;
+        
+        Ok(revision)
+    }
+
+    pub async fn get_template_revisions(&self, template_id: &str) -> Result<Vec<DbTemplateRevision>, sqlx::Error> {
+        let revisions = sqlx::query_as::<_, DbTemplateRevision>(
+            "SELECT * FROM template_revisions WHERE template_id = ? ORDER BY revision"
+        )
+        .bind(template_id)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(revisions)
+    }
+
+    // Audit trail operations
+    pub async fn record_audit(&self, entry: &DbAuditEntry) -> Result<(), sqlx::Error> {
         sqlx::query(
             r#"
-            INSERT INTO active_orders (template_id, ib_order_id, ib_stop_order_id, submitted_at)
-            VALUES (?, ?, ?, ?)
+            INSERT INTO order_audit (template_id, action, source, account_type, before_state, after_state, recorded_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
             "#
         )
-        .bind(&active_order.template_id)
-        .bind(active_order.ib_order_id)
-        .bind(active_order.ib_stop_order_id)
-        .bind(&active_order.submitted_at)
+        .bind(&entry.template_id)
+        .bind(&entry.action)
+        .bind(&entry.source)
+        .bind(&entry.account_type)
+        .bind(&entry.before_state)
+        .bind(&entry.after_state)
+        .bind(&entry.recorded_at)
         .execute(&self.pool)
         .await?;
         
         Ok(())
     }
 
-    pub async fn get_active_order_by_ib_id(&self, ib_order_id: i64) -> Result<Option<DbActiveOrder>, sqlx::Error> {
-        let order = sqlx::query_as::<_, DbActiveOrder>(
-            "SELECT * FROM active_orders WHERE ib_order_id = ?"
+    /// Up to `limit` entries matching `filter` after skipping `offset`, newest first, and how many
+    /// match in all. Entries outlive their templates, so the symbol comes from the snapshots.
+    pub async fn get_audit_page(&self, filter: &AuditFilter, offset: usize, limit: usize) -> Result<(Vec<DbAuditEntry>, usize), sqlx::Error> {
+        const MATCHING: &str = r#"
+            FROM order_audit
+            WHERE (?1 IS NULL OR template_id = ?1)
+                AND (?2 IS NULL OR json_extract(COALESCE(after_state, before_state), '$.symbol') = ?2)
+                AND (?3 IS NULL OR action = ?3)
+                AND (?4 IS NULL OR source = ?4)
+                AND (?5 IS NULL OR recorded_at >= ?5)
+                AND (?6 IS NULL OR recorded_at < ?6)
+        "#;
+        let symbol = filter.symbol.as_ref().map(|s| s.to_uppercase());
+        let action = filter.action.map(|a| a.as_str());
+        let source = filter.source.map(|s| s.as_str());
+        let (from, to) = (filter.from.map(|t| t.to_rfc3339()), filter.to.map(|t| t.to_rfc3339()));
+        
+        let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) {}", MATCHING))
+            .bind(&filter.template_id)
+            .bind(&symbol)
+            .bind(action)
+            .bind(source)
+            .bind(&from)
+            .bind(&to)
+            .fetch_one(&self.pool)
+            .await?;
+        let entries = sqlx::query_as::<_, DbAuditEntry>(&format!("SELECT * {} ORDER BY id DESC LIMIT ?7 OFFSET ?8", MATCHING))
+            .bind(&filter.template_id)
+            .bind(&symbol)
+            .bind(action)
+            .bind(source)
+            .bind(&from)
+            .bind(&to)
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok((entries, total as usize))
+    }
+
+    pub async fn get_audit_trail(&self, template_id: &str) -> Result<Vec<DbAuditEntry>, sqlx::Error> {
+        let entries = sqlx::query_as::<_, DbAuditEntry>(
+            "SELECT * FROM order_audit WHERE template_id = ? ORDER BY id"
         )
-        .bind(ib_order_id)
-        .fetch_optional(&self.pool)
+        .bind(template_id)
+        .fetch_all(&self.pool)
         .await?;
         
-        Ok(order)
+        Ok(entries)
     }
 
-    pub async fn delete_active_order(&self, template_id: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("DELETE FROM active_orders WHERE template_id = ?")
+    // Basket operations
+    /// Create a basket with its member templates in order, all or nothing
+    pub async fn create_basket(&self, basket: DbBasket, template_ids: &[String]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO baskets (id, name, created_at) VALUES (?, ?, ?)")
+            .bind(&basket.id)
+            .bind(&basket.name)
+            .bind(&basket.created_at)
+            .execute(&mut *tx)
+            .await?;
+        for template_id in template_ids {
+            insert_basket_template(&mut tx, &basket.id, template_id).await?;
+        }
+        tx.commit().await?;
+        
+        Ok(())
+    }
+
+    pub async fn get_basket(&self, id: &str) -> Result<Option<DbBasket>, sqlx::Error> {
+        let basket = sqlx::query_as::<_, DbBasket>("SELECT * FROM baskets WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(basket)
+    }
+
+    pub async fn get_all_baskets(&self) -> Result<Vec<DbBasket>, sqlx::Error> {
+        let baskets = sqlx::query_as::<_, DbBasket>("SELECT * FROM baskets ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok(baskets)
+    }
+
+    pub async fn delete_basket(&self, id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM baskets WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    /// Append a template to the end of the basket; adding an existing member is a no-op
+    pub async fn add_template_to_basket(&self, basket_id: &str, template_id: &str) -> Result<(), sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        insert_basket_template(&mut conn, basket_id, template_id).await
+    }
+
+    pub async fn remove_template_from_basket(&self, basket_id: &str, template_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM basket_templates WHERE basket_id = ? AND template_id = ?")
+            .bind(basket_id)
             .bind(template_id)
             .execute(&self.pool)
             .await?;
@@ -152,79 +617,1997 @@ impl Database {
         Ok(())
     }
 
-    // Settings operations
-    pub async fn get_setting(&self, key: &str) -> Result<Option<String>, sqlx::Error> {
-        let result = sqlx::query_as::<_, (String,)>(
-            "SELECT value FROM settings WHERE key = ?"
+    pub async fn get_basket_template_ids(&self, basket_id: &str) -> Result<Vec<String>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (String,)>(
+            "SELECT template_id FROM basket_templates WHERE basket_id = ? ORDER BY position"
         )
-        .bind(key)
-        .fetch_optional(&self.pool)
+        .bind(basket_id)
+        .fetch_all(&self.pool)
         .await?;
         
-        Ok(result.map(|(value,)| value))
+        Ok(rows.into_iter().map(|(id,)| id).collect())
     }
 
-    pub async fn set_setting(&self, key: &str, value: &str) -> Result<(), sqlx::Error> {
+    // Trade outcome operations
+    pub async fn record_trade_outcome(&self, outcome: DbTradeOutcome) -> Result<(), sqlx::Error> {
         sqlx::query(
-            "INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)"
+            r#"
+            INSERT OR REPLACE INTO trade_outcomes (
+                template_id, symbol, model, realized_pnl, initial_risk, r_multiple, closed_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#
         )
-        .bind(key)
-        .bind(value)
+        .bind(&outcome.template_id)
+        .bind(&outcome.symbol)
+        .bind(&outcome.model)
+        .bind(outcome.realized_pnl)
+        .bind(outcome.initial_risk)
+        .bind(outcome.r_multiple)
+        .bind(&outcome.closed_at)
         .execute(&self.pool)
         .await?;
         
         Ok(())
     }
 
-    pub async fn get_risk_per_trade(&self) -> Result<f64, sqlx::Error> {
-        let value = self.get_setting("risk_per_trade").await?
-            .unwrap_or_else(|| "100.0".to_string());
+    pub async fn has_trade_outcome(&self, template_id: &str) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query("SELECT 1 FROM trade_outcomes WHERE template_id = ?")
+            .bind(template_id)
+            .fetch_optional(&self.pool)
+            .await?;
         
-        Ok(value.parse::<f64>().unwrap_or(100.0))
+        Ok(row.is_some())
     }
 
-    // Position operations
-    pub async fn sync_position(&self, position: DbPosition) -> Result<(), sqlx::Error> {
+    /// R-multiples of closed trades, oldest first, limited to the latest `window` when given.
+    /// `None` for `model` or `symbol` matches all.
+    pub async fn get_r_multiples(&self, model: Option<&str>, symbol: Option<&str>, window: Option<usize>) -> Result<Vec<f64>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (f64,)>(
+            r#"
+            SELECT r_multiple FROM (
+                SELECT r_multiple, closed_at FROM trade_outcomes
+                WHERE (?1 IS NULL OR model = ?1) AND (?2 IS NULL OR symbol = ?2)
+                ORDER BY closed_at DESC
+                LIMIT ?3
+            ) ORDER BY closed_at
+            "#
+        )
+        .bind(model)
+        .bind(symbol)
+        .bind(window.map_or(-1, |w| w as i64)) // SQLite treats a negative limit as none
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows.into_iter().map(|(r,)| r).collect())
+    }
+
+    /// Every closed trade, oldest first
+    pub async fn get_trade_outcomes(&self) -> Result<Vec<DbTradeOutcome>, sqlx::Error> {
+        sqlx::query_as::<_, DbTradeOutcome>("SELECT * FROM trade_outcomes ORDER BY closed_at")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn save_atr_cache(&self, entry: DbAtrCache) -> Result<(), sqlx::Error> {
         sqlx::query(
             r#"
-            INSERT OR REPLACE INTO positions (
-                ib_position_id, template_id, symbol, quantity, avg_cost, is_read_only, synced_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            INSERT OR REPLACE INTO atr_cache (
+                symbol, period_days, method, smoothing, applied_method, method_rationale, filtered_atr, regular_atr, total_bars, used_bars, excluded_bars,
+                mean_range, median_range, std_dev_range, q1_range, q3_range, lower_bound, upper_bound, confidence_score, is_valid, calculated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#
         )
-        .bind(&position.ib_position_id)
-        .bind(&position.template_id)
-        .bind(&position.symbol)
-        .bind(position.quantity)
-        .bind(position.avg_cost)
-        .bind(position.is_read_only)
-        .bind(&position.synced_at)
+        .bind(&entry.symbol)
+        .bind(entry.period_days)
+        .bind(&entry.method)
+        .bind(&entry.smoothing)
+        .bind(&entry.applied_method)
+        .bind(&entry.method_rationale)
+        .bind(entry.filtered_atr)
+        .bind(entry.regular_atr)
+        .bind(entry.total_bars)
+        .bind(entry.used_bars)
+        .bind(entry.excluded_bars)
+        .bind(entry.mean_range)
+        .bind(entry.median_range)
+        .bind(entry.std_dev_range)
+        .bind(entry.q1_range)
+        .bind(entry.q3_range)
+        .bind(entry.lower_bound)
+        .bind(entry.upper_bound)
+        .bind(entry.confidence_score)
+        .bind(entry.is_valid)
+        .bind(&entry.calculated_at)
         .execute(&self.pool)
         .await?;
         
         Ok(())
     }
 
-    pub async fn get_all_positions(&self) -> Result<Vec<DbPosition>, sqlx::Error> {
-        let positions = sqlx::query_as::<_, DbPosition>(
-            "SELECT * FROM positions ORDER BY synced_at DESC"
+    pub async fn get_atr_cache(&self, symbol: &str, period_days: usize, method: &str, smoothing: &str) -> Result<Option<DbAtrCache>, sqlx::Error> {
+        sqlx::query_as::<_, DbAtrCache>(
+            "SELECT * FROM atr_cache WHERE symbol = ? AND period_days = ? AND method = ? AND smoothing = ?"
         )
-        .fetch_all(&self.pool)
+        .bind(symbol)
+        .bind(period_days as i64)
+        .bind(method)
+        .bind(smoothing)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Keep a completed analysis; `analysis_json` is the serialized ATRResult
+    pub async fn record_atr_analysis(&self, symbol: &str, calculated_at: &str, analysis_json: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO atr_analyses (symbol, calculated_at, analysis) VALUES (?, ?, ?)")
+            .bind(symbol)
+            .bind(calculated_at)
+            .bind(analysis_json)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    /// The latest analysis of `symbol` calculated at or before `at` (RFC 3339)
+    pub async fn get_atr_analysis_at(&self, symbol: &str, at: &str) -> Result<Option<String>, sqlx::Error> {
+        let row = sqlx::query_as::<_, (String,)>(
+            r#"
+            SELECT analysis FROM atr_analyses
+            WHERE symbol = ? AND calculated_at <= ?
+            ORDER BY calculated_at DESC, id DESC
+            LIMIT 1
+            "#
+        )
+        .bind(symbol)
+        .bind(at)
+        .fetch_optional(&self.pool)
         .await?;
         
-        Ok(positions)
+        Ok(row.map(|(analysis,)| analysis))
     }
 
-    pub async fn clear_positions(&self) -> Result<(), sqlx::Error> {
-        sqlx::query("DELETE FROM positions")
+    // Sector operations
+    /// Create a sector or update its caps; symbol assignments are kept
+    pub async fn save_sector(&self, sector: DbSector) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO sectors (name, max_notional, max_risk, created_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT(name) DO UPDATE SET max_notional = excluded.max_notional, max_risk = excluded.max_risk
+            "#
+        )
+        .bind(&sector.name)
+        .bind(sector.max_notional)
+        .bind(sector.max_risk)
+        .bind(&sector.created_at)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+
+    pub async fn save_checklist_item(&self, item: DbChecklistItem) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO checklist_items (id, model, text, position, created_at) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET model = excluded.model, text = excluded.text, position = excluded.position
+            "#
+        )
+        .bind(&item.id)
+        .bind(&item.model)
+        .bind(&item.text)
+        .bind(item.position)
+        .bind(&item.created_at)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+
+    pub async fn delete_checklist_item(&self, id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM checklist_items WHERE id = ?")
+            .bind(id)
             .execute(&self.pool)
             .await?;
         
         Ok(())
     }
 
-    // Transaction support
-    pub async fn begin_transaction(&self) -> Result<sqlx::Transaction<'_, sqlx::Sqlite>, sqlx::Error> {
-        self.pool.begin().await
+    /// Items asked of `model` (shared ones included), or every item with None
+    pub async fn get_checklist_items(&self, model: Option<&str>) -> Result<Vec<DbChecklistItem>, sqlx::Error> {
+        let items = match model {
+            Some(model) => sqlx::query_as::<_, DbChecklistItem>(
+                "SELECT * FROM checklist_items WHERE model IS NULL OR model = ? ORDER BY position, created_at"
+            )
+            .bind(model)
+            .fetch_all(&self.pool)
+            .await?,
+            None => sqlx::query_as::<_, DbChecklistItem>("SELECT * FROM checklist_items ORDER BY position, created_at")
+                .fetch_all(&self.pool)
+                .await?,
+        };
+        
+        Ok(items)
+    }
+
+    pub async fn get_all_sectors(&self) -> Result<Vec<DbSector>, sqlx::Error> {
+        let sectors = sqlx::query_as::<_, DbSector>("SELECT * FROM sectors ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok(sectors)
+    }
+
+    /// Deleting a sector also drops its symbol assignments
+    pub async fn delete_sector(&self, name: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM sectors WHERE name = ?")
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    /// Put a symbol in a sector, moving it out of any previous one
+    pub async fn set_symbol_sector(&self, symbol: &str, sector: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR REPLACE INTO symbol_sectors (symbol, sector) VALUES (?, ?)")
+            .bind(symbol)
+            .bind(sector)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
     }
-}
\ No newline at end of file
+
+    pub async fn clear_symbol_sector(&self, symbol: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM symbol_sectors WHERE symbol = ?")
+            .bind(symbol)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    pub async fn get_sector_symbols(&self, sector: &str) -> Result<Vec<String>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (String,)>(
+            "SELECT symbol FROM symbol_sectors WHERE sector = ? ORDER BY symbol"
+        )
+        .bind(sector)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows.into_iter().map(|(symbol,)| symbol).collect())
+    }
+
+    // Per-symbol settings operations
+    pub async fn get_symbol_settings(&self, symbol: &str) -> Result<Option<DbSymbolSettings>, sqlx::Error> {
+        let settings = sqlx::query_as::<_, DbSymbolSettings>(
+            r#"
+            SELECT s.symbol, s.risk_per_trade, s.atr_period, s.tick_size, ss.sector, s.notes, s.updated_at
+            FROM symbol_settings s
+            LEFT JOIN symbol_sectors ss ON ss.symbol = s.symbol
+            WHERE s.symbol = ?
+            "#
+        )
+        .bind(symbol)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        Ok(settings)
+    }
+
+    pub async fn get_all_symbol_settings(&self) -> Result<Vec<DbSymbolSettings>, sqlx::Error> {
+        let settings = sqlx::query_as::<_, DbSymbolSettings>(
+            r#"
+            SELECT s.symbol, s.risk_per_trade, s.atr_period, s.tick_size, ss.sector, s.notes, s.updated_at
+            FROM symbol_settings s
+            LEFT JOIN symbol_sectors ss ON ss.symbol = s.symbol
+            ORDER BY s.symbol
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(settings)
+    }
+
+    /// Insert or replace a symbol's settings. The sector tag is kept in `symbol_sectors`, so
+    /// setting it moves the symbol between sectors and clearing it takes the symbol out of one.
+    pub async fn save_symbol_settings(&self, settings: DbSymbolSettings) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO symbol_settings (symbol, risk_per_trade, atr_period, tick_size, notes, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(symbol) DO UPDATE SET
+                risk_per_trade = excluded.risk_per_trade, atr_period = excluded.atr_period,
+                tick_size = excluded.tick_size, notes = excluded.notes, updated_at = excluded.updated_at
+            "#
+        )
+        .bind(&settings.symbol)
+        .bind(settings.risk_per_trade)
+        .bind(settings.atr_period)
+        .bind(settings.tick_size)
+        .bind(&settings.notes)
+        .bind(&settings.updated_at)
+        .execute(&mut *tx)
+        .await?;
+
+        match &settings.sector {
+            Some(sector) => sqlx::query("INSERT OR REPLACE INTO symbol_sectors (symbol, sector) VALUES (?, ?)")
+                .bind(&settings.symbol)
+                .bind(sector)
+                .execute(&mut *tx)
+                .await?,
+            None => sqlx::query("DELETE FROM symbol_sectors WHERE symbol = ?")
+                .bind(&settings.symbol)
+                .execute(&mut *tx)
+                .await?,
+        };
+        tx.commit().await?;
+        
+        Ok(())
+    }
+
+    /// Drop a symbol's settings, returning whether it had any. Its sector assignment stays,
+    /// as that is also managed from the sector side.
+    pub async fn delete_symbol_settings(&self, symbol: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM symbol_settings WHERE symbol = ?")
+            .bind(symbol)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Earnings calendar operations
+    pub async fn save_earnings_event(&self, event: DbEarningsEvent) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR REPLACE INTO earnings_dates (symbol, date, timing) VALUES (?, ?, ?)")
+            .bind(&event.symbol)
+            .bind(&event.date)
+            .bind(&event.timing)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    pub async fn delete_earnings_event(&self, symbol: &str, date: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM earnings_dates WHERE symbol = ? AND date = ?")
+            .bind(symbol)
+            .bind(date)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    /// Events for `symbol`, or for every symbol with None, by date
+    pub async fn get_earnings_events(&self, symbol: Option<&str>) -> Result<Vec<DbEarningsEvent>, sqlx::Error> {
+        let events = match symbol {
+            Some(symbol) => sqlx::query_as::<_, DbEarningsEvent>(
+                "SELECT * FROM earnings_dates WHERE symbol = ? ORDER BY date"
+            )
+            .bind(symbol)
+            .fetch_all(&self.pool)
+            .await?,
+            None => sqlx::query_as::<_, DbEarningsEvent>("SELECT * FROM earnings_dates ORDER BY date, symbol")
+                .fetch_all(&self.pool)
+                .await?,
+        };
+        
+        Ok(events)
+    }
+
+    // Execution operations
+    /// Store fills, replacing any already recorded under the same id (IB corrections). A
+    /// commission already recorded is kept when the new fill has none.
+    pub async fn save_executions(&self, executions: &[DbExecution]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for execution in executions {
+            sqlx::query(
+                r#"
+                INSERT INTO executions (
+                    exec_id, order_id, template_id, symbol, side, quantity, price, executed_at, commission
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(exec_id) DO UPDATE SET
+                    order_id = excluded.order_id,
+                    template_id = excluded.template_id,
+                    symbol = excluded.symbol,
+                    side = excluded.side,
+                    quantity = excluded.quantity,
+                    price = excluded.price,
+                    executed_at = excluded.executed_at,
+                    commission = COALESCE(excluded.commission, executions.commission)
+                "#
+            )
+            .bind(&execution.exec_id)
+            .bind(execution.order_id)
+            .bind(&execution.template_id)
+            .bind(&execution.symbol)
+            .bind(&execution.side)
+            .bind(execution.quantity)
+            .bind(execution.price)
+            .bind(&execution.executed_at)
+            .bind(execution.commission)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        
+        Ok(())
+    }
+
+    /// Fills for a symbol, oldest first
+    pub async fn get_executions(&self, symbol: &str) -> Result<Vec<DbExecution>, sqlx::Error> {
+        let executions = sqlx::query_as::<_, DbExecution>(
+            "SELECT * FROM executions WHERE symbol = ? ORDER BY executed_at"
+        )
+        .bind(symbol)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(executions)
+    }
+
+    /// Every symbol with recorded fills
+    pub async fn get_execution_symbols(&self) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT DISTINCT symbol FROM executions ORDER BY symbol")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Fills of the orders a template placed, oldest first
+    pub async fn get_template_executions(&self, template_id: &str) -> Result<Vec<DbExecution>, sqlx::Error> {
+        let executions = sqlx::query_as::<_, DbExecution>(
+            "SELECT * FROM executions WHERE template_id = ? ORDER BY executed_at"
+        )
+        .bind(template_id)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(executions)
+    }
+
+    /// Fills from `from` up to but not including `to`, oldest first
+    pub async fn get_executions_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<DbExecution>, sqlx::Error> {
+        let executions = sqlx::query_as::<_, DbExecution>(
+            "SELECT * FROM executions WHERE executed_at >= ? AND executed_at < ? ORDER BY executed_at"
+        )
+        .bind(from.to_rfc3339())
+        .bind(to.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(executions)
+    }
+
+    // Trade operations
+    /// Replace a symbol's trades with ones rebuilt from its fills
+    pub async fn replace_trades(&self, symbol: &str, trades: &[DbTrade]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM trades WHERE symbol = ?")
+            .bind(symbol)
+            .execute(&mut *tx)
+            .await?;
+        for trade in trades {
+            sqlx::query(
+                r#"
+                INSERT INTO trades (
+                    id, symbol, side, template_id, model, quantity, opened_at, closed_at,
+                    avg_entry, avg_exit, realized_pnl, commission, r_multiple
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(&trade.id)
+            .bind(&trade.symbol)
+            .bind(&trade.side)
+            .bind(&trade.template_id)
+            .bind(&trade.model)
+            .bind(trade.quantity)
+            .bind(&trade.opened_at)
+            .bind(&trade.closed_at)
+            .bind(trade.avg_entry)
+            .bind(trade.avg_exit)
+            .bind(trade.realized_pnl)
+            .bind(trade.commission)
+            .bind(trade.r_multiple)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        
+        Ok(())
+    }
+
+    /// Trades opened from `from` up to but not including `to`, oldest first. Each filter is
+    /// skipped when None.
+    pub async fn get_trades(
+        &self,
+        symbol: Option<&str>,
+        model: Option<TradingModel>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<DbTrade>, sqlx::Error> {
+        let trades = sqlx::query_as::<_, DbTrade>(
+            r#"
+            SELECT * FROM trades
+            WHERE (?1 IS NULL OR symbol = ?1)
+                AND (?2 IS NULL OR model = ?2)
+                AND (?3 IS NULL OR opened_at >= ?3)
+                AND (?4 IS NULL OR opened_at < ?4)
+            ORDER BY opened_at, id
+            "#
+        )
+        .bind(symbol)
+        .bind(model.map(model_to_str))
+        .bind(from.map(|t| t.to_rfc3339()))
+        .bind(to.map(|t| t.to_rfc3339()))
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(trades)
+    }
+
+    // Historical bar cache operations
+    /// The span (from, to) cached bars cover, in Unix seconds
+    pub async fn get_bar_coverage(&self, symbol: &str, bar_size: &str, use_rth: bool) -> Result<Option<(i64, i64)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT covered_from, covered_to FROM bar_coverage WHERE symbol = ? AND bar_size = ? AND use_rth = ?"
+        )
+        .bind(symbol)
+        .bind(bar_size)
+        .bind(use_rth)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Store fetched bars, replacing cached ones at the same times, and the span now covered
+    pub async fn save_bars(
+        &self,
+        symbol: &str,
+        bar_size: &str,
+        use_rth: bool,
+        bars: &[DbBar],
+        covered: (i64, i64),
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for bar in bars {
+            sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO bars (
+                    symbol, bar_size, use_rth, timestamp, open, high, low, close, volume, wap, count
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(symbol)
+            .bind(bar_size)
+            .bind(use_rth)
+            .bind(bar.timestamp)
+            .bind(bar.open)
+            .bind(bar.high)
+            .bind(bar.low)
+            .bind(bar.close)
+            .bind(bar.volume)
+            .bind(bar.wap)
+            .bind(bar.count)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO bar_coverage (symbol, bar_size, use_rth, covered_from, covered_to)
+            VALUES (?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(symbol)
+        .bind(bar_size)
+        .bind(use_rth)
+        .bind(covered.0)
+        .bind(covered.1)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        
+        Ok(())
+    }
+
+    /// Cached bars from `from` (Unix seconds) on, oldest first
+    pub async fn get_bars(&self, symbol: &str, bar_size: &str, use_rth: bool, from: i64) -> Result<Vec<DbBar>, sqlx::Error> {
+        let bars = sqlx::query_as::<_, DbBar>(
+            r#"
+            SELECT timestamp, open, high, low, close, volume, wap, count FROM bars
+            WHERE symbol = ? AND bar_size = ? AND use_rth = ? AND timestamp >= ?
+            ORDER BY timestamp
+            "#
+        )
+        .bind(symbol)
+        .bind(bar_size)
+        .bind(use_rth)
+        .bind(from)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(bars)
+    }
+
+    // Chart drawing operations
+    /// Add a drawing, or replace the shape and color of one already stored
+    pub async fn save_drawing(&self, drawing: &DbDrawing) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO chart_drawings (id, symbol, shape, color, created_at) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET shape = excluded.shape, color = excluded.color
+            "#
+        )
+        .bind(&drawing.id)
+        .bind(&drawing.symbol)
+        .bind(&drawing.shape)
+        .bind(&drawing.color)
+        .bind(&drawing.created_at)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+
+    pub async fn get_drawing(&self, id: &str) -> Result<Option<DbDrawing>, sqlx::Error> {
+        let drawing = sqlx::query_as::<_, DbDrawing>("SELECT * FROM chart_drawings WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(drawing)
+    }
+
+    /// Drawings on a symbol's chart, oldest first so newer ones draw on top
+    pub async fn get_drawings(&self, symbol: &str) -> Result<Vec<DbDrawing>, sqlx::Error> {
+        let drawings = sqlx::query_as::<_, DbDrawing>(
+            "SELECT * FROM chart_drawings WHERE symbol = ? ORDER BY created_at"
+        )
+        .bind(symbol)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(drawings)
+    }
+
+    pub async fn delete_drawing(&self, id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM chart_drawings WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    // Chart theme operations
+    /// Add a theme, or replace the one saved under its name
+    pub async fn save_theme(&self, theme: &DbTheme) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR REPLACE INTO themes (name, theme, updated_at) VALUES (?, ?, ?)")
+            .bind(&theme.name)
+            .bind(&theme.theme)
+            .bind(&theme.updated_at)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    pub async fn get_theme(&self, name: &str) -> Result<Option<DbTheme>, sqlx::Error> {
+        let theme = sqlx::query_as::<_, DbTheme>("SELECT * FROM themes WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(theme)
+    }
+
+    /// Saved themes by name
+    pub async fn get_themes(&self) -> Result<Vec<DbTheme>, sqlx::Error> {
+        let themes = sqlx::query_as::<_, DbTheme>("SELECT * FROM themes ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok(themes)
+    }
+
+    // Watchlist operations
+    pub async fn create_watchlist(&self, watchlist: DbWatchlist) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO watchlists (id, name, position, created_at) VALUES (?, ?, ?, ?)")
+            .bind(&watchlist.id)
+            .bind(&watchlist.name)
+            .bind(watchlist.position)
+            .bind(&watchlist.created_at)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    /// False when there is no such watchlist
+    pub async fn rename_watchlist(&self, id: &str, name: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE watchlists SET name = ? WHERE id = ?")
+            .bind(name)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Delete a watchlist and its symbols; false when there is no such watchlist
+    pub async fn delete_watchlist(&self, id: &str) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM watchlist_symbols WHERE watchlist_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM watchlists WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_watchlists(&self) -> Result<Vec<DbWatchlist>, sqlx::Error> {
+        let watchlists = sqlx::query_as::<_, DbWatchlist>("SELECT * FROM watchlists ORDER BY position, name")
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok(watchlists)
+    }
+
+    /// Add a symbol at the end of a watchlist, or replace its note when already there
+    pub async fn save_watchlist_entry(&self, watchlist_id: &str, entry: DbWatchlistEntry) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO watchlist_symbols (watchlist_id, symbol, position, note, added_at)
+            VALUES (?1, ?2, (SELECT COALESCE(MAX(position) + 1, 0) FROM watchlist_symbols WHERE watchlist_id = ?1), ?3, ?4)
+            ON CONFLICT(watchlist_id, symbol) DO UPDATE SET note = COALESCE(excluded.note, watchlist_symbols.note)
+            "#
+        )
+        .bind(watchlist_id)
+        .bind(&entry.symbol)
+        .bind(&entry.note)
+        .bind(&entry.added_at)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+
+    pub async fn delete_watchlist_entry(&self, watchlist_id: &str, symbol: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM watchlist_symbols WHERE watchlist_id = ? AND symbol = ?")
+            .bind(watchlist_id)
+            .bind(symbol)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    /// A watchlist's symbols in their order
+    pub async fn get_watchlist(&self, watchlist_id: &str) -> Result<Vec<DbWatchlistEntry>, sqlx::Error> {
+        let entries = sqlx::query_as::<_, DbWatchlistEntry>(
+            "SELECT symbol, note, added_at FROM watchlist_symbols WHERE watchlist_id = ? ORDER BY position"
+        )
+        .bind(watchlist_id)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(entries)
+    }
+
+    /// Put a watchlist's symbols in the order given
+    pub async fn reorder_watchlist(&self, watchlist_id: &str, symbols: &[String]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (position, symbol) in symbols.iter().enumerate() {
+            sqlx::query("UPDATE watchlist_symbols SET position = ? WHERE watchlist_id = ? AND symbol = ?")
+                .bind(position as i64)
+                .bind(watchlist_id)
+                .bind(symbol)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        
+        Ok(())
+    }
+
+    /// Every symbol on any watchlist
+    pub async fn get_watched_symbols(&self) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT DISTINCT symbol FROM watchlist_symbols ORDER BY symbol")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    // Active order operations
+    pub async fn create_active_order(&self, active_order: DbActiveOrder) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO active_orders (template_id, ib_order_id, ib_stop_order_id, ib_tranche_order_ids, submitted_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&active_order.template_id)
+        .bind(active_order.ib_order_id)
+        .bind(active_order.ib_stop_order_id)
+        .bind(&active_order.ib_tranche_order_ids)
+        .bind(&active_order.submitted_at)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+
+    pub async fn get_active_order_by_ib_id(&self, ib_order_id: i64) -> Result<Option<DbActiveOrder>, sqlx::Error> {
+        let order = sqlx::query_as::<_, DbActiveOrder>(
+            "SELECT * FROM active_orders WHERE ib_order_id = ?"
+        )
+        .bind(ib_order_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        Ok(order)
+    }
+
+    pub async fn get_all_active_orders(&self) -> Result<Vec<DbActiveOrder>, sqlx::Error> {
+        let orders = sqlx::query_as::<_, DbActiveOrder>(
+            "SELECT * FROM active_orders ORDER BY submitted_at"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(orders)
+    }
+
+    pub async fn delete_active_order(&self, template_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM active_orders WHERE template_id = ?")
+            .bind(template_id)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    // Settings operations
+    pub async fn get_setting(&self, key: &str) -> Result<Option<String>, sqlx::Error> {
+        let result = sqlx::query_as::<_, (String,)>(
+            "SELECT value FROM settings WHERE key = ?"
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        Ok(result.map(|(value,)| value))
+    }
+
+    pub async fn set_setting(&self, key: &str, value: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)"
+        )
+        .bind(key)
+        .bind(value)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+
+    /// Every typed setting; see `Settings::from_values` for what a bad value turns into
+    pub async fn get_settings(&self) -> Result<Settings, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (String, String)>("SELECT key, value FROM settings")
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok(Settings::from_values(&rows.into_iter().collect()))
+    }
+
+    /// Write every typed setting at once, so a failure leaves the old ones in place
+    pub async fn save_settings(&self, settings: &Settings) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (key, value) in settings.to_values() {
+            sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)")
+                .bind(key)
+                .bind(value)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    /// A setting with a per-model override: `<key>:<model>` wins over `<key>`
+    async fn get_model_setting(&self, key: &str, model: &str) -> Result<Option<String>, sqlx::Error> {
+        match self.get_setting(&format!("{}:{}", key, model)).await? {
+            Some(value) => Ok(Some(value)),
+            None => self.get_setting(key).await,
+        }
+    }
+
+    /// Stop thresholds for `model`, e.g. `stop_loss_atr_percentage:Bounce` overrides `stop_loss_atr_percentage`
+    pub async fn get_stop_rules(&self, model: &str) -> Result<StopRules, sqlx::Error> {
+        let defaults = StopRules::default();
+        let value = |setting: Option<String>, default: f64| setting
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(default);
+        
+        Ok(StopRules {
+            min_distance: value(self.get_model_setting("min_stop_distance", model).await?, defaults.min_distance),
+            default_atr_fraction: value(self.get_model_setting("stop_loss_atr_percentage", model).await?, defaults.default_atr_fraction),
+            max_atr_fraction: value(self.get_model_setting("max_technical_stop_atr_percentage", model).await?, defaults.max_atr_fraction),
+        })
+    }
+
+    /// Defaults for new `model` templates; `risk_per_trade`, `stop_loss_atr_percentage`
+    /// and `default_target_r` each take a `:<Model>` override
+    pub async fn get_model_defaults(&self, model: &str) -> Result<ModelDefaults, sqlx::Error> {
+        let defaults = ModelDefaults::default();
+        let value = |setting: Option<String>, default: f64| setting
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(default);
+        
+        Ok(ModelDefaults {
+            risk_per_trade: value(self.get_model_setting("risk_per_trade", model).await?, defaults.risk_per_trade),
+            stop_atr_fraction: value(self.get_model_setting("stop_loss_atr_percentage", model).await?, defaults.stop_atr_fraction),
+            target_r: value(self.get_model_setting("default_target_r", model).await?, defaults.target_r),
+        })
+    }
+
+    pub async fn set_model_defaults(&self, model: &str, defaults: &ModelDefaults) -> Result<(), sqlx::Error> {
+        self.set_setting(&format!("risk_per_trade:{}", model), &defaults.risk_per_trade.to_string()).await?;
+        self.set_setting(&format!("stop_loss_atr_percentage:{}", model), &defaults.stop_atr_fraction.to_string()).await?;
+        self.set_setting(&format!("default_target_r:{}", model), &defaults.target_r.to_string()).await
+    }
+
+    /// JSON-encoded lock from the last daily loss limit breach
+    pub async fn get_trading_lock(&self) -> Result<Option<String>, sqlx::Error> {
+        self.get_setting("trading_lock").await
+    }
+
+    pub async fn set_trading_lock(&self, lock_json: &str) -> Result<(), sqlx::Error> {
+        self.set_setting("trading_lock", lock_json).await
+    }
+
+    /// JSON-encoded equity high-water mark for one account (Paper or Live)
+    pub async fn get_drawdown(&self, account: &str) -> Result<Option<String>, sqlx::Error> {
+        self.get_setting(&format!("equity_high_water_mark:{}", account)).await
+    }
+
+    pub async fn set_drawdown(&self, account: &str, drawdown_json: &str) -> Result<(), sqlx::Error> {
+        self.set_setting(&format!("equity_high_water_mark:{}", account), drawdown_json).await
+    }
+
+    // Position operations
+    pub async fn sync_position(&self, position: DbPosition) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO positions (
+                ib_position_id, template_id, symbol, quantity, avg_cost, is_read_only, synced_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&position.ib_position_id)
+        .bind(&position.template_id)
+        .bind(&position.symbol)
+        .bind(position.quantity)
+        .bind(position.avg_cost)
+        .bind(position.is_read_only)
+        .bind(&position.synced_at)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+
+    pub async fn get_all_positions(&self) -> Result<Vec<DbPosition>, sqlx::Error> {
+        let positions = sqlx::query_as::<_, DbPosition>(
+            "SELECT * FROM positions ORDER BY synced_at DESC"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(positions)
+    }
+
+    pub async fn clear_positions(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM positions")
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+
+    // Order id high-water mark
+    /// Atomically reserve `count` order ids starting at or above `floor`, returning the first.
+    /// The stored mark only moves forward, so ids stay unique across restarts.
+    pub async fn reserve_order_ids(&self, floor: i64, count: i64) -> Result<i64, sqlx::Error> {
+        let (next,) = sqlx::query_as::<_, (i64,)>(
+            r#"
+            INSERT INTO settings (key, value) VALUES ('next_order_id', ?1 + ?2)
+            ON CONFLICT(key) DO UPDATE SET
+                value = MAX(CAST(value AS INTEGER), ?1) + ?2,
+                updated_at = datetime('now')
+            RETURNING CAST(value AS INTEGER)
+            "#
+        )
+        .bind(floor)
+        .bind(count)
+        .fetch_one(&self.pool)
+        .await?;
+        
+        Ok(next - count)
+    }
+
+    // Search
+    /// Templates, trades and watchlist entries matching every word of `query`, best first
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, sqlx::Error> {
+        let Some(query) = search::fts_query(query) else {
+            return Ok(Vec::new());
+        };
+        let rows: Vec<(String, String, String, String, String)> = sqlx::query_as(
+            r#"
+            SELECT kind, ref_id, symbol, title, snippet(search_index, -1, '[', ']', '...', 12)
+            FROM search_index WHERE search_index MATCH ? ORDER BY rank LIMIT ?
+            "#
+        )
+        .bind(query)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows.into_iter().filter_map(|(kind, id, symbol, title, snippet)| search::hit(&kind, id, symbol, title, snippet)).collect())
+    }
+
+    // Transaction support
+    pub async fn begin_transaction(&self) -> Result<sqlx::Transaction<'_, sqlx::Sqlite>, sqlx::Error> {
+        self.pool.begin().await
+    }
+}
+
+/// An update that matched no row is the template having gone missing
+async fn insert_basket_template(conn: &mut SqliteConnection, basket_id: &str, template_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO basket_templates (basket_id, template_id, position)
+        VALUES (
+            ?,
+            ?,
+            (SELECT COALESCE(MAX(position), 0) + 1 FROM basket_templates WHERE basket_id = ?)
+        )
+        "#
+    )
+    .bind(basket_id)
+    .bind(template_id)
+    .bind(basket_id)
+    .execute(conn)
+    .await?;
+    
+    Ok(())
+}
+
+fn found(rows_affected: u64) -> Result<(), sqlx::Error> {
+    if rows_affected == 0 {
+        Err(sqlx::Error::RowNotFound)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::charts::ChartTheme;
+    use crate::charts::types::{Drawing, DrawingAnchor, DrawingShape};
+    use crate::ib::analytics::AtrCacheTtl;
+    use crate::ib::earnings::{EarningsEvent, EarningsPolicy, EarningsTiming};
+    use crate::ib::orders::calculations::CommissionModel;
+    use crate::ib::journal::Trade;
+    use crate::ib::types::{ATRResult, AtrSmoothing, ChecklistItem, Fill, OrderSide, OutlierMethod, Sector, SymbolSettings, TradingModel, WatchlistEntry, DEFAULT_WATCHLIST_ID};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_connection_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.db");
+        let db = Database::open(&format!("sqlite:{}", path.display())).await.unwrap();
+        let pool = db.lock().await.pool.clone();
+
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode").fetch_one(&pool).await.unwrap();
+        assert_eq!(journal_mode, "wal");
+        let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys").fetch_one(&pool).await.unwrap();
+        assert_eq!(foreign_keys, 1);
+        let busy_timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout").fetch_one(&pool).await.unwrap();
+        assert_eq!(busy_timeout, BUSY_TIMEOUT.as_millis() as i64);
+
+        // Writers on separate connections wait their turn rather than failing
+        let writers: Vec<_> = (0..MAX_CONNECTIONS)
+            .map(|i| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    let mut tx = pool.begin().await?;
+                    sqlx::query("INSERT INTO settings (key, value) VALUES (?, 'x')").bind(format!("concurrent_{}", i)).execute(&mut *tx).await?;
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    tx.commit().await
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap().unwrap();
+        }
+
+        pool.close().await;
+        assert!(!backup::is_corrupt(&path).await);
+    }
+
+    #[tokio::test]
+    async fn test_template_persistence() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let mut template = DbOrderTemplate::new(
+            "Persisted".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            100,
+            150.0,
+            145.0,
+            TradingModel::Breakout,
+        );
+        db.save_template(template.clone()).await.unwrap();
+
+        // Saving again with the same id updates in place
+        template.limit_price = 151.0;
+        template.notes = Some("raised entry".to_string());
+        db.save_template(template.clone()).await.unwrap();
+
+        let all = db.get_all_templates().await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].limit_price, 151.0);
+        assert_eq!(all[0].notes.as_deref(), Some("raised entry"));
+
+        db.delete_template(&template.id).await.unwrap();
+        assert!(db.get_template(&template.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_template_soft_delete_and_purge() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let kept = DbOrderTemplate::new("Kept".to_string(), "AAPL".to_string(), OrderSide::Long, 100, 150.0, 145.0, TradingModel::Breakout);
+        let mut archived = DbOrderTemplate::new("Archived".to_string(), "MSFT".to_string(), OrderSide::Long, 10, 400.0, 390.0, TradingModel::Bounce);
+        archived.archived_at = Some(Utc::now().to_rfc3339());
+        let deleted = DbOrderTemplate::new("Deleted".to_string(), "NVDA".to_string(), OrderSide::Short, 50, 120.0, 125.0, TradingModel::FalseBreakout);
+        for template in [&kept, &archived, &deleted] {
+            db.create_template(template.clone()).await.unwrap();
+        }
+        db.record_template_revision(&deleted.id, "[]").await.unwrap();
+        db.delete_template(&deleted.id).await.unwrap();
+
+        let ids = |templates: Vec<DbOrderTemplate>| templates.into_iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids(db.get_all_templates().await.unwrap()), [kept.id.as_str()]);
+        assert_eq!(ids(db.get_archived_templates().await.unwrap()), [archived.id.as_str()]);
+        assert!(db.get_template(&deleted.id).await.unwrap().is_none());
+        // History stays until the purge
+        assert_eq!(db.get_template_revisions(&deleted.id).await.unwrap().len(), 1);
+
+        assert_eq!(db.purge_deleted_templates(Utc::now() - chrono::Duration::days(30)).await.unwrap(), 0);
+        assert_eq!(db.purge_deleted_templates(Utc::now() + chrono::Duration::seconds(1)).await.unwrap(), 1);
+        assert!(db.get_template_revisions(&deleted.id).await.unwrap().is_empty());
+        assert_eq!(db.get_all_templates().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_audit_pages() {
+        use crate::ib::types::{AuditAction, AuditSource, OrderTemplate};
+
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let aapl = DbOrderTemplate::new("AAPL".to_string(), "AAPL".to_string(), OrderSide::Long, 100, 150.0, 145.0, TradingModel::Breakout)
+            .to_order_template(None).unwrap();
+        let msft = DbOrderTemplate::new("MSFT".to_string(), "MSFT".to_string(), OrderSide::Long, 10, 400.0, 390.0, TradingModel::Bounce)
+            .to_order_template(None).unwrap();
+        let record = |template: &OrderTemplate, action, source, day: u32| {
+            let mut entry = DbAuditEntry::new(&template.id, action, source, None, Some(template), Some(template)).unwrap();
+            entry.recorded_at = chrono::TimeZone::with_ymd_and_hms(&Utc, 2025, 7, day, 14, 0, 0).unwrap().to_rfc3339();
+            entry
+        };
+        for entry in [
+            record(&aapl, AuditAction::Create, AuditSource::UI, 1),
+            record(&msft, AuditAction::Create, AuditSource::UI, 2),
+            record(&aapl, AuditAction::Activate, AuditSource::Scheduler, 3),
+            record(&aapl, AuditAction::Fill, AuditSource::Reconciliation, 4),
+        ] {
+            db.record_audit(&entry).await.unwrap();
+        }
+        // Deleting a template keeps its audit trail
+        db.record_audit(&DbAuditEntry::new(&msft.id, AuditAction::Delete, AuditSource::UI, None, Some(&msft), None).unwrap()).await.unwrap();
+
+        let actions = |entries: Vec<DbAuditEntry>| entries.into_iter().map(|e| e.action).collect::<Vec<_>>();
+        let (page, total) = db.get_audit_page(&AuditFilter::default(), 0, 2).await.unwrap();
+        assert_eq!((actions(page), total), (vec!["Delete".to_string(), "Fill".to_string()], 5));
+        let (page, _) = db.get_audit_page(&AuditFilter::default(), 4, 2).await.unwrap();
+        assert_eq!(actions(page), ["Create"]);
+
+        let by_symbol = AuditFilter { symbol: Some("msft".to_string()), ..Default::default() };
+        assert_eq!(db.get_audit_page(&by_symbol, 0, 10).await.unwrap().1, 2);
+        let by_template = AuditFilter { template_id: Some(aapl.id.clone()), source: Some(AuditSource::UI), ..Default::default() };
+        assert_eq!(actions(db.get_audit_page(&by_template, 0, 10).await.unwrap().0), ["Create"]);
+        let by_action = AuditFilter { action: Some(AuditAction::Create), ..Default::default() };
+        assert_eq!(db.get_audit_page(&by_action, 0, 10).await.unwrap().1, 2);
+        let in_range = AuditFilter {
+            from: Some(chrono::TimeZone::with_ymd_and_hms(&Utc, 2025, 7, 2, 0, 0, 0).unwrap()),
+            to: Some(chrono::TimeZone::with_ymd_and_hms(&Utc, 2025, 7, 4, 0, 0, 0).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(actions(db.get_audit_page(&in_range, 0, 10).await.unwrap().0), ["Activate", "Create"]);
+    }
+
+    #[tokio::test]
+    async fn test_template_update() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let mut template = DbOrderTemplate::new(
+            "Planned".to_string(), "AAPL".to_string(), OrderSide::Long, 100, 150.0, 145.0, TradingModel::Breakout,
+        );
+        db.create_template(template.clone()).await.unwrap();
+
+        template.name = "Replanned".to_string();
+        template.quantity = 80;
+        template.stop_price = 144.0;
+        template.risk_per_trade = Some(480.0);
+        db.update_template(template.clone()).await.unwrap();
+        let stored = db.get_template(&template.id).await.unwrap().unwrap();
+        assert_eq!((stored.name.as_str(), stored.quantity, stored.stop_price, stored.risk_per_trade), ("Replanned", 80, 144.0, Some(480.0)));
+
+        db.update_template_prices(&template.id, 151.0, 146.0).await.unwrap();
+        db.update_template_quantity(&template.id, 60).await.unwrap();
+        db.update_template_notes(&template.id, Some("after earnings")).await.unwrap();
+        let stored = db.get_template(&template.id).await.unwrap().unwrap();
+        assert_eq!((stored.limit_price, stored.stop_price, stored.quantity), (151.0, 146.0, 60));
+        assert_eq!(stored.notes.as_deref(), Some("after earnings"));
+        assert_eq!(stored.name, "Replanned");
+        db.update_template_notes(&template.id, None).await.unwrap();
+        assert_eq!(db.get_template(&template.id).await.unwrap().unwrap().notes, None);
+
+        // Updates never bring back a deleted template
+        db.delete_template(&template.id).await.unwrap();
+        assert!(matches!(db.update_template(template.clone()).await, Err(sqlx::Error::RowNotFound)));
+        assert!(matches!(db.update_template_notes(&template.id, None).await, Err(sqlx::Error::RowNotFound)));
+        assert!(db.get_template(&template.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_template_revisions_are_numbered() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let first = DbOrderTemplate::new(
+            "First".to_string(), "AAPL".to_string(), OrderSide::Long, 100, 150.0, 145.0, TradingModel::Breakout,
+        );
+        let second = DbOrderTemplate::new(
+            "Second".to_string(), "MSFT".to_string(), OrderSide::Short, 10, 410.0, 415.0, TradingModel::Bounce,
+        );
+        db.save_template(first.clone()).await.unwrap();
+        db.save_template(second.clone()).await.unwrap();
+
+        assert_eq!(db.record_template_revision(&first.id, "[]").await.unwrap(), 1);
+        assert_eq!(db.record_template_revision(&first.id, "[]").await.unwrap(), 2);
+        assert_eq!(db.record_template_revision(&second.id, "[]").await.unwrap(), 1);
+
+        let revisions = db.get_template_revisions(&first.id).await.unwrap();
+        assert_eq!(revisions.iter().map(|r| r.revision).collect::<Vec<_>>(), vec![1, 2]);
+
+        // History goes away when the deleted template is purged
+        db.delete_template(&first.id).await.unwrap();
+        assert_eq!(db.get_template_revisions(&first.id).await.unwrap().len(), 2);
+        db.purge_deleted_templates(Utc::now() + chrono::Duration::seconds(1)).await.unwrap();
+        assert!(db.get_template_revisions(&first.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reserve_order_ids() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        assert_eq!(db.reserve_order_ids(1000, 2).await.unwrap(), 1000);
+        assert_eq!(db.reserve_order_ids(1000, 3).await.unwrap(), 1002);
+
+        // A higher floor (e.g. IB's next valid id) skips ahead; a lower one never goes back
+        assert_eq!(db.reserve_order_ids(5000, 2).await.unwrap(), 5000);
+        assert_eq!(db.reserve_order_ids(1000, 2).await.unwrap(), 5002);
+    }
+
+    #[tokio::test]
+    async fn test_stop_rules_per_model() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        assert_eq!(db.get_stop_rules("Bounce").await.unwrap(), StopRules::default());
+
+        db.set_setting("max_technical_stop_atr_percentage", "0.2").await.unwrap();
+        db.set_setting("stop_loss_atr_percentage:Bounce", "0.05").await.unwrap();
+        let bounce = db.get_stop_rules("Bounce").await.unwrap();
+        assert_eq!(bounce.default_atr_fraction, 0.05);
+        assert_eq!(bounce.max_atr_fraction, 0.2);
+        assert_eq!(db.get_stop_rules("Breakout").await.unwrap().default_atr_fraction, 0.10);
+    }
+
+    #[tokio::test]
+    async fn test_model_defaults() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        assert_eq!(db.get_model_defaults("Breakout").await.unwrap(), ModelDefaults::default());
+
+        let bounce = ModelDefaults { risk_per_trade: 50.0, stop_atr_fraction: 0.05, target_r: 1.5 };
+        db.set_model_defaults("Bounce", &bounce).await.unwrap();
+        assert_eq!(db.get_model_defaults("Bounce").await.unwrap(), bounce);
+        // The stop rules read the same override
+        assert_eq!(db.get_stop_rules("Bounce").await.unwrap().default_atr_fraction, 0.05);
+
+        db.set_setting("risk_per_trade", "200").await.unwrap();
+        assert_eq!(db.get_model_defaults("Breakout").await.unwrap().risk_per_trade, 200.0);
+        assert_eq!(db.get_model_defaults("Bounce").await.unwrap().risk_per_trade, 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_checklist_items() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let shared = ChecklistItem { id: "earnings".to_string(), model: None, text: "No earnings within 5 days".to_string(), position: 1 };
+        let bounce = ChecklistItem { id: "trend".to_string(), model: Some(TradingModel::Bounce), text: "Higher timeframe trend aligned".to_string(), position: 0 };
+        db.save_checklist_item(DbChecklistItem::from_item(&shared)).await.unwrap();
+        db.save_checklist_item(DbChecklistItem::from_item(&bounce)).await.unwrap();
+
+        let for_bounce: Vec<ChecklistItem> = db.get_checklist_items(Some("Bounce")).await.unwrap()
+            .iter()
+            .map(|i| i.to_item())
+            .collect();
+        assert_eq!(for_bounce, vec![bounce.clone(), shared.clone()]);
+        assert_eq!(db.get_checklist_items(Some("Breakout")).await.unwrap().len(), 1);
+        assert_eq!(db.get_checklist_items(None).await.unwrap().len(), 2);
+
+        db.delete_checklist_item("earnings").await.unwrap();
+        assert!(db.get_checklist_items(Some("Breakout")).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_atr_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let method = OutlierMethod::default();
+        let mut result = ATRResult::new("AAPL".to_string(), 14, method, AtrSmoothing::Wilder);
+        result.filtered_atr = 3.0;
+        result.regular_atr = 4.0;
+        result.total_bars = 40;
+        result.excluded_bars = 4;
+        result.is_valid = true;
+        db.save_atr_cache(DbAtrCache::from_result(&result)).await.unwrap();
+
+        // Keyed by period, method and smoothing as well as symbol
+        assert!(db.get_atr_cache("AAPL", 14, &method.key(), "Simple").await.unwrap().is_none());
+        assert!(db.get_atr_cache("AAPL", 20, &method.key(), "Wilder").await.unwrap().is_none());
+        let cached = db.get_atr_cache("AAPL", 14, "IQR:1.5", "Wilder").await.unwrap().unwrap()
+            .to_result(method, AtrSmoothing::Wilder)
+            .unwrap();
+        assert_eq!(cached.filtered_atr, 3.0);
+        assert_eq!(cached.atr_difference_percent, -25.0);
+        assert_eq!(cached.exclusion_rate, 0.1);
+        assert_eq!(cached.calculation_date.timestamp(), result.calculation_date.timestamp());
+
+        // A recalculation replaces the entry
+        result.filtered_atr = 3.5;
+        db.save_atr_cache(DbAtrCache::from_result(&result)).await.unwrap();
+        assert_eq!(db.get_atr_cache("AAPL", 14, "IQR:1.5", "Wilder").await.unwrap().unwrap().filtered_atr, 3.5);
+        assert_eq!(db.get_settings().await.unwrap().atr.cache_ttl, AtrCacheTtl::Session);
+
+        // Auto is cached under its own key, with the method it picked
+        let mut auto = ATRResult::new("AAPL".to_string(), 14, OutlierMethod::Auto, AtrSmoothing::Wilder);
+        auto.applied_method = OutlierMethod::Percentile { low: 5.0, high: 95.0 };
+        auto.method_rationale = Some("heavy tails".to_string());
+        db.save_atr_cache(DbAtrCache::from_result(&auto)).await.unwrap();
+        let cached = db.get_atr_cache("AAPL", 14, "Auto", "Wilder").await.unwrap().unwrap()
+            .to_result(OutlierMethod::Auto, AtrSmoothing::Wilder)
+            .unwrap();
+        assert_eq!(cached.applied_method, auto.applied_method);
+        assert_eq!(cached.method_rationale.as_deref(), Some("heavy tails"));
+    }
+
+    #[tokio::test]
+    async fn test_atr_analysis_at() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        db.record_atr_analysis("AAPL", "2025-07-21T14:00:00+00:00", "monday").await.unwrap();
+        db.record_atr_analysis("AAPL", "2025-07-23T14:00:00+00:00", "wednesday").await.unwrap();
+        db.record_atr_analysis("MSFT", "2025-07-22T14:00:00+00:00", "msft").await.unwrap();
+
+        // A template created Tuesday was placed on Monday's analysis
+        let tuesday = db.get_atr_analysis_at("AAPL", "2025-07-22T15:00:00+00:00").await.unwrap();
+        assert_eq!(tuesday.as_deref(), Some("monday"));
+        let later = db.get_atr_analysis_at("AAPL", "2025-07-30T15:00:00+00:00").await.unwrap();
+        assert_eq!(later.as_deref(), Some("wednesday"));
+        assert!(db.get_atr_analysis_at("AAPL", "2025-07-20T00:00:00+00:00").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_basket_membership() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let first = DbOrderTemplate::new(
+            "First".to_string(), "AAPL".to_string(), OrderSide::Long, 100, 150.0, 145.0, TradingModel::Breakout,
+        );
+        let second = DbOrderTemplate::new(
+            "Second".to_string(), "MSFT".to_string(), OrderSide::Short, 10, 410.0, 415.0, TradingModel::Bounce,
+        );
+        db.save_template(first.clone()).await.unwrap();
+        db.save_template(second.clone()).await.unwrap();
+
+        let basket = DbBasket::new("Morning setups".to_string());
+        db.create_basket(basket.clone(), std::slice::from_ref(&second.id)).await.unwrap();
+        db.add_template_to_basket(&basket.id, &first.id).await.unwrap();
+        db.add_template_to_basket(&basket.id, &second.id).await.unwrap();
+
+        // Members keep insertion order and are not duplicated
+        assert_eq!(db.get_basket_template_ids(&basket.id).await.unwrap(), vec![second.id.clone(), first.id.clone()]);
+
+        // Deleting a template drops it from the basket
+        db.delete_template(&second.id).await.unwrap();
+        assert_eq!(db.get_basket_template_ids(&basket.id).await.unwrap(), vec![first.id.clone()]);
+
+        db.delete_basket(&basket.id).await.unwrap();
+        assert!(db.get_basket(&basket.id).await.unwrap().is_none());
+        assert!(db.get_basket_template_ids(&basket.id).await.unwrap().is_empty());
+
+        // A member that can't be added leaves no basket behind
+        let partial = DbBasket::new("Partial".to_string());
+        assert!(db.create_basket(partial.clone(), &[first.id.clone(), "missing".to_string()]).await.is_err());
+        assert!(db.get_basket(&partial.id).await.unwrap().is_none());
+        assert!(db.get_basket_template_ids(&partial.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_r_multiples_by_model() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        for (template_id, model, r_multiple, closed_at) in [
+            ("a", "Breakout", 2.0, "2025-07-01T15:00:00Z"),
+            ("b", "Bounce", -1.0, "2025-07-02T15:00:00Z"),
+            ("c", "Breakout", -0.5, "2025-07-03T15:00:00Z"),
+        ] {
+            db.record_trade_outcome(DbTradeOutcome {
+                template_id: template_id.to_string(),
+                symbol: "AAPL".to_string(),
+                model: model.to_string(),
+                realized_pnl: r_multiple * 100.0,
+                initial_risk: 100.0,
+                r_multiple,
+                closed_at: closed_at.to_string(),
+            }).await.unwrap();
+        }
+
+        assert_eq!(db.get_r_multiples(Some("Breakout"), None, None).await.unwrap(), vec![2.0, -0.5]);
+        assert_eq!(db.get_r_multiples(None, None, None).await.unwrap(), vec![2.0, -1.0, -0.5]);
+        assert_eq!(db.get_r_multiples(None, Some("AAPL"), Some(2)).await.unwrap(), vec![-1.0, -0.5]);
+        assert_eq!(db.get_r_multiples(None, Some("MSFT"), None).await.unwrap(), Vec::<f64>::new());
+        assert!(db.has_trade_outcome("a").await.unwrap());
+        assert!(!db.has_trade_outcome("z").await.unwrap());
+
+        let outcomes = db.get_trade_outcomes().await.unwrap();
+        assert_eq!(outcomes.iter().map(|o| o.template_id.as_str()).collect::<Vec<_>>(), ["a", "b", "c"]);
+        let outcome = outcomes[1].to_outcome().unwrap();
+        assert_eq!((outcome.model, outcome.realized_pnl), (TradingModel::Bounce, -100.0));
+    }
+
+    #[tokio::test]
+    async fn test_sector_membership() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let mut semis = DbSector::from_sector(&Sector {
+            name: "Semis".to_string(),
+            max_notional: Some(50000.0),
+            max_risk: None,
+            symbols: Vec::new(),
+        });
+        db.save_sector(semis.clone()).await.unwrap();
+        db.set_symbol_sector("NVDA", "Semis").await.unwrap();
+        db.set_symbol_sector("AMD", "Semis").await.unwrap();
+
+        // Updating caps keeps the members
+        semis.max_risk = Some(1000.0);
+        db.save_sector(semis).await.unwrap();
+        let sectors = db.get_all_sectors().await.unwrap();
+        assert_eq!(sectors.len(), 1);
+        assert_eq!(sectors[0].max_risk, Some(1000.0));
+        assert_eq!(db.get_sector_symbols("Semis").await.unwrap(), vec!["AMD".to_string(), "NVDA".to_string()]);
+
+        db.clear_symbol_sector("AMD").await.unwrap();
+        assert_eq!(db.get_sector_symbols("Semis").await.unwrap(), vec!["NVDA".to_string()]);
+
+        db.delete_sector("Semis").await.unwrap();
+        assert!(db.get_all_sectors().await.unwrap().is_empty());
+        assert!(db.get_sector_symbols("Semis").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_symbol_settings() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+        db.save_sector(DbSector::from_sector(&Sector {
+            name: "Semis".to_string(),
+            max_notional: None,
+            max_risk: None,
+            symbols: Vec::new(),
+        })).await.unwrap();
+
+        let mut nvda = SymbolSettings {
+            symbol: "NVDA".to_string(),
+            risk_per_trade: Some(50.0),
+            atr_period: Some(10),
+            tick_size: Some(0.01),
+            sector: Some("Semis".to_string()),
+            notes: Some("Gaps on earnings".to_string()),
+        };
+        db.save_symbol_settings(DbSymbolSettings::from_settings(&nvda)).await.unwrap();
+        assert_eq!(db.get_symbol_settings("NVDA").await.unwrap().unwrap().to_settings(), nvda);
+        // The sector tag is the symbol's sector membership
+        assert_eq!(db.get_sector_symbols("Semis").await.unwrap(), vec!["NVDA".to_string()]);
+        assert!(db.get_symbol_settings("AMD").await.unwrap().is_none());
+
+        nvda.risk_per_trade = None;
+        nvda.sector = None;
+        db.save_symbol_settings(DbSymbolSettings::from_settings(&nvda)).await.unwrap();
+        let all = db.get_all_symbol_settings().await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].to_settings(), nvda);
+        assert!(db.get_sector_symbols("Semis").await.unwrap().is_empty());
+
+        assert!(db.delete_symbol_settings("NVDA").await.unwrap());
+        assert!(!db.delete_symbol_settings("NVDA").await.unwrap());
+        assert!(db.get_all_symbol_settings().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watchlist() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+        assert!(db.get_settings().await.unwrap().scanner.universe.is_empty());
+
+        let entry = |symbol: &str, note: Option<&str>| DbWatchlistEntry::from_entry(&WatchlistEntry {
+            symbol: symbol.to_string(),
+            note: note.map(str::to_string),
+            added_at: chrono::Utc::now(),
+        });
+        // The migrations leave one default watchlist
+        let watchlists = db.get_watchlists().await.unwrap();
+        assert_eq!(watchlists.iter().map(|w| w.id.as_str()).collect::<Vec<_>>(), [DEFAULT_WATCHLIST_ID]);
+
+        db.save_watchlist_entry(DEFAULT_WATCHLIST_ID, entry("NVDA", Some("Base breakout"))).await.unwrap();
+        db.save_watchlist_entry(DEFAULT_WATCHLIST_ID, entry("AMD", None)).await.unwrap();
+        // Re-adding without a note keeps the old one
+        db.save_watchlist_entry(DEFAULT_WATCHLIST_ID, entry("NVDA", None)).await.unwrap();
+
+        let symbols = |entries: Vec<DbWatchlistEntry>| entries.iter().map(|e| e.symbol.clone()).collect::<Vec<_>>();
+        let watchlist: Vec<WatchlistEntry> = db.get_watchlist(DEFAULT_WATCHLIST_ID).await.unwrap().iter().map(|e| e.to_entry()).collect();
+        assert_eq!(watchlist.iter().map(|e| e.symbol.as_str()).collect::<Vec<_>>(), ["NVDA", "AMD"]);
+        assert_eq!(watchlist[0].note.as_deref(), Some("Base breakout"));
+
+        db.reorder_watchlist(DEFAULT_WATCHLIST_ID, &["AMD".to_string(), "NVDA".to_string()]).await.unwrap();
+        assert_eq!(symbols(db.get_watchlist(DEFAULT_WATCHLIST_ID).await.unwrap()), ["AMD", "NVDA"]);
+
+        let swing = DbWatchlist::new("Swing".to_string(), 1);
+        db.create_watchlist(swing.clone()).await.unwrap();
+        assert!(db.create_watchlist(DbWatchlist::new("Swing".to_string(), 2)).await.is_err());
+        db.save_watchlist_entry(&swing.id, entry("TSLA", None)).await.unwrap();
+        db.save_watchlist_entry(&swing.id, entry("AMD", None)).await.unwrap();
+        assert_eq!(db.get_watched_symbols().await.unwrap(), ["AMD", "NVDA", "TSLA"]);
+
+        assert!(db.rename_watchlist(&swing.id, "Swing trades").await.unwrap());
+        assert!(!db.rename_watchlist("missing", "Nothing").await.unwrap());
+        assert_eq!(db.get_watchlists().await.unwrap()[1].name, "Swing trades");
+
+        db.delete_watchlist_entry(DEFAULT_WATCHLIST_ID, "AMD").await.unwrap();
+        assert_eq!(symbols(db.get_watchlist(DEFAULT_WATCHLIST_ID).await.unwrap()), ["NVDA"]);
+
+        assert!(db.delete_watchlist(&swing.id).await.unwrap());
+        assert!(db.get_watchlist(&swing.id).await.unwrap().is_empty());
+        assert_eq!(db.get_watched_symbols().await.unwrap(), ["NVDA"]);
+
+        db.set_setting("scanner_universe", "msft, aapl").await.unwrap();
+        assert_eq!(db.get_settings().await.unwrap().scanner.universe, ["MSFT", "AAPL"]);
+    }
+
+    #[tokio::test]
+    async fn test_earnings_calendar() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+        let settings = db.get_settings().await.unwrap();
+        assert_eq!((settings.orders.earnings_policy, settings.orders.earnings_hold_days), (EarningsPolicy::Warn, 5));
+        assert!(!settings.atr.exclude_earnings);
+
+        let event = |symbol: &str, day: u32, timing: EarningsTiming| DbEarningsEvent::from_event(&EarningsEvent {
+            symbol: symbol.to_string(),
+            date: chrono::NaiveDate::from_ymd_opt(2025, 8, day).unwrap(),
+            timing,
+        });
+        db.save_earnings_event(event("NVDA", 27, EarningsTiming::Unknown)).await.unwrap();
+        db.save_earnings_event(event("NVDA", 27, EarningsTiming::AfterClose)).await.unwrap();
+        db.save_earnings_event(event("AAPL", 1, EarningsTiming::AfterClose)).await.unwrap();
+
+        let nvda = db.get_earnings_events(Some("NVDA")).await.unwrap();
+        assert_eq!(nvda.len(), 1);
+        assert_eq!(nvda[0].to_event().unwrap().timing, EarningsTiming::AfterClose);
+        assert_eq!(db.get_earnings_events(None).await.unwrap()[0].symbol, "AAPL");
+
+        db.delete_earnings_event("NVDA", "2025-08-27").await.unwrap();
+        assert!(db.get_earnings_events(Some("NVDA")).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_settings() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        // The seeded values are the defaults
+        let mut settings = db.get_settings().await.unwrap();
+        assert_eq!(settings, Settings::default());
+
+        settings.risk.daily_loss_limit = 750.0;
+        settings.orders.commission_model = CommissionModel::PerOrder { amount: 1.5 };
+        settings.connection.paper_address = "10.0.0.5:4002".to_string();
+        db.save_settings(&settings).await.unwrap();
+        assert_eq!(db.get_settings().await.unwrap(), settings);
+        // Per-model overrides still fall back to the saved value
+        settings.risk.risk_per_trade = 300.0;
+        db.save_settings(&settings).await.unwrap();
+        assert_eq!(db.get_model_defaults("Breakout").await.unwrap().risk_per_trade, 300.0);
+        // The per-share amounts are kept for switching back
+        assert_eq!(db.get_setting("commission_per_share").await.unwrap().as_deref(), Some("0.005"));
+    }
+
+    #[tokio::test]
+    async fn test_executions() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let fill = |exec_id: &str, side: OrderSide, price: f64, minute: u32| Fill {
+            exec_id: exec_id.to_string(),
+            order_id: 7,
+            template_id: Some("t1".to_string()),
+            symbol: "AAPL".to_string(),
+            side,
+            quantity: 100.0,
+            price,
+            time: chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2025, 7, 14, 14, minute, 0).unwrap(),
+            commission: None,
+        };
+        db.save_executions(&[
+            DbExecution::from_fill(&fill("e2", OrderSide::Short, 101.0, 30)),
+            DbExecution::from_fill(&fill("e1", OrderSide::Long, 100.0, 0)),
+        ]).await.unwrap();
+        // A correction replaces the original
+        db.save_executions(&[DbExecution::from_fill(&fill("e1", OrderSide::Long, 100.5, 0))]).await.unwrap();
+
+        let fills: Vec<Fill> = db.get_executions("AAPL").await.unwrap().iter().filter_map(|e| e.to_fill()).collect();
+        assert_eq!(fills, vec![fill("e1", OrderSide::Long, 100.5, 0), fill("e2", OrderSide::Short, 101.0, 30)]);
+        assert!(db.get_executions("MSFT").await.unwrap().is_empty());
+
+        // A commission report fills in the commission, and a later sync without one keeps it
+        let charged = Fill { commission: Some(1.25), ..fill("e2", OrderSide::Short, 101.0, 30) };
+        db.save_executions(&[DbExecution::from_fill(&charged)]).await.unwrap();
+        db.save_executions(&[DbExecution::from_fill(&fill("e2", OrderSide::Short, 101.0, 30))]).await.unwrap();
+        let by_template = db.get_template_executions("t1").await.unwrap();
+        assert_eq!(by_template.iter().map(|e| e.commission).collect::<Vec<_>>(), vec![None, Some(1.25)]);
+        assert!(db.get_template_executions("t2").await.unwrap().is_empty());
+
+        let at = |minute: u32| chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2025, 7, 14, 14, minute, 0).unwrap();
+        let between = db.get_executions_between(at(15), at(45)).await.unwrap();
+        assert_eq!(between.iter().map(|e| e.exec_id.as_str()).collect::<Vec<_>>(), vec!["e2"]);
+        assert!(db.get_executions_between(at(30), at(30)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_csv_export() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let mut template = DbOrderTemplate::new(
+            "Breakout, daily".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            100,
+            150.0,
+            145.0,
+            TradingModel::Breakout,
+        );
+        template.notes = Some("said \"buy\"".to_string());
+        db.save_template(template).await.unwrap();
+        let fill = Fill {
+            exec_id: "e1".to_string(),
+            order_id: 7,
+            template_id: None,
+            symbol: "AAPL".to_string(),
+            side: OrderSide::Long,
+            quantity: 100.0,
+            price: 150.0,
+            time: chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2025, 7, 14, 14, 0, 0).unwrap(),
+            commission: Some(1.0),
+        };
+        db.save_executions(&[DbExecution::from_fill(&fill)]).await.unwrap();
+
+        let path = temp_dir.path().join("templates.csv");
+        assert_eq!(db.export_csv(CsvTable::Templates, &path, None, None).await.unwrap(), 1);
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        assert_eq!(reader.headers().unwrap().iter().collect::<Vec<_>>(), export::TEMPLATE_COLUMNS);
+        let row = reader.records().next().unwrap().unwrap();
+        assert_eq!(&row[1], "Breakout, daily");
+        assert_eq!(&row[22], "said \"buy\"");
+        assert_eq!(&row[11], "");
+
+        let path = temp_dir.path().join("executions.csv");
+        assert_eq!(db.export_csv(CsvTable::Executions, &path, None, None).await.unwrap(), 1);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().nth(1), Some("e1,7,,AAPL,Buy,100,150,1,2025-07-14T14:00:00+00:00"));
+        // Outside the range nothing but the header is written
+        let later = chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2025, 8, 1, 0, 0, 0).unwrap();
+        assert_eq!(db.export_csv(CsvTable::Executions, &path, Some(later), None).await.unwrap(), 0);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+        let path = temp_dir.path().join("trades.csv");
+        assert_eq!(db.export_csv(CsvTable::Trades, &path, None, None).await.unwrap(), 0);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), export::TRADE_COLUMNS.join(","));
+    }
+
+    #[tokio::test]
+    async fn test_trades() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let at = |day: u32| chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2025, 7, day, 14, 0, 0).unwrap();
+        let trade = |id: &str, symbol: &str, model: Option<TradingModel>, day: u32| Trade {
+            id: id.to_string(),
+            symbol: symbol.to_string(),
+            side: OrderSide::Long,
+            template_id: None,
+            model,
+            quantity: 100.0,
+            opened_at: at(day),
+            closed_at: Some(at(day + 1)),
+            avg_entry: 100.0,
+            avg_exit: Some(102.0),
+            realized_pnl: 198.0,
+            commission: 2.0,
+            r_multiple: model.map(|_| 1.0),
+        };
+        let aapl = [trade("a1", "AAPL", Some(TradingModel::Breakout), 14), trade("a2", "AAPL", None, 16)];
+        db.replace_trades("AAPL", &aapl.iter().map(DbTrade::from_trade).collect::<Vec<_>>()).await.unwrap();
+        db.replace_trades("MSFT", &[DbTrade::from_trade(&trade("m1", "MSFT", Some(TradingModel::Bounce), 15))]).await.unwrap();
+
+        let ids = |trades: Vec<DbTrade>| trades.iter().map(|t| t.id.clone()).collect::<Vec<_>>();
+        let all = db.get_trades(None, None, None, None).await.unwrap();
+        assert_eq!(all[0].to_trade().unwrap(), aapl[0]);
+        assert_eq!(ids(all), vec!["a1", "m1", "a2"]);
+        assert_eq!(ids(db.get_trades(Some("AAPL"), None, None, None).await.unwrap()), vec!["a1", "a2"]);
+        assert_eq!(ids(db.get_trades(None, Some(TradingModel::Bounce), None, None).await.unwrap()), vec!["m1"]);
+        assert_eq!(ids(db.get_trades(None, None, Some(at(15)), Some(at(16))).await.unwrap()), vec!["m1"]);
+
+        // Rebuilding a symbol replaces only its trades
+        db.replace_trades("AAPL", &[]).await.unwrap();
+        assert_eq!(ids(db.get_trades(None, None, None, None).await.unwrap()), vec!["m1"]);
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let mut fade = DbOrderTemplate::new("NVDA fade".to_string(), "NVDA".to_string(), OrderSide::Short, 50, 120.0, 125.0, TradingModel::FalseBreakout);
+        fade.created_at = "2025-03-12T14:00:00+00:00".to_string();
+        fade.notes = Some("Failed above the February high on light volume".to_string());
+        db.create_template(fade.clone()).await.unwrap();
+        let mut other = DbOrderTemplate::new("NVDA base".to_string(), "NVDA".to_string(), OrderSide::Long, 50, 130.0, 125.0, TradingModel::Breakout);
+        other.created_at = "2025-05-02T14:00:00+00:00".to_string();
+        db.create_template(other.clone()).await.unwrap();
+        let opened = chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2025, 3, 13, 14, 0, 0).unwrap();
+        let trade = Trade {
+            id: "t1".to_string(),
+            symbol: "NVDA".to_string(),
+            side: OrderSide::Short,
+            template_id: Some(fade.id.clone()),
+            model: Some(TradingModel::FalseBreakout),
+            quantity: 50.0,
+            opened_at: opened,
+            closed_at: None,
+            avg_entry: 120.0,
+            avg_exit: None,
+            realized_pnl: 0.0,
+            commission: 1.0,
+            r_multiple: None,
+        };
+        db.replace_trades("NVDA", &[DbTrade::from_trade(&trade)]).await.unwrap();
+        db.save_watchlist_entry(DEFAULT_WATCHLIST_ID, DbWatchlistEntry {
+            symbol: "AMD".to_string(),
+            note: Some("Volume dried up before earnings".to_string()),
+            added_at: Utc::now().to_rfc3339(),
+        }).await.unwrap();
+
+        let kinds = |hits: Vec<SearchHit>| hits.into_iter().map(|h| (h.kind, h.id)).collect::<Vec<_>>();
+        let fade_hits = vec![(search::SearchKind::Template, fade.id.clone()), (search::SearchKind::Trade, "t1".to_string())];
+        let mut hits = kinds(db.search("that NVDA false breakout from March", 10).await.unwrap());
+        assert!(hits.is_empty(), "every word must match, 'that' and 'from' included");
+        hits = kinds(db.search("NVDA false breakout March", 10).await.unwrap());
+        hits.sort_by_key(|(kind, _)| *kind == search::SearchKind::Trade);
+        assert_eq!(hits, fade_hits);
+        // Prefixes match, and trades carry their template's notes
+        let hits = db.search("light vol", 10).await.unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].snippet.contains("[light] [volume]"));
+        assert_eq!(kinds(db.search("volume earnings", 10).await.unwrap()), [(search::SearchKind::Watchlist, DEFAULT_WATCHLIST_ID.to_string())]);
+        assert!(db.search("  ", 10).await.unwrap().is_empty());
+
+        // Edits and deletes keep the index current
+        db.update_template_notes(&fade.id, Some("Reclaimed the level")).await.unwrap();
+        assert_eq!(db.search("light", 10).await.unwrap().len(), 0);
+        assert_eq!(db.search("reclaimed", 10).await.unwrap().len(), 2);
+        db.delete_template(&fade.id).await.unwrap();
+        assert_eq!(kinds(db.search("reclaimed", 10).await.unwrap()), [(search::SearchKind::Trade, "t1".to_string())]);
+        db.replace_trades("NVDA", &[]).await.unwrap();
+        db.delete_watchlist_entry(DEFAULT_WATCHLIST_ID, "AMD").await.unwrap();
+        assert!(db.search("reclaimed", 10).await.unwrap().is_empty());
+        assert!(db.search("earnings", 10).await.unwrap().is_empty());
+        assert_eq!(kinds(db.search("NVDA", 10).await.unwrap()), [(search::SearchKind::Template, other.id.clone())]);
+    }
+
+    #[tokio::test]
+    async fn test_bar_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let bar = |timestamp: i64, close: f64| DbBar { timestamp, open: 100.0, high: 102.0, low: 99.0, close, volume: 1000, wap: 100.5, count: 10 };
+        assert_eq!(db.get_bar_coverage("AAPL", "1 day", true).await.unwrap(), None);
+
+        db.save_bars("AAPL", "1 day", true, &[bar(100, 101.0), bar(200, 101.5)], (50, 250)).await.unwrap();
+        // The forming last bar is replaced by its update
+        db.save_bars("AAPL", "1 day", true, &[bar(200, 102.0), bar(300, 103.0)], (50, 350)).await.unwrap();
+        db.save_bars("AAPL", "1 day", false, &[bar(150, 90.0)], (0, 150)).await.unwrap();
+
+        assert_eq!(db.get_bar_coverage("AAPL", "1 day", true).await.unwrap(), Some((50, 350)));
+        let closes = |bars: Vec<DbBar>| bars.iter().map(|b| b.close).collect::<Vec<_>>();
+        assert_eq!(closes(db.get_bars("AAPL", "1 day", true, 0).await.unwrap()), vec![101.0, 102.0, 103.0]);
+        assert_eq!(closes(db.get_bars("AAPL", "1 day", true, 200).await.unwrap()), vec![102.0, 103.0]);
+        assert_eq!(closes(db.get_bars("AAPL", "1 day", false, 0).await.unwrap()), vec![90.0]);
+        assert!(db.get_bars("AAPL", "1 hour", true, 0).await.unwrap().is_empty());
+
+        let restored = db.get_bars("AAPL", "1 day", true, 0).await.unwrap()[0].to_bar().unwrap();
+        assert_eq!(restored.timestamp.timestamp(), 100);
+        assert_eq!(restored.volume, 1000);
+    }
+
+    #[tokio::test]
+    async fn test_chart_drawings() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        let anchor = |day: u32, price: f64| DrawingAnchor {
+            time: chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2025, 7, day, 20, 0, 0).unwrap(),
+            price,
+        };
+        let level = Drawing::new("AAPL", DrawingShape::HorizontalLine { price: 200.0 });
+        let mut trend = Drawing::new("AAPL", DrawingShape::Trendline { start: anchor(1, 190.0), end: anchor(10, 205.0) });
+        db.save_drawing(&DbDrawing::from_drawing(&level).unwrap()).await.unwrap();
+        db.save_drawing(&DbDrawing::from_drawing(&trend).unwrap()).await.unwrap();
+        db.save_drawing(&DbDrawing::from_drawing(&Drawing::new("MSFT", DrawingShape::Zone { start: anchor(1, 400.0), end: anchor(3, 410.0) })).unwrap()).await.unwrap();
+        let vwap = Drawing::new("MSFT", DrawingShape::AnchoredVwap { time: anchor(7, 0.0).time });
+        db.save_drawing(&DbDrawing::from_drawing(&vwap).unwrap()).await.unwrap();
+
+        // Moving a drawing replaces its shape in place
+        trend.shape = DrawingShape::Trendline { start: anchor(2, 191.0), end: anchor(11, 207.0) };
+        trend.color = Some("#ff0000".to_string());
+        db.save_drawing(&DbDrawing::from_drawing(&trend).unwrap()).await.unwrap();
+
+        let drawings: Vec<Drawing> = db.get_drawings("AAPL").await.unwrap().iter().map(|d| d.to_drawing().unwrap()).collect();
+        assert_eq!(drawings.len(), 2);
+        assert!(drawings.contains(&level) && drawings.contains(&trend));
+        assert_eq!(db.get_drawing(&trend.id).await.unwrap().unwrap().to_drawing().unwrap(), trend);
+
+        db.delete_drawing(&level.id).await.unwrap();
+        assert_eq!(db.get_drawings("AAPL").await.unwrap().len(), 1);
+        assert!(db.get_drawing(&level.id).await.unwrap().is_none());
+        assert_eq!(db.get_drawings("MSFT").await.unwrap().len(), 2);
+        assert_eq!(db.get_drawing(&vwap.id).await.unwrap().unwrap().to_drawing().unwrap(), vwap);
+    }
+
+    #[tokio::test]
+    async fn test_themes() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        let db = db.lock().await;
+
+        // Charts start on the built-in dark theme
+        assert_eq!(db.get_setting("chart_theme").await.unwrap().as_deref(), Some("dark"));
+
+        let mut theme = ChartTheme::light();
+        theme.colors.background = "#fdf6e3".to_string();
+        db.save_theme(&DbTheme::from_theme("solarized", &theme).unwrap()).await.unwrap();
+        db.save_theme(&DbTheme::from_theme("amber", &ChartTheme::default()).unwrap()).await.unwrap();
+
+        // Saving under a taken name replaces the theme
+        theme.font_size = 14.0;
+        theme.fonts.tooltip.family = Some("DejaVu Sans Mono".to_string());
+        db.save_theme(&DbTheme::from_theme("solarized", &theme).unwrap()).await.unwrap();
+
+        let names: Vec<String> = db.get_themes().await.unwrap().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["amber", "solarized"]);
+        let loaded = db.get_theme("solarized").await.unwrap().unwrap().to_theme().unwrap();
+        assert_eq!(loaded.colors.background, "#fdf6e3");
+        assert_eq!(loaded.font_size, 14.0);
+        assert_eq!(loaded.fonts.tooltip.family.as_deref(), Some("DejaVu Sans Mono"));
+        assert!(loaded.fonts.tooltip.size.is_none());
+        assert!(db.get_theme("missing").await.unwrap().is_none());
+
+        // Themes saved before per-part fonts use the theme font everywhere
+        let mut json = serde_json::to_value(&theme).unwrap();
+        json.as_object_mut().unwrap().remove("fonts");
+        let old = DbTheme { name: "old".to_string(), theme: json.to_string(), updated_at: String::new() };
+        assert!(old.to_theme().unwrap().fonts.axis.family.is_none());
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::models::{DbExecution, DbOrderTemplate, DbTrade};
+
+/// What `ExportCsv` writes out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CsvTable {
+    Templates,
+    Trades,
+    Executions,
+}
+
+// Column layouts. Spreadsheets and tax tools key on these names and positions, so columns
+// are only ever added at the end.
+pub const TEMPLATE_COLUMNS: &[&str] = &[
+    "id", "name", "symbol", "currency", "side", "model", "status", "quantity", "limit_price", "stop_price",
+    "technical_stop_price", "risk_per_trade", "time_in_force", "entry_type", "filled_quantity", "avg_fill_price",
+    "filled_at", "activated_at", "expires_at", "archived_at", "created_at", "updated_at", "notes",
+];
+
+pub const TRADE_COLUMNS: &[&str] = &[
+    "id", "symbol", "side", "model", "template_id", "quantity", "opened_at", "closed_at", "avg_entry", "avg_exit",
+    "realized_pnl", "commission", "r_multiple",
+];
+
+pub const EXECUTION_COLUMNS: &[&str] = &[
+    "exec_id", "order_id", "template_id", "symbol", "side", "quantity", "price", "commission", "executed_at",
+];
+
+pub fn template_row(t: &DbOrderTemplate) -> Vec<String> {
+    vec![
+        t.id.clone(),
+        t.name.clone(),
+        t.symbol.clone(),
+        t.currency.clone(),
+        t.side.clone(),
+        t.model.clone(),
+        t.status.clone(),
+        t.quantity.to_string(),
+        t.limit_price.to_string(),
+        t.stop_price.to_string(),
+        optional(t.technical_stop_price),
+        optional(t.risk_per_trade),
+        t.time_in_force.clone(),
+        t.entry_type.clone(),
+        t.filled_quantity.to_string(),
+        optional(t.avg_fill_price),
+        optional(t.filled_at.as_ref()),
+        optional(t.activated_at.as_ref()),
+        optional(t.expires_at.as_ref()),
+        optional(t.archived_at.as_ref()),
+        t.created_at.clone(),
+        t.updated_at.clone(),
+        optional(t.notes.as_ref()),
+    ]
+}
+
+pub fn trade_row(t: &DbTrade) -> Vec<String> {
+    vec![
+        t.id.clone(),
+        t.symbol.clone(),
+        t.side.clone(),
+        optional(t.model.as_ref()),
+        optional(t.template_id.as_ref()),
+        t.quantity.to_string(),
+        t.opened_at.clone(),
+        optional(t.closed_at.as_ref()),
+        t.avg_entry.to_string(),
+        optional(t.avg_exit),
+        t.realized_pnl.to_string(),
+        t.commission.to_string(),
+        optional(t.r_multiple),
+    ]
+}
+
+pub fn execution_row(e: &DbExecution) -> Vec<String> {
+    vec![
+        e.exec_id.clone(),
+        e.order_id.to_string(),
+        optional(e.template_id.as_ref()),
+        e.symbol.clone(),
+        e.side.clone(),
+        e.quantity.to_string(),
+        e.price.to_string(),
+        optional(e.commission),
+        e.executed_at.clone(),
+    ]
+}
+
+/// Write `columns` as the header and then `rows`, replacing any file at `path`
+pub fn write_csv(path: &Path, columns: &[&str], rows: &[Vec<String>]) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(columns)?;
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()
+}
+
+/// Empty for None, so blanks stay blank in a spreadsheet
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
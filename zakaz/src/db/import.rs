@@ -0,0 +1,172 @@
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ib::types::{OrderSide, OrderTemplate, TimeInForce, TradingModel};
+
+/// One planned trade read from an import file
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedTrade {
+    pub name: Option<String>,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub entry: f64,
+    pub stop: f64,
+    pub model: TradingModel,
+    pub quantity: Option<f64>, // None sizes from risk
+}
+
+impl PlannedTrade {
+    /// A GTC limit template for the plan, quantity 0 until sized when none was given
+    pub fn to_template(&self) -> OrderTemplate {
+        OrderTemplate::new(
+            self.name.clone().unwrap_or_else(|| format!("{} {:?} (import)", self.symbol, self.model)),
+            self.symbol.clone(),
+            self.side,
+            self.quantity.unwrap_or(0.0),
+            self.entry,
+            self.stop,
+            TimeInForce::GTC,
+            self.model,
+        )
+    }
+}
+
+/// A row of the file and what became of it: the planned trade, or why it couldn't be read
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    pub line: usize, // In the file, the header being line 1
+    pub plan: Result<PlannedTrade, String>,
+}
+
+/// What importing one row did: the new template's id, or the error
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub line: usize,
+    pub symbol: String,
+    pub result: Result<String, String>,
+}
+
+/// Read planned trades from a CSV file with a header row. Columns are matched by name in any
+/// order, ignoring case: `symbol`, `side` (long/buy or short/sell), `entry`, `stop` and `model`
+/// are required; `quantity` and `name` are optional, a blank quantity meaning auto-size.
+pub fn read_template_csv(path: &Path) -> std::io::Result<Vec<ImportRow>> {
+    parse_template_csv(std::fs::File::open(path)?)
+}
+
+pub fn parse_template_csv(input: impl Read) -> std::io::Result<Vec<ImportRow>> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).flexible(true).from_reader(input);
+    let headers: Vec<String> = reader.headers()?.iter().map(|h| h.to_lowercase()).collect();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let missing: Vec<&str> = ["symbol", "side", "entry", "stop", "model"].into_iter()
+        .filter(|name| column(name).is_none())
+        .collect();
+    if !missing.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Missing column(s): {}", missing.join(", ")),
+        ));
+    }
+    let columns = Columns {
+        symbol: column("symbol"),
+        side: column("side"),
+        entry: column("entry"),
+        stop: column("stop"),
+        model: column("model"),
+        quantity: column("quantity"),
+        name: column("name"),
+    };
+
+    let mut rows = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let line = index + 2;
+        let plan = match record {
+            Ok(record) => columns.plan(&record),
+            Err(e) => Err(e.to_string()),
+        };
+        rows.push(ImportRow { line, plan });
+    }
+    Ok(rows)
+}
+
+struct Columns {
+    symbol: Option<usize>,
+    side: Option<usize>,
+    entry: Option<usize>,
+    stop: Option<usize>,
+    model: Option<usize>,
+    quantity: Option<usize>,
+    name: Option<usize>,
+}
+
+impl Columns {
+    fn plan(&self, record: &csv::StringRecord) -> Result<PlannedTrade, String> {
+        let field = |column: Option<usize>| column.and_then(|i| record.get(i)).filter(|v| !v.is_empty());
+        let required = |column: Option<usize>, name: &str| field(column).ok_or_else(|| format!("{} is required", name));
+        let price = |column: Option<usize>, name: &str| {
+            let value = required(column, name)?;
+            value.parse::<f64>().map_err(|_| format!("{} {:?} is not a number", name, value))
+        };
+
+        let symbol = required(self.symbol, "symbol")?.to_uppercase();
+        let side = match required(self.side, "side")?.to_lowercase().as_str() {
+            "long" | "buy" => OrderSide::Long,
+            "short" | "sell" => OrderSide::Short,
+            other => return Err(format!("side {:?} should be long or short", other)),
+        };
+        let model_name = required(self.model, "model")?;
+        let model = [TradingModel::Breakout, TradingModel::FalseBreakout, TradingModel::Bounce, TradingModel::Continuation]
+            .into_iter()
+            .find(|m| format!("{:?}", m).eq_ignore_ascii_case(model_name))
+            .ok_or_else(|| format!("model {:?} should be Breakout, FalseBreakout, Bounce or Continuation", model_name))?;
+        let quantity = match field(self.quantity) {
+            Some(value) => Some(value.parse::<f64>().map_err(|_| format!("quantity {:?} is not a number", value))?),
+            None => None,
+        };
+
+        Ok(PlannedTrade {
+            name: field(self.name).map(str::to_string),
+            symbol,
+            side,
+            entry: price(self.entry, "entry")?,
+            stop: price(self.stop, "stop")?,
+            model,
+            quantity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template_csv() {
+        let csv = "Symbol,Side,Entry,Stop,Model,Quantity,Name\n\
+                   aapl,long,150,145,Breakout,100,\n\
+                   MSFT,Sell,400.5,410,falsebreakout,,MSFT fade\n\
+                   NVDA,up,120,115,Bounce,,\n\
+                   AMD,long,abc,115,Bounce,,\n\
+                   TSLA,long,200,190,Swing,,\n";
+        let rows = parse_template_csv(csv.as_bytes()).unwrap();
+        assert_eq!(rows.iter().map(|r| r.line).collect::<Vec<_>>(), [2, 3, 4, 5, 6]);
+
+        let aapl = rows[0].plan.as_ref().unwrap();
+        assert_eq!((aapl.symbol.as_str(), aapl.side, aapl.quantity, aapl.name.as_deref()), ("AAPL", OrderSide::Long, Some(100.0), None));
+        let msft = rows[1].plan.as_ref().unwrap();
+        assert_eq!((msft.side, msft.model, msft.quantity), (OrderSide::Short, TradingModel::FalseBreakout, None));
+        assert_eq!(msft.to_template().name, "MSFT fade");
+        assert_eq!(aapl.to_template().name, "AAPL Breakout (import)");
+
+        assert!(rows[2].plan.as_ref().unwrap_err().contains("side"));
+        assert!(rows[3].plan.as_ref().unwrap_err().contains("entry"));
+        assert!(rows[4].plan.as_ref().unwrap_err().contains("model"));
+    }
+
+    #[test]
+    fn test_missing_columns() {
+        let error = parse_template_csv("symbol,entry,stop\nAAPL,150,145\n".as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("side, model"));
+    }
+}
@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+use crate::{inf, wrn};
+
+/// Profile used when none is picked
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// File the default profile keeps its data in; other profiles use `<profile>.db`
+pub const DEFAULT_FILE: &str = "zakaz.db";
+
+/// Where the database lives and which profile it belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseLocation {
+    pub profile: String,
+    pub path: PathBuf,
+}
+
+impl DatabaseLocation {
+    /// The location picked at startup: `--database <file>` or `--profile <name>` on the command
+    /// line, then the `ZAKAZ_DATABASE` and `ZAKAZ_PROFILE` variables, then the default profile.
+    /// Profiles live in the data directory, `ZAKAZ_DATA_DIR` when set.
+    pub fn from_startup() -> Result<Self, String> {
+        let location = Self::resolve(std::env::args().skip(1), |name| std::env::var(name).ok())?;
+        if location.profile == DEFAULT_PROFILE {
+            adopt_legacy_database(Path::new(DEFAULT_FILE), &location.path);
+        }
+        Ok(location)
+    }
+
+    pub fn resolve(args: impl IntoIterator<Item = String>, env: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let mut database = None;
+        let mut profile = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let slot = match flag.as_str() {
+                "--database" => &mut database,
+                "--profile" => &mut profile,
+                _ => continue,
+            };
+            let value = value.or_else(|| args.next()).ok_or_else(|| format!("{} needs a value", flag))?;
+            *slot = Some(value);
+        }
+        let database = database.or_else(|| env("ZAKAZ_DATABASE")).filter(|p| !p.is_empty());
+        let profile = profile.or_else(|| env("ZAKAZ_PROFILE")).filter(|p| !p.is_empty());
+
+        let profile = profile.unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+        validate_profile(&profile)?;
+        if let Some(path) = database {
+            return Ok(Self { profile, path: PathBuf::from(path) });
+        }
+
+        let dir = data_dir(&env).ok_or("No data directory; set ZAKAZ_DATA_DIR or pass --database")?;
+        let file = if profile == DEFAULT_PROFILE {
+            DEFAULT_FILE.to_string()
+        } else {
+            format!("{}.db", profile)
+        };
+        Ok(Self { path: dir.join(file), profile })
+    }
+
+    /// The sqlx URL of the database file
+    pub fn url(&self) -> String {
+        format!("sqlite:{}", self.path.display())
+    }
+
+    /// Create the directory the database goes in
+    pub fn prepare(&self) -> std::io::Result<()> {
+        match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => std::fs::create_dir_all(dir),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Profile names become file names, so they stay to letters, digits, `-` and `_`
+pub fn validate_profile(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid profile name {:?}: use letters, digits, '-' and '_'", name));
+    }
+    Ok(())
+}
+
+/// The platform's per-user data directory for zakaz
+fn data_dir(env: &impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    let var = |name: &str| env(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    if let Some(dir) = var("ZAKAZ_DATA_DIR") {
+        return Some(dir);
+    }
+    let base = if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        var("XDG_DATA_HOME").or_else(|| var("HOME").map(|home| home.join(".local").join("share")))
+    };
+    base.map(|dir| dir.join("zakaz"))
+}
+
+/// Databases used to be kept in the working directory; the first start after the move copies
+/// one found there into the data directory. The original stays where it was.
+fn adopt_legacy_database(legacy: &Path, path: &Path) {
+    if path.exists() || !legacy.is_file() {
+        return;
+    }
+    if let Some(dir) = path.parent()
+        && let Err(e) = std::fs::create_dir_all(dir)
+    {
+        wrn!("Couldn't create {}: {}", dir.display(), e);
+        return;
+    }
+    match std::fs::copy(legacy, path) {
+        Ok(_) => inf!("Copied {} to {}", legacy.display(), path.display()),
+        Err(e) => wrn!("Couldn't copy {} to {}: {}", legacy.display(), path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        let data = env(&[("ZAKAZ_DATA_DIR", "/data")]);
+
+        let default = DatabaseLocation::resolve(args(&[]), &data).unwrap();
+        assert_eq!(default, DatabaseLocation { profile: DEFAULT_PROFILE.to_string(), path: PathBuf::from("/data/zakaz.db") });
+
+        let swing = DatabaseLocation::resolve(args(&["--profile", "swing"]), &data).unwrap();
+        assert_eq!(swing.path, PathBuf::from("/data/swing.db"));
+        assert_eq!(swing.profile, "swing");
+
+        let explicit = DatabaseLocation::resolve(args(&["--profile=day", "--database=/tmp/x.db"]), &data).unwrap();
+        assert_eq!(explicit.path, PathBuf::from("/tmp/x.db"));
+        assert_eq!(explicit.profile, "day");
+
+        // The command line wins over the environment
+        let vars = env(&[("ZAKAZ_DATA_DIR", "/data"), ("ZAKAZ_PROFILE", "swing")]);
+        assert_eq!(DatabaseLocation::resolve(args(&[]), &vars).unwrap().profile, "swing");
+        assert_eq!(DatabaseLocation::resolve(args(&["--profile", "day"]), &vars).unwrap().profile, "day");
+
+        assert!(DatabaseLocation::resolve(args(&["--profile"]), &data).is_err());
+        assert!(DatabaseLocation::resolve(args(&["--profile", "../etc"]), &data).is_err());
+        assert!(DatabaseLocation::resolve(args(&[]), env(&[])).is_err());
+    }
+
+    #[test]
+    fn test_platform_data_dir() {
+        let vars = env(&[("HOME", "/home/me"), ("APPDATA", "C:\\Users\\me\\AppData\\Roaming")]);
+        let dir = data_dir(&vars).unwrap();
+        assert!(dir.ends_with("zakaz"));
+        if cfg!(not(any(windows, target_os = "macos"))) {
+            assert_eq!(dir, PathBuf::from("/home/me/.local/share/zakaz"));
+            let xdg = env(&[("HOME", "/home/me"), ("XDG_DATA_HOME", "/xdg")]);
+            assert_eq!(data_dir(&xdg).unwrap(), PathBuf::from("/xdg/zakaz"));
+        }
+    }
+
+    #[test]
+    fn test_adopt_legacy_database() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let legacy = temp_dir.path().join("zakaz.db");
+        let path = temp_dir.path().join("data").join("zakaz.db");
+        std::fs::write(&legacy, b"old").unwrap();
+
+        adopt_legacy_database(&legacy, &path);
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+
+        // An existing database is never replaced
+        std::fs::write(&legacy, b"newer").unwrap();
+        adopt_legacy_database(&legacy, &path);
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+    }
+}
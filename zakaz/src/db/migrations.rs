@@ -0,0 +1,889 @@
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
+use sqlx::{Connection, Row};
+use crate::{err, inf};
+use super::backup::{database_file, vacuum_into};
+use super::schema::create_schema;
+use super::search;
+
+/// One step of the schema's history. Versions only go up; a released step is never edited,
+/// later changes get a new one.
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub step: MigrationStep,
+}
+
+pub enum MigrationStep {
+    /// The tables that predate versioning, from `create_schema`
+    Baseline,
+    /// Statements run in order, in the migration's transaction
+    Sql(&'static [&'static str]),
+    /// Statements that copy a table into a new one, run like `Sql` but with foreign keys off so
+    /// dropping the old table doesn't cascade to the rows referencing it, and with legacy renames
+    /// so triggers elsewhere that name the table aren't checked while it is gone. The references
+    /// are checked before the migration commits.
+    Rebuild(&'static [&'static str]),
+    /// A `Rebuild` of the templates table, after which the search triggers dropped with the old
+    /// table are recreated
+    RebuildTemplates(&'static [&'static str]),
+    /// The full-text search index and its triggers, from `search::create_index`
+    SearchIndex,
+}
+
+/// Every migration, oldest first
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Baseline schema",
+        step: MigrationStep::Baseline,
+    },
+    Migration {
+        version: 2,
+        description: "Template notes and activation time",
+        step: MigrationStep::Sql(&[
+            "ALTER TABLE templates ADD COLUMN notes TEXT",
+            "ALTER TABLE templates ADD COLUMN activated_at TEXT",
+        ]),
+    },
+    Migration {
+        version: 3,
+        description: "Template revisions",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE template_revisions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                template_id TEXT NOT NULL,
+                revision INTEGER NOT NULL,
+                changes TEXT NOT NULL,
+                changed_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE (template_id, revision),
+                FOREIGN KEY (template_id) REFERENCES templates(id) ON DELETE CASCADE
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 4,
+        description: "Template baskets",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE baskets (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+            r#"
+            CREATE TABLE basket_templates (
+                basket_id TEXT NOT NULL,
+                template_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                PRIMARY KEY (basket_id, template_id),
+                FOREIGN KEY (basket_id) REFERENCES baskets(id) ON DELETE CASCADE,
+                FOREIGN KEY (template_id) REFERENCES templates(id) ON DELETE CASCADE
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 5,
+        description: "Scheduled activation",
+        step: MigrationStep::Sql(&["ALTER TABLE templates ADD COLUMN activate_at TEXT"]),
+    },
+    Migration {
+        version: 6,
+        description: "Entry order types",
+        step: MigrationStep::Sql(&[
+            "ALTER TABLE templates ADD COLUMN entry_type TEXT NOT NULL DEFAULT 'LMT'",
+            "ALTER TABLE templates ADD COLUMN entry_limit_offset REAL",
+        ]),
+    },
+    Migration {
+        version: 7,
+        description: "Good-till-date expiry",
+        step: MigrationStep::Sql(&["ALTER TABLE templates ADD COLUMN time_in_force_expires_at TEXT"]),
+    },
+    Migration {
+        version: 8,
+        description: "Outside regular trading hours",
+        step: MigrationStep::Sql(&["ALTER TABLE templates ADD COLUMN outside_rth BOOLEAN NOT NULL DEFAULT 0"]),
+    },
+    Migration {
+        version: 9,
+        description: "Exit tranches",
+        step: MigrationStep::Sql(&[
+            "ALTER TABLE templates ADD COLUMN exit_tranches TEXT",
+            "ALTER TABLE active_orders ADD COLUMN ib_tranche_order_ids TEXT",
+        ]),
+    },
+    Migration {
+        version: 10,
+        description: "OCO entries",
+        step: MigrationStep::Sql(&["ALTER TABLE templates ADD COLUMN oca_group TEXT"]),
+    },
+    Migration {
+        version: 11,
+        description: "Template archiving",
+        step: MigrationStep::Sql(&["ALTER TABLE templates ADD COLUMN archived_at TEXT"]),
+    },
+    Migration {
+        version: 12,
+        description: "Entry fills",
+        step: MigrationStep::Sql(&[
+            "ALTER TABLE templates ADD COLUMN filled_quantity REAL NOT NULL DEFAULT 0",
+            "ALTER TABLE templates ADD COLUMN avg_fill_price REAL",
+            "ALTER TABLE templates ADD COLUMN filled_at TEXT",
+        ]),
+    },
+    Migration {
+        version: 13,
+        description: "Template expiry",
+        step: MigrationStep::Sql(&["ALTER TABLE templates ADD COLUMN expires_at TEXT"]),
+    },
+    Migration {
+        version: 14,
+        description: "Archived, partially filled and expired template statuses",
+        // SQLite can't alter a CHECK constraint, so the table is copied into a new one
+        step: MigrationStep::Rebuild(&[
+            r#"
+            CREATE TABLE templates_new (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                symbol TEXT NOT NULL,
+                side TEXT NOT NULL CHECK (side IN ('Buy', 'Sell')),
+                quantity INTEGER NOT NULL,
+                limit_price REAL NOT NULL,
+                stop_price REAL NOT NULL,
+                technical_stop_price REAL,
+                time_in_force TEXT NOT NULL DEFAULT 'GTC',
+                model TEXT NOT NULL CHECK (model IN ('Breakout', 'FalseBreakout', 'Bounce', 'Continuation')),
+                status TEXT NOT NULL CHECK (status IN ('Template', 'Active', 'PartiallyFilled', 'Filled', 'Cancelled', 'Archived', 'Expired')),
+                is_read_only BOOLEAN NOT NULL DEFAULT 0,
+                risk_per_trade REAL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                notes TEXT,
+                activated_at TEXT,
+                activate_at TEXT,
+                entry_type TEXT NOT NULL DEFAULT 'LMT',
+                entry_limit_offset REAL,
+                time_in_force_expires_at TEXT,
+                outside_rth BOOLEAN NOT NULL DEFAULT 0,
+                exit_tranches TEXT,
+                oca_group TEXT,
+                archived_at TEXT,
+                filled_quantity REAL NOT NULL DEFAULT 0,
+                avg_fill_price REAL,
+                filled_at TEXT,
+                expires_at TEXT
+            )
+            "#,
+            r#"
+            INSERT INTO templates_new
+            SELECT id, name, symbol, side, quantity, limit_price, stop_price, technical_stop_price, time_in_force, model,
+                status, is_read_only, risk_per_trade, created_at, updated_at, notes, activated_at, activate_at, entry_type,
+                entry_limit_offset, time_in_force_expires_at, outside_rth, exit_tranches, oca_group, archived_at,
+                filled_quantity, avg_fill_price, filled_at, expires_at
+            FROM templates
+            "#,
+            "DROP TABLE templates",
+            "ALTER TABLE templates_new RENAME TO templates",
+            // Dropped with the old table
+            "CREATE INDEX idx_templates_symbol ON templates(symbol)",
+            "CREATE INDEX idx_templates_status ON templates(status)",
+            r#"
+            CREATE TRIGGER update_templates_timestamp
+            AFTER UPDATE ON templates
+            BEGIN
+                UPDATE templates SET updated_at = datetime('now') WHERE id = NEW.id;
+            END
+            "#,
+        ]),
+    },
+    Migration {
+        version: 15,
+        description: "Order audit trail",
+        step: MigrationStep::Sql(&[
+            // No foreign key: the trail must outlive deleted templates
+            r#"
+            CREATE TABLE order_audit (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                template_id TEXT NOT NULL,
+                action TEXT NOT NULL,
+                source TEXT NOT NULL CHECK (source IN ('UI', 'Scheduler', 'Reconciliation')),
+                account_type TEXT,
+                before_state TEXT,
+                after_state TEXT,
+                recorded_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+            "CREATE INDEX idx_order_audit_template_id ON order_audit(template_id)",
+        ]),
+    },
+    Migration {
+        version: 16,
+        description: "Sectors",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE sectors (
+                name TEXT PRIMARY KEY,
+                max_notional REAL,
+                max_risk REAL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+            r#"
+            CREATE TABLE symbol_sectors (
+                symbol TEXT PRIMARY KEY,
+                sector TEXT NOT NULL,
+                FOREIGN KEY (sector) REFERENCES sectors(name) ON DELETE CASCADE
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 17,
+        description: "Sizing strategies and trade outcomes",
+        step: MigrationStep::Sql(&[
+            "ALTER TABLE templates ADD COLUMN sizing_strategy TEXT",
+            r#"
+            CREATE TABLE trade_outcomes (
+                template_id TEXT PRIMARY KEY,
+                symbol TEXT NOT NULL,
+                model TEXT NOT NULL,
+                realized_pnl REAL NOT NULL,
+                initial_risk REAL NOT NULL,
+                r_multiple REAL NOT NULL,
+                closed_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 18,
+        description: "Stop rules",
+        step: MigrationStep::Sql(&["ALTER TABLE templates ADD COLUMN stop_rules TEXT"]),
+    },
+    Migration {
+        version: 19,
+        description: "Pre-trade checklists",
+        step: MigrationStep::Sql(&[
+            "ALTER TABLE templates ADD COLUMN checklist TEXT",
+            // A NULL model asks every model
+            r#"
+            CREATE TABLE checklist_items (
+                id TEXT PRIMARY KEY,
+                model TEXT,
+                text TEXT NOT NULL,
+                position INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 20,
+        description: "Short availability",
+        step: MigrationStep::Sql(&[
+            "ALTER TABLE templates ADD COLUMN short_availability TEXT CHECK (short_availability IN ('Easy', 'HardToBorrow', 'Unavailable'))",
+        ]),
+    },
+    Migration {
+        version: 21,
+        description: "Template currency",
+        step: MigrationStep::Sql(&["ALTER TABLE templates ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD'"]),
+    },
+    Migration {
+        version: 22,
+        description: "ATR cache",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE atr_cache (
+                symbol TEXT NOT NULL,
+                period_days INTEGER NOT NULL,
+                method TEXT NOT NULL,
+                smoothing TEXT NOT NULL,
+                applied_method TEXT NOT NULL,
+                method_rationale TEXT,
+                filtered_atr REAL NOT NULL,
+                regular_atr REAL NOT NULL,
+                total_bars INTEGER NOT NULL,
+                used_bars INTEGER NOT NULL,
+                excluded_bars INTEGER NOT NULL,
+                mean_range REAL NOT NULL,
+                median_range REAL NOT NULL,
+                std_dev_range REAL NOT NULL,
+                q1_range REAL NOT NULL,
+                q3_range REAL NOT NULL,
+                lower_bound REAL NOT NULL,
+                upper_bound REAL NOT NULL,
+                confidence_score REAL NOT NULL,
+                is_valid BOOLEAN NOT NULL,
+                calculated_at TEXT NOT NULL,
+                PRIMARY KEY (symbol, period_days, method, smoothing)
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 23,
+        description: "ATR analyses",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE atr_analyses (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                symbol TEXT NOT NULL,
+                calculated_at TEXT NOT NULL,
+                analysis TEXT NOT NULL
+            )
+            "#,
+            "CREATE INDEX idx_atr_analyses_symbol ON atr_analyses(symbol, calculated_at)",
+        ]),
+    },
+    Migration {
+        version: 24,
+        description: "Watchlist",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE watchlist (
+                symbol TEXT PRIMARY KEY,
+                note TEXT,
+                added_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 25,
+        description: "Earnings dates",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE earnings_dates (
+                symbol TEXT NOT NULL,
+                date TEXT NOT NULL,
+                timing TEXT NOT NULL,
+                PRIMARY KEY (symbol, date)
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 26,
+        description: "Executions",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE executions (
+                exec_id TEXT PRIMARY KEY,
+                order_id INTEGER NOT NULL,
+                template_id TEXT,
+                symbol TEXT NOT NULL,
+                side TEXT NOT NULL,
+                quantity REAL NOT NULL,
+                price REAL NOT NULL,
+                executed_at TEXT NOT NULL
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 27,
+        description: "Chart drawings",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE chart_drawings (
+                id TEXT PRIMARY KEY,
+                symbol TEXT NOT NULL,
+                shape TEXT NOT NULL,
+                color TEXT,
+                created_at TEXT NOT NULL
+            )
+            "#,
+            "CREATE INDEX idx_chart_drawings_symbol ON chart_drawings(symbol)",
+        ]),
+    },
+    Migration {
+        version: 28,
+        description: "Chart themes",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE themes (
+                name TEXT PRIMARY KEY,
+                theme TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 29,
+        description: "Execution commissions",
+        step: MigrationStep::Sql(&["ALTER TABLE executions ADD COLUMN commission REAL"]),
+    },
+    Migration {
+        version: 30,
+        description: "Round-trip trades",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE trades (
+                id TEXT PRIMARY KEY,
+                symbol TEXT NOT NULL,
+                side TEXT NOT NULL,
+                template_id TEXT,
+                model TEXT,
+                quantity REAL NOT NULL,
+                opened_at TEXT NOT NULL,
+                closed_at TEXT,
+                avg_entry REAL NOT NULL,
+                avg_exit REAL,
+                realized_pnl REAL NOT NULL,
+                commission REAL NOT NULL,
+                r_multiple REAL
+            )
+            "#,
+            "CREATE INDEX idx_trades_opened_at ON trades (opened_at)",
+        ]),
+    },
+    Migration {
+        version: 31,
+        description: "Historical bar cache",
+        step: MigrationStep::Sql(&[
+            // Times are Unix seconds so ranges compare numerically
+            r#"
+            CREATE TABLE bars (
+                symbol TEXT NOT NULL,
+                bar_size TEXT NOT NULL,
+                use_rth INTEGER NOT NULL,
+                timestamp INTEGER NOT NULL,
+                open REAL NOT NULL,
+                high REAL NOT NULL,
+                low REAL NOT NULL,
+                close REAL NOT NULL,
+                volume INTEGER NOT NULL,
+                wap REAL NOT NULL,
+                count INTEGER NOT NULL,
+                PRIMARY KEY (symbol, bar_size, use_rth, timestamp)
+            )
+            "#,
+            r#"
+            CREATE TABLE bar_coverage (
+                symbol TEXT NOT NULL,
+                bar_size TEXT NOT NULL,
+                use_rth INTEGER NOT NULL,
+                covered_from INTEGER NOT NULL,
+                covered_to INTEGER NOT NULL,
+                PRIMARY KEY (symbol, bar_size, use_rth)
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 32,
+        description: "Named watchlists",
+        step: MigrationStep::Sql(&[
+            r#"
+            CREATE TABLE watchlists (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                position INTEGER NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+            r#"
+            CREATE TABLE watchlist_symbols (
+                watchlist_id TEXT NOT NULL REFERENCES watchlists(id) ON DELETE CASCADE,
+                symbol TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                note TEXT,
+                added_at TEXT NOT NULL DEFAULT (datetime('now')),
+                PRIMARY KEY (watchlist_id, symbol)
+            )
+            "#,
+            // The single watchlist becomes the default one
+            "INSERT INTO watchlists (id, name, position) VALUES ('default', 'Watchlist', 0)",
+            r#"
+            INSERT INTO watchlist_symbols (watchlist_id, symbol, position, note, added_at)
+            SELECT 'default', symbol, ROW_NUMBER() OVER (ORDER BY symbol) - 1, note, added_at FROM watchlist
+            "#,
+            "DROP TABLE watchlist",
+        ]),
+    },
+    Migration {
+        version: 33,
+        description: "Template soft delete",
+        step: MigrationStep::Sql(&[
+            "ALTER TABLE templates ADD COLUMN deleted_at TEXT",
+            "CREATE INDEX idx_templates_deleted_at ON templates (deleted_at)",
+        ]),
+    },
+    Migration {
+        version: 34,
+        description: "Full-text search",
+        step: MigrationStep::SearchIndex,
+    },
+    Migration {
+        version: 35,
+        description: "Per-symbol settings",
+        step: MigrationStep::Sql(&[
+            // The sector stays in symbol_sectors, where the exposure caps read it
+            r#"
+            CREATE TABLE symbol_settings (
+                symbol TEXT PRIMARY KEY,
+                risk_per_trade REAL,
+                atr_period INTEGER,
+                tick_size REAL,
+                notes TEXT,
+                updated_at TEXT NOT NULL
+            )
+            "#,
+        ]),
+    },
+    Migration {
+        version: 36,
+        description: "Closed template status",
+        step: MigrationStep::RebuildTemplates(&[
+            r#"
+            CREATE TABLE templates_new (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                symbol TEXT NOT NULL,
+                side TEXT NOT NULL CHECK (side IN ('Buy', 'Sell')),
+                quantity INTEGER NOT NULL,
+                limit_price REAL NOT NULL,
+                stop_price REAL NOT NULL,
+                technical_stop_price REAL,
+                time_in_force TEXT NOT NULL DEFAULT 'GTC',
+                model TEXT NOT NULL CHECK (model IN ('Breakout', 'FalseBreakout', 'Bounce', 'Continuation')),
+                status TEXT NOT NULL CHECK (status IN ('Template', 'Active', 'PartiallyFilled', 'Filled', 'Closed', 'Cancelled', 'Archived', 'Expired')),
+                is_read_only BOOLEAN NOT NULL DEFAULT 0,
+                risk_per_trade REAL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                notes TEXT,
+                activated_at TEXT,
+                activate_at TEXT,
+                entry_type TEXT NOT NULL DEFAULT 'LMT',
+                entry_limit_offset REAL,
+                time_in_force_expires_at TEXT,
+                outside_rth BOOLEAN NOT NULL DEFAULT 0,
+                exit_tranches TEXT,
+                oca_group TEXT,
+                archived_at TEXT,
+                filled_quantity REAL NOT NULL DEFAULT 0,
+                avg_fill_price REAL,
+                filled_at TEXT,
+                expires_at TEXT,
+                sizing_strategy TEXT,
+                stop_rules TEXT,
+                checklist TEXT,
+                short_availability TEXT CHECK (short_availability IN ('Easy', 'HardToBorrow', 'Unavailable')),
+                currency TEXT NOT NULL DEFAULT 'USD',
+                deleted_at TEXT
+            )
+            "#,
+            r#"
+            INSERT INTO templates_new
+            SELECT id, name, symbol, side, quantity, limit_price, stop_price, technical_stop_price, time_in_force, model,
+                status, is_read_only, risk_per_trade, created_at, updated_at, notes, activated_at, activate_at, entry_type,
+                entry_limit_offset, time_in_force_expires_at, outside_rth, exit_tranches, oca_group, archived_at,
+                filled_quantity, avg_fill_price, filled_at, expires_at, sizing_strategy, stop_rules, checklist,
+                short_availability, currency, deleted_at
+            FROM templates
+            "#,
+            "DROP TABLE templates",
+            "ALTER TABLE templates_new RENAME TO templates",
+            "CREATE INDEX idx_templates_symbol ON templates(symbol)",
+            "CREATE INDEX idx_templates_status ON templates(status)",
+            "CREATE INDEX idx_templates_deleted_at ON templates (deleted_at)",
+            r#"
+            CREATE TRIGGER update_templates_timestamp
+            AFTER UPDATE ON templates
+            BEGIN
+                UPDATE templates SET updated_at = datetime('now') WHERE id = NEW.id;
+            END
+            "#,
+        ]),
+    },
+];
+
+/// The version a database is at once every migration has run
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Bring the database up to the latest schema version. A database that already has tables
+/// is backed up next to its file before the first pending step runs.
+pub async fn run(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    run_migrations(pool, MIGRATIONS).await
+}
+
+async fn run_migrations(pool: &SqlitePool, migrations: &[Migration]) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    let current = current_version(pool).await?;
+    let latest = migrations.last().map_or(0, |m| m.version);
+    if current > latest {
+        return Err(sqlx::Error::Protocol(format!(
+            "Database schema is at version {}, newer than this build's {}",
+            current, latest
+        )));
+    }
+
+    let pending: Vec<&Migration> = migrations.iter().filter(|m| m.version > current).collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let backup = if has_tables(pool).await? {
+        backup(pool, current).await?
+    } else {
+        None
+    };
+
+    for migration in pending {
+        inf!("Migrating database to version {}: {}", migration.version, migration.description);
+        if let Err(e) = apply(pool, migration).await {
+            match &backup {
+                Some(path) => err!("Migration to version {} failed, the database before migrating is at {}: {}", migration.version, path, e),
+                None => err!("Migration to version {} failed: {}", migration.version, e),
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Highest version recorded, 0 for a database from before versioning or a new one
+pub async fn current_version(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query("SELECT COALESCE(MAX(version), 0) AS version FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(row.get("version"))
+}
+
+async fn has_tables(pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name NOT IN ('schema_version', 'sqlite_sequence')",
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.get::<i64, _>("count") > 0)
+}
+
+/// Copy the database to `<file>.v<version>.bak`, replacing an older copy. In-memory databases
+/// have no file and aren't backed up.
+async fn backup(pool: &SqlitePool, version: i64) -> Result<Option<String>, sqlx::Error> {
+    let Some(file) = database_file(pool).await? else {
+        return Ok(None);
+    };
+    let path = format!("{}.v{}.bak", file.display(), version);
+    vacuum_into(pool, std::path::Path::new(&path)).await?;
+    inf!("Backed up the database to {}", path);
+    Ok(Some(path))
+}
+
+/// Run one migration and record it, all or nothing
+async fn apply(pool: &SqlitePool, migration: &Migration) -> Result<(), sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    if !matches!(migration.step, MigrationStep::Rebuild(_) | MigrationStep::RebuildTemplates(_)) {
+        return apply_on(&mut conn, migration).await;
+    }
+
+    // The foreign keys pragma does nothing inside a transaction, so both are set around it
+    let foreign_keys: bool = sqlx::query_scalar("PRAGMA foreign_keys").fetch_one(&mut *conn).await?;
+    sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await?;
+    sqlx::query("PRAGMA legacy_alter_table = ON").execute(&mut *conn).await?;
+    let result = apply_on(&mut conn, migration).await;
+    sqlx::query("PRAGMA legacy_alter_table = OFF").execute(&mut *conn).await?;
+    if foreign_keys {
+        sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await?;
+    }
+    result
+}
+
+async fn apply_on(conn: &mut SqliteConnection, migration: &Migration) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+    run_step(&mut tx, &migration.step).await?;
+    sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)")
+        .bind(migration.version)
+        .bind(migration.description)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+async fn run_step(conn: &mut SqliteConnection, step: &MigrationStep) -> Result<(), sqlx::Error> {
+    match step {
+        MigrationStep::Baseline => create_schema(conn).await,
+        MigrationStep::SearchIndex => search::create_index(conn).await,
+        MigrationStep::Sql(statements) => {
+            for statement in *statements {
+                sqlx::query(statement).execute(&mut *conn).await?;
+            }
+            Ok(())
+        }
+        MigrationStep::Rebuild(statements) => rebuild(conn, statements).await,
+        MigrationStep::RebuildTemplates(statements) => {
+            rebuild(conn, statements).await?;
+            search::create_template_triggers(conn).await
+        }
+    }
+}
+
+async fn rebuild(conn: &mut SqliteConnection, statements: &[&str]) -> Result<(), sqlx::Error> {
+    for statement in statements {
+        sqlx::query(statement).execute(&mut *conn).await?;
+    }
+    let broken = sqlx::query("PRAGMA foreign_key_check").fetch_all(&mut *conn).await?;
+    if !broken.is_empty() {
+        return Err(sqlx::Error::Protocol(format!("Rebuild left {} broken foreign key reference(s)", broken.len())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::database::Database;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+    use std::path::Path;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    async fn open(path: &Path) -> SqlitePool {
+        let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display()))
+            .unwrap()
+            .create_if_missing(true);
+        SqlitePoolOptions::new().max_connections(1).connect_with(options).await.unwrap()
+    }
+
+    async fn table_exists(pool: &SqlitePool, name: &str) -> bool {
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind(name)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        row.get::<i64, _>("count") > 0
+    }
+
+    #[tokio::test]
+    async fn test_new_database_reaches_latest_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("new.db");
+        let pool = open(&path).await;
+
+        run(&pool).await.unwrap();
+        assert_eq!(current_version(&pool).await.unwrap(), latest_version());
+        assert!(table_exists(&pool, "templates").await);
+        // Nothing to lose, so nothing is backed up
+        assert!(std::fs::read_dir(temp_dir.path()).unwrap().all(|e| !e.unwrap().file_name().to_string_lossy().ends_with(".bak")));
+
+        // Running again is a no-op
+        run(&pool).await.unwrap();
+        assert_eq!(current_version(&pool).await.unwrap(), latest_version());
+    }
+
+    #[tokio::test]
+    async fn test_unversioned_database_is_backed_up_and_versioned() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("old.db");
+        let pool = open(&path).await;
+        // A database as the app left it before versioning
+        let mut conn = pool.acquire().await.unwrap();
+        create_schema(&mut conn).await.unwrap();
+        drop(conn);
+        sqlx::query("INSERT INTO settings (key, value) VALUES ('kept', 'yes')").execute(&pool).await.unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO templates (id, name, symbol, side, quantity, limit_price, stop_price, model, status)
+            VALUES ('t1', 'Old', 'AAPL', 'Buy', 100, 150.0, 145.0, 'Breakout', 'Active')
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO active_orders (template_id, ib_order_id, ib_stop_order_id) VALUES ('t1', 7, 8)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        run(&pool).await.unwrap();
+        assert_eq!(current_version(&pool).await.unwrap(), latest_version());
+        // Rebuilding the templates table kept the orders referencing it
+        let orders: Vec<(String, i64)> = sqlx::query_as("SELECT template_id, ib_order_id FROM active_orders")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(orders, [("t1".to_string(), 7)]);
+        pool.close().await;
+
+        // Every column added since is there for the app to read
+        let db = Database::open(&format!("sqlite:{}", path.display())).await.unwrap();
+        let templates = db.lock().await.get_all_templates().await.unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!((templates[0].currency.as_str(), templates[0].filled_quantity), ("USD", 0.0));
+
+        let backup = open(&temp_dir.path().join("old.db.v0.bak")).await;
+        let row = sqlx::query("SELECT value FROM settings WHERE key = 'kept'").fetch_one(&backup).await.unwrap();
+        assert_eq!(row.get::<String, _>("value"), "yes");
+    }
+
+    #[tokio::test]
+    async fn test_watchlist_moves_into_default() {
+        let temp_dir = TempDir::new().unwrap();
+        let pool = open(&temp_dir.path().join("watchlist.db")).await;
+        let single = MIGRATIONS.iter().position(|m| m.description == "Watchlist").unwrap();
+        run_migrations(&pool, &MIGRATIONS[..=single]).await.unwrap();
+        sqlx::query("INSERT INTO watchlist (symbol, note) VALUES ('NVDA', 'Base'), ('AMD', NULL)").execute(&pool).await.unwrap();
+
+        run(&pool).await.unwrap();
+        let moved: Vec<(String, i64)> = sqlx::query_as("SELECT symbol, position FROM watchlist_symbols WHERE watchlist_id = 'default' ORDER BY position")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(moved, [("AMD".to_string(), 0), ("NVDA".to_string(), 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_migration_keeps_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("failing.db");
+        let pool = open(&path).await;
+        run(&pool).await.unwrap();
+
+        // Only the pending step matters to the run, so the earlier ones can be left out
+        let broken = [Migration {
+            version: latest_version() + 1,
+            description: "Broken",
+            step: MigrationStep::Sql(&["CREATE TABLE half_done (id INTEGER)", "NOT SQL"]),
+        }];
+        assert!(run_migrations(&pool, &broken).await.is_err());
+        assert_eq!(current_version(&pool).await.unwrap(), latest_version());
+        assert!(!table_exists(&pool, "half_done").await);
+        assert!(temp_dir.path().join(format!("failing.db.v{}.bak", latest_version())).exists());
+    }
+
+    #[tokio::test]
+    async fn test_newer_database_is_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let pool = open(&temp_dir.path().join("newer.db")).await;
+        run(&pool).await.unwrap();
+        sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, 'From the future', '')")
+            .bind(latest_version() + 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(run(&pool).await.is_err());
+    }
+}
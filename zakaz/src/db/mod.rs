@@ -1,7 +1,12 @@
 pub mod schema;
+pub mod migrations;
+pub mod backup;
+pub mod export;
+pub mod import;
+pub mod location;
 pub mod database;
 pub mod models;
+pub mod search;
+pub mod settings;
 
-// TODO: Uncomment when these are used
-// pub use database::Database;
-// pub use models::*;
\ No newline at end of file
+pub use database::Database;
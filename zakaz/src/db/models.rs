@@ -1,24 +1,53 @@
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Serialize, Deserialize};
 use sqlx::FromRow;
 use uuid::Uuid;
-use crate::ib::types::{OrderSide, TradingModel};
+use crate::charts::ChartTheme;
+use crate::charts::types::{Drawing, DrawingShape};
+use crate::ib::earnings::{EarningsEvent, EarningsTiming};
+use crate::ib::fx::DEFAULT_CURRENCY;
+use crate::ib::journal::Trade;
+use crate::ib::outcomes::TradeOutcome;
+use crate::ib::types::{
+    ATRResult, AtrSmoothing, AuditAction, AuditEntry, AuditSource, ChecklistAnswer, ChecklistItem, EntryType, ExitTranche, Fill, HistoricalBar, OrderSide, OrderTemplate, OrderTemplateStatus, Sector, ShortAvailability, SizingStrategy, StopRule, SymbolSettings, TemplateBasket, TemplateFieldChange, TemplateRevision, Watchlist, WatchlistEntry,
+    OutlierMethod, TimeInForce, TradingModel,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
 pub struct DbOrderTemplate {
     pub id: String,
     pub name: String,
     pub symbol: String,
+    pub currency: String,
     pub side: String, // Will be converted to/from OrderSide
     pub quantity: i64,
     pub limit_price: f64,
     pub stop_price: f64,
     pub technical_stop_price: Option<f64>,
     pub time_in_force: String,
+    pub time_in_force_expires_at: Option<String>, // GTD expiry
+    pub entry_type: String,
+    pub entry_limit_offset: Option<f64>,
+    pub outside_rth: bool,
+    pub exit_tranches: Option<String>, // JSON-encoded Vec<ExitTranche>
+    pub oca_group: Option<String>,
+    pub filled_quantity: f64,
+    pub avg_fill_price: Option<f64>,
+    pub filled_at: Option<String>,
     pub model: String, // Will be converted to/from TradingModel
     pub status: String, // Will be converted to/from OrderStatus
     pub is_read_only: bool,
     pub risk_per_trade: Option<f64>,
+    pub sizing_strategy: Option<String>, // JSON-encoded SizingStrategy; NULL is fixed risk
+    pub stop_rules: Option<String>, // JSON-encoded Vec<StopRule>
+    pub checklist: Option<String>, // JSON-encoded Vec<ChecklistAnswer>
+    pub short_availability: Option<String>, // ShortAvailability variant name
+    pub notes: Option<String>,
+    pub activated_at: Option<String>,
+    pub activate_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub archived_at: Option<String>,
+    pub deleted_at: Option<String>, // Soft-deleted; purged after `purge_deleted_templates_days`
     pub created_at: String,
     pub updated_at: String,
 }
@@ -27,8 +56,12 @@ pub struct DbOrderTemplate {
 pub enum OrderStatus {
     Template,   // Not yet submitted to IB
     Active,     // Submitted to IB
+    PartiallyFilled, // Entry partly executed
     Filled,     // Order executed
+    Closed,     // Position exited
     Cancelled,  // Order cancelled
+    Archived,   // Retired template, excluded from default queries
+    Expired,    // Never activated before expires_at
 }
 
 impl OrderStatus {
@@ -36,8 +69,12 @@ impl OrderStatus {
         match self {
             OrderStatus::Template => "Template",
             OrderStatus::Active => "Active",
+            OrderStatus::PartiallyFilled => "PartiallyFilled",
             OrderStatus::Filled => "Filled",
+            OrderStatus::Closed => "Closed",
             OrderStatus::Cancelled => "Cancelled",
+            OrderStatus::Archived => "Archived",
+            OrderStatus::Expired => "Expired",
         }
     }
 
@@ -45,11 +82,45 @@ impl OrderStatus {
         match s {
             "Template" => Some(OrderStatus::Template),
             "Active" => Some(OrderStatus::Active),
+            "PartiallyFilled" => Some(OrderStatus::PartiallyFilled),
             "Filled" => Some(OrderStatus::Filled),
+            "Closed" => Some(OrderStatus::Closed),
             "Cancelled" => Some(OrderStatus::Cancelled),
+            "Archived" => Some(OrderStatus::Archived),
+            "Expired" => Some(OrderStatus::Expired),
             _ => None,
         }
     }
+
+    /// Map the in-memory template lifecycle onto the persisted status.
+    /// Transient states collapse to the state they settle back into.
+    pub fn from_template_status(status: OrderTemplateStatus) -> Self {
+        match status {
+            OrderTemplateStatus::Inactive
+            | OrderTemplateStatus::Activating
+            | OrderTemplateStatus::Failed => OrderStatus::Template,
+            OrderTemplateStatus::Active
+            | OrderTemplateStatus::Deactivating => OrderStatus::Active,
+            OrderTemplateStatus::PartiallyFilled => OrderStatus::PartiallyFilled,
+            OrderTemplateStatus::Filled => OrderStatus::Filled,
+            OrderTemplateStatus::Closed => OrderStatus::Closed,
+            OrderTemplateStatus::Archived => OrderStatus::Archived,
+            OrderTemplateStatus::Expired => OrderStatus::Expired,
+        }
+    }
+
+    pub fn to_template_status(self) -> OrderTemplateStatus {
+        match self {
+            OrderStatus::Active => OrderTemplateStatus::Active,
+            OrderStatus::PartiallyFilled => OrderTemplateStatus::PartiallyFilled,
+            OrderStatus::Filled => OrderTemplateStatus::Filled,
+            OrderStatus::Closed => OrderTemplateStatus::Closed,
+            OrderStatus::Template
+            | OrderStatus::Cancelled => OrderTemplateStatus::Inactive,
+            OrderStatus::Archived => OrderTemplateStatus::Archived,
+            OrderStatus::Expired => OrderTemplateStatus::Expired,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -57,9 +128,628 @@ pub struct DbActiveOrder {
     pub template_id: String,
     pub ib_order_id: i64,
     pub ib_stop_order_id: Option<i64>,
+    pub ib_tranche_order_ids: Option<String>, // JSON-encoded Vec<i32>
     pub submitted_at: String,
 }
 
+impl DbActiveOrder {
+    pub fn encode_tranche_ids(ids: &[i32]) -> Option<String> {
+        (!ids.is_empty()).then(|| serde_json::to_string(ids).ok()).flatten()
+    }
+    
+    pub fn tranche_order_ids(&self) -> Vec<i32> {
+        self.ib_tranche_order_ids.as_deref()
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbTemplateRevision {
+    pub id: i64,
+    pub template_id: String,
+    pub revision: i64,
+    pub changes: String, // JSON-encoded Vec<TemplateFieldChange>
+    pub changed_at: String,
+}
+
+impl DbTemplateRevision {
+    pub fn to_revision(&self) -> Result<TemplateRevision, String> {
+        let changes: Vec<TemplateFieldChange> = serde_json::from_str(&self.changes)
+            .map_err(|e| format!("Revision {} of template {} is unreadable: {}", self.revision, self.template_id, e))?;
+        Ok(TemplateRevision {
+            template_id: self.template_id.clone(),
+            revision: self.revision,
+            changed_at: parse_db_timestamp(&self.changed_at).unwrap_or_else(Utc::now),
+            changes,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbAuditEntry {
+    pub id: i64,
+    pub template_id: String,
+    pub action: String,
+    pub source: String,
+    pub account_type: Option<String>,
+    pub before_state: Option<String>, // JSON-encoded OrderTemplate
+    pub after_state: Option<String>,  // JSON-encoded OrderTemplate
+    pub recorded_at: String,
+}
+
+impl DbAuditEntry {
+    pub fn new(
+        template_id: &str,
+        action: AuditAction,
+        source: AuditSource,
+        account_type: Option<String>,
+        before: Option<&OrderTemplate>,
+        after: Option<&OrderTemplate>,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            id: 0, // Assigned by the database
+            template_id: template_id.to_string(),
+            action: action.as_str().to_string(),
+            source: source.as_str().to_string(),
+            account_type,
+            before_state: before.map(serde_json::to_string).transpose()?,
+            after_state: after.map(serde_json::to_string).transpose()?,
+            recorded_at: Utc::now().to_rfc3339(),
+        })
+    }
+
+    pub fn to_entry(&self) -> Result<AuditEntry, String> {
+        let action = AuditAction::parse(&self.action)
+            .ok_or_else(|| format!("Audit entry {} has unknown action '{}'", self.id, self.action))?;
+        let source = AuditSource::parse(&self.source)
+            .ok_or_else(|| format!("Audit entry {} has unknown source '{}'", self.id, self.source))?;
+        let snapshot = |json: &Option<String>| -> Result<Option<OrderTemplate>, String> {
+            json.as_deref()
+                .map(serde_json::from_str)
+                .transpose()
+                .map_err(|e| format!("Audit entry {} has an unreadable snapshot: {}", self.id, e))
+        };
+        Ok(AuditEntry {
+            template_id: self.template_id.clone(),
+            action,
+            source,
+            account_type: self.account_type.clone(),
+            before: snapshot(&self.before_state)?,
+            after: snapshot(&self.after_state)?,
+            recorded_at: parse_db_timestamp(&self.recorded_at).unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbBasket {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+}
+
+impl DbBasket {
+    pub fn new(name: String) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn to_basket(&self, template_ids: Vec<String>) -> TemplateBasket {
+        TemplateBasket {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            template_ids,
+            created_at: parse_db_timestamp(&self.created_at).unwrap_or_else(Utc::now),
+        }
+    }
+}
+
+/// A closed trade's result, measured against the risk planned at entry
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbTradeOutcome {
+    pub template_id: String,
+    pub symbol: String,
+    pub model: String,
+    pub realized_pnl: f64,
+    pub initial_risk: f64,
+    pub r_multiple: f64,
+    pub closed_at: String,
+}
+
+impl DbTradeOutcome {
+    pub fn from_outcome(outcome: &TradeOutcome) -> Self {
+        Self {
+            template_id: outcome.template_id.clone(),
+            symbol: outcome.symbol.clone(),
+            model: model_to_str(outcome.model).to_string(),
+            realized_pnl: outcome.realized_pnl,
+            initial_risk: outcome.initial_risk,
+            r_multiple: outcome.r_multiple,
+            closed_at: outcome.closed_at.to_rfc3339(),
+        }
+    }
+    
+    pub fn to_outcome(&self) -> Result<TradeOutcome, String> {
+        Ok(TradeOutcome {
+            template_id: self.template_id.clone(),
+            symbol: self.symbol.clone(),
+            model: model_from_str(&self.model)
+                .ok_or_else(|| format!("Trade {} has unknown model '{}'", self.template_id, self.model))?,
+            realized_pnl: self.realized_pnl,
+            initial_risk: self.initial_risk,
+            r_multiple: self.r_multiple,
+            closed_at: parse_db_timestamp(&self.closed_at)
+                .ok_or_else(|| format!("Trade {} has invalid closed_at '{}'", self.template_id, self.closed_at))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbAtrCache {
+    pub symbol: String,
+    pub period_days: i64,
+    pub method: String,    // OutlierMethod::key
+    pub smoothing: String, // AtrSmoothing variant name
+    pub applied_method: String, // OutlierMethod::key of the filter Auto chose, or `method`
+    pub method_rationale: Option<String>,
+    pub filtered_atr: f64,
+    pub regular_atr: f64,
+    pub total_bars: i64,
+    pub used_bars: i64,
+    pub excluded_bars: i64,
+    pub mean_range: f64,
+    pub median_range: f64,
+    pub std_dev_range: f64,
+    pub q1_range: f64,
+    pub q3_range: f64,
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+    pub confidence_score: f64,
+    pub is_valid: bool,
+    pub calculated_at: String,
+}
+
+impl DbAtrCache {
+    pub fn from_result(result: &ATRResult) -> Self {
+        Self {
+            symbol: result.symbol.clone(),
+            period_days: result.period_days as i64,
+            method: result.method.key(),
+            smoothing: format!("{:?}", result.smoothing),
+            applied_method: result.applied_method.key(),
+            method_rationale: result.method_rationale.clone(),
+            filtered_atr: result.filtered_atr,
+            regular_atr: result.regular_atr,
+            total_bars: result.total_bars as i64,
+            used_bars: result.used_bars as i64,
+            excluded_bars: result.excluded_bars as i64,
+            mean_range: result.mean_range,
+            median_range: result.median_range,
+            std_dev_range: result.std_dev_range,
+            q1_range: result.q1_range,
+            q3_range: result.q3_range,
+            lower_bound: result.lower_bound,
+            upper_bound: result.upper_bound,
+            confidence_score: result.confidence_score,
+            is_valid: result.is_valid,
+            calculated_at: result.calculation_date.to_rfc3339(),
+        }
+    }
+
+    /// The cached figures; bar details aren't cached and come back empty
+    pub fn to_result(&self, method: OutlierMethod, smoothing: AtrSmoothing) -> Result<ATRResult, String> {
+        let mut result = ATRResult::new(self.symbol.clone(), self.period_days as usize, method, smoothing);
+        result.calculation_date = DateTime::parse_from_rfc3339(&self.calculated_at)
+            .map_err(|e| format!("Invalid calculated_at: {}", e))?
+            .with_timezone(&Utc);
+        result.applied_method = OutlierMethod::from_key(&self.applied_method)
+            .ok_or(format!("Invalid applied_method: {}", self.applied_method))?;
+        result.method_rationale = self.method_rationale.clone();
+        result.filtered_atr = self.filtered_atr;
+        result.regular_atr = self.regular_atr;
+        if result.regular_atr > 0.0 {
+            result.atr_difference = result.filtered_atr - result.regular_atr;
+            result.atr_difference_percent = result.atr_difference / result.regular_atr * 100.0;
+        }
+        result.total_bars = self.total_bars as usize;
+        result.used_bars = self.used_bars as usize;
+        result.excluded_bars = self.excluded_bars as usize;
+        if result.total_bars > 0 {
+            result.exclusion_rate = result.excluded_bars as f64 / result.total_bars as f64;
+        }
+        result.mean_range = self.mean_range;
+        result.median_range = self.median_range;
+        result.std_dev_range = self.std_dev_range;
+        result.q1_range = self.q1_range;
+        result.q3_range = self.q3_range;
+        result.iqr = self.q3_range - self.q1_range;
+        result.lower_bound = self.lower_bound;
+        result.upper_bound = self.upper_bound;
+        result.confidence_score = self.confidence_score;
+        result.is_valid = self.is_valid;
+        Ok(result)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbSector {
+    pub name: String,
+    pub max_notional: Option<f64>,
+    pub max_risk: Option<f64>,
+    pub created_at: String,
+}
+
+impl DbSector {
+    pub fn from_sector(sector: &Sector) -> Self {
+        Self {
+            name: sector.name.clone(),
+            max_notional: sector.max_notional,
+            max_risk: sector.max_risk,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn to_sector(&self, symbols: Vec<String>) -> Sector {
+        Sector {
+            name: self.name.clone(),
+            max_notional: self.max_notional,
+            max_risk: self.max_risk,
+            symbols,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct DbSymbolSettings {
+    pub symbol: String,
+    pub risk_per_trade: Option<f64>,
+    pub atr_period: Option<i64>,
+    pub tick_size: Option<f64>,
+    pub sector: Option<String>, // From symbol_sectors
+    pub notes: Option<String>,
+    pub updated_at: String,
+}
+
+impl DbSymbolSettings {
+    pub fn from_settings(settings: &SymbolSettings) -> Self {
+        Self {
+            symbol: settings.symbol.clone(),
+            risk_per_trade: settings.risk_per_trade,
+            atr_period: settings.atr_period.map(|p| p as i64),
+            tick_size: settings.tick_size,
+            sector: settings.sector.clone(),
+            notes: settings.notes.clone(),
+            updated_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn to_settings(&self) -> SymbolSettings {
+        SymbolSettings {
+            symbol: self.symbol.clone(),
+            risk_per_trade: self.risk_per_trade,
+            atr_period: self.atr_period.map(|p| p.max(0) as usize),
+            tick_size: self.tick_size,
+            sector: self.sector.clone(),
+            notes: self.notes.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbWatchlist {
+    pub id: String,
+    pub name: String,
+    pub position: i64,
+    pub created_at: String,
+}
+
+impl DbWatchlist {
+    pub fn new(name: String, position: i64) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            position,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn to_watchlist(&self, entries: &[DbWatchlistEntry]) -> Watchlist {
+        Watchlist {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            entries: entries.iter().map(|e| e.to_entry()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbWatchlistEntry {
+    pub symbol: String,
+    pub note: Option<String>,
+    pub added_at: String,
+}
+
+impl DbWatchlistEntry {
+    pub fn from_entry(entry: &WatchlistEntry) -> Self {
+        Self {
+            symbol: entry.symbol.clone(),
+            note: entry.note.clone(),
+            added_at: entry.added_at.to_rfc3339(),
+        }
+    }
+
+    pub fn to_entry(&self) -> WatchlistEntry {
+        WatchlistEntry {
+            symbol: self.symbol.clone(),
+            note: self.note.clone(),
+            added_at: parse_db_timestamp(&self.added_at).unwrap_or_else(Utc::now),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbEarningsEvent {
+    pub symbol: String,
+    pub date: String, // YYYY-MM-DD
+    pub timing: String,
+}
+
+impl DbEarningsEvent {
+    pub fn from_event(event: &EarningsEvent) -> Self {
+        Self {
+            symbol: event.symbol.clone(),
+            date: event.date.format("%Y-%m-%d").to_string(),
+            timing: event.timing.as_str().to_string(),
+        }
+    }
+
+    /// None when the stored date doesn't parse
+    pub fn to_event(&self) -> Option<EarningsEvent> {
+        Some(EarningsEvent {
+            symbol: self.symbol.clone(),
+            date: NaiveDate::parse_from_str(&self.date, "%Y-%m-%d").ok()?,
+            timing: EarningsTiming::parse(&self.timing),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbExecution {
+    pub exec_id: String,
+    pub order_id: i64,
+    pub template_id: Option<String>,
+    pub symbol: String,
+    pub side: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub executed_at: String,
+    pub commission: Option<f64>,
+}
+
+impl DbExecution {
+    pub fn from_fill(fill: &Fill) -> Self {
+        Self {
+            exec_id: fill.exec_id.clone(),
+            order_id: fill.order_id as i64,
+            template_id: fill.template_id.clone(),
+            symbol: fill.symbol.clone(),
+            side: side_to_str(fill.side).to_string(),
+            quantity: fill.quantity,
+            price: fill.price,
+            executed_at: fill.time.to_rfc3339(),
+            commission: fill.commission,
+        }
+    }
+
+    /// None when the stored time doesn't parse
+    pub fn to_fill(&self) -> Option<Fill> {
+        Some(Fill {
+            exec_id: self.exec_id.clone(),
+            order_id: self.order_id as i32,
+            template_id: self.template_id.clone(),
+            symbol: self.symbol.clone(),
+            side: if self.side == "Sell" { OrderSide::Short } else { OrderSide::Long },
+            quantity: self.quantity,
+            price: self.price,
+            time: DateTime::parse_from_rfc3339(&self.executed_at).ok()?.with_timezone(&Utc),
+            commission: self.commission,
+        })
+    }
+}
+
+/// A cached historical bar; `timestamp` is Unix seconds
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbBar {
+    pub timestamp: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: i64,
+    pub wap: f64,
+    pub count: i64,
+}
+
+impl DbBar {
+    pub fn from_bar(bar: &HistoricalBar) -> Self {
+        Self {
+            timestamp: bar.timestamp.timestamp(),
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            volume: bar.volume,
+            wap: bar.wap,
+            count: bar.count,
+        }
+    }
+
+    /// None when the timestamp is out of range
+    pub fn to_bar(&self) -> Option<HistoricalBar> {
+        Some(HistoricalBar {
+            timestamp: DateTime::from_timestamp(self.timestamp, 0)?,
+            open: self.open,
+            high: self.high,
+            low: self.low,
+            close: self.close,
+            volume: self.volume,
+            wap: self.wap,
+            count: self.count,
+        })
+    }
+}
+
+/// A round trip built from the executions table; see `journal::build_trades`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbTrade {
+    pub id: String,
+    pub symbol: String,
+    pub side: String,
+    pub template_id: Option<String>,
+    pub model: Option<String>,
+    pub quantity: f64,
+    pub opened_at: String,
+    pub closed_at: Option<String>,
+    pub avg_entry: f64,
+    pub avg_exit: Option<f64>,
+    pub realized_pnl: f64,
+    pub commission: f64,
+    pub r_multiple: Option<f64>,
+}
+
+impl DbTrade {
+    pub fn from_trade(trade: &Trade) -> Self {
+        Self {
+            id: trade.id.clone(),
+            symbol: trade.symbol.clone(),
+            side: side_to_str(trade.side).to_string(),
+            template_id: trade.template_id.clone(),
+            model: trade.model.map(|m| model_to_str(m).to_string()),
+            quantity: trade.quantity,
+            opened_at: trade.opened_at.to_rfc3339(),
+            closed_at: trade.closed_at.map(|t| t.to_rfc3339()),
+            avg_entry: trade.avg_entry,
+            avg_exit: trade.avg_exit,
+            realized_pnl: trade.realized_pnl,
+            commission: trade.commission,
+            r_multiple: trade.r_multiple,
+        }
+    }
+
+    pub fn to_trade(&self) -> Result<Trade, String> {
+        let time = |value: &str| parse_db_timestamp(value)
+            .ok_or_else(|| format!("Trade {} has invalid time '{}'", self.id, value));
+        Ok(Trade {
+            id: self.id.clone(),
+            symbol: self.symbol.clone(),
+            side: if self.side == "Sell" { OrderSide::Short } else { OrderSide::Long },
+            template_id: self.template_id.clone(),
+            model: match &self.model {
+                Some(model) => Some(model_from_str(model)
+                    .ok_or_else(|| format!("Trade {} has unknown model '{}'", self.id, model))?),
+                None => None,
+            },
+            quantity: self.quantity,
+            opened_at: time(&self.opened_at)?,
+            closed_at: self.closed_at.as_deref().map(time).transpose()?,
+            avg_entry: self.avg_entry,
+            avg_exit: self.avg_exit,
+            realized_pnl: self.realized_pnl,
+            commission: self.commission,
+            r_multiple: self.r_multiple,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbDrawing {
+    pub id: String,
+    pub symbol: String,
+    pub shape: String, // JSON-encoded DrawingShape
+    pub color: Option<String>,
+    pub created_at: String,
+}
+
+impl DbDrawing {
+    pub fn from_drawing(drawing: &Drawing) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            id: drawing.id.clone(),
+            symbol: drawing.symbol.clone(),
+            shape: serde_json::to_string(&drawing.shape)?,
+            color: drawing.color.clone(),
+            created_at: Utc::now().to_rfc3339(),
+        })
+    }
+
+    pub fn to_drawing(&self) -> Result<Drawing, String> {
+        let shape: DrawingShape = serde_json::from_str(&self.shape)
+            .map_err(|e| format!("Drawing {} on {} is unreadable: {}", self.id, self.symbol, e))?;
+        Ok(Drawing {
+            id: self.id.clone(),
+            symbol: self.symbol.clone(),
+            shape,
+            color: self.color.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbTheme {
+    pub name: String,
+    pub theme: String, // JSON-encoded ChartTheme
+    pub updated_at: String,
+}
+
+impl DbTheme {
+    pub fn from_theme(name: &str, theme: &ChartTheme) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            name: name.to_string(),
+            theme: serde_json::to_string(theme)?,
+            updated_at: Utc::now().to_rfc3339(),
+        })
+    }
+
+    pub fn to_theme(&self) -> Result<ChartTheme, String> {
+        serde_json::from_str(&self.theme)
+            .map_err(|e| format!("Theme {} is unreadable: {}", self.name, e))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbChecklistItem {
+    pub id: String,
+    pub model: Option<String>, // NULL applies to every model
+    pub text: String,
+    pub position: i64,
+    pub created_at: String,
+}
+
+impl DbChecklistItem {
+    pub fn from_item(item: &ChecklistItem) -> Self {
+        Self {
+            id: item.id.clone(),
+            model: item.model.map(|m| model_to_str(m).to_string()),
+            text: item.text.clone(),
+            position: item.position,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// An unknown model name widens the item to every model rather than dropping it
+    pub fn to_item(&self) -> ChecklistItem {
+        ChecklistItem {
+            id: self.id.clone(),
+            model: self.model.as_deref().and_then(model_from_str),
+            text: self.text.clone(),
+            position: self.position,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct DbSetting {
     pub key: String,
@@ -94,29 +784,172 @@ impl DbOrderTemplate {
             id: Uuid::new_v4().to_string(),
             name,
             symbol,
-            side: match side {
-                OrderSide::Long => "Buy".to_string(),
-                OrderSide::Short => "Sell".to_string(),
-            },
+            currency: DEFAULT_CURRENCY.to_string(),
+            side: side_to_str(side).to_string(),
             quantity,
             limit_price,
             stop_price,
             technical_stop_price: None,
             time_in_force: "GTC".to_string(),
-            model: match model {
-                TradingModel::Breakout => "Breakout",
-                TradingModel::FalseBreakout => "FalseBreakout",
-                TradingModel::Bounce => "Bounce",
-                TradingModel::Continuation => "Continuation",
-            }.to_string(),
+            time_in_force_expires_at: None,
+            entry_type: EntryType::default().ib_order_type().to_string(),
+            entry_limit_offset: None,
+            outside_rth: false,
+            exit_tranches: None,
+            oca_group: None,
+            filled_quantity: 0.0,
+            avg_fill_price: None,
+            filled_at: None,
+            model: model_to_str(model).to_string(),
             status: OrderStatus::Template.as_str().to_string(),
             is_read_only: false,
             risk_per_trade: None,
+            sizing_strategy: None,
+            stop_rules: None,
+            checklist: None,
+            short_availability: None,
+            notes: None,
+            activated_at: None,
+            activate_at: None,
+            expires_at: None,
+            archived_at: None,
+            deleted_at: None,
             created_at: now.clone(),
             updated_at: now,
         }
     }
 
+    pub fn from_order_template(template: &OrderTemplate) -> Self {
+        Self {
+            id: template.id.clone(),
+            name: template.name.clone(),
+            symbol: template.symbol.clone(),
+            currency: template.currency.clone(),
+            side: side_to_str(template.side).to_string(),
+            quantity: template.quantity.round() as i64,
+            limit_price: template.limit_price,
+            stop_price: template.stop_price,
+            technical_stop_price: template.technical_stop_price,
+            time_in_force: template.time_in_force.to_string(),
+            time_in_force_expires_at: template.time_in_force.expires_at().map(|t| t.to_rfc3339()),
+            entry_type: template.entry_type.ib_order_type().to_string(),
+            entry_limit_offset: template.entry_type.limit_offset(),
+            outside_rth: template.outside_rth,
+            exit_tranches: (!template.exit_tranches.is_empty())
+                .then(|| serde_json::to_string(&template.exit_tranches).ok())
+                .flatten(),
+            oca_group: template.oca_group.clone(),
+            filled_quantity: template.filled_quantity,
+            avg_fill_price: template.avg_fill_price,
+            filled_at: template.filled_at.map(|t| t.to_rfc3339()),
+            model: model_to_str(template.model).to_string(),
+            status: OrderStatus::from_template_status(template.status).as_str().to_string(),
+            is_read_only: template.is_read_only,
+            risk_per_trade: Some(template.risk_per_trade),
+            sizing_strategy: (template.sizing_strategy != SizingStrategy::default())
+                .then(|| serde_json::to_string(&template.sizing_strategy).ok())
+                .flatten(),
+            stop_rules: (!template.stop_rules.is_empty())
+                .then(|| serde_json::to_string(&template.stop_rules).ok())
+                .flatten(),
+            checklist: (!template.checklist.is_empty())
+                .then(|| serde_json::to_string(&template.checklist).ok())
+                .flatten(),
+            short_availability: template.short_availability.map(|a| format!("{:?}", a)),
+            notes: template.notes.clone(),
+            activated_at: template.activated_at.map(|t| t.to_rfc3339()),
+            activate_at: template.activate_at.map(|t| t.to_rfc3339()),
+            expires_at: template.expires_at.map(|t| t.to_rfc3339()),
+            archived_at: template.archived_at.map(|t| t.to_rfc3339()),
+            deleted_at: None,
+            created_at: template.created_at.to_rfc3339(),
+            updated_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Rebuild an `OrderTemplate`, attaching IB order ids from the active order row if present
+    pub fn to_order_template(&self, active_order: Option<&DbActiveOrder>) -> Result<OrderTemplate, String> {
+        let side = self.get_order_side()
+            .ok_or_else(|| format!("Template {} has unknown side '{}'", self.id, self.side))?;
+        let model = self.get_trading_model()
+            .ok_or_else(|| format!("Template {} has unknown model '{}'", self.id, self.model))?;
+        let status = self.get_order_status()
+            .ok_or_else(|| format!("Template {} has unknown status '{}'", self.id, self.status))?;
+        let tif_expires_at = self.time_in_force_expires_at.as_deref().and_then(parse_db_timestamp);
+        let time_in_force = TimeInForce::from_code(&self.time_in_force, tif_expires_at)
+            .map_err(|e| format!("Template {}: {}", self.id, e))?;
+        let entry_type = EntryType::from_ib_order_type(&self.entry_type, self.entry_limit_offset)
+            .map_err(|e| format!("Template {}: {}", self.id, e))?;
+        let exit_tranches = match &self.exit_tranches {
+            Some(json) => serde_json::from_str::<Vec<ExitTranche>>(json)
+                .map_err(|e| format!("Template {} has unreadable exit tranches: {}", self.id, e))?,
+            None => Vec::new(),
+        };
+        let sizing_strategy = match &self.sizing_strategy {
+            Some(json) => serde_json::from_str::<SizingStrategy>(json)
+                .map_err(|e| format!("Template {} has unreadable sizing strategy: {}", self.id, e))?,
+            None => SizingStrategy::default(),
+        };
+        let stop_rules = match &self.stop_rules {
+            Some(json) => serde_json::from_str::<Vec<StopRule>>(json)
+                .map_err(|e| format!("Template {} has unreadable stop rules: {}", self.id, e))?,
+            None => Vec::new(),
+        };
+        let checklist = match &self.checklist {
+            Some(json) => serde_json::from_str::<Vec<ChecklistAnswer>>(json)
+                .map_err(|e| format!("Template {} has unreadable checklist: {}", self.id, e))?,
+            None => Vec::new(),
+        };
+
+        let mut status = status.to_template_status();
+        // An "active" row without IB order ids can't be deactivated; treat it as inactive
+        if status == OrderTemplateStatus::Active && active_order.is_none() {
+            status = OrderTemplateStatus::Inactive;
+        }
+
+        Ok(OrderTemplate {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            symbol: self.symbol.clone(),
+            currency: self.currency.clone(),
+            side,
+            quantity: self.quantity as f64,
+            limit_price: self.limit_price,
+            stop_price: self.stop_price,
+            technical_stop_price: self.technical_stop_price,
+            time_in_force,
+            entry_type,
+            outside_rth: self.outside_rth,
+            exit_tranches,
+            oca_group: self.oca_group.clone(),
+            status,
+            parent_order_id: active_order.map(|o| o.ib_order_id as i32),
+            stop_order_id: active_order.and_then(|o| o.ib_stop_order_id.map(|id| id as i32)),
+            tranche_order_ids: active_order.map(|o| o.tranche_order_ids()).unwrap_or_default(),
+            filled_quantity: self.filled_quantity,
+            avg_fill_price: self.avg_fill_price,
+            filled_at: self.filled_at.as_deref().and_then(parse_db_timestamp),
+            created_at: parse_db_timestamp(&self.created_at).unwrap_or_else(Utc::now),
+            activated_at: self.activated_at.as_deref().and_then(parse_db_timestamp),
+            activate_at: self.activate_at.as_deref().and_then(parse_db_timestamp),
+            expires_at: self.expires_at.as_deref().and_then(parse_db_timestamp),
+            archived_at: self.archived_at.as_deref().and_then(parse_db_timestamp),
+            notes: self.notes.clone(),
+            model,
+            is_read_only: self.is_read_only,
+            risk_per_trade: self.risk_per_trade.unwrap_or(100.0),
+            sizing_strategy,
+            stop_rules,
+            checklist,
+            short_availability: self.short_availability.as_deref().and_then(|a| match a {
+                "Easy" => Some(ShortAvailability::Easy),
+                "HardToBorrow" => Some(ShortAvailability::HardToBorrow),
+                "Unavailable" => Some(ShortAvailability::Unavailable),
+                _ => None,
+            }),
+        })
+    }
+
     pub fn get_order_side(&self) -> Option<OrderSide> {
         match self.side.as_str() {
             "Buy" => Some(OrderSide::Long),
@@ -126,13 +959,7 @@ impl DbOrderTemplate {
     }
 
     pub fn get_trading_model(&self) -> Option<TradingModel> {
-        match self.model.as_str() {
-            "Breakout" => Some(TradingModel::Breakout),
-            "FalseBreakout" => Some(TradingModel::FalseBreakout),
-            "Bounce" => Some(TradingModel::Bounce),
-            "Continuation" => Some(TradingModel::Continuation),
-            _ => None,
-        }
+        model_from_str(&self.model)
     }
 
     pub fn get_order_status(&self) -> Option<OrderStatus> {
@@ -143,4 +970,125 @@ impl DbOrderTemplate {
         // Return technical stop if set, otherwise use calculated stop
         self.technical_stop_price.unwrap_or(self.stop_price)
     }
-}
\ No newline at end of file
+}
+
+fn side_to_str(side: OrderSide) -> &'static str {
+    match side {
+        OrderSide::Long => "Buy",
+        OrderSide::Short => "Sell",
+    }
+}
+
+pub(crate) fn model_to_str(model: TradingModel) -> &'static str {
+    match model {
+        TradingModel::Breakout => "Breakout",
+        TradingModel::FalseBreakout => "FalseBreakout",
+        TradingModel::Bounce => "Bounce",
+        TradingModel::Continuation => "Continuation",
+    }
+}
+
+pub(crate) fn model_from_str(model: &str) -> Option<TradingModel> {
+    match model {
+        "Breakout" => Some(TradingModel::Breakout),
+        "FalseBreakout" => Some(TradingModel::FalseBreakout),
+        "Bounce" => Some(TradingModel::Bounce),
+        "Continuation" => Some(TradingModel::Continuation),
+        _ => None,
+    }
+}
+
+/// Parse timestamps written either by us (RFC 3339) or by SQLite's `datetime('now')`
+pub fn parse_db_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|t| t.and_utc())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_template_roundtrip() {
+        let mut template = OrderTemplate::new(
+            "Roundtrip".to_string(),
+            "MSFT".to_string(),
+            OrderSide::Short,
+            25.0,
+            410.0,
+            415.0,
+            TimeInForce::Day,
+            TradingModel::FalseBreakout,
+        );
+        template.notes = Some("fade the open".to_string());
+        template.entry_type = EntryType::StopLimit { limit_offset: 0.2 };
+        template.exit_tranches = vec![ExitTranche::target(0.5, 1.0), ExitTranche::trailing(0.5, 2.0)];
+        template.sizing_strategy = SizingStrategy::FractionalKelly { fraction: 0.25 };
+        template.stop_rules = vec![StopRule::Breakeven { after_r: 1.0 }, StopRule::TimeStop { days: 5, min_r: 0.5 }];
+        template.checklist = vec![ChecklistAnswer {
+            item_id: "trend".to_string(),
+            text: "Higher timeframe trend aligned".to_string(),
+            checked: true,
+            note: Some("daily and weekly up".to_string()),
+            answered_at: Some(Utc::now()),
+        }];
+        template.short_availability = Some(ShortAvailability::HardToBorrow);
+        template.currency = "EUR".to_string();
+        template.status = OrderTemplateStatus::Active;
+
+        let db_template = DbOrderTemplate::from_order_template(&template);
+        assert_eq!(db_template.side, "Sell");
+        assert_eq!(db_template.status, "Active");
+
+        let active_order = DbActiveOrder {
+            template_id: template.id.clone(),
+            ib_order_id: 2000,
+            ib_stop_order_id: Some(2001),
+            ib_tranche_order_ids: DbActiveOrder::encode_tranche_ids(&[2002, 2003]),
+            submitted_at: Utc::now().to_rfc3339(),
+        };
+        let restored = db_template.to_order_template(Some(&active_order)).unwrap();
+        assert_eq!(restored.id, template.id);
+        assert_eq!(restored.side, OrderSide::Short);
+        assert_eq!(restored.time_in_force, TimeInForce::Day);
+        assert_eq!(restored.entry_type, EntryType::StopLimit { limit_offset: 0.2 });
+        assert_eq!(restored.model, TradingModel::FalseBreakout);
+        assert_eq!(restored.status, OrderTemplateStatus::Active);
+        assert_eq!(restored.parent_order_id, Some(2000));
+        assert_eq!(restored.stop_order_id, Some(2001));
+        assert_eq!(restored.tranche_order_ids, vec![2002, 2003]);
+        assert_eq!(restored.exit_tranches, template.exit_tranches);
+        assert_eq!(restored.sizing_strategy, template.sizing_strategy);
+        assert_eq!(restored.stop_rules, template.stop_rules);
+        assert_eq!(restored.checklist, template.checklist);
+        assert_eq!(restored.short_availability, Some(ShortAvailability::HardToBorrow));
+        assert_eq!(restored.currency, "EUR");
+        assert_eq!(restored.notes.as_deref(), Some("fade the open"));
+
+        // Without IB order ids the template can't be live
+        let orphaned = db_template.to_order_template(None).unwrap();
+        assert_eq!(orphaned.status, OrderTemplateStatus::Inactive);
+
+        template.apply_entry_fill(10.0, 409.85, Utc::now());
+        let db_template = DbOrderTemplate::from_order_template(&template);
+        assert_eq!(db_template.status, "PartiallyFilled");
+        let restored = db_template.to_order_template(Some(&active_order)).unwrap();
+        assert_eq!(restored.status, OrderTemplateStatus::PartiallyFilled);
+        assert_eq!(restored.filled_quantity, 10.0);
+        assert_eq!(restored.avg_fill_price, Some(409.85));
+        assert!(restored.filled_at.is_some());
+    }
+
+    #[test]
+    fn test_parse_db_timestamp() {
+        assert!(parse_db_timestamp("2025-07-25 18:16:38").is_some());
+        assert!(parse_db_timestamp("2025-07-25T18:16:38+00:00").is_some());
+        assert!(parse_db_timestamp("yesterday").is_none());
+    }
+}
@@ -1,8 +1,9 @@
-use sqlx::sqlite::SqlitePool;
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
 
-pub const DATABASE_URL: &str = "sqlite:zakaz.db";
-
-pub async fn create_schema(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+/// The tables as of schema version 1, as the app created them before the schema was versioned.
+/// Never edit this: tables and columns added since go in `migrations`, so existing databases
+/// get them too.
+pub async fn create_schema(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
     // Templates table: All order templates
     sqlx::query(
         r#"
@@ -25,7 +26,7 @@ pub async fn create_schema(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         )
         "#
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await?;
 
     // Active orders table: Template ID + IB order ID mapping
@@ -41,7 +42,7 @@ pub async fn create_schema(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         )
         "#
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await?;
 
     // Settings table: Risk parameters, ATR settings
@@ -54,7 +55,7 @@ pub async fn create_schema(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         )
         "#
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await?;
 
     // Positions table: IB positions with optional template association
@@ -72,20 +73,20 @@ pub async fn create_schema(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         )
         "#
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await?;
 
     // Create indexes for performance
     sqlx::query("CREATE INDEX IF NOT EXISTS idx_templates_symbol ON templates(symbol)")
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
     
     sqlx::query("CREATE INDEX IF NOT EXISTS idx_templates_status ON templates(status)")
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
     
     sqlx::query("CREATE INDEX IF NOT EXISTS idx_active_orders_ib_order_id ON active_orders(ib_order_id)")
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     // Create triggers to update timestamps
@@ -98,7 +99,7 @@ pub async fn create_schema(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         END
         "#
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await?;
 
     sqlx::query(
@@ -110,7 +111,7 @@ pub async fn create_schema(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         END
         "#
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await?;
 
     Ok(())
@@ -143,5 +144,179 @@ pub async fn init_default_settings(pool: &SqlitePool) -> Result<(), sqlx::Error>
         .execute(pool)
         .await?;
 
+    // Minimum stop distance from entry: $0.01
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('min_stop_distance', '0.01')")
+        .execute(pool)
+        .await?;
+
+    // Maximum distance of an entry from the market at activation: 5%
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('max_price_deviation_pct', '5.0')")
+        .execute(pool)
+        .await?;
+
+    // Minutes before the close at which DAY templates are deactivated: 5
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('eod_deactivation_minutes', '5')")
+        .execute(pool)
+        .await?;
+
+    // Trading days until an unactivated template expires (0 = never): 5
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('template_expiry_trading_days', '5')")
+        .execute(pool)
+        .await?;
+
+    // Day P&L loss that locks trading until the next session (0 = no limit): 0
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('daily_loss_limit', '0')")
+        .execute(pool)
+        .await?;
+
+    // Close all positions at market when the daily loss limit trips: false
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('flatten_on_loss_limit', 'false')")
+        .execute(pool)
+        .await?;
+
+    // Symbols held at once before activation is refused (0 = no limit): 0
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('max_open_positions', '0')")
+        .execute(pool)
+        .await?;
+
+    // Unfilled active templates at once (0 = no limit): 0
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('max_pending_templates', '0')")
+        .execute(pool)
+        .await?;
+
+    // Notional across live templates on one symbol (0 = no cap): 0
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('max_symbol_notional', '0')")
+        .execute(pool)
+        .await?;
+
+    // Stop risk across live templates on one symbol (0 = no cap): 0
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('max_symbol_risk', '0')")
+        .execute(pool)
+        .await?;
+
+    // Share of notional held as initial margin when sizing (1.0 = cash, 0.5 = Reg T): 1.0
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('initial_margin_rate', '1.0')")
+        .execute(pool)
+        .await?;
+
+    // Warn before adding a symbol this correlated with a held one (0 = off): 0.8
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('correlation_warning_threshold', '0.8')")
+        .execute(pool)
+        .await?;
+
+    // Daily returns compared for the correlation warning: 60
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('correlation_window_days', '60')")
+        .execute(pool)
+        .await?;
+
+    // Largest entry notional sent on the live account (0 = no cap): 0
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('live_max_order_notional', '0')")
+        .execute(pool)
+        .await?;
+
+    // Whether market entries may be sent on the live account: true
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('live_allow_market_orders', 'true')")
+        .execute(pool)
+        .await?;
+
+    // Full-size take-profit at this R on new templates, per model via default_target_r:<Model> (0 = none): 0
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('default_target_r', '0')")
+        .execute(pool)
+        .await?;
+
+    // How long a calculated ATR is reused: "session" (until the next open), hours, or 0 to always recalculate: session
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('atr_cache_ttl', 'session')")
+        .execute(pool)
+        .await?;
+
+    // Warn at stop validation when ATR and realized volatility differ by more than this factor either way (0 = off): 2
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('atr_volatility_divergence', '2')")
+        .execute(pool)
+        .await?;
+
+    // Alert when a watched symbol's range today reaches this many ATRs (0 = off): 2
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('volatility_spike_multiple', '2')")
+        .execute(pool)
+        .await?;
+
+    // Currency risk and P&L are reported in; other currencies are converted at IB FX rates: USD
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('base_currency', 'USD')")
+        .execute(pool)
+        .await?;
+
+    // Scale risk_per_trade down in drawdown, "<drawdown %>:<factor>" steps e.g. "5:0.5,10:0.25" (empty = off): empty
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('drawdown_risk_steps', '')")
+        .execute(pool)
+        .await?;
+
+    // Symbols the market scanner screens, comma separated (empty = the watchlist): empty
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('scanner_universe', '')")
+        .execute(pool)
+        .await?;
+
+    // Leave earnings reaction days out of ATR before outlier filtering: false
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('atr_exclude_earnings', 'false')")
+        .execute(pool)
+        .await?;
+
+    // What activation does when earnings fall within the expected hold (off, warn or block): warn
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('earnings_policy', 'warn')")
+        .execute(pool)
+        .await?;
+
+    // Expected holding period checked for earnings at activation, trading days: 5
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('earnings_hold_days', '5')")
+        .execute(pool)
+        .await?;
+
+    // Short entries whose what-if initial margin exceeds this % of notional are flagged (0 = off): 100
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('short_max_margin_pct', '100')")
+        .execute(pool)
+        .await?;
+
+    // What a hard-to-borrow or margin-heavy short does at activation (block or warn): block
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('short_check_policy', 'block')")
+        .execute(pool)
+        .await?;
+
+    // Commission model for cost-aware risk figures (per_share, per_order or none): per_share
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('commission_model', 'per_share')")
+        .execute(pool)
+        .await?;
+
+    // Per-share commission, its minimum and its cap as % of trade value (IB fixed pricing): 0.005 / 1.0 / 1.0
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('commission_per_share', '0.005')")
+        .execute(pool)
+        .await?;
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('commission_minimum', '1.0')")
+        .execute(pool)
+        .await?;
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('commission_maximum_pct', '1.0')")
+        .execute(pool)
+        .await?;
+
+    // Flat commission per order for the per_order model: 1.0
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('commission_per_order', '1.0')")
+        .execute(pool)
+        .await?;
+
+    // Theme charts are drawn with, built-in or from the themes table: dark
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('chart_theme', 'dark')")
+        .execute(pool)
+        .await?;
+
+    // Automatic backups: every 24 hours, keeping the newest 7; 0 hours turns them off
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('backup_interval_hours', '24')")
+        .execute(pool)
+        .await?;
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('backup_keep', '7')")
+        .execute(pool)
+        .await?;
+
+    // Deleted templates are purged, history and all, 30 days on; 0 keeps them
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('purge_deleted_templates_days', '30')")
+        .execute(pool)
+        .await?;
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteConnection;
+
+/// What a search hit points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchKind {
+    Template,  // `id` is the template id
+    Trade,     // `id` is the round-trip trade id
+    Watchlist, // `id` is the watchlist id, `symbol` the entry on it
+}
+
+impl SearchKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            SearchKind::Template => "template",
+            SearchKind::Trade => "trade",
+            SearchKind::Watchlist => "watchlist",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        [SearchKind::Template, SearchKind::Trade, SearchKind::Watchlist].into_iter().find(|k| k.as_str() == s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub kind: SearchKind,
+    pub id: String,
+    pub symbol: String,
+    pub title: String,
+    pub snippet: String, // Matched text with the terms in [brackets]
+}
+
+/// Turn what the user typed into an FTS5 query: every word must match, each as a prefix so
+/// "break" finds "breakout". Punctuation and FTS operators are dropped; None when no words remain.
+pub fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word.to_lowercase()))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+pub(crate) fn hit(kind: &str, id: String, symbol: String, title: String, snippet: String) -> Option<SearchHit> {
+    Some(SearchHit { kind: SearchKind::parse(kind)?, id, symbol, title, snippet })
+}
+
+/// The `search_index` table over template names and notes, round-trip trades (with their
+/// template's name and notes) and watchlist notes, filled from what is already there and kept
+/// current by triggers. Each row is tagged with its model and side in words and the month it
+/// was created or opened, so "NVDA false breakout March" finds it.
+pub async fn create_index(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    for statement in index_statements() {
+        sqlx::query(&statement).execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// The triggers keeping template rows current; a rebuild of the templates table drops them with it
+pub async fn create_template_triggers(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    for statement in template_trigger_statements() {
+        sqlx::query(&statement).execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+fn index_statements() -> Vec<String> {
+    let mut statements = vec![
+        "CREATE VIRTUAL TABLE search_index USING fts5(kind UNINDEXED, ref_id UNINDEXED, symbol, title, body, tags)".to_string(),
+        // Backfill
+        format!("INSERT INTO search_index SELECT {} FROM templates t WHERE t.deleted_at IS NULL", template_entry("t")),
+        format!("INSERT INTO search_index SELECT {} FROM trades t", trade_entry("t")),
+        format!("INSERT INTO search_index SELECT {} FROM watchlist_symbols w", watchlist_entry("w")),
+    ];
+    statements.extend(template_trigger_statements());
+    statements.extend([
+        // Trades
+        format!(
+            "CREATE TRIGGER trades_search_insert AFTER INSERT ON trades BEGIN
+                INSERT INTO search_index SELECT {};
+            END",
+            trade_entry("NEW"),
+        ),
+        format!(
+            "CREATE TRIGGER trades_search_update AFTER UPDATE ON trades BEGIN
+                DELETE FROM search_index WHERE kind = 'trade' AND ref_id = OLD.id;
+                INSERT INTO search_index SELECT {};
+            END",
+            trade_entry("NEW"),
+        ),
+        "CREATE TRIGGER trades_search_delete AFTER DELETE ON trades BEGIN
+            DELETE FROM search_index WHERE kind = 'trade' AND ref_id = OLD.id;
+        END"
+        .to_string(),
+        // Watchlist notes; reordering alone leaves the index be
+        format!(
+            "CREATE TRIGGER watchlist_symbols_search_insert AFTER INSERT ON watchlist_symbols BEGIN
+                INSERT INTO search_index SELECT {};
+            END",
+            watchlist_entry("NEW"),
+        ),
+        format!(
+            "CREATE TRIGGER watchlist_symbols_search_update AFTER UPDATE OF note ON watchlist_symbols BEGIN
+                DELETE FROM search_index WHERE kind = 'watchlist' AND ref_id = OLD.watchlist_id AND symbol = OLD.symbol;
+                INSERT INTO search_index SELECT {};
+            END",
+            watchlist_entry("NEW"),
+        ),
+        "CREATE TRIGGER watchlist_symbols_search_delete AFTER DELETE ON watchlist_symbols BEGIN
+            DELETE FROM search_index WHERE kind = 'watchlist' AND ref_id = OLD.watchlist_id AND symbol = OLD.symbol;
+        END"
+        .to_string(),
+    ]);
+    statements
+}
+
+/// Templates, and the trades that show their name and notes
+fn template_trigger_statements() -> Vec<String> {
+    vec![
+        format!(
+            "CREATE TRIGGER templates_search_insert AFTER INSERT ON templates WHEN NEW.deleted_at IS NULL BEGIN
+                INSERT INTO search_index SELECT {};
+            END",
+            template_entry("NEW"),
+        ),
+        format!(
+            "CREATE TRIGGER templates_search_update AFTER UPDATE ON templates BEGIN
+                DELETE FROM search_index WHERE kind = 'template' AND ref_id = OLD.id;
+                INSERT INTO search_index SELECT {} WHERE NEW.deleted_at IS NULL;
+                UPDATE search_index SET title = NEW.name, body = COALESCE(NEW.notes, '')
+                    WHERE kind = 'trade' AND ref_id IN (SELECT id FROM trades WHERE template_id = NEW.id);
+            END",
+            template_entry("NEW"),
+        ),
+        "CREATE TRIGGER templates_search_delete AFTER DELETE ON templates BEGIN
+            DELETE FROM search_index WHERE kind = 'template' AND ref_id = OLD.id;
+        END"
+        .to_string(),
+    ]
+}
+
+// Each entry is the row's values in `search_index` column order, for the row named `row`
+
+fn template_entry(row: &str) -> String {
+    format!(
+        "'template', {row}.id, {row}.symbol, {row}.name, COALESCE({row}.notes, ''), {}",
+        tags(&format!("{row}.model"), &format!("{row}.side"), &format!("{row}.created_at")),
+    )
+}
+
+fn trade_entry(row: &str) -> String {
+    format!(
+        "'trade', {row}.id, {row}.symbol,
+            COALESCE((SELECT name FROM templates WHERE id = {row}.template_id), {row}.symbol || ' trade'),
+            COALESCE((SELECT notes FROM templates WHERE id = {row}.template_id), ''), {}",
+        tags(&format!("{row}.model"), &format!("{row}.side"), &format!("{row}.opened_at")),
+    )
+}
+
+fn watchlist_entry(row: &str) -> String {
+    format!(
+        "'watchlist', {row}.watchlist_id, {row}.symbol, (SELECT name FROM watchlists WHERE id = {row}.watchlist_id),
+            COALESCE({row}.note, ''), {}",
+        month(&format!("{row}.added_at")),
+    )
+}
+
+/// Model and side as typed and in words, then the month, e.g. "FalseBreakout false breakout Sell short March 2025"
+fn tags(model: &str, side: &str, time: &str) -> String {
+    format!(
+        "CASE {model} WHEN 'FalseBreakout' THEN 'FalseBreakout false breakout' ELSE COALESCE({model}, '') END
+            || ' ' || CASE {side} WHEN 'Buy' THEN 'Buy long' WHEN 'Sell' THEN 'Sell short' ELSE {side} END
+            || ' ' || {}",
+        month(time),
+    )
+}
+
+fn month(time: &str) -> String {
+    let names = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+    let cases: String = names.iter().enumerate().map(|(i, name)| format!(" WHEN '{:02}' THEN '{}'", i + 1, name)).collect();
+    format!("COALESCE(CASE strftime('%m', {time}){cases} END || ' ' || strftime('%Y', {time}), '')")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(fts_query("NVDA false-breakout, March").as_deref(), Some(r#""nvda"* "false"* "breakout"* "march"*"#));
+        // FTS syntax can't get through
+        assert_eq!(fts_query(r#"AAPL" OR notes:*"#).as_deref(), Some(r#""aapl"* "or"* "notes"*"#));
+        assert_eq!(fts_query(" -- "), None);
+    }
+}
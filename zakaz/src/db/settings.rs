@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::charts::theme::{ColorSchemePreference, BUILTIN_THEMES};
+use crate::ib::analytics::AtrCacheTtl;
+use crate::ib::earnings::EarningsPolicy;
+use crate::ib::orders::calculations::CommissionModel;
+use crate::ib::risk::RiskScaling;
+use crate::wrn;
+
+/// Every app-wide setting, typed. Loaded from the settings table in one go and written back
+/// whole; per-model overrides (`<key>:<Model>`) and stored state stay in their own accessors.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    pub risk: RiskSettings,
+    pub orders: OrderSettings,
+    pub atr: AtrSettings,
+    pub scanner: ScannerSettings,
+    pub connection: ConnectionSettings,
+    pub ui: UiSettings,
+    pub backup: BackupSettings,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskSettings {
+    pub risk_per_trade: f64,                // Models without their own override
+    pub daily_loss_limit: f64,              // 0 = off
+    pub flatten_on_loss_limit: bool,
+    pub max_open_positions: usize,          // 0 = no limit, here and below
+    pub max_pending_templates: usize,
+    pub max_symbol_notional: f64,
+    pub max_symbol_risk: f64,
+    pub initial_margin_rate: f64,
+    pub short_max_margin_pct: f64,
+    pub short_check_blocks: bool,           // `short_check_policy` block, or warn
+    pub correlation_warning_threshold: f64, // 0 = off
+    pub correlation_window_days: usize,
+    pub drawdown_risk_steps: RiskScaling,
+    pub base_currency: String,
+}
+
+impl Default for RiskSettings {
+    fn default() -> Self {
+        Self {
+            risk_per_trade: 100.0,
+            daily_loss_limit: 0.0,
+            flatten_on_loss_limit: false,
+            max_open_positions: 0,
+            max_pending_templates: 0,
+            max_symbol_notional: 0.0,
+            max_symbol_risk: 0.0,
+            initial_margin_rate: 1.0,
+            short_max_margin_pct: 100.0,
+            short_check_blocks: true,
+            correlation_warning_threshold: 0.8,
+            correlation_window_days: 60,
+            drawdown_risk_steps: RiskScaling::default(),
+            base_currency: "USD".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderSettings {
+    pub max_price_deviation_pct: f64,
+    pub eod_deactivation_minutes: i64,
+    pub template_expiry_trading_days: u32, // 0 = templates don't expire
+    pub purge_deleted_templates_days: u32, // 0 = deleted templates are kept
+    pub earnings_policy: EarningsPolicy,
+    pub earnings_hold_days: u32,
+    pub commission_model: CommissionModel,
+    pub live_max_order_notional: f64,      // 0 = no limit
+    pub live_allow_market_orders: bool,
+}
+
+impl Default for OrderSettings {
+    fn default() -> Self {
+        Self {
+            max_price_deviation_pct: 5.0,
+            eod_deactivation_minutes: 5,
+            template_expiry_trading_days: 5,
+            purge_deleted_templates_days: 30,
+            earnings_policy: EarningsPolicy::Warn,
+            earnings_hold_days: 5,
+            commission_model: CommissionModel::PerShare { rate: 0.005, minimum: 1.0, maximum_pct: 1.0 },
+            live_max_order_notional: 0.0,
+            live_allow_market_orders: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtrSettings {
+    pub period: usize,
+    pub outlier_multiplier: f64,
+    pub cache_ttl: AtrCacheTtl,
+    pub volatility_divergence: f64,     // 0 = don't compare ATR with realized volatility
+    pub volatility_spike_multiple: f64, // 0 = no spike alerts
+    pub exclude_earnings: bool,
+}
+
+impl Default for AtrSettings {
+    fn default() -> Self {
+        Self {
+            period: 14,
+            outlier_multiplier: 2.5,
+            cache_ttl: AtrCacheTtl::Session,
+            volatility_divergence: 2.0,
+            volatility_spike_multiple: 2.0,
+            exclude_earnings: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScannerSettings {
+    pub universe: Vec<String>, // Empty scans the watchlist
+}
+
+/// Where TWS or the gateway listens for each account
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionSettings {
+    pub paper_address: String,
+    pub live_address: String,
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        Self {
+            paper_address: "127.0.0.1:7497".to_string(),
+            live_address: "127.0.0.1:7496".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiSettings {
+    pub chart_theme: String,
+    pub color_scheme: ColorSchemePreference,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        Self {
+            chart_theme: BUILTIN_THEMES[0].to_string(),
+            color_scheme: ColorSchemePreference::default(),
+        }
+    }
+}
+
+/// Automatic backups into the `backups` folder beside the database
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupSettings {
+    pub interval_hours: u32, // 0 = off
+    pub keep: usize,         // Newest backups kept; older ones are deleted
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self { interval_hours: 24, keep: 7 }
+    }
+}
+
+impl Settings {
+    /// Settings from the table's key/value pairs. Missing keys take their default, as does
+    /// a value that doesn't parse, with a warning.
+    pub fn from_values(values: &HashMap<String, String>) -> Self {
+        let defaults = Settings::default();
+        let raw = |key: &str| values.get(key).map(|v| v.trim());
+
+        let risk = RiskSettings {
+            risk_per_trade: value(values, "risk_per_trade", defaults.risk.risk_per_trade),
+            daily_loss_limit: value(values, "daily_loss_limit", defaults.risk.daily_loss_limit),
+            flatten_on_loss_limit: value(values, "flatten_on_loss_limit", defaults.risk.flatten_on_loss_limit),
+            max_open_positions: value(values, "max_open_positions", defaults.risk.max_open_positions),
+            max_pending_templates: value(values, "max_pending_templates", defaults.risk.max_pending_templates),
+            max_symbol_notional: value(values, "max_symbol_notional", defaults.risk.max_symbol_notional),
+            max_symbol_risk: value(values, "max_symbol_risk", defaults.risk.max_symbol_risk),
+            initial_margin_rate: value(values, "initial_margin_rate", defaults.risk.initial_margin_rate),
+            short_max_margin_pct: value(values, "short_max_margin_pct", defaults.risk.short_max_margin_pct),
+            short_check_blocks: raw("short_check_policy").map_or(defaults.risk.short_check_blocks, |v| v != "warn"),
+            correlation_warning_threshold: value(values, "correlation_warning_threshold", defaults.risk.correlation_warning_threshold),
+            correlation_window_days: value(values, "correlation_window_days", defaults.risk.correlation_window_days),
+            drawdown_risk_steps: raw("drawdown_risk_steps").map_or(defaults.risk.drawdown_risk_steps.clone(), |v| {
+                RiskScaling::parse(v).unwrap_or_else(|e| {
+                    wrn!("Ignoring drawdown_risk_steps: {}", e);
+                    RiskScaling::default()
+                })
+            }),
+            base_currency: raw("base_currency").filter(|v| !v.is_empty()).map_or(defaults.risk.base_currency.clone(), str::to_uppercase),
+        };
+
+        let orders = OrderSettings {
+            max_price_deviation_pct: value(values, "max_price_deviation_pct", defaults.orders.max_price_deviation_pct),
+            eod_deactivation_minutes: value(values, "eod_deactivation_minutes", defaults.orders.eod_deactivation_minutes),
+            template_expiry_trading_days: value(values, "template_expiry_trading_days", defaults.orders.template_expiry_trading_days),
+            purge_deleted_templates_days: value(values, "purge_deleted_templates_days", defaults.orders.purge_deleted_templates_days),
+            earnings_policy: raw("earnings_policy").map_or(defaults.orders.earnings_policy, EarningsPolicy::parse),
+            earnings_hold_days: value(values, "earnings_hold_days", defaults.orders.earnings_hold_days),
+            commission_model: match raw("commission_model").unwrap_or("per_share") {
+                "per_share" => CommissionModel::PerShare {
+                    rate: value(values, "commission_per_share", 0.005),
+                    minimum: value(values, "commission_minimum", 1.0),
+                    maximum_pct: value(values, "commission_maximum_pct", 1.0),
+                },
+                "per_order" => CommissionModel::PerOrder {
+                    amount: value(values, "commission_per_order", 1.0),
+                },
+                _ => CommissionModel::None,
+            },
+            live_max_order_notional: value(values, "live_max_order_notional", defaults.orders.live_max_order_notional),
+            live_allow_market_orders: value(values, "live_allow_market_orders", defaults.orders.live_allow_market_orders),
+        };
+
+        let atr = AtrSettings {
+            period: value(values, "atr_period", defaults.atr.period),
+            outlier_multiplier: value(values, "atr_outlier_multiplier", defaults.atr.outlier_multiplier),
+            cache_ttl: raw("atr_cache_ttl").map_or(defaults.atr.cache_ttl, AtrCacheTtl::parse),
+            volatility_divergence: value(values, "atr_volatility_divergence", defaults.atr.volatility_divergence),
+            volatility_spike_multiple: value(values, "volatility_spike_multiple", defaults.atr.volatility_spike_multiple),
+            exclude_earnings: value(values, "atr_exclude_earnings", defaults.atr.exclude_earnings),
+        };
+
+        let scanner = ScannerSettings {
+            universe: raw("scanner_universe").map(crate::ib::scanner::parse_symbols).unwrap_or_default(),
+        };
+
+        let connection = ConnectionSettings {
+            paper_address: raw("ib_paper_address").filter(|v| !v.is_empty()).map_or(defaults.connection.paper_address, str::to_string),
+            live_address: raw("ib_live_address").filter(|v| !v.is_empty()).map_or(defaults.connection.live_address, str::to_string),
+        };
+
+        let ui = UiSettings {
+            chart_theme: raw("chart_theme").filter(|v| !v.is_empty()).map_or(defaults.ui.chart_theme, str::to_string),
+            color_scheme: raw("color_scheme").and_then(ColorSchemePreference::parse).unwrap_or(defaults.ui.color_scheme),
+        };
+
+        let backup = BackupSettings {
+            interval_hours: value(values, "backup_interval_hours", defaults.backup.interval_hours),
+            keep: value(values, "backup_keep", defaults.backup.keep),
+        };
+
+        Self { risk, orders, atr, scanner, connection, ui, backup }
+    }
+
+    /// The key/value pairs `from_values` reads back into these settings
+    pub fn to_values(&self) -> Vec<(&'static str, String)> {
+        let risk = &self.risk;
+        let orders = &self.orders;
+        let atr = &self.atr;
+        let mut values = vec![
+            ("risk_per_trade", risk.risk_per_trade.to_string()),
+            ("daily_loss_limit", risk.daily_loss_limit.to_string()),
+            ("flatten_on_loss_limit", risk.flatten_on_loss_limit.to_string()),
+            ("max_open_positions", risk.max_open_positions.to_string()),
+            ("max_pending_templates", risk.max_pending_templates.to_string()),
+            ("max_symbol_notional", risk.max_symbol_notional.to_string()),
+            ("max_symbol_risk", risk.max_symbol_risk.to_string()),
+            ("initial_margin_rate", risk.initial_margin_rate.to_string()),
+            ("short_max_margin_pct", risk.short_max_margin_pct.to_string()),
+            ("short_check_policy", if risk.short_check_blocks { "block" } else { "warn" }.to_string()),
+            ("correlation_warning_threshold", risk.correlation_warning_threshold.to_string()),
+            ("correlation_window_days", risk.correlation_window_days.to_string()),
+            ("drawdown_risk_steps", risk.drawdown_risk_steps.to_setting()),
+            ("base_currency", risk.base_currency.clone()),
+            ("max_price_deviation_pct", orders.max_price_deviation_pct.to_string()),
+            ("eod_deactivation_minutes", orders.eod_deactivation_minutes.to_string()),
+            ("template_expiry_trading_days", orders.template_expiry_trading_days.to_string()),
+            ("purge_deleted_templates_days", orders.purge_deleted_templates_days.to_string()),
+            ("earnings_policy", orders.earnings_policy.as_str().to_string()),
+            ("earnings_hold_days", orders.earnings_hold_days.to_string()),
+            ("live_max_order_notional", orders.live_max_order_notional.to_string()),
+            ("live_allow_market_orders", orders.live_allow_market_orders.to_string()),
+            ("atr_period", atr.period.to_string()),
+            ("atr_outlier_multiplier", atr.outlier_multiplier.to_string()),
+            ("atr_cache_ttl", atr.cache_ttl.to_setting()),
+            ("atr_volatility_divergence", atr.volatility_divergence.to_string()),
+            ("volatility_spike_multiple", atr.volatility_spike_multiple.to_string()),
+            ("atr_exclude_earnings", atr.exclude_earnings.to_string()),
+            ("scanner_universe", self.scanner.universe.join(",")),
+            ("ib_paper_address", self.connection.paper_address.clone()),
+            ("ib_live_address", self.connection.live_address.clone()),
+            ("chart_theme", self.ui.chart_theme.clone()),
+            ("color_scheme", self.ui.color_scheme.as_str().to_string()),
+            ("backup_interval_hours", self.backup.interval_hours.to_string()),
+            ("backup_keep", self.backup.keep.to_string()),
+        ];
+        // The other model's amounts are left as they were, ready for switching back
+        match orders.commission_model {
+            CommissionModel::None => values.push(("commission_model", "none".to_string())),
+            CommissionModel::PerShare { rate, minimum, maximum_pct } => values.extend([
+                ("commission_model", "per_share".to_string()),
+                ("commission_per_share", rate.to_string()),
+                ("commission_minimum", minimum.to_string()),
+                ("commission_maximum_pct", maximum_pct.to_string()),
+            ]),
+            CommissionModel::PerOrder { amount } => values.extend([
+                ("commission_model", "per_order".to_string()),
+                ("commission_per_order", amount.to_string()),
+            ]),
+        }
+        values
+    }
+
+    /// Reject values no subsystem could work with
+    pub fn validate(&self) -> Result<(), String> {
+        let risk = &self.risk;
+        let orders = &self.orders;
+        let atr = &self.atr;
+        let not_negative = [
+            ("risk_per_trade", risk.risk_per_trade),
+            ("daily_loss_limit", risk.daily_loss_limit),
+            ("max_symbol_notional", risk.max_symbol_notional),
+            ("max_symbol_risk", risk.max_symbol_risk),
+            ("short_max_margin_pct", risk.short_max_margin_pct),
+            ("max_price_deviation_pct", orders.max_price_deviation_pct),
+            ("live_max_order_notional", orders.live_max_order_notional),
+            ("atr_outlier_multiplier", atr.outlier_multiplier),
+            ("atr_volatility_divergence", atr.volatility_divergence),
+            ("volatility_spike_multiple", atr.volatility_spike_multiple),
+        ];
+        if let Some((key, value)) = not_negative.iter().find(|(_, value)| !value.is_finite() || *value < 0.0) {
+            return Err(format!("{} can't be {}", key, value));
+        }
+        if risk.risk_per_trade == 0.0 {
+            return Err("risk_per_trade must be above 0".to_string());
+        }
+        if !(risk.initial_margin_rate > 0.0 && risk.initial_margin_rate <= 1.0) {
+            return Err(format!("initial_margin_rate must be above 0 and at most 1, not {}", risk.initial_margin_rate));
+        }
+        if !(0.0..=1.0).contains(&risk.correlation_warning_threshold) {
+            return Err(format!("correlation_warning_threshold must be between 0 and 1, not {}", risk.correlation_warning_threshold));
+        }
+        if risk.correlation_window_days < 2 {
+            return Err("correlation_window_days must be at least 2".to_string());
+        }
+        if risk.base_currency.len() != 3 || !risk.base_currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(format!("base_currency should be a three-letter code like USD, not {:?}", risk.base_currency));
+        }
+        if orders.eod_deactivation_minutes < 0 {
+            return Err("eod_deactivation_minutes can't be negative".to_string());
+        }
+        let commission_amounts = match orders.commission_model {
+            CommissionModel::None => vec![],
+            CommissionModel::PerShare { rate, minimum, maximum_pct } => vec![rate, minimum, maximum_pct],
+            CommissionModel::PerOrder { amount } => vec![amount],
+        };
+        if commission_amounts.iter().any(|amount| !amount.is_finite() || *amount < 0.0) {
+            return Err("Commission amounts can't be negative".to_string());
+        }
+        if atr.period < 2 {
+            return Err("atr_period must be at least 2".to_string());
+        }
+        for (key, address) in [("ib_paper_address", &self.connection.paper_address), ("ib_live_address", &self.connection.live_address)] {
+            let valid = address.rsplit_once(':')
+                .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+            if !valid {
+                return Err(format!("{} should look like 127.0.0.1:7497, not {:?}", key, address));
+            }
+        }
+        if self.backup.keep == 0 {
+            return Err("backup_keep must be at least 1".to_string());
+        }
+        if self.ui.chart_theme.trim().is_empty() {
+            return Err("chart_theme can't be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Setting `key` parsed as `T`; `default` when missing or unreadable
+fn value<T: FromStr>(values: &HashMap<String, String>, key: &str, default: T) -> T {
+    match values.get(key).map(|v| v.trim()) {
+        Some(raw) => raw.parse().unwrap_or_else(|_| {
+            wrn!("Ignoring setting {} = {:?}, not a valid value", key, raw);
+            default
+        }),
+        None => default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_round_trip() {
+        let mut settings = Settings::default();
+        settings.risk.daily_loss_limit = 500.0;
+        settings.risk.short_check_blocks = false;
+        settings.risk.drawdown_risk_steps = RiskScaling::parse("10:0.25,5:0.5").unwrap();
+        settings.orders.earnings_policy = EarningsPolicy::Block;
+        settings.orders.commission_model = CommissionModel::PerOrder { amount: 2.5 };
+        settings.atr.cache_ttl = AtrCacheTtl::Hours(6);
+        settings.scanner.universe = vec!["AAPL".to_string(), "MSFT".to_string()];
+        settings.ui.color_scheme = ColorSchemePreference::Light;
+
+        let values: HashMap<String, String> = settings.to_values().into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+        assert_eq!(Settings::from_values(&values), settings);
+        assert_eq!(Settings::from_values(&HashMap::new()), Settings::default());
+    }
+
+    #[test]
+    fn test_unreadable_values_fall_back() {
+        let values = HashMap::from([
+            ("atr_period".to_string(), "fourteen".to_string()),
+            ("max_open_positions".to_string(), " 3 ".to_string()),
+            ("base_currency".to_string(), "eur".to_string()),
+            ("drawdown_risk_steps".to_string(), "5".to_string()),
+        ]);
+        let settings = Settings::from_values(&values);
+        assert_eq!(settings.atr.period, 14);
+        assert_eq!(settings.risk.max_open_positions, 3);
+        assert_eq!(settings.risk.base_currency, "EUR");
+        assert_eq!(settings.risk.drawdown_risk_steps, RiskScaling::default());
+    }
+
+    #[test]
+    fn test_validate_settings() {
+        assert!(Settings::default().validate().is_ok());
+
+        let mut settings = Settings::default();
+        settings.risk.risk_per_trade = -1.0;
+        assert!(settings.validate().is_err());
+
+        let mut settings = Settings::default();
+        settings.risk.initial_margin_rate = 1.5;
+        assert!(settings.validate().is_err());
+
+        let mut settings = Settings::default();
+        settings.risk.base_currency = "dollars".to_string();
+        assert!(settings.validate().is_err());
+
+        let mut settings = Settings::default();
+        settings.connection.live_address = "localhost".to_string();
+        assert!(settings.validate().unwrap_err().contains("ib_live_address"));
+
+        let mut settings = Settings::default();
+        settings.orders.commission_model = CommissionModel::PerShare { rate: -0.01, minimum: 1.0, maximum_pct: 1.0 };
+        assert!(settings.validate().is_err());
+    }
+}
@@ -16,6 +16,9 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     Validation(String),
     
+    #[error("Stop validation failed: {0}")]
+    StopLoss(crate::ib::position_sizing::StopLossViolation),
+    
     #[error("Not found: {0}")]
     NotFound(String),
     
@@ -24,6 +27,9 @@ pub enum AppError {
     
     #[error("Chart rendering error: {0}")]
     ChartError(String),
+    
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
 }
 
 impl From<String> for AppError {
@@ -0,0 +1,584 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use super::market_hours;
+use super::position_sizing::{calculate_default_stop_loss, StopRules};
+use super::types::{AtrSmoothing, HistoricalBar, OrderSide, OutlierMethod};
+
+/// Bars on each side a swing low (high) must undercut (exceed)
+pub const SWING_STRENGTH: usize = 2;
+
+/// How long a cached ATR is served before it is recalculated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtrCacheTtl {
+    Off,
+    Session,     // Until the next regular session opens and adds a bar
+    Hours(i64),
+}
+
+impl AtrCacheTtl {
+    /// `session`, a whole number of hours, or 0 to disable the cache
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "session" => AtrCacheTtl::Session,
+            hours => match hours.parse::<i64>() {
+                Ok(hours) if hours > 0 => AtrCacheTtl::Hours(hours),
+                _ => AtrCacheTtl::Off,
+            },
+        }
+    }
+
+    /// The value `parse` reads back
+    pub fn to_setting(self) -> String {
+        match self {
+            AtrCacheTtl::Off => "0".to_string(),
+            AtrCacheTtl::Session => "session".to_string(),
+            AtrCacheTtl::Hours(hours) => hours.to_string(),
+        }
+    }
+
+    pub fn is_fresh(&self, calculated_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        match self {
+            AtrCacheTtl::Off => false,
+            AtrCacheTtl::Session => now < market_hours::next_session_open(calculated_at),
+            AtrCacheTtl::Hours(hours) => now - calculated_at < Duration::hours(*hours),
+        }
+    }
+}
+
+/// True Range per bar, oldest first: the largest of high − low and the gaps from the
+/// previous close. The first bar has no previous close and falls back to high − low.
+pub fn true_ranges(bars: &[HistoricalBar]) -> Vec<f64> {
+    let mut previous_close: Option<f64> = None;
+    bars.iter()
+        .map(|bar| {
+            let range = bar.high - bar.low;
+            let tr = match previous_close {
+                Some(close) => range.max((bar.high - close).abs()).max((bar.low - close).abs()),
+                None => range,
+            };
+            previous_close = Some(bar.close);
+            tr
+        })
+        .collect()
+}
+
+/// Smoothed average of `values` (oldest first) over `period`. Wilder and EMA are seeded
+/// with the simple mean of the first `period` values and run through the rest; with fewer
+/// values than that, every method falls back to the simple mean of what there is.
+pub fn smooth(values: &[f64], period: usize, smoothing: AtrSmoothing) -> Option<f64> {
+    if values.is_empty() || period == 0 {
+        return None;
+    }
+    let mean = |slice: &[f64]| slice.iter().sum::<f64>() / slice.len() as f64;
+    if values.len() <= period {
+        return Some(mean(values));
+    }
+
+    let alpha = match smoothing {
+        AtrSmoothing::Simple => return Some(mean(&values[values.len() - period..])),
+        AtrSmoothing::Wilder => 1.0 / period as f64,
+        AtrSmoothing::Exponential => 2.0 / (period as f64 + 1.0),
+    };
+    let seed = mean(&values[..period]);
+    Some(values[period..].iter().fold(seed, |average, value| average + alpha * (value - average)))
+}
+
+/// ATR of `bars` (oldest first) from True Range
+pub fn atr(bars: &[HistoricalBar], period: usize, smoothing: AtrSmoothing) -> Option<f64> {
+    smooth(&true_ranges(bars), period, smoothing)
+}
+
+/// ATR as of each bar (oldest first) from per-bar True Ranges. A None range is left out of the
+/// average, so the previous value carries over that bar; None until `period` ranges are kept
+pub fn rolling_atr(ranges: &[Option<f64>], period: usize, smoothing: AtrSmoothing) -> Vec<Option<f64>> {
+    let mut kept = Vec::with_capacity(ranges.len());
+    ranges.iter()
+        .map(|range| {
+            kept.extend(*range);
+            if kept.len() < period.max(1) { None } else { smooth(&kept, period, smoothing) }
+        })
+        .collect()
+}
+
+/// Trading days per year, for annualizing daily volatility
+const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+/// Expected daily high − low of a random walk, in daily standard deviations (√(8/π), Parkinson)
+const RANGE_PER_SIGMA: f64 = 1.5958;
+
+/// How volatile a symbol has been, by several measures over the same daily bars
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolatilityProfile {
+    pub symbol: String,
+    pub bars: usize,
+    pub last_close: f64,
+    pub atr: f64,                    // Wilder ATR of True Range
+    pub atr_pct: f64,                // ATR as % of the last close
+    pub daily_volatility_pct: f64,   // Std-dev of daily log returns, %
+    pub historical_volatility: f64,  // The same annualized, %
+    pub average_gap: f64,            // Mean |open − previous close|
+    pub average_gap_pct: f64,        // The same as % of the previous close
+    pub range_percentile: f64,       // Where the latest bar's high − low ranks in the window, 0–100
+}
+
+impl VolatilityProfile {
+    /// Needs at least two bars (oldest first) for returns and gaps
+    pub fn from_bars(symbol: &str, bars: &[HistoricalBar], period: usize) -> Option<Self> {
+        let last = bars.last()?;
+        if bars.len() < 2 || last.close <= 0.0 {
+            return None;
+        }
+
+        let returns: Vec<f64> = bars.windows(2)
+            .filter(|pair| pair[0].close > 0.0 && pair[1].close > 0.0)
+            .map(|pair| (pair[1].close / pair[0].close).ln())
+            .collect();
+        let mean_return = returns.iter().sum::<f64>() / returns.len().max(1) as f64;
+        let variance = returns.iter().map(|r| (r - mean_return).powi(2)).sum::<f64>() / (returns.len().max(2) - 1) as f64;
+        let daily_volatility_pct = variance.sqrt() * 100.0;
+
+        let gaps: Vec<(f64, f64)> = bars.windows(2)
+            .map(|pair| ((pair[1].open - pair[0].close).abs(), pair[0].close))
+            .collect();
+        let average_gap = gaps.iter().map(|(gap, _)| gap).sum::<f64>() / gaps.len() as f64;
+        let average_gap_pct = gaps.iter()
+            .filter(|(_, close)| *close > 0.0)
+            .map(|(gap, close)| gap / close * 100.0)
+            .sum::<f64>() / gaps.len() as f64;
+
+        let latest_range = last.high - last.low;
+        let at_or_below = bars.iter().filter(|bar| bar.high - bar.low <= latest_range).count();
+        let atr = atr(bars, period, AtrSmoothing::Wilder)?;
+
+        Some(Self {
+            symbol: symbol.to_string(),
+            bars: bars.len(),
+            last_close: last.close,
+            atr,
+            atr_pct: atr / last.close * 100.0,
+            daily_volatility_pct,
+            historical_volatility: daily_volatility_pct * TRADING_DAYS_PER_YEAR.sqrt(),
+            average_gap,
+            average_gap_pct,
+            range_percentile: at_or_below as f64 / bars.len() as f64 * 100.0,
+        })
+    }
+
+    /// ATR over the daily range realized volatility implies; near 1 when they agree
+    pub fn atr_to_volatility_ratio(&self) -> Option<f64> {
+        let implied_range_pct = self.daily_volatility_pct * RANGE_PER_SIGMA;
+        (implied_range_pct > 0.0).then(|| self.atr_pct / implied_range_pct)
+    }
+
+    /// A warning when ATR and realized volatility differ by more than `max_ratio` either way
+    pub fn divergence_warning(&self, max_ratio: f64) -> Option<String> {
+        let ratio = self.atr_to_volatility_ratio()?;
+        if max_ratio <= 1.0 || (ratio <= max_ratio && ratio >= 1.0 / max_ratio) {
+            return None;
+        }
+        let reading = if ratio > 1.0 {
+            "wide intraday ranges on small closes; stops sized from ATR may be loose"
+        } else {
+            "close-to-close moves outrun the daily ranges; ATR may understate gap risk"
+        };
+        Some(format!(
+            "{}: ATR {:.2}% vs {:.2}% daily volatility ({:.1}x): {}",
+            self.symbol, self.atr_pct, self.daily_volatility_pct, ratio, reading
+        ))
+    }
+}
+
+/// A symbol whose range today has already run to several ATRs
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolatilitySpike {
+    pub symbol: String,
+    pub session: NaiveDate,
+    pub high: f64,
+    pub low: f64,
+    pub previous_close: Option<f64>,
+    pub range: f64,    // True range so far: a gap from the previous close counts
+    pub atr: f64,
+    pub multiple: f64, // range / atr
+    pub held: bool,    // An open position rather than a planned entry
+}
+
+impl VolatilitySpike {
+    /// A spike when today's true range reaches `threshold` ATRs. `bars` are today's
+    /// intraday bars and `last` the latest trade, which may run past them.
+    pub fn detect(
+        symbol: &str,
+        session: NaiveDate,
+        bars: &[HistoricalBar],
+        last: Option<f64>,
+        previous_close: Option<f64>,
+        atr: f64,
+        threshold: f64,
+    ) -> Option<Self> {
+        if atr <= 0.0 || threshold <= 0.0 {
+            return None;
+        }
+        let (low, high) = bars.iter()
+            .flat_map(|bar| [bar.low, bar.high])
+            .chain(last)
+            .filter(|price| *price > 0.0)
+            .fold(None, |extremes: Option<(f64, f64)>, price| {
+                Some(extremes.map_or((price, price), |(low, high)| (low.min(price), high.max(price))))
+            })?;
+        let range = high.max(previous_close.unwrap_or(high)) - low.min(previous_close.unwrap_or(low));
+        let multiple = range / atr;
+        (multiple >= threshold).then(|| Self {
+            symbol: symbol.to_string(),
+            session,
+            high,
+            low,
+            previous_close,
+            range,
+            atr,
+            multiple,
+            held: false,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let advice = if self.held { "consider tightening the stop" } else { "consider skipping new entries" };
+        format!(
+            "{}: range today {:.2} ({:.2}-{:.2}) is {:.1}x ATR {:.2}; {}",
+            self.symbol, self.range, self.low, self.high, self.multiple, self.atr, advice
+        )
+    }
+}
+
+/// Fewer ranges than this say too little about their distribution's shape
+const MIN_SHAPE_SAMPLE: usize = 20;
+
+/// Sample skewness and excess kurtosis; None for fewer than three values or no spread
+pub fn skewness_kurtosis(values: &[f64]) -> Option<(f64, f64)> {
+    let n = values.len() as f64;
+    if values.len() < 3 {
+        return None;
+    }
+    let mean = values.iter().sum::<f64>() / n;
+    let moment = |power: i32| values.iter().map(|v| (v - mean).powi(power)).sum::<f64>() / n;
+    let variance = moment(2);
+    if variance <= 0.0 {
+        return None;
+    }
+    Some((moment(3) / variance.powf(1.5), moment(4) / variance.powi(2) - 3.0))
+}
+
+/// The filter `OutlierMethod::Auto` applies to these ranges, and why:
+/// - too few ranges to judge their shape: IQR, which assumes none
+/// - roughly symmetric without heavy tails: Z-score, whose bounds then mean what they say
+/// - heavy tails: 5th–95th percentile, trimming a fixed share however many spikes there are
+/// - otherwise skewed: IQR, robust to the long side
+pub fn select_outlier_method(ranges: &[f64]) -> (OutlierMethod, String) {
+    let n = ranges.len();
+    let Some((skewness, kurtosis)) = skewness_kurtosis(ranges).filter(|_| n >= MIN_SHAPE_SAMPLE) else {
+        return (
+            OutlierMethod::IQR { multiplier: 1.5 },
+            format!("{} ranges are too few to judge the distribution; IQR assumes no shape", n),
+        );
+    };
+    let shape = format!("skewness {:.2}, excess kurtosis {:.2} over {} ranges", skewness, kurtosis, n);
+    if skewness.abs() < 0.5 && kurtosis < 1.0 {
+        (OutlierMethod::ZScore { threshold: 2.0 }, format!("{}: close to normal, so Z-score bounds hold", shape))
+    } else if kurtosis > 3.0 {
+        (OutlierMethod::Percentile { low: 5.0, high: 95.0 }, format!("{}: heavy tails, so a fixed 5% is trimmed each side", shape))
+    } else {
+        (OutlierMethod::IQR { multiplier: 1.5 }, format!("{}: skewed, so IQR bounds resist the long tail", shape))
+    }
+}
+
+/// Lows (long) or highs (short) of bars that are extremes against `strength` bars either side, oldest first
+pub fn swing_points(bars: &[HistoricalBar], side: OrderSide, strength: usize) -> Vec<f64> {
+    if bars.len() < strength * 2 + 1 {
+        return Vec::new();
+    }
+    (strength..bars.len() - strength)
+        .filter_map(|i| {
+            let neighbours = bars[i - strength..=i + strength].iter().enumerate().filter(|(j, _)| *j != strength);
+            match side {
+                OrderSide::Long => neighbours.clone().all(|(_, bar)| bar.low > bars[i].low).then_some(bars[i].low),
+                OrderSide::Short => neighbours.clone().all(|(_, bar)| bar.high < bars[i].high).then_some(bars[i].high),
+            }
+        })
+        .collect()
+}
+
+/// Pre-computed stop choices for an entry, for the template editor
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StopSuggestions {
+    pub atr: Option<f64>,
+    pub atr_stop: Option<f64>,       // Entry ∓ default_atr_fraction × ATR
+    pub swing_price: Option<f64>,    // Nearest swing low below (long) or high above (short) the entry
+    pub technical_stop: Option<f64>, // Just beyond the swing, by min_distance
+    pub max_distance: Option<f64>,   // Widest stop allowed: max_atr_fraction × ATR
+    pub widest_stop: Option<f64>,    // Entry ∓ max_distance
+    pub min_distance: f64,
+}
+
+impl StopSuggestions {
+    pub fn new(entry_price: f64, side: OrderSide, atr: Option<f64>, bars: &[HistoricalBar], rules: &StopRules) -> Self {
+        let swing_price = swing_points(bars, side, SWING_STRENGTH)
+            .into_iter()
+            .filter(|price| match side {
+                OrderSide::Long => *price < entry_price,
+                OrderSide::Short => *price > entry_price,
+            })
+            .min_by(|a, b| (entry_price - a).abs().total_cmp(&(entry_price - b).abs()));
+        let beyond = |price: f64, distance: f64| match side {
+            OrderSide::Long => price - distance,
+            OrderSide::Short => price + distance,
+        };
+        let max_distance = atr.map(|atr| atr * rules.max_atr_fraction);
+
+        Self {
+            atr,
+            atr_stop: atr.map(|atr| calculate_default_stop_loss(entry_price, side, atr, rules)),
+            swing_price,
+            technical_stop: swing_price.map(|price| beyond(price, rules.min_distance)),
+            max_distance,
+            widest_stop: max_distance.map(|distance| beyond(entry_price, distance)),
+            min_distance: rules.min_distance,
+        }
+    }
+}
+
+/// Today's volume so far against the usual volume by the same time of day
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RelativeVolume {
+    pub session: NaiveDate,
+    pub elapsed_minutes: i64,           // Into the session at the start of today's latest bar
+    pub cumulative_volume: i64,         // Today through that bar
+    pub average_cumulative_volume: f64, // Prior sessions through the same bar of their day
+    pub sessions: usize,                // Prior sessions averaged
+    pub ratio: Option<f64>,             // RVOL; None when the average is zero
+}
+
+impl RelativeVolume {
+    /// From intraday bars (oldest first) whose last session is today, averaging
+    /// up to `sessions` earlier sessions. Bars are matched by time since the open.
+    pub fn from_bars(bars: &[HistoricalBar], sessions: usize) -> Option<Self> {
+        let elapsed = |bar: &HistoricalBar| {
+            let session = market_hours::session_date(bar.timestamp);
+            (session, (bar.timestamp - market_hours::market_open_on(session)).num_minutes())
+        };
+        let (session, cutoff) = elapsed(bars.last()?);
+        if cutoff < 0 || sessions == 0 {
+            return None;
+        }
+
+        // Session -> volume through the cutoff, newest session first
+        let mut totals: Vec<(NaiveDate, i64)> = Vec::new();
+        for bar in bars.iter().rev() {
+            let (date, minutes) = elapsed(bar);
+            if !(0..=cutoff).contains(&minutes) {
+                continue;
+            }
+            match totals.last_mut() {
+                Some((last, volume)) if *last == date => *volume += bar.volume,
+                _ => totals.push((date, bar.volume)),
+            }
+        }
+        let (today, prior) = totals.split_first()?;
+        if today.0 != session || prior.is_empty() {
+            return None;
+        }
+        let prior = &prior[..prior.len().min(sessions)];
+        let average = prior.iter().map(|(_, volume)| *volume as f64).sum::<f64>() / prior.len() as f64;
+
+        Some(Self {
+            session,
+            elapsed_minutes: cutoff,
+            cumulative_volume: today.1,
+            average_cumulative_volume: average,
+            sessions: prior.len(),
+            ratio: (average > 0.0).then(|| today.1 as f64 / average),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn bar(day: i64, high: f64, low: f64, close: f64) -> HistoricalBar {
+        HistoricalBar {
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap() + Duration::days(day),
+            open: close,
+            high,
+            low,
+            close,
+            volume: 0,
+            wap: close,
+            count: 0,
+        }
+    }
+
+    #[test]
+    fn test_true_range() {
+        let bars = vec![
+            bar(0, 102.0, 100.0, 101.0),
+            bar(1, 106.0, 105.0, 105.5), // Gap up: 106 - 101
+            bar(2, 104.0, 103.0, 103.5), // Gap down: 105.5 - 103
+            bar(3, 106.0, 103.0, 105.0), // Inside the previous close: plain range
+        ];
+        assert_eq!(true_ranges(&bars), vec![2.0, 5.0, 2.5, 3.0]);
+    }
+
+    #[test]
+    fn test_smoothing() {
+        let values = [2.0, 4.0, 6.0, 8.0];
+        assert_eq!(smooth(&values, 2, AtrSmoothing::Simple), Some(7.0));
+        // Seed 3, then 3 + (6 - 3) / 2 = 4.5, then 4.5 + (8 - 4.5) / 2 = 6.25
+        assert_eq!(smooth(&values, 2, AtrSmoothing::Wilder), Some(6.25));
+        // Seed 3, alpha 2/3: 5, then 7
+        assert!((smooth(&values, 2, AtrSmoothing::Exponential).unwrap() - 7.0).abs() < 1e-9);
+        // Too few values for the period: the simple mean
+        assert_eq!(smooth(&values, 10, AtrSmoothing::Wilder), Some(5.0));
+        assert_eq!(smooth(&[], 14, AtrSmoothing::Simple), None);
+
+        // Rolling: the skipped range carries the previous value and the last matches `smooth`
+        let ranges = [Some(2.0), Some(4.0), None, Some(6.0), Some(8.0)];
+        assert_eq!(
+            rolling_atr(&ranges, 2, AtrSmoothing::Wilder),
+            vec![None, Some(3.0), Some(3.0), Some(4.5), Some(6.25)]
+        );
+    }
+
+    #[test]
+    fn test_volatility_profile() {
+        // Closes alternate 100 / 102 with 1.00 gaps at the open and 2.00 ranges
+        let bars: Vec<HistoricalBar> = (0..20)
+            .map(|day| {
+                let close = if day % 2 == 0 { 100.0 } else { 102.0 };
+                let mut bar = bar(day, close + 1.0, close - 1.0, close);
+                bar.open = 101.0;
+                bar
+            })
+            .collect();
+        let profile = VolatilityProfile::from_bars("XYZ", &bars, 14).unwrap();
+        assert_eq!(profile.bars, 20);
+        assert_eq!(profile.average_gap, 1.0);
+        // Every range is 2.00, so the latest ranks at the top
+        assert_eq!(profile.range_percentile, 100.0);
+        assert!(profile.historical_volatility > profile.daily_volatility_pct);
+
+        // ±2% a day against ~3% True Ranges agree well enough
+        let ratio = profile.atr_to_volatility_ratio().unwrap();
+        assert!((0.8..1.2).contains(&ratio));
+        assert!(profile.divergence_warning(2.0).is_none());
+        assert!(profile.divergence_warning(1.01).unwrap().contains("XYZ"));
+        assert!(profile.divergence_warning(0.0).is_none());
+
+        assert!(VolatilityProfile::from_bars("XYZ", &bars[..1], 14).is_none());
+    }
+
+    #[test]
+    fn test_stop_suggestions() {
+        // Swing lows at 95 (day 2) and 97 (day 6); swing highs at 104 (day 4) and 108 (day 8)
+        let levels = [(100.0, 98.0), (99.0, 96.0), (98.0, 95.0), (101.0, 97.0), (104.0, 99.0),
+            (102.0, 98.0), (100.0, 97.0), (103.0, 99.0), (108.0, 102.0), (106.0, 101.0), (105.0, 100.5)];
+        let bars: Vec<HistoricalBar> = levels.iter().enumerate()
+            .map(|(day, (high, low))| bar(day as i64, *high, *low, (high + low) / 2.0))
+            .collect();
+        assert_eq!(swing_points(&bars, OrderSide::Long, 2), vec![95.0, 97.0]);
+        assert_eq!(swing_points(&bars, OrderSide::Short, 2), vec![104.0, 108.0]);
+
+        let rules = StopRules { min_distance: 0.05, default_atr_fraction: 0.10, max_atr_fraction: 0.5 };
+        let long = StopSuggestions::new(103.0, OrderSide::Long, Some(4.0), &bars, &rules);
+        assert_eq!(long.swing_price, Some(97.0));
+        assert_eq!(long.technical_stop, Some(96.95));
+        assert_eq!(long.atr_stop, Some(102.6));
+        assert_eq!(long.widest_stop, Some(101.0));
+
+        // Nearest swing high above a short entry at 105
+        let short = StopSuggestions::new(105.0, OrderSide::Short, None, &bars, &rules);
+        assert_eq!(short.technical_stop, Some(108.05));
+        assert_eq!(short.atr_stop, None);
+        assert_eq!(short.max_distance, None);
+    }
+
+    #[test]
+    fn test_volatility_spike() {
+        let session = NaiveDate::from_ymd_opt(2025, 7, 25).unwrap();
+        let bars = vec![bar(0, 101.0, 99.0, 100.0), bar(0, 102.0, 100.0, 101.0)];
+
+        // 3.00 of range against a 2.00 ATR is 1.5x
+        assert_eq!(VolatilitySpike::detect("AAPL", session, &bars, None, None, 2.0, 2.0), None);
+        // The latest trade extends the range; a gap from yesterday's close counts too
+        let spike = VolatilitySpike::detect("AAPL", session, &bars, Some(103.0), None, 2.0, 2.0).unwrap();
+        assert_eq!((spike.low, spike.high, spike.multiple), (99.0, 103.0, 2.0));
+        let spike = VolatilitySpike::detect("AAPL", session, &bars, None, Some(97.0), 2.0, 2.0).unwrap();
+        assert_eq!(spike.range, 5.0);
+        assert!(spike.describe().contains("skipping new entries"));
+
+        assert_eq!(VolatilitySpike::detect("AAPL", session, &[], None, Some(97.0), 2.0, 2.0), None);
+        assert_eq!(VolatilitySpike::detect("AAPL", session, &bars, Some(110.0), None, 2.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_select_outlier_method() {
+        let (method, rationale) = select_outlier_method(&[1.0, 2.0, 3.0]);
+        assert_eq!(method, OutlierMethod::IQR { multiplier: 1.5 });
+        assert!(rationale.contains("too few"));
+
+        // Evenly spread: no skew, light tails
+        let even: Vec<f64> = (0..30).map(|i| 1.0 + i as f64 * 0.1).collect();
+        let (skewness, kurtosis) = skewness_kurtosis(&even).unwrap();
+        assert!(skewness.abs() < 1e-9 && kurtosis < 0.0);
+        assert!(matches!(select_outlier_method(&even).0, OutlierMethod::ZScore { .. }));
+
+        // One huge spike among steady ranges
+        let mut spiky = vec![2.0; 29];
+        spiky[10] = 2.1;
+        spiky.push(10.0);
+        assert_eq!(select_outlier_method(&spiky).0, OutlierMethod::Percentile { low: 5.0, high: 95.0 });
+
+        // A long right tail without extreme kurtosis
+        let skewed: Vec<f64> = (0..30).map(|i| if i < 20 { 1.0 + i as f64 * 0.01 } else { 1.5 + (i - 20) as f64 * 0.1 }).collect();
+        let (method, rationale) = select_outlier_method(&skewed);
+        assert_eq!(method, OutlierMethod::IQR { multiplier: 1.5 }, "{}", rationale);
+    }
+
+    #[test]
+    fn test_atr_cache_ttl() {
+        // Thursday 2025-07-24 after the close
+        let calculated_at = Utc.with_ymd_and_hms(2025, 7, 24, 22, 0, 0).unwrap();
+        let session = AtrCacheTtl::parse("session");
+        assert!(session.is_fresh(calculated_at, Utc.with_ymd_and_hms(2025, 7, 25, 13, 0, 0).unwrap()));
+        assert!(!session.is_fresh(calculated_at, Utc.with_ymd_and_hms(2025, 7, 25, 13, 30, 0).unwrap()));
+
+        let hours = AtrCacheTtl::parse("4");
+        assert_eq!(hours, AtrCacheTtl::Hours(4));
+        assert!(hours.is_fresh(calculated_at, calculated_at + Duration::hours(3)));
+        assert!(!hours.is_fresh(calculated_at, calculated_at + Duration::hours(4)));
+        assert!(!AtrCacheTtl::parse("0").is_fresh(calculated_at, calculated_at));
+    }
+
+    #[test]
+    fn test_relative_volume() {
+        let intraday = |day: u32, volumes: &[i64]| -> Vec<HistoricalBar> {
+            let open = market_hours::market_open_on(NaiveDate::from_ymd_opt(2025, 7, day).unwrap());
+            volumes.iter().enumerate().map(|(i, volume)| HistoricalBar {
+                timestamp: open + Duration::minutes(30 * i as i64),
+                volume: *volume,
+                ..bar(0, 1.0, 1.0, 1.0)
+            }).collect()
+        };
+        // Monday and Tuesday full mornings, Wednesday an hour in
+        let mut bars = intraday(14, &[100, 100, 100, 100]);
+        bars.extend(intraday(15, &[200, 200, 200, 200]));
+        bars.extend(intraday(16, &[300, 300]));
+
+        let rvol = RelativeVolume::from_bars(&bars, 20).unwrap();
+        assert_eq!((rvol.elapsed_minutes, rvol.cumulative_volume, rvol.sessions), (30, 600, 2));
+        assert_eq!(rvol.average_cumulative_volume, 300.0);
+        assert_eq!(rvol.ratio, Some(2.0));
+        assert_eq!(RelativeVolume::from_bars(&bars, 1).unwrap().ratio, Some(1.5));
+
+        // Nothing earlier to compare with
+        assert!(RelativeVolume::from_bars(&intraday(16, &[300, 300]), 20).is_none());
+    }
+}
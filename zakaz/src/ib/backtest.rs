@@ -0,0 +1,584 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::analytics;
+use super::market_hours;
+use super::orders::calculations::CommissionModel;
+use super::outcomes::RStats;
+use super::stop_management::{self, StopAction};
+use super::types::{
+    AtrSmoothing, EntryType, HistoricalBar, OrderSide, OrderTemplate, OrderTemplateStatus, StopRule, TimeInForce,
+    TradingModel, TrancheExit,
+};
+
+/// Daily bars before the replay start used to seed the ATR behind trailing stop rules
+pub const BACKTEST_ATR_PERIOD: usize = 14;
+
+/// Adverse price movement on fills that take liquidity: market and stop orders
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Slippage {
+    #[default]
+    None,
+    PerShare(f64),
+    Percent(f64),
+}
+
+impl Slippage {
+    pub fn amount(&self, price: f64) -> f64 {
+        match *self {
+            Slippage::None => 0.0,
+            Slippage::PerShare(amount) => amount,
+            Slippage::Percent(pct) => price * pct / 100.0,
+        }
+    }
+}
+
+/// How templates are replayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BacktestConfig {
+    pub max_entry_bars: usize, // How long a GTC entry waits to fill
+    pub max_hold_bars: usize,  // Positions still open after this many bars exit at the close
+    pub slippage: Slippage,
+    pub commissions: Option<CommissionModel>, // None: the configured commission_model
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            max_entry_bars: 10,
+            max_hold_bars: 20,
+            slippage: Slippage::None,
+            commissions: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    Stop,
+    Target,
+    Trailing,
+    TimeStop,
+    EndOfTest, // Held past max_hold_bars, or the bars ran out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BacktestExit {
+    pub date: NaiveDate,
+    pub quantity: f64,
+    pub price: f64,
+    pub reason: ExitReason,
+}
+
+/// One template's simulated trade
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestTrade {
+    pub template_id: String,
+    pub symbol: String,
+    pub model: TradingModel,
+    pub side: OrderSide,
+    pub entry_date: NaiveDate,
+    pub entry_price: f64,
+    pub quantity: f64,
+    pub exits: Vec<BacktestExit>,
+    pub commission: f64,
+    pub pnl: f64,          // Net of commission; slippage is already in the fill prices
+    pub initial_risk: f64, // Planned risk per share on the full size
+    pub r_multiple: f64,
+}
+
+/// What became of a replayed template
+#[derive(Debug, Clone, PartialEq)]
+pub enum Replay {
+    Traded(BacktestTrade),
+    Unfilled, // The entry never triggered while it was working
+}
+
+/// A plan's entry and exits replayed over daily bars (oldest first) from `bars[start]`.
+/// Bars before `start` only seed the ATR. Within a bar the path is unknown, so the
+/// stop is assumed to trade before any target, and targets don't fill on the entry bar.
+pub fn replay(template: &OrderTemplate, bars: &[HistoricalBar], start: usize, config: &BacktestConfig) -> Result<Replay, String> {
+    if template.quantity <= 0.0 {
+        return Err("No quantity to trade".to_string());
+    }
+    let risk_per_share = template.risk_per_share();
+    if risk_per_share <= 0.0 {
+        return Err("No risk between entry and stop".to_string());
+    }
+    if start >= bars.len() {
+        return Err("No bars to replay".to_string());
+    }
+
+    let entry_bars = match template.time_in_force {
+        TimeInForce::Day | TimeInForce::OPG | TimeInForce::IOC => 1,
+        TimeInForce::GTC | TimeInForce::GTD { .. } => config.max_entry_bars.max(1),
+    };
+    let expires_at = match template.time_in_force {
+        TimeInForce::GTD { expires_at } => Some(expires_at),
+        _ => template.expires_at,
+    };
+    let mut triggered = false;
+    let Some((entry_index, entry_price)) = bars[start..].iter()
+        .enumerate()
+        .take(entry_bars)
+        .take_while(|(_, bar)| expires_at.is_none_or(|at| bar.timestamp.date_naive() <= market_hours::session_date(at)))
+        .find_map(|(offset, bar)| entry_fill(template, bar, &mut triggered, config.slippage).map(|price| (start + offset, price)))
+    else {
+        return Ok(Replay::Unfilled);
+    };
+
+    let mut position = Position::open(template, entry_price);
+    let mut stop = template.get_stop_loss();
+    let mut stop_rules = template.stop_rules.clone();
+    let entry_date = bars[entry_index].timestamp.date_naive();
+    let last_index = (entry_index + config.max_hold_bars.max(1)).min(bars.len() - 1);
+    let adverse = |price: f64| match template.side {
+        OrderSide::Long => price - config.slippage.amount(price),
+        OrderSide::Short => price + config.slippage.amount(price),
+    };
+
+    for (index, bar) in bars.iter().enumerate().take(last_index + 1).skip(entry_index) {
+        let date = bar.timestamp.date_naive();
+        let on_entry_bar = index == entry_index;
+
+        if let Some(price) = stop_fill(template.side, bar, stop) {
+            position.close_protected(date, adverse(price), ExitReason::Stop);
+        }
+        for runner in position.runners.iter_mut().filter(|r| !r.closed) {
+            let trail_stop = match template.side {
+                OrderSide::Long => runner.best - runner.trail_amount,
+                OrderSide::Short => runner.best + runner.trail_amount,
+            };
+            if let Some(price) = stop_fill(template.side, bar, trail_stop) {
+                runner.closed = true;
+                position.exits.push(BacktestExit { date, quantity: runner.quantity, price: adverse(price), reason: ExitReason::Trailing });
+            }
+        }
+        if !on_entry_bar {
+            for target in position.targets.iter_mut().filter(|t| !t.closed) {
+                let fill = match template.side {
+                    OrderSide::Long => (bar.high >= target.price).then(|| bar.open.max(target.price)),
+                    OrderSide::Short => (bar.low <= target.price).then(|| bar.open.min(target.price)),
+                };
+                if let Some(price) = fill {
+                    target.closed = true;
+                    position.exits.push(BacktestExit { date, quantity: target.quantity, price, reason: ExitReason::Target });
+                }
+            }
+        }
+        for runner in position.runners.iter_mut().filter(|r| !r.closed) {
+            runner.best = match template.side {
+                OrderSide::Long => runner.best.max(bar.high),
+                OrderSide::Short => runner.best.min(bar.low),
+            };
+        }
+        if position.is_flat() {
+            break;
+        }
+
+        // Stop rules act on the close, as the scheduler would at the end of the day
+        if !stop_rules.is_empty() {
+            let mut managed = template.clone();
+            managed.status = OrderTemplateStatus::Filled;
+            managed.filled_quantity = template.quantity;
+            managed.avg_fill_price = Some(entry_price);
+            managed.risk_per_trade = risk_per_share * template.quantity;
+            managed.filled_at = Some(market_hours::market_close_on(entry_date));
+            managed.stop_price = stop;
+            managed.technical_stop_price = None;
+            managed.stop_rules = stop_rules.clone();
+            let trails = stop_rules.iter().any(|r| matches!(r, StopRule::TrailAtr { .. }));
+            let atr = if trails { analytics::atr(&bars[..=index], BACKTEST_ATR_PERIOD, AtrSmoothing::Wilder) } else { None };
+            match stop_management::evaluate(&managed, bar.close, atr, market_hours::market_close_on(date)) {
+                Some(StopAction::MoveStop { price, rule }) => {
+                    stop = price;
+                    if rule.fires_once() {
+                        stop_rules.retain(|r| *r != rule);
+                    }
+                }
+                Some(StopAction::Exit { .. }) => {
+                    position.close_all(date, adverse(bar.close), ExitReason::TimeStop);
+                    break;
+                }
+                None => {}
+            }
+        }
+
+        if index == last_index {
+            position.close_all(date, bar.close, ExitReason::EndOfTest);
+        }
+    }
+
+    let commissions = config.commissions.unwrap_or_default();
+    let commission = commissions.commission(template.quantity, entry_price)
+        + position.exits.iter().map(|e| commissions.commission(e.quantity, e.price)).sum::<f64>();
+    let direction = match template.side {
+        OrderSide::Long => 1.0,
+        OrderSide::Short => -1.0,
+    };
+    let gross: f64 = position.exits.iter().map(|e| e.quantity * (e.price - entry_price) * direction).sum();
+    let initial_risk = risk_per_share * template.quantity;
+    let pnl = gross - commission;
+
+    Ok(Replay::Traded(BacktestTrade {
+        template_id: template.id.clone(),
+        symbol: template.symbol.clone(),
+        model: template.model,
+        side: template.side,
+        entry_date,
+        entry_price,
+        quantity: template.quantity,
+        exits: position.exits,
+        commission,
+        pnl,
+        initial_risk,
+        r_multiple: pnl / initial_risk,
+    }))
+}
+
+/// Entry price if the parent order fills on `bar`. A stop-limit that gaps past its
+/// limit stays `triggered` and works as a limit order on later bars.
+fn entry_fill(template: &OrderTemplate, bar: &HistoricalBar, triggered: &mut bool, slippage: Slippage) -> Option<f64> {
+    let long = template.side == OrderSide::Long;
+    let adverse = |price: f64| if long { price + slippage.amount(price) } else { price - slippage.amount(price) };
+    let trigger = template.limit_price;
+    let touched = |price: f64| if long { bar.high >= price } else { bar.low <= price };
+    let reached = |price: f64| if long { bar.low <= price } else { bar.high >= price };
+    // Where a resting order fills: at its price, or better if the bar opens through it
+    let better = |price: f64| if long { bar.open.min(price) } else { bar.open.max(price) };
+    let worse = |price: f64| if long { bar.open.max(price) } else { bar.open.min(price) };
+
+    match template.entry_type {
+        EntryType::Market => Some(adverse(bar.open)),
+        EntryType::Limit => reached(trigger).then(|| better(trigger)),
+        EntryType::Stop => touched(trigger).then(|| adverse(worse(trigger))),
+        EntryType::StopLimit { .. } => {
+            let limit = template.entry_limit_price()?;
+            if *triggered {
+                return reached(limit).then(|| better(limit));
+            }
+            if !touched(trigger) {
+                return None;
+            }
+            *triggered = true;
+            let price = worse(trigger);
+            let within_limit = if long { price <= limit } else { price >= limit };
+            if within_limit {
+                Some(price)
+            } else {
+                reached(limit).then_some(limit)
+            }
+        }
+    }
+}
+
+/// Where a protective stop at `stop` fills on `bar`, if it's hit; a gap through it fills at the open
+fn stop_fill(side: OrderSide, bar: &HistoricalBar, stop: f64) -> Option<f64> {
+    match side {
+        OrderSide::Long => (bar.low <= stop).then(|| bar.open.min(stop)),
+        OrderSide::Short => (bar.high >= stop).then(|| bar.open.max(stop)),
+    }
+}
+
+struct TargetLeg {
+    quantity: f64,
+    price: f64,
+    closed: bool,
+}
+
+struct RunnerLeg {
+    quantity: f64,
+    trail_amount: f64,
+    best: f64, // Most favourable price since entry
+    closed: bool,
+}
+
+/// An open simulated position, split the way the live orders would split it
+struct Position {
+    core: f64, // Shares outside every tranche, exited only by the stop
+    targets: Vec<TargetLeg>,
+    runners: Vec<RunnerLeg>,
+    exits: Vec<BacktestExit>,
+}
+
+impl Position {
+    fn open(template: &OrderTemplate, entry_price: f64) -> Self {
+        let mut position = Position { core: template.quantity, targets: Vec::new(), runners: Vec::new(), exits: Vec::new() };
+        for (tranche, quantity) in template.exit_tranches.iter().zip(template.tranche_quantities()) {
+            if quantity <= 0.0 {
+                continue;
+            }
+            position.core -= quantity;
+            match tranche.exit {
+                TrancheExit::Target { .. } => position.targets.push(TargetLeg {
+                    quantity,
+                    price: template.tranche_target_price(tranche).unwrap_or(entry_price),
+                    closed: false,
+                }),
+                TrancheExit::Trailing { trail_amount } => position.runners.push(RunnerLeg {
+                    quantity,
+                    trail_amount,
+                    best: entry_price,
+                    closed: false,
+                }),
+            }
+        }
+        position.core = position.core.max(0.0);
+        position
+    }
+
+    /// Exit everything the fixed stop covers: the core and unfilled targets
+    fn close_protected(&mut self, date: NaiveDate, price: f64, reason: ExitReason) {
+        let mut quantity = std::mem::take(&mut self.core);
+        for target in self.targets.iter_mut().filter(|t| !t.closed) {
+            target.closed = true;
+            quantity += target.quantity;
+        }
+        if quantity > 0.0 {
+            self.exits.push(BacktestExit { date, quantity, price, reason });
+        }
+    }
+
+    fn close_all(&mut self, date: NaiveDate, price: f64, reason: ExitReason) {
+        self.close_protected(date, price, reason);
+        let mut quantity = 0.0;
+        for runner in self.runners.iter_mut().filter(|r| !r.closed) {
+            runner.closed = true;
+            quantity += runner.quantity;
+        }
+        if quantity > 0.0 {
+            self.exits.push(BacktestExit { date, quantity, price, reason });
+        }
+    }
+
+    fn is_flat(&self) -> bool {
+        self.core <= 0.0 && self.targets.iter().all(|t| t.closed) && self.runners.iter().all(|r| r.closed)
+    }
+}
+
+/// Results for one trading model
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelStats {
+    pub model: TradingModel,
+    pub r: RStats, // Win rate, expectancy (average R) and the R distribution
+    pub net_pnl: f64,
+    pub max_drawdown: f64,   // Largest fall from a peak of cumulative P&L, trades in exit order
+    pub max_drawdown_r: f64, // The same in R
+    pub unfilled: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BacktestReport {
+    pub trades: Vec<BacktestTrade>,
+    pub unfilled: Vec<(String, TradingModel)>, // Templates whose entry never triggered
+    pub skipped: Vec<(String, String)>,        // Templates that couldn't be replayed, and why
+    pub models: Vec<ModelStats>,
+}
+
+impl BacktestReport {
+    pub fn new(
+        mut trades: Vec<BacktestTrade>,
+        unfilled: Vec<(String, TradingModel)>,
+        skipped: Vec<(String, String)>,
+    ) -> Self {
+        trades.sort_by_key(|t| (t.exits.last().map(|e| e.date), t.entry_date));
+        let mut models: Vec<TradingModel> = trades.iter().map(|t| t.model).collect();
+        models.sort_by_key(|m| format!("{:?}", m));
+        models.dedup();
+
+        let models = models.into_iter()
+            .filter_map(|model| {
+                let model_trades: Vec<&BacktestTrade> = trades.iter().filter(|t| t.model == model).collect();
+                let r_multiples: Vec<f64> = model_trades.iter().map(|t| t.r_multiple).collect();
+                Some(ModelStats {
+                    model,
+                    r: RStats::from_r_multiples(&r_multiples)?,
+                    net_pnl: model_trades.iter().map(|t| t.pnl).sum(),
+                    max_drawdown: max_drawdown(model_trades.iter().map(|t| t.pnl)),
+                    max_drawdown_r: max_drawdown(r_multiples.iter().copied()),
+                    unfilled: unfilled.iter().filter(|(_, m)| *m == model).count(),
+                })
+            })
+            .collect();
+
+        Self { trades, unfilled, skipped, models }
+    }
+
+    /// R of every replayed trade, in exit order, e.g. for a Monte Carlo run
+    pub fn r_multiples(&self) -> Vec<f64> {
+        self.trades.iter().map(|t| t.r_multiple).collect()
+    }
+}
+
+/// Largest peak-to-trough fall of the running total of `results`
+pub fn max_drawdown(results: impl IntoIterator<Item = f64>) -> f64 {
+    let (mut total, mut peak, mut drawdown) = (0.0_f64, 0.0_f64, 0.0_f64);
+    for result in results {
+        total += result;
+        peak = peak.max(total);
+        drawdown = drawdown.max(peak - total);
+    }
+    drawdown
+}
+
+/// Index of the first bar on or after `from`'s session; daily bars are stamped at midnight UTC
+pub fn start_index(bars: &[HistoricalBar], from: DateTime<Utc>) -> usize {
+    let session = market_hours::session_date(from);
+    bars.iter().position(|bar| bar.timestamp.date_naive() >= session).unwrap_or(bars.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ib::types::ExitTranche;
+    use chrono::{Duration, TimeZone};
+
+    fn bar(day: i64, open: f64, high: f64, low: f64, close: f64) -> HistoricalBar {
+        HistoricalBar {
+            timestamp: Utc.with_ymd_and_hms(2025, 7, 21, 0, 0, 0).unwrap() + Duration::days(day),
+            open,
+            high,
+            low,
+            close,
+            volume: 0,
+            wap: close,
+            count: 0,
+        }
+    }
+
+    fn long_plan() -> OrderTemplate {
+        // Buy 100 at 100 limit, stop 98: 2.00 risk per share, half out at 2R
+        let mut template = OrderTemplate::new(
+            "MSFT long".to_string(),
+            "MSFT".to_string(),
+            OrderSide::Long,
+            100.0,
+            100.0,
+            98.0,
+            TimeInForce::GTC,
+            TradingModel::Breakout,
+        );
+        template.exit_tranches = vec![ExitTranche::target(0.5, 2.0)];
+        template
+    }
+
+    fn traded(replay: Result<Replay, String>) -> BacktestTrade {
+        match replay.unwrap() {
+            Replay::Traded(trade) => trade,
+            Replay::Unfilled => panic!("entry should fill"),
+        }
+    }
+
+    #[test]
+    fn test_replay_target_then_stop() {
+        let bars = vec![
+            bar(0, 101.0, 102.0, 100.5, 101.5), // Entry not reached
+            bar(1, 101.0, 101.5, 99.5, 101.0),  // Fills at 100
+            bar(2, 101.0, 104.5, 100.5, 104.0), // Target 104 takes half
+            bar(3, 103.0, 103.0, 97.0, 97.5),   // Gap-free stop at 98 takes the rest
+        ];
+        let config = BacktestConfig { commissions: Some(CommissionModel::PerOrder { amount: 1.0 }), ..Default::default() };
+        let trade = traded(replay(&long_plan(), &bars, 0, &config));
+        assert_eq!(trade.entry_price, 100.0);
+        assert_eq!(trade.entry_date, bars[1].timestamp.date_naive());
+        assert_eq!(trade.exits.len(), 2);
+        assert_eq!((trade.exits[0].price, trade.exits[0].reason), (104.0, ExitReason::Target));
+        assert_eq!((trade.exits[1].price, trade.exits[1].reason), (98.0, ExitReason::Stop));
+        // +200 on the target, -100 on the stop, three orders at 1.00
+        assert_eq!(trade.pnl, 97.0);
+        assert_eq!(trade.r_multiple, 97.0 / 200.0);
+    }
+
+    #[test]
+    fn test_replay_fills() {
+        let mut template = long_plan();
+        template.exit_tranches.clear();
+        // A gap down through the stop fills at the open, slipped further
+        let bars = vec![bar(0, 100.5, 101.0, 99.8, 100.2), bar(1, 96.0, 97.0, 95.0, 96.5)];
+        let config = BacktestConfig { slippage: Slippage::PerShare(0.25), ..Default::default() };
+        let trade = traded(replay(&template, &bars, 0, &config));
+        assert_eq!(trade.exits[0].price, 95.75);
+        assert_eq!(trade.pnl, -425.0);
+
+        // A day order that doesn't fill on its first bar never does
+        template.time_in_force = TimeInForce::Day;
+        let bars = vec![bar(0, 101.0, 102.0, 100.5, 101.5), bar(1, 100.5, 101.0, 99.0, 100.0)];
+        assert_eq!(replay(&template, &bars, 0, &BacktestConfig::default()), Ok(Replay::Unfilled));
+
+        // A buy stop gapping over its trigger fills at the open
+        template.entry_type = EntryType::Stop;
+        let bars = vec![bar(0, 101.0, 102.0, 100.5, 101.5)];
+        assert_eq!(traded(replay(&template, &bars, 0, &BacktestConfig::default())).entry_price, 101.0);
+        // ...but a stop-limit gapping past its limit waits for the limit
+        template.entry_type = EntryType::StopLimit { limit_offset: 0.5 };
+        assert_eq!(traded(replay(&template, &bars, 0, &BacktestConfig::default())).entry_price, 100.5);
+
+        // Held past max_hold_bars: out at the close
+        template.entry_type = EntryType::Market;
+        let bars = vec![bar(0, 100.0, 100.5, 99.5, 100.0), bar(1, 100.0, 101.0, 99.5, 100.8), bar(2, 101.0, 102.0, 100.0, 101.0)];
+        let config = BacktestConfig { max_hold_bars: 1, ..Default::default() };
+        let trade = traded(replay(&template, &bars, 0, &config));
+        assert_eq!(trade.exits, vec![BacktestExit {
+            date: bars[1].timestamp.date_naive(),
+            quantity: 100.0,
+            price: 100.8,
+            reason: ExitReason::EndOfTest,
+        }]);
+
+        assert!(replay(&template, &bars, 3, &config).is_err());
+    }
+
+    #[test]
+    fn test_replay_stop_rules() {
+        let mut template = long_plan();
+        template.exit_tranches.clear();
+        template.stop_rules = vec![StopRule::Breakeven { after_r: 1.0 }];
+        let bars = vec![
+            bar(0, 100.0, 100.5, 99.5, 100.0),  // Fills at 100
+            bar(1, 100.5, 103.0, 100.2, 102.5), // Closes above 1R: stop to 100
+            bar(2, 102.0, 102.0, 99.0, 99.5),   // Out at breakeven, not at 98
+        ];
+        let trade = traded(replay(&template, &bars, 0, &BacktestConfig::default()));
+        assert_eq!((trade.exits[0].price, trade.exits[0].reason), (100.0, ExitReason::Stop));
+        assert_eq!(trade.pnl, 0.0);
+    }
+
+    #[test]
+    fn test_report() {
+        let trade = |model: TradingModel, day: i64, r: f64| BacktestTrade {
+            template_id: format!("t{}", day),
+            symbol: "MSFT".to_string(),
+            model,
+            side: OrderSide::Long,
+            entry_date: bar(day, 0.0, 0.0, 0.0, 0.0).timestamp.date_naive(),
+            entry_price: 100.0,
+            quantity: 100.0,
+            exits: vec![BacktestExit { date: bar(day + 1, 0.0, 0.0, 0.0, 0.0).timestamp.date_naive(), quantity: 100.0, price: 100.0, reason: ExitReason::Stop }],
+            commission: 0.0,
+            pnl: r * 200.0,
+            initial_risk: 200.0,
+            r_multiple: r,
+        };
+        let report = BacktestReport::new(
+            vec![
+                trade(TradingModel::Breakout, 3, -1.0),
+                trade(TradingModel::Breakout, 1, 2.0),
+                trade(TradingModel::Breakout, 2, -1.0),
+                trade(TradingModel::FalseBreakout, 1, 1.0),
+            ],
+            vec![("t9".to_string(), TradingModel::Breakout)],
+            Vec::new(),
+        );
+        assert_eq!(report.trades[0].template_id, "t1");
+        let breakout = report.models.iter().find(|m| m.model == TradingModel::Breakout).unwrap();
+        assert_eq!(breakout.r.trades, 3);
+        assert_eq!(breakout.r.average_r, 0.0);
+        // +2R, then two losers in a row
+        assert_eq!(breakout.max_drawdown_r, 2.0);
+        assert_eq!(breakout.max_drawdown, 400.0);
+        assert_eq!(breakout.unfilled, 1);
+        assert_eq!(report.models.len(), 2);
+
+        assert_eq!(max_drawdown([-1.0, 1.0, 3.0, -2.0]), 2.0);
+    }
+}
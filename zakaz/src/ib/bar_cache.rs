@@ -0,0 +1,85 @@
+use chrono::{DateTime, Duration, Utc};
+
+/// One historical data request: `days` calendar days back from `end`, or from now when None
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BarRequest {
+    pub end: Option<DateTime<Utc>>,
+    pub days: u32,
+}
+
+/// The requests that fill in bars from `from` to `now` around the span already cached,
+/// `covered` as (from, to). The newest bars are always asked for again, since the last one
+/// keeps changing until its interval closes. A cached span that doesn't reach `from` or
+/// overlaps nothing requested is ignored and the whole span fetched.
+pub fn missing_requests(covered: Option<(DateTime<Utc>, DateTime<Utc>)>, from: DateTime<Utc>, now: DateTime<Utc>) -> Vec<BarRequest> {
+    let Some((covered_from, covered_to)) = covered.filter(|(covered_from, covered_to)| *covered_to >= from && *covered_from <= now) else {
+        return vec![BarRequest { end: None, days: days_between(from, now) }];
+    };
+
+    let mut requests = Vec::new();
+    if from < covered_from {
+        requests.push(BarRequest { end: Some(covered_from), days: days_between(from, covered_from) });
+    }
+    requests.push(BarRequest { end: None, days: days_between(covered_to.max(from), now) });
+    requests
+}
+
+/// The span cached once `requests` have been stored on top of `covered`
+pub fn covered_after(covered: Option<(DateTime<Utc>, DateTime<Utc>)>, requests: &[BarRequest], now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let fetched_from = requests.iter()
+        .map(|r| r.end.unwrap_or(now) - Duration::days(r.days as i64))
+        .min()
+        .unwrap_or(now);
+    match covered {
+        Some((covered_from, covered_to)) if covered_to >= fetched_from => (covered_from.min(fetched_from), now),
+        _ => (fetched_from, now),
+    }
+}
+
+/// Whole days from `start` to `end`, rounded up and at least one
+fn days_between(start: DateTime<Utc>, end: DateTime<Utc>) -> u32 {
+    let seconds = (end - start).num_seconds().max(0) as u64;
+    (seconds.div_ceil(86_400) as u32).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 7, d, 20, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_missing_requests() {
+        let now = day(20);
+
+        // Nothing cached: the whole span
+        assert_eq!(missing_requests(None, day(10), now), vec![BarRequest { end: None, days: 10 }]);
+
+        // Cached up to two days ago: only the last two days
+        let covered = Some((day(5), day(18)));
+        assert_eq!(missing_requests(covered, day(10), now), vec![BarRequest { end: None, days: 2 }]);
+        // Asking further back fetches the older part too
+        assert_eq!(
+            missing_requests(covered, day(1), now),
+            vec![BarRequest { end: Some(day(5)), days: 4 }, BarRequest { end: None, days: 2 }],
+        );
+        // A cache ending before the span starts doesn't help
+        assert_eq!(missing_requests(Some((day(1), day(3))), day(10), now), vec![BarRequest { end: None, days: 10 }]);
+        // Fresh cache still refreshes the forming bar
+        assert_eq!(missing_requests(Some((day(5), now)), day(10), now), vec![BarRequest { end: None, days: 1 }]);
+    }
+
+    #[test]
+    fn test_covered_after() {
+        let now = day(20);
+        let covered = Some((day(5), day(18)));
+        assert_eq!(covered_after(covered, &missing_requests(covered, day(1), now), now), (day(1), now));
+        assert_eq!(covered_after(covered, &missing_requests(covered, day(10), now), now), (day(5), now));
+        // A stale cache that doesn't reach the new span is replaced
+        let stale = Some((day(1), day(3)));
+        assert_eq!(covered_after(stale, &missing_requests(stale, day(10), now), now), (day(10), now));
+    }
+}
@@ -1,21 +1,61 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 
 use ibapi::{contracts::Contract, orders, Client};
+use ibapi::contracts::tick_types::TickType;
+use ibapi::market_data::realtime::TickTypes;
 use ibapi::prelude::{HistoricalBarSize, HistoricalWhatToShow};
-use tokio::sync::{Mutex, RwLock};
+use ibapi::market_data::realtime::{BarSize as RealtimeBarSize, WhatToShow as RealtimeWhatToShow};
+use tokio::sync::{mpsc, Mutex, RwLock};
 
+use crate::db::{backup, Database};
+use crate::db::export::CsvTable;
+use crate::db::search::SearchHit;
+use crate::db::settings::Settings;
+use crate::db::models::{model_to_str, DbActiveOrder, DbAtrCache, DbAuditEntry, DbBar, DbBasket, DbChecklistItem, DbDrawing, DbEarningsEvent, DbExecution, DbOrderTemplate, DbSector, DbSymbolSettings, DbTheme, DbTrade, DbTradeOutcome, DbWatchlist, DbWatchlistEntry};
+use crate::charts::theme::{ChartTheme, ColorSchemePreference, BUILTIN_THEMES};
+use crate::charts::types::{Drawing, DrawingShape};
 use crate::error::AppError;
 use crate::{err, inf, wrn};
+use super::analytics::{self, AtrCacheTtl, RelativeVolume, StopSuggestions, VolatilityProfile, VolatilitySpike};
+use super::backtest::{self, BACKTEST_ATR_PERIOD};
+use super::bar_cache;
+use super::earnings::{self, EarningsEvent, EarningsPolicy};
+use super::fx::FxRates;
+use super::gaps::GapStats;
+use super::live_guard::{LiveAction, LiveConfirmation, LiveOrderLimits, PendingConfirmations};
+use super::market_hours;
+use super::orders::calculations::{CommissionModel, TradeCosts};
+use super::orphans::{self, OpenOrderInfo, PositionInfo};
+use super::journal::{self, Trade, TradePlan};
+use super::outcomes::{RStats, TradeOutcome};
+use super::monte_carlo::{self, MonteCarloConfig, MonteCarloResult};
+use super::performance::{PerformanceRange, PerformanceStats};
+use super::stop_management::{self, StopAction};
+use super::position_sizing::{
+    calculate_kelly_risk, calculate_position_size, calculate_volatility_normalized_size, check_stop_loss, fit_to_account, AccountCapacity,
+    ModelDefaults, SizingResult, StopRules, TradeStats, MIN_KELLY_TRADES,
+};
+use super::scanner::{ScanCriteria, ScanResult};
+use super::risk::{
+    return_correlation, CorrelationWarning, DailyPnL, Drawdown, ExposureCaps, MarkedPosition, PositionLimits, RiskScaling, RiskSummary, ShortCheck, TradingLock, TradingLockChange,
+};
 use super::messages::{ConnectionStatus, MarketData};
-use super::types::{ATRResult, ExcludedBar, HistoricalBar, HistoricalData, OrderTemplate, OrderTemplateStatus, OutlierMethod};
+use super::types::{
+    ATRResult, AtrSmoothing, AuditAction, AuditEntry, AuditFilter, AuditPage, AuditSource, BasketResult, ChecklistAnswer, ChecklistItem, EntryType, ExcludedBar, ExitTranche, Fill, HistoricalBar, HistoricalData, OrderLeg, OrderRef, OrderSide,
+    OrderTemplate, OrderTemplateStatus, OutlierMethod, RepriceProposal, Sector, ShortAvailability, SizingStrategy, StopRule, SymbolSettings, TemplateBasket, TemplateFieldChange, TemplateRevision,
+    TimeInForce, TradingModel, TrancheExit, Watchlist, WatchlistAtr, WatchlistEntry, DEFAULT_WATCHLIST_ID,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AccountType {
     Paper,
     Live,
 }
 
+/// RTH daily bars per symbol and when they were fetched
+type DailyBarCache = HashMap<String, (chrono::DateTime<chrono::Utc>, Vec<HistoricalBar>)>;
+
 pub struct IBClient {
     paper_client: Option<Arc<Mutex<Client>>>,
     live_client: Option<Arc<Mutex<Client>>>,
@@ -24,8 +64,50 @@ pub struct IBClient {
     active_orders: Arc<Mutex<HashMap<i32, String>>>, // order_id -> template_id
     market_data: Arc<RwLock<HashMap<String, MarketData>>>,
     next_order_id: Arc<Mutex<i32>>,
+    atr_results: Arc<RwLock<HashMap<String, ATRResult>>>, // Latest valid ATR per symbol
+    daily_bars: Arc<RwLock<DailyBarCache>>,
+    trading_lock: Arc<RwLock<Option<TradingLock>>>, // Set by the daily loss limit
+    live_confirmations: Arc<Mutex<PendingConfirmations>>,
+    drawdowns: Arc<RwLock<HashMap<AccountType, Drawdown>>>, // Equity high-water mark per account
+    fx_rates: Arc<RwLock<FxRates>>,
+    spike_checked_at: Arc<RwLock<Option<chrono::DateTime<chrono::Utc>>>>,
+    spike_alerts: Arc<RwLock<HashMap<String, chrono::NaiveDate>>>, // Session each symbol last alerted in
+    scan_results: Arc<RwLock<HashMap<String, ScanResult>>>, // Latest passing scan per symbol
+    stream_client_ids: Arc<std::sync::atomic::AtomicI32>, // Next client id for a live chart stream
+    settings: Arc<RwLock<Option<Settings>>>, // Read from the database on first use
+    db: Option<Arc<Mutex<Database>>>,
 }
 
+/// Equity is re-read for the drawdown at most this often
+const DRAWDOWN_REFRESH_MINUTES: i64 = 5;
+
+/// Watched symbols are checked for volatility spikes at most this often
+const VOLATILITY_SPIKE_CHECK_MINUTES: i64 = 5;
+
+/// Client ids of live chart streams start here, clear of the trading connections
+const FIRST_STREAM_CLIENT_ID: i32 = 110;
+
+/// How often a live chart stream checks whether its chart still wants bars
+const STREAM_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How long to wait for IB's shortable tick or a what-if order response
+const SHORT_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Earlier sessions the relative volume on market data updates is measured against
+const MARKET_DATA_RVOL_SESSIONS: usize = 20;
+
+/// How long a quote snapshot may take to complete
+const QUOTE_SNAPSHOT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How long to wait for IB's first account P&L update
+const PNL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Daily bars searched for the swing behind a technical stop
+const STOP_SWING_WINDOW: usize = 30;
+
+/// ATR results older than this are recomputed before validating stops
+const ATR_MAX_AGE_HOURS: i64 = 24;
+
 impl std::fmt::Debug for IBClient {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("IBClient")
@@ -34,6 +116,7 @@ impl std::fmt::Debug for IBClient {
             .field("active_account", &"<async>")
             .field("templates_count", &"<async>")
             .field("active_orders_count", &"<async>")
+            .field("persistent", &self.db.is_some())
             .finish()
     }
 }
@@ -48,21 +131,290 @@ impl IBClient {
             active_orders: Arc::new(Mutex::new(HashMap::new())),
             market_data: Arc::new(RwLock::new(HashMap::new())),
             next_order_id: Arc::new(Mutex::new(1000)),
+            atr_results: Arc::new(RwLock::new(HashMap::new())),
+            daily_bars: Arc::new(RwLock::new(HashMap::new())),
+            trading_lock: Arc::new(RwLock::new(None)),
+            live_confirmations: Arc::new(Mutex::new(PendingConfirmations::default())),
+            drawdowns: Arc::new(RwLock::new(HashMap::new())),
+            fx_rates: Arc::new(RwLock::new(FxRates::default())),
+            spike_checked_at: Arc::new(RwLock::new(None)),
+            spike_alerts: Arc::new(RwLock::new(HashMap::new())),
+            scan_results: Arc::new(RwLock::new(HashMap::new())),
+            stream_client_ids: Arc::new(std::sync::atomic::AtomicI32::new(FIRST_STREAM_CLIENT_ID)),
+            settings: Arc::new(RwLock::new(None)),
+            db: None,
+        }
+    }
+    
+    /// Create a client whose templates are persisted to the database
+    pub fn with_database(db: Arc<Mutex<Database>>) -> Self {
+        let mut client = Self::new();
+        client.db = Some(db);
+        client
+    }
+    
+    /// Current settings. Read from the database once and kept; the defaults without one, or
+    /// while it can't be read.
+    pub async fn settings(&self) -> Settings {
+        if let Some(settings) = self.settings.read().await.as_ref() {
+            return settings.clone();
+        }
+        let Some(db) = &self.db else {
+            return Settings::default();
+        };
+        let loaded = db.lock().await.get_settings().await;
+        match loaded {
+            Ok(settings) => {
+                *self.settings.write().await = Some(settings.clone());
+                settings
+            }
+            Err(e) => {
+                wrn!("Failed to read settings, using defaults: {}", e);
+                Settings::default()
+            }
+        }
+    }
+    
+    /// Validate and save `settings`; everything reading them picks the new values up from
+    /// the next call on
+    pub async fn update_settings(&self, settings: Settings) -> Result<Settings, AppError> {
+        settings.validate().map_err(AppError::Validation)?;
+        if let Some(db) = &self.db {
+            db.lock().await.save_settings(&settings).await?;
+        }
+        *self.settings.write().await = Some(settings.clone());
+        inf!("Settings updated");
+        Ok(settings)
+    }
+    
+    /// Back the database up to `path`, or as an automatic backup beside it when None,
+    /// returning where the copy went
+    pub async fn backup_database(&self, path: Option<std::path::PathBuf>) -> Result<std::path::PathBuf, AppError> {
+        let db = self.database()?.lock().await;
+        let path = match path {
+            Some(path) => path,
+            None => {
+                let file = db.file_path().await?
+                    .ok_or_else(|| AppError::Validation("An in-memory database has no file to back up".to_string()))?;
+                backup::backup_path(&file, chrono::Utc::now())
+            }
+        };
+        db.backup(&path).await?;
+        Ok(path)
+    }
+    
+    /// Take an automatic backup once `backup_interval_hours` have passed since the last one,
+    /// then delete all but the newest `backup_keep`. Returns the new backup, if one was taken.
+    pub async fn backup_if_due(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Option<std::path::PathBuf>, AppError> {
+        let Some(db) = &self.db else {
+            return Ok(None);
+        };
+        let settings = self.settings().await.backup;
+        let db = db.lock().await;
+        let Some(file) = db.file_path().await? else {
+            return Ok(None);
+        };
+        let last = backup::list_backups(&file).last().map(|(taken_at, _)| *taken_at);
+        if !backup::backup_due(last, now, settings.interval_hours) {
+            return Ok(None);
+        }
+        let path = backup::backup_path(&file, now);
+        db.backup(&path).await?;
+        let pruned = backup::prune_backups(&file, settings.keep)?;
+        if pruned > 0 {
+            inf!("Deleted {} old backup(s) of {}", pruned, file.display());
+        }
+        Ok(Some(path))
+    }
+    
+    /// Purge templates deleted more than `purge_deleted_templates_days` ago; returns how many went
+    pub async fn purge_deleted_templates(&self, now: chrono::DateTime<chrono::Utc>) -> Result<u64, AppError> {
+        let Some(db) = &self.db else {
+            return Ok(0);
+        };
+        let days = self.settings().await.orders.purge_deleted_templates_days;
+        if days == 0 {
+            return Ok(0);
+        }
+        let purged = db.lock().await.purge_deleted_templates(now - chrono::Duration::days(days as i64)).await?;
+        if purged > 0 {
+            inf!("Purged {} template(s) deleted over {} days ago", purged, days);
+        }
+        Ok(purged)
+    }
+    
+    /// Load persisted templates (and their live IB order ids) into memory
+    pub async fn load_templates(&self) -> Result<usize, AppError> {
+        let Some(db) = &self.db else {
+            return Ok(0);
+        };
+        
+        let (db_templates, db_active_orders) = {
+            let db = db.lock().await;
+            (db.get_all_templates().await?, db.get_all_active_orders().await?)
+        };
+        
+        let mut templates = self.order_templates.write().await;
+        let mut active_orders = self.active_orders.lock().await;
+        templates.clear();
+        active_orders.clear();
+        
+        for db_template in db_templates {
+            let active_order = db_active_orders.iter().find(|o| o.template_id == db_template.id);
+            match db_template.to_order_template(active_order) {
+                Ok(template) => {
+                    if let Some(parent_id) = template.parent_order_id {
+                        active_orders.insert(parent_id, template.id.clone());
+                    }
+                    if let Some(stop_id) = template.stop_order_id {
+                        active_orders.insert(stop_id, template.id.clone());
+                    }
+                    for tranche_id in &template.tranche_order_ids {
+                        active_orders.insert(*tranche_id, template.id.clone());
+                    }
+                    templates.insert(template.id.clone(), template);
+                }
+                Err(e) => {
+                    wrn!("Skipping unreadable template: {}", e);
+                }
+            }
+        }
+        
+        // Never hand out order ids that were already used by a restored template
+        if let Some(max_id) = active_orders.keys().max() {
+            let mut next_id = self.next_order_id.lock().await;
+            *next_id = (*next_id).max(max_id + 1);
+        }
+        
+        inf!("Loaded {} order templates from database", templates.len());
+        Ok(templates.len())
+    }
+    
+    async fn persist_template(&self, template: &OrderTemplate) -> Result<(), AppError> {
+        if let Some(db) = &self.db {
+            db.lock().await.save_template(DbOrderTemplate::from_order_template(template)).await?;
+        }
+        Ok(())
+    }
+    
+    /// Write an edit to an existing template, touching only the prices, quantity or notes when
+    /// nothing else changed
+    async fn persist_template_edit(&self, existing: &OrderTemplate, template: &OrderTemplate) -> Result<(), AppError> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        let before = DbOrderTemplate::from_order_template(existing);
+        let after = DbOrderTemplate::from_order_template(template);
+        let only = |apply: fn(&mut DbOrderTemplate, &DbOrderTemplate)| {
+            let mut patched = before.clone();
+            patched.updated_at = after.updated_at.clone();
+            apply(&mut patched, &after);
+            patched == after
+        };
+        
+        let db = db.lock().await;
+        if only(|t, after| t.notes = after.notes.clone()) {
+            db.update_template_notes(&after.id, after.notes.as_deref()).await?;
+        } else if only(|t, after| (t.limit_price, t.stop_price) = (after.limit_price, after.stop_price)) {
+            db.update_template_prices(&after.id, after.limit_price, after.stop_price).await?;
+        } else if only(|t, after| t.quantity = after.quantity) {
+            db.update_template_quantity(&after.id, after.quantity).await?;
+        } else {
+            db.update_template(after).await?;
+        }
+        Ok(())
+    }
+    
+    async fn record_revision(&self, template_id: &str, changes: &[TemplateFieldChange]) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        
+        let changes_json = match serde_json::to_string(changes) {
+            Ok(json) => json,
+            Err(e) => {
+                err!("Failed to serialize revision for template {}: {}", template_id, e);
+                return;
+            }
+        };
+        
+        // History is best effort: the edit itself already succeeded
+        match db.lock().await.record_template_revision(template_id, &changes_json).await {
+            Ok(revision) => inf!("Recorded revision {} for template {}", revision, template_id),
+            Err(e) => err!("Failed to record revision for template {}: {}", template_id, e),
+        }
+    }
+    
+    fn database(&self) -> Result<&Arc<Mutex<Database>>, AppError> {
+        self.db.as_ref()
+            .ok_or_else(|| AppError::Custom("This operation requires a database".to_string()))
+    }
+    
+    /// Append to the order audit trail. Best effort, like revision history.
+    async fn audit(
+        &self,
+        template_id: &str,
+        action: AuditAction,
+        source: AuditSource,
+        before: Option<&OrderTemplate>,
+        after: Option<&OrderTemplate>,
+    ) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        
+        let account_type = self.active_account.read().await.map(|a| format!("{:?}", a));
+        let entry = match DbAuditEntry::new(template_id, action, source, account_type, before, after) {
+            Ok(entry) => entry,
+            Err(e) => {
+                err!("Failed to serialize audit entry for template {}: {}", template_id, e);
+                return;
+            }
+        };
+        if let Err(e) = db.lock().await.record_audit(&entry).await {
+            err!("Failed to record {:?} audit for template {}: {}", action, template_id, e);
         }
     }
     
+    pub async fn get_audit_trail(&self, template_id: &str) -> Result<Vec<AuditEntry>, AppError> {
+        let entries = self.database()?.lock().await.get_audit_trail(template_id).await?;
+        entries.iter()
+            .map(|e| e.to_entry().map_err(AppError::Serialization))
+            .collect()
+    }
+    
+    /// A page of the audit log across all templates, for the audit viewer
+    pub async fn get_audit_page(&self, filter: &AuditFilter, offset: usize, limit: usize) -> Result<AuditPage, AppError> {
+        let (entries, total) = self.database()?.lock().await.get_audit_page(filter, offset, limit).await?;
+        let entries = entries.iter()
+            .map(|e| e.to_entry().map_err(AppError::Serialization))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(AuditPage { entries, offset, total })
+    }
+    
+    pub async fn get_template_history(&self, template_id: &str) -> Result<Vec<TemplateRevision>, AppError> {
+        let db = self.database()?;
+        let db_revisions = db.lock().await.get_template_revisions(template_id).await?;
+        db_revisions.iter()
+            .map(|r| r.to_revision().map_err(AppError::Serialization))
+            .collect()
+    }
+    
     pub async fn connect_paper(&mut self) -> Result<(), AppError> {
-        let paper_url = "127.0.0.1:7497"; // Default TWS paper trading port
         let client_id = 101;
+        let address = self.settings().await.connection.paper_address;
         
         // Run sync connection in blocking task
         let result = tokio::task::spawn_blocking(move || {
-            Client::connect(paper_url, client_id)
+            let client = Client::connect(&address, client_id)?;
+            let next_valid_id = client.next_valid_order_id()?;
+            Ok::<_, ibapi::Error>((client, next_valid_id))
         }).await
         .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?;
         
         match result {
-            Ok(client) => {
+            Ok((client, next_valid_id)) => {
+                self.advance_order_ids(next_valid_id).await;
                 self.paper_client = Some(Arc::new(Mutex::new(client)));
                 // Automatically set as active account
                 *self.active_account.write().await = Some(AccountType::Paper);
@@ -77,17 +429,20 @@ impl IBClient {
     }
     
     pub async fn connect_live(&mut self) -> Result<(), AppError> {
-        let live_url = "127.0.0.1:7496"; // Default TWS live trading port
         let client_id = 102;
+        let address = self.settings().await.connection.live_address;
         
         // Run sync connection in blocking task
         let result = tokio::task::spawn_blocking(move || {
-            Client::connect(live_url, client_id)
+            let client = Client::connect(&address, client_id)?;
+            let next_valid_id = client.next_valid_order_id()?;
+            Ok::<_, ibapi::Error>((client, next_valid_id))
         }).await
         .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?;
         
         match result {
-            Ok(client) => {
+            Ok((client, next_valid_id)) => {
+                self.advance_order_ids(next_valid_id).await;
                 self.live_client = Some(Arc::new(Mutex::new(client)));
                 // Automatically set as active account
                 *self.active_account.write().await = Some(AccountType::Live);
@@ -151,31 +506,69 @@ impl IBClient {
         }
     }
     
-    async fn get_next_order_id(&self) -> i32 {
+    /// Reserve `count` consecutive order ids, returning the first.
+    /// With a database the block is claimed against the persisted high-water mark.
+    async fn reserve_order_ids(&self, count: i32) -> Result<i32, AppError> {
+        let mut id = self.next_order_id.lock().await;
+        let first = match &self.db {
+            Some(db) => db.lock().await.reserve_order_ids(*id as i64, count as i64).await? as i32,
+            None => *id,
+        };
+        *id = first + count;
+        Ok(first)
+    }
+    
+    /// Never hand out ids below what IB reports as the next valid one
+    async fn advance_order_ids(&self, next_valid_id: i32) {
         let mut id = self.next_order_id.lock().await;
-        let current = *id;
-        *id += 1;
-        current
+        if next_valid_id > *id {
+            inf!("Advancing order ids from {} to IB's next valid id {}", *id, next_valid_id);
+            *id = next_valid_id;
+        }
     }
     
     // Order template management
-    pub async fn create_template(&self, template: OrderTemplate) -> Result<String, AppError> {
+    pub async fn create_template(&self, mut template: OrderTemplate) -> Result<String, AppError> {
+        if template.expires_at.is_none() {
+            template.expires_at = self.default_expiry(chrono::Utc::now()).await;
+        }
         template.validate()
-            .map_err(|e| AppError::Validation(e))?;
+            .map_err(AppError::Validation)?;
+        self.check_stop_against_atr(&template).await?;
         
         let template_id = template.id.clone();
+        self.persist_template(&template).await?;
+        self.record_revision(&template_id, &template.initial_revision()).await;
+        self.audit(&template_id, AuditAction::Create, AuditSource::UI, None, Some(&template)).await;
         self.order_templates.write().await.insert(template_id.clone(), template);
         inf!("Created order template: {}", template_id);
         Ok(template_id)
     }
     
-    pub async fn update_template(&self, template: OrderTemplate) -> Result<(), AppError> {
+    pub async fn update_template(&self, mut template: OrderTemplate) -> Result<(), AppError> {
+        // Saving an expired template with a fresh (or no) expiry is the re-analysis that revives it
+        if template.is_expired() && template.expires_at.is_none_or(|at| at > chrono::Utc::now()) {
+            template.status = OrderTemplateStatus::Inactive;
+        }
         template.validate()
-            .map_err(|e| AppError::Validation(e))?;
+            .map_err(AppError::Validation)?;
+        // ATR rules govern the initial plan, not a stop being managed on a live trade
+        if !template.is_active() && !template.is_closed() {
+            self.check_stop_against_atr(&template).await?;
+        }
         
         let mut templates = self.order_templates.write().await;
-        if templates.contains_key(&template.id) {
+        if let Some(existing) = templates.get(&template.id) {
+            if existing.is_read_only {
+                return Err(AppError::Validation("Read-only template; claim it before editing".to_string()));
+            }
             let template_id = template.id.clone();
+            let changes = existing.diff(&template);
+            self.persist_template_edit(existing, &template).await?;
+            if !changes.is_empty() {
+                self.record_revision(&template_id, &changes).await;
+            }
+            self.audit(&template_id, AuditAction::Update, AuditSource::UI, Some(existing), Some(&template)).await;
             templates.insert(template_id.clone(), template);
             inf!("Updated order template: {}", template_id);
             Ok(())
@@ -190,6 +583,10 @@ impl IBClient {
             if template.is_active() {
                 return Err(AppError::Validation("Cannot delete active template".to_string()));
             }
+            if let Some(db) = &self.db {
+                db.lock().await.delete_template(template_id).await?;
+            }
+            self.audit(template_id, AuditAction::Delete, AuditSource::UI, Some(template), None).await;
             templates.remove(template_id);
             inf!("Deleted order template: {}", template_id);
             Ok(())
@@ -198,180 +595,3200 @@ impl IBClient {
         }
     }
     
-    pub async fn get_template(&self, template_id: &str) -> Option<OrderTemplate> {
-        self.order_templates.read().await.get(template_id).cloned()
+    /// Retire a template: it leaves the working set but stays in the database for the journal
+    pub async fn archive_template(&self, template_id: &str) -> Result<(), AppError> {
+        let mut templates = self.order_templates.write().await;
+        let template = templates.get(template_id)
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        if !template.can_archive() {
+            return Err(AppError::Validation("Only inactive, expired or closed templates can be archived".to_string()));
+        }
+        
+        let mut archived = template.clone();
+        archived.status = OrderTemplateStatus::Archived;
+        archived.activate_at = None;
+        archived.archived_at = Some(chrono::Utc::now());
+        if let Some(db) = &self.db {
+            db.lock().await.save_template(DbOrderTemplate::from_order_template(&archived)).await?;
+        }
+        self.audit(template_id, AuditAction::Archive, AuditSource::UI, Some(template), Some(&archived)).await;
+        templates.remove(template_id);
+        inf!("Archived order template: {}", template_id);
+        Ok(())
     }
     
-    pub async fn get_all_templates(&self) -> Vec<OrderTemplate> {
-        self.order_templates.read().await.values().cloned().collect()
+    /// Bring an archived template back into the working set as inactive
+    pub async fn restore_template(&self, template_id: &str) -> Result<(), AppError> {
+        let db = self.database()?;
+        let db_template = db.lock().await.get_template(template_id).await?
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        let mut template = db_template.to_order_template(None).map_err(AppError::Serialization)?;
+        if !template.is_archived() {
+            return Err(AppError::Validation("Template is not archived".to_string()));
+        }
+        
+        let before = template.clone();
+        template.status = OrderTemplateStatus::Inactive;
+        template.archived_at = None;
+        db.lock().await.save_template(DbOrderTemplate::from_order_template(&template)).await?;
+        self.audit(template_id, AuditAction::Restore, AuditSource::UI, Some(&before), Some(&template)).await;
+        self.order_templates.write().await.insert(template.id.clone(), template);
+        inf!("Restored order template: {}", template_id);
+        Ok(())
     }
     
-    // Order activation
-    pub async fn activate_template(&self, template_id: &str) -> Result<(), AppError> {
-        let client = self.get_active_client().await?;
+    pub async fn get_archived_templates(&self) -> Result<Vec<OrderTemplate>, AppError> {
+        let db_templates = self.database()?.lock().await.get_archived_templates().await?;
+        db_templates.iter()
+            .map(|t| t.to_order_template(None).map_err(AppError::Serialization))
+            .collect()
+    }
+    
+    /// The symbol's latest valid filtered ATR calculation, if one was made
+    pub async fn latest_atr_result(&self, symbol: &str) -> Option<ATRResult> {
+        self.atr_results.read().await.get(symbol).cloned()
+    }
+    
+    /// The symbol's settings, or empty ones (everything from the global settings) when it has
+    /// none or they can't be read
+    async fn symbol_settings(&self, symbol: &str) -> SymbolSettings {
+        let Some(db) = &self.db else {
+            return SymbolSettings::default();
+        };
+        match db.lock().await.get_symbol_settings(symbol).await {
+            Ok(settings) => settings.map(|s| s.to_settings()).unwrap_or_default(),
+            Err(e) => {
+                wrn!("Could not read settings for {}: {}", symbol, e);
+                SymbolSettings::default()
+            }
+        }
+    }
+    
+    /// The ATR period for `symbol`: its own, else the `atr_period` setting
+    async fn atr_period(&self, symbol: &str) -> usize {
+        match self.symbol_settings(symbol).await.atr_period {
+            Some(period) => period,
+            None => self.settings().await.atr.period,
+        }
+    }
+    
+    /// Recent filtered ATR for the symbol, computing it if needed; None when unavailable
+    async fn recent_atr(&self, symbol: &str) -> Option<f64> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::hours(ATR_MAX_AGE_HOURS);
+        if let Some(result) = self.atr_results.read().await.get(symbol)
+            && result.calculation_date > cutoff {
+            return Some(result.filtered_atr);
+        }
         
-        let mut templates = self.order_templates.write().await;
-        let template = templates.get_mut(template_id)
-            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        let period_days = self.atr_period(symbol).await;
+        match self.calculate_filtered_atr(symbol, period_days, OutlierMethod::default(), AtrSmoothing::default(), false).await {
+            Ok(result) if result.is_valid => Some(result.filtered_atr),
+            Ok(_) => None,
+            Err(e) => {
+                wrn!("Could not compute ATR for {}: {}", symbol, e);
+                None
+            }
+        }
+    }
+    
+    /// Validate the stop distance against ATR when an ATR is available
+    async fn check_stop_against_atr(&self, template: &OrderTemplate) -> Result<(), AppError> {
+        let Some(atr) = self.recent_atr(&template.symbol).await else {
+            wrn!("No ATR for {}, skipping stop distance validation", template.symbol);
+            return Ok(());
+        };
         
-        if !template.can_activate() {
-            return Err(AppError::Validation("Template cannot be activated in current state".to_string()));
+        let rules = self.stop_rules(template.model).await?;
+        check_stop_loss(
+            template.limit_price,
+            template.get_stop_loss(),
+            template.side,
+            atr,
+            rules.min_distance,
+            rules.max_atr_fraction,
+        ).map_err(AppError::StopLoss)?;
+        
+        self.warn_volatility_divergence(&template.symbol).await;
+        Ok(())
+    }
+    
+    /// Log when ATR and realized volatility disagree enough that an ATR-based stop deserves a second look
+    async fn warn_volatility_divergence(&self, symbol: &str) {
+        let max_ratio = self.settings().await.atr.volatility_divergence;
+        if max_ratio <= 0.0 {
+            return;
+        }
+        match self.get_volatility_profile(symbol).await {
+            Ok(profile) => {
+                if let Some(warning) = profile.divergence_warning(max_ratio) {
+                    wrn!("{}", warning);
+                }
+            }
+            Err(e) => inf!("No volatility profile for {}: {}", symbol, e),
+        }
+    }
+    
+    /// Stop choices for an entry. Either half may be missing: the ATR ones without an ATR,
+    /// the swing one without daily bars or a swing on the right side of the entry.
+    pub async fn suggest_stops(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        entry_price: f64,
+        model: Option<TradingModel>,
+    ) -> Result<StopSuggestions, AppError> {
+        if entry_price <= 0.0 {
+            return Err(AppError::Validation("Entry price must be positive".to_string()));
+        }
+        let rules = match &self.db {
+            Some(db) => db.lock().await.get_stop_rules(model.map_or("", model_to_str)).await?,
+            None => StopRules::default(),
+        };
+        let atr = self.recent_atr(symbol).await;
+        let bars = self.daily_bars(symbol, STOP_SWING_WINDOW).await.unwrap_or_else(|e| {
+            wrn!("No daily bars for {} swing stops: {}", symbol, e);
+            Vec::new()
+        });
+        let start = bars.len().saturating_sub(STOP_SWING_WINDOW);
+        Ok(StopSuggestions::new(entry_price, side, atr, &bars[start..], &rules))
+    }
+    
+    /// Volatility measures over the ATR period's worth of daily bars and more
+    pub async fn get_volatility_profile(&self, symbol: &str) -> Result<VolatilityProfile, AppError> {
+        let period_days = self.atr_period(symbol).await;
+        let window = (period_days * 3).clamp(30, 60);
+        let bars = self.daily_bars(symbol, window).await?;
+        let start = bars.len().saturating_sub(window + 1);
+        VolatilityProfile::from_bars(symbol, &bars[start..], period_days)
+            .ok_or_else(|| AppError::Validation(format!("Not enough daily bars for {}", symbol)))
+    }
+    
+    /// Today's volume so far against the same time of day over the last `sessions` sessions, from RTH 5-minute bars
+    pub async fn get_relative_volume(&self, symbol: &str, sessions: usize) -> Result<RelativeVolume, AppError> {
+        if sessions == 0 {
+            return Err(AppError::Validation("Relative volume needs at least one earlier session".to_string()));
+        }
+        // Calendar days: weekends and holidays take roughly a third of them
+        let duration_days = (sessions * 3 / 2 + 3) as u32;
+        let bars = self.get_historical_data(symbol, duration_days, "5 mins", true).await?.bars;
+        let rvol = RelativeVolume::from_bars(&bars, sessions)
+            .ok_or_else(|| AppError::Validation(format!("No intraday volume today and before for {}", symbol)))?;
+        if let Some(data) = self.market_data.write().await.get_mut(symbol) {
+            data.relative_volume = rvol.ratio;
+        }
+        Ok(rvol)
+    }
+    
+    /// Gap frequency, size and same-session fill rate over the last `lookback_days` daily bars
+    pub async fn get_gap_stats(&self, symbol: &str, lookback_days: usize, min_gap_pct: f64) -> Result<GapStats, AppError> {
+        if lookback_days < 2 || min_gap_pct < 0.0 {
+            return Err(AppError::Validation("Gap statistics need two or more days and a non-negative minimum gap".to_string()));
+        }
+        let bars = self.daily_bars(symbol, lookback_days).await?;
+        let start = bars.len().saturating_sub(lookback_days + 1);
+        GapStats::from_bars(symbol, &bars[start..], min_gap_pct)
+            .ok_or_else(|| AppError::Validation(format!("Not enough daily bars for {}", symbol)))
+    }
+    
+    /// Symbols with working templates, positions or a watchlist entry whose range today has reached
+    /// `volatility_spike_multiple` ATRs. Each symbol alerts at most once a session.
+    pub async fn check_volatility_spikes(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<VolatilitySpike>, AppError> {
+        if !market_hours::in_regular_session(now) {
+            return Ok(Vec::new());
+        }
+        {
+            let mut checked_at = self.spike_checked_at.write().await;
+            if checked_at.is_some_and(|at| now - at < chrono::Duration::minutes(VOLATILITY_SPIKE_CHECK_MINUTES)) {
+                return Ok(Vec::new());
+            }
+            *checked_at = Some(now);
+        }
+        let threshold = self.settings().await.atr.volatility_spike_multiple;
+        if threshold <= 0.0 {
+            return Ok(Vec::new());
         }
         
-        // Create contract
-        let contract = Contract::stock(&template.symbol);
+        // Symbol -> whether any of its templates holds a position
+        let mut watched: std::collections::BTreeMap<String, bool> = std::collections::BTreeMap::new();
+        for template in self.order_templates.read().await.values()
+            .filter(|t| !matches!(t.status, OrderTemplateStatus::Archived | OrderTemplateStatus::Expired))
+        {
+            *watched.entry(template.symbol.clone()).or_default() |= template.has_fills();
+        }
+        if let Some(db) = &self.db {
+            for symbol in db.lock().await.get_watched_symbols().await? {
+                watched.entry(symbol).or_default();
+            }
+        }
         
-        // Get order IDs
-        let parent_order_id = self.get_next_order_id().await;
-        let stop_order_id = parent_order_id + 1;
+        let session = market_hours::session_date(now);
+        let mut spikes = Vec::new();
+        for (symbol, held) in watched {
+            if self.spike_alerts.read().await.get(&symbol) == Some(&session) {
+                continue;
+            }
+            let Some(atr) = self.recent_atr(&symbol).await else {
+                continue;
+            };
+            let bars: Vec<HistoricalBar> = match self.get_historical_data(&symbol, 1, "1 hour", true).await {
+                Ok(data) => data.bars.into_iter()
+                    .filter(|bar| market_hours::session_date(bar.timestamp) == session)
+                    .collect(),
+                Err(e) => {
+                    wrn!("No intraday bars for {} spike check: {}", symbol, e);
+                    continue;
+                }
+            };
+            let last = self.get_market_data(&symbol).await.map(|data| data.last);
+            // Daily bars are stamped with their date at midnight UTC
+            let previous_close = self.daily_bars(&symbol, 1).await.ok()
+                .and_then(|bars| bars.iter().rev().find(|bar| bar.timestamp.date_naive() < session).map(|bar| bar.close));
+            
+            if let Some(mut spike) = VolatilitySpike::detect(&symbol, session, &bars, last, previous_close, atr, threshold) {
+                spike.held = held;
+                self.spike_alerts.write().await.insert(symbol, session);
+                spikes.push(spike);
+            }
+        }
+        Ok(spikes)
+    }
+    
+    pub async fn get_model_defaults(&self, model: TradingModel) -> Result<ModelDefaults, AppError> {
+        match &self.db {
+            Some(db) => Ok(db.lock().await.get_model_defaults(model_to_str(model)).await?),
+            None => Ok(ModelDefaults::default()),
+        }
+    }
+    
+    pub async fn set_model_defaults(&self, model: TradingModel, defaults: ModelDefaults) -> Result<(), AppError> {
+        if defaults.risk_per_trade <= 0.0 || defaults.stop_atr_fraction <= 0.0 || defaults.target_r < 0.0 {
+            return Err(AppError::Validation(
+                "Risk per trade and stop fraction must be positive, target R zero or more".to_string()
+            ));
+        }
+        self.database()?.lock().await.set_model_defaults(model_to_str(model), &defaults).await?;
+        inf!("Saved {:?} defaults: {:?}", model, defaults);
+        Ok(())
+    }
+    
+    /// Fill what a new template leaves open from its model's defaults: a stop
+    /// `stop_atr_fraction` of ATR from the entry when none is given, and a full-size
+    /// target at `target_r` when no exits are planned. Prices go on the symbol's tick grid.
+    pub async fn apply_model_defaults(&self, template: &mut OrderTemplate) -> Result<(), AppError> {
+        let defaults = self.get_model_defaults(template.model).await?;
+        let symbol_settings = self.symbol_settings(&template.symbol).await;
         
-        // Create parent limit order
-        let mut parent_order = orders::Order::default();
-        parent_order.action = template.side.to_action();
-        parent_order.order_type = "LMT".to_string();
-        parent_order.total_quantity = template.quantity;
-        parent_order.limit_price = Some(template.limit_price);
-        parent_order.tif = template.time_in_force.to_string();
-        parent_order.transmit = false; // Don't transmit until stop is attached
-        
-        // Create attached stop order
-        let mut stop_order = orders::Order::default();
-        stop_order.action = template.side.stop_action();
-        stop_order.order_type = "STP".to_string();
-        stop_order.total_quantity = template.quantity;
-        stop_order.aux_price = Some(template.stop_price);
-        stop_order.parent_id = parent_order_id;
-        stop_order.tif = "GTC".to_string(); // Stop is always GTC
-        stop_order.transmit = true; // This will transmit both orders
+        template.limit_price = symbol_settings.round_to_tick(template.limit_price);
+        if template.stop_price <= 0.0 {
+            let atr = self.recent_atr(&template.symbol).await
+                .ok_or_else(|| AppError::Validation(format!("No ATR for {}; enter a stop price", template.symbol)))?;
+            template.stop_price = defaults.default_stop(template.limit_price, template.side, atr);
+        }
+        template.stop_price = symbol_settings.round_to_tick(template.stop_price);
+        if defaults.target_r > 0.0 && template.exit_tranches.is_empty() {
+            template.exit_tranches.push(ExitTranche::target(1.0, defaults.target_r));
+        }
+        Ok(())
+    }
+    
+    /// Risk to size `template` for: the symbol's override, else its model's risk per trade.
+    /// Without a database the template's own risk is used.
+    async fn risk_per_trade(&self, template: &OrderTemplate) -> Result<f64, AppError> {
+        let Some(db) = &self.db else {
+            return Ok(template.risk_per_trade);
+        };
+        let db = db.lock().await;
+        if let Some(risk) = db.get_symbol_settings(&template.symbol).await?.and_then(|s| s.risk_per_trade) {
+            return Ok(risk);
+        }
+        Ok(db.get_model_defaults(model_to_str(template.model)).await?.risk_per_trade)
+    }
+    
+    async fn stop_rules(&self, model: TradingModel) -> Result<StopRules, AppError> {
+        match &self.db {
+            Some(db) => Ok(db.lock().await.get_stop_rules(model_to_str(model)).await?),
+            None => Ok(StopRules::default()),
+        }
+    }
+    
+    /// Set quantity using the template's sizing strategy and the configured risk per trade,
+    /// cut back to what the account can fund and rounded to the symbol's board lot
+    pub async fn size_from_risk(&self, template: &mut OrderTemplate) -> Result<SizingResult, AppError> {
+        let risk_per_trade = self.risk_per_trade(template).await?;
+        let (drawdown, risk_scale) = self.drawdown_state().await;
+        let risk_per_trade = risk_per_trade * risk_scale;
+        if let Some(drawdown) = drawdown.filter(|_| risk_scale < 1.0) {
+            inf!("Risk per trade scaled to {:.0}% in a {:.1}% drawdown", risk_scale * 100.0, drawdown.pct());
+        }
         
-        // Update template status
-        template.status = OrderTemplateStatus::Activating;
-        template.parent_order_id = Some(parent_order_id);
-        template.stop_order_id = Some(stop_order_id);
+        let (shares, risk) = match template.sizing_strategy {
+            SizingStrategy::FixedRisk => {
+                let shares = calculate_position_size(
+                    risk_per_trade,
+                    template.limit_price,
+                    template.get_stop_loss(),
+                    template.side,
+                ).map_err(AppError::Validation)?;
+                (shares, risk_per_trade)
+            }
+            SizingStrategy::VolatilityNormalized { atr_multiple } => {
+                let atr = self.recent_atr(&template.symbol).await
+                    .ok_or_else(|| AppError::Validation(format!("No ATR for {}; can't size by volatility", template.symbol)))?;
+                let shares = calculate_volatility_normalized_size(risk_per_trade, atr, atr_multiple)
+                    .map_err(AppError::Validation)?;
+                (shares, risk_per_trade)
+            }
+            SizingStrategy::FractionalKelly { fraction } => {
+                let stats = self.trade_stats(template.model).await?;
+                let equity = self.account_equity().await?;
+                let risk = calculate_kelly_risk(equity, &stats, fraction).map_err(AppError::Validation)?;
+                let shares = calculate_position_size(
+                    risk,
+                    template.limit_price,
+                    template.get_stop_loss(),
+                    template.side,
+                ).map_err(AppError::Validation)?;
+                (shares, risk)
+            }
+        };
         
-        let template_id_clone = template_id.to_string();
-        let active_orders = self.active_orders.clone();
+        let sizing = self.fit_to_account(&template.symbol, shares, template.limit_price).await?;
+        for reduction in &sizing.reductions {
+            wrn!("{}: {}", template.symbol, reduction);
+        }
         
-        // Place orders in a blocking task
-        let client_clone = client.clone();
-        let contract_clone = contract.clone();
-        let parent_order_clone = parent_order.clone();
-        let stop_order_clone = stop_order.clone();
+        // Risk shrinks with the size; scale rather than recompute so every strategy stays consistent
+        template.quantity = sizing.shares as f64;
+        template.risk_per_trade = risk * sizing.shares as f64 / shares as f64;
+        inf!(
+            "Sized {} at {} shares for ${:.2} risk ({:?})",
+            template.symbol, sizing.shares, template.risk_per_trade, template.sizing_strategy
+        );
+        Ok(sizing)
+    }
+    
+    /// Check a computed size against buying power, margin and board lots.
+    /// Offline there is nothing to check against, so the size passes through unchecked.
+    async fn fit_to_account(&self, symbol: &str, shares: i64, entry_price: f64) -> Result<SizingResult, AppError> {
+        if self.get_active_client().await.is_err() {
+            wrn!("Not connected; {} shares of {} not checked against buying power", shares, symbol);
+            return Ok(SizingResult::unchecked(shares));
+        }
         
-        let result = tokio::task::spawn_blocking(move || {
-            let client_guard = futures::executor::block_on(client_clone.lock());
-            
-            // Place parent order
-            let parent_result = client_guard.place_order(parent_order_id, &contract_clone, &parent_order_clone);
-            if parent_result.is_err() {
-                return Err(parent_result.unwrap_err());
+        let initial_margin_rate = self.settings().await.risk.initial_margin_rate;
+        let values = self.fetch_account_values(&[
+            ibapi::accounts::AccountSummaryTags::BUYING_POWER,
+            ibapi::accounts::AccountSummaryTags::AVAILABLE_FUNDS,
+        ]).await?;
+        let value = |tag: &str| values.get(tag).copied()
+            .ok_or_else(|| AppError::IBConnection(format!("IB reported no {} value", tag)));
+        let capacity = AccountCapacity {
+            buying_power: value(ibapi::accounts::AccountSummaryTags::BUYING_POWER)?,
+            available_funds: value(ibapi::accounts::AccountSummaryTags::AVAILABLE_FUNDS)?,
+            initial_margin_rate,
+        };
+        let lot_size = self.board_lot(symbol).await?;
+        
+        fit_to_account(shares, entry_price, &capacity, lot_size).map_err(AppError::Validation)
+    }
+    
+    /// Smallest tradable increment for the symbol, at least one share
+    async fn board_lot(&self, symbol: &str) -> Result<i64, AppError> {
+        let client = self.get_active_client().await?;
+        let contract = Contract::stock(symbol);
+        let size_increment = tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            let details = client_guard.contract_details(&contract)?;
+            Ok::<_, ibapi::Error>(details.first().map(|d| d.size_increment))
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("Failed to fetch contract details for {}: {}", symbol, e)))?
+        .ok_or_else(|| AppError::NotFound(format!("No contract details for {}", symbol)))?;
+        
+        // Older servers send zero; fractional increments still trade whole shares here
+        Ok((size_increment.ceil() as i64).max(1))
+    }
+    
+    /// Borrow availability from the shortable generic tick; None when IB sends nothing in time
+    async fn short_availability(&self, symbol: &str) -> Result<Option<ShortAvailability>, AppError> {
+        let client = self.get_active_client().await?;
+        let contract = Contract::stock(symbol);
+        tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            let subscription = client_guard.market_data(&contract, &["236"], false, false)?;
+            let tick = subscription.timeout_iter(SHORT_CHECK_TIMEOUT).find_map(|tick| match tick {
+                TickTypes::Generic(generic) if generic.tick_type == TickType::Shortable => Some(generic.value),
+                _ => None,
+            });
+            subscription.cancel();
+            Ok::<_, ibapi::Error>(tick.map(ShortAvailability::from_shortable_tick))
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("Failed to fetch shortability for {}: {}", symbol, e)))
+    }
+    
+    /// Initial margin the entry would add, from a what-if order that IB evaluates but never works
+    async fn what_if_margin(&self, template: &OrderTemplate) -> Result<Option<f64>, AppError> {
+        let client = self.get_active_client().await?;
+        let order_id = self.reserve_order_ids(1).await?;
+        let contract = template.contract();
+        let mut order = Self::entry_order(template);
+        order.what_if = true;
+        order.oca_group = String::new();
+        order.transmit = true;
+        
+        tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            let subscription = client_guard.place_order(order_id, &contract, &order)?;
+            let margin = subscription.timeout_iter(SHORT_CHECK_TIMEOUT).find_map(|event| match event {
+                orders::PlaceOrder::OpenOrder(data) => Some(data.order_state.initial_margin_change.or_else(|| {
+                    Some(data.order_state.initial_margin_after? - data.order_state.initial_margin_before?)
+                })),
+                _ => None,
+            });
+            Ok::<_, ibapi::Error>(margin.flatten())
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("What-if order for {} failed: {}", template.symbol, e)))
+    }
+    
+    /// Borrow status and margin for a short template, recording the borrow status on it.
+    /// None for longs. Either figure is left unknown if IB can't supply it.
+    pub async fn check_short(&self, template_id: &str) -> Result<Option<ShortCheck>, AppError> {
+        let template = self.get_template(template_id).await
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        if template.side != OrderSide::Short {
+            return Ok(None);
+        }
+        
+        let availability = self.short_availability(&template.symbol).await
+            .unwrap_or_else(|e| {
+                wrn!("Borrow status unknown for {}: {}", template.symbol, e);
+                None
+            });
+        let margin_requirement = self.what_if_margin(&template).await
+            .unwrap_or_else(|e| {
+                wrn!("Short margin unknown for {}: {}", template.symbol, e);
+                None
+            });
+        
+        if availability.is_some() && availability != template.short_availability {
+            let mut templates = self.order_templates.write().await;
+            if let Some(template) = templates.get_mut(template_id) {
+                template.short_availability = availability;
+                self.persist_template(template).await?;
             }
-            
-            // Place stop order
-            let stop_result = client_guard.place_order(stop_order_id, &contract_clone, &stop_order_clone);
-            if stop_result.is_err() {
-                // Try to cancel parent order if stop fails
-                let _ = client_guard.cancel_order(parent_order_id, "");
-                return Err(stop_result.unwrap_err());
+        }
+        
+        Ok(Some(ShortCheck {
+            symbol: template.symbol.clone(),
+            availability,
+            margin_requirement,
+            notional: template.notional(),
+        }))
+    }
+    
+    pub async fn get_short_max_margin_pct(&self) -> f64 {
+        self.settings().await.risk.short_max_margin_pct
+    }
+    
+    /// Journal statistics for the model, or across all models until it has enough trades
+    async fn trade_stats(&self, model: TradingModel) -> Result<TradeStats, AppError> {
+        let db = self.database()?.lock().await;
+        let mut r_multiples = db.get_r_multiples(Some(model_to_str(model)), None, None).await?;
+        if r_multiples.len() < MIN_KELLY_TRADES {
+            r_multiples = db.get_r_multiples(None, None, None).await?;
+        }
+        TradeStats::from_r_multiples(&r_multiples).ok_or_else(|| AppError::Validation(format!(
+            "Kelly sizing needs {} closed trades with wins and losses in the journal, found {}",
+            MIN_KELLY_TRADES, r_multiples.len()
+        )))
+    }
+    
+    /// Net liquidation value of the active account
+    async fn account_equity(&self) -> Result<f64, AppError> {
+        self.fetch_account_values(&[ibapi::accounts::AccountSummaryTags::NET_LIQUIDATION]).await?
+            .get(ibapi::accounts::AccountSummaryTags::NET_LIQUIDATION)
+            .copied()
+            .ok_or_else(|| AppError::IBConnection("IB reported no net liquidation value".to_string()))
+    }
+    
+    /// Numeric account summary values by tag, for the first account IB reports
+    async fn fetch_account_values(&self, tags: &'static [&'static str]) -> Result<HashMap<String, f64>, AppError> {
+        let client = self.get_active_client().await?;
+        tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            let mut account = None;
+            let mut values = HashMap::new();
+            for item in &client_guard.account_summary("All", tags)? {
+                match item {
+                    ibapi::accounts::AccountSummaries::Summary(summary) => {
+                        let account = account.get_or_insert_with(|| summary.account.clone());
+                        if summary.account == *account
+                            && let Ok(value) = summary.value.parse::<f64>() {
+                            values.insert(summary.tag, value);
+                        }
+                    }
+                    ibapi::accounts::AccountSummaries::End => break,
+                }
+            }
+            Ok::<_, ibapi::Error>(values)
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("Failed to fetch account summary: {}", e)))
+    }
+    
+    /// Recompute the stop and size from a freshly calculated ATR; nothing is saved
+    pub async fn reprice_template(&self, template_id: &str) -> Result<RepriceProposal, AppError> {
+        let template = self.get_template(template_id).await
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        if template.is_active() {
+            return Err(AppError::Validation("Deactivate the template before re-pricing it".to_string()));
+        }
+        
+        let period_days = self.atr_period(&template.symbol).await;
+        let risk_per_trade = self.risk_per_trade(&template).await?;
+        
+        // Always recalculate; a cached value may be the stale one we're replacing
+        let atr = self.calculate_filtered_atr(&template.symbol, period_days, OutlierMethod::default(), AtrSmoothing::default(), false).await?;
+        if !atr.is_valid {
+            return Err(AppError::Validation(format!("Not enough data for a reliable ATR on {}", template.symbol)));
+        }
+        
+        let rules = self.stop_rules(template.model).await?;
+        let proposal = RepriceProposal::for_template(&template, atr.filtered_atr, risk_per_trade, &rules)
+            .map_err(AppError::Validation)?;
+        inf!(
+            "Re-priced {}: stop {:.2} -> {:.2}, quantity {} -> {} (ATR {:.2})",
+            template.symbol,
+            proposal.current_stop_price,
+            proposal.proposed_stop_price,
+            proposal.current_quantity,
+            proposal.proposed_quantity,
+            proposal.filtered_atr
+        );
+        Ok(proposal)
+    }
+    
+    pub async fn duplicate_template(&self, template_id: &str, new_name: Option<String>) -> Result<String, AppError> {
+        let template = self.get_template(template_id).await
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        
+        let copy = template.duplicate(new_name);
+        inf!("Duplicating template {} as {}", template_id, copy.id);
+        self.create_template(copy).await
+    }
+    
+    pub async fn get_template(&self, template_id: &str) -> Option<OrderTemplate> {
+        self.order_templates.read().await.get(template_id).cloned()
+    }
+    
+    pub async fn get_all_templates(&self) -> Vec<OrderTemplate> {
+        self.order_templates.read().await.values().cloned().collect()
+    }
+    
+    // Order activation
+    /// Price the entry is compared against: the side we'd trade into, else the last price. The
+    /// symbol's streamed quote is used when it has one, otherwise a snapshot is taken; the latest
+    /// historical close is the fallback when IB sends no quote.
+    async fn reference_price(&self, template: &OrderTemplate) -> Result<f64, AppError> {
+        if let Some(price) = self.get_market_data(&template.symbol).await
+            .and_then(|data| reference_quote(template.side, &data))
+        {
+            return Ok(price);
+        }
+        match self.quote_snapshot(template.contract()).await {
+            Ok(data) => {
+                if let Some(price) = reference_quote(template.side, &data) {
+                    return Ok(price);
+                }
+                wrn!("No quote for {} in its snapshot, comparing against the last close", template.symbol);
+            }
+            Err(e) => wrn!("No quote snapshot for {}, comparing against the last close: {}", template.symbol, e),
+        }
+        self.current_price(&template.symbol).await
+    }
+    
+    /// One-shot quote and last price for a contract
+    async fn quote_snapshot(&self, contract: Contract) -> Result<MarketData, AppError> {
+        let client = self.get_active_client().await?;
+        let symbol = contract.symbol.clone();
+        tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            let subscription = client_guard.market_data(&contract, &[], true, false)?;
+            let mut data = MarketData::new(&contract.symbol);
+            for tick in subscription.timeout_iter(QUOTE_SNAPSHOT_TIMEOUT) {
+                if matches!(tick, TickTypes::SnapshotEnd) {
+                    break;
+                }
+                apply_tick(&mut data, &tick);
+            }
+            Ok::<_, ibapi::Error>(data)
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("Failed to fetch a quote for {}: {}", symbol, e)))
+    }
+    
+    async fn position_limits(&self) -> PositionLimits {
+        if self.db.is_none() {
+            return PositionLimits::default();
+        }
+        let risk = self.settings().await.risk;
+        PositionLimits {
+            max_open_positions: risk.max_open_positions,
+            max_pending_templates: risk.max_pending_templates,
+        }
+    }
+    
+    async fn exposure_caps(&self) -> Result<ExposureCaps, AppError> {
+        if self.db.is_none() {
+            return Ok(ExposureCaps::default());
+        }
+        let risk = self.settings().await.risk;
+        Ok(ExposureCaps {
+            max_symbol_notional: risk.max_symbol_notional,
+            max_symbol_risk: risk.max_symbol_risk,
+            sectors: self.get_all_sectors().await?,
+        })
+    }
+    
+    async fn live_order_limits(&self) -> Result<LiveOrderLimits, AppError> {
+        let orders = self.settings().await.orders;
+        Ok(LiveOrderLimits {
+            max_order_notional: orders.live_max_order_notional,
+            allow_market_orders: orders.live_allow_market_orders,
+        })
+    }
+    
+    pub async fn is_live(&self) -> bool {
+        *self.active_account.read().await == Some(AccountType::Live)
+    }
+    
+    /// Stage `action` for confirmation, describing what it will send
+    pub async fn prepare_live_action(&self, action: LiveAction) -> Result<LiveConfirmation, AppError> {
+        let account = if self.is_live().await { "LIVE account" } else { "paper account" };
+        let describe = |verb: &str, template: &OrderTemplate| {
+            format!(
+                "{} {} {:?} {} @ {:.2}, stop {:.2} (notional {:.2}, risk {:.2}) on the {}",
+                verb, template.symbol, template.side, template.quantity, template.entry_price(),
+                template.stop_price, template.notional(), template.open_risk(), account
+            )
+        };
+        let summary = match &action {
+            LiveAction::Activate { template_id, .. } | LiveAction::ActivateOcoPair { template_id, .. } => {
+                let template = self.get_template(template_id).await
+                    .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+                let mut summary = describe("Activate", &template);
+                if let Some(oca_group) = &template.oca_group {
+                    summary.push_str(&format!(" as part of OCO group {}", oca_group));
+                }
+                summary
+            }
+            LiveAction::Deactivate { template_id } => {
+                let template = self.get_template(template_id).await
+                    .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+                describe("Cancel orders for", &template)
+            }
+            LiveAction::ActivateBasket { basket_id } | LiveAction::DeactivateBasket { basket_id } => {
+                let basket = self.get_basket(basket_id).await?;
+                let verb = if matches!(action, LiveAction::ActivateBasket { .. }) { "Activate" } else { "Deactivate" };
+                format!("{} basket {} ({} templates) on the {}", verb, basket.name, basket.template_ids.len(), account)
+            }
+        };
+        
+        Ok(self.live_confirmations.lock().await.prepare(action, summary, chrono::Utc::now()))
+    }
+    
+    /// Redeem a token from `prepare_live_action`, returning the action it now allows
+    pub async fn confirm_live_action(&self, token: &str) -> Result<LiveAction, AppError> {
+        self.live_confirmations.lock().await.confirm(token, chrono::Utc::now())
+            .map_err(AppError::Validation)
+    }
+    
+    /// On the live account, user actions run only once confirmed; paper needs no confirmation
+    pub async fn require_confirmation(&self, action: &LiveAction) -> Result<(), AppError> {
+        if !self.is_live().await || self.live_confirmations.lock().await.take_confirmed(action) {
+            return Ok(());
+        }
+        Err(AppError::Validation(
+            "Live account: prepare and confirm this action (PrepareActivation, then ConfirmActivation)".to_string()
+        ))
+    }
+    
+    /// Held symbols whose recent daily returns move with the template's symbol beyond
+    /// `correlation_warning_threshold`. Advisory only; symbols without enough history are skipped.
+    pub async fn correlation_warnings(&self, template_id: &str) -> Result<Vec<CorrelationWarning>, AppError> {
+        let template = self.get_template(template_id).await
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        let risk = self.settings().await.risk;
+        let (threshold, window) = (risk.correlation_warning_threshold, risk.correlation_window_days);
+        if threshold <= 0.0 {
+            return Ok(Vec::new());
+        }
+        
+        let mut held: Vec<String> = self.order_templates.read().await
+            .values()
+            .filter(|t| t.is_active() && t.has_fills() && !t.symbol.eq_ignore_ascii_case(&template.symbol))
+            .map(|t| t.symbol.to_uppercase())
+            .collect();
+        held.sort();
+        held.dedup();
+        if held.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let candidate = self.daily_bars(&template.symbol, window).await?;
+        let mut warnings = Vec::new();
+        for symbol in held {
+            let bars = match self.daily_bars(&symbol, window).await {
+                Ok(bars) => bars,
+                Err(e) => {
+                    wrn!("No daily bars for {}, skipping correlation: {}", symbol, e);
+                    continue;
+                }
+            };
+            if let Some((correlation, observations)) = return_correlation(&candidate, &bars, window)
+                && correlation >= threshold
+            {
+                warnings.push(CorrelationWarning {
+                    symbol: template.symbol.clone(),
+                    held: symbol,
+                    correlation,
+                    observations,
+                });
+            }
+        }
+        Ok(warnings)
+    }
+    
+    /// RTH daily bars covering `window` returns, fetched only when the cached set is stale or short
+    async fn daily_bars(&self, symbol: &str, window: usize) -> Result<Vec<HistoricalBar>, AppError> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::hours(ATR_MAX_AGE_HOURS);
+        if let Some((fetched_at, bars)) = self.daily_bars.read().await.get(symbol)
+            && *fetched_at > cutoff
+            && bars.len() > window
+        {
+            return Ok(bars.clone());
+        }
+        
+        // Calendar days: weekends and holidays take roughly a third of them
+        let duration_days = (window * 3 / 2 + 10) as u32;
+        Ok(self.get_historical_data(symbol, duration_days, "1 day", true).await?.bars)
+    }
+    
+    /// Fat-finger guard: refuse entries too far from the market
+    async fn check_price_band(&self, template: &OrderTemplate) -> Result<(), AppError> {
+        // A market entry has no price of its own to get wrong
+        if template.entry_type == EntryType::Market {
+            return Ok(());
+        }
+        
+        let max_pct = self.settings().await.orders.max_price_deviation_pct;
+        let reference = self.reference_price(template).await?;
+        let deviation = template.price_deviation_pct(reference);
+        if deviation > max_pct {
+            return Err(AppError::Validation(format!(
+                "Entry {:.2} is {:.1}% from the market ({:.2}), limit is {:.1}%; override to send anyway",
+                template.limit_price, deviation, reference, max_pct
+            )));
+        }
+        Ok(())
+    }
+    
+    pub async fn activate_template(
+        &self,
+        template_id: &str,
+        override_price_band: bool,
+        override_limits: bool,
+        source: AuditSource,
+    ) -> Result<(), AppError> {
+        let client = self.get_active_client().await?;
+        
+        if let Some(lock) = self.active_trading_lock(chrono::Utc::now()).await {
+            return Err(AppError::Validation(lock.describe()));
+        }
+        
+        if self.is_live().await {
+            let limits = self.live_order_limits().await?;
+            let template = self.get_template(template_id).await
+                .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+            limits.check(&template).map_err(AppError::Validation)?;
+        }
+        
+        {
+            let template = self.get_template(template_id).await
+                .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+            let items = self.get_checklist_items(Some(template.model)).await?;
+            template.check_checklist(&items).map_err(AppError::Validation)?;
+        }
+        
+        if !override_limits && let Some(check) = self.check_short(template_id).await? {
+            let risk = self.settings().await.risk;
+            let (max_margin_pct, blocks) = (risk.short_max_margin_pct, risk.short_check_blocks);
+            let issues = check.issues(max_margin_pct);
+            if !issues.is_empty() {
+                if blocks {
+                    return Err(AppError::Validation(format!("{}; override limits to short anyway", issues.join("; "))));
+                }
+                wrn!("Activating short {} despite: {}", check.symbol, issues.join("; "));
+            }
+        }
+        
+        if !override_limits && let Some(event) = self.earnings_within_hold(template_id).await? {
+            let (policy, hold_days) = self.earnings_policy().await;
+            let warning = format!("{}, within the {}-day expected hold", event.describe(), hold_days);
+            if policy == EarningsPolicy::Block {
+                return Err(AppError::Validation(format!("{}; override limits to activate anyway", warning)));
+            }
+            wrn!("Activating {} despite earnings: {}", template_id, warning);
+        }
+        
+        if !override_limits {
+            let limits = self.position_limits().await;
+            let caps = self.exposure_caps().await?;
+            let currencies = self.template_currencies().await;
+            let fx = self.fx_rates(currencies.iter().map(String::as_str)).await;
+            let templates = self.order_templates.read().await;
+            let template = templates.get(template_id)
+                .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+            limits.check(template, templates.values()).map_err(AppError::Validation)?;
+            caps.check(template, templates.values(), &fx).map_err(AppError::Validation)?;
+        }
+        
+        if !override_price_band {
+            let template = self.get_template(template_id).await
+                .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+            self.check_price_band(&template).await?;
+        }
+        
+        let mut templates = self.order_templates.write().await;
+        let template = templates.get_mut(template_id)
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        
+        if !template.can_activate() {
+            return Err(AppError::Validation("Template cannot be activated in current state".to_string()));
+        }
+        let before = template.clone();
+        
+        // Create contract
+        let contract = template.contract();
+        
+        // Reserve IDs for parent, stop and one exit order per tranche
+        let order_count = 2 + template.exit_tranches.len() as i32;
+        let parent_order_id = self.reserve_order_ids(order_count).await?;
+        let stop_order_id = parent_order_id + 1;
+        let tranche_order_ids: Vec<i32> = (0..template.exit_tranches.len() as i32)
+            .map(|i| stop_order_id + 1 + i)
+            .collect();
+        
+        let mut bracket = vec![
+            (parent_order_id, Self::entry_order(template)),
+            (stop_order_id, Self::stop_order(template, parent_order_id, template.remaining_stop_quantity())),
+        ];
+        for ((index, tranche), (order_id, quantity)) in template.exit_tranches.iter()
+            .enumerate()
+            .zip(tranche_order_ids.iter().zip(template.tranche_quantities()))
+        {
+            bracket.push((*order_id, Self::tranche_order(template, index, tranche, parent_order_id, quantity)));
+        }
+        // Only the last order transmits, releasing the whole bracket at once
+        if let Some((_, last)) = bracket.last_mut() {
+            last.transmit = true;
+        }
+        
+        // Update template status
+        template.status = OrderTemplateStatus::Activating;
+        template.parent_order_id = Some(parent_order_id);
+        template.stop_order_id = Some(stop_order_id);
+        template.tranche_order_ids = tranche_order_ids.clone();
+        
+        let template_id_clone = template_id.to_string();
+        let active_orders = self.active_orders.clone();
+        
+        // Place orders in a blocking task
+        let client_clone = client.clone();
+        let contract_clone = contract.clone();
+        
+        let result = tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client_clone.lock());
+            
+            for (order_id, order) in &bracket {
+                if let Err(e) = client_guard.place_order(*order_id, &contract_clone, order) {
+                    // Cancelling the parent also cancels any children already sent
+                    if *order_id != parent_order_id {
+                        let _ = client_guard.cancel_order(parent_order_id, "");
+                    }
+                    return Err(e);
+                }
+            }
+            
+            Ok(())
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?;
+        
+        match result {
+            Ok(()) => {
+                // Track orders
+                {
+                    let mut active_orders = active_orders.lock().await;
+                    for order_id in [parent_order_id, stop_order_id].iter().chain(&tranche_order_ids) {
+                        active_orders.insert(*order_id, template_id_clone.clone());
+                    }
+                }
+                
+                // Update template status
+                template.status = OrderTemplateStatus::Active;
+                template.activated_at = Some(chrono::Utc::now());
+                // The risk as placed is what closed trades are measured against in R
+                template.risk_per_trade = template.risk_per_share() * template.quantity;
+                
+                if let Some(db) = &self.db {
+                    let db = db.lock().await;
+                    let active_order = DbActiveOrder {
+                        template_id: template_id_clone,
+                        ib_order_id: parent_order_id as i64,
+                        ib_stop_order_id: Some(stop_order_id as i64),
+                        ib_tranche_order_ids: DbActiveOrder::encode_tranche_ids(&tranche_order_ids),
+                        submitted_at: chrono::Utc::now().to_rfc3339(),
+                    };
+                    // Orders are already live, so a persistence failure must not undo activation
+                    if let Err(e) = db.create_active_order(active_order).await {
+                        err!("Failed to persist active orders for template {}: {}", template_id, e);
+                    }
+                    if let Err(e) = db.save_template(DbOrderTemplate::from_order_template(template)).await {
+                        err!("Failed to persist activated template {}: {}", template_id, e);
+                    }
+                }
+                
+                self.audit(template_id, AuditAction::Activate, source, Some(&before), Some(template)).await;
+                inf!("Activated template {} with orders {} and {} and {} exit tranches",
+                    template_id, parent_order_id, stop_order_id, tranche_order_ids.len());
+                Ok(())
+            }
+            Err(e) => {
+                err!("Failed to place orders: {}", e);
+                template.status = OrderTemplateStatus::Failed;
+                template.parent_order_id = None;
+                template.stop_order_id = None;
+                template.tranche_order_ids.clear();
+                Err(AppError::IBConnection(format!("Failed to place orders: {}", e)))
+            }
+        }
+    }
+    
+    /// Parent entry order; held back until the bracket's last child transmits
+    fn entry_order(template: &OrderTemplate) -> orders::Order {
+        orders::Order {
+            action: template.side.to_action(),
+            order_type: template.entry_type.ib_order_type().to_string(),
+            total_quantity: template.quantity,
+            limit_price: template.entry_limit_price(),
+            aux_price: template.entry_trigger_price(),
+            tif: template.time_in_force.to_string(),
+            good_till_date: template.time_in_force.ib_good_till_date(),
+            outside_rth: template.outside_rth,
+            order_ref: OrderRef::new(template, OrderLeg::Entry).to_string(),
+            oca_group: template.oca_group.clone().unwrap_or_default(),
+            oca_type: if template.oca_group.is_some() { 1 } else { 0 }, // Cancel the other entry when one fills
+            transmit: false,
+            ..Default::default()
+        }
+    }
+    
+    /// Protective stop for `quantity` shares, attached to the parent. It sits at the effective
+    /// stop, the one the R targets and the runner's trail are measured from.
+    fn stop_order(template: &OrderTemplate, parent_order_id: i32, quantity: f64) -> orders::Order {
+        orders::Order {
+            action: template.side.stop_action(),
+            order_type: "STP".to_string(),
+            total_quantity: quantity,
+            aux_price: Some(template.get_stop_loss()),
+            parent_id: parent_order_id,
+            tif: "GTC".to_string(), // Stop is always GTC
+            // Stop stays RTH-only: premarket prints are thin, a stop there fills far from its price
+            order_ref: OrderRef::new(template, OrderLeg::Stop).to_string(),
+            transmit: false,
+            ..Default::default()
+        }
+    }
+    
+    /// Exit for one tranche: a GTC limit at its R target, or a trailing stop for the runner
+    fn tranche_order(
+        template: &OrderTemplate,
+        index: usize,
+        tranche: &ExitTranche,
+        parent_order_id: i32,
+        quantity: f64,
+    ) -> orders::Order {
+        let exit = match tranche.exit {
+            TrancheExit::Target { .. } => orders::Order {
+                order_type: "LMT".to_string(),
+                limit_price: template.tranche_target_price(tranche),
+                ..Default::default()
+            },
+            TrancheExit::Trailing { trail_amount } => orders::Order {
+                order_type: "TRAIL".to_string(),
+                aux_price: Some(trail_amount),
+                trail_stop_price: Some(template.get_stop_loss()),
+                ..Default::default()
+            },
+        };
+        orders::Order {
+            action: template.side.stop_action(),
+            total_quantity: quantity,
+            parent_id: parent_order_id,
+            tif: "GTC".to_string(),
+            order_ref: OrderRef::new(template, OrderLeg::Tranche(index)).to_string(),
+            transmit: false,
+            ..exit
+        }
+    }
+    
+    /// Re-send the live stop with the template's current stop price and quantity
+    async fn modify_stop_order(&self, template: &OrderTemplate) -> Result<(), AppError> {
+        let client = self.get_active_client().await?;
+        
+        let (Some(parent_order_id), Some(stop_order_id)) = (template.parent_order_id, template.stop_order_id) else {
+            return Err(AppError::Validation("Template has no live stop order".to_string()));
+        };
+        let contract = template.contract();
+        let mut stop_order = Self::stop_order(template, parent_order_id, template.remaining_stop_quantity());
+        stop_order.transmit = true;
+        
+        // Re-placing an existing order ID modifies it in TWS
+        tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            client_guard.place_order(stop_order_id, &contract, &stop_order).map(|_| ())
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("Failed to modify stop order: {}", e)))
+    }
+    
+    /// Pull today's executions from IB, keep them in the executions table and apply them to
+    /// the templates owning the orders. Returns the ids of templates whose fill state changed.
+    pub async fn sync_fills(&self) -> Result<Vec<String>, AppError> {
+        let client = self.get_active_client().await?;
+        let executions = tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            let subscription = client_guard.executions(orders::ExecutionFilter::default())?;
+            let mut executions = Vec::new();
+            let mut commissions = HashMap::new();
+            for item in &subscription {
+                match item {
+                    orders::Executions::ExecutionData(data) => executions.push((data.contract.symbol, data.execution)),
+                    orders::Executions::CommissionReport(report) => {
+                        commissions.insert(execution_base_id(&report.execution_id).to_string(), report.commission);
+                    }
+                    _ => {}
+                }
+            }
+            Ok::<_, ibapi::Error>((executions, commissions))
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("Failed to fetch executions: {}", e)))?;
+        let (executions, commissions) = executions;
+        
+        self.record_executions(&executions, &commissions).await;
+        
+        let executions: Vec<orders::Execution> = executions.into_iter().map(|(_, execution)| execution).collect();
+        self.apply_fills(&fills_by_order(&executions), chrono::Utc::now()).await
+    }
+    
+    /// Apply per-order fill totals (quantity, average price) to the templates owning the orders,
+    /// closing those whose exits cover the position
+    async fn apply_fills(&self, fills: &HashMap<i32, (f64, f64)>, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<String>, AppError> {
+        let tracked: Vec<OrderTemplate> = self.order_templates.read().await
+            .values()
+            .filter(|t| t.is_active())
+            .cloned()
+            .collect();
+        let mut changed = Vec::new();
+        
+        for template in tracked {
+            if let Some(parent_id) = template.parent_order_id
+                && let Some(&(quantity, avg_price)) = fills.get(&parent_id)
+            {
+                let mut templates = self.order_templates.write().await;
+                if let Some(live) = templates.get_mut(&template.id)
+                    && live.apply_entry_fill(quantity, avg_price, now)
+                {
+                    self.persist_template(live).await?;
+                    self.audit(&live.id, AuditAction::Fill, AuditSource::Reconciliation, Some(&template), Some(live)).await;
+                    inf!("Template {} entry filled {}/{} @ {:.4}", live.id, quantity, live.quantity, avg_price);
+                    changed.push(template.id.clone());
+                }
+            }
+            
+            for (index, order_id) in template.tranche_order_ids.iter().enumerate() {
+                let Some(&(quantity, _)) = fills.get(order_id) else {
+                    continue;
+                };
+                let recorded = template.exit_tranches.get(index).map_or(quantity, |t| t.filled_quantity);
+                if quantity > recorded {
+                    match self.record_tranche_fill(&template.id, index, quantity).await {
+                        Ok(()) => changed.push(template.id.clone()),
+                        Err(e) => err!("Failed to record tranche {} fill for {}: {}", index, template.id, e),
+                    }
+                }
+            }
+            
+            if let Some(stop_order_id) = template.stop_order_id
+                && fills.contains_key(&stop_order_id)
+            {
+                self.cancel_target_orders(&template).await;
+            }
+            
+            let exits: Vec<(f64, f64)> = template.stop_order_id.into_iter()
+                .chain(template.tranche_order_ids.iter().copied())
+                .filter_map(|order_id| fills.get(&order_id).copied())
+                .collect();
+            if !exits.is_empty()
+                && let Err(e) = self.record_trade_outcome(&template.id, &exits, now).await
+            {
+                err!("Failed to record outcome for {}: {}", template.id, e);
+            }
+            
+            if let Some(live) = self.get_template(&template.id).await
+                && live.is_flat_after(&exits)
+            {
+                match self.close_template(&live, fills).await {
+                    Ok(()) => changed.push(template.id.clone()),
+                    Err(e) => err!("Failed to close template {}: {}", template.id, e),
+                }
+            }
+        }
+        
+        changed.dedup();
+        Ok(changed)
+    }
+    
+    /// The exits covered the position: cancel whatever of the template is still working (an
+    /// unfilled entry remainder, a leftover exit) and retire it from trading so it can be archived
+    async fn close_template(&self, template: &OrderTemplate, fills: &HashMap<i32, (f64, f64)>) -> Result<(), AppError> {
+        let leftover: Vec<i32> = {
+            let active_orders = self.active_orders.lock().await;
+            template.parent_order_id.iter()
+                .chain(&template.stop_order_id)
+                .chain(&template.tranche_order_ids)
+                .filter(|id| !fills.contains_key(id) && active_orders.contains_key(id))
+                .copied()
+                .collect()
+        };
+        self.cancel_orders(&template.id, leftover).await;
+        
+        let mut templates = self.order_templates.write().await;
+        let Some(live) = templates.get_mut(&template.id) else {
+            return Ok(());
+        };
+        let before = live.clone();
+        self.untrack_orders(live).await;
+        live.status = OrderTemplateStatus::Closed;
+        live.parent_order_id = None;
+        live.stop_order_id = None;
+        live.tranche_order_ids.clear();
+        if let Some(db) = &self.db
+            && let Err(e) = db.lock().await.delete_active_order(&live.id).await {
+            err!("Failed to remove active orders for template {}: {}", live.id, e);
+        }
+        self.persist_template(live).await?;
+        self.audit(&live.id, AuditAction::Close, AuditSource::Reconciliation, Some(&before), Some(live)).await;
+        inf!("Closed template {}", live.id);
+        Ok(())
+    }
+    
+    /// Cancel the target exits still working once the stop has filled; left alone they would sell
+    /// shares no longer held and open a position the other way. The runner's trailing stop covers
+    /// only the runner's shares, which the fixed stop leaves out, so it keeps working.
+    async fn cancel_target_orders(&self, template: &OrderTemplate) {
+        let open: Vec<i32> = {
+            let active_orders = self.active_orders.lock().await;
+            template.open_target_order_ids().into_iter().filter(|id| active_orders.contains_key(id)).collect()
+        };
+        let cancelled = self.cancel_orders(&template.id, open).await;
+        if !cancelled.is_empty() {
+            inf!("Stop filled for template {}; cancelled target orders {:?}", template.id, cancelled);
+        }
+    }
+    
+    /// Cancel orders still working for a template and stop tracking the ones cancelled, so later
+    /// syncs don't cancel them again. Returns those cancelled.
+    async fn cancel_orders(&self, template_id: &str, order_ids: Vec<i32>) -> Vec<i32> {
+        if order_ids.is_empty() {
+            return Vec::new();
+        }
+        let client = match self.get_active_client().await {
+            Ok(client) => client,
+            Err(e) => {
+                wrn!("Can't cancel orders {:?} for {}: {}", order_ids, template_id, e);
+                return Vec::new();
+            }
+        };
+        
+        let cancelled = tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            order_ids.into_iter()
+                .filter(|&order_id| match client_guard.cancel_order(order_id, "") {
+                    Ok(_) => true,
+                    Err(e) => {
+                        wrn!("Failed to cancel order {}: {}", order_id, e);
+                        false
+                    }
+                })
+                .collect::<Vec<i32>>()
+        }).await;
+        let cancelled = match cancelled {
+            Ok(cancelled) => cancelled,
+            Err(e) => {
+                err!("Task join error: {}", e);
+                return Vec::new();
+            }
+        };
+        
+        let mut active_orders = self.active_orders.lock().await;
+        for order_id in &cancelled {
+            active_orders.remove(order_id);
+        }
+        cancelled
+    }
+    
+    /// Store `(symbol, execution)` pairs as fills, tagged with the template that placed each order
+    /// and with their commissions, keyed by base execution id
+    async fn record_executions(&self, executions: &[(String, orders::Execution)], commissions: &HashMap<String, f64>) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        if executions.is_empty() {
+            return;
+        }
+        
+        let records: Vec<DbExecution> = {
+            let templates = self.order_templates.read().await;
+            let owner = |order_id: i32| templates.values()
+                .find(|t| t.parent_order_id == Some(order_id)
+                    || t.stop_order_id == Some(order_id)
+                    || t.tranche_order_ids.contains(&order_id))
+                .map(|t| t.id.clone());
+            executions.iter()
+                .filter_map(|(symbol, execution)| {
+                    let Some(time) = market_hours::parse_ib_time(&execution.time) else {
+                        wrn!("Skipping execution {} with unreadable time '{}'", execution.execution_id, execution.time);
+                        return None;
+                    };
+                    let exec_id = execution_base_id(&execution.execution_id);
+                    Some(DbExecution::from_fill(&Fill {
+                        exec_id: exec_id.to_string(),
+                        order_id: execution.order_id,
+                        template_id: owner(execution.order_id),
+                        symbol: symbol.clone(),
+                        side: if execution.side == "BOT" { OrderSide::Long } else { OrderSide::Short },
+                        quantity: execution.shares,
+                        price: execution.price,
+                        time,
+                        commission: commissions.get(exec_id).copied(),
+                    }))
+                })
+                .collect()
+        };
+        
+        if let Err(e) = db.lock().await.save_executions(&records).await {
+            err!("Failed to record executions: {}", e);
+            return;
+        }
+        
+        let mut symbols: Vec<String> = records.into_iter().map(|r| r.symbol).collect();
+        symbols.sort();
+        symbols.dedup();
+        if let Err(e) = self.rebuild_trades(Some(&symbols)).await {
+            err!("Failed to update trades: {}", e);
+        }
+    }
+    
+    /// Rebuild the trades table from the recorded fills of `symbols`, or of every symbol
+    pub async fn rebuild_trades(&self, symbols: Option<&[String]>) -> Result<(), AppError> {
+        let db = self.database()?;
+        let plans: HashMap<String, TradePlan> = self.order_templates.read().await
+            .values()
+            .map(|t| (t.id.clone(), TradePlan::from_template(t)))
+            .collect();
+        let db = db.lock().await;
+        let symbols = match symbols {
+            Some(symbols) => symbols.to_vec(),
+            None => db.get_execution_symbols().await?,
+        };
+        for symbol in symbols {
+            let fills: Vec<Fill> = db.get_executions(&symbol).await?.iter().filter_map(|e| e.to_fill()).collect();
+            let trades: Vec<DbTrade> = journal::build_trades(&fills, &plans).iter().map(DbTrade::from_trade).collect();
+            db.replace_trades(&symbol, &trades).await?;
+        }
+        Ok(())
+    }
+    
+    /// Trades opened in `[from, to)`, oldest first, optionally only one symbol's or model's
+    pub async fn get_trades(
+        &self,
+        symbol: Option<&str>,
+        model: Option<TradingModel>,
+        from: Option<chrono::DateTime<chrono::Utc>>,
+        to: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<Trade>, AppError> {
+        let db_trades = self.database()?.lock().await.get_trades(symbol, model, from, to).await?;
+        db_trades.iter()
+            .map(|t| t.to_trade().map_err(AppError::Serialization))
+            .collect()
+    }
+    
+    /// Write templates, trades or executions to a CSV file, returning the rows written
+    pub async fn export_csv(
+        &self,
+        table: CsvTable,
+        path: &std::path::Path,
+        from: Option<chrono::DateTime<chrono::Utc>>,
+        to: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<usize, AppError> {
+        Ok(self.database()?.lock().await.export_csv(table, path, from, to).await?)
+    }
+    
+    /// Full-text search over template names and notes, trades and watchlist notes
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, AppError> {
+        Ok(self.database()?.lock().await.search(query, limit).await?)
+    }
+    
+    /// Recorded fills for a symbol, oldest first
+    pub async fn get_fills(&self, symbol: &str) -> Result<Vec<Fill>, AppError> {
+        let executions = self.database()?.lock().await.get_executions(symbol).await?;
+        Ok(executions.iter().filter_map(|e| e.to_fill()).collect())
+    }
+    
+    /// Recorded fills of a template's orders, oldest first
+    pub async fn get_template_fills(&self, template_id: &str) -> Result<Vec<Fill>, AppError> {
+        let executions = self.database()?.lock().await.get_template_executions(template_id).await?;
+        Ok(executions.iter().filter_map(|e| e.to_fill()).collect())
+    }
+    
+    /// Drawings on a symbol's chart, oldest first
+    pub async fn get_drawings(&self, symbol: &str) -> Result<Vec<Drawing>, AppError> {
+        let db_drawings = self.database()?.lock().await.get_drawings(symbol).await?;
+        db_drawings.iter()
+            .map(|d| d.to_drawing().map_err(AppError::Serialization))
+            .collect()
+    }
+    
+    pub async fn save_drawing(&self, drawing: &Drawing) -> Result<(), AppError> {
+        let db_drawing = DbDrawing::from_drawing(drawing)
+            .map_err(|e| AppError::Serialization(e.to_string()))?;
+        self.database()?.lock().await.save_drawing(&db_drawing).await?;
+        Ok(())
+    }
+    
+    /// Replace a stored drawing's geometry, returning the moved drawing
+    pub async fn move_drawing(&self, id: &str, shape: DrawingShape) -> Result<Drawing, AppError> {
+        let db_drawing = self.database()?.lock().await.get_drawing(id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Drawing {}", id)))?;
+        let mut drawing = db_drawing.to_drawing().map_err(AppError::Serialization)?;
+        drawing.shape = shape;
+        self.save_drawing(&drawing).await?;
+        Ok(drawing)
+    }
+    
+    pub async fn delete_drawing(&self, id: &str) -> Result<(), AppError> {
+        self.database()?.lock().await.delete_drawing(id).await?;
+        Ok(())
+    }
+    
+    /// Names of the chart themes to choose from: the built-in ones, then the saved ones
+    pub async fn chart_theme_names(&self) -> Result<Vec<String>, AppError> {
+        let saved = self.database()?.lock().await.get_themes().await?;
+        Ok(BUILTIN_THEMES.iter()
+            .map(|name| name.to_string())
+            .chain(saved.into_iter().map(|t| t.name))
+            .collect())
+    }
+    
+    /// A built-in or saved chart theme by name
+    pub async fn get_chart_theme(&self, name: &str) -> Result<ChartTheme, AppError> {
+        if let Some(theme) = ChartTheme::builtin(name) {
+            return Ok(theme);
+        }
+        let db_theme = self.database()?.lock().await.get_theme(name).await?
+            .ok_or_else(|| AppError::NotFound(format!("Theme {}", name)))?;
+        db_theme.to_theme().map_err(AppError::Serialization)
+    }
+    
+    /// Save a custom theme under `name`, replacing one saved there before. The built-in
+    /// themes' names are taken.
+    pub async fn save_chart_theme(&self, name: &str, theme: &ChartTheme) -> Result<(), AppError> {
+        if name.trim().is_empty() {
+            return Err(AppError::Validation("A theme needs a name".to_string()));
+        }
+        if BUILTIN_THEMES.contains(&name) {
+            return Err(AppError::Validation(format!("{} is a built-in theme", name)));
+        }
+        let db_theme = DbTheme::from_theme(name, theme)
+            .map_err(|e| AppError::Serialization(e.to_string()))?;
+        self.database()?.lock().await.save_theme(&db_theme).await?;
+        Ok(())
+    }
+    
+    /// Draw charts with the theme named `name` from now on, returning it
+    pub async fn select_chart_theme(&self, name: &str) -> Result<ChartTheme, AppError> {
+        let theme = self.get_chart_theme(name).await?;
+        self.database()?;
+        let mut settings = self.settings().await;
+        settings.ui.chart_theme = name.to_string();
+        self.update_settings(settings).await?;
+        Ok(theme)
+    }
+    
+    /// Name of the selected chart theme and the theme. Falls back to the built-in dark theme
+    /// when the selected one can't be read.
+    pub async fn selected_chart_theme(&self) -> Result<(String, ChartTheme), AppError> {
+        self.database()?;
+        let name = self.settings().await.ui.chart_theme;
+        match self.get_chart_theme(&name).await {
+            Ok(theme) => Ok((name, theme)),
+            Err(e) => {
+                wrn!("Chart theme {} is unavailable, using {}: {}", name, BUILTIN_THEMES[0], e);
+                Ok((BUILTIN_THEMES[0].to_string(), ChartTheme::default()))
+            }
+        }
+    }
+    
+    /// Whether the UI follows the OS's color scheme or stays light or dark; the OS's until set
+    pub async fn color_scheme(&self) -> Result<ColorSchemePreference, AppError> {
+        self.database()?;
+        Ok(self.settings().await.ui.color_scheme)
+    }
+    
+    pub async fn set_color_scheme(&self, preference: ColorSchemePreference) -> Result<(), AppError> {
+        self.database()?;
+        let mut settings = self.settings().await;
+        settings.ui.color_scheme = preference;
+        self.update_settings(settings).await?;
+        Ok(())
+    }
+    
+    /// Select the built-in theme matching the UI, now `dark` or light, when the selected
+    /// theme is the other built-in one, returning it. Custom themes are kept.
+    pub async fn match_color_scheme(&self, dark: bool) -> Result<Option<ChartTheme>, AppError> {
+        let (selected, _) = self.selected_chart_theme().await?;
+        let wanted = if dark { "dark" } else { "light" };
+        if selected == wanted || !BUILTIN_THEMES.contains(&selected.as_str()) {
+            return Ok(None);
+        }
+        self.select_chart_theme(wanted).await.map(Some)
+    }
+    
+    /// The active account's open position in a symbol, if any
+    pub async fn get_position(&self, symbol: &str) -> Result<Option<PositionInfo>, AppError> {
+        let (_, positions) = self.fetch_account_state().await?;
+        Ok(positions.into_iter().find(|p| p.symbol == symbol && p.quantity != 0.0))
+    }
+    
+    /// Journal the trade's result in R once today's exit fills cover the position.
+    /// Exits from earlier sessions aren't in IB's execution report, so those trades go unrecorded.
+    async fn record_trade_outcome(&self, template_id: &str, exits: &[(f64, f64)], now: chrono::DateTime<chrono::Utc>) -> Result<(), AppError> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        let Some(template) = self.get_template(template_id).await else {
+            return Ok(());
+        };
+        let Some(outcome) = TradeOutcome::from_exits(&template, exits, now) else {
+            return Ok(());
+        };
+        
+        let db = db.lock().await;
+        if db.has_trade_outcome(template_id).await? {
+            return Ok(());
+        }
+        db.record_trade_outcome(DbTradeOutcome::from_outcome(&outcome)).await?;
+        inf!("Closed {} ({}): {:.2} P&L, {:.2}R", template.symbol, template_id, outcome.realized_pnl, outcome.r_multiple);
+        Ok(())
+    }
+    
+    /// Rolling R statistics over the latest `window` closed trades, optionally for one model or symbol
+    pub async fn get_r_stats(&self, model: Option<TradingModel>, symbol: Option<&str>, window: Option<usize>) -> Result<Option<RStats>, AppError> {
+        let db = self.database()?.lock().await;
+        let r_multiples = db.get_r_multiples(model.map(model_to_str), symbol, window).await?;
+        Ok(RStats::from_r_multiples(&r_multiples))
+    }
+    
+    /// Journal statistics and the daily equity curve over trades closed within `range`
+    pub async fn get_performance_stats(&self, range: PerformanceRange) -> Result<PerformanceStats, AppError> {
+        let outcomes = self.database()?.lock().await.get_trade_outcomes().await?;
+        let outcomes = outcomes.iter()
+            .map(|o| o.to_outcome().map_err(AppError::Serialization))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PerformanceStats::new(&outcomes, range))
+    }
+    
+    /// Monte Carlo over `r_multiples` (e.g. a backtest's), or the journal's closed trades for
+    /// `model` (all models with None). Risk and equity default to the model's current
+    /// drawdown-scaled risk per trade and the account's net liquidation.
+    pub async fn run_monte_carlo(
+        &self,
+        model: Option<TradingModel>,
+        r_multiples: Option<Vec<f64>>,
+        config: MonteCarloConfig,
+    ) -> Result<MonteCarloResult, AppError> {
+        let r_multiples = match r_multiples {
+            Some(r_multiples) => r_multiples,
+            None => self.database()?.lock().await.get_r_multiples(model.map(model_to_str), None, None).await?,
+        };
+        let risk_per_trade = match config.risk_per_trade {
+            Some(risk) => risk,
+            None => {
+                let (_, risk_scale) = self.drawdown_state().await;
+                self.get_model_defaults(model.unwrap_or_default()).await?.risk_per_trade * risk_scale
+            }
+        };
+        let starting_equity = match config.starting_equity {
+            Some(equity) => equity,
+            None => self.account_equity().await?,
+        };
+        
+        let result = tokio::task::spawn_blocking(move || monte_carlo::simulate(&r_multiples, &config, risk_per_trade, starting_equity))
+            .await
+            .map_err(|e| AppError::Custom(format!("Task join error: {}", e)))?
+            .map_err(AppError::Validation)?;
+        inf!(
+            "Monte Carlo: {} x {} trades at ${:.2} risk, median final equity {:.2}, risk of ruin {:.1}%",
+            result.simulations, result.trades, risk_per_trade, result.final_equity.p50, result.risk_of_ruin * 100.0
+        );
+        Ok(result)
+    }
+    
+    /// Record shares exited by a target tranche and shrink the live stop to match
+    pub async fn record_tranche_fill(&self, template_id: &str, tranche_index: usize, filled_quantity: f64) -> Result<(), AppError> {
+        let mut templates = self.order_templates.write().await;
+        let template = templates.get_mut(template_id)
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        let before = template.clone();
+        let tranche = template.exit_tranches.get_mut(tranche_index)
+            .ok_or(AppError::NotFound(format!("Template {} has no tranche {}", template_id, tranche_index)))?;
+        tranche.filled_quantity = filled_quantity;
+        
+        self.modify_stop_order(template).await?;
+        self.persist_template(template).await?;
+        self.audit(template_id, AuditAction::Fill, AuditSource::Reconciliation, Some(&before), Some(template)).await;
+        inf!("Template {} tranche {} filled {} shares, stop reduced to {}",
+            template_id, tranche_index, filled_quantity, template.remaining_stop_quantity());
+        Ok(())
+    }
+    
+    /// Move the live stop to the entry price, `offset` into profit
+    pub async fn move_stop_to_breakeven(&self, template_id: &str, offset: f64) -> Result<f64, AppError> {
+        let mut templates = self.order_templates.write().await;
+        let template = templates.get_mut(template_id)
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        
+        if !template.is_active() {
+            return Err(AppError::Validation("Only active templates have a stop to move".to_string()));
+        }
+        if template.is_read_only {
+            return Err(AppError::Validation("Read-only template; claim it before moving its stop".to_string()));
+        }
+        
+        let new_stop = template.breakeven_stop_price(offset);
+        let improves = match template.side {
+            OrderSide::Long => new_stop > template.stop_price,
+            OrderSide::Short => new_stop < template.stop_price,
+        };
+        if !improves {
+            return Err(AppError::Validation(format!(
+                "Stop {:.2} is already at or beyond breakeven {:.2}", template.stop_price, new_stop
+            )));
+        }
+        
+        self.move_stop(template, new_stop, AuditSource::UI).await?;
+        inf!("Moved stop for template {} to breakeven {:.2}", template_id, new_stop);
+        Ok(new_stop)
+    }
+    
+    /// Re-send the live stop at `new_stop`, rolling the template back if IB refuses
+    async fn move_stop(&self, template: &mut OrderTemplate, new_stop: f64, source: AuditSource) -> Result<(), AppError> {
+        let before = template.clone();
+        template.stop_price = new_stop;
+        template.technical_stop_price = None;
+        if let Err(e) = self.modify_stop_order(template).await {
+            *template = before;
+            return Err(e);
+        }
+        
+        self.persist_template(template).await?;
+        self.record_revision(&template.id, &before.diff(template)).await;
+        self.audit(&template.id, AuditAction::Modify, source, Some(&before), Some(template)).await;
+        Ok(())
+    }
+    
+    /// Replace a template's stop-management rules
+    pub async fn set_stop_rules(&self, template_id: &str, rules: Vec<StopRule>) -> Result<(), AppError> {
+        let mut templates = self.order_templates.write().await;
+        let template = templates.get_mut(template_id)
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        if template.is_read_only {
+            return Err(AppError::Validation("Read-only template; claim it before adding stop rules".to_string()));
+        }
+        
+        let before = template.clone();
+        template.stop_rules = rules;
+        if let Err(e) = template.validate() {
+            *template = before;
+            return Err(AppError::Validation(e));
+        }
+        
+        self.persist_template(template).await?;
+        self.record_revision(template_id, &before.diff(template)).await;
+        self.audit(template_id, AuditAction::Update, AuditSource::UI, Some(&before), Some(template)).await;
+        Ok(())
+    }
+    
+    /// Create (no id) or edit a pre-trade checklist item, returning its id
+    pub async fn save_checklist_item(
+        &self,
+        id: Option<String>,
+        model: Option<TradingModel>,
+        text: String,
+        position: i64,
+    ) -> Result<String, AppError> {
+        if text.trim().is_empty() {
+            return Err(AppError::Validation("Checklist item text cannot be empty".to_string()));
+        }
+        let item = ChecklistItem {
+            id: id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            model,
+            text: text.trim().to_string(),
+            position,
+        };
+        self.database()?.lock().await.save_checklist_item(DbChecklistItem::from_item(&item)).await?;
+        inf!("Saved checklist item {}: {}", item.id, item.text);
+        Ok(item.id)
+    }
+    
+    /// Answers already given on templates keep the question's text
+    pub async fn delete_checklist_item(&self, id: &str) -> Result<(), AppError> {
+        self.database()?.lock().await.delete_checklist_item(id).await?;
+        inf!("Deleted checklist item {}", id);
+        Ok(())
+    }
+    
+    /// Items asked of `model`, or every item with None. Without a database there are none.
+    pub async fn get_checklist_items(&self, model: Option<TradingModel>) -> Result<Vec<ChecklistItem>, AppError> {
+        let Some(db) = &self.db else {
+            return Ok(Vec::new());
+        };
+        let items = db.lock().await.get_checklist_items(model.map(model_to_str)).await?;
+        Ok(items.iter().map(|i| i.to_item()).collect())
+    }
+    
+    /// The template's checklist: every item for its model, answered or not
+    pub async fn get_checklist(&self, template_id: &str) -> Result<Vec<ChecklistAnswer>, AppError> {
+        let template = self.get_template(template_id).await
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        let items = self.get_checklist_items(Some(template.model)).await?;
+        Ok(template.checklist_state(&items))
+    }
+    
+    /// Replace the template's checklist answers. Answers are frozen once the template is live.
+    pub async fn submit_checklist(&self, template_id: &str, answers: Vec<ChecklistAnswer>) -> Result<(), AppError> {
+        let model = self.get_template(template_id).await
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?
+            .model;
+        let items = self.get_checklist_items(Some(model)).await?;
+        
+        let now = chrono::Utc::now();
+        let mut checklist = Vec::new();
+        for answer in answers {
+            let item = items.iter().find(|i| i.id == answer.item_id)
+                .ok_or_else(|| AppError::Validation(format!("Checklist item {} doesn't apply to {:?}", answer.item_id, model)))?;
+            checklist.push(ChecklistAnswer { text: item.text.clone(), answered_at: Some(now), ..answer });
+        }
+        
+        let mut templates = self.order_templates.write().await;
+        let template = templates.get_mut(template_id)
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        if template.is_read_only {
+            return Err(AppError::Validation("Read-only template; claim it before answering its checklist".to_string()));
+        }
+        if template.is_active() {
+            return Err(AppError::Validation("The checklist can't change once the template is active".to_string()));
+        }
+        
+        let before = template.clone();
+        template.checklist = checklist;
+        self.persist_template(template).await?;
+        self.audit(template_id, AuditAction::Update, AuditSource::UI, Some(&before), Some(template)).await;
+        Ok(())
+    }
+    
+    /// Symbols of the filled templates whose stop rules need a price
+    pub async fn stop_rule_symbols(&self) -> BTreeSet<String> {
+        self.order_templates.read().await
+            .values()
+            .filter(|t| manages_stop(t))
+            .map(|t| t.symbol.clone())
+            .collect()
+    }
+    
+    /// Run stop-management rules on filled templates at the latest price, only on `symbol`'s if given.
+    /// One-shot rules are dropped from the template once their action goes through.
+    pub async fn apply_stop_rules(&self, now: chrono::DateTime<chrono::Utc>, symbol: Option<&str>) -> Vec<(OrderTemplate, StopAction, Result<(), AppError>)> {
+        let managed: Vec<OrderTemplate> = self.order_templates.read().await
+            .values()
+            .filter(|t| manages_stop(t) && symbol.is_none_or(|s| t.symbol == s))
+            .cloned()
+            .collect();
+        
+        let mut results = Vec::new();
+        for template in managed {
+            let price = match self.current_price(&template.symbol).await {
+                Ok(price) => price,
+                Err(e) => {
+                    wrn!("No price for {}, stop rules skipped: {}", template.symbol, e);
+                    continue;
+                }
+            };
+            let trails = template.stop_rules.iter().any(|r| matches!(r, StopRule::TrailAtr { .. }));
+            let atr = if trails { self.recent_atr(&template.symbol).await } else { None };
+            let Some(action) = stop_management::evaluate(&template, price, atr, now) else {
+                continue;
+            };
+            
+            let mut templates = self.order_templates.write().await;
+            let Some(live) = templates.get_mut(&template.id) else {
+                continue;
+            };
+            let result = match action {
+                StopAction::MoveStop { price: new_stop, .. } => self.move_stop(live, new_stop, AuditSource::Scheduler).await,
+                StopAction::Exit { .. } => self.exit_at_market(live).await,
+            };
+            if result.is_ok() && action.rule().fires_once() {
+                let before = live.clone();
+                live.stop_rules.retain(|rule| *rule != action.rule());
+                if let Err(e) = self.persist_template(live).await {
+                    err!("Failed to persist stop rules for {}: {}", live.id, e);
+                }
+                self.record_revision(&live.id, &before.diff(live)).await;
+            }
+            match &result {
+                Ok(()) => inf!("Stop rule {:?} on {} at {:.2}: {:?}", action.rule(), live.id, price, action),
+                Err(e) => err!("Stop rule {:?} on {} failed: {}", action.rule(), live.id, e),
+            }
+            results.push((live.clone(), action, result));
+        }
+        results
+    }
+    
+    /// Close the position now: cancel the exit tranches, then turn the stop into a market order
+    async fn exit_at_market(&self, template: &OrderTemplate) -> Result<(), AppError> {
+        let client = self.get_active_client().await?;
+        let (Some(parent_order_id), Some(stop_order_id)) = (template.parent_order_id, template.stop_order_id) else {
+            return Err(AppError::Validation("Template has no live stop order".to_string()));
+        };
+        
+        let exited: f64 = template.exit_tranches.iter()
+            .filter(|t| !t.is_trailing())
+            .map(|t| t.filled_quantity)
+            .sum();
+        let mut exit_order = Self::stop_order(template, parent_order_id, (template.quantity - exited).max(0.0));
+        exit_order.order_type = "MKT".to_string();
+        exit_order.aux_price = None;
+        exit_order.transmit = true;
+        let contract = template.contract();
+        let tranche_order_ids = template.tranche_order_ids.clone();
+        
+        tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            for order_id in tranche_order_ids {
+                // Already-filled tranches can't be cancelled; that's fine
+                if let Err(e) = client_guard.cancel_order(order_id, "") {
+                    wrn!("Failed to cancel exit order {}: {}", order_id, e);
+                }
+            }
+            // Re-placing the stop's id modifies it in TWS
+            client_guard.place_order(stop_order_id, &contract, &exit_order).map(|_| ())
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("Failed to exit at market: {}", e)))?;
+        
+        self.audit(&template.id, AuditAction::Modify, AuditSource::Scheduler, Some(template), Some(template)).await;
+        Ok(())
+    }
+    
+    pub async fn deactivate_template(&self, template_id: &str, source: AuditSource) -> Result<(), AppError> {
+        let client = self.get_active_client().await?;
+        
+        let mut templates = self.order_templates.write().await;
+        let template = templates.get_mut(template_id)
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        
+        if !template.can_deactivate() {
+            return Err(AppError::Validation("Template cannot be deactivated in current state".to_string()));
+        }
+        let before = template.clone();
+        
+        template.status = OrderTemplateStatus::Deactivating;
+        
+        // Cancel all orders in blocking task
+        let client_clone = client.clone();
+        let parent_id = template.parent_order_id;
+        let stop_id = template.stop_order_id;
+        let tranche_ids = template.tranche_order_ids.clone();
+        
+        let result = tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client_clone.lock());
+            let mut errors = Vec::new();
+            
+            if let Some(parent_id) = parent_id
+                && let Err(e) = client_guard.cancel_order(parent_id, "") {
+                errors.push(format!("Failed to cancel parent order {}: {}", parent_id, e));
+            }
+            
+            if let Some(stop_id) = stop_id
+                && let Err(e) = client_guard.cancel_order(stop_id, "") {
+                errors.push(format!("Failed to cancel stop order {}: {}", stop_id, e));
+            }
+            
+            for tranche_id in tranche_ids {
+                if let Err(e) = client_guard.cancel_order(tranche_id, "") {
+                    errors.push(format!("Failed to cancel exit order {}: {}", tranche_id, e));
+                }
+            }
+            
+            errors
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?;
+        
+        let errors = result;
+        
+        // Update active orders
+        if let Some(parent_id) = template.parent_order_id
+            && !errors.iter().any(|e| e.contains(&format!("parent order {}", parent_id))) {
+            self.active_orders.lock().await.remove(&parent_id);
+        }
+        if let Some(stop_id) = template.stop_order_id
+            && !errors.iter().any(|e| e.contains(&format!("stop order {}", stop_id))) {
+            self.active_orders.lock().await.remove(&stop_id);
+        }
+        for tranche_id in &template.tranche_order_ids {
+            if !errors.iter().any(|e| e.contains(&format!("exit order {}", tranche_id))) {
+                self.active_orders.lock().await.remove(tranche_id);
+            }
+        }
+        
+        if errors.is_empty() {
+            template.status = OrderTemplateStatus::Inactive;
+            template.parent_order_id = None;
+            template.stop_order_id = None;
+            template.tranche_order_ids.clear();
+            
+            if let Some(db) = &self.db {
+                let db = db.lock().await;
+                if let Err(e) = db.delete_active_order(template_id).await {
+                    err!("Failed to remove active orders for template {}: {}", template_id, e);
+                }
+                if let Err(e) = db.save_template(DbOrderTemplate::from_order_template(template)).await {
+                    err!("Failed to persist deactivated template {}: {}", template_id, e);
+                }
+            }
+            
+            self.audit(template_id, AuditAction::Deactivate, source, Some(&before), Some(template)).await;
+            inf!("Deactivated template {}", template_id);
+            Ok(())
+        } else {
+            template.status = OrderTemplateStatus::Failed;
+            Err(AppError::IBConnection(errors.join(", ")))
+        }
+    }
+    
+    // OCO entry pairs
+    /// Create two templates whose entries are placed as one IB OCA group
+    pub async fn create_oco_pair(
+        &self,
+        mut first: OrderTemplate,
+        mut second: OrderTemplate,
+    ) -> Result<(String, String), AppError> {
+        first.validate_oco_pair(&second).map_err(AppError::Validation)?;
+        
+        let group = format!("zakaz-oca-{}", uuid::Uuid::new_v4());
+        first.oca_group = Some(group.clone());
+        second.oca_group = Some(group.clone());
+        
+        let first_id = self.create_template(first).await?;
+        let second_id = match self.create_template(second).await {
+            Ok(id) => id,
+            Err(e) => {
+                // Don't leave half a pair behind
+                let _ = self.delete_template(&first_id).await;
+                return Err(e);
+            }
+        };
+        
+        inf!("Created OCO pair {} / {} in group {}", first_id, second_id, group);
+        Ok((first_id, second_id))
+    }
+    
+    /// Activate a template together with every template sharing its OCA group
+    pub async fn activate_oco_pair(&self, template_id: &str, override_price_band: bool, override_limits: bool) -> Result<Vec<String>, AppError> {
+        let template = self.get_template(template_id).await
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        let group = template.oca_group
+            .ok_or(AppError::Validation("Template is not part of an OCO pair".to_string()))?;
+        
+        let mut members: Vec<OrderTemplate> = self.order_templates.read().await
+            .values()
+            .filter(|t| t.oca_group.as_ref() == Some(&group))
+            .cloned()
+            .collect();
+        members.sort_by_key(|t| t.created_at);
+        
+        let mut activated: Vec<String> = Vec::new();
+        for member in members {
+            if let Err(e) = self.activate_template(&member.id, override_price_band, override_limits, AuditSource::UI).await {
+                // One live entry without its partner is not the planned trade
+                for id in &activated {
+                    if let Err(cancel_err) = self.deactivate_template(id, AuditSource::UI).await {
+                        err!("Failed to roll back OCO member {}: {}", id, cancel_err);
+                    }
+                }
+                return Err(e);
+            }
+            activated.push(member.id);
+        }
+        
+        inf!("Activated OCO group {} ({} templates)", group, activated.len());
+        Ok(activated)
+    }
+    
+    // Orphan adoption
+    /// Open orders and positions on the active account, as reported by IB
+    async fn fetch_account_state(&self) -> Result<(Vec<OpenOrderInfo>, Vec<PositionInfo>), AppError> {
+        let client = self.get_active_client().await?;
+        tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            
+            let mut open_orders = Vec::new();
+            for item in &client_guard.all_open_orders()? {
+                if let orders::Orders::OrderData(data) = item {
+                    open_orders.push(OpenOrderInfo {
+                        order_id: data.order_id,
+                        parent_id: data.order.parent_id,
+                        symbol: data.contract.symbol.clone(),
+                        currency: data.contract.currency.clone(),
+                        side: match data.order.action {
+                            orders::Action::Buy => OrderSide::Long,
+                            _ => OrderSide::Short,
+                        },
+                        order_type: data.order.order_type.clone(),
+                        quantity: data.order.total_quantity,
+                        limit_price: data.order.limit_price,
+                        aux_price: data.order.aux_price,
+                        time_in_force: data.order.tif.clone(),
+                        order_ref: data.order.order_ref.clone(),
+                    });
+                }
+            }
+            
+            // The positions stream stays open for updates; the snapshot ends at PositionEnd
+            let mut positions = Vec::new();
+            for update in &client_guard.positions()? {
+                match update {
+                    ibapi::accounts::PositionUpdate::Position(position) => positions.push(PositionInfo {
+                        symbol: position.contract.symbol.clone(),
+                        currency: position.contract.currency.clone(),
+                        quantity: position.position,
+                        average_cost: position.average_cost,
+                    }),
+                    ibapi::accounts::PositionUpdate::PositionEnd => break,
+                }
+            }
+            
+            Ok::<_, ibapi::Error>((open_orders, positions))
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("Failed to fetch account state: {}", e)))
+    }
+    
+    /// Mirror IB orders and positions zakaz didn't place as read-only templates.
+    /// Re-running refreshes them: ones that vanished from IB are dropped, claimed ones are left alone.
+    pub async fn adopt_orphans(&self) -> Result<usize, AppError> {
+        let (open_orders, positions) = self.fetch_account_state().await?;
+        
+        let mut templates = self.order_templates.write().await;
+        let managed: Vec<OrderTemplate> = templates.values().filter(|t| !t.is_read_only).cloned().collect();
+        let adopted = orphans::adopt(&open_orders, &positions, &managed);
+        
+        let stale: Vec<OrderTemplate> = templates.values()
+            .filter(|t| t.is_read_only && !adopted.iter().any(|a| a.id == t.id))
+            .cloned()
+            .collect();
+        for template in stale {
+            if let Some(db) = &self.db {
+                db.lock().await.delete_template(&template.id).await?;
+            }
+            self.untrack_orders(&template).await;
+            templates.remove(&template.id);
+            inf!("Dropped read-only template {}: no longer on IB", template.id);
+        }
+        
+        let count = adopted.len();
+        for template in adopted {
+            let previous = templates.get(&template.id).cloned();
+            self.persist_template(&template).await?;
+            if let Some(db) = &self.db
+                && let Some(parent_id) = template.parent_order_id
+            {
+                let db = db.lock().await;
+                db.delete_active_order(&template.id).await?;
+                db.create_active_order(DbActiveOrder {
+                    template_id: template.id.clone(),
+                    ib_order_id: parent_id as i64,
+                    ib_stop_order_id: template.stop_order_id.map(|id| id as i64),
+                    ib_tranche_order_ids: None,
+                    submitted_at: chrono::Utc::now().to_rfc3339(),
+                }).await?;
+            }
+            {
+                let mut active_orders = self.active_orders.lock().await;
+                for order_id in template.parent_order_id.into_iter().chain(template.stop_order_id) {
+                    active_orders.insert(order_id, template.id.clone());
+                }
+            }
+            if previous.is_none() {
+                self.audit(&template.id, AuditAction::Adopt, AuditSource::Reconciliation, None, Some(&template)).await;
+                inf!("Adopted {} from IB as read-only template {}", template.name, template.id);
+            }
+            templates.insert(template.id.clone(), template);
+        }
+        
+        Ok(count)
+    }
+    
+    async fn untrack_orders(&self, template: &OrderTemplate) {
+        let mut active_orders = self.active_orders.lock().await;
+        for order_id in template.parent_order_id.iter().chain(&template.stop_order_id).chain(&template.tranche_order_ids) {
+            active_orders.remove(order_id);
+        }
+    }
+    
+    /// Take over a read-only template so zakaz manages it from now on
+    pub async fn claim_template(&self, template_id: &str) -> Result<(), AppError> {
+        let mut templates = self.order_templates.write().await;
+        let template = templates.get_mut(template_id)
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        if !template.is_read_only {
+            return Err(AppError::Validation("Template is already managed by zakaz".to_string()));
+        }
+        
+        let before = template.clone();
+        template.is_read_only = false;
+        self.persist_template(template).await?;
+        self.audit(template_id, AuditAction::Claim, AuditSource::UI, Some(&before), Some(template)).await;
+        inf!("Claimed read-only template {}", template_id);
+        Ok(())
+    }
+    
+    // Scheduled activation
+    pub async fn schedule_activation(
+        &self,
+        template_id: &str,
+        activate_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<(), AppError> {
+        let mut template = self.get_template(template_id).await
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        
+        if activate_at.is_some() && !template.can_activate() {
+            return Err(AppError::Validation("Only inactive templates can be scheduled".to_string()));
+        }
+        
+        template.activate_at = activate_at;
+        self.update_template(template).await
+    }
+    
+    /// Best available current price: streamed market data, else the latest hourly close
+    async fn current_price(&self, symbol: &str) -> Result<f64, AppError> {
+        if let Some(data) = self.get_market_data(symbol).await
+            && data.last > 0.0 {
+            return Ok(data.last);
+        }
+        
+        // Include extended hours so premarket checks see the latest trade
+        self.get_historical_data(symbol, 1, "1 hour", false).await?
+            .bars
+            .last()
+            .map(|bar| bar.close)
+            .ok_or_else(|| AppError::NotFound(format!("No recent price for {}", symbol)))
+    }
+    
+    /// Activate every template whose scheduled time has passed.
+    /// Each schedule fires once: it is cleared whether or not activation succeeds.
+    pub async fn activate_due_templates(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<(OrderTemplate, Result<(), AppError>)> {
+        let due: Vec<OrderTemplate> = self.order_templates.read().await
+            .values()
+            .filter(|t| t.is_due(now))
+            .cloned()
+            .collect();
+        
+        let mut results = Vec::new();
+        for template in due {
+            inf!("Scheduled activation of template {} ({})", template.id, template.name);
+            if let Err(e) = self.clear_schedule(&template.id).await {
+                err!("Failed to clear schedule for template {}: {}", template.id, e);
+            }
+            
+            let result = match self.current_price(&template.symbol).await {
+                Ok(price) => match template.validate_against_price(price) {
+                    Ok(()) => self.activate_template(&template.id, false, false, AuditSource::Scheduler).await,
+                    Err(e) => Err(AppError::Validation(e)),
+                },
+                Err(e) => Err(e),
+            };
+            if let Err(e) = &result {
+                wrn!("Scheduled activation of template {} failed: {}", template.id, e);
+            }
+            results.push((template, result));
+        }
+        results
+    }
+    
+    async fn clear_schedule(&self, template_id: &str) -> Result<(), AppError> {
+        let mut templates = self.order_templates.write().await;
+        if let Some(template) = templates.get_mut(template_id) {
+            let before = template.clone();
+            template.activate_at = None;
+            self.persist_template(template).await?;
+            self.audit(template_id, AuditAction::Update, AuditSource::Scheduler, Some(&before), Some(template)).await;
+        }
+        Ok(())
+    }
+    
+    // Template expiration
+    /// Close of the session `template_expiry_trading_days` from now; None when expiry is disabled
+    async fn default_expiry(&self, now: chrono::DateTime<chrono::Utc>) -> Option<chrono::DateTime<chrono::Utc>> {
+        let days = self.settings().await.orders.template_expiry_trading_days;
+        if days == 0 {
+            return None;
+        }
+        
+        let today = market_hours::session_date(now);
+        Some(market_hours::market_close_on(market_hours::add_trading_days(today, days)))
+    }
+    
+    /// Mark templates that passed their expiry without activating as expired.
+    /// Returns the templates that were expired.
+    pub async fn expire_templates(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<OrderTemplate> {
+        let mut expired = Vec::new();
+        let mut templates = self.order_templates.write().await;
+        for template in templates.values_mut().filter(|t| t.should_expire(now)) {
+            let before = template.clone();
+            template.status = OrderTemplateStatus::Expired;
+            template.activate_at = None;
+            if let Err(e) = self.persist_template(template).await {
+                err!("Failed to persist expiry of template {}: {}", template.id, e);
+            }
+            self.audit(&template.id, AuditAction::Expire, AuditSource::Scheduler, Some(&before), Some(template)).await;
+            inf!("Template {} ({}) expired without activating", template.id, template.name);
+            expired.push(template.clone());
+        }
+        expired
+    }
+    
+    // End-of-day deactivation
+    async fn eod_deactivation_minutes(&self) -> i64 {
+        self.settings().await.orders.eod_deactivation_minutes
+    }
+    
+    /// Near the close, cancel active DAY templates so their entries don't linger in TWS.
+    /// Returns nothing outside the pre-close window.
+    pub async fn deactivate_day_templates(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<(OrderTemplate, Result<(), AppError>)> {
+        let minutes = self.eod_deactivation_minutes().await;
+        if market_hours::close_within(now, minutes).is_none() {
+            return Vec::new();
+        }
+        
+        let day_templates: Vec<OrderTemplate> = self.order_templates.read().await
+            .values()
+            // Filled entries keep their exits; only unfilled brackets are pulled
+            .filter(|t| t.can_deactivate() && t.time_in_force == TimeInForce::Day)
+            .cloned()
+            .collect();
+        
+        let mut results = Vec::new();
+        for template in day_templates {
+            let result = self.deactivate_template(&template.id, AuditSource::Scheduler).await;
+            match &result {
+                Ok(()) => inf!("End of day: deactivated DAY template {} ({})", template.id, template.name),
+                Err(e) => err!("End of day: failed to deactivate DAY template {}: {}", template.id, e),
+            }
+            results.push((template, result));
+        }
+        results
+    }
+    
+    // Daily loss limit
+    /// Restore a lock tripped earlier in the session
+    pub async fn load_trading_lock(&self) -> Result<(), AppError> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        let lock = match db.lock().await.get_trading_lock().await? {
+            Some(json) => Some(serde_json::from_str::<TradingLock>(&json)
+                .map_err(|e| AppError::Serialization(format!("Unreadable trading lock: {}", e)))?),
+            None => None,
+        };
+        *self.trading_lock.write().await = lock.filter(|l| l.is_active(chrono::Utc::now()));
+        Ok(())
+    }
+    
+    pub async fn active_trading_lock(&self, now: chrono::DateTime<chrono::Utc>) -> Option<TradingLock> {
+        self.trading_lock.read().await.clone().filter(|lock| lock.is_active(now))
+    }
+    
+    /// Open risk across live templates plus the day's P&L against the loss limit.
+    /// Offline the P&L and remaining budget are left empty rather than failing the summary.
+    pub async fn get_risk_summary(&self) -> RiskSummary {
+        let (loss_limit, _) = self.loss_limit_settings().await;
+        let daily_pnl = if self.active_account.read().await.is_some() {
+            match self.fetch_daily_pnl().await {
+                Ok((pnl, _)) => Some(pnl),
+                Err(e) => {
+                    wrn!("Risk summary without daily P&L: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        
+        let (drawdown, risk_scale) = self.drawdown_state().await;
+        let currencies = self.template_currencies().await;
+        let fx = self.fx_rates(currencies.iter().map(String::as_str)).await;
+        let templates = self.order_templates.read().await;
+        let summary = RiskSummary {
+            drawdown,
+            risk_scale,
+            ..RiskSummary::from_templates(templates.values(), daily_pnl, loss_limit, &fx)
+        };
+        if !summary.missing_fx_rates.is_empty() {
+            wrn!("Risk summary counts {:?} unconverted: no FX rate into {}", summary.missing_fx_rates, summary.base_currency);
+        }
+        summary
+    }
+    
+    /// Currencies of live templates, adopted positions included
+    async fn template_currencies(&self) -> Vec<String> {
+        let mut currencies: Vec<String> = self.order_templates.read().await
+            .values()
+            .filter(|t| t.is_active())
+            .map(|t| t.currency.to_uppercase())
+            .collect();
+        currencies.sort();
+        currencies.dedup();
+        currencies
+    }
+    
+    /// Cached FX rates into the base currency, refreshing any of `currencies` that are
+    /// missing or stale. A rate IB can't supply is left out; callers decide how to count it.
+    pub async fn fx_rates<'a>(&self, currencies: impl IntoIterator<Item = &'a str>) -> FxRates {
+        let base = self.settings().await.risk.base_currency;
+        if self.fx_rates.read().await.base != base {
+            *self.fx_rates.write().await = FxRates::new(&base);
+        }
+        
+        let now = chrono::Utc::now();
+        let stale = self.fx_rates.read().await.needs_refresh(currencies, now);
+        if !stale.is_empty() && self.active_account.read().await.is_some() {
+            for currency in stale {
+                match self.fetch_fx_rate(&currency, &base).await {
+                    Ok(rate) => self.fx_rates.write().await.set(&currency, rate, now),
+                    Err(e) => wrn!("No FX rate for {}.{}: {}", currency, base, e),
+                }
+            }
+        }
+        self.fx_rates.read().await.clone()
+    }
+    
+    /// Units of `base` per unit of `currency`, from the latest IDEALPRO midpoint. IB lists each
+    /// pair one way round only, so the inverse pair is tried when the direct one fails.
+    async fn fetch_fx_rate(&self, currency: &str, base: &str) -> Result<f64, AppError> {
+        match self.fx_midpoint(currency, base).await {
+            Ok(rate) => Ok(rate),
+            Err(direct) => match self.fx_midpoint(base, currency).await {
+                Ok(inverse) if inverse > 0.0 => Ok(1.0 / inverse),
+                _ => Err(direct),
+            },
+        }
+    }
+    
+    async fn fx_midpoint(&self, currency: &str, quote: &str) -> Result<f64, AppError> {
+        let client = self.get_active_client().await?;
+        let contract = Contract {
+            symbol: currency.to_string(),
+            security_type: ibapi::contracts::SecurityType::ForexPair,
+            exchange: "IDEALPRO".to_string(),
+            currency: quote.to_string(),
+            ..Default::default()
+        };
+        let pair = format!("{}.{}", currency, quote);
+        let bars = tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            client_guard.historical_data(
+                &contract,
+                None,
+                ibapi::market_data::historical::Duration::days(2),
+                HistoricalBarSize::Hour,
+                HistoricalWhatToShow::MidPoint,
+                false,
+            )
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("Failed to fetch {} rate: {}", pair, e)))?;
+        
+        bars.bars.last()
+            .map(|bar| bar.close)
+            .filter(|rate| *rate > 0.0)
+            .ok_or_else(|| AppError::NotFound(format!("No recent {} rate", pair)))
+    }
+    
+    async fn risk_scaling(&self) -> RiskScaling {
+        self.settings().await.risk.drawdown_risk_steps
+    }
+    
+    /// The active account's last tracked drawdown and the risk multiplier it calls for
+    async fn drawdown_state(&self) -> (Option<Drawdown>, f64) {
+        let Some(account) = *self.active_account.read().await else {
+            return (None, 1.0);
+        };
+        let drawdown = self.load_drawdown(account).await;
+        let risk_scale = match drawdown {
+            Some(drawdown) => self.risk_scaling().await.factor(drawdown.pct()),
+            None => 1.0,
+        };
+        (drawdown, risk_scale)
+    }
+    
+    async fn load_drawdown(&self, account: AccountType) -> Option<Drawdown> {
+        if let Some(drawdown) = self.drawdowns.read().await.get(&account) {
+            return Some(*drawdown);
+        }
+        let json = self.db.as_ref()?.lock().await.get_drawdown(&format!("{:?}", account)).await.ok()??;
+        let drawdown = serde_json::from_str::<Drawdown>(&json)
+            .inspect_err(|e| wrn!("Ignoring unreadable high-water mark for {:?}: {}", account, e))
+            .ok()?;
+        self.drawdowns.write().await.insert(account, drawdown);
+        Some(drawdown)
+    }
+    
+    /// Fold the account's current equity into its high-water mark. Returns the new risk
+    /// multiplier when the drawdown moved it across a step.
+    pub async fn track_drawdown(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Option<(f64, Drawdown)>, AppError> {
+        let Some(account) = *self.active_account.read().await else {
+            return Ok(None);
+        };
+        let previous = self.load_drawdown(account).await;
+        if previous.is_some_and(|d| now - d.updated_at < chrono::Duration::minutes(DRAWDOWN_REFRESH_MINUTES)) {
+            return Ok(None);
+        }
+        
+        let equity = self.account_equity().await?;
+        let mut drawdown = previous.unwrap_or_else(|| Drawdown::new(equity, now));
+        drawdown.update(equity, now);
+        self.drawdowns.write().await.insert(account, drawdown);
+        if let Some(db) = &self.db {
+            let json = serde_json::to_string(&drawdown)
+                .map_err(|e| AppError::Serialization(format!("Failed to serialize high-water mark: {}", e)))?;
+            db.lock().await.set_drawdown(&format!("{:?}", account), &json).await?;
+        }
+        
+        let scaling = self.risk_scaling().await;
+        let old_scale = previous.map_or(1.0, |d| scaling.factor(d.pct()));
+        let new_scale = scaling.factor(drawdown.pct());
+        Ok((new_scale != old_scale).then_some((new_scale, drawdown)))
+    }
+    
+    /// Commission-aware risk, breakeven and reward:risk for a template
+    pub async fn get_trade_costs(&self, template_id: &str, target_price: Option<f64>) -> Result<TradeCosts, AppError> {
+        let template = self.get_template(template_id).await
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        let commissions = self.commission_model().await?;
+        Ok(TradeCosts::for_template(&template, &commissions, target_price))
+    }
+    
+    pub async fn commission_model(&self) -> Result<CommissionModel, AppError> {
+        if self.db.is_none() {
+            return Ok(CommissionModel::None);
+        }
+        Ok(self.settings().await.orders.commission_model)
+    }
+    
+    /// Templates to backtest: the ones named, archived included, or else the whole working set
+    pub async fn backtest_templates(&self, template_ids: &[String]) -> Result<Vec<OrderTemplate>, AppError> {
+        if template_ids.is_empty() {
+            return Ok(self.get_all_templates().await);
+        }
+        let archived = match &self.db {
+            Some(_) => self.get_archived_templates().await?,
+            None => Vec::new(),
+        };
+        let mut templates = Vec::new();
+        for id in template_ids {
+            let template = match self.get_template(id).await {
+                Some(template) => template,
+                None => archived.iter().find(|t| &t.id == id).cloned()
+                    .ok_or(AppError::NotFound(format!("Template {} not found", id)))?,
+            };
+            templates.push(template);
+        }
+        Ok(templates)
+    }
+    
+    /// Daily bars from `from`'s session to today, with enough before it to seed the replay's ATR.
+    /// Returns the bars and the index of the first one to replay.
+    pub async fn backtest_bars(&self, symbol: &str, from: chrono::DateTime<chrono::Utc>) -> Result<(Vec<HistoricalBar>, usize), AppError> {
+        let days = market_hours::trading_days_between(
+            market_hours::session_date(from),
+            market_hours::session_date(chrono::Utc::now()),
+        ) as usize;
+        let bars = self.daily_bars(symbol, days + BACKTEST_ATR_PERIOD + 1).await?;
+        let start = backtest::start_index(&bars, from);
+        Ok((bars, start))
+    }
+    
+    /// The account's P&L for the day as IB computes it, and its open positions
+    async fn fetch_daily_pnl(&self) -> Result<(DailyPnL, Vec<MarkedPosition>), AppError> {
+        let client = self.get_active_client().await?;
+        let result = tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            
+            let mut pnl = None;
+            let mut positions = Vec::new();
+            if let Some(account) = client_guard.managed_accounts()?.first() {
+                // The subscription keeps streaming updates; the first one is the current figure
+                pnl = client_guard.pnl(account, None)?.next_timeout(PNL_TIMEOUT)
+                    .and_then(|pnl| DailyPnL::from_ib(pnl.daily_pnl, pnl.realized_pnl));
+                for update in &client_guard.account_updates(account)? {
+                    match update {
+                        ibapi::accounts::AccountUpdate::PortfolioValue(value) if value.position != 0.0 => {
+                            positions.push(MarkedPosition {
+                                symbol: value.contract.symbol.clone(),
+                                currency: value.contract.currency.clone(),
+                                quantity: value.position,
+                                market_price: value.market_price,
+                                average_cost: value.average_cost,
+                            });
+                        }
+                        ibapi::accounts::AccountUpdate::End => break,
+                        _ => {}
+                    }
+                }
+            }
+            
+            Ok::<_, ibapi::Error>((pnl, positions))
+        }).await;
+        let (pnl, positions) = result
+            .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+            .map_err(|e| AppError::IBConnection(format!("Failed to fetch daily P&L: {}", e)))?;
+        let pnl = pnl.ok_or(AppError::IBConnection("IB sent no daily P&L for the account".to_string()))?;
+        Ok((pnl, positions))
+    }
+    
+    async fn loss_limit_settings(&self) -> (f64, bool) {
+        let risk = self.settings().await.risk;
+        (risk.daily_loss_limit, risk.flatten_on_loss_limit)
+    }
+    
+    /// Trip the kill switch once the day's P&L breaches `daily_loss_limit`: cancel pending
+    /// templates, optionally flatten, and refuse activations until the next session.
+    /// Also reports when a previous session's lock lapses.
+    pub async fn check_loss_limit(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Option<TradingLockChange>, AppError> {
+        {
+            let mut lock = self.trading_lock.write().await;
+            match lock.as_ref() {
+                Some(held) if held.is_active(now) => return Ok(None),
+                Some(_) => {
+                    *lock = None;
+                    inf!("Trading lock released for the new session");
+                    return Ok(Some(TradingLockChange::Released));
+                }
+                None => {}
+            }
+        }
+        
+        let (loss_limit, flatten) = self.loss_limit_settings().await;
+        if loss_limit <= 0.0 || self.active_account.read().await.is_none() {
+            return Ok(None);
+        }
+        
+        let (pnl, positions) = self.fetch_daily_pnl().await?;
+        if !pnl.breaches(loss_limit) {
+            return Ok(None);
+        }
+        
+        let lock = TradingLock::new(pnl, loss_limit, now);
+        *self.trading_lock.write().await = Some(lock.clone());
+        if let Some(db) = &self.db {
+            let json = serde_json::to_string(&lock)
+                .map_err(|e| AppError::Serialization(format!("Failed to serialize trading lock: {}", e)))?;
+            if let Err(e) = db.lock().await.set_trading_lock(&json).await {
+                err!("Failed to persist trading lock: {}", e);
+            }
+        }
+        wrn!("Daily loss limit breached: P&L {:.2} against limit {:.2}", pnl.total(), loss_limit);
+        
+        let pending: Vec<String> = self.order_templates.read().await
+            .values()
+            .filter(|t| t.can_deactivate())
+            .map(|t| t.id.clone())
+            .collect();
+        let mut cancelled = Vec::new();
+        let mut errors = Vec::new();
+        for template_id in pending {
+            match self.deactivate_template(&template_id, AuditSource::Scheduler).await {
+                Ok(()) => cancelled.push(template_id),
+                Err(e) => errors.push(format!("{}: {}", template_id, e)),
+            }
+        }
+        
+        let mut flattened = Vec::new();
+        if flatten {
+            match self.flatten_positions(&positions).await {
+                Ok(symbols) => flattened = symbols,
+                Err(e) => errors.push(format!("Flatten failed: {}", e)),
+            }
+        }
+        
+        Ok(Some(TradingLockChange::Locked { lock, cancelled, flattened, errors }))
+    }
+    
+    /// Cancel the orders zakaz placed and close all positions at market; orders placed elsewhere
+    /// are left alone. Templates holding filled entries lose their (now cancelled) exit orders and are closed.
+    async fn flatten_positions(&self, positions: &[MarkedPosition]) -> Result<Vec<String>, AppError> {
+        let client = self.get_active_client().await?;
+        let first_order_id = self.reserve_order_ids(positions.len().max(1) as i32).await?;
+        let closing: Vec<(i32, Contract, orders::Order)> = positions.iter()
+            .zip(first_order_id..)
+            .map(|(position, order_id)| {
+                let order = orders::Order {
+                    action: if position.quantity > 0.0 { orders::Action::Sell } else { orders::Action::Buy },
+                    order_type: "MKT".to_string(),
+                    total_quantity: position.quantity.abs(),
+                    tif: "DAY".to_string(),
+                    order_ref: "zakaz:loss-limit".to_string(),
+                    ..Default::default()
+                };
+                let contract = Contract { currency: position.currency.clone(), ..Contract::stock(&position.symbol) };
+                (order_id, contract, order)
+            })
+            .collect();
+        let tracked: Vec<i32> = self.active_orders.lock().await.keys().copied().collect();
+        
+        let flattened = tokio::task::spawn_blocking(move || {
+            let client_guard = futures::executor::block_on(client.lock());
+            // Stops and exits would otherwise fire against the closing orders
+            for order_id in tracked {
+                if let Err(e) = client_guard.cancel_order(order_id, "") {
+                    wrn!("Failed to cancel order {} before flattening: {}", order_id, e);
+                }
+            }
+            
+            let mut flattened = Vec::new();
+            for (order_id, contract, order) in &closing {
+                client_guard.place_order(*order_id, contract, order)?;
+                flattened.push(contract.symbol.clone());
+            }
+            Ok::<_, ibapi::Error>(flattened)
+        }).await
+        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+        .map_err(|e| AppError::IBConnection(format!("Failed to flatten positions: {}", e)))?;
+        
+        let mut templates = self.order_templates.write().await;
+        for template in templates.values_mut().filter(|t| t.has_fills() && !t.is_read_only) {
+            let before = template.clone();
+            self.untrack_orders(template).await;
+            template.status = OrderTemplateStatus::Closed;
+            template.parent_order_id = None;
+            template.stop_order_id = None;
+            template.tranche_order_ids.clear();
+            if let Some(db) = &self.db
+                && let Err(e) = db.lock().await.delete_active_order(&template.id).await {
+                err!("Failed to remove active orders for template {}: {}", template.id, e);
+            }
+            self.persist_template(template).await?;
+            self.audit(&template.id, AuditAction::Close, AuditSource::Scheduler, Some(&before), Some(template)).await;
+        }
+        
+        wrn!("Flattened {} position(s) after the daily loss limit", flattened.len());
+        Ok(flattened)
+    }
+    
+    // Sectors
+    /// Create a sector or change its caps
+    pub async fn save_sector(&self, name: String, max_notional: Option<f64>, max_risk: Option<f64>) -> Result<(), AppError> {
+        if name.trim().is_empty() {
+            return Err(AppError::Validation("Sector name cannot be empty".to_string()));
+        }
+        if max_notional.into_iter().chain(max_risk).any(|cap| cap <= 0.0) {
+            return Err(AppError::Validation("Sector caps must be positive".to_string()));
+        }
+        
+        let sector = Sector { name: name.trim().to_string(), max_notional, max_risk, symbols: Vec::new() };
+        self.database()?.lock().await.save_sector(DbSector::from_sector(&sector)).await?;
+        inf!("Saved sector {}", sector.name);
+        Ok(())
+    }
+    
+    pub async fn delete_sector(&self, name: &str) -> Result<(), AppError> {
+        self.database()?.lock().await.delete_sector(name).await?;
+        inf!("Deleted sector {}", name);
+        Ok(())
+    }
+    
+    /// Move a symbol into a sector, or out of any sector with None
+    pub async fn assign_symbol_sector(&self, symbol: &str, sector: Option<&str>) -> Result<(), AppError> {
+        let symbol = symbol.trim().to_uppercase();
+        let db = self.database()?.lock().await;
+        match sector {
+            Some(sector) => {
+                if !db.get_all_sectors().await?.iter().any(|s| s.name == sector) {
+                    return Err(AppError::NotFound(format!("Sector {} not found", sector)));
+                }
+                db.set_symbol_sector(&symbol, sector).await?;
+            }
+            None => db.clear_symbol_sector(&symbol).await?,
+        }
+        Ok(())
+    }
+    
+    // Per-symbol settings
+    pub async fn get_symbol_settings(&self, symbol: &str) -> Result<Option<SymbolSettings>, AppError> {
+        let symbol = symbol.trim().to_uppercase();
+        let settings = self.database()?.lock().await.get_symbol_settings(&symbol).await?;
+        Ok(settings.map(|s| s.to_settings()))
+    }
+    
+    pub async fn get_all_symbol_settings(&self) -> Result<Vec<SymbolSettings>, AppError> {
+        let settings = self.database()?.lock().await.get_all_symbol_settings().await?;
+        Ok(settings.iter().map(DbSymbolSettings::to_settings).collect())
+    }
+    
+    /// Save a symbol's overrides, replacing any it had. The sector must already exist.
+    pub async fn save_symbol_settings(&self, mut settings: SymbolSettings) -> Result<(), AppError> {
+        settings.symbol = settings.symbol.trim().to_uppercase();
+        settings.validate().map_err(AppError::Validation)?;
+        
+        let db = self.database()?.lock().await;
+        if let Some(sector) = &settings.sector
+            && !db.get_all_sectors().await?.iter().any(|s| &s.name == sector) {
+            return Err(AppError::NotFound(format!("Sector {} not found", sector)));
+        }
+        db.save_symbol_settings(DbSymbolSettings::from_settings(&settings)).await?;
+        inf!("Saved settings for {}", settings.symbol);
+        Ok(())
+    }
+    
+    /// Drop a symbol's overrides so it follows the global settings again; its sector stays
+    pub async fn delete_symbol_settings(&self, symbol: &str) -> Result<(), AppError> {
+        let symbol = symbol.trim().to_uppercase();
+        if !self.database()?.lock().await.delete_symbol_settings(&symbol).await? {
+            return Err(AppError::NotFound(format!("No settings for {}", symbol)));
+        }
+        inf!("Deleted settings for {}", symbol);
+        Ok(())
+    }
+    
+    pub async fn get_all_sectors(&self) -> Result<Vec<Sector>, AppError> {
+        let db = self.database()?.lock().await;
+        let mut sectors = Vec::new();
+        for sector in db.get_all_sectors().await? {
+            let symbols = db.get_sector_symbols(&sector.name).await?;
+            sectors.push(sector.to_sector(symbols));
+        }
+        Ok(sectors)
+    }
+    
+    // Earnings calendar
+    pub async fn save_earnings_event(&self, mut event: EarningsEvent) -> Result<(), AppError> {
+        event.symbol = event.symbol.trim().to_uppercase();
+        if event.symbol.is_empty() {
+            return Err(AppError::Validation("Earnings need a symbol".to_string()));
+        }
+        self.database()?.lock().await.save_earnings_event(DbEarningsEvent::from_event(&event)).await?;
+        inf!("Saved earnings: {}", event.describe());
+        Ok(())
+    }
+    
+    pub async fn remove_earnings_event(&self, symbol: &str, date: chrono::NaiveDate) -> Result<(), AppError> {
+        self.database()?.lock().await
+            .delete_earnings_event(&symbol.trim().to_uppercase(), &date.format("%Y-%m-%d").to_string())
+            .await?;
+        Ok(())
+    }
+    
+    pub async fn get_earnings_events(&self, symbol: Option<&str>) -> Result<Vec<EarningsEvent>, AppError> {
+        let events = match &self.db {
+            Some(db) => db.lock().await.get_earnings_events(symbol.map(|s| s.trim().to_uppercase()).as_deref()).await?,
+            None => Vec::new(),
+        };
+        Ok(events.iter().filter_map(|e| e.to_event()).collect())
+    }
+    
+    async fn earnings_policy(&self) -> (EarningsPolicy, u32) {
+        let orders = self.settings().await.orders;
+        (orders.earnings_policy, orders.earnings_hold_days)
+    }
+    
+    /// Earnings reacting between today and the end of the expected hold, unless the policy is off
+    pub async fn earnings_within_hold(&self, template_id: &str) -> Result<Option<EarningsEvent>, AppError> {
+        let template = self.get_template(template_id).await
+            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+        let (policy, hold_days) = self.earnings_policy().await;
+        if policy == EarningsPolicy::Off {
+            return Ok(None);
+        }
+        let events = self.get_earnings_events(Some(&template.symbol)).await?;
+        let today = market_hours::session_date(chrono::Utc::now());
+        Ok(earnings::within_hold(&events, today, hold_days).cloned())
+    }
+    
+    /// Daily bar dates to leave out of ATR, when `atr_exclude_earnings` is on
+    pub async fn earnings_reaction_days(&self, symbol: &str) -> std::collections::HashSet<chrono::NaiveDate> {
+        let Some(db) = &self.db else {
+            return std::collections::HashSet::new();
+        };
+        if !self.settings().await.atr.exclude_earnings {
+            return std::collections::HashSet::new();
+        }
+        let events = db.lock().await.get_earnings_events(Some(symbol)).await;
+        match events {
+            Ok(events) => events.iter()
+                .filter_map(|e| e.to_event())
+                .flat_map(|e| e.reaction_dates())
+                .collect(),
+            Err(e) => {
+                wrn!("No earnings dates for {} ATR: {}", symbol, e);
+                std::collections::HashSet::new()
+            }
+        }
+    }
+    
+    // Watchlist
+    /// Every watchlist with its symbols, the default one first
+    pub async fn get_watchlists(&self) -> Result<Vec<Watchlist>, AppError> {
+        let Some(db) = &self.db else {
+            return Ok(Vec::new());
+        };
+        let db = db.lock().await;
+        let mut watchlists = Vec::new();
+        for watchlist in db.get_watchlists().await? {
+            let entries = db.get_watchlist(&watchlist.id).await?;
+            watchlists.push(watchlist.to_watchlist(&entries));
+        }
+        Ok(watchlists)
+    }
+    
+    pub async fn create_watchlist(&self, name: &str) -> Result<Watchlist, AppError> {
+        let db = self.database()?.lock().await;
+        let existing = db.get_watchlists().await?;
+        let name = Self::watchlist_name(name, &existing, None)?;
+        let watchlist = DbWatchlist::new(name, existing.len() as i64);
+        db.create_watchlist(watchlist.clone()).await?;
+        inf!("Created watchlist {}", watchlist.name);
+        Ok(watchlist.to_watchlist(&[]))
+    }
+    
+    pub async fn rename_watchlist(&self, watchlist_id: &str, name: &str) -> Result<(), AppError> {
+        let db = self.database()?.lock().await;
+        let name = Self::watchlist_name(name, &db.get_watchlists().await?, Some(watchlist_id))?;
+        if !db.rename_watchlist(watchlist_id, &name).await? {
+            return Err(AppError::NotFound(format!("Watchlist {}", watchlist_id)));
+        }
+        Ok(())
+    }
+    
+    /// Delete a watchlist and its symbols; the default one stays
+    pub async fn delete_watchlist(&self, watchlist_id: &str) -> Result<(), AppError> {
+        if watchlist_id == DEFAULT_WATCHLIST_ID {
+            return Err(AppError::Validation("The default watchlist can't be deleted".to_string()));
+        }
+        if !self.database()?.lock().await.delete_watchlist(watchlist_id).await? {
+            return Err(AppError::NotFound(format!("Watchlist {}", watchlist_id)));
+        }
+        Ok(())
+    }
+    
+    /// A trimmed name no other watchlist than `renaming` has, ignoring case
+    fn watchlist_name(name: &str, existing: &[DbWatchlist], renaming: Option<&str>) -> Result<String, AppError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::Validation("Watchlist name is empty".to_string()));
+        }
+        if existing.iter().any(|w| Some(w.id.as_str()) != renaming && w.name.eq_ignore_ascii_case(name)) {
+            return Err(AppError::Validation(format!("A watchlist named {} already exists", name)));
+        }
+        Ok(name.to_string())
+    }
+    
+    /// The watchlist `watchlist_id` names, or the default one
+    async fn watchlist_id(&self, watchlist_id: Option<&str>) -> Result<String, AppError> {
+        let id = watchlist_id.unwrap_or(DEFAULT_WATCHLIST_ID);
+        if !self.database()?.lock().await.get_watchlists().await?.iter().any(|w| w.id == id) {
+            return Err(AppError::NotFound(format!("Watchlist {}", id)));
+        }
+        Ok(id.to_string())
+    }
+    
+    /// Watch `symbols` at the end of a watchlist (None: the default one), returning them
+    /// normalized; a note replaces any earlier one
+    pub async fn add_to_watchlist(&self, watchlist_id: Option<&str>, symbols: Vec<String>, note: Option<String>) -> Result<Vec<String>, AppError> {
+        let symbols: Vec<String> = symbols.iter().map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty()).collect();
+        if symbols.is_empty() {
+            return Err(AppError::Validation("No symbols to watch".to_string()));
+        }
+        
+        let watchlist_id = self.watchlist_id(watchlist_id).await?;
+        let db = self.database()?.lock().await;
+        for symbol in &symbols {
+            let entry = WatchlistEntry { symbol: symbol.clone(), note: note.clone(), added_at: chrono::Utc::now() };
+            db.save_watchlist_entry(&watchlist_id, DbWatchlistEntry::from_entry(&entry)).await?;
+        }
+        inf!("Watching {}", symbols.join(", "));
+        Ok(symbols)
+    }
+    
+    pub async fn remove_from_watchlist(&self, watchlist_id: Option<&str>, symbol: &str) -> Result<(), AppError> {
+        let watchlist_id = self.watchlist_id(watchlist_id).await?;
+        self.database()?.lock().await.delete_watchlist_entry(&watchlist_id, &symbol.trim().to_uppercase()).await?;
+        Ok(())
+    }
+    
+    /// Put a watchlist's symbols in the order given, which must list each of them once
+    pub async fn reorder_watchlist(&self, watchlist_id: &str, symbols: Vec<String>) -> Result<(), AppError> {
+        let symbols: Vec<String> = symbols.iter().map(|s| s.trim().to_uppercase()).collect();
+        let db = self.database()?.lock().await;
+        let mut current: Vec<String> = db.get_watchlist(watchlist_id).await?.into_iter().map(|e| e.symbol).collect();
+        let mut requested = symbols.clone();
+        current.sort();
+        requested.sort();
+        if current != requested {
+            return Err(AppError::Validation("A new order must list each of the watchlist's symbols once".to_string()));
+        }
+        db.reorder_watchlist(watchlist_id, &symbols).await?;
+        Ok(())
+    }
+    
+    /// A watchlist's symbols in order (None: the default one)
+    pub async fn get_watchlist(&self, watchlist_id: Option<&str>) -> Result<Vec<WatchlistEntry>, AppError> {
+        let entries = match &self.db {
+            Some(db) => db.lock().await.get_watchlist(watchlist_id.unwrap_or(DEFAULT_WATCHLIST_ID)).await?,
+            None => Vec::new(),
+        };
+        Ok(entries.iter().map(|e| e.to_entry()).collect())
+    }
+    
+    /// Every symbol on any watchlist
+    pub async fn watched_symbols(&self) -> Result<Vec<String>, AppError> {
+        match &self.db {
+            Some(db) => Ok(db.lock().await.get_watched_symbols().await?),
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    /// Filtered ATR for each symbol on a watchlist (None: the default one), in its order;
+    /// a symbol that fails keeps its error
+    pub async fn watchlist_atr(
+        &self,
+        watchlist_id: Option<&str>,
+        period_days: usize,
+        method: OutlierMethod,
+        smoothing: AtrSmoothing,
+    ) -> Result<WatchlistAtr, AppError> {
+        let watchlist_id = self.watchlist_id(watchlist_id).await?;
+        let mut results = Vec::new();
+        for entry in self.get_watchlist(Some(&watchlist_id)).await? {
+            let result = self.calculate_filtered_atr(&entry.symbol, period_days, method, smoothing, false).await;
+            if let Err(e) = &result {
+                wrn!("No ATR for watched {}: {}", entry.symbol, e);
+            }
+            results.push((entry.symbol, result.map_err(|e| e.to_string())));
+        }
+        Ok(results)
+    }
+    
+    // Market scanner
+    /// Screen `symbols` (None: `watchlist_id`'s symbols, else the `scanner_universe` setting,
+    /// else every watched symbol) on cached daily bars. Returns the passing symbols, closest to
+    /// their N-day high first. A symbol with its own ATR period is scanned with it.
+    pub async fn run_scan(&self, criteria: ScanCriteria, symbols: Option<Vec<String>>, watchlist_id: Option<String>) -> Result<Vec<ScanResult>, AppError> {
+        let symbols = match (symbols, watchlist_id) {
+            (Some(symbols), _) => symbols.iter().map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty()).collect(),
+            (None, Some(watchlist_id)) => {
+                let watchlist_id = self.watchlist_id(Some(&watchlist_id)).await?;
+                self.get_watchlist(Some(&watchlist_id)).await?.into_iter().map(|e| e.symbol).collect()
+            }
+            (None, None) => {
+                let universe = self.settings().await.scanner.universe;
+                if universe.is_empty() {
+                    self.watched_symbols().await?
+                } else {
+                    universe
+                }
             }
-            
-            Ok(())
-        }).await
-        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?;
+        };
+        if symbols.is_empty() {
+            return Err(AppError::Validation("Nothing to scan; set scanner_universe or add to the watchlist".to_string()));
+        }
         
-        match result {
-            Ok(()) => {
-                // Track orders
-                active_orders.lock().await.insert(parent_order_id, template_id_clone.clone());
-                active_orders.lock().await.insert(stop_order_id, template_id_clone.clone());
-                
-                // Update template status
-                template.status = OrderTemplateStatus::Active;
-                template.activated_at = Some(chrono::Utc::now());
-                
-                inf!("Activated template {} with orders {} and {}", template_id, parent_order_id, stop_order_id);
-                Ok(())
+        let atr_periods: HashMap<String, usize> = match &self.db {
+            Some(db) => db.lock().await.get_all_symbol_settings().await?
+                .into_iter()
+                .filter_map(|s| Some((s.symbol.clone(), s.to_settings().atr_period?)))
+                .collect(),
+            None => HashMap::new(),
+        };
+        
+        let mut passed = Vec::new();
+        for symbol in &symbols {
+            let mut criteria = criteria;
+            if let Some(&period) = atr_periods.get(symbol) {
+                criteria.atr_period = period;
             }
-            Err(e) => {
-                err!("Failed to place orders: {}", e);
-                template.status = OrderTemplateStatus::Failed;
-                template.parent_order_id = None;
-                template.stop_order_id = None;
-                Err(AppError::IBConnection(format!("Failed to place orders: {}", e)))
+            let bars = match self.daily_bars(symbol, criteria.bars_needed()).await {
+                Ok(bars) => bars,
+                Err(e) => {
+                    wrn!("Scan skipped {}: {}", symbol, e);
+                    continue;
+                }
+            };
+            let Some(result) = ScanResult::from_bars(symbol, &bars, &criteria) else {
+                wrn!("Scan skipped {}: only {} daily bars", symbol, bars.len());
+                continue;
+            };
+            if !criteria.failures(&result).is_empty() {
+                continue;
+            }
+            // Intraday bars are only fetched for symbols that pass on daily bars
+            let mut result = result;
+            if criteria.min_relative_volume.is_some() {
+                result.relative_volume = match self.get_relative_volume(symbol, criteria.rvol_sessions).await {
+                    Ok(rvol) => rvol.ratio,
+                    Err(e) => {
+                        wrn!("No relative volume for {}: {}", symbol, e);
+                        None
+                    }
+                };
+                if !criteria.failures(&result).is_empty() {
+                    continue;
+                }
             }
+            passed.push(result);
         }
+        passed.sort_by(|a, b| a.from_high_pct.total_cmp(&b.from_high_pct));
+        
+        let mut scan_results = self.scan_results.write().await;
+        for result in &passed {
+            scan_results.insert(result.symbol.clone(), result.clone());
+        }
+        inf!("Scan passed {} of {} symbols", passed.len(), symbols.len());
+        Ok(passed)
     }
     
-    pub async fn deactivate_template(&self, template_id: &str) -> Result<(), AppError> {
-        let client = self.get_active_client().await?;
-        
-        let mut templates = self.order_templates.write().await;
-        let template = templates.get_mut(template_id)
-            .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?;
+    /// An unsaved template from the latest scan of `symbol`: a limit at the last close,
+    /// the model's ATR stop from the scan's ATR, and quantity left for sizing
+    pub async fn template_from_scan(&self, symbol: &str, side: OrderSide, model: TradingModel) -> Result<OrderTemplate, AppError> {
+        let symbol = symbol.trim().to_uppercase();
+        let scan = self.scan_results.read().await.get(&symbol).cloned()
+            .ok_or_else(|| AppError::NotFound(format!("No scan result for {}; run a scan first", symbol)))?;
+        let defaults = self.get_model_defaults(model).await?;
         
-        if !template.can_deactivate() {
-            return Err(AppError::Validation("Template cannot be deactivated in current state".to_string()));
+        let stop_price = defaults.default_stop(scan.last_close, side, scan.atr);
+        let mut template = OrderTemplate::new(
+            format!("{} {:?} (scan)", symbol, model),
+            symbol,
+            side,
+            0.0,
+            scan.last_close,
+            stop_price,
+            TimeInForce::GTC,
+            model,
+        );
+        template.entry_type = EntryType::Limit;
+        Ok(template)
+    }
+    
+    // Template baskets
+    pub async fn create_basket(&self, name: String, template_ids: Vec<String>) -> Result<String, AppError> {
+        if name.trim().is_empty() {
+            return Err(AppError::Validation("Basket name cannot be empty".to_string()));
+        }
+        {
+            let templates = self.order_templates.read().await;
+            if let Some(missing) = template_ids.iter().find(|id| !templates.contains_key(*id)) {
+                return Err(AppError::NotFound(format!("Template {} not found", missing)));
+            }
         }
         
-        template.status = OrderTemplateStatus::Deactivating;
+        let db = self.database()?.lock().await;
+        let basket = DbBasket::new(name);
+        let basket_id = basket.id.clone();
+        db.create_basket(basket, &template_ids).await?;
         
-        // Cancel both orders in blocking task
-        let client_clone = client.clone();
-        let parent_id = template.parent_order_id;
-        let stop_id = template.stop_order_id;
+        inf!("Created basket {} with {} templates", basket_id, template_ids.len());
+        Ok(basket_id)
+    }
+    
+    pub async fn delete_basket(&self, basket_id: &str) -> Result<(), AppError> {
+        let db = self.database()?.lock().await;
+        if db.get_basket(basket_id).await?.is_none() {
+            return Err(AppError::NotFound(format!("Basket {} not found", basket_id)));
+        }
+        db.delete_basket(basket_id).await?;
+        inf!("Deleted basket {}", basket_id);
+        Ok(())
+    }
+    
+    pub async fn add_to_basket(&self, basket_id: &str, template_id: &str) -> Result<(), AppError> {
+        if !self.order_templates.read().await.contains_key(template_id) {
+            return Err(AppError::NotFound(format!("Template {} not found", template_id)));
+        }
+        let db = self.database()?.lock().await;
+        if db.get_basket(basket_id).await?.is_none() {
+            return Err(AppError::NotFound(format!("Basket {} not found", basket_id)));
+        }
+        db.add_template_to_basket(basket_id, template_id).await?;
+        Ok(())
+    }
+    
+    pub async fn remove_from_basket(&self, basket_id: &str, template_id: &str) -> Result<(), AppError> {
+        self.database()?.lock().await.remove_template_from_basket(basket_id, template_id).await?;
+        Ok(())
+    }
+    
+    pub async fn get_basket(&self, basket_id: &str) -> Result<TemplateBasket, AppError> {
+        let db = self.database()?.lock().await;
+        let basket = db.get_basket(basket_id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Basket {} not found", basket_id)))?;
+        let template_ids = db.get_basket_template_ids(basket_id).await?;
+        Ok(basket.to_basket(template_ids))
+    }
+    
+    pub async fn get_all_baskets(&self) -> Result<Vec<TemplateBasket>, AppError> {
+        let db = self.database()?.lock().await;
+        let mut baskets = Vec::new();
+        for basket in db.get_all_baskets().await? {
+            let template_ids = db.get_basket_template_ids(&basket.id).await?;
+            baskets.push(basket.to_basket(template_ids));
+        }
+        Ok(baskets)
+    }
+    
+    /// Activate every basket member in order; one failure doesn't stop the rest
+    pub async fn activate_basket(&self, basket_id: &str) -> Result<BasketResult, AppError> {
+        let basket = self.get_basket(basket_id).await?;
+        let mut result = BasketResult::new(basket.id.clone());
         
-        let result = tokio::task::spawn_blocking(move || {
-            let client_guard = futures::executor::block_on(client_clone.lock());
-            let mut errors = Vec::new();
-            
-            if let Some(parent_id) = parent_id {
-                if let Err(e) = client_guard.cancel_order(parent_id, "") {
-                    errors.push(format!("Failed to cancel parent order {}: {}", parent_id, e));
+        for template_id in basket.template_ids {
+            match self.activate_template(&template_id, false, false, AuditSource::UI).await {
+                Ok(()) => result.succeeded.push(template_id),
+                Err(e) => {
+                    wrn!("Basket {}: failed to activate {}: {}", basket.name, template_id, e);
+                    result.failed.push((template_id, e.to_string()));
                 }
             }
-            
-            if let Some(stop_id) = stop_id {
-                if let Err(e) = client_guard.cancel_order(stop_id, "") {
-                    errors.push(format!("Failed to cancel stop order {}: {}", stop_id, e));
-                }
-            }
-            
-            errors
-        }).await
-        .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?;
+        }
         
-        let errors = result;
+        inf!("Basket {} activation: {}/{} succeeded", basket.name, result.succeeded.len(), result.total());
+        Ok(result)
+    }
+    
+    /// Deactivate every active basket member; inactive members are skipped
+    pub async fn deactivate_basket(&self, basket_id: &str) -> Result<BasketResult, AppError> {
+        let basket = self.get_basket(basket_id).await?;
+        let mut result = BasketResult::new(basket.id.clone());
         
-        // Update active orders
-        if let Some(parent_id) = template.parent_order_id {
-            if !errors.iter().any(|e| e.contains(&format!("parent order {}", parent_id))) {
-                self.active_orders.lock().await.remove(&parent_id);
+        for template_id in basket.template_ids {
+            let is_active = self.get_template(&template_id).await
+                .is_some_and(|t| t.can_deactivate());
+            if !is_active {
+                continue;
             }
-        }
-        if let Some(stop_id) = template.stop_order_id {
-            if !errors.iter().any(|e| e.contains(&format!("stop order {}", stop_id))) {
-                self.active_orders.lock().await.remove(&stop_id);
+            match self.deactivate_template(&template_id, AuditSource::UI).await {
+                Ok(()) => result.succeeded.push(template_id),
+                Err(e) => {
+                    wrn!("Basket {}: failed to deactivate {}: {}", basket.name, template_id, e);
+                    result.failed.push((template_id, e.to_string()));
+                }
             }
         }
         
-        if errors.is_empty() {
-            template.status = OrderTemplateStatus::Inactive;
-            template.parent_order_id = None;
-            template.stop_order_id = None;
-            inf!("Deactivated template {}", template_id);
-            Ok(())
-        } else {
-            template.status = OrderTemplateStatus::Failed;
-            Err(AppError::IBConnection(errors.join(", ")))
-        }
+        inf!("Basket {} deactivation: {}/{} succeeded", basket.name, result.succeeded.len(), result.total());
+        Ok(result)
     }
     
     // Market data
-    pub async fn subscribe_market_data(&self, symbol: &str) -> Result<(), AppError> {
-        // TODO: Implement market data subscription with sync API
-        // For now, just log the request
-        inf!("Market data subscription requested for {} (not yet implemented)", symbol);
-        Ok(())
+    /// Stream `symbol`'s quote and last trade into the market data kept for it, on a connection
+    /// of its own like the bar streams. Each change is also sent on the returned receiver, with
+    /// the relative volume measured when the stream starts (and whenever it is measured again).
+    /// The stream ends once the receiver is dropped, the symbol is unsubscribed or IB stops sending.
+    pub async fn subscribe_market_data(&self, symbol: &str) -> Result<mpsc::UnboundedReceiver<MarketData>, AppError> {
+        if self.market_data.read().await.contains_key(symbol) {
+            return Err(AppError::Validation(format!("Already subscribed to market data for {}", symbol)));
+        }
+        let (client, client_id) = self.connect_stream(symbol).await?;
+        self.market_data.write().await.insert(symbol.to_string(), MarketData::new(symbol));
+        if let Err(e) = self.refresh_relative_volume(symbol).await {
+            wrn!("Market data for {} without relative volume: {}", symbol, e);
+        }
+        
+        let contract = Contract::stock(symbol);
+        let symbol = symbol.to_string();
+        let market_data = self.market_data.clone();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            let subscription = match client.market_data(&contract, &[], false, false) {
+                Ok(subscription) => subscription,
+                Err(e) => {
+                    err!("Failed to stream market data for {}: {}", symbol, e);
+                    market_data.blocking_write().remove(&symbol);
+                    return;
+                }
+            };
+            inf!("Streaming market data for {} on client {}", symbol, client_id);
+            while !sender.is_closed() {
+                let tick = subscription.next_timeout(STREAM_POLL_INTERVAL);
+                if let Some(e) = subscription.error() {
+                    wrn!("Market data stream for {} ended: {}", symbol, e);
+                    // Otherwise the last quote would pass for a live one
+                    market_data.blocking_write().remove(&symbol);
+                    break;
+                }
+                
+                let mut streams = market_data.blocking_write();
+                let Some(data) = streams.get_mut(&symbol) else {
+                    break; // Unsubscribed
+                };
+                let Some(tick) = tick else {
+                    continue;
+                };
+                if !apply_tick(data, &tick) {
+                    continue;
+                }
+                data.timestamp = chrono::Utc::now();
+                let update = data.clone();
+                drop(streams);
+                if sender.send(update).is_err() {
+                    break;
+                }
+            }
+            subscription.cancel();
+            inf!("Stopped streaming market data for {}", symbol);
+        });
+        
+        Ok(receiver)
+    }
+    
+    /// Measure a subscribed symbol's relative volume again for its market data updates
+    pub async fn refresh_relative_volume(&self, symbol: &str) -> Result<(), AppError> {
+        self.get_relative_volume(symbol, MARKET_DATA_RVOL_SESSIONS).await.map(|_| ())
     }
     
     pub async fn unsubscribe_market_data(&self, symbol: &str) {
@@ -383,16 +3800,81 @@ impl IBClient {
         self.market_data.read().await.get(symbol).cloned()
     }
     
+    /// A connection of its own to the active account's TWS for a long-running stream, and its client id
+    async fn connect_stream(&self, symbol: &str) -> Result<(Client, i32), AppError> {
+        let connection = self.settings().await.connection;
+        let url = match *self.active_account.read().await {
+            Some(AccountType::Paper) => connection.paper_address,
+            Some(AccountType::Live) => connection.live_address,
+            None => return Err(AppError::IBConnection("No active account selected".to_string())),
+        };
+        let client_id = self.stream_client_ids.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let client = tokio::task::spawn_blocking(move || Client::connect(&url, client_id)).await
+            .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?
+            .map_err(|e| AppError::IBConnection(format!("Stream connection for {} failed: {}", symbol, e)))?;
+        Ok((client, client_id))
+    }
+    
+    /// Stream IB's 5-second real-time trade bars for `symbol` from the active account's TWS.
+    /// A subscription holds its connection for as long as it runs, so each stream connects on
+    /// its own client id rather than tying up the trading connection. The stream ends when
+    /// the receiver is dropped or IB stops sending.
+    pub async fn stream_realtime_bars(&self, symbol: &str, use_rth: bool) -> Result<mpsc::UnboundedReceiver<HistoricalBar>, AppError> {
+        let (client, client_id) = self.connect_stream(symbol).await?;
+        let contract = Contract::stock(symbol);
+        let symbol = symbol.to_string();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        
+        std::thread::spawn(move || {
+            let subscription = match client.realtime_bars(&contract, RealtimeBarSize::Sec5, RealtimeWhatToShow::Trades, use_rth) {
+                Ok(subscription) => subscription,
+                Err(e) => {
+                    err!("Failed to stream bars for {}: {}", symbol, e);
+                    return;
+                }
+            };
+            inf!("Streaming bars for {} on client {}", symbol, client_id);
+            while !sender.is_closed() {
+                let Some(bar) = subscription.next_timeout(STREAM_POLL_INTERVAL) else {
+                    if let Some(e) = subscription.error() {
+                        wrn!("Bar stream for {} ended: {}", symbol, e);
+                        break;
+                    }
+                    continue;
+                };
+                let bar = HistoricalBar {
+                    timestamp: chrono::DateTime::from_timestamp(bar.date.unix_timestamp(), bar.date.nanosecond())
+                        .unwrap_or_else(chrono::Utc::now),
+                    open: bar.open,
+                    high: bar.high,
+                    low: bar.low,
+                    close: bar.close,
+                    volume: bar.volume as i64,
+                    wap: bar.wap,
+                    count: bar.count as i64,
+                };
+                if sender.send(bar).is_err() {
+                    break;
+                }
+            }
+            subscription.cancel();
+            inf!("Stopped streaming bars for {}", symbol);
+        });
+        
+        Ok(receiver)
+    }
+    
     // Historical data
+    /// Bars over the last `duration_days` calendar days. With a database, bars already in its
+    /// bar cache are served from there and only the spans it lacks are fetched from IB; when IB
+    /// can't be reached, whatever is cached is served.
     pub async fn get_historical_data(
         &self, 
         symbol: &str, 
         duration_days: u32,
         bar_size: &str,  // e.g., "1 day", "1 hour"
+        use_rth: bool,   // false includes pre/post-market bars
     ) -> Result<HistoricalData, AppError> {
-        let client = self.get_active_client().await?;
-        let contract = Contract::stock(symbol);
-        
         inf!("Fetching historical data for {} - {} days of {} bars", symbol, duration_days, bar_size);
         
         // Convert bar size string to enum
@@ -400,54 +3882,113 @@ impl IBClient {
         let bar_size_enum = match bar_size {
             "1 day" => HistoricalBarSize::Day,
             "1 hour" => HistoricalBarSize::Hour,
+            "5 mins" => HistoricalBarSize::Min5,
             _ => {
-                return Err(AppError::Validation(format!("Unsupported bar size: {}. Currently only '1 day' and '1 hour' are supported.", bar_size)));
+                return Err(AppError::Validation(format!("Unsupported bar size: {}. Currently only '1 day', '1 hour' and '5 mins' are supported.", bar_size)));
             }
         };
         
-        let symbol_clone = symbol.to_string();
-        let bar_size_clone = bar_size.to_string();
-        let duration_str = format!("{} days", duration_days);
+        let mut historical_data = HistoricalData::new(
+            symbol.to_string(),
+            bar_size.to_string(),
+            format!("{} days", duration_days),
+        );
+        historical_data.bars = match &self.db {
+            Some(db) => self.cached_bars(db, symbol, duration_days, bar_size, bar_size_enum, use_rth).await?,
+            None => self.fetch_bars(symbol, None, duration_days, bar_size_enum, use_rth).await?,
+        };
+        
+        inf!("Received {} historical bars for {}", historical_data.bars.len(), symbol);
+        historical_data.sort_by_time();
+        if bar_size == "1 day" && use_rth {
+            self.daily_bars.write().await
+                .insert(symbol.to_string(), (chrono::Utc::now(), historical_data.bars.clone()));
+        }
+        Ok(historical_data)
+    }
+    
+    /// Bars over the last `duration_days` from the bar cache, after fetching the spans it lacks
+    async fn cached_bars(
+        &self,
+        db: &Arc<Mutex<Database>>,
+        symbol: &str,
+        duration_days: u32,
+        bar_size: &str,
+        bar_size_enum: HistoricalBarSize,
+        use_rth: bool,
+    ) -> Result<Vec<HistoricalBar>, AppError> {
+        let now = chrono::Utc::now();
+        let from = now - chrono::Duration::days(duration_days as i64);
+        let covered = db.lock().await.get_bar_coverage(symbol, bar_size, use_rth).await?
+            .and_then(|(from, to)| Some((chrono::DateTime::from_timestamp(from, 0)?, chrono::DateTime::from_timestamp(to, 0)?)));
+        
+        let requests = bar_cache::missing_requests(covered, from, now);
+        let mut fetched = Vec::new();
+        for request in &requests {
+            match self.fetch_bars(symbol, request.end, request.days, bar_size_enum, use_rth).await {
+                Ok(bars) => fetched.extend(bars),
+                Err(e) if covered.is_some() => {
+                    wrn!("Showing cached {} bars for {}: {}", bar_size, symbol, e);
+                    fetched.clear();
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        
+        let db = db.lock().await;
+        if !fetched.is_empty() {
+            let (covered_from, covered_to) = bar_cache::covered_after(covered, &requests, now);
+            let bars: Vec<DbBar> = fetched.iter().map(DbBar::from_bar).collect();
+            db.save_bars(symbol, bar_size, use_rth, &bars, (covered_from.timestamp(), covered_to.timestamp())).await?;
+        }
+        let bars = db.get_bars(symbol, bar_size, use_rth, from.timestamp()).await?;
+        Ok(bars.iter().filter_map(DbBar::to_bar).collect())
+    }
+    
+    /// `days` of bars from IB, ending at `end` or now
+    async fn fetch_bars(
+        &self,
+        symbol: &str,
+        end: Option<chrono::DateTime<chrono::Utc>>,
+        days: u32,
+        bar_size: HistoricalBarSize,
+        use_rth: bool,
+    ) -> Result<Vec<HistoricalBar>, AppError> {
+        let client = self.get_active_client().await?;
+        let contract = Contract::stock(symbol);
+        let end = end.and_then(|end| time::OffsetDateTime::from_unix_timestamp(end.timestamp()).ok());
         
         // Run in blocking task
-        let client_clone = client.clone();
-        let contract_clone = contract.clone();
         let result = tokio::task::spawn_blocking(move || {
             use ibapi::market_data::historical::Duration;
             
-            let client_guard = futures::executor::block_on(client_clone.lock());
-            let duration = Duration::days(duration_days as i32);
+            let client_guard = futures::executor::block_on(client.lock());
             
             // Request historical data
             client_guard.historical_data(
-                &contract_clone,
-                None, // end date time (None = now)
-                duration,
-                bar_size_enum,
+                &contract,
+                end, // None = now
+                Duration::days(days as i32),
+                bar_size,
                 HistoricalWhatToShow::Trades,
-                true, // use RTH (regular trading hours)
+                use_rth,
             )
         }).await
         .map_err(|e| AppError::IBConnection(format!("Task join error: {}", e)))?;
         
         match result {
             Ok(hist_data) => {
-                let mut historical_data = HistoricalData::new(
-                    symbol_clone,
-                    bar_size_clone,
-                    duration_str,
-                );
-                
                 // Convert IB bars to our HistoricalBar format
-                for bar in hist_data.bars {
+                let bars = hist_data.bars.iter().map(|bar| {
                     // bar.date is an OffsetDateTime from the time crate
                     // Convert it to chrono DateTime
                     let timestamp = chrono::DateTime::from_timestamp(
                         bar.date.unix_timestamp(),
                         bar.date.nanosecond(),
-                    ).unwrap_or_else(|| chrono::Utc::now());
+                    ).unwrap_or_else(chrono::Utc::now);
                     
-                    let hist_bar = HistoricalBar {
+                    HistoricalBar {
                         timestamp,
                         open: bar.open,
                         high: bar.high,
@@ -456,13 +3997,9 @@ impl IBClient {
                         volume: bar.volume as i64,
                         wap: bar.wap,
                         count: bar.count as i64,
-                    };
-                    historical_data.add_bar(hist_bar);
-                }
-                
-                inf!("Received {} historical bars for {}", historical_data.bars.len(), symbol);
-                historical_data.sort_by_time();
-                Ok(historical_data)
+                    }
+                }).collect();
+                Ok(bars)
             }
             Err(e) => {
                 err!("Failed to fetch historical data: {}", e);
@@ -477,37 +4014,55 @@ impl IBClient {
         symbol: &str,
         period_days: usize,
         method: OutlierMethod,
+        smoothing: AtrSmoothing,
+        force_refresh: bool,
     ) -> Result<ATRResult, AppError> {
+        if !force_refresh
+            && let Some(cached) = self.cached_atr(symbol, period_days, method, smoothing).await {
+            inf!("Using ATR for {} calculated {}", symbol, cached.calculation_date);
+            if cached.is_valid {
+                self.atr_results.write().await.insert(symbol.to_string(), cached.clone());
+            }
+            return Ok(cached);
+        }
+        
         // Fetch more days to ensure we have enough after filtering
-        let fetch_days = (period_days * 3).max(30).min(60) as u32;
+        let fetch_days = (period_days * 3).clamp(30, 60) as u32;
         
         inf!("Calculating filtered ATR for {} - {} days period", symbol, period_days);
         
         // Get historical data
-        let historical_data = self.get_historical_data(symbol, fetch_days, "1 day").await?;
+        let historical_data = self.get_historical_data(symbol, fetch_days, "1 day", true).await?;
         
         if historical_data.bars.is_empty() {
             return Err(AppError::Validation("No historical data available".to_string()));
         }
         
-        let mut result = ATRResult::new(symbol.to_string(), period_days, method);
+        let mut result = ATRResult::new(symbol.to_string(), period_days, method, smoothing);
         result.total_bars = historical_data.bars.len();
         
-        // Calculate ranges for all bars
-        let mut ranges: Vec<(usize, f64)> = historical_data.bars
-            .iter()
-            .enumerate()
-            .map(|(idx, bar)| (idx, bar.high - bar.low))
-            .collect();
+        // True Range for all bars, so overnight gaps count
+        let true_ranges = analytics::true_ranges(&historical_data.bars);
+        let ranges: Vec<(usize, f64)> = true_ranges.iter().copied().enumerate().collect();
+        
+        // Known earnings reactions are left out before the statistics, not left to the outlier filter
+        let earnings_days = self.earnings_reaction_days(symbol).await;
+        let is_earnings = |idx: usize| earnings_days.contains(&historical_data.bars[idx].timestamp.date_naive());
         
         // Sort ranges for percentile calculations
-        let mut sorted_ranges: Vec<f64> = ranges.iter().map(|(_, r)| *r).collect();
+        let mut sorted_ranges: Vec<f64> = ranges.iter()
+            .filter(|(idx, _)| !is_earnings(*idx))
+            .map(|(_, r)| *r)
+            .collect();
         sorted_ranges.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
         
         // Calculate statistics
         let n = sorted_ranges.len();
+        if n == 0 {
+            return Err(AppError::Validation(format!("No bars left for {} outside earnings days", symbol)));
+        }
         result.mean_range = sorted_ranges.iter().sum::<f64>() / n as f64;
-        result.median_range = if n % 2 == 0 {
+        result.median_range = if n.is_multiple_of(2) {
             (sorted_ranges[n/2 - 1] + sorted_ranges[n/2]) / 2.0
         } else {
             sorted_ranges[n/2]
@@ -524,8 +4079,16 @@ impl IBClient {
         result.q3_range = sorted_ranges[3 * n / 4];
         result.iqr = result.q3_range - result.q1_range;
         
+        // Auto settles on a concrete method from the shape of the ranges
+        if method == OutlierMethod::Auto {
+            let (applied, rationale) = analytics::select_outlier_method(&sorted_ranges);
+            inf!("Outlier method for {}: {:?} ({})", symbol, applied, rationale);
+            result.applied_method = applied;
+            result.method_rationale = Some(rationale);
+        }
+        
         // Determine outlier bounds based on method
-        let (lower_bound, upper_bound) = match method {
+        let (lower_bound, upper_bound) = match result.applied_method {
             OutlierMethod::IQR { multiplier } => {
                 let lb = result.q1_range - multiplier * result.iqr;
                 let ub = result.q3_range + multiplier * result.iqr;
@@ -541,6 +4104,7 @@ impl IBClient {
                 let high_idx = ((high / 100.0) * n as f64) as usize;
                 (sorted_ranges[low_idx], sorted_ranges[high_idx.min(n-1)])
             }
+            OutlierMethod::Auto => (0.0, f64::INFINITY), // Resolved above; never applied
         };
         
         result.lower_bound = lower_bound;
@@ -553,7 +4117,15 @@ impl IBClient {
         for (idx, range) in ranges.iter().rev().take(fetch_days as usize) {
             let bar = &historical_data.bars[*idx];
             
-            if *range < lower_bound || *range > upper_bound {
+            if is_earnings(*idx) {
+                excluded_bars.push(ExcludedBar {
+                    date: bar.timestamp,
+                    range: *range,
+                    reason: "Earnings reaction day".to_string(),
+                    high: bar.high,
+                    low: bar.low,
+                });
+            } else if *range < lower_bound || *range > upper_bound {
                 // This bar is an outlier
                 let reason = if *range < lower_bound {
                     format!("Range {:.2} below lower bound {:.2}", range, lower_bound)
@@ -599,26 +4171,20 @@ impl IBClient {
                 result.used_bars, period_days);
         }
         
-        // Calculate filtered ATR (simple average of ranges for now)
+        // Filtered ATR smooths every in-bounds range of the window, oldest first,
+        // so Wilder and EMA have history to settle on
         if result.used_bars > 0 {
-            let filtered_ranges: Vec<f64> = filtered_bars.iter()
-                .take(period_days)
-                .map(|bar| bar.high - bar.low)
+            let filtered_ranges: Vec<f64> = true_ranges.iter()
+                .copied()
+                .enumerate()
+                .filter(|(idx, range)| !is_earnings(*idx) && *range >= lower_bound && *range <= upper_bound)
+                .map(|(_, range)| range)
                 .collect();
-            
-            result.filtered_atr = filtered_ranges.iter().sum::<f64>() / filtered_ranges.len() as f64;
+            result.filtered_atr = analytics::smooth(&filtered_ranges, period_days, smoothing).unwrap_or(0.0);
         }
         
-        // Calculate regular ATR for comparison (using all bars)
-        let regular_ranges: Vec<f64> = historical_data.bars.iter()
-            .rev()
-            .take(period_days)
-            .map(|bar| bar.high - bar.low)
-            .collect();
-        
-        if !regular_ranges.is_empty() {
-            result.regular_atr = regular_ranges.iter().sum::<f64>() / regular_ranges.len() as f64;
-        }
+        // Regular ATR for comparison (using all bars)
+        result.regular_atr = analytics::smooth(&true_ranges, period_days, smoothing).unwrap_or(0.0);
         
         // Calculate differences
         if result.regular_atr > 0.0 {
@@ -632,6 +4198,413 @@ impl IBClient {
         inf!("ATR calculation complete. Filtered: {:.2}, Regular: {:.2}, Excluded {} bars", 
             result.filtered_atr, result.regular_atr, result.excluded_bars);
         
+        if result.is_valid {
+            self.atr_results.write().await.insert(symbol.to_string(), result.clone());
+        }
+        if let Some(db) = &self.db {
+            let db = db.lock().await;
+            if let Err(e) = db.save_atr_cache(DbAtrCache::from_result(&result)).await {
+                wrn!("Failed to cache ATR for {}: {}", symbol, e);
+            }
+            let recorded = match serde_json::to_string(&result) {
+                Ok(json) => db.record_atr_analysis(symbol, &result.calculation_date.to_rfc3339(), &json).await
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = recorded {
+                wrn!("Failed to record ATR analysis for {}: {}", symbol, e);
+            }
+        }
+        
         Ok(result)
     }
-}
\ No newline at end of file
+    
+    /// The ATR analysis in effect when the template was created: the latest one for its
+    /// symbol calculated up to then. None when no analysis preceded the template.
+    pub async fn get_template_atr_analysis(&self, template_id: &str) -> Result<Option<ATRResult>, AppError> {
+        let template = match self.get_template(template_id).await {
+            Some(template) => template,
+            None => self.database()?.lock().await.get_template(template_id).await?
+                .ok_or(AppError::NotFound(format!("Template {} not found", template_id)))?
+                .to_order_template(None)
+                .map_err(AppError::Serialization)?,
+        };
+        let json = self.database()?.lock().await
+            .get_atr_analysis_at(&template.symbol, &template.created_at.to_rfc3339())
+            .await?;
+        json.map(|json| serde_json::from_str(&json)
+            .map_err(|e| AppError::Serialization(format!("Failed to read ATR analysis: {}", e))))
+            .transpose()
+    }
+    
+    /// A stored ATR for the same settings still within `atr_cache_ttl`
+    async fn cached_atr(&self, symbol: &str, period_days: usize, method: OutlierMethod, smoothing: AtrSmoothing) -> Option<ATRResult> {
+        let ttl = self.settings().await.atr.cache_ttl;
+        if ttl == AtrCacheTtl::Off {
+            return None;
+        }
+        let db = self.db.as_ref()?.lock().await;
+        let entry = db.get_atr_cache(symbol, period_days, &method.key(), &format!("{:?}", smoothing)).await.ok()??;
+        let result = entry.to_result(method, smoothing)
+            .inspect_err(|e| wrn!("Ignoring cached ATR for {}: {}", symbol, e))
+            .ok()?;
+        ttl.is_fresh(result.calculation_date, chrono::Utc::now()).then_some(result)
+    }
+}
+
+/// Whether stop rules manage the template: it holds a position and has rules to apply
+fn manages_stop(template: &OrderTemplate) -> bool {
+    template.is_active() && template.has_fills() && !template.is_read_only && !template.stop_rules.is_empty()
+}
+
+/// An execution id without the part after the last period, which is all a correction changes
+fn execution_base_id(execution_id: &str) -> &str {
+    execution_id.rsplit_once('.').map_or(execution_id, |(base, _)| base)
+}
+
+/// Cumulative (quantity, average price) per order id.
+/// A corrected execution replaces the one it corrects: the ids differ only after the last period.
+fn fills_by_order(executions: &[orders::Execution]) -> HashMap<i32, (f64, f64)> {
+    let mut latest: HashMap<&str, &orders::Execution> = HashMap::new();
+    for execution in executions {
+        latest.insert(execution_base_id(&execution.execution_id), execution);
+    }
+    
+    let mut totals: HashMap<i32, (f64, f64)> = HashMap::new();
+    for execution in latest.values() {
+        let (quantity, notional) = totals.entry(execution.order_id).or_default();
+        *quantity += execution.shares;
+        *notional += execution.shares * execution.price;
+    }
+    totals.into_iter()
+        .filter(|(_, (quantity, _))| *quantity > 0.0)
+        .map(|(order_id, (quantity, notional))| (order_id, (quantity, notional / quantity)))
+        .collect()
+}
+
+/// The side of the quote an entry would trade into, else the last price; None without either
+fn reference_quote(side: OrderSide, data: &MarketData) -> Option<f64> {
+    let quote = match side {
+        OrderSide::Long => data.ask,
+        OrderSide::Short => data.bid,
+    };
+    [quote, data.last].into_iter().find(|&price| price > 0.0)
+}
+
+/// Fold a tick into `data`; whether it changed a quote, the last price or the volume.
+/// Delayed ticks stand in when the account has no live subscription for the symbol.
+fn apply_tick(data: &mut MarketData, tick: &TickTypes) -> bool {
+    let (tick_type, value) = match tick {
+        TickTypes::Price(price) => (&price.tick_type, price.price),
+        TickTypes::PriceSize(price) => (&price.price_tick_type, price.price),
+        TickTypes::Size(size) => (&size.tick_type, size.size),
+        _ => return false,
+    };
+    // IB sends -1 for a side with no quote
+    if value < 0.0 {
+        return false;
+    }
+    match tick_type {
+        TickType::Bid | TickType::DelayedBid => data.bid = value,
+        TickType::Ask | TickType::DelayedAsk => data.ask = value,
+        TickType::Last | TickType::DelayedLast => data.last = value,
+        TickType::Volume | TickType::DelayedVolume => data.volume = value as i64,
+        _ => return false,
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_size_from_risk() {
+        let mut template = OrderTemplate::new(
+            "Sized".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            0.0,
+            50.0,
+            48.0,
+            TimeInForce::Day,
+            TradingModel::Breakout,
+        );
+
+        // Without a database the template's own risk is used
+        IBClient::new().size_from_risk(&mut template).await.unwrap();
+        assert_eq!(template.quantity, 50.0);
+
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        db.lock().await.set_setting("risk_per_trade", "250").await.unwrap();
+
+        let client = IBClient::with_database(db);
+        client.size_from_risk(&mut template).await.unwrap();
+        assert_eq!(template.quantity, 125.0);
+        assert_eq!(template.risk_per_trade, 250.0);
+
+        // A symbol's own risk wins over the model's
+        client.save_symbol_settings(SymbolSettings {
+            symbol: "aapl".to_string(),
+            risk_per_trade: Some(100.0),
+            ..Default::default()
+        }).await.unwrap();
+        client.size_from_risk(&mut template).await.unwrap();
+        assert_eq!(template.quantity, 50.0);
+
+        template.stop_price = 49.999;
+        template.risk_per_trade = 0.0005;
+        assert!(IBClient::new().size_from_risk(&mut template).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backup_if_due() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.db");
+        let db = Database::open(&format!("sqlite:{}", path.display())).await.unwrap();
+        let client = IBClient::with_database(db);
+        let mut settings = client.settings().await;
+        settings.backup.keep = 2;
+        client.update_settings(settings).await.unwrap();
+
+        let start = chrono::Utc::now();
+        assert!(client.backup_if_due(start).await.unwrap().is_some());
+        assert!(client.backup_if_due(start + chrono::Duration::hours(1)).await.unwrap().is_none());
+        for day in 1..=3 {
+            assert!(client.backup_if_due(start + chrono::Duration::days(day)).await.unwrap().is_some());
+        }
+        assert_eq!(backup::list_backups(&path).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_match_color_scheme() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let client = IBClient::with_database(Database::open(&url).await.unwrap());
+        assert_eq!(client.color_scheme().await.unwrap(), ColorSchemePreference::System);
+        client.set_color_scheme(ColorSchemePreference::Light).await.unwrap();
+        assert_eq!(client.color_scheme().await.unwrap(), ColorSchemePreference::Light);
+
+        // The built-in themes swap for each other
+        assert!(client.match_color_scheme(true).await.unwrap().is_none());
+        assert!(client.match_color_scheme(false).await.unwrap().is_some());
+        assert_eq!(client.selected_chart_theme().await.unwrap().0, "light");
+
+        // A custom theme stays whatever the scheme
+        client.save_chart_theme("amber", &ChartTheme::default()).await.unwrap();
+        client.select_chart_theme("amber").await.unwrap();
+        assert!(client.match_color_scheme(true).await.unwrap().is_none());
+        assert_eq!(client.selected_chart_theme().await.unwrap().0, "amber");
+    }
+
+    #[test]
+    fn test_exit_orders_share_the_stop() {
+        let mut template = OrderTemplate::new(
+            "Bracket".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            148.0,
+            TimeInForce::GTC,
+            TradingModel::Breakout,
+        );
+        template.technical_stop_price = Some(147.0);
+        template.exit_tranches = vec![ExitTranche::target(0.75, 1.0), ExitTranche::trailing(0.25, 1.5)];
+
+        // Targets are measured from the stop that is actually working
+        let stop = IBClient::stop_order(&template, 1, template.remaining_stop_quantity());
+        assert_eq!((stop.aux_price, stop.total_quantity), (Some(147.0), 75.0));
+        let target = IBClient::tranche_order(&template, 0, &template.exit_tranches[0], 1, 75.0);
+        assert_eq!(target.limit_price, Some(153.0));
+        let runner = IBClient::tranche_order(&template, 1, &template.exit_tranches[1], 1, 25.0);
+        assert_eq!(runner.trail_stop_price, stop.aux_price);
+    }
+
+    #[test]
+    fn test_fills_by_order() {
+        let execution = |order_id: i32, execution_id: &str, shares: f64, price: f64| orders::Execution {
+            order_id,
+            execution_id: execution_id.to_string(),
+            shares,
+            price,
+            ..Default::default()
+        };
+        let executions = vec![
+            execution(1000, "0001f4e8.6543.01", 30.0, 10.0),
+            execution(1000, "0001f4e8.6544.01", 10.0, 11.0),
+            // Correction of the first execution
+            execution(1000, "0001f4e8.6543.02", 30.0, 10.5),
+            execution(1002, "0001f4e8.6545.01", 5.0, 12.0),
+        ];
+
+        let fills = fills_by_order(&executions);
+        let (quantity, avg_price) = fills[&1000];
+        assert_eq!(quantity, 40.0);
+        assert!((avg_price - 10.625).abs() < 1e-9);
+        assert_eq!(fills[&1002], (5.0, 12.0));
+        assert!(!fills.contains_key(&1001));
+    }
+
+    #[tokio::test]
+    async fn test_order_ids_survive_restart() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+
+        let first_session = IBClient::with_database(db.clone());
+        let first = first_session.reserve_order_ids(3).await.unwrap();
+        assert_eq!(first_session.reserve_order_ids(2).await.unwrap(), first + 3);
+
+        // A fresh client starts from the persisted mark, not its default
+        let second_session = IBClient::with_database(db.clone());
+        assert_eq!(second_session.reserve_order_ids(2).await.unwrap(), first + 5);
+
+        second_session.advance_order_ids(9000).await;
+        assert_eq!(second_session.reserve_order_ids(2).await.unwrap(), 9000);
+        second_session.advance_order_ids(10).await;
+        assert_eq!(second_session.reserve_order_ids(2).await.unwrap(), 9002);
+    }
+
+    #[tokio::test]
+    async fn test_archive_and_restore() {
+        let template = OrderTemplate::new(
+            "Old setup".to_string(),
+            "MSFT".to_string(),
+            OrderSide::Long,
+            10.0,
+            400.0,
+            395.0,
+            TimeInForce::Day,
+            TradingModel::Bounce,
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        db.lock().await.create_template(DbOrderTemplate::from_order_template(&template)).await.unwrap();
+
+        let client = IBClient::with_database(db.clone());
+        client.load_templates().await.unwrap();
+        client.archive_template(&template.id).await.unwrap();
+        assert!(client.get_all_templates().await.is_empty());
+
+        // Archived templates survive a reload but stay out of the working set
+        client.load_templates().await.unwrap();
+        assert!(client.get_all_templates().await.is_empty());
+        let archived = client.get_archived_templates().await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert!(archived[0].is_archived());
+        assert!(archived[0].archived_at.is_some());
+        assert!(client.archive_template(&template.id).await.is_err());
+
+        client.restore_template(&template.id).await.unwrap();
+        let restored = client.get_template(&template.id).await.unwrap();
+        assert_eq!(restored.status, OrderTemplateStatus::Inactive);
+        assert_eq!(restored.archived_at, None);
+        assert!(client.get_archived_templates().await.unwrap().is_empty());
+        assert!(client.restore_template(&template.id).await.is_err());
+
+        // Both actions are on the audit trail; failed attempts are not
+        let trail = client.get_audit_trail(&template.id).await.unwrap();
+        let actions: Vec<AuditAction> = trail.iter().map(|e| e.action).collect();
+        assert_eq!(actions, vec![AuditAction::Archive, AuditAction::Restore]);
+        assert!(trail.iter().all(|e| e.source == AuditSource::UI && e.account_type.is_none()));
+        let archive = &trail[0];
+        assert_eq!(archive.before.as_ref().unwrap().status, OrderTemplateStatus::Inactive);
+        assert_eq!(archive.after.as_ref().unwrap().status, OrderTemplateStatus::Archived);
+    }
+
+    #[test]
+    fn test_apply_tick() {
+        use ibapi::market_data::realtime::{TickPrice, TickSize};
+        let price = |tick_type, price| TickTypes::Price(TickPrice { tick_type, price, ..Default::default() });
+
+        let mut data = MarketData::new("AAPL");
+        assert!(apply_tick(&mut data, &price(TickType::Bid, 149.9)));
+        assert!(apply_tick(&mut data, &price(TickType::DelayedAsk, 150.1)));
+        assert!(apply_tick(&mut data, &price(TickType::Last, 150.0)));
+        assert!(apply_tick(&mut data, &TickTypes::Size(TickSize { tick_type: TickType::Volume, size: 12000.0 })));
+        assert_eq!((data.bid, data.ask, data.last, data.volume), (149.9, 150.1, 150.0, 12000));
+
+        // No quote on a side, and ticks the stream doesn't keep, leave it as it was
+        assert!(!apply_tick(&mut data, &price(TickType::Bid, -1.0)));
+        assert!(!apply_tick(&mut data, &price(TickType::High, 151.0)));
+        assert!(!apply_tick(&mut data, &TickTypes::SnapshotEnd));
+        assert_eq!(data.bid, 149.9);
+    }
+
+    #[test]
+    fn test_reference_quote() {
+        let data = MarketData { bid: 99.9, ask: 100.1, last: 100.0, ..MarketData::new("AAPL") };
+        assert_eq!(reference_quote(OrderSide::Long, &data), Some(100.1));
+        assert_eq!(reference_quote(OrderSide::Short, &data), Some(99.9));
+
+        // One-sided or empty quotes fall back to the last trade
+        let no_ask = MarketData { ask: 0.0, ..data.clone() };
+        assert_eq!(reference_quote(OrderSide::Long, &no_ask), Some(100.0));
+        assert_eq!(reference_quote(OrderSide::Short, &no_ask), Some(99.9));
+        assert_eq!(reference_quote(OrderSide::Long, &MarketData::new("AAPL")), None);
+    }
+
+    #[tokio::test]
+    async fn test_stop_fill_closes_template() {
+        let mut template = OrderTemplate::new(
+            "Stopped out".to_string(),
+            "NVDA".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            147.0,
+            TimeInForce::GTC,
+            TradingModel::Breakout,
+        );
+        template.status = OrderTemplateStatus::Active;
+        template.parent_order_id = Some(11);
+        template.stop_order_id = Some(12);
+
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+        let db = Database::open(&url).await.unwrap();
+        db.lock().await.create_template(DbOrderTemplate::from_order_template(&template)).await.unwrap();
+        let client = IBClient::with_database(db.clone());
+        client.load_templates().await.unwrap();
+        client.order_templates.write().await.insert(template.id.clone(), template.clone());
+        assert!(client.archive_template(&template.id).await.is_err());
+
+        let now = chrono::Utc::now();
+        let mut fills = HashMap::from([(11, (100.0, 150.0))]);
+        assert_eq!(client.apply_fills(&fills, now).await.unwrap(), vec![template.id.clone()]);
+        let filled = client.get_template(&template.id).await.unwrap();
+        assert_eq!(filled.status, OrderTemplateStatus::Filled);
+        assert!(filled.has_fills());
+        assert!(client.archive_template(&template.id).await.is_err());
+
+        // The stop takes the whole position out
+        fills.insert(12, (100.0, 147.0));
+        client.apply_fills(&fills, now).await.unwrap();
+        let closed = client.get_template(&template.id).await.unwrap();
+        assert_eq!(closed.status, OrderTemplateStatus::Closed);
+        assert!(!closed.is_active() && !closed.has_fills());
+        assert_eq!((closed.parent_order_id, closed.stop_order_id), (None, None));
+        assert!(db.lock().await.has_trade_outcome(&template.id).await.unwrap());
+
+        // Closed templates no longer count as open positions
+        let limits = PositionLimits { max_open_positions: 1, max_pending_templates: 0 };
+        let mut next = template.duplicate(Some("Next".to_string()));
+        next.symbol = "AMD".to_string();
+        assert!(limits.check(&next, &client.get_all_templates().await).is_ok());
+
+        // A closed trade survives a reload and can be archived
+        client.load_templates().await.unwrap();
+        assert!(client.get_template(&template.id).await.unwrap().is_closed());
+        client.archive_template(&template.id).await.unwrap();
+        assert!(client.get_all_templates().await.is_empty());
+        let actions: Vec<AuditAction> = client.get_audit_trail(&template.id).await.unwrap()
+            .iter().map(|e| e.action).collect();
+        assert_eq!(actions, vec![AuditAction::Fill, AuditAction::Close, AuditAction::Archive]);
+    }
+}
@@ -0,0 +1,126 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::market_hours;
+
+/// When a company reports relative to the regular session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EarningsTiming {
+    BeforeOpen,
+    AfterClose,
+    Unknown,
+}
+
+impl EarningsTiming {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EarningsTiming::BeforeOpen => "before_open",
+            EarningsTiming::AfterClose => "after_close",
+            EarningsTiming::Unknown => "unknown",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "before_open" => EarningsTiming::BeforeOpen,
+            "after_close" => EarningsTiming::AfterClose,
+            _ => EarningsTiming::Unknown,
+        }
+    }
+}
+
+/// A scheduled or past earnings report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EarningsEvent {
+    pub symbol: String,
+    pub date: NaiveDate,
+    pub timing: EarningsTiming,
+}
+
+impl EarningsEvent {
+    /// Sessions whose bars carry the report's gap: the report date before the open,
+    /// the next trading day after the close, and both when the timing isn't known
+    pub fn reaction_dates(&self) -> Vec<NaiveDate> {
+        let next = market_hours::add_trading_days(self.date, 1);
+        match self.timing {
+            EarningsTiming::BeforeOpen => vec![self.date],
+            EarningsTiming::AfterClose => vec![next],
+            EarningsTiming::Unknown => vec![self.date, next],
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let timing = match self.timing {
+            EarningsTiming::BeforeOpen => " before the open",
+            EarningsTiming::AfterClose => " after the close",
+            EarningsTiming::Unknown => "",
+        };
+        format!("{} reports earnings {}{}", self.symbol, self.date.format("%Y-%m-%d"), timing)
+    }
+}
+
+/// What activation does when earnings fall within the expected hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EarningsPolicy {
+    Off,
+    Warn,
+    Block,
+}
+
+impl EarningsPolicy {
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "off" => EarningsPolicy::Off,
+            "block" => EarningsPolicy::Block,
+            _ => EarningsPolicy::Warn,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EarningsPolicy::Off => "off",
+            EarningsPolicy::Warn => "warn",
+            EarningsPolicy::Block => "block",
+        }
+    }
+}
+
+/// The first event whose reaction lands between `from` and `hold_days` trading days later
+pub fn within_hold(events: &[EarningsEvent], from: NaiveDate, hold_days: u32) -> Option<&EarningsEvent> {
+    let until = market_hours::add_trading_days(from, hold_days);
+    events.iter()
+        .filter(|event| event.reaction_dates().iter().any(|date| *date >= from && *date <= until))
+        .min_by_key(|event| event.date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(month: u32, day: u32, timing: EarningsTiming) -> EarningsEvent {
+        EarningsEvent {
+            symbol: "NVDA".to_string(),
+            date: NaiveDate::from_ymd_opt(2025, month, day).unwrap(),
+            timing,
+        }
+    }
+
+    #[test]
+    fn test_earnings_within_hold() {
+        // Friday after the close reacts on Monday
+        let friday = event(8, 22, EarningsTiming::AfterClose);
+        assert_eq!(friday.reaction_dates(), [NaiveDate::from_ymd_opt(2025, 8, 25).unwrap()]);
+        assert_eq!(event(8, 22, EarningsTiming::Unknown).reaction_dates().len(), 2);
+
+        let events = vec![event(11, 19, EarningsTiming::AfterClose), friday.clone()];
+        let monday = NaiveDate::from_ymd_opt(2025, 8, 18).unwrap();
+        assert_eq!(within_hold(&events, monday, 5), Some(&friday));
+        assert_eq!(within_hold(&events, monday, 4), None);
+        // Entering on the reaction day itself still counts
+        assert_eq!(within_hold(&events, NaiveDate::from_ymd_opt(2025, 8, 25).unwrap(), 0), Some(&friday));
+
+        assert_eq!(EarningsTiming::parse(EarningsTiming::AfterClose.as_str()), EarningsTiming::AfterClose);
+        assert_eq!(EarningsPolicy::parse("block"), EarningsPolicy::Block);
+        assert_eq!(EarningsPolicy::parse(""), EarningsPolicy::Warn);
+    }
+}
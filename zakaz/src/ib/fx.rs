@@ -0,0 +1,95 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Currency of US stocks, and of templates saved before currencies were tracked
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+/// Cached rates are re-fetched from IB after this long
+pub const FX_RATE_TTL_MINUTES: i64 = 15;
+
+/// Exchange rates into the account base currency, cached as IB returns them
+#[derive(Debug, Clone, PartialEq)]
+pub struct FxRates {
+    pub base: String,
+    rates: HashMap<String, (f64, DateTime<Utc>)>, // Base units per unit of the currency, and when fetched
+}
+
+impl Default for FxRates {
+    fn default() -> Self {
+        Self::new(DEFAULT_CURRENCY)
+    }
+}
+
+impl FxRates {
+    pub fn new(base: &str) -> Self {
+        Self {
+            base: base.to_uppercase(),
+            rates: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, currency: &str, rate: f64, now: DateTime<Utc>) {
+        self.rates.insert(currency.to_uppercase(), (rate, now));
+    }
+
+    /// Base units per unit of `currency`. A stale rate is still returned: it beats none.
+    pub fn rate(&self, currency: &str) -> Option<f64> {
+        if currency.eq_ignore_ascii_case(&self.base) {
+            return Some(1.0);
+        }
+        self.rates.get(&currency.to_uppercase()).map(|(rate, _)| *rate)
+    }
+
+    pub fn convert(&self, amount: f64, currency: &str) -> Option<f64> {
+        self.rate(currency).map(|rate| amount * rate)
+    }
+
+    /// `amount` in the base currency, or unconverted when no rate has been fetched
+    pub fn convert_or_keep(&self, amount: f64, currency: &str) -> f64 {
+        self.convert(amount, currency).unwrap_or(amount)
+    }
+
+    /// Which of `currencies` have no rate, or one older than the TTL
+    pub fn needs_refresh<'a>(&self, currencies: impl IntoIterator<Item = &'a str>, now: DateTime<Utc>) -> BTreeSet<String> {
+        currencies.into_iter()
+            .filter(|c| !c.eq_ignore_ascii_case(&self.base))
+            .map(str::to_uppercase)
+            .filter(|c| self.rates.get(c).is_none_or(|(_, at)| now - *at >= Duration::minutes(FX_RATE_TTL_MINUTES)))
+            .collect()
+    }
+
+    /// Which of `currencies` can't be converted at all
+    pub fn missing<'a>(&self, currencies: impl IntoIterator<Item = &'a str>) -> BTreeSet<String> {
+        currencies.into_iter()
+            .filter(|c| self.rate(c).is_none())
+            .map(str::to_uppercase)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fx_rates() {
+        let now = Utc::now();
+        let mut fx = FxRates::new("usd");
+        assert_eq!(fx.rate("USD"), Some(1.0));
+        assert_eq!(fx.rate("EUR"), None);
+        assert_eq!(fx.convert_or_keep(100.0, "EUR"), 100.0);
+        assert_eq!(fx.needs_refresh(["USD", "eur", "EUR"], now), BTreeSet::from(["EUR".to_string()]));
+
+        fx.set("eur", 1.25, now);
+        assert_eq!(fx.convert(200.0, "EUR"), Some(250.0));
+        assert!(fx.needs_refresh(["EUR"], now + Duration::minutes(1)).is_empty());
+        assert!(fx.missing(["EUR", "USD"]).is_empty());
+
+        // Expired rates are refreshed but still used meanwhile
+        let later = now + Duration::minutes(FX_RATE_TTL_MINUTES);
+        assert_eq!(fx.needs_refresh(["EUR"], later).len(), 1);
+        assert_eq!(fx.rate("EUR"), Some(1.25));
+        assert_eq!(fx.missing(["JPY"]), BTreeSet::from(["JPY".to_string()]));
+    }
+}
@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::types::HistoricalBar;
+
+/// An open away from the previous close
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Gap {
+    pub date: DateTime<Utc>,
+    pub gap_pct: f64,  // Signed, vs the previous close
+    pub filled: bool,  // Traded back to the previous close the same session
+}
+
+/// How often and how far a symbol opens away from the previous close, and how often it fills
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GapStats {
+    pub symbol: String,
+    pub sessions: usize,       // Opens measured, one per bar after the first
+    pub min_gap_pct: f64,      // Smaller moves don't count as gaps
+    pub gaps: Vec<Gap>,        // Oldest first
+    pub gap_frequency: f64,    // Gaps per session, 0–1
+    pub up_gaps: usize,
+    pub down_gaps: usize,
+    pub average_gap_pct: f64,  // Mean size, either direction
+    pub largest_gap_pct: f64,  // Signed
+    pub fill_probability: f64, // Share of gaps filled the same session
+    pub up_fill_probability: Option<f64>,   // None without an up gap
+    pub down_fill_probability: Option<f64>,
+}
+
+impl GapStats {
+    /// Needs at least two daily bars (oldest first)
+    pub fn from_bars(symbol: &str, bars: &[HistoricalBar], min_gap_pct: f64) -> Option<Self> {
+        if bars.len() < 2 {
+            return None;
+        }
+        let gaps: Vec<Gap> = bars.windows(2)
+            .filter(|pair| pair[0].close > 0.0)
+            .filter_map(|pair| {
+                let (previous, bar) = (&pair[0], &pair[1]);
+                let gap_pct = (bar.open - previous.close) / previous.close * 100.0;
+                if gap_pct.abs() < min_gap_pct.max(f64::EPSILON) {
+                    return None;
+                }
+                let filled = if gap_pct > 0.0 { bar.low <= previous.close } else { bar.high >= previous.close };
+                Some(Gap { date: bar.timestamp, gap_pct, filled })
+            })
+            .collect();
+
+        let fill_rate = |gaps: Vec<&Gap>| {
+            (!gaps.is_empty()).then(|| gaps.iter().filter(|g| g.filled).count() as f64 / gaps.len() as f64)
+        };
+        let up: Vec<&Gap> = gaps.iter().filter(|g| g.gap_pct > 0.0).collect();
+        let down: Vec<&Gap> = gaps.iter().filter(|g| g.gap_pct < 0.0).collect();
+        let sessions = bars.len() - 1;
+
+        Some(Self {
+            symbol: symbol.to_string(),
+            sessions,
+            min_gap_pct,
+            gap_frequency: gaps.len() as f64 / sessions as f64,
+            up_gaps: up.len(),
+            down_gaps: down.len(),
+            average_gap_pct: if gaps.is_empty() { 0.0 } else { gaps.iter().map(|g| g.gap_pct.abs()).sum::<f64>() / gaps.len() as f64 },
+            largest_gap_pct: gaps.iter().map(|g| g.gap_pct).max_by(|a, b| a.abs().total_cmp(&b.abs())).unwrap_or(0.0),
+            fill_probability: fill_rate(gaps.iter().collect()).unwrap_or(0.0),
+            up_fill_probability: fill_rate(up),
+            down_fill_probability: fill_rate(down),
+            gaps,
+        })
+    }
+
+    /// Share of sessions that opened at least `pct` away, e.g. a stop's distance from entry:
+    /// how often an overnight hold would have gapped through it
+    pub fn frequency_beyond(&self, pct: f64) -> f64 {
+        if self.sessions == 0 {
+            return 0.0;
+        }
+        self.gaps.iter().filter(|g| g.gap_pct.abs() >= pct).count() as f64 / self.sessions as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn bar(day: i64, open: f64, high: f64, low: f64, close: f64) -> HistoricalBar {
+        HistoricalBar {
+            timestamp: Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap() + Duration::days(day),
+            open,
+            high,
+            low,
+            close,
+            volume: 1000,
+            wap: close,
+            count: 0,
+        }
+    }
+
+    #[test]
+    fn test_gap_stats() {
+        let bars = vec![
+            bar(0, 100.0, 101.0, 99.0, 100.0),
+            bar(1, 102.0, 103.0, 99.5, 101.0), // Up 2%, filled
+            bar(2, 101.1, 102.0, 100.5, 101.5), // 0.1%, too small
+            bar(3, 98.455, 99.0, 97.0, 98.0),   // Down 3%, not filled
+            bar(4, 99.0, 100.0, 98.0, 99.5),    // Up ~1.02%, filled
+        ];
+        assert!(GapStats::from_bars("XYZ", &bars[..1], 0.5).is_none());
+
+        let stats = GapStats::from_bars("XYZ", &bars, 0.5).unwrap();
+        assert_eq!(stats.sessions, 4);
+        assert_eq!(stats.gaps.len(), 3);
+        assert_eq!(stats.gap_frequency, 0.75);
+        assert_eq!((stats.up_gaps, stats.down_gaps), (2, 1));
+        assert!((stats.largest_gap_pct + 3.0).abs() < 1e-9);
+        assert!((stats.fill_probability - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!((stats.up_fill_probability, stats.down_fill_probability), (Some(1.0), Some(0.0)));
+        assert_eq!(stats.frequency_beyond(1.5), 0.5);
+
+        let none = GapStats::from_bars("XYZ", &bars, 5.0).unwrap();
+        assert!(none.gaps.is_empty());
+        assert_eq!((none.fill_probability, none.up_fill_probability), (0.0, None));
+    }
+}
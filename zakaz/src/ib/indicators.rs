@@ -0,0 +1,429 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::market_hours;
+use super::types::{AtrSmoothing, HistoricalBar};
+
+/// A rolling calculation fed one bar at a time, oldest first
+pub trait Indicator {
+    type Output: Copy;
+
+    fn update(&mut self, bar: &HistoricalBar);
+
+    /// None until enough bars have been seen
+    fn value(&self) -> Option<Self::Output>;
+
+    /// Forget every bar seen
+    fn reset(&mut self);
+
+    /// Feed `bars` and collect the value after each one, aligned with the bars
+    fn series(&mut self, bars: &[HistoricalBar]) -> IndicatorSeries<Self::Output>
+    where
+        Self: Sized,
+    {
+        let points = bars.iter()
+            .map(|bar| {
+                self.update(bar);
+                (bar.timestamp, self.value())
+            })
+            .collect();
+        IndicatorSeries { points }
+    }
+}
+
+/// Indicator output per bar, for chart overlays and rules
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndicatorSeries<T> {
+    pub points: Vec<(DateTime<Utc>, Option<T>)>, // None while warming up
+}
+
+impl<T: Copy> IndicatorSeries<T> {
+    pub fn last(&self) -> Option<T> {
+        self.points.last().and_then(|(_, value)| *value)
+    }
+
+    /// Only the bars with a value
+    pub fn values(&self) -> impl Iterator<Item = (DateTime<Utc>, T)> + '_ {
+        self.points.iter().filter_map(|(at, value)| value.map(|v| (*at, v)))
+    }
+}
+
+/// Simple moving average of closes
+#[derive(Debug, Clone)]
+pub struct Sma {
+    period: usize,
+    window: VecDeque<f64>,
+    sum: f64,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        Self { period: period.max(1), window: VecDeque::new(), sum: 0.0 }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        self.window.push_back(value);
+        self.sum += value;
+        if self.window.len() > self.period {
+            self.sum -= self.window.pop_front().unwrap_or(0.0);
+        }
+    }
+
+    pub fn current(&self) -> Option<f64> {
+        (self.window.len() == self.period).then(|| self.sum / self.period as f64)
+    }
+}
+
+impl Indicator for Sma {
+    type Output = f64;
+
+    fn update(&mut self, bar: &HistoricalBar) {
+        self.push(bar.close);
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.current()
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period);
+    }
+}
+
+/// Exponential average of closes, seeded with the simple mean of the first `period`
+/// values as `analytics::smooth` is
+#[derive(Debug, Clone)]
+pub struct Ema {
+    period: usize,
+    alpha: f64,
+    seed: Sma,
+    value: Option<f64>,
+}
+
+impl Ema {
+    /// Weight 2 / (period + 1)
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self::with_alpha(period, 2.0 / (period as f64 + 1.0))
+    }
+
+    /// Wilder's smoothing, weight 1 / period
+    pub fn wilder(period: usize) -> Self {
+        let period = period.max(1);
+        Self::with_alpha(period, 1.0 / period as f64)
+    }
+
+    fn with_alpha(period: usize, alpha: f64) -> Self {
+        Self { period, alpha, seed: Sma::new(period), value: None }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        match self.value {
+            Some(average) => self.value = Some(average + self.alpha * (value - average)),
+            None => {
+                self.seed.push(value);
+                self.value = self.seed.current();
+            }
+        }
+    }
+
+    pub fn current(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+impl Indicator for Ema {
+    type Output = f64;
+
+    fn update(&mut self, bar: &HistoricalBar) {
+        self.push(bar.close);
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.current()
+    }
+
+    fn reset(&mut self) {
+        *self = Self::with_alpha(self.period, self.alpha);
+    }
+}
+
+/// Wilder's relative strength index of closes, 0–100
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    period: usize,
+    previous_close: Option<f64>,
+    gains: Ema,
+    losses: Ema,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Self { period, previous_close: None, gains: Ema::wilder(period), losses: Ema::wilder(period) }
+    }
+}
+
+impl Indicator for Rsi {
+    type Output = f64;
+
+    fn update(&mut self, bar: &HistoricalBar) {
+        if let Some(previous) = self.previous_close {
+            let change = bar.close - previous;
+            self.gains.push(change.max(0.0));
+            self.losses.push((-change).max(0.0));
+        }
+        self.previous_close = Some(bar.close);
+    }
+
+    fn value(&self) -> Option<f64> {
+        let (gain, loss) = (self.gains.current()?, self.losses.current()?);
+        Some(if loss == 0.0 {
+            if gain == 0.0 { 50.0 } else { 100.0 }
+        } else {
+            100.0 - 100.0 / (1.0 + gain / loss)
+        })
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MacdValue {
+    pub macd: f64,      // Fast EMA − slow EMA
+    pub signal: f64,    // EMA of the MACD line
+    pub histogram: f64, // MACD − signal
+}
+
+/// Moving average convergence/divergence of closes, 12/26/9 by default
+#[derive(Debug, Clone)]
+pub struct Macd {
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+    periods: (usize, usize, usize),
+    value: Option<MacdValue>,
+}
+
+impl Macd {
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Self {
+        Self {
+            fast: Ema::new(fast),
+            slow: Ema::new(slow),
+            signal: Ema::new(signal),
+            periods: (fast, slow, signal),
+            value: None,
+        }
+    }
+
+    /// Fast, slow and signal periods
+    pub fn periods(&self) -> (usize, usize, usize) {
+        self.periods
+    }
+}
+
+impl Default for Macd {
+    fn default() -> Self {
+        Self::new(12, 26, 9)
+    }
+}
+
+impl Indicator for Macd {
+    type Output = MacdValue;
+
+    fn update(&mut self, bar: &HistoricalBar) {
+        self.fast.push(bar.close);
+        self.slow.push(bar.close);
+        let (Some(fast), Some(slow)) = (self.fast.current(), self.slow.current()) else {
+            return;
+        };
+        let macd = fast - slow;
+        self.signal.push(macd);
+        self.value = self.signal.current().map(|signal| MacdValue { macd, signal, histogram: macd - signal });
+    }
+
+    fn value(&self) -> Option<MacdValue> {
+        self.value
+    }
+
+    fn reset(&mut self) {
+        let (fast, slow, signal) = self.periods;
+        *self = Self::new(fast, slow, signal);
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Averager {
+    Simple(Sma),
+    Exponential(Ema),
+}
+
+/// Average True Range; the first bar's range stands in for its True Range
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period: usize,
+    smoothing: AtrSmoothing,
+    previous_close: Option<f64>,
+    average: Averager,
+}
+
+impl Atr {
+    pub fn new(period: usize, smoothing: AtrSmoothing) -> Self {
+        let average = match smoothing {
+            AtrSmoothing::Simple => Averager::Simple(Sma::new(period)),
+            AtrSmoothing::Wilder => Averager::Exponential(Ema::wilder(period)),
+            AtrSmoothing::Exponential => Averager::Exponential(Ema::new(period)),
+        };
+        Self { period, smoothing, previous_close: None, average }
+    }
+}
+
+impl Indicator for Atr {
+    type Output = f64;
+
+    fn update(&mut self, bar: &HistoricalBar) {
+        let range = bar.high - bar.low;
+        let true_range = match self.previous_close {
+            Some(close) => range.max((bar.high - close).abs()).max((bar.low - close).abs()),
+            None => range,
+        };
+        self.previous_close = Some(bar.close);
+        match &mut self.average {
+            Averager::Simple(sma) => sma.push(true_range),
+            Averager::Exponential(ema) => ema.push(true_range),
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        match &self.average {
+            Averager::Simple(sma) => sma.current(),
+            Averager::Exponential(ema) => ema.current(),
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period, self.smoothing);
+    }
+}
+
+/// Volume-weighted average of typical price, restarting each session
+#[derive(Debug, Clone, Default)]
+pub struct Vwap {
+    session: Option<NaiveDate>,
+    price_volume: f64,
+    volume: f64,
+}
+
+impl Vwap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Indicator for Vwap {
+    type Output = f64;
+
+    fn update(&mut self, bar: &HistoricalBar) {
+        let session = market_hours::session_date(bar.timestamp);
+        if self.session != Some(session) {
+            *self = Self { session: Some(session), ..Self::default() };
+        }
+        let typical = (bar.high + bar.low + bar.close) / 3.0;
+        self.price_volume += typical * bar.volume as f64;
+        self.volume += bar.volume as f64;
+    }
+
+    fn value(&self) -> Option<f64> {
+        (self.volume > 0.0).then(|| self.price_volume / self.volume)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ib::analytics;
+    use chrono::{Duration, TimeZone};
+
+    fn bar(minutes: i64, high: f64, low: f64, close: f64, volume: i64) -> HistoricalBar {
+        HistoricalBar {
+            // 10:00 New York
+            timestamp: Utc.with_ymd_and_hms(2025, 7, 14, 14, 0, 0).unwrap() + Duration::minutes(minutes),
+            open: close,
+            high,
+            low,
+            close,
+            volume,
+            wap: close,
+            count: 0,
+        }
+    }
+
+    fn closes(values: &[f64]) -> Vec<HistoricalBar> {
+        values.iter().enumerate().map(|(i, c)| bar(i as i64, c + 1.0, c - 1.0, *c, 100)).collect()
+    }
+
+    #[test]
+    fn test_moving_averages() {
+        let bars = closes(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        let sma = Sma::new(3).series(&bars);
+        assert_eq!(sma.points.iter().map(|(_, v)| *v).collect::<Vec<_>>(), [None, None, Some(2.0), Some(3.0), Some(4.0)]);
+        assert_eq!(sma.values().count(), 3);
+
+        // Seeded at 2.0, then halfway to each close
+        let mut ema = Ema::new(3);
+        assert_eq!(ema.series(&bars).last(), Some(3.0 + 0.5 * (5.0 - 3.0)));
+        ema.reset();
+        assert_eq!(ema.value(), None);
+
+        // Rising closes: no losses
+        let mut rsi = Rsi::new(3);
+        rsi.series(&bars[..3]);
+        assert_eq!(rsi.value(), None);
+        rsi.update(&bars[3]);
+        assert_eq!(rsi.value(), Some(100.0));
+        rsi.update(&closes(&[3.0])[0]);
+        // Average gain 2/3, average loss 1/3: RS of 2
+        assert!((rsi.value().unwrap() - (100.0 - 100.0 / 3.0)).abs() < 1e-9);
+
+        let mut macd = Macd::new(2, 3, 2);
+        let series = macd.series(&bars);
+        assert_eq!(series.values().count(), 2);
+        let last = series.last().unwrap();
+        assert!((last.histogram - (last.macd - last.signal)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_atr_matches_analytics() {
+        let bars: Vec<HistoricalBar> = (0..20)
+            .map(|i| {
+                let close = 50.0 + (i as f64 * 1.7).sin() * 3.0;
+                bar(i, close + 1.0 + (i % 3) as f64 * 0.4, close - 0.8, close, 100)
+            })
+            .collect();
+        for smoothing in [AtrSmoothing::Simple, AtrSmoothing::Wilder, AtrSmoothing::Exponential] {
+            let mut atr = Atr::new(5, smoothing);
+            atr.series(&bars);
+            let expected = analytics::atr(&bars, 5, smoothing).unwrap();
+            assert!((atr.value().unwrap() - expected).abs() < 1e-9, "{:?}", smoothing);
+        }
+    }
+
+    #[test]
+    fn test_vwap() {
+        let mut vwap = Vwap::new();
+        vwap.update(&bar(0, 11.0, 9.0, 10.0, 100));
+        vwap.update(&bar(5, 21.0, 19.0, 20.0, 300));
+        assert_eq!(vwap.value(), Some(17.5));
+
+        // A new session starts over
+        vwap.update(&bar(24 * 60, 31.0, 29.0, 30.0, 50));
+        assert_eq!(vwap.value(), Some(30.0));
+    }
+}
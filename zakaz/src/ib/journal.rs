@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use super::types::{Fill, OrderSide, OrderTemplate, TradingModel};
+
+/// Shares left below this count as flat, absorbing float error in summed fills
+const FLAT: f64 = 1e-9;
+
+/// A round trip: fills from flat to a position and back to flat
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub id: String, // Execution id of the fill that opened it
+    pub symbol: String,
+    pub side: OrderSide,
+    pub template_id: Option<String>, // Template that placed the opening order
+    pub model: Option<TradingModel>,
+    pub quantity: f64, // Shares entered
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>, // None while the position is open
+    pub avg_entry: f64,
+    pub avg_exit: Option<f64>, // Over the shares exited so far
+    pub realized_pnl: f64, // On the shares exited so far, less every commission paid
+    pub commission: f64,
+    pub r_multiple: Option<f64>, // Closed trades whose template planned a risk
+}
+
+/// What a template planned, to measure its trades against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TradePlan {
+    pub model: TradingModel,
+    pub risk_per_share: f64,
+}
+
+impl TradePlan {
+    pub fn from_template(template: &OrderTemplate) -> Self {
+        Self {
+            model: template.model,
+            risk_per_share: if template.quantity > 0.0 { template.risk_per_trade / template.quantity } else { 0.0 },
+        }
+    }
+}
+
+/// A trade while its fills are being added up
+struct OpenTrade {
+    trade: Trade,
+    entry_value: f64,
+    exited: f64,
+    exit_value: f64,
+}
+
+impl OpenTrade {
+    fn finish(mut self, plans: &HashMap<String, TradePlan>) -> Trade {
+        let direction = match self.trade.side {
+            OrderSide::Long => 1.0,
+            OrderSide::Short => -1.0,
+        };
+        self.trade.avg_entry = self.entry_value / self.trade.quantity;
+        self.trade.avg_exit = (self.exited > 0.0).then(|| self.exit_value / self.exited);
+        self.trade.realized_pnl = (self.exit_value - self.exited * self.trade.avg_entry) * direction - self.trade.commission;
+
+        let plan = self.trade.template_id.as_ref().and_then(|id| plans.get(id));
+        self.trade.model = plan.map(|p| p.model);
+        let initial_risk = plan.map_or(0.0, |p| p.risk_per_share * self.trade.quantity);
+        self.trade.r_multiple = (self.trade.closed_at.is_some() && initial_risk > 0.0)
+            .then(|| self.trade.realized_pnl / initial_risk);
+        self.trade
+    }
+}
+
+/// Roll fills up into trades, oldest first. Each symbol's fills are taken in time order; a fill
+/// that takes the position through flat closes one trade and opens the next with the rest.
+/// `plans` are keyed by template id.
+pub fn build_trades(fills: &[Fill], plans: &HashMap<String, TradePlan>) -> Vec<Trade> {
+    let mut sorted: Vec<&Fill> = fills.iter().collect();
+    sorted.sort_by(|a, b| a.symbol.cmp(&b.symbol).then(a.time.cmp(&b.time)).then(a.exec_id.cmp(&b.exec_id)));
+
+    let mut trades = Vec::new();
+    let mut open: Option<OpenTrade> = None;
+    for fill in sorted {
+        if let Some(current) = &open
+            && current.trade.symbol != fill.symbol
+        {
+            trades.extend(open.take().map(|t| t.finish(plans)));
+        }
+        if fill.quantity <= 0.0 {
+            continue;
+        }
+        let commission_per_share = fill.commission.unwrap_or(0.0) / fill.quantity;
+        let mut remaining = fill.quantity;
+
+        if let Some(current) = &mut open
+            && current.trade.side != fill.side
+        {
+            let closing = remaining.min(current.trade.quantity - current.exited);
+            current.exited += closing;
+            current.exit_value += closing * fill.price;
+            current.trade.commission += closing * commission_per_share;
+            remaining -= closing;
+            if current.trade.quantity - current.exited <= FLAT {
+                current.trade.closed_at = Some(fill.time);
+                trades.extend(open.take().map(|t| t.finish(plans)));
+            }
+        }
+        if remaining <= FLAT {
+            continue;
+        }
+
+        match &mut open {
+            Some(current) => {
+                current.trade.quantity += remaining;
+                current.entry_value += remaining * fill.price;
+                current.trade.commission += remaining * commission_per_share;
+            }
+            None => {
+                open = Some(OpenTrade {
+                    trade: Trade {
+                        id: fill.exec_id.clone(),
+                        symbol: fill.symbol.clone(),
+                        side: fill.side,
+                        template_id: fill.template_id.clone(),
+                        model: None,
+                        quantity: remaining,
+                        opened_at: fill.time,
+                        closed_at: None,
+                        avg_entry: fill.price,
+                        avg_exit: None,
+                        realized_pnl: 0.0,
+                        commission: remaining * commission_per_share,
+                        r_multiple: None,
+                    },
+                    entry_value: remaining * fill.price,
+                    exited: 0.0,
+                    exit_value: 0.0,
+                });
+            }
+        }
+    }
+    trades.extend(open.map(|t| t.finish(plans)));
+    trades.sort_by(|a, b| a.opened_at.cmp(&b.opened_at).then(a.id.cmp(&b.id)));
+    trades
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn fill(exec_id: &str, symbol: &str, side: OrderSide, quantity: f64, price: f64, minute: u32) -> Fill {
+        Fill {
+            exec_id: exec_id.to_string(),
+            order_id: 1,
+            template_id: Some("t1".to_string()),
+            symbol: symbol.to_string(),
+            side,
+            quantity,
+            price,
+            time: Utc.with_ymd_and_hms(2025, 7, 14, 14, minute, 0).unwrap(),
+            commission: Some(1.0),
+        }
+    }
+
+    #[test]
+    fn test_build_trades() {
+        let plans = HashMap::from([("t1".to_string(), TradePlan { model: TradingModel::Breakout, risk_per_share: 2.0 })]);
+        let fills = [
+            fill("e1", "AAPL", OrderSide::Long, 100.0, 100.0, 0),
+            fill("e2", "AAPL", OrderSide::Long, 100.0, 102.0, 5),
+            fill("e3", "AAPL", OrderSide::Short, 50.0, 105.0, 10),
+            // Sells the remaining 150 and opens a 50 share short
+            fill("e4", "AAPL", OrderSide::Short, 200.0, 104.0, 20),
+            fill("m1", "MSFT", OrderSide::Long, 10.0, 400.0, 15),
+        ];
+
+        let trades = build_trades(&fills, &plans);
+        assert_eq!(trades.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), vec!["e1", "m1", "e4"]);
+
+        let long = &trades[0];
+        assert_eq!(long.quantity, 200.0);
+        assert_eq!(long.avg_entry, 101.0);
+        assert_eq!(long.avg_exit, Some((50.0 * 105.0 + 150.0 * 104.0) / 200.0));
+        assert_eq!(long.closed_at, Some(Utc.with_ymd_and_hms(2025, 7, 14, 14, 20, 0).unwrap()));
+        // Three whole commissions and three quarters of the one split with the short
+        assert!((long.commission - 3.75).abs() < 1e-9);
+        assert!((long.realized_pnl - (50.0 * 4.0 + 150.0 * 3.0 - 3.75)).abs() < 1e-9);
+        assert_eq!(long.model, Some(TradingModel::Breakout));
+        assert!((long.r_multiple.unwrap() - long.realized_pnl / 400.0).abs() < 1e-9);
+
+        let open_msft = &trades[1];
+        assert_eq!(open_msft.closed_at, None);
+        assert_eq!(open_msft.avg_exit, None);
+        assert_eq!(open_msft.r_multiple, None);
+        assert_eq!(open_msft.realized_pnl, -1.0);
+
+        let short = &trades[2];
+        assert_eq!(short.side, OrderSide::Short);
+        assert_eq!(short.quantity, 50.0);
+        assert_eq!(short.avg_entry, 104.0);
+        assert_eq!(short.closed_at, None);
+    }
+
+    #[test]
+    fn test_trade_without_plan_has_no_r() {
+        let mut entry = fill("e1", "AAPL", OrderSide::Short, 10.0, 50.0, 0);
+        entry.template_id = None;
+        let trades = build_trades(&[entry, fill("e2", "AAPL", OrderSide::Long, 10.0, 45.0, 1)], &HashMap::new());
+        assert_eq!(trades.len(), 1);
+        assert!((trades[0].realized_pnl - 48.0).abs() < 1e-9);
+        assert_eq!(trades[0].model, None);
+        assert_eq!(trades[0].r_multiple, None);
+    }
+}
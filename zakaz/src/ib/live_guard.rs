@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::types::{EntryType, OrderTemplate};
+
+/// How long a prepared live action waits for its confirmation
+pub const CONFIRMATION_TTL_SECS: i64 = 60;
+
+/// An action that needs a second, explicit step while the live account is active
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiveAction {
+    Activate { template_id: String, override_price_band: bool, override_limits: bool },
+    ActivateOcoPair { template_id: String, override_price_band: bool, override_limits: bool },
+    Deactivate { template_id: String },
+    ActivateBasket { basket_id: String },
+    DeactivateBasket { basket_id: String },
+}
+
+/// A prepared action: echo `token` back in `ConfirmActivation` before `expires_at`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveConfirmation {
+    pub token: String,
+    pub action: LiveAction,
+    pub summary: String, // What will be sent, for the confirmation prompt
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Prepared actions waiting for confirmation, and confirmed ones not yet carried out.
+/// Tokens are single use.
+#[derive(Debug, Default)]
+pub struct PendingConfirmations {
+    prepared: HashMap<String, LiveConfirmation>,
+    confirmed: Vec<LiveAction>,
+}
+
+impl PendingConfirmations {
+    pub fn prepare(&mut self, action: LiveAction, summary: String, now: DateTime<Utc>) -> LiveConfirmation {
+        self.prepared.retain(|_, c| c.expires_at > now);
+        let confirmation = LiveConfirmation {
+            token: uuid::Uuid::new_v4().to_string(),
+            action,
+            summary,
+            expires_at: now + Duration::seconds(CONFIRMATION_TTL_SECS),
+        };
+        self.prepared.insert(confirmation.token.clone(), confirmation.clone());
+        confirmation
+    }
+
+    /// Redeem `token`, clearing its action to run once
+    pub fn confirm(&mut self, token: &str, now: DateTime<Utc>) -> Result<LiveAction, String> {
+        let confirmation = self.prepared.remove(token)
+            .ok_or("Unknown or already used confirmation token".to_string())?;
+        if confirmation.expires_at <= now {
+            return Err("Confirmation expired; prepare the action again".to_string());
+        }
+        self.confirmed.push(confirmation.action.clone());
+        Ok(confirmation.action)
+    }
+
+    /// Consume the confirmation for `action`, if there is one
+    pub fn take_confirmed(&mut self, action: &LiveAction) -> bool {
+        match self.confirmed.iter().position(|a| a == action) {
+            Some(index) => {
+                self.confirmed.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Order limits applied on the live account; zero disables the notional cap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiveOrderLimits {
+    pub max_order_notional: f64,
+    pub allow_market_orders: bool,
+}
+
+impl Default for LiveOrderLimits {
+    fn default() -> Self {
+        Self {
+            max_order_notional: 0.0,
+            allow_market_orders: true,
+        }
+    }
+}
+
+impl LiveOrderLimits {
+    /// Refuse an entry order the live account shouldn't send. Not overridable.
+    pub fn check(&self, template: &OrderTemplate) -> Result<(), String> {
+        if !self.allow_market_orders && template.entry_type == EntryType::Market {
+            return Err(format!("{}: market orders are disabled on the live account (live_allow_market_orders)", template.symbol));
+        }
+        let notional = template.notional();
+        if self.max_order_notional > 0.0 && notional > self.max_order_notional {
+            return Err(format!(
+                "{}: order notional {:.2} exceeds the live maximum of {:.2} (live_max_order_notional)",
+                template.symbol, notional, self.max_order_notional
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ib::types::{OrderSide, TimeInForce, TradingModel};
+
+    #[test]
+    fn test_confirmations() {
+        let mut pending = PendingConfirmations::default();
+        let now = Utc::now();
+        let action = LiveAction::Deactivate { template_id: "t1".to_string() };
+
+        // Nothing runs without a confirmed token
+        assert!(!pending.take_confirmed(&action));
+
+        let prepared = pending.prepare(action.clone(), "Deactivate t1".to_string(), now);
+        assert!(!pending.take_confirmed(&action));
+        assert_eq!(pending.confirm(&prepared.token, now), Ok(action.clone()));
+        // Tokens are single use, as is the confirmation they grant
+        assert!(pending.confirm(&prepared.token, now).is_err());
+        assert!(pending.take_confirmed(&action));
+        assert!(!pending.take_confirmed(&action));
+
+        // A confirmation arriving after the window is refused
+        let late = pending.prepare(action.clone(), "Deactivate t1".to_string(), now);
+        assert!(pending.confirm(&late.token, now + Duration::seconds(CONFIRMATION_TTL_SECS)).is_err());
+        assert!(!pending.take_confirmed(&action));
+    }
+
+    #[test]
+    fn test_live_order_limits() {
+        let mut template = OrderTemplate::new(
+            "AAPL long".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            148.0,
+            TimeInForce::Day,
+            TradingModel::Breakout,
+        );
+        assert!(LiveOrderLimits::default().check(&template).is_ok());
+
+        let limits = LiveOrderLimits { max_order_notional: 10_000.0, allow_market_orders: false };
+        assert!(limits.check(&template).unwrap_err().contains("15000.00"));
+
+        template.quantity = 50.0;
+        assert!(limits.check(&template).is_ok());
+        template.entry_type = EntryType::Market;
+        assert!(limits.check(&template).unwrap_err().contains("market orders"));
+    }
+}
@@ -0,0 +1,209 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use super::trading_calendar;
+
+/// US equities regular session close, exchange local time
+pub fn market_close_time() -> NaiveTime {
+    NaiveTime::from_hms_opt(16, 0, 0).unwrap()
+}
+
+/// US equities regular session open, exchange local time
+pub fn market_open_time() -> NaiveTime {
+    NaiveTime::from_hms_opt(9, 30, 0).unwrap()
+}
+
+/// Weekdays other than exchange holidays
+pub fn is_trading_day(date: NaiveDate) -> bool {
+    trading_calendar::is_trading_day(date)
+}
+
+/// The date `days` trading days after `date`
+pub fn add_trading_days(date: NaiveDate, days: u32) -> NaiveDate {
+    let mut result = date;
+    let mut remaining = days;
+    while remaining > 0 {
+        result = result.succ_opt().expect("date within chrono's range");
+        if is_trading_day(result) {
+            remaining -= 1;
+        }
+    }
+    result
+}
+
+/// Trading days after `from` up to and including `to`; zero when `to` isn't later
+pub fn trading_days_between(from: NaiveDate, to: NaiveDate) -> u32 {
+    from.iter_days()
+        .skip(1)
+        .take_while(|date| *date <= to)
+        .filter(|date| is_trading_day(*date))
+        .count() as u32
+}
+
+/// Regular session close for the given exchange-local date, 13:00 on early-close days
+pub fn market_close_on(date: NaiveDate) -> DateTime<Utc> {
+    let close = if trading_calendar::is_early_close(date) {
+        trading_calendar::early_close_time()
+    } else {
+        market_close_time()
+    };
+    New_York
+        .from_local_datetime(&date.and_time(close))
+        .single()
+        .expect("13:00 and 16:00 are never ambiguous in New York")
+        .with_timezone(&Utc)
+}
+
+/// Regular session open for the given exchange-local date
+pub fn market_open_on(date: NaiveDate) -> DateTime<Utc> {
+    New_York
+        .from_local_datetime(&date.and_time(market_open_time()))
+        .single()
+        .expect("09:30 is never ambiguous in New York")
+        .with_timezone(&Utc)
+}
+
+/// The first regular session open after `after`
+pub fn next_session_open(after: DateTime<Utc>) -> DateTime<Utc> {
+    let date = session_date(after);
+    if is_trading_day(date) && after < market_open_on(date) {
+        return market_open_on(date);
+    }
+    market_open_on(add_trading_days(date, 1))
+}
+
+/// Exchange-local date of the session `now` falls in
+pub fn session_date(now: DateTime<Utc>) -> NaiveDate {
+    now.with_timezone(&New_York).date_naive()
+}
+
+/// A TWS timestamp such as execution time: `yyyymmdd hh:mm:ss` followed by an optional
+/// time zone name. Without a zone, exchange time is assumed
+pub fn parse_ib_time(value: &str) -> Option<DateTime<Utc>> {
+    let mut parts = value.split_whitespace();
+    let local = chrono::NaiveDateTime::parse_from_str(
+        &format!("{} {}", parts.next()?, parts.next()?),
+        "%Y%m%d %H:%M:%S",
+    ).ok()?;
+    let zone = match parts.next() {
+        Some(name) => name.parse::<chrono_tz::Tz>().ok()?,
+        None => New_York,
+    };
+    zone.from_local_datetime(&local).earliest().map(|time| time.with_timezone(&Utc))
+}
+
+/// Whether `now` falls between a trading day's regular open and close
+pub fn in_regular_session(now: DateTime<Utc>) -> bool {
+    let date = session_date(now);
+    is_trading_day(date) && now >= market_open_on(date) && now < market_close_on(date)
+}
+
+/// Regular-session time from `from` to `to` in seconds, negative when `to` is earlier;
+/// nights, weekends and holidays don't count
+pub fn session_seconds_between(from: DateTime<Utc>, to: DateTime<Utc>) -> i64 {
+    if to < from {
+        return -session_seconds_between(to, from);
+    }
+    session_date(from).iter_days()
+        .take_while(|date| *date <= session_date(to))
+        .filter(|date| is_trading_day(*date))
+        .map(|date| {
+            let start = market_open_on(date).max(from);
+            let end = market_close_on(date).min(to);
+            (end - start).num_seconds().max(0)
+        })
+        .sum()
+}
+
+/// Today's close if `now` falls within `minutes_before` of it on a trading day
+pub fn close_within(now: DateTime<Utc>, minutes_before: i64) -> Option<DateTime<Utc>> {
+    let date = session_date(now);
+    if !is_trading_day(date) {
+        return None;
+    }
+
+    let close = market_close_on(date);
+    (now >= close - Duration::minutes(minutes_before) && now < close).then_some(close)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_close_within() {
+        // 2025-07-25 is a Friday in EDT (UTC-4): close is 20:00 UTC
+        let friday = NaiveDate::from_ymd_opt(2025, 7, 25).unwrap();
+        let close = market_close_on(friday);
+        assert_eq!(close, Utc.with_ymd_and_hms(2025, 7, 25, 20, 0, 0).unwrap());
+
+        assert_eq!(close_within(close - Duration::minutes(3), 5), Some(close));
+        assert_eq!(close_within(close - Duration::minutes(10), 5), None);
+        assert_eq!(close_within(close, 5), None);
+
+        // Winter close is 21:00 UTC
+        let january = market_close_on(NaiveDate::from_ymd_opt(2025, 1, 10).unwrap());
+        assert_eq!(january, Utc.with_ymd_and_hms(2025, 1, 10, 21, 0, 0).unwrap());
+
+        // No session on Saturday
+        let saturday = Utc.with_ymd_and_hms(2025, 7, 26, 19, 58, 0).unwrap();
+        assert_eq!(close_within(saturday, 5), None);
+    }
+
+    #[test]
+    fn test_add_trading_days() {
+        let wednesday = NaiveDate::from_ymd_opt(2025, 7, 23).unwrap();
+        assert_eq!(add_trading_days(wednesday, 0), wednesday);
+        assert_eq!(add_trading_days(wednesday, 2), NaiveDate::from_ymd_opt(2025, 7, 25).unwrap());
+        // Weekends don't count
+        assert_eq!(add_trading_days(wednesday, 5), NaiveDate::from_ymd_opt(2025, 7, 30).unwrap());
+        let saturday = NaiveDate::from_ymd_opt(2025, 7, 26).unwrap();
+        assert_eq!(add_trading_days(saturday, 1), NaiveDate::from_ymd_opt(2025, 7, 28).unwrap());
+
+        assert_eq!(trading_days_between(wednesday, add_trading_days(wednesday, 5)), 5);
+        assert_eq!(trading_days_between(wednesday, saturday), 2);
+        assert_eq!(trading_days_between(saturday, wednesday), 0);
+    }
+
+    #[test]
+    fn test_next_session_open() {
+        // Friday 2025-07-25 opens 13:30 UTC
+        let friday_open = Utc.with_ymd_and_hms(2025, 7, 25, 13, 30, 0).unwrap();
+        assert_eq!(next_session_open(friday_open - Duration::hours(1)), friday_open);
+        // From the open onwards, and over the weekend, the next open is Monday's
+        let monday_open = Utc.with_ymd_and_hms(2025, 7, 28, 13, 30, 0).unwrap();
+        assert_eq!(next_session_open(friday_open), monday_open);
+        assert_eq!(next_session_open(Utc.with_ymd_and_hms(2025, 7, 26, 15, 0, 0).unwrap()), monday_open);
+
+        assert!(in_regular_session(friday_open));
+        assert!(!in_regular_session(friday_open - Duration::minutes(1)));
+        assert!(!in_regular_session(Utc.with_ymd_and_hms(2025, 7, 26, 15, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_parse_ib_time() {
+        let expected = Utc.with_ymd_and_hms(2025, 7, 14, 13, 35, 12).unwrap();
+        assert_eq!(parse_ib_time("20250714 09:35:12 US/Eastern"), Some(expected));
+        assert_eq!(parse_ib_time("20250714  09:35:12"), Some(expected));
+        assert_eq!(parse_ib_time("20250714 13:35:12 UTC"), Some(expected));
+        assert_eq!(parse_ib_time("20250714 09:35:12 Nowhere/Else"), None);
+        assert_eq!(parse_ib_time("2025-07-14"), None);
+    }
+
+    #[test]
+    fn test_session_seconds_between() {
+        // Thursday 2025-07-03 closes early at 13:00 ET, Friday is Independence Day
+        let thursday_noon = Utc.with_ymd_and_hms(2025, 7, 3, 16, 0, 0).unwrap();
+        assert_eq!(market_close_on(NaiveDate::from_ymd_opt(2025, 7, 3).unwrap()), thursday_noon + Duration::hours(1));
+        let monday_ten = Utc.with_ymd_and_hms(2025, 7, 7, 14, 0, 0).unwrap();
+        // An hour on Thursday, half an hour on Monday
+        assert_eq!(session_seconds_between(thursday_noon, monday_ten), 90 * 60);
+        assert_eq!(session_seconds_between(monday_ten, thursday_noon), -90 * 60);
+        // Overnight counts for nothing
+        let monday_close = Utc.with_ymd_and_hms(2025, 7, 7, 20, 0, 0).unwrap();
+        assert_eq!(session_seconds_between(monday_close, monday_close + Duration::hours(13)), 0);
+
+        // A holiday isn't a trading day for the rest of the module either
+        let thursday = NaiveDate::from_ymd_opt(2025, 7, 3).unwrap();
+        assert_eq!(add_trading_days(thursday, 1), NaiveDate::from_ymd_opt(2025, 7, 7).unwrap());
+    }
+}
@@ -1,7 +1,26 @@
-use super::types::{OrderTemplate, OrderSide, TimeInForce, ATRResult, OutlierMethod, TradingModel};
+use super::types::{
+    ATRResult, AtrSmoothing, AuditEntry, AuditFilter, AuditPage, BasketResult, ChecklistAnswer, ChecklistItem, EntryType, OrderSide, OrderTemplate, OutlierMethod, RepriceProposal, Sector, SizingStrategy, StopRule, SymbolSettings, TemplateBasket, TemplateRevision, TimeInForce,
+    TradingModel, Watchlist, WatchlistAtr, WatchlistEntry,
+};
+use super::analytics::{RelativeVolume, StopSuggestions, VolatilityProfile};
+use super::earnings::EarningsEvent;
+use super::gaps::GapStats;
+use super::live_guard::{LiveAction, LiveConfirmation};
+use super::orders::calculations::TradeCosts;
+use super::outcomes::RStats;
+use super::monte_carlo::{MonteCarloConfig, MonteCarloResult};
+use super::performance::{PerformanceRange, PerformanceStats};
+use super::position_sizing::ModelDefaults;
+use super::risk::{RiskSummary, ShortCheck, TradingLock};
+use super::scanner::{ScanCriteria, ScanResult};
+use crate::db::export::CsvTable;
+use crate::db::import::ImportResult;
+use crate::db::search::SearchHit;
+use crate::db::settings::Settings;
 use tokio::sync::oneshot;
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum IBMessage {
     // Connection management
     ConnectPaper {
@@ -26,10 +45,13 @@ pub enum IBMessage {
         name: String,
         symbol: String,
         side: OrderSide,
-        quantity: f64,
+        quantity: f64,                     // Ignored when auto-sizing
+        auto_size: bool,                   // Size from risk_per_trade; implied when quantity <= 0
+        sizing_strategy: SizingStrategy,   // How auto-sizing derives the quantity
         limit_price: f64,
-        stop_price: f64,
+        stop_price: f64,                   // 0 proposes the model's default ATR stop
         time_in_force: TimeInForce,
+        entry_type: EntryType,
         model: TradingModel,
         response: oneshot::Sender<Result<String, String>>, // Returns template ID
     },
@@ -41,6 +63,63 @@ pub enum IBMessage {
         template_id: String,
         response: oneshot::Sender<Result<(), String>>,
     },
+    GetAuditTrail {
+        template_id: String,
+        response: oneshot::Sender<Result<Vec<AuditEntry>, String>>, // Oldest first
+    },
+    /// A page of the audit log across templates, newest first
+    GetAuditLog {
+        filter: AuditFilter,
+        offset: usize,
+        limit: usize,
+        response: oneshot::Sender<Result<AuditPage, String>>,
+    },
+    AdoptOrphans {
+        response: oneshot::Sender<Result<usize, String>>, // Read-only templates now mirrored
+    },
+    ClaimTemplate {
+        template_id: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    ArchiveTemplate {
+        template_id: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    RestoreTemplate {
+        template_id: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    GetArchivedTemplates {
+        response: oneshot::Sender<Result<Vec<OrderTemplate>, String>>,
+    },
+    RepriceTemplate {
+        template_id: String,
+        response: oneshot::Sender<Result<RepriceProposal, String>>, // Apply with UpdateTemplate once confirmed
+    },
+    /// Risk per trade, default ATR stop and target R used for new templates of a model
+    GetModelDefaults {
+        model: TradingModel,
+        response: oneshot::Sender<Result<ModelDefaults, String>>,
+    },
+    SetModelDefaults {
+        model: TradingModel,
+        defaults: ModelDefaults,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    /// App-wide settings; per-model defaults are above
+    GetSettings {
+        response: oneshot::Sender<Result<Settings, String>>,
+    },
+    /// Validate and save all settings, announcing them with `UIMessage::SettingsChanged`
+    UpdateSettings {
+        settings: Settings,
+        response: oneshot::Sender<Result<Settings, String>>,
+    },
+    DuplicateTemplate {
+        template_id: String,
+        new_name: Option<String>, // Defaults to "<name> (copy)"
+        response: oneshot::Sender<Result<String, String>>, // Returns new template ID
+    },
     GetTemplate {
         template_id: String,
         response: oneshot::Sender<Option<OrderTemplate>>,
@@ -48,10 +127,42 @@ pub enum IBMessage {
     GetAllTemplates {
         response: oneshot::Sender<Vec<OrderTemplate>>,
     },
+    GetTemplateHistory {
+        template_id: String,
+        response: oneshot::Sender<Result<Vec<TemplateRevision>, String>>, // Oldest revision first
+    },
+    ExportTemplates {
+        path: std::path::PathBuf,
+        response: oneshot::Sender<Result<usize, String>>, // Returns number of exported templates
+    },
     
     // Order activation/deactivation
     ActivateTemplate {
         template_id: String,
+        override_price_band: bool, // Allow an entry far from the current quote
+        override_limits: bool,     // Exceed position limits and exposure caps
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    CreateOcoPair {
+        first: OrderTemplate,
+        second: OrderTemplate,
+        response: oneshot::Sender<Result<(String, String), String>>, // Returns both template IDs
+    },
+    ActivateOcoPair {
+        template_id: String, // Either member of the pair
+        override_price_band: bool,
+        override_limits: bool,
+        response: oneshot::Sender<Result<Vec<String>, String>>,
+    },
+    MoveStopToBreakeven {
+        template_id: String,
+        offset: f64, // Distance into profit from the entry
+        response: oneshot::Sender<Result<f64, String>>, // Returns the new stop price
+    },
+    /// Replace the automatic stop-management rules of a template
+    SetStopRules {
+        template_id: String,
+        rules: Vec<StopRule>,
         response: oneshot::Sender<Result<(), String>>,
     },
     DeactivateTemplate {
@@ -59,6 +170,290 @@ pub enum IBMessage {
         response: oneshot::Sender<Result<(), String>>,
     },
     
+    // Pre-trade checklists: activation waits for every item of the model to be ticked
+    SaveChecklistItem {
+        id: Option<String>,             // None creates a new item
+        model: Option<TradingModel>,    // None asks it of every model
+        text: String,
+        position: i64,
+        response: oneshot::Sender<Result<String, String>>, // Returns item ID
+    },
+    DeleteChecklistItem {
+        id: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    GetChecklistItems {
+        model: Option<TradingModel>,    // None lists every item
+        response: oneshot::Sender<Result<Vec<ChecklistItem>, String>>,
+    },
+    GetChecklist {
+        template_id: String,
+        response: oneshot::Sender<Result<Vec<ChecklistAnswer>, String>>,
+    },
+    SubmitChecklist {
+        template_id: String,
+        answers: Vec<ChecklistAnswer>,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    
+    // Live-account confirmation: activation and deactivation run only after both steps
+    PrepareActivation {
+        action: LiveAction,
+        response: oneshot::Sender<Result<LiveConfirmation, String>>,
+    },
+    ConfirmActivation {
+        token: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    
+    // Scheduled activation
+    ScheduleActivation {
+        template_id: String,
+        activate_at: Option<chrono::DateTime<chrono::Utc>>, // None clears the schedule
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    /// Sent periodically by the runtime scheduler
+    ActivateScheduledTemplates,
+    /// Sent periodically by the runtime scheduler; acts only shortly before the close
+    DeactivateDayTemplates,
+    /// Sent periodically by the runtime scheduler; applies IB executions to templates
+    SyncFills,
+    /// Sent periodically by the runtime scheduler; expires stale unactivated templates
+    ExpireTemplates,
+    /// Runs stop rules on filled templates at the latest price: for every symbol when sent by the
+    /// runtime scheduler, for one symbol when its streamed market data moves
+    ApplyStopRules { symbol: Option<String> },
+    
+    // Sectors and exposure caps
+    SaveSector {
+        name: String,
+        max_notional: Option<f64>, // None leaves notional uncapped
+        max_risk: Option<f64>,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    DeleteSector {
+        name: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    AssignSymbolSector {
+        symbol: String,
+        sector: Option<String>, // None removes the symbol from its sector
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    GetSectors {
+        response: oneshot::Sender<Result<Vec<Sector>, String>>,
+    },
+    
+    // Per-symbol settings
+    GetSymbolSettings {
+        symbol: String,
+        response: oneshot::Sender<Result<Option<SymbolSettings>, String>>, // None: the symbol follows the global settings
+    },
+    GetAllSymbolSettings {
+        response: oneshot::Sender<Result<Vec<SymbolSettings>, String>>,
+    },
+    SaveSymbolSettings {
+        settings: SymbolSettings,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    DeleteSymbolSettings {
+        symbol: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    
+    // Daily loss limit
+    /// Sent periodically by the runtime scheduler; locks trading once the day's loss passes `daily_loss_limit`
+    CheckLossLimit,
+    /// Sent periodically by the runtime scheduler; updates the equity high-water mark behind drawdown risk scaling
+    TrackDrawdown,
+    /// Sent periodically by the runtime scheduler; alerts when a watched symbol's range today runs to several ATRs
+    CheckVolatilitySpikes,
+    /// Sent periodically by the runtime scheduler; takes an automatic backup when `backup_interval_hours` have passed
+    BackupIfDue,
+    /// Sent periodically by the runtime scheduler; purges templates deleted more than `purge_deleted_templates_days` ago
+    PurgeDeletedTemplates,
+    /// Write templates, trades or executions to a CSV file at `path`, within `[from, to)` when
+    /// given; returns the rows written
+    ExportCsv {
+        table: CsvTable,
+        path: std::path::PathBuf,
+        from: Option<chrono::DateTime<chrono::Utc>>,
+        to: Option<chrono::DateTime<chrono::Utc>>,
+        response: oneshot::Sender<Result<usize, String>>,
+    },
+    /// Templates, trades and watchlist entries matching every word of `query`, best first
+    Search {
+        query: String,
+        limit: usize,
+        response: oneshot::Sender<Result<Vec<SearchHit>, String>>,
+    },
+    /// Create a template for each planned trade in the CSV file at `path`, sizing from risk
+    /// when a row has no quantity; returns what happened to each row
+    ImportTemplates {
+        path: std::path::PathBuf,
+        response: oneshot::Sender<Result<Vec<ImportResult>, String>>,
+    },
+    /// Copy the database to `path`, or into the automatic backups when None; returns where it went
+    BackupDatabase {
+        path: Option<std::path::PathBuf>,
+        response: oneshot::Sender<Result<std::path::PathBuf, String>>,
+    },
+    GetTradingLock {
+        response: oneshot::Sender<Option<TradingLock>>, // None when activations are allowed
+    },
+    /// Open risk by symbol and model, pending vs filled, and the remaining daily loss budget
+    GetRiskSummary {
+        response: oneshot::Sender<RiskSummary>,
+    },
+    /// Borrow status and what-if margin for a short template (None for longs); records the borrow status
+    CheckShortSale {
+        template_id: String,
+        response: oneshot::Sender<Result<Option<ShortCheck>, String>>,
+    },
+    /// Risk, breakeven and reward:risk (to `target_price`) with the configured commissions
+    GetTradeCosts {
+        template_id: String,
+        target_price: Option<f64>,
+        response: oneshot::Sender<Result<TradeCosts, String>>,
+    },
+    /// Rolling R statistics of closed trades; `None` filters match everything, `window` keeps the latest N
+    GetRStats {
+        model: Option<TradingModel>,
+        symbol: Option<String>,
+        window: Option<usize>,
+        response: oneshot::Sender<Result<Option<RStats>, String>>, // None before any trade closes
+    },
+    /// Expectancy, win rate, profit factor, per-model and per-symbol breakdowns and the daily equity curve
+    GetPerformanceStats {
+        range: PerformanceRange,
+        response: oneshot::Sender<Result<PerformanceStats, String>>,
+    },
+    /// Resampled trade sequences: equity percentile bands, drawdown distribution and risk of ruin.
+    /// `r_multiples` (e.g. `BacktestReport::r_multiples`) replaces the journal's trades for `model`
+    RunMonteCarlo {
+        model: Option<TradingModel>,
+        r_multiples: Option<Vec<f64>>,
+        config: MonteCarloConfig,
+        response: oneshot::Sender<Result<MonteCarloResult, String>>,
+    },
+    
+    // Earnings calendar
+    /// Add a report date, or change the timing of one already on the calendar
+    SaveEarningsDate {
+        event: EarningsEvent,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    RemoveEarningsDate {
+        symbol: String,
+        date: chrono::NaiveDate,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    GetEarningsDates {
+        symbol: Option<String>, // None lists every symbol
+        response: oneshot::Sender<Result<Vec<EarningsEvent>, String>>,
+    },
+    /// Earnings reacting within `earnings_hold_days` of today, as activation would see them
+    CheckEarnings {
+        template_id: String,
+        response: oneshot::Sender<Result<Option<EarningsEvent>, String>>,
+    },
+    
+    // Watchlists and market scanner
+    CreateWatchlist {
+        name: String,
+        response: oneshot::Sender<Result<Watchlist, String>>,
+    },
+    RenameWatchlist {
+        watchlist_id: String,
+        name: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    /// Delete a watchlist and its symbols; the default one can't be deleted
+    DeleteWatchlist {
+        watchlist_id: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    GetWatchlists {
+        response: oneshot::Sender<Result<Vec<Watchlist>, String>>,
+    },
+    /// `watchlist_id` None edits the default watchlist, here and below
+    AddToWatchlist {
+        watchlist_id: Option<String>,
+        symbols: Vec<String>,
+        note: Option<String>,
+        response: oneshot::Sender<Result<Vec<String>, String>>, // The symbols as stored
+    },
+    RemoveFromWatchlist {
+        watchlist_id: Option<String>,
+        symbol: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    /// `symbols` lists each of the watchlist's symbols once, in their new order
+    ReorderWatchlist {
+        watchlist_id: String,
+        symbols: Vec<String>,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    GetWatchlist {
+        watchlist_id: Option<String>,
+        response: oneshot::Sender<Result<Vec<WatchlistEntry>, String>>,
+    },
+    /// Filtered ATR for each of a watchlist's symbols, without forcing a refresh
+    CalculateWatchlistATR {
+        watchlist_id: Option<String>,
+        period_days: usize,
+        method: OutlierMethod,
+        smoothing: AtrSmoothing,
+        response: oneshot::Sender<Result<WatchlistAtr, String>>,
+    },
+    /// Screen cached daily bars; `symbols` None scans `watchlist_id`'s symbols, else
+    /// `scanner_universe`, else every watched symbol
+    RunScan {
+        criteria: ScanCriteria,
+        symbols: Option<Vec<String>>,
+        watchlist_id: Option<String>,
+        response: oneshot::Sender<Result<Vec<ScanResult>, String>>,
+    },
+    /// Create and auto-size a limit template at the scanned close with the model's ATR stop
+    CreateTemplateFromScan {
+        symbol: String,
+        side: OrderSide,
+        model: TradingModel,
+        response: oneshot::Sender<Result<String, String>>, // Returns template ID
+    },
+    
+    // Template baskets
+    CreateBasket {
+        name: String,
+        template_ids: Vec<String>,
+        response: oneshot::Sender<Result<String, String>>, // Returns basket ID
+    },
+    DeleteBasket {
+        basket_id: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    AddToBasket {
+        basket_id: String,
+        template_id: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    RemoveFromBasket {
+        basket_id: String,
+        template_id: String,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    GetAllBaskets {
+        response: oneshot::Sender<Result<Vec<TemplateBasket>, String>>,
+    },
+    ActivateBasket {
+        basket_id: String,
+        response: oneshot::Sender<Result<BasketResult, String>>,
+    },
+    DeactivateBasket {
+        basket_id: String,
+        response: oneshot::Sender<Result<BasketResult, String>>,
+    },
+    
     // Market data
     SubscribeMarketData {
         symbol: String,
@@ -81,6 +476,7 @@ pub enum IBMessage {
         symbol: String,
         duration_days: u32,
         bar_size: String,
+        use_rth: bool, // false includes pre/post-market bars
         response: oneshot::Sender<Result<super::types::HistoricalData, String>>,
     },
     
@@ -89,8 +485,43 @@ pub enum IBMessage {
         symbol: String,
         period_days: usize,
         method: OutlierMethod,
+        smoothing: AtrSmoothing,
+        force_refresh: bool, // Recalculate even when a cached result is within atr_cache_ttl
         response: oneshot::Sender<Result<ATRResult, String>>,
     },
+    /// ATR-default, swing-based and widest allowed stops for an entry, under `model`'s stop rules
+    /// (None uses the global settings)
+    SuggestStops {
+        symbol: String,
+        side: OrderSide,
+        entry_price: f64,
+        model: Option<TradingModel>,
+        response: oneshot::Sender<Result<StopSuggestions, String>>,
+    },
+    /// The ATR analysis, excluded bars included, that was current when the template was created
+    GetTemplateATRAnalysis {
+        template_id: String,
+        response: oneshot::Sender<Result<Option<ATRResult>, String>>,
+    },
+    /// Historical volatility, gaps and range percentile next to ATR
+    GetVolatilityProfile {
+        symbol: String,
+        response: oneshot::Sender<Result<VolatilityProfile, String>>,
+    },
+    /// Today's cumulative volume against the same time of day over the last `sessions` sessions
+    GetRelativeVolume {
+        symbol: String,
+        sessions: usize,
+        response: oneshot::Sender<Result<RelativeVolume, String>>,
+    },
+    /// Open-gap frequency, size and same-session fill rate over `lookback_days` sessions;
+    /// opens within `min_gap_pct` of the previous close don't count
+    GetGapStats {
+        symbol: String,
+        lookback_days: usize,
+        min_gap_pct: f64,
+        response: oneshot::Sender<Result<GapStats, String>>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -127,5 +558,21 @@ pub struct MarketData {
     pub ask: f64,
     pub last: f64,
     pub volume: i64,
+    pub relative_volume: Option<f64>, // Latest RVOL measured for the symbol
     pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl MarketData {
+    /// Nothing received yet
+    pub fn new(symbol: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            bid: 0.0,
+            ask: 0.0,
+            last: 0.0,
+            volume: 0,
+            relative_volume: None,
+            timestamp: chrono::Utc::now(),
+        }
+    }
 }
\ No newline at end of file
@@ -3,6 +3,24 @@ pub mod types;
 pub mod orders;
 pub mod messages;
 pub mod position_sizing;
+pub mod market_hours;
+pub mod trading_calendar;
+pub mod orphans;
+pub mod risk;
+pub mod outcomes;
+pub mod journal;
+pub mod stop_management;
+pub mod live_guard;
+pub mod fx;
+pub mod analytics;
+pub mod indicators;
+pub mod backtest;
+pub mod bar_cache;
+pub mod performance;
+pub mod monte_carlo;
+pub mod scanner;
+pub mod earnings;
+pub mod gaps;
 
 pub use client::{IBClient, AccountType};
 pub use types::OrderTemplate;
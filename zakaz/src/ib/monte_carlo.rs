@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+/// Fewest R-multiples worth resampling
+pub const MIN_SAMPLE_TRADES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MonteCarloConfig {
+    pub simulations: usize,
+    pub trades: usize,                  // Trades per simulated sequence
+    pub risk_per_trade: Option<f64>,    // None: the model's current risk per trade
+    pub starting_equity: Option<f64>,   // None: the account's net liquidation
+    pub ruin_drawdown_pct: f64,         // A fall this far from the peak counts as ruin
+    pub seed: Option<u64>,              // Fixed for repeatable runs
+}
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        Self {
+            simulations: 5000,
+            trades: 100,
+            risk_per_trade: None,
+            starting_equity: None,
+            ruin_drawdown_pct: 50.0,
+            seed: None,
+        }
+    }
+}
+
+/// 5th, 25th, 50th, 75th and 95th percentiles across simulations
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub p5: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+}
+
+impl Percentiles {
+    fn of(values: &mut [f64]) -> Self {
+        values.sort_by(|a, b| a.total_cmp(b));
+        let at = |pct: f64| {
+            let index = ((values.len() - 1) as f64 * pct / 100.0).round() as usize;
+            values[index]
+        };
+        Self { p5: at(5.0), p25: at(25.0), p50: at(50.0), p75: at(75.0), p95: at(95.0) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonteCarloResult {
+    pub sample_trades: usize,          // R-multiples resampled
+    pub expectancy_r: f64,             // Their mean
+    pub simulations: usize,
+    pub trades: usize,
+    pub risk_per_trade: f64,
+    pub starting_equity: f64,
+    pub equity_bands: Vec<Percentiles>, // Equity after 0..=trades trades, for plotting
+    pub final_equity: Percentiles,
+    pub max_drawdown: Percentiles,     // Deepest fall from a peak per sequence, in currency
+    pub max_drawdown_pct: Percentiles,
+    pub risk_of_ruin: f64,             // Share of sequences that hit `ruin_drawdown_pct` or zero, 0–1
+}
+
+/// Resample `r_multiples` with replacement into `config.trades`-long sequences, each trade
+/// risking a fixed `risk_per_trade`, and summarize equity, drawdown and ruin across them
+pub fn simulate(
+    r_multiples: &[f64],
+    config: &MonteCarloConfig,
+    risk_per_trade: f64,
+    starting_equity: f64,
+) -> Result<MonteCarloResult, String> {
+    if r_multiples.len() < MIN_SAMPLE_TRADES {
+        return Err(format!("Monte Carlo needs at least {} trades, found {}", MIN_SAMPLE_TRADES, r_multiples.len()));
+    }
+    if config.simulations == 0 || config.trades == 0 {
+        return Err("Simulations and trades per sequence must be positive".to_string());
+    }
+    if risk_per_trade <= 0.0 || starting_equity <= 0.0 {
+        return Err("Risk per trade and starting equity must be positive".to_string());
+    }
+
+    let mut rng = SplitMix64::new(config.seed.unwrap_or_else(|| chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0) as u64));
+    // Equity by step, one column per simulation
+    let mut equity = vec![vec![0.0; config.simulations]; config.trades + 1];
+    let mut drawdowns = Vec::with_capacity(config.simulations);
+    let mut drawdown_pcts = Vec::with_capacity(config.simulations);
+    let mut ruined = 0usize;
+
+    for simulation in 0..config.simulations {
+        let (mut balance, mut peak) = (starting_equity, starting_equity);
+        let (mut deepest, mut deepest_pct) = (0.0_f64, 0.0_f64);
+        let mut is_ruined = false;
+        equity[0][simulation] = balance;
+        for step in equity.iter_mut().skip(1) {
+            balance += r_multiples[rng.below(r_multiples.len())] * risk_per_trade;
+            peak = peak.max(balance);
+            deepest = deepest.max(peak - balance);
+            let pct = (peak - balance) / peak * 100.0;
+            deepest_pct = deepest_pct.max(pct);
+            is_ruined |= balance <= 0.0 || pct >= config.ruin_drawdown_pct;
+            step[simulation] = balance;
+        }
+        drawdowns.push(deepest);
+        drawdown_pcts.push(deepest_pct);
+        ruined += is_ruined as usize;
+    }
+
+    let equity_bands: Vec<Percentiles> = equity.iter_mut().map(|step| Percentiles::of(step)).collect();
+    let final_equity = equity_bands.last().copied().unwrap_or_default();
+
+    Ok(MonteCarloResult {
+        sample_trades: r_multiples.len(),
+        expectancy_r: r_multiples.iter().sum::<f64>() / r_multiples.len() as f64,
+        simulations: config.simulations,
+        trades: config.trades,
+        risk_per_trade,
+        starting_equity,
+        equity_bands,
+        final_equity,
+        max_drawdown: Percentiles::of(&mut drawdowns),
+        max_drawdown_pct: Percentiles::of(&mut drawdown_pcts),
+        risk_of_ruin: ruined as f64 / config.simulations as f64,
+    })
+}
+
+/// Small seedable generator; statistical quality is ample for resampling
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform index in 0..n
+    fn below(&mut self, n: usize) -> usize {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64 * n as f64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monte_carlo() {
+        let config = MonteCarloConfig { simulations: 2000, trades: 50, seed: Some(7), ..Default::default() };
+        assert!(simulate(&[1.0; 5], &config, 100.0, 10_000.0).is_err());
+
+        // Every trade wins 1R: no spread, no drawdown
+        let result = simulate(&[1.0; 10], &config, 100.0, 10_000.0).unwrap();
+        assert_eq!(result.equity_bands.len(), 51);
+        assert_eq!(result.final_equity.p5, 15_000.0);
+        assert_eq!(result.max_drawdown.p95, 0.0);
+        assert_eq!(result.risk_of_ruin, 0.0);
+
+        // Coin flips of +2R / -1R
+        let sample: Vec<f64> = (0..20).map(|i| if i % 2 == 0 { 2.0 } else { -1.0 }).collect();
+        let result = simulate(&sample, &config, 100.0, 10_000.0).unwrap();
+        assert_eq!(result.expectancy_r, 0.5);
+        let bands = &result.final_equity;
+        assert!(bands.p5 < bands.p25 && bands.p25 < bands.p50 && bands.p50 < bands.p75 && bands.p75 < bands.p95);
+        // Median near 10,000 + 50 × 0.5R × 100
+        assert!((bands.p50 - 12_500.0).abs() <= 600.0, "{}", bands.p50);
+        assert!(result.max_drawdown.p50 > 0.0);
+        assert_eq!(result, simulate(&sample, &config, 100.0, 10_000.0).unwrap());
+
+        // Risking a fifth of the account per trade ruins some sequences at a 50% threshold
+        let risky = simulate(&sample, &config, 2_000.0, 10_000.0).unwrap();
+        assert!(risky.risk_of_ruin > 0.0 && risky.risk_of_ruin < 1.0, "{}", risky.risk_of_ruin);
+    }
+}
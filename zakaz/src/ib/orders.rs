@@ -7,99 +7,105 @@ use crate::error::AppError;
 use crate::{err, inf};
 use super::types::OrderTemplate;
 
+/// JSON snapshot of order templates. The database is the source of truth;
+/// this format exists for exporting, backing up, and sharing templates.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct OrderTemplateStorage {
+pub struct OrderTemplateExport {
+    pub exported_at: chrono::DateTime<chrono::Utc>,
     pub templates: Vec<OrderTemplate>,
 }
 
-impl OrderTemplateStorage {
-    pub fn new() -> Self {
+impl OrderTemplateExport {
+    pub fn from_templates(templates: Vec<OrderTemplate>) -> Self {
         Self {
-            templates: Vec::new(),
+            exported_at: chrono::Utc::now(),
+            templates,
         }
     }
     
-    pub async fn load_from_file(path: &PathBuf) -> Result<Self, AppError> {
-        match fs::read_to_string(path).await {
-            Ok(content) => {
-                let storage: OrderTemplateStorage = serde_json::from_str(&content)
-                    .map_err(|e| AppError::Serialization(format!("Failed to parse templates: {}", e)))?;
-                inf!("Loaded {} order templates from file", storage.templates.len());
-                Ok(storage)
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                inf!("No existing order templates file found, creating new");
-                Ok(Self::new())
-            }
-            Err(e) => {
-                err!("Failed to read order templates file: {}", e);
-                Err(AppError::Io(e))
-            }
-        }
+    pub async fn read_from_file(path: &PathBuf) -> Result<Self, AppError> {
+        let content = fs::read_to_string(path).await
+            .map_err(|e| {
+                err!("Failed to read order template export: {}", e);
+                AppError::Io(e)
+            })?;
+        let export: OrderTemplateExport = serde_json::from_str(&content)
+            .map_err(|e| AppError::Serialization(format!("Failed to parse templates: {}", e)))?;
+        inf!("Read {} order templates from export file", export.templates.len());
+        Ok(export)
     }
     
-    pub async fn save_to_file(&self, path: &PathBuf) -> Result<(), AppError> {
+    pub async fn write_to_file(&self, path: &PathBuf) -> Result<(), AppError> {
         let json = serde_json::to_string_pretty(self)
             .map_err(|e| AppError::Serialization(format!("Failed to serialize templates: {}", e)))?;
         
         // Ensure directory exists
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent).await
-                .map_err(|e| AppError::Io(e))?;
+                .map_err(AppError::Io)?;
         }
         
         fs::write(path, json).await
-            .map_err(|e| AppError::Io(e))?;
+            .map_err(AppError::Io)?;
         
-        inf!("Saved {} order templates to file", self.templates.len());
+        inf!("Exported {} order templates to {}", self.templates.len(), path.display());
         Ok(())
     }
+}
+
+// Helper functions for order calculations
+pub mod calculations {
+    use super::*;
+    use crate::ib::types::OrderSide;
     
-    pub fn add_template(&mut self, template: OrderTemplate) {
-        self.templates.push(template);
+    /// What the broker charges per order; a round trip pays it twice
+    #[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+    pub enum CommissionModel {
+        #[default]
+        None,
+        // IB fixed pricing is 0.005 per share, 1.00 minimum, capped at 1% of trade value
+        PerShare { rate: f64, minimum: f64, maximum_pct: f64 }, // maximum_pct 0 = no cap
+        PerOrder { amount: f64 },
     }
     
-    pub fn update_template(&mut self, template: OrderTemplate) -> Result<(), AppError> {
-        if let Some(existing) = self.templates.iter_mut().find(|t| t.id == template.id) {
-            *existing = template;
-            Ok(())
-        } else {
-            Err(AppError::NotFound(format!("Template {} not found", template.id)))
+    impl CommissionModel {
+        /// Commission for one order of `quantity` shares at `price`
+        pub fn commission(&self, quantity: f64, price: f64) -> f64 {
+            if quantity <= 0.0 {
+                return 0.0;
+            }
+            match *self {
+                CommissionModel::None => 0.0,
+                CommissionModel::PerShare { rate, minimum, maximum_pct } => {
+                    let commission = (quantity * rate).max(minimum);
+                    if maximum_pct > 0.0 {
+                        commission.min(quantity * price * maximum_pct / 100.0)
+                    } else {
+                        commission
+                    }
+                }
+                CommissionModel::PerOrder { amount } => amount,
+            }
         }
-    }
-    
-    pub fn remove_template(&mut self, template_id: &str) -> Result<(), AppError> {
-        let initial_len = self.templates.len();
-        self.templates.retain(|t| t.id != template_id);
         
-        if self.templates.len() < initial_len {
-            Ok(())
-        } else {
-            Err(AppError::NotFound(format!("Template {} not found", template_id)))
+        /// Entry plus exit commission, with the exit filled at `exit_price`
+        pub fn round_trip(&self, quantity: f64, entry_price: f64, exit_price: f64) -> f64 {
+            self.commission(quantity, entry_price) + self.commission(quantity, exit_price)
         }
     }
     
-    pub fn get_template(&self, template_id: &str) -> Option<&OrderTemplate> {
-        self.templates.iter().find(|t| t.id == template_id)
-    }
-    
-    pub fn get_all_templates(&self) -> &[OrderTemplate] {
-        &self.templates
-    }
-}
-
-// Helper functions for order calculations
-pub mod calculations {
-    use super::*;
-    
-    pub fn calculate_risk(template: &OrderTemplate) -> f64 {
+    /// Loss if stopped out, commissions on both legs included
+    pub fn calculate_risk(template: &OrderTemplate, commissions: &CommissionModel) -> f64 {
         let price_diff = (template.limit_price - template.stop_price).abs();
         price_diff * template.quantity
+            + commissions.round_trip(template.quantity, template.limit_price, template.stop_price)
     }
     
-    pub fn calculate_reward_risk_ratio(template: &OrderTemplate, target_price: f64) -> f64 {
-        let risk = (template.limit_price - template.stop_price).abs();
-        let reward = (target_price - template.limit_price).abs();
+    /// Net reward over net risk: commissions shrink the reward and add to the risk
+    pub fn calculate_reward_risk_ratio(template: &OrderTemplate, target_price: f64, commissions: &CommissionModel) -> f64 {
+        let risk = calculate_risk(template, commissions);
+        let reward = (target_price - template.limit_price).abs() * template.quantity
+            - commissions.round_trip(template.quantity, template.limit_price, target_price);
         
         if risk > 0.0 {
             reward / risk
@@ -108,9 +114,42 @@ pub mod calculations {
         }
     }
     
+    /// Exit price at which the trade nets zero after commissions on both legs
+    pub fn calculate_breakeven_price(template: &OrderTemplate, commissions: &CommissionModel) -> f64 {
+        if template.quantity <= 0.0 {
+            return template.limit_price;
+        }
+        // Commission is priced at the entry for both legs; near breakeven the exit is close enough
+        let per_share = commissions.round_trip(template.quantity, template.limit_price, template.limit_price) / template.quantity;
+        match template.side {
+            OrderSide::Long => template.limit_price + per_share,
+            OrderSide::Short => template.limit_price - per_share,
+        }
+    }
+    
     pub fn calculate_position_value(template: &OrderTemplate) -> f64 {
         template.limit_price * template.quantity
     }
+    
+    /// A template's figures with commissions applied
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct TradeCosts {
+        pub commission: f64, // Round trip, exit at the stop
+        pub risk: f64,
+        pub breakeven_price: f64,
+        pub reward_risk_ratio: Option<f64>, // When a target price is given
+    }
+    
+    impl TradeCosts {
+        pub fn for_template(template: &OrderTemplate, commissions: &CommissionModel, target_price: Option<f64>) -> Self {
+            Self {
+                commission: commissions.round_trip(template.quantity, template.limit_price, template.stop_price),
+                risk: calculate_risk(template, commissions),
+                breakeven_price: calculate_breakeven_price(template, commissions),
+                reward_risk_ratio: target_price.map(|target| calculate_reward_risk_ratio(template, target, commissions)),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -119,12 +158,11 @@ mod tests {
     use tempfile::TempDir;
     
     #[tokio::test]
-    async fn test_order_template_storage() {
+    async fn test_order_template_export() {
         let temp_dir = TempDir::new().unwrap();
         let file_path = temp_dir.path().join("templates.json");
         
-        // Create and save templates
-        let mut storage = OrderTemplateStorage::new();
+        // Create and export templates
         let template = OrderTemplate::new(
             "Test Template".to_string(),
             "AAPL".to_string(),
@@ -136,13 +174,13 @@ mod tests {
             crate::ib::types::TradingModel::default(),
         );
         
-        storage.add_template(template.clone());
-        storage.save_to_file(&file_path).await.unwrap();
+        let export = OrderTemplateExport::from_templates(vec![template.clone()]);
+        export.write_to_file(&file_path).await.unwrap();
         
-        // Load and verify
-        let loaded_storage = OrderTemplateStorage::load_from_file(&file_path).await.unwrap();
-        assert_eq!(loaded_storage.templates.len(), 1);
-        assert_eq!(loaded_storage.templates[0].id, template.id);
+        // Read back and verify
+        let loaded = OrderTemplateExport::read_from_file(&file_path).await.unwrap();
+        assert_eq!(loaded.templates.len(), 1);
+        assert_eq!(loaded.templates[0].id, template.id);
     }
     
     #[test]
@@ -158,10 +196,49 @@ mod tests {
             crate::ib::types::TradingModel::default(),
         );
         
-        assert_eq!(calculations::calculate_risk(&template), 500.0); // (150-145) * 100
+        let no_commission = calculations::CommissionModel::None;
+        assert_eq!(calculations::calculate_risk(&template, &no_commission), 500.0); // (150-145) * 100
         assert_eq!(calculations::calculate_position_value(&template), 15000.0); // 150 * 100
         
-        let rr_ratio = calculations::calculate_reward_risk_ratio(&template, 160.0);
+        let rr_ratio = calculations::calculate_reward_risk_ratio(&template, 160.0, &no_commission);
         assert_eq!(rr_ratio, 2.0); // reward: 10, risk: 5
+        assert_eq!(calculations::calculate_breakeven_price(&template, &no_commission), 150.0);
+    }
+    
+    #[test]
+    fn test_commission_aware_calculations() {
+        use calculations::*;
+        
+        let per_share = CommissionModel::PerShare { rate: 0.005, minimum: 1.0, maximum_pct: 1.0 };
+        // The minimum applies to small orders, the rate to large ones, the cap to cheap shares
+        assert_eq!(per_share.commission(100.0, 150.0), 1.0);
+        assert_eq!(per_share.commission(1000.0, 150.0), 5.0);
+        assert!((per_share.commission(1000.0, 0.05) - 0.5).abs() < 1e-9);
+        assert_eq!(CommissionModel::PerOrder { amount: 2.5 }.commission(10.0, 150.0), 2.5);
+        
+        // 10 shares risking 0.50 each: 2.00 of commission is 40% on top of the 5.00 price risk
+        let mut template = OrderTemplate::new(
+            "Small".to_string(),
+            "AAPL".to_string(),
+            crate::ib::types::OrderSide::Long,
+            10.0,
+            150.0,
+            149.5,
+            crate::ib::types::TimeInForce::Day,
+            crate::ib::types::TradingModel::default(),
+        );
+        assert_eq!(calculate_risk(&template, &per_share), 7.0);
+        // Reward 10.00 gross, 8.00 net against 7.00 of risk
+        assert!((calculate_reward_risk_ratio(&template, 151.0, &per_share) - 8.0 / 7.0).abs() < 1e-9);
+        assert!((calculate_breakeven_price(&template, &per_share) - 150.2).abs() < 1e-9);
+        
+        template.side = crate::ib::types::OrderSide::Short;
+        template.stop_price = 150.5;
+        assert!((calculate_breakeven_price(&template, &per_share) - 149.8).abs() < 1e-9);
+        
+        let costs = TradeCosts::for_template(&template, &per_share, None);
+        assert_eq!(costs.commission, 2.0);
+        assert_eq!(costs.risk, 7.0);
+        assert_eq!(costs.reward_risk_ratio, None);
     }
 }
\ No newline at end of file
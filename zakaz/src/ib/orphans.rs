@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+
+use super::types::{
+    EntryType, OrderRef, OrderSide, OrderTemplate, OrderTemplateStatus, TimeInForce, TradingModel,
+};
+
+/// The parts of an IB open order needed to recognise and adopt it
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenOrderInfo {
+    pub order_id: i32,
+    pub parent_id: i32, // 0 for a top-level order
+    pub symbol: String,
+    pub currency: String,
+    pub side: OrderSide,
+    pub order_type: String,
+    pub quantity: f64,
+    pub limit_price: Option<f64>,
+    pub aux_price: Option<f64>, // Stop trigger
+    pub time_in_force: String,
+    pub order_ref: String,
+}
+
+impl OpenOrderInfo {
+    fn is_stop(&self) -> bool {
+        matches!(self.order_type.as_str(), "STP" | "STP LMT" | "TRAIL")
+    }
+
+    /// Trigger for stops, limit for everything else
+    fn price(&self) -> Option<f64> {
+        if self.is_stop() {
+            self.aux_price.or(self.limit_price)
+        } else {
+            self.limit_price.or(self.aux_price)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionInfo {
+    pub symbol: String,
+    pub currency: String,
+    pub quantity: f64, // Signed: negative is short
+    pub average_cost: f64,
+}
+
+pub fn order_template_id(order_id: i32) -> String {
+    format!("ib-order-{}", order_id)
+}
+
+pub fn position_template_id(symbol: &str) -> String {
+    format!("ib-position-{}", symbol)
+}
+
+/// Read-only templates for orders and positions zakaz didn't create.
+/// Ids are derived from the IB order id or symbol, so repeated adoption is stable.
+pub fn adopt(orders: &[OpenOrderInfo], positions: &[PositionInfo], managed: &[OrderTemplate]) -> Vec<OrderTemplate> {
+    let managed_order_ids: HashSet<i32> = managed.iter()
+        .flat_map(|t| t.parent_order_id.into_iter().chain(t.stop_order_id).chain(t.tranche_order_ids.iter().copied()))
+        .collect();
+    let orphans: Vec<&OpenOrderInfo> = orders.iter()
+        .filter(|o| OrderRef::parse(&o.order_ref).is_none() && !managed_order_ids.contains(&o.order_id))
+        .collect();
+
+    let mut adopted = Vec::new();
+    let mut used_stops = HashSet::new();
+
+    // Positions first, so standalone stops protecting them aren't mistaken for entries
+    for position in positions.iter().filter(|p| p.quantity != 0.0) {
+        let side = if position.quantity > 0.0 { OrderSide::Long } else { OrderSide::Short };
+        let protective_stop = orphans.iter()
+            .find(|o| o.parent_id == 0 && o.symbol == position.symbol && o.is_stop() && o.side != side);
+        if let Some(stop) = protective_stop {
+            used_stops.insert(stop.order_id);
+        }
+
+        let already_managed = managed.iter().any(|t| t.symbol == position.symbol && t.has_fills());
+        if already_managed {
+            continue;
+        }
+
+        let quantity = position.quantity.abs();
+        let mut template = read_only_template(
+            position_template_id(&position.symbol),
+            format!("{} position (IB)", position.symbol),
+            &position.symbol,
+            side,
+            quantity,
+            position.average_cost,
+            protective_stop.and_then(|o| o.price()),
+            TimeInForce::GTC,
+        );
+        template.currency = position.currency.clone();
+        template.status = OrderTemplateStatus::Filled;
+        template.filled_quantity = quantity;
+        template.avg_fill_price = Some(position.average_cost);
+        template.stop_order_id = protective_stop.map(|o| o.order_id);
+        adopted.push(template);
+    }
+
+    for entry in orphans.iter().filter(|o| o.parent_id == 0 && !used_stops.contains(&o.order_id)) {
+        let Some(price) = entry.price() else {
+            continue; // Market orders have no price to plan around
+        };
+        let stop = orphans.iter()
+            .find(|o| o.parent_id == entry.order_id && o.is_stop());
+
+        let mut template = read_only_template(
+            order_template_id(entry.order_id),
+            format!("{} {} (IB)", entry.symbol, entry.order_type),
+            &entry.symbol,
+            entry.side,
+            entry.quantity,
+            price,
+            stop.and_then(|o| o.price()),
+            TimeInForce::from_code(&entry.time_in_force, None).unwrap_or(TimeInForce::GTC),
+        );
+        let limit_offset = match (entry.limit_price, entry.aux_price) {
+            (Some(limit), Some(trigger)) => Some((limit - trigger).abs()),
+            _ => None,
+        };
+        template.currency = entry.currency.clone();
+        template.entry_type = EntryType::from_ib_order_type(&entry.order_type, limit_offset).unwrap_or_default();
+        template.status = OrderTemplateStatus::Active;
+        template.parent_order_id = Some(entry.order_id);
+        template.stop_order_id = stop.map(|o| o.order_id);
+        adopted.push(template);
+    }
+
+    adopted
+}
+
+#[allow(clippy::too_many_arguments)]
+fn read_only_template(
+    id: String,
+    name: String,
+    symbol: &str,
+    side: OrderSide,
+    quantity: f64,
+    entry_price: f64,
+    stop_price: Option<f64>,
+    time_in_force: TimeInForce,
+) -> OrderTemplate {
+    // Without a stop there is no defined risk; placing it at the entry keeps risk figures at zero
+    let mut template = OrderTemplate::new(
+        name,
+        symbol.to_string(),
+        side,
+        quantity,
+        entry_price,
+        stop_price.unwrap_or(entry_price),
+        time_in_force,
+        TradingModel::default(),
+    );
+    template.id = id;
+    template.is_read_only = true;
+    template.expires_at = None;
+    template.risk_per_trade = (entry_price - template.stop_price).abs() * quantity;
+    template.notes = Some(match stop_price {
+        Some(_) => "Adopted from IB: not placed by zakaz".to_string(),
+        None => "Adopted from IB: not placed by zakaz, no protective stop found".to_string(),
+    });
+    template
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(order_id: i32, parent_id: i32, side: OrderSide, order_type: &str, price: f64) -> OpenOrderInfo {
+        let is_stop = order_type == "STP";
+        OpenOrderInfo {
+            order_id,
+            parent_id,
+            symbol: "AAPL".to_string(),
+            currency: "USD".to_string(),
+            side,
+            order_type: order_type.to_string(),
+            quantity: 100.0,
+            limit_price: (!is_stop).then_some(price),
+            aux_price: is_stop.then_some(price),
+            time_in_force: "DAY".to_string(),
+            order_ref: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_adopt_orders_and_positions() {
+        let mut ours = order(1, 0, OrderSide::Long, "LMT", 150.0);
+        ours.order_ref = "zakaz:abc:entry:Breakout".to_string();
+        let orders = vec![
+            ours,
+            // Manual bracket placed in TWS
+            order(500, 0, OrderSide::Long, "LMT", 150.0),
+            order(501, 500, OrderSide::Short, "STP", 148.0),
+            // Standalone stop protecting a manual position
+            order(600, 0, OrderSide::Short, "STP", 95.0),
+        ];
+        let positions = vec![PositionInfo { symbol: "AAPL".to_string(), currency: "USD".to_string(), quantity: 50.0, average_cost: 100.0 }];
+
+        let adopted = adopt(&orders, &positions, &[]);
+        assert_eq!(adopted.len(), 2);
+        assert!(adopted.iter().all(|t| t.is_read_only));
+
+        let position = adopted.iter().find(|t| t.id == position_template_id("AAPL")).unwrap();
+        assert_eq!(position.status, OrderTemplateStatus::Filled);
+        assert_eq!(position.filled_quantity, 50.0);
+        assert_eq!(position.stop_price, 95.0);
+        assert_eq!(position.stop_order_id, Some(600));
+
+        let bracket = adopted.iter().find(|t| t.id == order_template_id(500)).unwrap();
+        assert_eq!(bracket.status, OrderTemplateStatus::Active);
+        assert_eq!(bracket.side, OrderSide::Long);
+        assert_eq!(bracket.limit_price, 150.0);
+        assert_eq!(bracket.stop_price, 148.0);
+        assert_eq!(bracket.parent_order_id, Some(500));
+        assert_eq!(bracket.stop_order_id, Some(501));
+        assert_eq!(bracket.time_in_force, TimeInForce::Day);
+        assert_eq!(bracket.risk_per_trade, 200.0);
+
+        // Once claimed, the bracket and a managed fill on the symbol are no longer orphans
+        let mut claimed = bracket.clone();
+        claimed.is_read_only = false;
+        claimed.filled_quantity = 100.0;
+        assert!(adopt(&orders, &positions, &[claimed]).is_empty());
+    }
+}
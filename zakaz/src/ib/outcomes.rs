@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use super::types::{OrderSide, OrderTemplate, TradingModel};
+
+/// A closed trade's result, measured against the risk planned at entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeOutcome {
+    pub template_id: String,
+    pub symbol: String,
+    pub model: TradingModel,
+    pub realized_pnl: f64,
+    pub initial_risk: f64, // Planned risk scaled to the shares actually filled
+    pub r_multiple: f64,
+    pub closed_at: DateTime<Utc>,
+}
+
+impl TradeOutcome {
+    /// The outcome once `exits` (quantity, average price) cover every filled entry share.
+    /// None while the position is open or when the template has no planned risk to measure against.
+    pub fn from_exits(template: &OrderTemplate, exits: &[(f64, f64)], closed_at: DateTime<Utc>) -> Option<Self> {
+        let entry_price = template.avg_fill_price?;
+        if !template.is_flat_after(exits) || template.quantity <= 0.0 {
+            return None;
+        }
+
+        let direction = match template.side {
+            OrderSide::Long => 1.0,
+            OrderSide::Short => -1.0,
+        };
+        let realized_pnl = exits.iter()
+            .map(|(quantity, price)| quantity * (price - entry_price) * direction)
+            .sum::<f64>();
+        let initial_risk = template.risk_per_trade * template.filled_quantity / template.quantity;
+        if initial_risk <= 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            template_id: template.id.clone(),
+            symbol: template.symbol.clone(),
+            model: template.model,
+            realized_pnl,
+            initial_risk,
+            r_multiple: realized_pnl / initial_risk,
+            closed_at,
+        })
+    }
+}
+
+/// Summary of a run of closed trades in R
+#[derive(Debug, Clone, PartialEq)]
+pub struct RStats {
+    pub trades: usize,
+    pub total_r: f64,
+    pub average_r: f64, // Expectancy per trade
+    pub median_r: f64,
+    pub win_rate: f64,
+    pub best_r: f64,
+    pub worst_r: f64,
+    pub distribution: BTreeMap<i64, usize>, // Trades per whole-R bucket: key -1 holds [-1R, 0R)
+}
+
+impl RStats {
+    pub fn from_r_multiples(r_multiples: &[f64]) -> Option<Self> {
+        if r_multiples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = r_multiples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let trades = sorted.len();
+        let median_r = if trades.is_multiple_of(2) {
+            (sorted[trades / 2 - 1] + sorted[trades / 2]) / 2.0
+        } else {
+            sorted[trades / 2]
+        };
+
+        let mut distribution = BTreeMap::new();
+        for r in &sorted {
+            *distribution.entry(r.floor() as i64).or_insert(0) += 1;
+        }
+
+        let total_r: f64 = sorted.iter().sum();
+        Some(Self {
+            trades,
+            total_r,
+            average_r: total_r / trades as f64,
+            median_r,
+            win_rate: sorted.iter().filter(|r| **r > 0.0).count() as f64 / trades as f64,
+            best_r: sorted[trades - 1],
+            worst_r: sorted[0],
+            distribution,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ib::types::{OrderTemplateStatus, TimeInForce};
+
+    #[test]
+    fn test_outcome_from_exits() {
+        let mut template = OrderTemplate::new(
+            "AAPL short".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Short,
+            100.0,
+            150.0,
+            152.0,
+            TimeInForce::GTC,
+            TradingModel::FalseBreakout,
+        );
+        template.risk_per_trade = 200.0;
+        template.status = OrderTemplateStatus::Filled;
+        template.filled_quantity = 50.0;
+        template.avg_fill_price = Some(150.0);
+        let now = Utc::now();
+
+        // Half the filled shares still open
+        assert!(TradeOutcome::from_exits(&template, &[(25.0, 146.0)], now).is_none());
+
+        // 25 covered at 146 (+100), 25 stopped at 152 (-50), against 100 of risk on half the plan
+        let outcome = TradeOutcome::from_exits(&template, &[(25.0, 146.0), (25.0, 152.0)], now).unwrap();
+        assert_eq!(outcome.realized_pnl, 50.0);
+        assert_eq!(outcome.initial_risk, 100.0);
+        assert_eq!(outcome.r_multiple, 0.5);
+        assert_eq!(outcome.model, TradingModel::FalseBreakout);
+    }
+
+    #[test]
+    fn test_r_stats() {
+        let stats = RStats::from_r_multiples(&[2.0, -1.0, 3.5, -0.5, -1.0]).unwrap();
+        assert_eq!(stats.trades, 5);
+        assert_eq!(stats.total_r, 3.0);
+        assert_eq!(stats.average_r, 0.6);
+        assert_eq!(stats.median_r, -0.5);
+        assert_eq!(stats.win_rate, 0.4);
+        assert_eq!(stats.best_r, 3.5);
+        assert_eq!(stats.worst_r, -1.0);
+        assert_eq!(stats.distribution, BTreeMap::from([(-1, 3), (2, 1), (3, 1)]));
+
+        assert!(RStats::from_r_multiples(&[]).is_none());
+    }
+}
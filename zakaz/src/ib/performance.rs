@@ -0,0 +1,213 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::market_hours;
+use super::outcomes::TradeOutcome;
+use super::types::TradingModel;
+
+/// Closed trades to include, by close time; open ends are unbounded
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PerformanceRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl PerformanceRange {
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| at >= from) && self.to.is_none_or(|to| at <= to)
+    }
+}
+
+/// Journal statistics for a set of closed trades
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PerformanceSummary {
+    pub trades: usize,
+    pub win_rate: f64,
+    pub average_r: f64,             // Expectancy in R
+    pub expectancy: f64,            // Average net P&L per trade
+    pub profit_factor: Option<f64>, // Gross profit over gross loss; None without losers
+    pub net_pnl: f64,
+    pub gross_profit: f64,
+    pub gross_loss: f64,            // Positive
+    pub average_win: f64,
+    pub average_loss: f64,          // Positive
+    pub total_r: f64,
+}
+
+impl PerformanceSummary {
+    pub fn from_outcomes<'a>(outcomes: impl IntoIterator<Item = &'a TradeOutcome>) -> Self {
+        let mut summary = Self::default();
+        let (mut wins, mut losses) = (0usize, 0usize);
+        for outcome in outcomes {
+            summary.trades += 1;
+            summary.net_pnl += outcome.realized_pnl;
+            summary.total_r += outcome.r_multiple;
+            if outcome.realized_pnl > 0.0 {
+                wins += 1;
+                summary.gross_profit += outcome.realized_pnl;
+            } else if outcome.realized_pnl < 0.0 {
+                losses += 1;
+                summary.gross_loss -= outcome.realized_pnl;
+            }
+        }
+        if summary.trades == 0 {
+            return summary;
+        }
+
+        let trades = summary.trades as f64;
+        summary.win_rate = wins as f64 / trades;
+        summary.average_r = summary.total_r / trades;
+        summary.expectancy = summary.net_pnl / trades;
+        summary.profit_factor = (summary.gross_loss > 0.0).then(|| summary.gross_profit / summary.gross_loss);
+        summary.average_win = if wins > 0 { summary.gross_profit / wins as f64 } else { 0.0 };
+        summary.average_loss = if losses > 0 { summary.gross_loss / losses as f64 } else { 0.0 };
+        summary
+    }
+}
+
+/// Account P&L from closed trades at the end of a trading day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EquityPoint {
+    pub date: NaiveDate,
+    pub pnl: f64,        // Closed that day
+    pub cumulative: f64,
+    pub drawdown: f64,   // Below the highest cumulative P&L so far
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PerformanceStats {
+    pub range: PerformanceRange,
+    pub overall: PerformanceSummary,
+    pub by_model: Vec<(TradingModel, PerformanceSummary)>,
+    pub by_symbol: Vec<(String, PerformanceSummary)>, // Alphabetical
+    pub equity_curve: Vec<EquityPoint>,               // Every trading day from the first close to the last
+    pub max_drawdown: f64,
+}
+
+impl PerformanceStats {
+    /// Statistics over the outcomes closed within `range`
+    pub fn new(outcomes: &[TradeOutcome], range: PerformanceRange) -> Self {
+        let mut outcomes: Vec<&TradeOutcome> = outcomes.iter().filter(|o| range.contains(o.closed_at)).collect();
+        outcomes.sort_by_key(|o| o.closed_at);
+
+        let mut models: Vec<TradingModel> = Vec::new();
+        let mut symbols: Vec<String> = Vec::new();
+        for outcome in &outcomes {
+            if !models.contains(&outcome.model) {
+                models.push(outcome.model);
+            }
+            if !symbols.contains(&outcome.symbol) {
+                symbols.push(outcome.symbol.clone());
+            }
+        }
+        symbols.sort();
+
+        let by_model = models.into_iter()
+            .map(|model| (model, PerformanceSummary::from_outcomes(outcomes.iter().copied().filter(|o| o.model == model))))
+            .collect();
+        let by_symbol = symbols.into_iter()
+            .map(|symbol| {
+                let summary = PerformanceSummary::from_outcomes(outcomes.iter().copied().filter(|o| o.symbol == symbol));
+                (symbol, summary)
+            })
+            .collect();
+        let equity_curve = equity_curve(&outcomes);
+        let max_drawdown = equity_curve.iter().map(|p| p.drawdown).fold(0.0, f64::max);
+
+        Self {
+            range,
+            overall: PerformanceSummary::from_outcomes(outcomes.iter().copied()),
+            by_model,
+            by_symbol,
+            equity_curve,
+            max_drawdown,
+        }
+    }
+}
+
+/// Daily closed P&L and its running total over `outcomes`, sorted by close time
+fn equity_curve(outcomes: &[&TradeOutcome]) -> Vec<EquityPoint> {
+    let (Some(first), Some(last)) = (outcomes.first(), outcomes.last()) else {
+        return Vec::new();
+    };
+    let last_date = market_hours::session_date(last.closed_at);
+    let mut date = market_hours::session_date(first.closed_at);
+    let mut remaining = outcomes.iter().peekable();
+    let (mut cumulative, mut peak) = (0.0_f64, 0.0_f64);
+    let mut curve = Vec::new();
+
+    loop {
+        let mut pnl = 0.0;
+        while let Some(outcome) = remaining.next_if(|o| market_hours::session_date(o.closed_at) <= date) {
+            pnl += outcome.realized_pnl;
+        }
+        // Trades closed on a weekend or holiday still count on their own day
+        if market_hours::is_trading_day(date) || pnl != 0.0 {
+            cumulative += pnl;
+            peak = peak.max(cumulative);
+            curve.push(EquityPoint { date, pnl, cumulative, drawdown: peak - cumulative });
+        }
+        match date.succ_opt() {
+            Some(next) if date < last_date => date = next,
+            _ => break,
+        }
+    }
+    curve
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn outcome(symbol: &str, model: TradingModel, day: u32, pnl: f64) -> TradeOutcome {
+        TradeOutcome {
+            template_id: format!("{}-{}", symbol, day),
+            symbol: symbol.to_string(),
+            model,
+            realized_pnl: pnl,
+            initial_risk: 100.0,
+            r_multiple: pnl / 100.0,
+            // 15:00 New York in July 2025
+            closed_at: Utc.with_ymd_and_hms(2025, 7, day, 19, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_performance_stats() {
+        let outcomes = vec![
+            outcome("MSFT", TradingModel::Breakout, 21, 200.0),
+            outcome("AAPL", TradingModel::Bounce, 22, -100.0),
+            outcome("MSFT", TradingModel::Breakout, 22, -50.0),
+            outcome("AAPL", TradingModel::Breakout, 28, 300.0),
+        ];
+        let stats = PerformanceStats::new(&outcomes, PerformanceRange::default());
+        assert_eq!(stats.overall.trades, 4);
+        assert_eq!(stats.overall.win_rate, 0.5);
+        assert_eq!(stats.overall.net_pnl, 350.0);
+        assert_eq!(stats.overall.expectancy, 87.5);
+        assert_eq!(stats.overall.average_r, 0.875);
+        assert_eq!(stats.overall.profit_factor, Some(500.0 / 150.0));
+        assert_eq!((stats.overall.average_win, stats.overall.average_loss), (250.0, 75.0));
+
+        let (model, breakout) = &stats.by_model[0];
+        assert_eq!((*model, breakout.trades, breakout.net_pnl), (TradingModel::Breakout, 3, 450.0));
+        assert_eq!(stats.by_symbol.iter().map(|(s, _)| s.as_str()).collect::<Vec<_>>(), ["AAPL", "MSFT"]);
+        assert_eq!(stats.by_model[1].1.profit_factor, Some(0.0));
+
+        // Monday 21st to Monday 28th: six trading days, the weekend skipped
+        assert_eq!(stats.equity_curve.len(), 6);
+        assert_eq!(stats.equity_curve[1].pnl, -150.0);
+        assert_eq!(stats.equity_curve[1].drawdown, 150.0);
+        assert_eq!(stats.equity_curve[4].cumulative, 50.0);
+        assert_eq!(stats.equity_curve[5].cumulative, 350.0);
+        assert_eq!(stats.max_drawdown, 150.0);
+
+        // A range keeps only the trades closed within it
+        let range = PerformanceRange { from: Some(Utc.with_ymd_and_hms(2025, 7, 22, 0, 0, 0).unwrap()), to: None };
+        let stats = PerformanceStats::new(&outcomes, range);
+        assert_eq!(stats.overall.trades, 3);
+        assert_eq!(stats.overall.profit_factor, Some(2.0));
+
+        assert_eq!(PerformanceStats::new(&[], range).equity_curve, Vec::new());
+    }
+}
@@ -41,57 +41,310 @@ pub fn calculate_position_size(
     Ok(shares)
 }
 
-/// Validate stop loss placement relative to ATR
-/// Stop loss must be between 0.01 below/above entry and 15% of ATR
-pub fn validate_stop_loss(
-    entry_price: f64,
-    stop_price: f64,
-    side: OrderSide,
+/// Size so the position's typical daily move (ATR x multiple) costs `risk_per_trade`
+pub fn calculate_volatility_normalized_size(
+    risk_per_trade: f64,
     atr: f64,
-) -> Result<(), String> {
-    let min_distance = 0.01;
-    let max_atr_percentage = 0.15; // 15% of ATR
-    let max_distance = atr * max_atr_percentage;
+    atr_multiple: f64,
+) -> Result<i64, String> {
+    let volatility = atr * atr_multiple;
+    if volatility <= 0.0 {
+        return Err("ATR must be positive for volatility-normalized sizing".to_string());
+    }
     
-    let stop_distance = match side {
-        OrderSide::Long => {
-            if stop_price >= entry_price {
-                return Err("For long orders, stop price must be below entry price".to_string());
+    let shares = (risk_per_trade / volatility).floor() as i64;
+    if shares <= 0 {
+        return Err("Calculated position size is too small (less than 1 share)".to_string());
+    }
+    
+    Ok(shares)
+}
+
+/// Closed trades needed before Kelly sizing trusts the statistics
+pub const MIN_KELLY_TRADES: usize = 20;
+
+/// Win rate and payoff from closed trades' R-multiples
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TradeStats {
+    pub trades: usize,
+    pub win_rate: f64,     // Fraction of trades with a positive R
+    pub payoff_ratio: f64, // Average winning R over average losing R
+}
+
+impl TradeStats {
+    /// None without at least one win and one loss, as the payoff ratio is undefined
+    pub fn from_r_multiples(r_multiples: &[f64]) -> Option<Self> {
+        let wins: Vec<f64> = r_multiples.iter().copied().filter(|r| *r > 0.0).collect();
+        let losses: Vec<f64> = r_multiples.iter().copied().filter(|r| *r <= 0.0).collect();
+        let avg_loss = -losses.iter().sum::<f64>() / losses.len() as f64;
+        if wins.is_empty() || losses.is_empty() || avg_loss <= 0.0 {
+            return None;
+        }
+        
+        let avg_win = wins.iter().sum::<f64>() / wins.len() as f64;
+        Some(Self {
+            trades: r_multiples.len(),
+            win_rate: wins.len() as f64 / r_multiples.len() as f64,
+            payoff_ratio: avg_win / avg_loss,
+        })
+    }
+    
+    /// Kelly-optimal fraction of equity to risk; zero when the edge is negative
+    pub fn kelly_fraction(&self) -> f64 {
+        (self.win_rate - (1.0 - self.win_rate) / self.payoff_ratio).max(0.0)
+    }
+}
+
+/// Dollar risk for fractional Kelly: `fraction` of the Kelly share of equity
+pub fn calculate_kelly_risk(equity: f64, stats: &TradeStats, fraction: f64) -> Result<f64, String> {
+    if stats.trades < MIN_KELLY_TRADES {
+        return Err(format!(
+            "Kelly sizing needs {} closed trades in the journal, found {}",
+            MIN_KELLY_TRADES, stats.trades
+        ));
+    }
+    
+    let kelly = stats.kelly_fraction();
+    if kelly <= 0.0 {
+        return Err("Journal statistics show no edge; Kelly sizing would risk nothing".to_string());
+    }
+    Ok(equity * kelly * fraction)
+}
+
+/// What the account can fund, from the IB account summary
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccountCapacity {
+    pub buying_power: f64,
+    pub available_funds: f64,    // Equity left after existing initial margin
+    pub initial_margin_rate: f64, // Share of notional held as initial margin (1.0 = cash)
+}
+
+/// Why a computed size was cut back
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeReduction {
+    BuyingPower { from: i64, to: i64, buying_power: f64 },
+    Margin { from: i64, to: i64, available_funds: f64, initial_margin_rate: f64 },
+    BoardLot { from: i64, to: i64, lot_size: i64 },
+}
+
+impl std::fmt::Display for SizeReduction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SizeReduction::BuyingPower { from, to, buying_power } => {
+                write!(f, "Reduced {} -> {} shares to fit ${:.2} buying power", from, to, buying_power)
+            }
+            SizeReduction::Margin { from, to, available_funds, initial_margin_rate } => write!(
+                f,
+                "Reduced {} -> {} shares to fit ${:.2} available funds at {:.0}% initial margin",
+                from, to, available_funds, initial_margin_rate * 100.0
+            ),
+            SizeReduction::BoardLot { from, to, lot_size } => {
+                write!(f, "Rounded {} -> {} shares to a {}-share lot", from, to, lot_size)
             }
-            entry_price - stop_price
         }
-        OrderSide::Short => {
-            if stop_price <= entry_price {
-                return Err("For short orders, stop price must be above entry price".to_string());
+    }
+}
+
+/// A position size after account checks, with the reasons it shrank
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizingResult {
+    pub requested: i64,
+    pub shares: i64,
+    pub reductions: Vec<SizeReduction>,
+    pub account_checked: bool, // False when no account summary was available
+}
+
+impl SizingResult {
+    pub fn unchecked(shares: i64) -> Self {
+        Self { requested: shares, shares, reductions: Vec::new(), account_checked: false }
+    }
+    
+    pub fn was_reduced(&self) -> bool {
+        !self.reductions.is_empty()
+    }
+}
+
+/// Cut `shares` back to what buying power and margin allow, then round down to `lot_size`
+pub fn fit_to_account(
+    shares: i64,
+    entry_price: f64,
+    capacity: &AccountCapacity,
+    lot_size: i64,
+) -> Result<SizingResult, String> {
+    if entry_price <= 0.0 {
+        return Err("Entry price must be positive to check affordability".to_string());
+    }
+    if capacity.initial_margin_rate <= 0.0 {
+        return Err("Initial margin rate must be positive".to_string());
+    }
+    
+    let mut result = SizingResult { requested: shares, shares, reductions: Vec::new(), account_checked: true };
+    
+    let by_buying_power = (capacity.buying_power.max(0.0) / entry_price).floor() as i64;
+    if result.shares > by_buying_power {
+        result.reductions.push(SizeReduction::BuyingPower {
+            from: result.shares,
+            to: by_buying_power,
+            buying_power: capacity.buying_power,
+        });
+        result.shares = by_buying_power;
+    }
+    
+    let by_margin = (capacity.available_funds.max(0.0) / (entry_price * capacity.initial_margin_rate)).floor() as i64;
+    if result.shares > by_margin {
+        result.reductions.push(SizeReduction::Margin {
+            from: result.shares,
+            to: by_margin,
+            available_funds: capacity.available_funds,
+            initial_margin_rate: capacity.initial_margin_rate,
+        });
+        result.shares = by_margin;
+    }
+    
+    let lot_size = lot_size.max(1);
+    let rounded = result.shares / lot_size * lot_size;
+    if rounded != result.shares {
+        result.reductions.push(SizeReduction::BoardLot { from: result.shares, to: rounded, lot_size });
+        result.shares = rounded;
+    }
+    
+    if result.shares <= 0 {
+        let reason = result.reductions.last()
+            .map(|r| r.to_string())
+            .unwrap_or_else(|| "no shares requested".to_string());
+        return Err(format!("Account can't fund a single lot of this trade: {}", reason));
+    }
+    
+    Ok(result)
+}
+
+/// Why a stop failed ATR-based validation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopLossViolation {
+    WrongSide { side: OrderSide },
+    TooClose { distance: f64, min_distance: f64 },
+    TooFar { distance: f64, max_distance: f64, atr: f64, max_atr_fraction: f64 },
+}
+
+impl std::fmt::Display for StopLossViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopLossViolation::WrongSide { side: OrderSide::Long } => {
+                write!(f, "For long orders, stop price must be below entry price")
             }
-            stop_price - entry_price
+            StopLossViolation::WrongSide { side: OrderSide::Short } => {
+                write!(f, "For short orders, stop price must be above entry price")
+            }
+            StopLossViolation::TooClose { min_distance, .. } => {
+                write!(f, "Stop loss too close to entry. Minimum distance is ${:.2}", min_distance)
+            }
+            StopLossViolation::TooFar { max_distance, atr, max_atr_fraction, .. } => write!(
+                f,
+                "Stop loss too far from entry. Maximum distance is ${:.2} ({:.0}% of ATR ${:.2})",
+                max_distance, max_atr_fraction * 100.0, atr
+            ),
         }
+    }
+}
+
+/// Check stop distance against explicit thresholds:
+/// at least `min_distance` and at most `max_atr_fraction` of ATR
+pub fn check_stop_loss(
+    entry_price: f64,
+    stop_price: f64,
+    side: OrderSide,
+    atr: f64,
+    min_distance: f64,
+    max_atr_fraction: f64,
+) -> Result<(), StopLossViolation> {
+    let max_distance = atr * max_atr_fraction;
+    
+    let stop_distance = match side {
+        OrderSide::Long => entry_price - stop_price,
+        OrderSide::Short => stop_price - entry_price,
     };
     
+    if stop_distance <= 0.0 {
+        return Err(StopLossViolation::WrongSide { side });
+    }
+    
     if stop_distance < min_distance {
-        return Err(format!(
-            "Stop loss too close to entry. Minimum distance is ${:.2}",
-            min_distance
-        ));
+        return Err(StopLossViolation::TooClose { distance: stop_distance, min_distance });
     }
     
     if stop_distance > max_distance {
-        return Err(format!(
-            "Stop loss too far from entry. Maximum distance is ${:.2} (15% of ATR ${:.2})",
-            max_distance, atr
-        ));
+        return Err(StopLossViolation::TooFar { distance: stop_distance, max_distance, atr, max_atr_fraction });
     }
     
     Ok(())
 }
 
-/// Calculate default stop loss based on ATR (10% of ATR)
+/// ATR-relative stop thresholds, from settings with optional per-model overrides
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StopRules {
+    pub min_distance: f64,         // Dollars between entry and stop, at least
+    pub default_atr_fraction: f64, // Stop distance proposed from a fresh ATR
+    pub max_atr_fraction: f64,     // Widest stop allowed, as a fraction of ATR
+}
+
+impl Default for StopRules {
+    /// The values seeded into the settings table
+    fn default() -> Self {
+        Self {
+            min_distance: 0.01,
+            default_atr_fraction: 0.10,
+            max_atr_fraction: 0.15,
+        }
+    }
+}
+
+/// Starting values for a new template of one trading model, from settings with per-model overrides
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelDefaults {
+    pub risk_per_trade: f64,    // Dollars risked when auto-sizing
+    pub stop_atr_fraction: f64, // Stop distance proposed when none is given
+    pub target_r: f64,          // Full-size take-profit on new templates; 0 = none
+}
+
+impl Default for ModelDefaults {
+    /// The values seeded into the settings table
+    fn default() -> Self {
+        Self {
+            risk_per_trade: 100.0,
+            stop_atr_fraction: StopRules::default().default_atr_fraction,
+            target_r: 0.0,
+        }
+    }
+}
+
+impl ModelDefaults {
+    pub fn default_stop(&self, entry_price: f64, side: OrderSide, atr: f64) -> f64 {
+        let rules = StopRules { default_atr_fraction: self.stop_atr_fraction, ..StopRules::default() };
+        calculate_default_stop_loss(entry_price, side, atr, &rules)
+    }
+}
+
+/// Validate stop loss placement relative to ATR:
+/// at least `min_distance` from entry and within `max_atr_fraction` of ATR
+pub fn validate_stop_loss(
+    entry_price: f64,
+    stop_price: f64,
+    side: OrderSide,
+    atr: f64,
+    rules: &StopRules,
+) -> Result<(), String> {
+    check_stop_loss(entry_price, stop_price, side, atr, rules.min_distance, rules.max_atr_fraction)
+        .map_err(|violation| violation.to_string())
+}
+
+/// Calculate default stop loss `default_atr_fraction` of ATR from the entry
 pub fn calculate_default_stop_loss(
     entry_price: f64,
     side: OrderSide,
     atr: f64,
+    rules: &StopRules,
 ) -> f64 {
-    let stop_distance = atr * 0.10; // 10% of ATR
+    let stop_distance = atr * rules.default_atr_fraction;
     
     match side {
         OrderSide::Long => entry_price - stop_distance,
@@ -130,6 +383,71 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_volatility_normalized_size() {
+        // $100 risk over 2 x $2.50 ATR = 20 shares, regardless of the stop
+        assert_eq!(calculate_volatility_normalized_size(100.0, 2.5, 2.0).unwrap(), 20);
+        assert!(calculate_volatility_normalized_size(100.0, 0.0, 2.0).is_err());
+        assert!(calculate_volatility_normalized_size(1.0, 2.5, 2.0).is_err());
+    }
+
+    #[test]
+    fn test_fractional_kelly() {
+        // 12 wins of 2R, 8 losses of 1R: win rate 0.6, payoff 2, Kelly 0.6 - 0.4 / 2 = 0.4
+        let mut r_multiples = vec![2.0; 12];
+        r_multiples.extend(vec![-1.0; 8]);
+        let stats = TradeStats::from_r_multiples(&r_multiples).unwrap();
+        assert_eq!(stats.trades, 20);
+        assert!((stats.kelly_fraction() - 0.4).abs() < 1e-9);
+
+        // Quarter Kelly on $50,000 risks $5,000
+        let risk = calculate_kelly_risk(50_000.0, &stats, 0.25).unwrap();
+        assert!((risk - 5_000.0).abs() < 1e-6);
+
+        // Too few trades, or no edge
+        let short_history = TradeStats::from_r_multiples(&r_multiples[6..]).unwrap();
+        assert!(calculate_kelly_risk(50_000.0, &short_history, 0.25).is_err());
+        let mut losing = vec![1.0; 5];
+        losing.extend(vec![-1.0; 15]);
+        let stats = TradeStats::from_r_multiples(&losing).unwrap();
+        assert_eq!(stats.kelly_fraction(), 0.0);
+        assert!(calculate_kelly_risk(50_000.0, &stats, 0.25).is_err());
+
+        assert!(TradeStats::from_r_multiples(&[1.0, 2.0]).is_none());
+    }
+
+    #[test]
+    fn test_fit_to_account() {
+        let cash = AccountCapacity { buying_power: 10_000.0, available_funds: 10_000.0, initial_margin_rate: 1.0 };
+
+        // Affordable: untouched
+        let result = fit_to_account(50, 100.0, &cash, 1).unwrap();
+        assert_eq!(result.shares, 50);
+        assert!(!result.was_reduced());
+
+        // $10,000 buys 100 shares at $100
+        let result = fit_to_account(250, 100.0, &cash, 1).unwrap();
+        assert_eq!(result.requested, 250);
+        assert_eq!(result.shares, 100);
+        assert_eq!(result.reductions, vec![SizeReduction::BuyingPower { from: 250, to: 100, buying_power: 10_000.0 }]);
+
+        // Plenty of buying power, but $3,000 of funds at 50% margin only covers $6,000
+        let margin = AccountCapacity { buying_power: 40_000.0, available_funds: 3_000.0, initial_margin_rate: 0.5 };
+        let result = fit_to_account(250, 100.0, &margin, 1).unwrap();
+        assert_eq!(result.shares, 60);
+        assert!(matches!(result.reductions[..], [SizeReduction::Margin { from: 250, to: 60, .. }]));
+
+        // Board lots round down after the affordability cut
+        let result = fit_to_account(250, 100.0, &margin, 25).unwrap();
+        assert_eq!(result.shares, 50);
+        assert_eq!(result.reductions.len(), 2);
+        assert_eq!(result.reductions[1], SizeReduction::BoardLot { from: 60, to: 50, lot_size: 25 });
+
+        // Not even one lot is affordable
+        assert!(fit_to_account(250, 100.0, &margin, 100).is_err());
+        assert!(fit_to_account(10, 20_000.0, &cash, 1).is_err());
+    }
+
     #[test]
     fn test_stop_validation() {
         let entry = 100.0;
@@ -137,15 +455,26 @@ mod tests {
         
         // Valid stop for long
         let stop = 99.8; // 0.2 below entry, within 15% ATR (0.3)
-        assert!(validate_stop_loss(entry, stop, OrderSide::Long, atr).is_ok());
+        assert!(validate_stop_loss(entry, stop, OrderSide::Long, atr, &StopRules::default()).is_ok());
         
         // Too close
         let stop = 99.995;
-        assert!(validate_stop_loss(entry, stop, OrderSide::Long, atr).is_err());
+        assert!(validate_stop_loss(entry, stop, OrderSide::Long, atr, &StopRules::default()).is_err());
         
         // Too far
         let stop = 99.0; // 1.0 below, exceeds 15% ATR (0.3)
-        assert!(validate_stop_loss(entry, stop, OrderSide::Long, atr).is_err());
+        assert!(validate_stop_loss(entry, stop, OrderSide::Long, atr, &StopRules::default()).is_err());
+        
+        // Custom thresholds: 1.0 is within 60% of ATR (1.2)
+        assert!(check_stop_loss(entry, stop, OrderSide::Long, atr, 0.01, 0.6).is_ok());
+        assert!(matches!(
+            check_stop_loss(entry, 99.9, OrderSide::Long, atr, 0.25, 0.6),
+            Err(StopLossViolation::TooClose { .. })
+        ));
+        assert!(matches!(
+            check_stop_loss(entry, 100.5, OrderSide::Long, atr, 0.01, 0.15),
+            Err(StopLossViolation::WrongSide { side: OrderSide::Long })
+        ));
     }
 
     #[test]
@@ -154,11 +483,15 @@ mod tests {
         let atr = 2.0;
         
         // Long stop
-        let stop = calculate_default_stop_loss(entry, OrderSide::Long, atr);
+        let stop = calculate_default_stop_loss(entry, OrderSide::Long, atr, &StopRules::default());
         assert_eq!(stop, 99.8); // 100 - (2 * 0.1)
         
         // Short stop
-        let stop = calculate_default_stop_loss(entry, OrderSide::Short, atr);
+        let stop = calculate_default_stop_loss(entry, OrderSide::Short, atr, &StopRules::default());
         assert_eq!(stop, 100.2); // 100 + (2 * 0.1)
+        
+        // A model configured for wider stops
+        let rules = StopRules { default_atr_fraction: 0.25, ..Default::default() };
+        assert_eq!(calculate_default_stop_loss(entry, OrderSide::Long, atr, &rules), 99.5);
     }
 }
\ No newline at end of file
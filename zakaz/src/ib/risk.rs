@@ -0,0 +1,685 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::fx::FxRates;
+use super::market_hours;
+use super::types::{HistoricalBar, OrderTemplate, OrderTemplateStatus, Sector, ShortAvailability, TradingModel};
+
+/// A held position as IB's portfolio reports it, for flattening
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkedPosition {
+    pub symbol: String,
+    pub currency: String,
+    pub quantity: f64, // Signed: negative is short
+    pub market_price: f64,
+    pub average_cost: f64,
+}
+
+/// Session P&L watched by the daily loss limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DailyPnL {
+    pub realized: f64,   // Today's executions, net of commission
+    pub unrealized: f64, // Open positions' move since the prior close
+}
+
+impl DailyPnL {
+    /// From IB's account P&L (reqPnL), in the account's base currency. IB marks positions held
+    /// overnight against the prior close, so losses from earlier sessions don't count against today.
+    /// None when IB has no daily figure yet.
+    pub fn from_ib(daily_pnl: f64, realized_pnl: Option<f64>) -> Option<Self> {
+        if !is_set(daily_pnl) {
+            return None;
+        }
+        let realized = realized_pnl.filter(|&pnl| is_set(pnl)).unwrap_or(0.0);
+        Some(Self { realized, unrealized: daily_pnl - realized })
+    }
+
+    pub fn total(&self) -> f64 {
+        self.realized + self.unrealized
+    }
+
+    /// A limit of zero or less disables the check
+    pub fn breaches(&self, loss_limit: f64) -> bool {
+        loss_limit > 0.0 && self.total() <= -loss_limit
+    }
+}
+
+/// IB reports an unset value as f64::MAX
+fn is_set(value: f64) -> bool {
+    value.abs() < f64::MAX / 2.0
+}
+
+/// Set when the daily loss limit trips; new activations are refused until the next session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradingLock {
+    pub session: NaiveDate, // Exchange-local date the limit tripped on
+    pub pnl: DailyPnL,
+    pub loss_limit: f64,
+    pub locked_at: DateTime<Utc>,
+}
+
+impl TradingLock {
+    pub fn new(pnl: DailyPnL, loss_limit: f64, now: DateTime<Utc>) -> Self {
+        Self {
+            session: market_hours::session_date(now),
+            pnl,
+            loss_limit,
+            locked_at: now,
+        }
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        market_hours::session_date(now) <= self.session
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "Trading locked: day P&L {:.2} breached the {:.2} loss limit; activations resume next session",
+            self.pnl.total(), self.loss_limit
+        )
+    }
+}
+
+/// What the loss limit check did this round
+#[derive(Debug, Clone)]
+pub enum TradingLockChange {
+    Locked {
+        lock: TradingLock,
+        cancelled: Vec<String>,   // Template ids whose pending orders were cancelled
+        flattened: Vec<String>,   // Symbols closed at market
+        errors: Vec<String>,
+    },
+    Released,
+}
+
+/// Caps on how much is live at once; zero disables a cap
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PositionLimits {
+    pub max_open_positions: usize,
+    pub max_pending_templates: usize,
+}
+
+impl PositionLimits {
+    /// Refuse `template` when activating it would exceed a cap.
+    /// Read-only templates adopted from IB count like managed ones.
+    pub fn check<'a>(&self, template: &OrderTemplate, templates: impl IntoIterator<Item = &'a OrderTemplate>) -> Result<(), String> {
+        let mut position_symbols = HashSet::new();
+        let mut pending = HashSet::new();
+        for other in templates {
+            if other.id == template.id {
+                continue;
+            }
+            if other.has_fills() {
+                position_symbols.insert(other.symbol.as_str());
+            }
+            // An OCO pair is one pending trade: only one side can fill
+            if other.status == OrderTemplateStatus::Active {
+                pending.insert(other.oca_group.as_deref().unwrap_or(other.id.as_str()));
+            }
+        }
+
+        // Adding to a symbol already held doesn't open another position
+        if self.max_open_positions > 0
+            && !position_symbols.contains(template.symbol.as_str())
+            && position_symbols.len() >= self.max_open_positions
+        {
+            return Err(format!(
+                "{} open positions already (max_open_positions {}); override to activate anyway",
+                position_symbols.len(), self.max_open_positions
+            ));
+        }
+
+        let joins_pending_pair = template.oca_group.as_deref().is_some_and(|group| pending.contains(group));
+        if self.max_pending_templates > 0 && !joins_pending_pair && pending.len() >= self.max_pending_templates {
+            return Err(format!(
+                "{} pending templates already (max_pending_templates {}); override to activate anyway",
+                pending.len(), self.max_pending_templates
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Notional and risk caps per symbol and per sector; zero or None disables a cap
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExposureCaps {
+    pub max_symbol_notional: f64,
+    pub max_symbol_risk: f64,
+    pub sectors: Vec<Sector>,
+}
+
+impl ExposureCaps {
+    /// Refuse `template` when its exposure plus that of live templates on the same
+    /// symbol, or in the same sector, would exceed a cap. Exposure is gross: shorts add to longs.
+    /// Caps are in the base currency of `fx`.
+    pub fn check<'a>(
+        &self,
+        template: &OrderTemplate,
+        templates: impl IntoIterator<Item = &'a OrderTemplate>,
+        fx: &FxRates,
+    ) -> Result<(), String> {
+        let live: Vec<&OrderTemplate> = templates.into_iter()
+            .filter(|t| t.id != template.id && t.is_active())
+            .collect();
+
+        let (notional, risk) = exposure(template, live.iter().filter(|t| t.symbol.eq_ignore_ascii_case(&template.symbol)), fx);
+        check_cap(&template.symbol, "notional", notional, self.max_symbol_notional)?;
+        check_cap(&template.symbol, "risk", risk, self.max_symbol_risk)?;
+
+        if let Some(sector) = self.sectors.iter().find(|s| s.contains(&template.symbol)) {
+            let (notional, risk) = exposure(template, live.iter().filter(|t| sector.contains(&t.symbol)), fx);
+            let label = format!("Sector {}", sector.name);
+            check_cap(&label, "notional", notional, sector.max_notional.unwrap_or(0.0))?;
+            check_cap(&label, "risk", risk, sector.max_risk.unwrap_or(0.0))?;
+        }
+        Ok(())
+    }
+}
+
+fn exposure<'a>(template: &OrderTemplate, others: impl Iterator<Item = &'a &'a OrderTemplate>, fx: &FxRates) -> (f64, f64) {
+    let in_base = |t: &OrderTemplate| (fx.convert_or_keep(t.notional(), &t.currency), fx.convert_or_keep(t.open_risk(), &t.currency));
+    others.fold(in_base(template), |(notional, risk), t| {
+        let (t_notional, t_risk) = in_base(t);
+        (notional + t_notional, risk + t_risk)
+    })
+}
+
+fn check_cap(label: &str, measure: &str, value: f64, cap: f64) -> Result<(), String> {
+    if cap > 0.0 && value > cap {
+        return Err(format!(
+            "{} {} would be {:.2}, cap is {:.2}; override to activate anyway",
+            label, measure, value, cap
+        ));
+    }
+    Ok(())
+}
+
+/// Exposure of a group of live templates
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RiskExposure {
+    pub templates: usize,
+    pub notional: f64,
+    pub risk: f64,   // Dollars lost if every stop is hit
+    pub risk_r: f64, // The same, in each template's planned risk per trade
+}
+
+impl RiskExposure {
+    /// `rate` converts the template's currency into the summary's base currency
+    fn add(&mut self, template: &OrderTemplate, rate: f64) {
+        let risk = template.open_risk();
+        self.templates += 1;
+        self.notional += template.notional() * rate;
+        self.risk += risk * rate;
+        if template.risk_per_trade > 0.0 {
+            self.risk_r += risk / template.risk_per_trade;
+        }
+    }
+}
+
+/// Open risk across live templates, adopted IB positions included, and what's left of the day's loss budget
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RiskSummary {
+    pub total: RiskExposure,
+    pub pending: RiskExposure, // Orders working, nothing filled yet
+    pub filled: RiskExposure,  // Entries with executions
+    pub by_symbol: BTreeMap<String, RiskExposure>,
+    pub by_model: HashMap<TradingModel, RiskExposure>,
+    pub daily_pnl: Option<DailyPnL>, // None when IB couldn't be asked
+    pub daily_loss_limit: f64,
+    pub remaining_daily_budget: Option<f64>, // None without a limit or P&L
+    pub drawdown: Option<Drawdown>,          // None until equity has been tracked on this account
+    pub risk_scale: f64,                     // Multiplier applied to risk_per_trade for the drawdown
+    pub base_currency: String,               // Currency of every amount above
+    pub missing_fx_rates: BTreeSet<String>,  // Currencies summed unconverted for want of a rate
+}
+
+impl RiskSummary {
+    pub fn from_templates<'a>(
+        templates: impl IntoIterator<Item = &'a OrderTemplate>,
+        daily_pnl: Option<DailyPnL>,
+        daily_loss_limit: f64,
+        fx: &FxRates,
+    ) -> Self {
+        let mut summary = Self {
+            daily_pnl,
+            daily_loss_limit,
+            remaining_daily_budget: daily_pnl
+                .filter(|_| daily_loss_limit > 0.0)
+                .map(|pnl| (daily_loss_limit + pnl.total()).max(0.0)),
+            risk_scale: 1.0,
+            base_currency: fx.base.clone(),
+            ..Default::default()
+        };
+
+        for template in templates.into_iter().filter(|t| t.is_active()) {
+            let rate = fx.rate(&template.currency).unwrap_or_else(|| {
+                summary.missing_fx_rates.insert(template.currency.to_uppercase());
+                1.0
+            });
+            summary.total.add(template, rate);
+            if template.has_fills() {
+                summary.filled.add(template, rate);
+            } else {
+                summary.pending.add(template, rate);
+            }
+            summary.by_symbol.entry(template.symbol.to_uppercase()).or_default().add(template, rate);
+            summary.by_model.entry(template.model).or_default().add(template, rate);
+        }
+        summary
+    }
+}
+
+/// Account equity against its peak
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Drawdown {
+    pub high_water_mark: f64,
+    pub equity: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Drawdown {
+    pub fn new(equity: f64, now: DateTime<Utc>) -> Self {
+        Self { high_water_mark: equity, equity, updated_at: now }
+    }
+
+    /// Take a new equity reading, raising the peak on a new high
+    pub fn update(&mut self, equity: f64, now: DateTime<Utc>) {
+        self.equity = equity;
+        self.high_water_mark = self.high_water_mark.max(equity);
+        self.updated_at = now;
+    }
+
+    /// Percent below the peak; 0 at a new high
+    pub fn pct(&self) -> f64 {
+        if self.high_water_mark <= 0.0 {
+            return 0.0;
+        }
+        ((self.high_water_mark - self.equity) / self.high_water_mark * 100.0).max(0.0)
+    }
+}
+
+/// Risk multipliers by drawdown depth, e.g. half risk beyond 5% and a quarter beyond 10%
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RiskScaling {
+    pub steps: Vec<(f64, f64)>, // (drawdown %, factor), shallowest first
+}
+
+impl RiskScaling {
+    /// Parse `drawdown_risk_steps`, e.g. "5:0.5,10:0.25". Empty turns scaling off.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut steps = Vec::new();
+        for step in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (pct, factor) = step.split_once(':')
+                .and_then(|(pct, factor)| Some((pct.trim().parse::<f64>().ok()?, factor.trim().parse::<f64>().ok()?)))
+                .ok_or_else(|| format!("Drawdown step '{}' should look like 5:0.5", step))?;
+            if pct <= 0.0 || !(0.0..=1.0).contains(&factor) {
+                return Err(format!("Drawdown step '{}' needs a positive drawdown and a factor between 0 and 1", step));
+            }
+            steps.push((pct, factor));
+        }
+        steps.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self { steps })
+    }
+
+    /// The steps as `parse` reads them
+    pub fn to_setting(&self) -> String {
+        self.steps.iter()
+            .map(|(pct, factor)| format!("{}:{}", pct, factor))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Factor of the deepest step the drawdown has reached; 1 above them all, so risk
+    /// comes back as equity recovers
+    pub fn factor(&self, drawdown_pct: f64) -> f64 {
+        self.steps.iter()
+            .rev()
+            .find(|(pct, _)| drawdown_pct >= *pct)
+            .map_or(1.0, |(_, factor)| *factor)
+    }
+}
+
+/// Fewer overlapping daily returns than this say too little to warn on
+pub const MIN_CORRELATION_RETURNS: usize = 20;
+
+/// A candidate that moves with a symbol already held
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorrelationWarning {
+    pub symbol: String,
+    pub held: String,
+    pub correlation: f64,
+    pub observations: usize, // Daily returns compared
+}
+
+impl std::fmt::Display for CorrelationWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is {:.2} correlated with held {} over {} days",
+            self.symbol, self.correlation, self.held, self.observations
+        )
+    }
+}
+
+/// Borrow and margin facts for a short entry; either may be unknown when IB doesn't answer
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShortCheck {
+    pub symbol: String,
+    pub availability: Option<ShortAvailability>,
+    pub margin_requirement: Option<f64>, // Initial margin the order adds, from a what-if order
+    pub notional: f64,
+}
+
+impl ShortCheck {
+    /// Reasons to hold back the short. `max_margin_pct` (of notional, 0 = no limit) bounds the margin.
+    pub fn issues(&self, max_margin_pct: f64) -> Vec<String> {
+        let mut issues = Vec::new();
+        match self.availability {
+            Some(ShortAvailability::Unavailable) => issues.push(format!("{} has no shares available to borrow", self.symbol)),
+            Some(ShortAvailability::HardToBorrow) => issues.push(format!("{} is hard to borrow", self.symbol)),
+            _ => {}
+        }
+        if let Some(margin) = self.margin_requirement
+            && max_margin_pct > 0.0
+            && self.notional > 0.0
+            && margin / self.notional * 100.0 > max_margin_pct
+        {
+            issues.push(format!(
+                "{} short needs {:.2} initial margin, {:.0}% of its {:.2} notional (short_max_margin_pct {:.0})",
+                self.symbol, margin, margin / self.notional * 100.0, self.notional, max_margin_pct
+            ));
+        }
+        issues
+    }
+}
+
+/// Pearson correlation of daily close-to-close returns over the latest `window` days both
+/// series traded. Returns the correlation and the number of returns used.
+pub fn return_correlation(a: &[HistoricalBar], b: &[HistoricalBar], window: usize) -> Option<(f64, usize)> {
+    let closes_b: HashMap<NaiveDate, f64> = b.iter()
+        .map(|bar| (bar.timestamp.date_naive(), bar.close))
+        .collect();
+    let mut aligned: Vec<(NaiveDate, f64, f64)> = a.iter()
+        .filter_map(|bar| {
+            let date = bar.timestamp.date_naive();
+            closes_b.get(&date).map(|close_b| (date, bar.close, *close_b))
+        })
+        .collect();
+    aligned.sort_by_key(|(date, _, _)| *date);
+
+    let returns: Vec<(f64, f64)> = aligned.windows(2)
+        .filter(|pair| pair[0].1 > 0.0 && pair[0].2 > 0.0)
+        .map(|pair| (pair[1].1 / pair[0].1 - 1.0, pair[1].2 / pair[0].2 - 1.0))
+        .collect();
+    let returns = &returns[returns.len().saturating_sub(window)..];
+    if returns.len() < MIN_CORRELATION_RETURNS {
+        return None;
+    }
+
+    let n = returns.len() as f64;
+    let mean_a = returns.iter().map(|(ra, _)| ra).sum::<f64>() / n;
+    let mean_b = returns.iter().map(|(_, rb)| rb).sum::<f64>() / n;
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (ra, rb) in returns {
+        covariance += (ra - mean_a) * (rb - mean_b);
+        variance_a += (ra - mean_a).powi(2);
+        variance_b += (rb - mean_b).powi(2);
+    }
+    if variance_a <= 0.0 || variance_b <= 0.0 {
+        return None; // A flat series has no defined correlation
+    }
+    Some((covariance / (variance_a * variance_b).sqrt(), returns.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ib::types::{OrderSide, TimeInForce, TradingModel};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_daily_pnl() {
+        let pnl = DailyPnL::from_ib(-550.0, Some(-250.0)).unwrap();
+        assert_eq!(pnl.realized, -250.0);
+        assert_eq!(pnl.unrealized, -300.0);
+        assert_eq!(pnl.total(), -550.0);
+        assert!(pnl.breaches(500.0));
+        assert!(!pnl.breaches(600.0));
+        assert!(!pnl.breaches(0.0));
+
+        // Nothing closed yet: the day's move is all unrealized
+        assert_eq!(DailyPnL::from_ib(-120.0, Some(f64::MAX)), Some(DailyPnL { realized: 0.0, unrealized: -120.0 }));
+        assert_eq!(DailyPnL::from_ib(f64::MAX, None), None);
+    }
+
+    #[test]
+    fn test_trading_lock_lasts_the_session() {
+        // 2025-07-25 15:00 EDT
+        let tripped = Utc.with_ymd_and_hms(2025, 7, 25, 19, 0, 0).unwrap();
+        let lock = TradingLock::new(DailyPnL { realized: -600.0, unrealized: 0.0 }, 500.0, tripped);
+        assert_eq!(lock.session, NaiveDate::from_ymd_opt(2025, 7, 25).unwrap());
+
+        // Still the same New York date after the close
+        assert!(lock.is_active(Utc.with_ymd_and_hms(2025, 7, 26, 3, 0, 0).unwrap()));
+        assert!(!lock.is_active(Utc.with_ymd_and_hms(2025, 7, 26, 5, 0, 0).unwrap()));
+    }
+
+    fn template(symbol: &str, status: OrderTemplateStatus) -> OrderTemplate {
+        let mut template = OrderTemplate::new(
+            format!("{} setup", symbol),
+            symbol.to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            145.0,
+            TimeInForce::GTC,
+            TradingModel::Breakout,
+        );
+        template.status = status;
+        template.risk_per_trade = 500.0;
+        if status == OrderTemplateStatus::Filled {
+            template.filled_quantity = 100.0;
+        }
+        template
+    }
+
+    #[test]
+    fn test_position_limits() {
+        let mut held = template("AAPL", OrderTemplateStatus::Filled);
+        held.is_read_only = true; // Adopted from IB
+        let mut pair_long = template("MSFT", OrderTemplateStatus::Active);
+        pair_long.oca_group = Some("pair".to_string());
+        let mut pair_short = template("MSFT", OrderTemplateStatus::Active);
+        pair_short.oca_group = Some("pair".to_string());
+        let live = vec![held, pair_long, pair_short, template("TSLA", OrderTemplateStatus::Inactive)];
+
+        let limits = PositionLimits { max_open_positions: 1, max_pending_templates: 2 };
+        assert!(limits.check(&template("NVDA", OrderTemplateStatus::Inactive), &live).is_err());
+        // Adds to the held symbol; the OCO pair is a single pending trade
+        assert!(limits.check(&template("AAPL", OrderTemplateStatus::Inactive), &live).is_ok());
+
+        let limits = PositionLimits { max_open_positions: 0, max_pending_templates: 1 };
+        let err = limits.check(&template("NVDA", OrderTemplateStatus::Inactive), &live).unwrap_err();
+        assert!(err.contains("override"));
+        let mut partner = template("MSFT", OrderTemplateStatus::Inactive);
+        partner.oca_group = Some("pair".to_string());
+        assert!(limits.check(&partner, &live).is_ok());
+
+        assert!(PositionLimits::default().check(&template("NVDA", OrderTemplateStatus::Inactive), &live).is_ok());
+    }
+
+    #[test]
+    fn test_exposure_caps() {
+        // 100 x 150 with a 145 stop: 15000 notional, 500 risk
+        let nvda = template("NVDA", OrderTemplateStatus::Filled);
+        let live = vec![nvda, template("MSFT", OrderTemplateStatus::Active), template("AMD", OrderTemplateStatus::Inactive)];
+        let semis = Sector {
+            name: "Semis".to_string(),
+            max_notional: None,
+            max_risk: Some(800.0),
+            symbols: vec!["NVDA".to_string(), "AMD".to_string()],
+        };
+
+        let fx = FxRates::default();
+
+        // A second semiconductor long takes the sector to 1000 risk
+        let caps = ExposureCaps { sectors: vec![semis], ..Default::default() };
+        let err = caps.check(&template("AMD", OrderTemplateStatus::Inactive), &live, &fx).unwrap_err();
+        assert!(err.starts_with("Sector Semis risk"));
+        // Inactive templates don't count, and other sectors are unaffected
+        assert!(caps.check(&template("AAPL", OrderTemplateStatus::Inactive), &live, &fx).is_ok());
+
+        let caps = ExposureCaps { max_symbol_notional: 20000.0, ..Default::default() };
+        assert!(caps.check(&template("MSFT", OrderTemplateStatus::Inactive), &live, &fx).is_err());
+        assert!(caps.check(&template("AAPL", OrderTemplateStatus::Inactive), &live, &fx).is_ok());
+    }
+
+    #[test]
+    fn test_risk_summary() {
+        // Each template risks 500 (1R) on 15000 notional
+        let mut trailed = template("NVDA", OrderTemplateStatus::Filled);
+        trailed.stop_price = 152.0; // Stop moved into profit
+        let mut bounce = template("MSFT", OrderTemplateStatus::Active);
+        bounce.model = TradingModel::Bounce;
+        let live = vec![
+            template("AAPL", OrderTemplateStatus::Filled),
+            template("AAPL", OrderTemplateStatus::Active),
+            trailed,
+            bounce,
+            template("AMD", OrderTemplateStatus::Inactive),
+        ];
+
+        let pnl = DailyPnL { realized: -200.0, unrealized: -100.0 };
+        let fx = FxRates::default();
+        let summary = RiskSummary::from_templates(&live, Some(pnl), 1000.0, &fx);
+        assert_eq!(summary.total.templates, 4);
+        assert_eq!(summary.total.risk, 1500.0);
+        assert!((summary.total.risk_r - 3.0).abs() < 1e-9);
+        assert_eq!(summary.filled.templates, 2);
+        assert_eq!(summary.filled.risk, 500.0);
+        assert_eq!(summary.pending.notional, 30000.0);
+        assert_eq!(summary.by_symbol["AAPL"].risk, 1000.0);
+        assert_eq!(summary.by_symbol["NVDA"].risk, 0.0);
+        assert!(!summary.by_symbol.contains_key("AMD"));
+        assert_eq!(summary.by_model[&TradingModel::Bounce].templates, 1);
+        assert_eq!(summary.by_model[&TradingModel::Breakout].risk, 1000.0);
+        assert_eq!(summary.remaining_daily_budget, Some(700.0));
+
+        assert_eq!(RiskSummary::from_templates(&live, Some(pnl), 0.0, &fx).remaining_daily_budget, None);
+        assert_eq!(RiskSummary::from_templates(&live, None, 1000.0, &fx).remaining_daily_budget, None);
+    }
+
+    #[test]
+    fn test_risk_in_base_currency() {
+        // 500 EUR of risk on 15000 EUR notional
+        let mut sap = template("SAP", OrderTemplateStatus::Active);
+        sap.currency = "EUR".to_string();
+        let mut shop = template("SHOP", OrderTemplateStatus::Active);
+        shop.currency = "CAD".to_string();
+        let live = vec![sap.clone(), shop, template("AAPL", OrderTemplateStatus::Active)];
+
+        let mut fx = FxRates::default();
+        fx.set("EUR", 1.2, Utc::now());
+        let summary = RiskSummary::from_templates(&live, None, 0.0, &fx);
+        assert_eq!(summary.base_currency, "USD");
+        assert_eq!(summary.by_symbol["SAP"].risk, 600.0);
+        assert_eq!(summary.by_symbol["SAP"].notional, 18000.0);
+        // R is currency-free
+        assert_eq!(summary.by_symbol["SAP"].risk_r, 1.0);
+        // No CAD rate yet: counted as is, and flagged
+        assert_eq!(summary.by_symbol["SHOP"].risk, 500.0);
+        assert_eq!(summary.missing_fx_rates, BTreeSet::from(["CAD".to_string()]));
+
+        let caps = ExposureCaps { max_symbol_risk: 1000.0, ..Default::default() };
+        assert!(caps.check(&sap, &live, &fx).is_ok());
+        fx.set("EUR", 2.5, Utc::now());
+        assert!(caps.check(&sap, &live, &fx).is_err());
+    }
+
+    fn bars(closes: &[f64]) -> Vec<HistoricalBar> {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        closes.iter().enumerate().map(|(day, close)| HistoricalBar {
+            timestamp: start + chrono::Duration::days(day as i64),
+            open: *close,
+            high: *close,
+            low: *close,
+            close: *close,
+            volume: 0,
+            wap: *close,
+            count: 0,
+        }).collect()
+    }
+
+    #[test]
+    fn test_drawdown_scaling() {
+        let now = Utc::now();
+        let mut drawdown = Drawdown::new(100_000.0, now);
+        drawdown.update(110_000.0, now);
+        drawdown.update(99_000.0, now);
+        assert_eq!(drawdown.high_water_mark, 110_000.0);
+        assert_eq!(drawdown.pct(), 10.0);
+
+        let scaling = RiskScaling::parse("10:0.25, 5:0.5").unwrap();
+        assert_eq!(scaling.steps, vec![(5.0, 0.5), (10.0, 0.25)]);
+        assert_eq!(scaling.factor(2.0), 1.0);
+        assert_eq!(scaling.factor(5.0), 0.5);
+        assert_eq!(scaling.factor(drawdown.pct()), 0.25);
+
+        // Recovery restores risk step by step
+        drawdown.update(104_000.0, now);
+        assert_eq!(scaling.factor(drawdown.pct()), 0.5);
+        drawdown.update(108_000.0, now);
+        assert_eq!(scaling.factor(drawdown.pct()), 1.0);
+
+        assert_eq!(RiskScaling::parse("").unwrap().factor(50.0), 1.0);
+        assert!(RiskScaling::parse("5").is_err());
+        assert!(RiskScaling::parse("5:1.5").is_err());
+    }
+
+    #[test]
+    fn test_short_check() {
+        assert_eq!(ShortAvailability::from_shortable_tick(3.0), ShortAvailability::Easy);
+        assert_eq!(ShortAvailability::from_shortable_tick(2.0), ShortAvailability::HardToBorrow);
+        assert_eq!(ShortAvailability::from_shortable_tick(1.0), ShortAvailability::Unavailable);
+
+        let mut check = ShortCheck {
+            symbol: "GME".to_string(),
+            availability: Some(ShortAvailability::Easy),
+            margin_requirement: Some(5_000.0),
+            notional: 10_000.0,
+        };
+        assert!(check.issues(100.0).is_empty());
+        // Nothing known, nothing to report
+        assert!(ShortCheck { availability: None, margin_requirement: None, ..check.clone() }.issues(100.0).is_empty());
+
+        check.margin_requirement = Some(15_000.0);
+        assert!(check.issues(100.0)[0].contains("150%"));
+        assert!(check.issues(0.0).is_empty());
+
+        check.availability = Some(ShortAvailability::HardToBorrow);
+        assert_eq!(check.issues(100.0).len(), 2);
+        check.availability = Some(ShortAvailability::Unavailable);
+        assert!(check.issues(0.0)[0].contains("no shares available"));
+    }
+
+    #[test]
+    fn test_return_correlation() {
+        // Alternating up and down days, one series twice as volatile as the other
+        let swing: Vec<f64> = (0..41).map(|i| if i % 2 == 0 { 100.0 } else { 102.0 }).collect();
+        let wider: Vec<f64> = (0..41).map(|i| if i % 2 == 0 { 50.0 } else { 52.0 }).collect();
+        let opposite: Vec<f64> = (0..41).map(|i| if i % 2 == 0 { 100.0 } else { 98.0 }).collect();
+
+        let (correlation, observations) = return_correlation(&bars(&swing), &bars(&wider), 60).unwrap();
+        assert!((correlation - 1.0).abs() < 1e-9);
+        assert_eq!(observations, 40);
+        let (correlation, _) = return_correlation(&bars(&swing), &bars(&opposite), 60).unwrap();
+        assert!((correlation + 1.0).abs() < 1e-9);
+
+        // Window keeps the latest returns; too few to judge gives nothing
+        assert_eq!(return_correlation(&bars(&swing), &bars(&wider), 25).unwrap().1, 25);
+        assert!(return_correlation(&bars(&swing), &bars(&wider), 10).is_none());
+        assert!(return_correlation(&bars(&swing), &bars(&swing[..15]), 60).is_none());
+    }
+}
@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+
+use super::analytics;
+use super::types::{AtrSmoothing, HistoricalBar};
+
+/// Screens applied to each symbol's daily bars; `None` leaves a measure unchecked
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScanCriteria {
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub min_average_volume: Option<f64>,
+    pub min_atr_pct: Option<f64>,
+    pub max_atr_pct: Option<f64>,
+    pub max_from_high_pct: Option<f64>,   // Close within this % below the N-day high
+    pub max_from_low_pct: Option<f64>,    // Close within this % above the N-day low
+    pub min_gap_pct: Option<f64>,         // Latest open vs previous close, either way
+    pub min_relative_volume: Option<f64>, // Today's volume vs the same time of day, from intraday bars
+    pub lookback_days: usize,             // N for the high and low
+    pub volume_days: usize,               // Bars in the average volume
+    pub atr_period: usize,
+    pub rvol_sessions: usize,             // Sessions averaged for relative volume
+}
+
+impl Default for ScanCriteria {
+    fn default() -> Self {
+        Self {
+            min_price: None,
+            max_price: None,
+            min_average_volume: None,
+            min_atr_pct: None,
+            max_atr_pct: None,
+            max_from_high_pct: None,
+            max_from_low_pct: None,
+            min_gap_pct: None,
+            min_relative_volume: None,
+            lookback_days: 20,
+            volume_days: 20,
+            atr_period: 14,
+            rvol_sessions: 20,
+        }
+    }
+}
+
+impl ScanCriteria {
+    /// Daily bars a symbol needs for every measure
+    pub fn bars_needed(&self) -> usize {
+        self.lookback_days.max(self.volume_days).max(self.atr_period + 1).max(2)
+    }
+
+    /// Why `result` fails the screen; empty when it passes
+    pub fn failures(&self, result: &ScanResult) -> Vec<String> {
+        let mut failures = Vec::new();
+        let mut check = |passes: bool, reason: String| {
+            if !passes {
+                failures.push(reason);
+            }
+        };
+        if let Some(min) = self.min_price {
+            check(result.last_close >= min, format!("price {:.2} below {:.2}", result.last_close, min));
+        }
+        if let Some(max) = self.max_price {
+            check(result.last_close <= max, format!("price {:.2} above {:.2}", result.last_close, max));
+        }
+        if let Some(min) = self.min_average_volume {
+            check(result.average_volume >= min, format!("average volume {:.0} below {:.0}", result.average_volume, min));
+        }
+        if let Some(min) = self.min_atr_pct {
+            check(result.atr_pct >= min, format!("ATR {:.2}% below {:.2}%", result.atr_pct, min));
+        }
+        if let Some(max) = self.max_atr_pct {
+            check(result.atr_pct <= max, format!("ATR {:.2}% above {:.2}%", result.atr_pct, max));
+        }
+        if let Some(max) = self.max_from_high_pct {
+            check(result.from_high_pct <= max, format!("{:.1}% below the {}-day high", result.from_high_pct, self.lookback_days));
+        }
+        if let Some(max) = self.max_from_low_pct {
+            check(result.from_low_pct <= max, format!("{:.1}% above the {}-day low", result.from_low_pct, self.lookback_days));
+        }
+        if let Some(min) = self.min_gap_pct {
+            check(result.gap_pct.abs() >= min, format!("gap {:.2}% smaller than {:.2}%", result.gap_pct, min));
+        }
+        // Unchecked until relative volume has been measured
+        if let (Some(min), Some(rvol)) = (self.min_relative_volume, result.relative_volume) {
+            check(rvol >= min, format!("relative volume {:.2} below {:.2}", rvol, min));
+        }
+        failures
+    }
+}
+
+/// A symbol's screened measures from its latest daily bars
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanResult {
+    pub symbol: String,
+    pub last_close: f64,
+    pub average_volume: f64,
+    pub atr: f64,
+    pub atr_pct: f64,
+    pub period_high: f64,
+    pub period_low: f64,
+    pub from_high_pct: f64, // Close below the N-day high, %
+    pub from_low_pct: f64,  // Close above the N-day low, %
+    pub gap_pct: f64,       // Latest open vs previous close, signed %
+    pub relative_volume: Option<f64>, // Only measured when the criteria ask for it
+}
+
+impl ScanResult {
+    /// None when there are too few bars (oldest first) for the criteria
+    pub fn from_bars(symbol: &str, bars: &[HistoricalBar], criteria: &ScanCriteria) -> Option<Self> {
+        if bars.len() < criteria.bars_needed() {
+            return None;
+        }
+        let last = bars.last()?;
+        let previous = &bars[bars.len() - 2];
+        if last.close <= 0.0 || previous.close <= 0.0 {
+            return None;
+        }
+
+        let recent = &bars[bars.len() - criteria.lookback_days.max(1)..];
+        let period_high = recent.iter().map(|b| b.high).fold(f64::MIN, f64::max);
+        let period_low = recent.iter().map(|b| b.low).fold(f64::MAX, f64::min);
+        let volume_bars = &bars[bars.len() - criteria.volume_days.max(1)..];
+        let average_volume = volume_bars.iter().map(|b| b.volume as f64).sum::<f64>() / volume_bars.len() as f64;
+        let atr = analytics::atr(bars, criteria.atr_period, AtrSmoothing::Wilder)?;
+
+        Some(Self {
+            symbol: symbol.to_string(),
+            last_close: last.close,
+            average_volume,
+            atr,
+            atr_pct: atr / last.close * 100.0,
+            period_high,
+            period_low,
+            from_high_pct: (period_high - last.close) / period_high * 100.0,
+            from_low_pct: if period_low > 0.0 { (last.close - period_low) / period_low * 100.0 } else { 0.0 },
+            gap_pct: (last.open - previous.close) / previous.close * 100.0,
+            relative_volume: None,
+        })
+    }
+}
+
+/// Parse a comma or whitespace separated symbol list, upper-cased and without duplicates
+pub fn parse_symbols(value: &str) -> Vec<String> {
+    let mut symbols: Vec<String> = Vec::new();
+    for symbol in value.split(|c: char| c == ',' || c.is_whitespace()).filter(|s| !s.is_empty()) {
+        let symbol = symbol.to_uppercase();
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn bar(day: i64, open: f64, high: f64, low: f64, close: f64, volume: i64) -> HistoricalBar {
+        HistoricalBar {
+            timestamp: Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap() + Duration::days(day),
+            open,
+            high,
+            low,
+            close,
+            volume,
+            wap: close,
+            count: 0,
+        }
+    }
+
+    #[test]
+    fn test_scan() {
+        let criteria = ScanCriteria { lookback_days: 5, volume_days: 3, atr_period: 3, ..Default::default() };
+        assert_eq!(criteria.bars_needed(), 5);
+
+        // Ranges of 2.00 around 50, then a gap up to a new high
+        let mut bars: Vec<HistoricalBar> = (0..4).map(|i| bar(i, 50.0, 51.0, 49.0, 50.0, 1000)).collect();
+        assert!(ScanResult::from_bars("XYZ", &bars, &criteria).is_none());
+        bars.push(bar(4, 51.0, 53.0, 51.0, 52.0, 4000));
+
+        let result = ScanResult::from_bars("XYZ", &bars, &criteria).unwrap();
+        assert_eq!(result.average_volume, 2000.0);
+        // Wilder: seeded at 2.00, then a 3.00 True Range weighted 1/3
+        assert_eq!(result.atr, 2.0 + (3.0 - 2.0) / 3.0);
+        assert_eq!((result.period_high, result.period_low), (53.0, 49.0));
+        assert_eq!(result.gap_pct, 2.0);
+        assert_eq!(result.from_low_pct, (52.0 - 49.0) / 49.0 * 100.0);
+        assert!(criteria.failures(&result).is_empty());
+
+        let strict = ScanCriteria {
+            min_price: Some(60.0),
+            min_average_volume: Some(2500.0),
+            max_from_high_pct: Some(5.0),
+            min_gap_pct: Some(3.0),
+            ..criteria
+        };
+        let failures = strict.failures(&result);
+        assert_eq!(failures.len(), 3);
+        assert!(failures[0].starts_with("price 52.00 below"));
+
+        let active = ScanCriteria { min_relative_volume: Some(1.5), ..criteria };
+        assert!(active.failures(&result).is_empty());
+        let measured = ScanResult { relative_volume: Some(1.2), ..result.clone() };
+        assert_eq!(active.failures(&measured), ["relative volume 1.20 below 1.50"]);
+
+        assert_eq!(parse_symbols("aapl, MSFT  msft,,nvda"), ["AAPL", "MSFT", "NVDA"]);
+    }
+}
@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+
+use super::market_hours;
+use super::types::{OrderSide, OrderTemplate, StopRule};
+
+/// What a template's stop rules ask for at the current price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopAction {
+    MoveStop { price: f64, rule: StopRule },
+    Exit { rule: StopRule },
+}
+
+impl StopAction {
+    pub fn rule(&self) -> StopRule {
+        match self {
+            StopAction::MoveStop { rule, .. } | StopAction::Exit { rule } => *rule,
+        }
+    }
+}
+
+/// Open profit in R: price move from the entry over the planned risk per share
+pub fn current_r(template: &OrderTemplate, price: f64) -> Option<f64> {
+    if template.quantity <= 0.0 || template.risk_per_trade <= 0.0 {
+        return None;
+    }
+    let risk_per_share = template.risk_per_trade / template.quantity;
+    let gain = match template.side {
+        OrderSide::Long => price - template.entry_price(),
+        OrderSide::Short => template.entry_price() - price,
+    };
+    Some(gain / risk_per_share)
+}
+
+/// The action the rules call for, if any. A due time stop wins; otherwise the tightest
+/// stop any triggered rule proposes, provided it improves on the current stop.
+/// `atr` is only needed by trailing rules, which are skipped without it.
+pub fn evaluate(template: &OrderTemplate, price: f64, atr: Option<f64>, now: DateTime<Utc>) -> Option<StopAction> {
+    if !template.is_active() || !template.has_fills() || template.is_read_only {
+        return None;
+    }
+    let r = current_r(template, price)?;
+    let days_held = template.filled_at.map(|filled_at| {
+        market_hours::trading_days_between(market_hours::session_date(filled_at), market_hours::session_date(now))
+    });
+
+    for rule in &template.stop_rules {
+        if let StopRule::TimeStop { days, min_r } = *rule
+            && days_held.is_some_and(|held| held >= days)
+            && r < min_r
+        {
+            return Some(StopAction::Exit { rule: *rule });
+        }
+    }
+
+    let tighter = |a: f64, b: f64| match template.side {
+        OrderSide::Long => a > b,
+        OrderSide::Short => a < b,
+    };
+    let mut best: Option<(f64, StopRule)> = None;
+    for rule in &template.stop_rules {
+        let proposed = match *rule {
+            StopRule::Breakeven { after_r } if r >= after_r => template.breakeven_stop_price(0.0),
+            StopRule::TrailAtr { after_r, atr_fraction } if r >= after_r => {
+                let Some(atr) = atr else { continue };
+                match template.side {
+                    OrderSide::Long => price - atr * atr_fraction,
+                    OrderSide::Short => price + atr * atr_fraction,
+                }
+            }
+            _ => continue,
+        };
+        // A stop through the market would fill at once; leave that to the time stop
+        if !tighter(price, proposed) || !tighter(proposed, template.stop_price) {
+            continue;
+        }
+        if best.is_none_or(|(stop, _)| tighter(proposed, stop)) {
+            best = Some((proposed, *rule));
+        }
+    }
+
+    best.map(|(price, rule)| StopAction::MoveStop { price, rule })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ib::types::{OrderTemplateStatus, TimeInForce, TradingModel};
+    use chrono::TimeZone;
+
+    fn filled_long() -> OrderTemplate {
+        // Entry 100, stop 98: 2.00 risk per share
+        let mut template = OrderTemplate::new(
+            "MSFT long".to_string(),
+            "MSFT".to_string(),
+            OrderSide::Long,
+            100.0,
+            100.0,
+            98.0,
+            TimeInForce::GTC,
+            TradingModel::Breakout,
+        );
+        template.risk_per_trade = 200.0;
+        template.status = OrderTemplateStatus::Filled;
+        template.filled_quantity = 100.0;
+        template.avg_fill_price = Some(100.0);
+        // Monday 2025-07-21, 10:00 EDT
+        template.filled_at = Some(Utc.with_ymd_and_hms(2025, 7, 21, 14, 0, 0).unwrap());
+        template.stop_rules = vec![
+            StopRule::Breakeven { after_r: 1.0 },
+            StopRule::TrailAtr { after_r: 2.0, atr_fraction: 0.5 },
+            StopRule::TimeStop { days: 3, min_r: 0.5 },
+        ];
+        template
+    }
+
+    #[test]
+    fn test_stop_rules() {
+        let template = filled_long();
+        let monday = Utc.with_ymd_and_hms(2025, 7, 21, 18, 0, 0).unwrap();
+        assert_eq!(current_r(&template, 103.0), Some(1.5));
+
+        // Below 1R nothing happens
+        assert_eq!(evaluate(&template, 101.0, Some(4.0), monday), None);
+
+        // +1.5R: breakeven only
+        assert_eq!(
+            evaluate(&template, 103.0, Some(4.0), monday),
+            Some(StopAction::MoveStop { price: 100.0, rule: StopRule::Breakeven { after_r: 1.0 } })
+        );
+
+        // +3R: trailing 2.00 behind price beats breakeven
+        let action = evaluate(&template, 106.0, Some(4.0), monday).unwrap();
+        assert_eq!(action, StopAction::MoveStop { price: 104.0, rule: template.stop_rules[1] });
+        // Without an ATR the trail can't be placed
+        assert!(matches!(evaluate(&template, 106.0, None, monday), Some(StopAction::MoveStop { price: 100.0, .. })));
+
+        // A stop already past the proposal is left alone
+        let mut trailed = template.clone();
+        trailed.stop_price = 105.0;
+        assert_eq!(evaluate(&trailed, 106.0, Some(4.0), monday), None);
+
+        // Thursday is the third trading day: still short of 0.5R, so exit
+        let thursday = Utc.with_ymd_and_hms(2025, 7, 24, 18, 0, 0).unwrap();
+        assert_eq!(evaluate(&template, 100.5, Some(4.0), thursday), Some(StopAction::Exit { rule: template.stop_rules[2] }));
+        assert!(!matches!(evaluate(&template, 103.0, Some(4.0), thursday), Some(StopAction::Exit { .. })));
+
+        // Nothing to manage before the entry fills
+        let mut pending = template.clone();
+        pending.status = OrderTemplateStatus::Active;
+        pending.filled_quantity = 0.0;
+        assert_eq!(evaluate(&pending, 106.0, Some(4.0), monday), None);
+    }
+}
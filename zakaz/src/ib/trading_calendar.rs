@@ -0,0 +1,144 @@
+use chrono::{Datelike, NaiveDate, NaiveTime, Weekday};
+
+/// Closures outside the holiday rules: national days of mourning and weather
+const SPECIAL_CLOSURES: &[(i32, u32, u32)] = &[
+    (2012, 10, 29), // Hurricane Sandy
+    (2012, 10, 30),
+    (2018, 12, 5),  // President George H. W. Bush
+    (2025, 1, 9),   // President Jimmy Carter
+];
+
+/// NYSE early close, exchange local time
+pub fn early_close_time() -> NaiveTime {
+    NaiveTime::from_hms_opt(13, 0, 0).unwrap()
+}
+
+/// A weekday that isn't an exchange holiday
+pub fn is_trading_day(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && holiday_name(date).is_none()
+}
+
+/// The NYSE holiday observed on `date`, if any. Holidays on a Saturday are observed the
+/// Friday before and on a Sunday the Monday after, except New Year's Day, which isn't
+/// made up on the last day of the previous year.
+pub fn holiday_name(date: NaiveDate) -> Option<&'static str> {
+    let year = date.year();
+    if SPECIAL_CLOSURES.contains(&(year, date.month(), date.day())) {
+        return Some("Special closure");
+    }
+
+    let fixed = [
+        (1, 1, "New Year's Day", 0),
+        (6, 19, "Juneteenth", 2022),
+        (7, 4, "Independence Day", 0),
+        (12, 25, "Christmas Day", 0),
+    ];
+    for (month, day, name, since) in fixed {
+        let Some(holiday) = NaiveDate::from_ymd_opt(year, month, day) else {
+            continue;
+        };
+        let observed = match holiday.weekday() {
+            Weekday::Sat if month == 1 => continue,
+            Weekday::Sat => holiday.pred_opt()?,
+            Weekday::Sun => holiday.succ_opt()?,
+            _ => holiday,
+        };
+        if year >= since && observed == date {
+            return Some(name);
+        }
+    }
+
+    let nth = |month, weekday, n| NaiveDate::from_weekday_of_month_opt(year, month, weekday, n);
+    let last_monday_of_may = nth(5, Weekday::Mon, 5).or_else(|| nth(5, Weekday::Mon, 4));
+    let floating = [
+        (nth(1, Weekday::Mon, 3), "Martin Luther King Jr. Day"),
+        (nth(2, Weekday::Mon, 3), "Washington's Birthday"),
+        (easter_sunday(year).and_then(|easter| easter.checked_sub_days(chrono::Days::new(2))), "Good Friday"),
+        (last_monday_of_may, "Memorial Day"),
+        (nth(9, Weekday::Mon, 1), "Labor Day"),
+        (nth(11, Weekday::Thu, 4), "Thanksgiving Day"),
+    ];
+    floating.into_iter()
+        .find(|(holiday, _)| *holiday == Some(date))
+        .map(|(_, name)| name)
+}
+
+/// Trading days that close at 13:00: the day before Independence Day, the day after
+/// Thanksgiving and Christmas Eve
+pub fn is_early_close(date: NaiveDate) -> bool {
+    if !is_trading_day(date) {
+        return false;
+    }
+    let year = date.year();
+    let day_after_thanksgiving = NaiveDate::from_weekday_of_month_opt(year, 11, Weekday::Thu, 4)
+        .and_then(|thanksgiving| thanksgiving.succ_opt());
+    (date.month(), date.day()) == (7, 3)
+        || (date.month(), date.day()) == (12, 24)
+        || Some(date) == day_after_thanksgiving
+}
+
+/// Gregorian Easter Sunday (anonymous Gregorian algorithm)
+fn easter_sunday(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_holidays() {
+        // NYSE 2025 holiday schedule
+        let closed = [
+            date(2025, 1, 1), date(2025, 1, 9), date(2025, 1, 20), date(2025, 2, 17),
+            date(2025, 4, 18), date(2025, 5, 26), date(2025, 6, 19), date(2025, 7, 4),
+            date(2025, 9, 1), date(2025, 11, 27), date(2025, 12, 25),
+        ];
+        for day in closed {
+            assert!(!is_trading_day(day), "{}", day);
+        }
+        assert_eq!(holiday_name(date(2025, 4, 18)), Some("Good Friday"));
+        assert!(is_trading_day(date(2025, 4, 17)));
+        assert!(!is_trading_day(date(2025, 7, 5))); // Saturday
+
+        // Observed on the nearest weekday: Independence Day 2026 is a Saturday,
+        // Christmas 2022 a Sunday
+        assert_eq!(holiday_name(date(2026, 7, 3)), Some("Independence Day"));
+        assert_eq!(holiday_name(date(2022, 12, 26)), Some("Christmas Day"));
+        // New Year's Day 2022 fell on a Saturday and wasn't made up on the Friday
+        assert!(is_trading_day(date(2021, 12, 31)));
+        // No Juneteenth before 2022
+        assert!(is_trading_day(date(2021, 6, 18)));
+
+        assert_eq!(easter_sunday(2024), Some(date(2024, 3, 31)));
+        assert_eq!(easter_sunday(2026), Some(date(2026, 4, 5)));
+    }
+
+    #[test]
+    fn test_early_closes() {
+        assert!(is_early_close(date(2025, 7, 3)));
+        assert!(is_early_close(date(2025, 11, 28)));
+        assert!(is_early_close(date(2025, 12, 24)));
+        assert!(!is_early_close(date(2025, 7, 2)));
+        // July 3, 2026 is the observed Independence Day, not a half day
+        assert!(!is_early_close(date(2026, 7, 3)));
+    }
+}
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid;
+use crate::ib::fx::DEFAULT_CURRENCY;
+use crate::ib::position_sizing::StopRules;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OrderSide {
@@ -9,14 +11,14 @@ pub enum OrderSide {
 }
 
 impl OrderSide {
-    pub fn to_action(&self) -> ibapi::orders::Action {
+    pub fn to_action(self) -> ibapi::orders::Action {
         match self {
             OrderSide::Long => ibapi::orders::Action::Buy,
             OrderSide::Short => ibapi::orders::Action::Sell,
         }
     }
     
-    pub fn stop_action(&self) -> ibapi::orders::Action {
+    pub fn stop_action(self) -> ibapi::orders::Action {
         match self {
             OrderSide::Long => ibapi::orders::Action::Sell,
             OrderSide::Short => ibapi::orders::Action::Buy,
@@ -25,18 +27,137 @@ impl OrderSide {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)] // IB's own names, also what is serialized
 pub enum TimeInForce {
     Day,
     GTC,
+    GTD { expires_at: DateTime<Utc> }, // Good till date
+    OPG,                               // Market/limit on open
+    IOC,                               // Immediate or cancel
 }
 
 impl TimeInForce {
-    pub fn to_string(&self) -> String {
+    /// Rebuild from the IB code; GTD needs its expiry
+    pub fn from_code(code: &str, expires_at: Option<DateTime<Utc>>) -> Result<Self, String> {
+        match code {
+            "DAY" => Ok(TimeInForce::Day),
+            "GTC" => Ok(TimeInForce::GTC),
+            "GTD" => expires_at
+                .map(|expires_at| TimeInForce::GTD { expires_at })
+                .ok_or_else(|| "GTD time in force requires an expiry".to_string()),
+            "OPG" => Ok(TimeInForce::OPG),
+            "IOC" => Ok(TimeInForce::IOC),
+            _ => Err(format!("Unknown time in force '{}'", code)),
+        }
+    }
+    
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
         match self {
-            TimeInForce::Day => "DAY".to_string(),
-            TimeInForce::GTC => "GTC".to_string(),
+            TimeInForce::GTD { expires_at } => Some(*expires_at),
+            _ => None,
         }
     }
+    
+    /// IB `good_till_date` value (UTC), empty unless GTD
+    pub fn ib_good_till_date(&self) -> String {
+        self.expires_at()
+            .map(|t| t.format("%Y%m%d-%H:%M:%S").to_string())
+            .unwrap_or_default()
+    }
+}
+
+impl std::fmt::Display for TimeInForce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeInForce::Day => write!(f, "DAY"),
+            TimeInForce::GTC => write!(f, "GTC"),
+            TimeInForce::GTD { .. } => write!(f, "GTD"),
+            TimeInForce::OPG => write!(f, "OPG"),
+            TimeInForce::IOC => write!(f, "IOC"),
+        }
+    }
+}
+
+impl std::str::FromStr for TimeInForce {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_code(s, None)
+    }
+}
+
+/// How the parent (entry) order is sent. `OrderTemplate::limit_price` is the planned
+/// entry price: the limit for `Limit`, the trigger for `Stop`/`StopLimit`, and only a
+/// reference price for `Market`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum EntryType {
+    #[default]
+    Limit,
+    Market,
+    Stop,
+    StopLimit { limit_offset: f64 }, // Limit placed this far beyond the trigger
+}
+
+impl EntryType {
+    /// IB `order_type` for the parent order
+    pub fn ib_order_type(&self) -> &'static str {
+        match self {
+            EntryType::Limit => "LMT",
+            EntryType::Market => "MKT",
+            EntryType::Stop => "STP",
+            EntryType::StopLimit { .. } => "STP LMT",
+        }
+    }
+    
+    pub fn from_ib_order_type(order_type: &str, limit_offset: Option<f64>) -> Result<Self, String> {
+        match order_type {
+            "LMT" => Ok(EntryType::Limit),
+            "MKT" => Ok(EntryType::Market),
+            "STP" => Ok(EntryType::Stop),
+            "STP LMT" => Ok(EntryType::StopLimit { limit_offset: limit_offset.unwrap_or(0.0) }),
+            _ => Err(format!("Unknown entry type '{}'", order_type)),
+        }
+    }
+    
+    pub fn limit_offset(&self) -> Option<f64> {
+        match self {
+            EntryType::StopLimit { limit_offset } => Some(*limit_offset),
+            _ => None,
+        }
+    }
+}
+
+/// Maximum number of exit tranches per template
+pub const MAX_EXIT_TRANCHES: usize = 4;
+
+/// How an exit tranche leaves the trade
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TrancheExit {
+    Target { r_multiple: f64 },     // Limit at entry ± r_multiple × initial risk
+    Trailing { trail_amount: f64 }, // Trailing-stop runner; replaces the fixed stop for its shares
+}
+
+/// Part of the position exited on its own order, e.g. 50% at 1R
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ExitTranche {
+    pub fraction: f64,                 // Share of the template quantity, 0..=1
+    pub exit: TrancheExit,
+    #[serde(default)]
+    pub filled_quantity: f64,          // Shares exited so far
+}
+
+impl ExitTranche {
+    pub fn target(fraction: f64, r_multiple: f64) -> Self {
+        Self { fraction, exit: TrancheExit::Target { r_multiple }, filled_quantity: 0.0 }
+    }
+    
+    pub fn trailing(fraction: f64, trail_amount: f64) -> Self {
+        Self { fraction, exit: TrancheExit::Trailing { trail_amount }, filled_quantity: 0.0 }
+    }
+    
+    pub fn is_trailing(&self) -> bool {
+        matches!(self.exit, TrancheExit::Trailing { .. })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -44,47 +165,108 @@ pub enum OrderTemplateStatus {
     Inactive,      // Not sent to IB
     Activating,    // Being sent to IB
     Active,        // Live on IB
+    PartiallyFilled, // Entry partly executed; remainder and exits still live
+    Filled,        // Entry fully executed; exit orders working
+    Closed,        // Exits covered the position; can be archived
     Deactivating,  // Being canceled on IB
     Failed,        // Failed to activate/deactivate
+    Archived,      // Retired; kept for the journal, hidden from the working set
+    Expired,       // Passed expires_at without activating; needs re-analysis
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub enum TradingModel {
+    #[default]
     Breakout,      // Price breaks through resistance/support
     FalseBreakout, // Failed breakout, reversal trade
     Bounce,        // Price bounces off support/resistance
     Continuation,  // Trend continuation pattern
 }
 
-impl Default for TradingModel {
-    fn default() -> Self {
-        TradingModel::Breakout
+/// How auto-sizing turns the plan into a share quantity
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SizingStrategy {
+    #[default]
+    FixedRisk,                                  // risk_per_trade / stop distance
+    VolatilityNormalized { atr_multiple: f64 }, // risk_per_trade / (ATR x multiple): equal volatility per position
+    FractionalKelly { fraction: f64 },          // Risk `fraction` of the Kelly share of equity, from journal statistics
+}
+
+/// Automatic stop management for a filled template, measured in R from the entry
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StopRule {
+    Breakeven { after_r: f64 },                   // Stop to entry once price is `after_r` in profit; fires once
+    TrailAtr { after_r: f64, atr_fraction: f64 }, // Past `after_r`, keep the stop `atr_fraction` of ATR behind price
+    TimeStop { days: u32, min_r: f64 },           // Exit at market after `days` trading days below `min_r`; fires once
+}
+
+impl StopRule {
+    /// One-shot rules are removed from the template once they act
+    pub fn fires_once(&self) -> bool {
+        !matches!(self, StopRule::TrailAtr { .. })
     }
 }
 
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderTemplate {
     pub id: String,                    // Local template ID
     pub name: String,                  // User-friendly name
     pub symbol: String,                // Stock symbol
+    #[serde(default = "default_currency")]
+    pub currency: String,              // Quote currency of the contract, e.g. USD or EUR
     pub side: OrderSide,               // Long or Short
     pub quantity: f64,                 // Number of shares
     pub limit_price: f64,              // Entry limit price
     pub stop_price: f64,               // Stop loss price (calculated)
     pub technical_stop_price: Option<f64>, // Technical adjustment stop
-    pub time_in_force: TimeInForce,   // DAY or GTC for main order
+    pub time_in_force: TimeInForce,   // Main order TIF; the stop is always GTC
+    #[serde(default)]
+    pub entry_type: EntryType,         // Parent order type
+    #[serde(default)]
+    pub outside_rth: bool,             // Entry may fill outside regular trading hours
+    #[serde(default)]
+    pub exit_tranches: Vec<ExitTranche>, // Scale-out plan; empty exits everything on the stop
+    #[serde(default)]
+    pub oca_group: Option<String>,     // Shared with a paired template; one entry filling cancels the other
     pub status: OrderTemplateStatus,   // Current status
     pub parent_order_id: Option<i32>,  // IB order ID when active
     pub stop_order_id: Option<i32>,    // IB stop order ID when active
+    #[serde(default)]
+    pub tranche_order_ids: Vec<i32>,   // IB exit order IDs when active, one per tranche
+    #[serde(default)]
+    pub filled_quantity: f64,          // Entry shares executed
+    #[serde(default)]
+    pub avg_fill_price: Option<f64>,   // Average entry execution price
+    #[serde(default)]
+    pub filled_at: Option<DateTime<Utc>>, // First entry execution seen
     pub created_at: DateTime<Utc>,     // When template was created
     pub activated_at: Option<DateTime<Utc>>, // When last activated
+    #[serde(default)]
+    pub activate_at: Option<DateTime<Utc>>,  // Scheduled automatic activation
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,   // Unactivated setups go stale after this
+    #[serde(default)]
+    pub archived_at: Option<DateTime<Utc>>,  // When archived
     pub notes: Option<String>,         // User notes
     pub model: TradingModel,           // Trading model/strategy type
     pub is_read_only: bool,            // For IB positions without templates
     pub risk_per_trade: f64,           // Risk amount for position sizing
+    #[serde(default)]
+    pub sizing_strategy: SizingStrategy, // Used when the quantity is auto-sized
+    #[serde(default)]
+    pub stop_rules: Vec<StopRule>,     // Applied by the scheduler once the entry fills
+    #[serde(default)]
+    pub checklist: Vec<ChecklistAnswer>, // Pre-trade checklist as last submitted
+    #[serde(default)]
+    pub short_availability: Option<ShortAvailability>, // Borrow status when last checked (shorts only)
 }
 
 impl OrderTemplate {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: String,
         symbol: String,
@@ -99,78 +281,976 @@ impl OrderTemplate {
             id: uuid::Uuid::new_v4().to_string(),
             name,
             symbol,
+            currency: DEFAULT_CURRENCY.to_string(),
             side,
             quantity,
             limit_price,
             stop_price,
             technical_stop_price: None,
             time_in_force,
+            entry_type: EntryType::default(),
+            outside_rth: false,
+            exit_tranches: Vec::new(),
+            oca_group: None,
             status: OrderTemplateStatus::Inactive,
             parent_order_id: None,
             stop_order_id: None,
+            tranche_order_ids: Vec::new(),
+            filled_quantity: 0.0,
+            avg_fill_price: None,
+            filled_at: None,
             created_at: Utc::now(),
             activated_at: None,
+            activate_at: None,
+            expires_at: None,
+            archived_at: None,
             notes: None,
             model,
             is_read_only: false,
             risk_per_trade: 100.0, // Default risk per trade
+            sizing_strategy: SizingStrategy::default(),
+            stop_rules: Vec::new(),
+            checklist: Vec::new(),
+            short_availability: None,
+        }
+    }
+    
+    /// Orders are live on IB, whether or not the entry has executed
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.status,
+            OrderTemplateStatus::Active | OrderTemplateStatus::PartiallyFilled | OrderTemplateStatus::Filled
+        )
+    }
+    
+    /// Holds a position: the entry executed and the exits haven't closed it yet
+    pub fn has_fills(&self) -> bool {
+        self.filled_quantity > 0.0 && self.is_active()
+    }
+    
+    pub fn is_closed(&self) -> bool {
+        matches!(self.status, OrderTemplateStatus::Closed)
+    }
+    
+    /// Out of the working set's way: never activated, expired or done trading
+    pub fn can_archive(&self) -> bool {
+        self.can_activate() || self.is_expired() || self.is_closed()
+    }
+    
+    /// `exits` (quantity, average price) add up to every entry share filled
+    pub fn is_flat_after(&self, exits: &[(f64, f64)]) -> bool {
+        let exited: f64 = exits.iter().map(|(quantity, _)| quantity).sum();
+        self.filled_quantity > 0.0 && exited >= self.filled_quantity
+    }
+    
+    pub fn can_activate(&self) -> bool {
+        matches!(self.status, OrderTemplateStatus::Inactive | OrderTemplateStatus::Failed)
+    }
+    
+    /// Once the entry executes, cancelling the bracket would leave the position without its stop.
+    /// Read-only templates mirror orders placed elsewhere and are never cancelled from here.
+    pub fn can_deactivate(&self) -> bool {
+        matches!(self.status, OrderTemplateStatus::Active) && !self.is_read_only
+    }
+    
+    /// Record the cumulative entry execution. Returns false if nothing changed.
+    pub fn apply_entry_fill(&mut self, filled_quantity: f64, avg_fill_price: f64, now: DateTime<Utc>) -> bool {
+        if filled_quantity <= 0.0
+            || (filled_quantity == self.filled_quantity && self.avg_fill_price == Some(avg_fill_price))
+        {
+            return false;
+        }
+        
+        self.filled_quantity = filled_quantity;
+        self.avg_fill_price = Some(avg_fill_price);
+        self.filled_at.get_or_insert(now);
+        self.status = if filled_quantity >= self.quantity {
+            OrderTemplateStatus::Filled
+        } else {
+            OrderTemplateStatus::PartiallyFilled
+        };
+        true
+    }
+    
+    /// Actual entry price once filled, otherwise the planned one
+    pub fn entry_price(&self) -> f64 {
+        self.avg_fill_price.unwrap_or(self.limit_price)
+    }
+    
+    pub fn is_archived(&self) -> bool {
+        matches!(self.status, OrderTemplateStatus::Archived)
+    }
+    
+    pub fn is_expired(&self) -> bool {
+        matches!(self.status, OrderTemplateStatus::Expired)
+    }
+    
+    /// Not yet activated and past its expiry
+    pub fn should_expire(&self, now: DateTime<Utc>) -> bool {
+        self.can_activate() && self.expires_at.is_some_and(|at| at <= now)
+    }
+    
+    /// Scheduled activation time has passed and the template can still be armed
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.activate_at.is_some_and(|at| at <= now) && self.can_activate()
+    }
+    
+    pub fn validate(&self) -> Result<(), String> {
+        if self.quantity <= 0.0 {
+            return Err("Quantity must be positive".to_string());
+        }
+        
+        if self.limit_price <= 0.0 {
+            return Err("Limit price must be positive".to_string());
+        }
+        
+        if self.stop_price <= 0.0 {
+            return Err("Stop price must be positive".to_string());
+        }
+        
+        if let Some(offset) = self.entry_type.limit_offset()
+            && offset < 0.0 {
+            return Err("Stop-limit offset cannot be negative".to_string());
+        }
+        
+        match self.sizing_strategy {
+            SizingStrategy::VolatilityNormalized { atr_multiple } if atr_multiple <= 0.0 => {
+                return Err("ATR multiple must be positive".to_string());
+            }
+            SizingStrategy::FractionalKelly { fraction } if fraction <= 0.0 || fraction > 1.0 => {
+                return Err("Kelly fraction must be in (0, 1]".to_string());
+            }
+            _ => {}
+        }
+        
+        for rule in &self.stop_rules {
+            match *rule {
+                StopRule::Breakeven { after_r } if after_r <= 0.0 => {
+                    return Err("Breakeven rule must trigger at a positive R".to_string());
+                }
+                StopRule::TrailAtr { after_r, atr_fraction } if after_r <= 0.0 || atr_fraction <= 0.0 => {
+                    return Err("Trailing rule needs a positive trigger R and ATR fraction".to_string());
+                }
+                StopRule::TimeStop { days: 0, .. } => {
+                    return Err("Time stop must allow at least one day".to_string());
+                }
+                _ => {}
+            }
+        }
+        
+        match self.time_in_force {
+            TimeInForce::GTD { expires_at } if expires_at <= Utc::now() => {
+                return Err("GTD expiry must be in the future".to_string());
+            }
+            TimeInForce::OPG if !matches!(self.entry_type, EntryType::Limit | EntryType::Market) => {
+                return Err("OPG is only valid for limit or market entries".to_string());
+            }
+            _ => {}
+        }
+        
+        self.validate_exit_tranches()?;
+        
+        // A schedule that fires after expiry would never run
+        if let (Some(activate_at), Some(expires_at)) = (self.activate_at, self.expires_at)
+            && activate_at >= expires_at {
+            return Err("Scheduled activation must come before the template expires".to_string());
+        }
+        
+        // IB only works limit-priced orders outside RTH; a plain stop would sit until the open
+        if self.outside_rth {
+            if !matches!(self.entry_type, EntryType::Limit | EntryType::StopLimit { .. }) {
+                return Err("Outside-RTH entries must be limit or stop-limit orders".to_string());
+            }
+            if self.time_in_force == TimeInForce::OPG {
+                return Err("OPG orders cannot be flagged outside RTH".to_string());
+            }
+        }
+        
+        // Validate stop placement relative to side; once live the stop may trail past the entry
+        match self.side {
+            _ if self.is_active() || self.is_closed() => {}
+            OrderSide::Long => {
+                if self.stop_price >= self.limit_price {
+                    return Err("For long orders, stop price must be below limit price".to_string());
+                }
+            }
+            OrderSide::Short => {
+                if self.stop_price <= self.limit_price {
+                    return Err("For short orders, stop price must be above limit price".to_string());
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    fn validate_exit_tranches(&self) -> Result<(), String> {
+        if self.exit_tranches.len() > MAX_EXIT_TRANCHES {
+            return Err(format!("At most {} exit tranches are allowed", MAX_EXIT_TRANCHES));
+        }
+        if self.exit_tranches.iter().filter(|t| t.is_trailing()).count() > 1 {
+            return Err("Only one trailing runner is allowed".to_string());
+        }
+        
+        for (tranche, quantity) in self.exit_tranches.iter().zip(self.tranche_quantities()) {
+            if tranche.fraction <= 0.0 || tranche.fraction > 1.0 {
+                return Err("Tranche fractions must be between 0 and 1".to_string());
+            }
+            if quantity < 1.0 {
+                return Err(format!("A {:.0}% tranche is less than one share", tranche.fraction * 100.0));
+            }
+            match tranche.exit {
+                TrancheExit::Target { r_multiple } if r_multiple <= 0.0 => {
+                    return Err("Tranche targets must be above 0R".to_string());
+                }
+                TrancheExit::Trailing { trail_amount } if trail_amount <= 0.0 => {
+                    return Err("Trailing amount must be positive".to_string());
+                }
+                _ => {}
+            }
+        }
+        
+        let total: f64 = self.exit_tranches.iter().map(|t| t.fraction).sum();
+        if total > 1.0 + 1e-9 {
+            return Err("Tranche fractions add up to more than 100%".to_string());
+        }
+        
+        Ok(())
+    }
+    
+    /// Check two templates can be armed as a one-cancels-all entry pair
+    pub fn validate_oco_pair(&self, other: &OrderTemplate) -> Result<(), String> {
+        if self.symbol != other.symbol {
+            return Err("OCO pair templates must be on the same symbol".to_string());
+        }
+        
+        // A long above and a short below the range: entries must not overlap
+        let (long, short) = match (self.side, other.side) {
+            (OrderSide::Long, OrderSide::Short) => (self, other),
+            (OrderSide::Short, OrderSide::Long) => (other, self),
+            _ => return Ok(()),
+        };
+        let stop_entries = |t: &OrderTemplate| matches!(t.entry_type, EntryType::Stop | EntryType::StopLimit { .. });
+        if stop_entries(long) && stop_entries(short) && long.limit_price <= short.limit_price {
+            return Err("Long stop entry must be above the short stop entry".to_string());
+        }
+        Ok(())
+    }
+    
+    /// Stop at the entry price, `offset` into profit
+    pub fn breakeven_stop_price(&self, offset: f64) -> f64 {
+        match self.side {
+            OrderSide::Long => self.entry_price() + offset,
+            OrderSide::Short => self.entry_price() - offset,
+        }
+    }
+    
+    /// Capital committed by the full planned size at the entry
+    /// The IB stock contract orders are placed on, in the template's currency
+    pub fn contract(&self) -> ibapi::contracts::Contract {
+        ibapi::contracts::Contract {
+            currency: self.currency.clone(),
+            ..ibapi::contracts::Contract::stock(&self.symbol)
+        }
+    }
+    
+    pub fn notional(&self) -> f64 {
+        self.quantity * self.entry_price()
+    }
+    
+    /// Loss if the live stop is hit on the full planned size; none once the stop locks in profit
+    pub fn open_risk(&self) -> f64 {
+        let distance = match self.side {
+            OrderSide::Long => self.entry_price() - self.stop_price,
+            OrderSide::Short => self.stop_price - self.entry_price(),
+        };
+        distance.max(0.0) * self.quantity
+    }
+    
+    /// Initial risk per share: distance from entry to the effective stop
+    pub fn risk_per_share(&self) -> f64 {
+        (self.limit_price - self.get_stop_loss()).abs()
+    }
+    
+    /// Whole-share quantity of each exit tranche
+    pub fn tranche_quantities(&self) -> Vec<f64> {
+        self.exit_tranches.iter()
+            .map(|t| (self.quantity * t.fraction).floor())
+            .collect()
+    }
+    
+    /// Limit price of a target tranche; None for the trailing runner
+    pub fn tranche_target_price(&self, tranche: &ExitTranche) -> Option<f64> {
+        match tranche.exit {
+            TrancheExit::Target { r_multiple } => {
+                let distance = r_multiple * self.risk_per_share();
+                Some(match self.side {
+                    OrderSide::Long => self.limit_price + distance,
+                    OrderSide::Short => self.limit_price - distance,
+                })
+            }
+            TrancheExit::Trailing { .. } => None,
+        }
+    }
+    
+    /// Shares the fixed stop protects: everything except the trailing runner and exited targets
+    pub fn remaining_stop_quantity(&self) -> f64 {
+        self.exit_tranches.iter()
+            .zip(self.tranche_quantities())
+            .fold(self.quantity, |remaining, (tranche, quantity)| {
+                if tranche.is_trailing() {
+                    remaining - quantity
+                } else {
+                    remaining - tranche.filled_quantity
+                }
+            })
+            .max(0.0)
+    }
+    
+    /// Order ids of the target tranches still waiting to exit shares; the runner isn't one
+    pub fn open_target_order_ids(&self) -> Vec<i32> {
+        self.exit_tranches.iter()
+            .zip(self.tranche_quantities())
+            .zip(&self.tranche_order_ids)
+            .filter(|((tranche, quantity), _)| !tranche.is_trailing() && tranche.filled_quantity < *quantity)
+            .map(|(_, order_id)| *order_id)
+            .collect()
+    }
+    
+    /// Re-check the plan against the current price before arming it unattended
+    pub fn validate_against_price(&self, current_price: f64) -> Result<(), String> {
+        self.validate()?;
+        
+        let stop = self.get_stop_loss();
+        match self.side {
+            OrderSide::Long if current_price <= stop => return Err(format!(
+                "{} is trading at {:.2}, at or below the stop {:.2}", self.symbol, current_price, stop
+            )),
+            OrderSide::Short if current_price >= stop => return Err(format!(
+                "{} is trading at {:.2}, at or above the stop {:.2}", self.symbol, current_price, stop
+            )),
+            _ => {}
+        }
+        
+        // A stop entry that is already through its trigger would fill as a market order
+        if matches!(self.entry_type, EntryType::Stop | EntryType::StopLimit { .. }) {
+            let triggered = match self.side {
+                OrderSide::Long => current_price >= self.limit_price,
+                OrderSide::Short => current_price <= self.limit_price,
+            };
+            if triggered {
+                return Err(format!(
+                    "{} is trading at {:.2}, already through the entry trigger {:.2}",
+                    self.symbol, current_price, self.limit_price
+                ));
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Distance of the planned entry from `reference_price`, in percent
+    pub fn price_deviation_pct(&self, reference_price: f64) -> f64 {
+        (self.limit_price - reference_price).abs() / reference_price * 100.0
+    }
+    
+    /// Limit price for the parent order, if its type takes one
+    pub fn entry_limit_price(&self) -> Option<f64> {
+        match self.entry_type {
+            EntryType::Limit => Some(self.limit_price),
+            EntryType::StopLimit { limit_offset } => Some(match self.side {
+                OrderSide::Long => self.limit_price + limit_offset,
+                OrderSide::Short => self.limit_price - limit_offset,
+            }),
+            EntryType::Market | EntryType::Stop => None,
+        }
+    }
+    
+    /// Trigger (aux) price for the parent order, if its type takes one
+    pub fn entry_trigger_price(&self) -> Option<f64> {
+        match self.entry_type {
+            EntryType::Stop | EntryType::StopLimit { .. } => Some(self.limit_price),
+            EntryType::Limit | EntryType::Market => None,
+        }
+    }
+    
+    /// Copy of this plan ready to be armed again: fresh id, inactive, no IB orders
+    pub fn duplicate(&self, new_name: Option<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: new_name.unwrap_or_else(|| format!("{} (copy)", self.name)),
+            status: OrderTemplateStatus::Inactive,
+            parent_order_id: None,
+            stop_order_id: None,
+            tranche_order_ids: Vec::new(),
+            exit_tranches: self.exit_tranches.iter()
+                .map(|t| ExitTranche { filled_quantity: 0.0, ..*t })
+                .collect(),
+            oca_group: None,
+            filled_quantity: 0.0,
+            avg_fill_price: None,
+            filled_at: None,
+            created_at: Utc::now(),
+            activated_at: None,
+            activate_at: None,
+            expires_at: None,
+            archived_at: None,
+            is_read_only: false,
+            checklist: Vec::new(),
+            ..self.clone()
+        }
+    }
+    
+    /// The checklist for this template's model, with any answers already given
+    pub fn checklist_state(&self, items: &[ChecklistItem]) -> Vec<ChecklistAnswer> {
+        let mut applicable: Vec<&ChecklistItem> = items.iter().filter(|i| i.applies_to(self.model)).collect();
+        applicable.sort_by_key(|i| i.position);
+        applicable.into_iter()
+            .map(|item| match self.checklist.iter().find(|a| a.item_id == item.id) {
+                Some(answer) => ChecklistAnswer { text: item.text.clone(), ..answer.clone() },
+                None => ChecklistAnswer::unanswered(item),
+            })
+            .collect()
+    }
+    
+    /// Refuse activation until every checklist item for the model is ticked
+    pub fn check_checklist(&self, items: &[ChecklistItem]) -> Result<(), String> {
+        let open: Vec<String> = self.checklist_state(items).into_iter()
+            .filter(|a| !a.checked)
+            .map(|a| a.text)
+            .collect();
+        if open.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Pre-trade checklist incomplete: {}", open.join("; ")))
+        }
+    }
+    
+    pub fn get_stop_loss(&self) -> f64 {
+        // Return technical stop if set, otherwise use calculated stop
+        self.technical_stop_price.unwrap_or(self.stop_price)
+    }
+    
+    /// Plan fields tracked in the revision history, rendered for display
+    fn plan_fields(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("name", Some(self.name.clone())),
+            ("symbol", Some(self.symbol.clone())),
+            ("currency", Some(self.currency.clone())),
+            ("side", Some(format!("{:?}", self.side))),
+            ("quantity", Some(self.quantity.to_string())),
+            ("limit_price", Some(self.limit_price.to_string())),
+            ("stop_price", Some(self.stop_price.to_string())),
+            ("technical_stop_price", self.technical_stop_price.map(|p| p.to_string())),
+            ("time_in_force", Some(self.time_in_force.to_string())),
+            ("time_in_force_expires_at", self.time_in_force.expires_at().map(|t| t.to_rfc3339())),
+            ("entry_type", Some(self.entry_type.ib_order_type().to_string())),
+            ("entry_limit_offset", self.entry_type.limit_offset().map(|o| o.to_string())),
+            ("outside_rth", Some(self.outside_rth.to_string())),
+            ("exit_tranches", (!self.exit_tranches.is_empty()).then(|| self.exit_tranches_summary())),
+            ("stop_rules", (!self.stop_rules.is_empty()).then(|| format!("{:?}", self.stop_rules))),
+            ("model", Some(format!("{:?}", self.model))),
+            ("risk_per_trade", Some(self.risk_per_trade.to_string())),
+            ("notes", self.notes.clone()),
+            ("activate_at", self.activate_at.map(|t| t.to_rfc3339())),
+            ("expires_at", self.expires_at.map(|t| t.to_rfc3339())),
+        ]
+    }
+    
+    /// e.g. "50%@1R, 25%@2R, 25% trail 0.5"
+    fn exit_tranches_summary(&self) -> String {
+        self.exit_tranches.iter()
+            .map(|t| match t.exit {
+                TrancheExit::Target { r_multiple } => format!("{}%@{}R", t.fraction * 100.0, r_multiple),
+                TrancheExit::Trailing { trail_amount } => format!("{}% trail {}", t.fraction * 100.0, trail_amount),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+    
+    /// Field-level changes between `self` (before) and `updated` (after)
+    pub fn diff(&self, updated: &OrderTemplate) -> Vec<TemplateFieldChange> {
+        self.plan_fields()
+            .into_iter()
+            .zip(updated.plan_fields())
+            .filter(|((_, old), (_, new))| old != new)
+            .map(|((field, old_value), (_, new_value))| TemplateFieldChange {
+                field: field.to_string(),
+                old_value,
+                new_value,
+            })
+            .collect()
+    }
+    
+    /// The initial plan, expressed as changes from nothing
+    pub fn initial_revision(&self) -> Vec<TemplateFieldChange> {
+        self.plan_fields()
+            .into_iter()
+            .filter(|(_, value)| value.is_some())
+            .map(|(field, new_value)| TemplateFieldChange {
+                field: field.to_string(),
+                old_value: None,
+                new_value,
+            })
+            .collect()
+    }
+}
+
+/// Which order of a template an IB order is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderLeg {
+    Entry,
+    Stop,
+    Tranche(usize), // Index into exit_tranches
+}
+
+impl std::fmt::Display for OrderLeg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderLeg::Entry => write!(f, "entry"),
+            OrderLeg::Stop => write!(f, "stop"),
+            OrderLeg::Tranche(index) => write!(f, "tranche{}", index),
+        }
+    }
+}
+
+impl OrderLeg {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "entry" => Some(OrderLeg::Entry),
+            "stop" => Some(OrderLeg::Stop),
+            _ => s.strip_prefix("tranche")?.parse().ok().map(OrderLeg::Tranche),
+        }
+    }
+}
+
+/// IB `order_ref` tagging an order as placed by zakaz: `zakaz:<template id>:<leg>:<name>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderRef {
+    pub template_id: String,
+    pub leg: OrderLeg,
+    pub name: String,
+}
+
+impl OrderRef {
+    const PREFIX: &'static str = "zakaz";
+    /// Keep refs readable in TWS columns
+    const MAX_NAME_CHARS: usize = 40;
+    
+    pub fn new(template: &OrderTemplate, leg: OrderLeg) -> Self {
+        Self {
+            template_id: template.id.clone(),
+            leg,
+            name: template.name.chars().take(Self::MAX_NAME_CHARS).collect(),
+        }
+    }
+    
+    /// Parse an `order_ref` read back from IB; None for orders not placed by zakaz
+    pub fn parse(order_ref: &str) -> Option<Self> {
+        let mut parts = order_ref.splitn(4, ':');
+        if parts.next()? != Self::PREFIX {
+            return None;
+        }
+        let template_id = parts.next().filter(|id| !id.is_empty())?.to_string();
+        let leg = OrderLeg::parse(parts.next()?)?;
+        let name = parts.next().unwrap_or_default().to_string();
+        Some(Self { template_id, leg, name })
+    }
+}
+
+impl std::fmt::Display for OrderRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}:{}", Self::PREFIX, self.template_id, self.leg, self.name)
+    }
+}
+
+/// Named group of templates that are activated/deactivated together
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateBasket {
+    pub id: String,
+    pub name: String,
+    pub template_ids: Vec<String>,     // In activation order
+    pub created_at: DateTime<Utc>,
+}
+
+/// User-defined group of correlated symbols sharing exposure caps
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sector {
+    pub name: String,
+    pub max_notional: Option<f64>,
+    pub max_risk: Option<f64>,
+    pub symbols: Vec<String>,
+}
+
+impl Sector {
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.symbols.iter().any(|s| s.eq_ignore_ascii_case(symbol))
+    }
+}
+
+/// Overrides of the global settings for one symbol; None falls back to them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SymbolSettings {
+    pub symbol: String,
+    pub risk_per_trade: Option<f64>, // Over the model's and global risk when sizing
+    pub atr_period: Option<usize>,   // Over `atr_period`, for stops, sizing and scans
+    pub tick_size: Option<f64>,      // New templates' prices are rounded to it
+    pub sector: Option<String>,      // The symbol's `Sector`, shared with `AssignSymbolSector`
+    pub notes: Option<String>,
+}
+
+impl SymbolSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.symbol.trim().is_empty() {
+            return Err("Symbol cannot be empty".to_string());
+        }
+        if let Some(risk) = self.risk_per_trade
+            && !(risk.is_finite() && risk > 0.0) {
+            return Err(format!("Risk per trade must be positive, not {}", risk));
+        }
+        if self.atr_period.is_some_and(|period| period < 2) {
+            return Err("ATR period must be at least 2".to_string());
+        }
+        if let Some(tick) = self.tick_size
+            && !(tick.is_finite() && tick > 0.0) {
+            return Err(format!("Tick size must be positive, not {}", tick));
+        }
+        Ok(())
+    }
+    
+    /// `price` on the symbol's tick grid, or as is without a tick size
+    pub fn round_to_tick(&self, price: f64) -> f64 {
+        match self.tick_size {
+            // Rounding the tick count first keeps 0.05 ticks from landing on 12.350000000000001
+            Some(tick) => {
+                let ticks = (price / tick).round();
+                let decimals = (-tick.log10().floor()).max(0.0) as i32 + 1;
+                let scale = 10f64.powi(decimals);
+                (ticks * tick * scale).round() / scale
+            }
+            None => price,
+        }
+    }
+}
+
+/// A symbol kept under watch; scans and volatility alerts cover it without a template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchlistEntry {
+    pub symbol: String,
+    pub note: Option<String>,
+    pub added_at: DateTime<Utc>,
+}
+
+/// The watchlist edits go to when none is named; it can't be deleted
+pub const DEFAULT_WATCHLIST_ID: &str = "default";
+
+/// A named list of watched symbols, in the order the trader keeps them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Watchlist {
+    pub id: String,
+    pub name: String,
+    pub entries: Vec<WatchlistEntry>,
+}
+
+/// Each watched symbol with its filtered ATR, or why there is none
+pub type WatchlistAtr = Vec<(String, Result<ATRResult, String>)>;
+
+/// One execution as reported by IB, kept so trade history can be shown after the session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub exec_id: String,             // Without IB's correction suffix, so a correction replaces the original
+    pub order_id: i32,
+    pub template_id: Option<String>, // The template that placed the order, when zakaz did
+    pub symbol: String,
+    pub side: OrderSide,             // Long for bought, Short for sold
+    pub quantity: f64,
+    pub price: f64,
+    pub time: DateTime<Utc>,
+    pub commission: Option<f64>,     // From IB's commission report, None until it arrives
+}
+
+/// IB's borrow availability for a short sale (generic tick 236)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShortAvailability {
+    Easy,
+    HardToBorrow, // Needs a locate; borrow fees may be high
+    Unavailable,
+}
+
+impl ShortAvailability {
+    /// Above 2.5 shares can be borrowed, above 1.5 only after a locate, otherwise not at all
+    pub fn from_shortable_tick(value: f64) -> Self {
+        if value > 2.5 {
+            ShortAvailability::Easy
+        } else if value > 1.5 {
+            ShortAvailability::HardToBorrow
+        } else {
+            ShortAvailability::Unavailable
+        }
+    }
+}
+
+/// A pre-trade question every template of `model` must pass before activation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChecklistItem {
+    pub id: String,
+    pub model: Option<TradingModel>, // None asks it of every model
+    pub text: String,                // e.g. "No earnings within 5 days"
+    pub position: i64,               // Display order
+}
+
+impl ChecklistItem {
+    pub fn applies_to(&self, model: TradingModel) -> bool {
+        self.model.is_none_or(|m| m == model)
+    }
+}
+
+/// A checklist item as answered for one template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChecklistAnswer {
+    pub item_id: String,
+    pub text: String,                // The question as asked, kept for review
+    pub checked: bool,
+    pub note: Option<String>,
+    pub answered_at: Option<DateTime<Utc>>,
+}
+
+impl ChecklistAnswer {
+    pub fn unanswered(item: &ChecklistItem) -> Self {
+        Self {
+            item_id: item.id.clone(),
+            text: item.text.clone(),
+            checked: false,
+            note: None,
+            answered_at: None,
+        }
+    }
+}
+
+/// Per-template outcome of a basket activation/deactivation
+#[derive(Debug, Clone, Default)]
+pub struct BasketResult {
+    pub basket_id: String,
+    pub succeeded: Vec<String>,              // Template ids
+    pub failed: Vec<(String, String)>,       // (template id, error)
+}
+
+impl BasketResult {
+    pub fn new(basket_id: String) -> Self {
+        Self {
+            basket_id,
+            ..Default::default()
+        }
+    }
+    
+    pub fn total(&self) -> usize {
+        self.succeeded.len() + self.failed.len()
+    }
+    
+    pub fn is_complete_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Stop and size recomputed from fresh volatility, pending user confirmation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepriceProposal {
+    pub template_id: String,
+    pub filtered_atr: f64,
+    pub risk_per_trade: f64,
+    pub current_stop_price: f64,
+    pub proposed_stop_price: f64,
+    pub current_quantity: f64,
+    pub proposed_quantity: f64,
+}
+
+impl RepriceProposal {
+    /// Default ATR stop from the entry, then size the position to `risk_per_trade`.
+    /// A technical stop still takes precedence for sizing, as it does on the live order.
+    pub fn for_template(template: &OrderTemplate, filtered_atr: f64, risk_per_trade: f64, rules: &StopRules) -> Result<Self, String> {
+        let proposed_stop_price = crate::ib::position_sizing::calculate_default_stop_loss(
+            template.limit_price,
+            template.side,
+            filtered_atr,
+            rules,
+        );
+        let sizing_stop = template.technical_stop_price.unwrap_or(proposed_stop_price);
+        let shares = crate::ib::position_sizing::calculate_position_size(
+            risk_per_trade,
+            template.limit_price,
+            sizing_stop,
+            template.side,
+        )?;
+        
+        Ok(Self {
+            template_id: template.id.clone(),
+            filtered_atr,
+            risk_per_trade,
+            current_stop_price: template.stop_price,
+            proposed_stop_price,
+            current_quantity: template.quantity,
+            proposed_quantity: shares as f64,
+        })
+    }
+    
+    /// Write the proposed values into the template once the user confirms
+    pub fn apply_to(&self, template: &mut OrderTemplate) {
+        template.stop_price = self.proposed_stop_price;
+        template.quantity = self.proposed_quantity;
+        template.risk_per_trade = self.risk_per_trade;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateFieldChange {
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateRevision {
+    pub template_id: String,
+    pub revision: i64,                 // 1 = the template as created
+    pub changed_at: DateTime<Utc>,
+    pub changes: Vec<TemplateFieldChange>,
+}
+
+/// What happened to a template or its IB orders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Archive,
+    Restore,
+    Activate,
+    Deactivate,
+    Modify,     // Live order changed (e.g. stop moved)
+    Fill,
+    Cancel,     // Orders cancelled without a deactivate request
+    Close,      // Exits covered the position
+    Expire,
+    Adopt,      // IB order/position zakaz didn't place, materialized read-only
+    Claim,      // Read-only template taken over for management
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "Create",
+            AuditAction::Update => "Update",
+            AuditAction::Delete => "Delete",
+            AuditAction::Archive => "Archive",
+            AuditAction::Restore => "Restore",
+            AuditAction::Activate => "Activate",
+            AuditAction::Deactivate => "Deactivate",
+            AuditAction::Modify => "Modify",
+            AuditAction::Fill => "Fill",
+            AuditAction::Cancel => "Cancel",
+            AuditAction::Close => "Close",
+            AuditAction::Expire => "Expire",
+            AuditAction::Adopt => "Adopt",
+            AuditAction::Claim => "Claim",
         }
     }
     
-    pub fn is_active(&self) -> bool {
-        matches!(self.status, OrderTemplateStatus::Active)
-    }
-    
-    pub fn can_activate(&self) -> bool {
-        matches!(self.status, OrderTemplateStatus::Inactive | OrderTemplateStatus::Failed)
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Create" => Some(AuditAction::Create),
+            "Update" => Some(AuditAction::Update),
+            "Delete" => Some(AuditAction::Delete),
+            "Archive" => Some(AuditAction::Archive),
+            "Restore" => Some(AuditAction::Restore),
+            "Activate" => Some(AuditAction::Activate),
+            "Deactivate" => Some(AuditAction::Deactivate),
+            "Modify" => Some(AuditAction::Modify),
+            "Fill" => Some(AuditAction::Fill),
+            "Cancel" => Some(AuditAction::Cancel),
+            "Close" => Some(AuditAction::Close),
+            "Expire" => Some(AuditAction::Expire),
+            "Adopt" => Some(AuditAction::Adopt),
+            "Claim" => Some(AuditAction::Claim),
+            _ => None,
+        }
     }
-    
-    pub fn can_deactivate(&self) -> bool {
-        matches!(self.status, OrderTemplateStatus::Active)
+}
+
+/// Who initiated an audited action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditSource {
+    UI,
+    Scheduler,
+    Reconciliation, // Syncing state back from IB
+}
+
+impl AuditSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditSource::UI => "UI",
+            AuditSource::Scheduler => "Scheduler",
+            AuditSource::Reconciliation => "Reconciliation",
+        }
     }
     
-    pub fn validate(&self) -> Result<(), String> {
-        if self.quantity <= 0.0 {
-            return Err("Quantity must be positive".to_string());
-        }
-        
-        if self.limit_price <= 0.0 {
-            return Err("Limit price must be positive".to_string());
-        }
-        
-        if self.stop_price <= 0.0 {
-            return Err("Stop price must be positive".to_string());
-        }
-        
-        // Validate stop placement relative to side
-        match self.side {
-            OrderSide::Long => {
-                if self.stop_price >= self.limit_price {
-                    return Err("For long orders, stop price must be below limit price".to_string());
-                }
-            }
-            OrderSide::Short => {
-                if self.stop_price <= self.limit_price {
-                    return Err("For short orders, stop price must be above limit price".to_string());
-                }
-            }
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "UI" => Some(AuditSource::UI),
+            "Scheduler" => Some(AuditSource::Scheduler),
+            "Reconciliation" => Some(AuditSource::Reconciliation),
+            _ => None,
         }
-        
-        Ok(())
     }
-    
-    pub fn get_stop_loss(&self) -> f64 {
-        // Return technical stop if set, otherwise use calculated stop
-        self.technical_stop_price.unwrap_or(self.stop_price)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub template_id: String,
+    pub action: AuditAction,
+    pub source: AuditSource,
+    pub account_type: Option<String>,  // "Paper"/"Live" when connected
+    pub before: Option<OrderTemplate>,
+    pub after: Option<OrderTemplate>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    /// Plan fields that differ between the before and after snapshots
+    pub fn changes(&self) -> Vec<TemplateFieldChange> {
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => before.diff(after),
+            _ => Vec::new(),
+        }
     }
 }
 
+/// Which audit entries the audit viewer shows; None matches anything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuditFilter {
+    pub template_id: Option<String>,
+    pub symbol: Option<String>,
+    pub action: Option<AuditAction>,
+    pub source: Option<AuditSource>,
+    pub from: Option<DateTime<Utc>>, // Inclusive
+    pub to: Option<DateTime<Utc>>,   // Exclusive
+}
+
+/// One page of matching audit entries, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    pub offset: usize,
+    pub total: usize, // Matching entries on all pages
+}
+
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum OutlierMethod {
     IQR { multiplier: f64 },      // Default 1.5
     ZScore { threshold: f64 },    // Default 2.0
     Percentile { low: f64, high: f64 }, // Default 10th-90th
+    Auto,                         // Picks one of the above from the range distribution
 }
 
 impl Default for OutlierMethod {
@@ -179,7 +1259,42 @@ impl Default for OutlierMethod {
     }
 }
 
-#[derive(Debug, Clone)]
+impl OutlierMethod {
+    /// Method and parameters, e.g. "IQR:1.5" or "Percentile:10-90"
+    pub fn key(&self) -> String {
+        match self {
+            OutlierMethod::IQR { multiplier } => format!("IQR:{}", multiplier),
+            OutlierMethod::ZScore { threshold } => format!("ZScore:{}", threshold),
+            OutlierMethod::Percentile { low, high } => format!("Percentile:{}-{}", low, high),
+            OutlierMethod::Auto => "Auto".to_string(),
+        }
+    }
+    
+    pub fn from_key(key: &str) -> Option<Self> {
+        let (name, params) = key.split_once(':').unwrap_or((key, ""));
+        match name {
+            "IQR" => params.parse().ok().map(|multiplier| OutlierMethod::IQR { multiplier }),
+            "ZScore" => params.parse().ok().map(|threshold| OutlierMethod::ZScore { threshold }),
+            "Percentile" => {
+                let (low, high) = params.split_once('-')?;
+                Some(OutlierMethod::Percentile { low: low.parse().ok()?, high: high.parse().ok()? })
+            }
+            "Auto" => Some(OutlierMethod::Auto),
+            _ => None,
+        }
+    }
+}
+
+/// How True Ranges are averaged into an ATR
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AtrSmoothing {
+    Simple,      // Mean of the last `period` ranges
+    #[default]
+    Wilder,      // RMA: each new range weighs 1/period
+    Exponential, // EMA: each new range weighs 2/(period + 1)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExcludedBar {
     pub date: chrono::DateTime<chrono::Utc>,
     pub range: f64,
@@ -188,7 +1303,7 @@ pub struct ExcludedBar {
     pub low: f64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ATRResult {
     pub symbol: String,
     pub period_days: usize,
@@ -218,6 +1333,9 @@ pub struct ATRResult {
     
     // Details
     pub method: OutlierMethod,
+    pub applied_method: OutlierMethod,   // What filtered the bars: `method` unless that was Auto
+    pub method_rationale: Option<String>, // Why Auto chose `applied_method`
+    pub smoothing: AtrSmoothing,
     pub excluded_bars_detail: Vec<ExcludedBar>,
     pub used_bars_detail: Vec<HistoricalBar>,
     
@@ -227,7 +1345,7 @@ pub struct ATRResult {
 }
 
 impl ATRResult {
-    pub fn new(symbol: String, period_days: usize, method: OutlierMethod) -> Self {
+    pub fn new(symbol: String, period_days: usize, method: OutlierMethod, smoothing: AtrSmoothing) -> Self {
         Self {
             symbol,
             period_days,
@@ -249,6 +1367,9 @@ impl ATRResult {
             lower_bound: 0.0,
             upper_bound: 0.0,
             method,
+            applied_method: method,
+            method_rationale: None,
+            smoothing,
             excluded_bars_detail: Vec::new(),
             used_bars_detail: Vec::new(),
             confidence_score: 0.0,
@@ -320,7 +1441,7 @@ impl HistoricalData {
     }
     
     pub fn sort_by_time(&mut self) {
-        self.bars.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        self.bars.sort_by_key(|bar| bar.timestamp);
     }
 }
 
@@ -365,4 +1486,452 @@ mod tests {
         short_template.stop_price = 145.0;
         assert!(short_template.validate().is_err());
     }
+    
+    #[test]
+    fn test_order_template_duplicate() {
+        let mut original = OrderTemplate::new(
+            "NVDA breakout".to_string(),
+            "NVDA".to_string(),
+            OrderSide::Long,
+            50.0,
+            120.0,
+            118.5,
+            TimeInForce::GTC,
+            TradingModel::Breakout,
+        );
+        original.status = OrderTemplateStatus::Active;
+        original.parent_order_id = Some(1000);
+        original.stop_order_id = Some(1001);
+        original.activated_at = Some(Utc::now());
+        
+        let copy = original.duplicate(None);
+        assert_ne!(copy.id, original.id);
+        assert_eq!(copy.name, "NVDA breakout (copy)");
+        assert_eq!(copy.status, OrderTemplateStatus::Inactive);
+        assert_eq!(copy.parent_order_id, None);
+        assert_eq!(copy.stop_order_id, None);
+        assert_eq!(copy.activated_at, None);
+        assert_eq!(copy.limit_price, original.limit_price);
+        assert_eq!(copy.stop_price, original.stop_price);
+        
+        assert_eq!(original.duplicate(Some("Re-entry".to_string())).name, "Re-entry");
+    }
+    
+    #[test]
+    fn test_checklist() {
+        let mut template = OrderTemplate::new(
+            "AMD bounce".to_string(),
+            "AMD".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            148.0,
+            TimeInForce::Day,
+            TradingModel::Bounce,
+        );
+        let item = |id: &str, model: Option<TradingModel>, position: i64| ChecklistItem {
+            id: id.to_string(),
+            model,
+            text: format!("Question {}", id),
+            position,
+        };
+        let items = vec![
+            item("earnings", None, 2),
+            item("trend", Some(TradingModel::Bounce), 1),
+            item("volume", Some(TradingModel::Breakout), 0),
+        ];
+        
+        // Only the shared and Bounce items apply, in display order
+        let state = template.checklist_state(&items);
+        assert_eq!(state.iter().map(|a| a.item_id.as_str()).collect::<Vec<_>>(), vec!["trend", "earnings"]);
+        assert!(template.check_checklist(&items).unwrap_err().contains("Question trend; Question earnings"));
+        assert!(template.check_checklist(&[]).is_ok());
+        
+        template.checklist = vec![ChecklistAnswer { checked: true, answered_at: Some(Utc::now()), ..ChecklistAnswer::unanswered(&items[1]) }];
+        assert!(template.check_checklist(&items).unwrap_err().ends_with("Question earnings"));
+        template.checklist.push(ChecklistAnswer { checked: true, ..ChecklistAnswer::unanswered(&items[0]) });
+        assert!(template.check_checklist(&items).is_ok());
+        
+        // A re-armed copy answers afresh
+        assert!(template.duplicate(None).check_checklist(&items).is_err());
+    }
+    
+    #[test]
+    fn test_order_template_diff() {
+        let original = OrderTemplate::new(
+            "Diff".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            145.0,
+            TimeInForce::Day,
+            TradingModel::Breakout,
+        );
+        
+        let mut updated = original.clone();
+        assert!(original.diff(&updated).is_empty());
+        
+        updated.stop_price = 146.0;
+        updated.notes = Some("tighter stop".to_string());
+        let changes = original.diff(&updated);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].field, "stop_price");
+        assert_eq!(changes[0].old_value.as_deref(), Some("145"));
+        assert_eq!(changes[0].new_value.as_deref(), Some("146"));
+        assert_eq!(changes[1].field, "notes");
+        assert_eq!(changes[1].old_value, None);
+        
+        // Unset optional fields are left out of the initial snapshot
+        assert!(original.initial_revision().iter().all(|c| c.field != "notes"));
+    }
+    
+    #[test]
+    fn test_time_in_force_options() {
+        let expires_at = Utc::now() + chrono::Duration::days(3);
+        let gtd = TimeInForce::GTD { expires_at };
+        assert_eq!(gtd.to_string(), "GTD");
+        assert_eq!(TimeInForce::from_code("GTD", Some(expires_at)), Ok(gtd));
+        assert!("GTD".parse::<TimeInForce>().is_err());
+        assert_eq!("OPG".parse::<TimeInForce>(), Ok(TimeInForce::OPG));
+        assert_eq!("IOC".parse::<TimeInForce>(), Ok(TimeInForce::IOC));
+        assert_eq!(TimeInForce::Day.ib_good_till_date(), "");
+        assert_eq!(gtd.ib_good_till_date().len(), "20250725-20:00:00".len());
+        
+        let mut template = OrderTemplate::new(
+            "Open".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            145.0,
+            gtd,
+            TradingModel::Breakout,
+        );
+        assert!(template.validate().is_ok());
+        
+        template.time_in_force = TimeInForce::GTD { expires_at: Utc::now() - chrono::Duration::minutes(1) };
+        assert!(template.validate().is_err());
+        
+        template.time_in_force = TimeInForce::OPG;
+        assert!(template.validate().is_ok());
+        template.entry_type = EntryType::Stop;
+        assert!(template.validate().is_err());
+        
+        template.time_in_force = TimeInForce::GTC;
+        template.outside_rth = true;
+        assert!(template.validate().is_err());
+        template.entry_type = EntryType::StopLimit { limit_offset: 0.1 };
+        assert!(template.validate().is_ok());
+        template.entry_type = EntryType::Limit;
+        template.time_in_force = TimeInForce::OPG;
+        assert!(template.validate().is_err());
+    }
+    
+    #[test]
+    fn test_exit_tranches() {
+        let mut template = OrderTemplate::new(
+            "Scale out".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            148.0,
+            TimeInForce::GTC,
+            TradingModel::Breakout,
+        );
+        template.exit_tranches = vec![
+            ExitTranche::target(0.5, 1.0),
+            ExitTranche::target(0.25, 2.0),
+            ExitTranche::trailing(0.25, 1.5),
+        ];
+        assert!(template.validate().is_ok());
+        assert_eq!(template.tranche_quantities(), vec![50.0, 25.0, 25.0]);
+        assert_eq!(template.tranche_target_price(&template.exit_tranches[0]), Some(152.0));
+        assert_eq!(template.tranche_target_price(&template.exit_tranches[1]), Some(154.0));
+        assert_eq!(template.tranche_target_price(&template.exit_tranches[2]), None);
+        
+        // The runner is protected by its trailing stop, the rest by the fixed stop
+        assert_eq!(template.remaining_stop_quantity(), 75.0);
+        template.exit_tranches[0].filled_quantity = 50.0;
+        assert_eq!(template.remaining_stop_quantity(), 25.0);
+        assert_eq!(template.duplicate(None).exit_tranches[0].filled_quantity, 0.0);
+        
+        // Once the stop fills only the unfilled target is left to cancel
+        template.tranche_order_ids = vec![11, 12, 13];
+        assert_eq!(template.open_target_order_ids(), vec![12]);
+        
+        // A live stop moved to breakeven may sit above the entry
+        assert_eq!(template.breakeven_stop_price(0.05), 150.05);
+        template.status = OrderTemplateStatus::Active;
+        template.stop_price = template.breakeven_stop_price(0.05);
+        assert!(template.validate().is_ok());
+        template.status = OrderTemplateStatus::Inactive;
+        template.stop_price = 148.0;
+        
+        template.exit_tranches.push(ExitTranche::target(0.1, 3.0));
+        assert!(template.validate().is_err()); // 110%
+        
+        template.exit_tranches = vec![ExitTranche::trailing(0.5, 1.0), ExitTranche::trailing(0.5, 2.0)];
+        assert!(template.validate().is_err());
+        
+        template.quantity = 1.0;
+        template.exit_tranches = vec![ExitTranche::target(0.5, 1.0)];
+        assert!(template.validate().is_err()); // Half a share
+    }
+    
+    #[test]
+    fn test_oco_pair_validation() {
+        let mut breakout = OrderTemplate::new(
+            "Range breakout".to_string(),
+            "TSLA".to_string(),
+            OrderSide::Long,
+            10.0,
+            250.0,
+            248.0,
+            TimeInForce::Day,
+            TradingModel::Breakout,
+        );
+        breakout.entry_type = EntryType::Stop;
+        let mut breakdown = OrderTemplate::new(
+            "Range breakdown".to_string(),
+            "TSLA".to_string(),
+            OrderSide::Short,
+            10.0,
+            240.0,
+            242.0,
+            TimeInForce::Day,
+            TradingModel::FalseBreakout,
+        );
+        breakdown.entry_type = EntryType::Stop;
+        assert!(breakout.validate_oco_pair(&breakdown).is_ok());
+        
+        breakdown.limit_price = 251.0;
+        assert!(breakdown.validate_oco_pair(&breakout).is_err());
+        
+        breakdown.limit_price = 240.0;
+        breakdown.symbol = "AAPL".to_string();
+        assert!(breakout.validate_oco_pair(&breakdown).is_err());
+    }
+    
+    #[test]
+    fn test_reprice_proposal() {
+        let mut template = OrderTemplate::new(
+            "Last week's setup".to_string(),
+            "NVDA".to_string(),
+            OrderSide::Long,
+            40.0,
+            100.0,
+            99.5,
+            TimeInForce::GTC,
+            TradingModel::Breakout,
+        );
+        
+        // Volatility doubled: ATR 10 gives a 1.00 stop distance
+        let proposal = RepriceProposal::for_template(&template, 10.0, 50.0, &StopRules::default()).unwrap();
+        assert!((proposal.proposed_stop_price - 99.0).abs() < 1e-9);
+        assert_eq!(proposal.proposed_quantity, 50.0);
+        assert_eq!(proposal.current_stop_price, 99.5);
+        assert_eq!(proposal.current_quantity, 40.0);
+        
+        proposal.apply_to(&mut template);
+        assert_eq!(template.stop_price, proposal.proposed_stop_price);
+        assert_eq!(template.quantity, 50.0);
+        assert_eq!(template.risk_per_trade, 50.0);
+        
+        // A technical stop keeps driving the size
+        template.technical_stop_price = Some(98.0);
+        let proposal = RepriceProposal::for_template(&template, 10.0, 50.0, &StopRules::default()).unwrap();
+        assert_eq!(proposal.proposed_quantity, 25.0);
+    }
+    
+    #[test]
+    fn test_entry_fills() {
+        let mut template = OrderTemplate::new(
+            "Fill tracking".to_string(),
+            "AMD".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            148.0,
+            TimeInForce::GTC,
+            TradingModel::Breakout,
+        );
+        template.status = OrderTemplateStatus::Active;
+        let first = Utc::now();
+        
+        assert!(template.apply_entry_fill(40.0, 150.02, first));
+        assert_eq!(template.status, OrderTemplateStatus::PartiallyFilled);
+        assert!(template.is_active());
+        assert!(!template.can_deactivate());
+        assert!(!template.apply_entry_fill(40.0, 150.02, first));
+        
+        let later = first + chrono::Duration::seconds(30);
+        assert!(template.apply_entry_fill(100.0, 150.05, later));
+        assert_eq!(template.status, OrderTemplateStatus::Filled);
+        assert_eq!(template.filled_at, Some(first));
+        
+        // Breakeven is measured from the actual fill
+        assert!((template.breakeven_stop_price(0.05) - 150.10).abs() < 1e-9);
+        
+        let copy = template.duplicate(None);
+        assert_eq!(copy.filled_quantity, 0.0);
+        assert_eq!(copy.avg_fill_price, None);
+        assert_eq!(copy.filled_at, None);
+    }
+    
+    #[test]
+    fn test_order_ref_roundtrip() {
+        let template = OrderTemplate::new(
+            "NVDA: opening range breakout".to_string(),
+            "NVDA".to_string(),
+            OrderSide::Long,
+            10.0,
+            120.0,
+            118.0,
+            TimeInForce::Day,
+            TradingModel::Breakout,
+        );
+        
+        let order_ref = OrderRef::new(&template, OrderLeg::Stop).to_string();
+        assert!(order_ref.starts_with("zakaz:"));
+        
+        let parsed = OrderRef::parse(&order_ref).unwrap();
+        assert_eq!(parsed.template_id, template.id);
+        assert_eq!(parsed.leg, OrderLeg::Stop);
+        assert_eq!(parsed.name, "NVDA: opening range breakout");
+        
+        let tranche_ref = OrderRef::new(&template, OrderLeg::Tranche(2)).to_string();
+        assert_eq!(OrderRef::parse(&tranche_ref).unwrap().leg, OrderLeg::Tranche(2));
+        
+        assert_eq!(OrderRef::parse("manual order"), None);
+        assert_eq!(OrderRef::parse("zakaz::entry:x"), None);
+    }
+    
+    #[test]
+    fn test_entry_order_prices() {
+        let mut template = OrderTemplate::new(
+            "Breakout".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            145.0,
+            TimeInForce::Day,
+            TradingModel::Breakout,
+        );
+        assert_eq!(template.entry_type.ib_order_type(), "LMT");
+        assert_eq!(template.entry_limit_price(), Some(150.0));
+        assert_eq!(template.entry_trigger_price(), None);
+        
+        // Buy stop above resistance, limit 0.25 beyond the trigger
+        template.entry_type = EntryType::StopLimit { limit_offset: 0.25 };
+        assert!(template.validate().is_ok());
+        assert_eq!(template.entry_trigger_price(), Some(150.0));
+        assert_eq!(template.entry_limit_price(), Some(150.25));
+        assert!(template.validate_against_price(149.0).is_ok());
+        assert!(template.validate_against_price(150.5).is_err());
+        
+        template.side = OrderSide::Short;
+        template.stop_price = 155.0;
+        assert_eq!(template.entry_limit_price(), Some(149.75));
+        
+        template.entry_type = EntryType::StopLimit { limit_offset: -1.0 };
+        assert!(template.validate().is_err());
+        
+        template.entry_type = EntryType::Market;
+        assert_eq!(template.entry_limit_price(), None);
+        assert_eq!(template.entry_trigger_price(), None);
+        assert_eq!(EntryType::from_ib_order_type("STP LMT", Some(0.1)), Ok(EntryType::StopLimit { limit_offset: 0.1 }));
+        
+        template.limit_price = 110.0;
+        assert!((template.price_deviation_pct(100.0) - 10.0).abs() < 1e-9);
+        assert!((template.price_deviation_pct(120.0) - 8.333).abs() < 1e-3);
+    }
+    
+    #[test]
+    fn test_scheduled_activation_checks() {
+        let mut template = OrderTemplate::new(
+            "Open drive".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            145.0,
+            TimeInForce::Day,
+            TradingModel::Breakout,
+        );
+        let now = Utc::now();
+        assert!(!template.is_due(now));
+        
+        template.activate_at = Some(now + chrono::Duration::minutes(5));
+        assert!(!template.is_due(now));
+        assert!(template.is_due(now + chrono::Duration::minutes(5)));
+        
+        template.status = OrderTemplateStatus::Active;
+        assert!(!template.is_due(now + chrono::Duration::minutes(5)));
+        
+        assert!(template.validate_against_price(148.0).is_ok());
+        assert!(template.validate_against_price(145.0).is_err());
+        
+        // A technical stop overrides the calculated one
+        template.technical_stop_price = Some(147.0);
+        assert!(template.validate_against_price(146.5).is_err());
+    }
+    
+    #[test]
+    fn test_expiration() {
+        let mut template = OrderTemplate::new(
+            "Stale setup".to_string(),
+            "AAPL".to_string(),
+            OrderSide::Long,
+            100.0,
+            150.0,
+            145.0,
+            TimeInForce::GTC,
+            TradingModel::Bounce,
+        );
+        let now = Utc::now();
+        assert!(!template.should_expire(now));
+        
+        template.expires_at = Some(now);
+        assert!(template.should_expire(now));
+        assert!(!template.should_expire(now - chrono::Duration::seconds(1)));
+        
+        // Live orders are managed, not expired
+        template.status = OrderTemplateStatus::Active;
+        assert!(!template.should_expire(now));
+        
+        template.status = OrderTemplateStatus::Expired;
+        assert!(!template.can_activate());
+        assert!(!template.should_expire(now));
+        
+        template.status = OrderTemplateStatus::Inactive;
+        template.activate_at = Some(now + chrono::Duration::hours(1));
+        assert!(template.validate().is_err());
+        template.expires_at = Some(now + chrono::Duration::hours(2));
+        assert!(template.validate().is_ok());
+        assert_eq!(template.duplicate(None).expires_at, None);
+    }
+    
+    #[test]
+    fn test_symbol_settings() {
+        let mut settings = SymbolSettings { symbol: "BRK.A".to_string(), tick_size: Some(0.05), ..Default::default() };
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.round_to_tick(12.34), 12.35);
+        assert_eq!(settings.round_to_tick(12.32), 12.3);
+        settings.tick_size = Some(0.25);
+        assert_eq!(settings.round_to_tick(101.1), 101.0);
+        settings.tick_size = None;
+        assert_eq!(settings.round_to_tick(12.34), 12.34);
+        
+        settings.atr_period = Some(1);
+        assert!(settings.validate().is_err());
+        settings.atr_period = Some(20);
+        settings.risk_per_trade = Some(0.0);
+        assert!(settings.validate().is_err());
+        settings.risk_per_trade = Some(50.0);
+        settings.tick_size = Some(-0.01);
+        assert!(settings.validate().is_err());
+    }
 }
\ No newline at end of file
@@ -24,6 +24,7 @@ async fn main() -> Result<(), slint::PlatformError> {
     // Create UI
     let ui = MainWindow::new()?;
     let ui_handle = ui.as_weak();
+    #[allow(clippy::arc_with_non_send_sync)] // MainWindow never leaves the UI thread
     let ui_arc = Arc::new(ui);
 
     // Set up runtime
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::{oneshot, Mutex};
+
+use crate::{
+    err, inf, wrn,
+    error::AppError,
+    ib::{
+        backtest::{self, BacktestConfig, BacktestReport, Replay},
+        IBClient,
+    },
+    system::{state::State, types::UIMessage},
+};
+
+pub async fn run_backtest(
+    template_ids: Vec<String>,
+    from: Option<DateTime<Utc>>,
+    config: BacktestConfig,
+    response: oneshot::Sender<Result<BacktestReport, String>>,
+    state: State,
+) {
+    let Some(ib_client) = state.ib_client.clone() else {
+        let _ = response.send(Err("Not connected to IB".to_string()));
+        return;
+    };
+    
+    let result = backtest(&template_ids, from, config, &state, &ib_client).await;
+    match &result {
+        Ok(report) => {
+            let summary = report.models.iter()
+                .map(|m| format!("{:?} {} trades, {:.0}% won, {:+.2}R avg", m.model, m.r.trades, m.r.win_rate * 100.0, m.r.average_r))
+                .collect::<Vec<_>>()
+                .join("; ");
+            inf!("Backtest finished: {}", summary);
+            state.send_message_to_ui(UIMessage::StatusMessage(format!(
+                "Backtest: {} trades, {} unfilled, {} skipped. {}",
+                report.trades.len(), report.unfilled.len(), report.skipped.len(), summary
+            )));
+        }
+        Err(e) => {
+            err!("Backtest failed: {}", e);
+            state.send_message_to_ui(UIMessage::ErrorMessage(format!("Backtest failed: {}", e)));
+        }
+    }
+    let _ = response.send(result.map_err(|e| e.to_string()));
+}
+
+/// The IB client is locked only to read templates and bars, not while replaying
+async fn backtest(
+    template_ids: &[String],
+    from: Option<DateTime<Utc>>,
+    mut config: BacktestConfig,
+    state: &State,
+    ib_client: &Arc<Mutex<IBClient>>,
+) -> Result<BacktestReport, AppError> {
+    if config.commissions.is_none() {
+        let result = ib_client.lock().await.commission_model().await;
+        config.commissions = Some(result?);
+    }
+    let result = ib_client.lock().await.backtest_templates(template_ids).await;
+    let templates = result?;
+    
+    let total = templates.len();
+    let (mut trades, mut unfilled, mut skipped) = (Vec::new(), Vec::new(), Vec::new());
+    for (index, template) in templates.iter().enumerate() {
+        let result = ib_client.lock().await.backtest_bars(&template.symbol, from.unwrap_or(template.created_at)).await;
+        let replay = result
+            .map_err(|e| e.to_string())
+            .and_then(|(bars, start)| backtest::replay(template, &bars, start, &config));
+        match replay {
+            Ok(Replay::Traded(trade)) => trades.push(trade),
+            Ok(Replay::Unfilled) => unfilled.push((template.id.clone(), template.model)),
+            Err(e) => {
+                wrn!("Backtest skipped template {} ({}): {}", template.id, template.name, e);
+                skipped.push((template.id.clone(), e));
+            }
+        }
+        state.send_message_to_ui(UIMessage::BacktestProgress {
+            completed: index + 1,
+            total,
+            symbol: template.symbol.clone(),
+        });
+    }
+    
+    Ok(BacktestReport::new(trades, unfilled, skipped))
+}
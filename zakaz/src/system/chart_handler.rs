@@ -1,16 +1,30 @@
+use slint::{Rgb8Pixel, SharedPixelBuffer};
 use tokio::sync::oneshot;
+use std::path::Path;
 use std::sync::Arc;
 use crate::{
-    inf, err,
+    inf, err, wrn,
     charts::{
-        CandlestickChart, ViewportController, ChartTheme,
+        CandlestickChart, ChartCell, ChartGrid, EquityCurveChart, ReplayController, ViewportController, ChartTheme,
+        layout::{blit_cell, fill_cell},
+        types::{
+            bar_close_time, merge_live_bar, AtrFilter, ChartSize, ChartViewport, ComparisonSeries, ExportFormat, IndicatorPane,
+            LiveBarUpdate, TradeMarkers,
+        },
     },
+    ib::{types::{AtrSmoothing, HistoricalBar, OutlierMethod}, IBClient},
     system::{
-        state::State,
-        types::{RuntimeOutMessage, UIMessage, ChartMessage},
+        state::{ChartBarCache, ChartSlot, State},
+        types::{RuntimeInMessage, RuntimeOutMessage, UIMessage, ChartMessage},
     },
 };
 
+/// Comparison bars are refetched once older than this
+const COMPARISON_CACHE_MINUTES: i64 = 5;
+
+/// How often a streaming chart redraws its bar countdown
+const COUNTDOWN_TICK: std::time::Duration = std::time::Duration::from_secs(1);
+
 pub async fn handle_chart_message(
     msg: ChartMessage,
     state: State,
@@ -19,30 +33,97 @@ pub async fn handle_chart_message(
     let mut state_local = state.clone();
     
     match msg {
-        ChartMessage::UpdateChart { symbol, theme } => {
+        ChartMessage::UpdateChart { symbol, theme, indicators, bar_size, kind, comparisons } => {
             inf!("Updating chart for {}", symbol);
+            // A new chart ends any replay; its pending ticks find nothing to step
+            state_local.chart_replay = None;
+            // and the old chart's stream
+            if let Some(live) = state_local.chart_live.take() {
+                live.abort();
+            }
+            if let Some(indicators) = indicators {
+                state_local.chart_indicators = indicators;
+            }
+            if let Some(bar_size) = bar_size {
+                state_local.chart_bar_size = bar_size;
+            }
+            if let Some(kind) = kind {
+                state_local.chart_kind = kind;
+            }
+            if let Some(comparisons) = comparisons {
+                state_local.chart_comparisons = comparisons;
+            }
+            // Roughly 100 bars whatever the bar size
+            let duration_days = match state_local.chart_bar_size.as_str() {
+                "1 hour" => 15,
+                "5 mins" => 2,
+                _ => 100,
+            };
             
             // Get IB client
             if let Some(ib_client) = &state_local.ib_client {
-                // Fetch historical data
-                match ib_client.lock().await.get_historical_data(&symbol, 100, "1 day").await {
+                // Show extended-hours bars when a template for the symbol can trade outside RTH
+                let use_rth = !ib_client.lock().await.get_all_templates().await
+                    .iter()
+                    .any(|t| t.symbol == symbol && t.outside_rth);
+                
+                // Fetch historical data; the client is locked again below, so not held across the match
+                let result = ib_client.lock().await.get_historical_data(&symbol, duration_days, &state_local.chart_bar_size, use_rth).await;
+                match result {
                     Ok(historical_data) => {
                         // Store data in state
                         state_local.chart_data = Some((symbol.clone(), historical_data.bars.clone()));
                         
-                        // Update or create viewport controller
-                        if state_local.viewport_controller.is_none() {
+                        state_local.chart_trades = trade_markers(ib_client, &symbol).await;
+                        state_local.chart_drawings = ib_client.lock().await.get_drawings(&symbol).await.unwrap_or_else(|e| {
+                            wrn!("No drawings for the {} chart: {}", symbol, e);
+                            Vec::new()
+                        });
+                        state_local.chart_comparison_series = comparison_series(
+                            ib_client,
+                            &mut state_local.chart_bar_cache,
+                            &state_local.chart_comparisons,
+                            duration_days,
+                            &state_local.chart_bar_size,
+                            use_rth,
+                        ).await;
+                        
+                        // ATR panes filter with the symbol's daily outlier bounds, so only on daily bars
+                        let is_daily = state_local.chart_bar_size == "1 day";
+                        for pane in state_local.chart_indicators.panes.iter_mut() {
+                            if let IndicatorPane::Atr { period, smoothing, filter } = pane {
+                                *filter = if is_daily {
+                                    atr_filter(ib_client, &symbol, *period, *smoothing).await
+                                } else {
+                                    None
+                                };
+                            }
+                        }
+                        
+                        // Bars the last filtered ATR for the symbol left out, on daily bars like it
+                        state_local.chart_atr_exclusions = if is_daily {
+                            ib_client.lock().await.latest_atr_result(&symbol).await
+                                .map(|result| result.excluded_bars_detail)
+                                .unwrap_or_default()
+                        } else {
+                            Vec::new()
+                        };
+                        
+                        // Update or create viewport controller, sized to the bars as charted
+                        let chart_length = state_local.chart_kind.transform(&historical_data.bars).len();
+                        if let Some(controller) = &state_local.viewport_controller {
+                            controller.lock().await
+                                .update_data_length(chart_length);
+                        } else {
                             state_local.viewport_controller = Some(Arc::new(tokio::sync::Mutex::new(
-                                ViewportController::new(historical_data.bars.len())
+                                ViewportController::new(chart_length)
                             )));
-                        } else {
-                            state_local.viewport_controller.as_ref().unwrap()
-                                .lock().await
-                                .update_data_length(historical_data.bars.len());
                         }
                         
+                        state_local.chart_live = stream_live_bars(&state_local, ib_client, &symbol, use_rth).await;
+                        
                         // Generate chart
-                        if let Err(e) = generate_and_send_chart(&state_local, theme).await {
+                        if let Err(e) = generate_and_send_chart(&mut state_local, theme).await {
                             err!("Failed to generate chart: {}", e);
                             state.send_message_to_ui(UIMessage::ErrorMessage(
                                 format!("Failed to generate chart: {}", e)
@@ -64,40 +145,459 @@ pub async fn handle_chart_message(
         }
         
         ChartMessage::Pan { dx, dy } => {
-            if let Some(controller) = &state_local.viewport_controller {
+            if let Some(controller) = state_local.viewport_controller.clone() {
                 controller.lock().await.pan(dx, dy);
-                if let Err(e) = generate_and_send_chart(&state_local, None).await {
+                if let Err(e) = generate_and_send_chart(&mut state_local, None).await {
                     err!("Failed to update chart after pan: {}", e);
                 }
             }
         }
         
         ChartMessage::Zoom { factor, center_x, center_y } => {
-            if let Some(controller) = &state_local.viewport_controller {
+            if let Some(controller) = state_local.viewport_controller.clone() {
                 controller.lock().await.zoom(factor, center_x, center_y);
-                if let Err(e) = generate_and_send_chart(&state_local, None).await {
+                if let Err(e) = generate_and_send_chart(&mut state_local, None).await {
                     err!("Failed to update chart after zoom: {}", e);
                 }
             }
         }
         
         ChartMessage::ResetZoom => {
-            if let Some(controller) = &state_local.viewport_controller {
+            if let Some(controller) = state_local.viewport_controller.clone() {
                 controller.lock().await.reset_zoom();
-                if let Err(e) = generate_and_send_chart(&state_local, None).await {
+                if let Err(e) = generate_and_send_chart(&mut state_local, None).await {
                     err!("Failed to update chart after reset: {}", e);
                 }
             }
         }
         
+        ChartMessage::CursorMoved { x, y } => {
+            state_local.chart_cursor = active_cursor(&state_local, (x.round() as i32, y.round() as i32));
+            if let Err(e) = send_crosshair(&mut state_local).await {
+                err!("Failed to update chart crosshair: {}", e);
+            }
+        }
+        
+        ChartMessage::CursorLeft => {
+            if state_local.chart_cursor.take().is_some()
+                && let Err(e) = send_crosshair(&mut state_local).await {
+                err!("Failed to clear chart crosshair: {}", e);
+            }
+        }
+        
         ChartMessage::SetViewport(viewport) => {
-            if let Some(controller) = &state_local.viewport_controller {
+            if let Some(controller) = state_local.viewport_controller.clone() {
                 controller.lock().await.set_viewport(viewport);
-                if let Err(e) = generate_and_send_chart(&state_local, None).await {
+                if let Err(e) = generate_and_send_chart(&mut state_local, None).await {
                     err!("Failed to update chart after viewport change: {}", e);
                 }
             }
         }
+        
+        ChartMessage::ApplyPreset { preset } => {
+            navigate(&mut state_local, |controller, bars| controller.show_latest_from(preset.first_bar(bars))).await;
+        }
+        
+        ChartMessage::PageLeft => navigate(&mut state_local, |controller, _| controller.page(-1.0)).await,
+        
+        ChartMessage::PageRight => navigate(&mut state_local, |controller, _| controller.page(1.0)).await,
+        
+        ChartMessage::JumpToLatest => navigate(&mut state_local, |controller, _| controller.jump_to_latest()).await,
+        
+        ChartMessage::ExportChart { path, format, width, height } => {
+            if let Err(e) = export_chart(&state_local, &path, format, width, height).await {
+                err!("Failed to export chart to {}: {}", path.display(), e);
+                state.send_message_to_ui(UIMessage::ErrorMessage(
+                    format!("Failed to export chart: {}", e)
+                ));
+            } else {
+                state.send_message_to_ui(UIMessage::StatusMessage(
+                    format!("Chart saved to {}", path.display())
+                ));
+            }
+        }
+        
+        ChartMessage::SaveTheme { name, theme } => {
+            if let Some(ib_client) = &state_local.ib_client {
+                let result = ib_client.lock().await.save_chart_theme(&name, &theme).await;
+                match result {
+                    Ok(()) => {
+                        let selected = ib_client.lock().await.selected_chart_theme().await;
+                        if let Ok((selected, _)) = selected && selected == name {
+                            state_local.chart_theme = Some(theme);
+                            if let Err(e) = generate_and_send_chart(&mut state_local, None).await {
+                                err!("Failed to redraw chart with theme {}: {}", name, e);
+                            }
+                        }
+                        send_themes(&state_local).await;
+                    }
+                    Err(e) => {
+                        err!("Failed to save theme {}: {}", name, e);
+                        state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("Failed to save theme: {}", e)
+                        ));
+                    }
+                }
+            }
+        }
+        
+        ChartMessage::LoadThemes => send_themes(&state_local).await,
+        
+        ChartMessage::SetColorScheme { preference } => {
+            if let Some(ib_client) = &state_local.ib_client {
+                let result = ib_client.lock().await.set_color_scheme(preference).await;
+                match result {
+                    Ok(()) => state.send_message_to_ui(UIMessage::ColorScheme(preference)),
+                    Err(e) => {
+                        err!("Failed to save color scheme {}: {}", preference.as_str(), e);
+                        state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("Failed to save color scheme: {}", e)
+                        ));
+                    }
+                }
+            }
+        }
+        
+        ChartMessage::LoadColorScheme => {
+            if let Some(ib_client) = &state_local.ib_client {
+                match ib_client.lock().await.color_scheme().await {
+                    Ok(preference) => state.send_message_to_ui(UIMessage::ColorScheme(preference)),
+                    Err(e) => err!("Failed to load color scheme: {}", e),
+                }
+            }
+        }
+        
+        ChartMessage::UiColorScheme { dark } => {
+            if let Some(ib_client) = &state_local.ib_client {
+                let result = ib_client.lock().await.match_color_scheme(dark).await;
+                match result {
+                    Ok(Some(theme)) => {
+                        state_local.chart_theme = Some(theme);
+                        if let Err(e) = generate_and_send_chart(&mut state_local, None).await {
+                            err!("Failed to redraw chart for the color scheme: {}", e);
+                        }
+                        send_themes(&state_local).await;
+                    }
+                    Ok(None) => {}
+                    Err(e) => err!("Failed to match the chart theme to the color scheme: {}", e),
+                }
+            }
+        }
+        
+        ChartMessage::SelectTheme { name } => {
+            if let Some(ib_client) = &state_local.ib_client {
+                let result = ib_client.lock().await.select_chart_theme(&name).await;
+                match result {
+                    Ok(theme) => {
+                        state_local.chart_theme = Some(theme);
+                        if let Err(e) = generate_and_send_chart(&mut state_local, None).await {
+                            err!("Failed to redraw chart with theme {}: {}", name, e);
+                        }
+                        send_themes(&state_local).await;
+                    }
+                    Err(e) => {
+                        err!("Failed to select theme {}: {}", name, e);
+                        state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("Failed to select theme: {}", e)
+                        ));
+                    }
+                }
+            }
+        }
+        
+        ChartMessage::Resize { width, height, scale_factor } => {
+            let size = ChartSize { width, height, scale_factor };
+            // A view collapsed by the layout has nothing to show
+            if width > 0 && height > 0 && size != state_local.chart_size {
+                state_local.chart_size = size;
+                if let Err(e) = generate_and_send_chart(&mut state_local, None).await {
+                    err!("Failed to update chart after resize: {}", e);
+                }
+            }
+        }
+        
+        ChartMessage::ReplayStart { from } => {
+            if let Some((symbol, bars)) = state_local.chart_data.clone() {
+                // Restarting replays the same bars, not just the ones revealed so far
+                let bars = state_local.chart_replay.take()
+                    .map(|replay| replay.all_bars().to_vec())
+                    .unwrap_or(bars);
+                inf!("Replaying {} bars of {}", bars.len(), symbol);
+                state_local.chart_replay = Some(ReplayController::new(bars, from));
+                if let Err(e) = show_replay(&mut state_local).await {
+                    err!("Failed to show replay: {}", e);
+                }
+            } else {
+                state.send_message_to_ui(UIMessage::ErrorMessage("No chart to replay".to_string()));
+            }
+        }
+        
+        ChartMessage::ReplayPlay => {
+            if let Some(replay) = &mut state_local.chart_replay
+                && !replay.is_finished() {
+                let generation = replay.play();
+                let interval = replay.interval();
+                schedule_replay_tick(&state_local, generation, interval);
+            }
+        }
+        
+        ChartMessage::ReplayPause => {
+            if let Some(replay) = &mut state_local.chart_replay {
+                replay.pause();
+            }
+        }
+        
+        ChartMessage::ReplayStep => {
+            if let Some(replay) = &mut state_local.chart_replay
+                && replay.step()
+                && let Err(e) = show_replay(&mut state_local).await {
+                err!("Failed to show replay: {}", e);
+            }
+        }
+        
+        ChartMessage::ReplayStepBack => {
+            if let Some(replay) = &mut state_local.chart_replay
+                && replay.step_back()
+                && let Err(e) = show_replay(&mut state_local).await {
+                err!("Failed to show replay: {}", e);
+            }
+        }
+        
+        ChartMessage::ReplaySpeed { bars_per_second } => {
+            if let Some(replay) = &mut state_local.chart_replay {
+                // Ticks already scheduled at the old speed go stale
+                let generation = replay.set_speed(bars_per_second);
+                let interval = replay.interval();
+                if replay.is_playing() {
+                    schedule_replay_tick(&state_local, generation, interval);
+                }
+            }
+        }
+        
+        ChartMessage::ReplayTick { generation } => {
+            if let Some(replay) = &mut state_local.chart_replay
+                && replay.is_playing()
+                && replay.generation() == generation {
+                replay.step();
+                let finished = replay.is_finished();
+                if finished {
+                    replay.pause();
+                }
+                let interval = replay.interval();
+                if let Err(e) = show_replay(&mut state_local).await {
+                    err!("Failed to show replay: {}", e);
+                }
+                if finished {
+                    state.send_message_to_ui(UIMessage::StatusMessage("Replay finished".to_string()));
+                } else {
+                    schedule_replay_tick(&state_local, generation, interval);
+                }
+            }
+        }
+        
+        ChartMessage::ReplayEnter { template_id } => {
+            if state_local.chart_replay.is_none() {
+                state.send_message_to_ui(UIMessage::ErrorMessage("No replay to enter".to_string()));
+            } else if state_local.chart_bar_size != "1 day" {
+                // Templates are simulated like backtests, over daily bars
+                state.send_message_to_ui(UIMessage::ErrorMessage(
+                    "Simulated entries need a daily chart".to_string()
+                ));
+            } else if let Some(ib_client) = &state_local.ib_client {
+                let template = ib_client.lock().await.get_template(&template_id).await;
+                match (template, &mut state_local.chart_replay) {
+                    (Some(template), Some(replay)) => {
+                        inf!("Simulating template {} in the replay", template_id);
+                        replay.enter(template);
+                    }
+                    _ => {
+                        state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("Template {} not found", template_id)
+                        ));
+                    }
+                }
+            }
+        }
+        
+        ChartMessage::ReplayStop => {
+            if let Some(replay) = state_local.chart_replay.take()
+                && let Some((_, bars)) = &mut state_local.chart_data {
+                *bars = replay.all_bars().to_vec();
+                let chart_length = state_local.chart_kind.transform(bars).len();
+                if let Some(controller) = &state_local.viewport_controller {
+                    controller.lock().await.update_data_length(chart_length);
+                }
+                if let Err(e) = generate_and_send_chart(&mut state_local, None).await {
+                    err!("Failed to update chart after replay: {}", e);
+                }
+            }
+        }
+        
+        ChartMessage::LiveBar { symbol, bar } => {
+            let mut changed = apply_live_bar(&mut state_local, &symbol, &bar).await;
+            let active = state_local.chart_layout.active;
+            for index in 0..state_local.chart_layout.slots.len() {
+                if index == active {
+                    continue;
+                }
+                let mut chart = std::mem::take(&mut state_local.chart_layout.slots[index]);
+                chart.swap_with(&mut state_local);
+                changed |= apply_live_bar(&mut state_local, &symbol, &bar).await;
+                chart.swap_with(&mut state_local);
+                state_local.chart_layout.slots[index] = chart;
+            }
+            if changed && let Err(e) = generate_and_send_chart(&mut state_local, None).await {
+                err!("Failed to update chart with a streamed bar: {}", e);
+            }
+        }
+        
+        ChartMessage::CountdownTick { symbol } => {
+            let mut counting = shows_countdown(&state_local, &symbol);
+            let active = state_local.chart_layout.active;
+            for index in 0..state_local.chart_layout.slots.len() {
+                if index == active || counting {
+                    continue;
+                }
+                let mut chart = std::mem::take(&mut state_local.chart_layout.slots[index]);
+                chart.swap_with(&mut state_local);
+                counting = shows_countdown(&state_local, &symbol);
+                chart.swap_with(&mut state_local);
+                state_local.chart_layout.slots[index] = chart;
+            }
+            if counting && let Err(e) = generate_and_send_chart(&mut state_local, None).await {
+                err!("Failed to update chart countdown: {}", e);
+            }
+        }
+        
+        ChartMessage::SetChartGrid { grid } => {
+            let count = grid.slot_count();
+            let layout = &mut state_local.chart_layout;
+            if layout.active >= count {
+                // The active chart stays, taking the first slot's place
+                layout.active = 0;
+                layout.slots[0] = ChartSlot::default();
+            }
+            // Charts in dropped slots stop streaming
+            for slot in layout.slots.iter_mut().skip(count) {
+                if let Some(live) = slot.live.take() {
+                    live.abort();
+                }
+            }
+            layout.slots.resize_with(count, ChartSlot::default);
+            layout.grid = grid;
+            layout.composite = None;
+            state_local.chart_cursor = None;
+            if let Err(e) = generate_and_send_chart(&mut state_local, None).await {
+                err!("Failed to update chart after changing the grid: {}", e);
+            }
+        }
+        
+        ChartMessage::SelectChartSlot { slot } => {
+            if select_slot(&mut state_local, slot)
+                && let Err(e) = send_crosshair(&mut state_local).await {
+                err!("Failed to update chart after selecting slot {}: {}", slot, e);
+            }
+        }
+        
+        ChartMessage::AssignChartSlot { slot, symbol, bar_size } => {
+            if slot < state_local.chart_layout.slots.len() {
+                select_slot(&mut state_local, slot);
+                let update = ChartMessage::UpdateChart {
+                    symbol,
+                    theme: None,
+                    indicators: None,
+                    bar_size,
+                    kind: None,
+                    comparisons: None,
+                };
+                return Box::pin(handle_chart_message(update, state_local, reply_channel)).await;
+            }
+            state.send_message_to_ui(UIMessage::ErrorMessage(format!("No chart slot {}", slot + 1)));
+        }
+        
+        ChartMessage::AddDrawing(drawing) => {
+            if let Some(ib_client) = &state_local.ib_client {
+                let result = ib_client.lock().await.save_drawing(&drawing).await;
+                match result {
+                    Ok(()) => refresh_drawings(&mut state_local, &drawing.symbol).await,
+                    Err(e) => {
+                        err!("Failed to save drawing on {}: {}", drawing.symbol, e);
+                        state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("Failed to save drawing: {}", e)
+                        ));
+                    }
+                }
+            }
+        }
+        
+        ChartMessage::MoveDrawing { id, shape } => {
+            if let Some(ib_client) = &state_local.ib_client {
+                let result = ib_client.lock().await.move_drawing(&id, shape).await;
+                match result {
+                    Ok(drawing) => refresh_drawings(&mut state_local, &drawing.symbol).await,
+                    Err(e) => {
+                        err!("Failed to move drawing {}: {}", id, e);
+                        state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("Failed to move drawing: {}", e)
+                        ));
+                    }
+                }
+            }
+        }
+        
+        ChartMessage::DeleteDrawing { id } => {
+            if let Some(ib_client) = &state_local.ib_client {
+                let result = ib_client.lock().await.delete_drawing(&id).await;
+                match result {
+                    Ok(()) => {
+                        let before = state_local.chart_drawings.len();
+                        state_local.chart_drawings.retain(|d| d.id != id);
+                        if state_local.chart_drawings.len() != before
+                            && let Err(e) = generate_and_send_chart(&mut state_local, None).await {
+                            err!("Failed to update chart after deleting a drawing: {}", e);
+                        }
+                    }
+                    Err(e) => {
+                        err!("Failed to delete drawing {}: {}", id, e);
+                        state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("Failed to delete drawing: {}", e)
+                        ));
+                    }
+                }
+            }
+        }
+        
+        ChartMessage::UpdateEquityCurve { range, theme } => {
+            inf!("Updating equity curve");
+            
+            if let Some(ib_client) = &state_local.ib_client {
+                let result = ib_client.lock().await.get_performance_stats(range).await;
+                let chart_theme = theme.unwrap_or_else(|| state_local.chart_theme.clone().unwrap_or_default());
+                let rendered = result.and_then(|stats| {
+                    let size = state_local.chart_size;
+                    EquityCurveChart::new(size.width, size.height, chart_theme.scaled(size.scale_factor))
+                        .render_to_buffer(&stats.equity_curve)
+                });
+                match rendered {
+                    Ok(buffer) => {
+                        state.send_message_to_ui(UIMessage::ChartImageUpdate {
+                            image: buffer,
+                            symbol: "Equity curve".to_string(),
+                        });
+                    }
+                    Err(e) => {
+                        err!("Failed to chart equity curve: {}", e);
+                        state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("Failed to chart equity curve: {}", e)
+                        ));
+                    }
+                }
+            } else {
+                state.send_message_to_ui(UIMessage::ErrorMessage(
+                    "IB client not connected".to_string()
+                ));
+            }
+        }
     }
     
     // Send acknowledgment if needed
@@ -108,39 +608,445 @@ pub async fn handle_chart_message(
     state_local
 }
 
-async fn generate_and_send_chart(
-    state: &State,
+/// Recorded fills and the open position for the symbol; whatever can't be loaded is left off
+async fn trade_markers(ib_client: &Arc<tokio::sync::Mutex<IBClient>>, symbol: &str) -> TradeMarkers {
+    let client = ib_client.lock().await;
+    let fills = client.get_fills(symbol).await.unwrap_or_else(|e| {
+        wrn!("No fills for the {} chart: {}", symbol, e);
+        Vec::new()
+    });
+    let position = client.get_position(symbol).await.unwrap_or_else(|e| {
+        wrn!("No position for the {} chart: {}", symbol, e);
+        None
+    });
+    TradeMarkers { fills, position }
+}
+
+/// Bars of each comparison symbol over the chart's span, reusing ones fetched within
+/// `COMPARISON_CACHE_MINUTES`; a symbol whose bars can't be fetched is left off
+async fn comparison_series(
+    ib_client: &Arc<tokio::sync::Mutex<IBClient>>,
+    cache: &mut ChartBarCache,
+    symbols: &[String],
+    duration_days: u32,
+    bar_size: &str,
+    use_rth: bool,
+) -> Vec<ComparisonSeries> {
+    let cutoff = chrono::Utc::now() - chrono::Duration::minutes(COMPARISON_CACHE_MINUTES);
+    cache.retain(|_, (fetched_at, _)| *fetched_at > cutoff);
+    
+    let mut series = Vec::new();
+    for symbol in symbols {
+        let key = (symbol.clone(), bar_size.to_string(), use_rth);
+        if !cache.contains_key(&key) {
+            let result = ib_client.lock().await.get_historical_data(symbol, duration_days, bar_size, use_rth).await;
+            match result {
+                Ok(data) => {
+                    cache.insert(key.clone(), (chrono::Utc::now(), data.bars));
+                }
+                Err(e) => {
+                    wrn!("No bars to compare {} against: {}", symbol, e);
+                    continue;
+                }
+            }
+        }
+        if let Some((_, bars)) = cache.get(&key) {
+            series.push(ComparisonSeries { symbol: symbol.clone(), bars: bars.clone() });
+        }
+    }
+    series
+}
+
+/// Send the theme names and the selected one to the UI
+async fn send_themes(state: &State) {
+    let Some(ib_client) = &state.ib_client else {
+        return;
+    };
+    let client = ib_client.lock().await;
+    match (client.chart_theme_names().await, client.selected_chart_theme().await) {
+        (Ok(names), Ok((selected, _))) => state.send_message_to_ui(UIMessage::ChartThemes { names, selected }),
+        (Err(e), _) | (_, Err(e)) => err!("Failed to list chart themes: {}", e),
+    }
+}
+
+/// Reload the charted symbol's drawings and redraw after one of `symbol`'s drawings changed
+async fn refresh_drawings(state: &mut State, symbol: &str) {
+    let Some((charted, _)) = &state.chart_data else {
+        return;
+    };
+    if charted != symbol {
+        return;
+    }
+    if let Some(ib_client) = &state.ib_client {
+        match ib_client.lock().await.get_drawings(symbol).await {
+            Ok(drawings) => state.chart_drawings = drawings,
+            Err(e) => wrn!("Failed to reload drawings for {}: {}", symbol, e),
+        }
+    }
+    if let Err(e) = generate_and_send_chart(state, None).await {
+        err!("Failed to update chart drawings: {}", e);
+    }
+}
+
+/// Outlier bounds and earnings days behind the symbol's filtered ATR
+async fn atr_filter(
+    ib_client: &Arc<tokio::sync::Mutex<IBClient>>,
+    symbol: &str,
+    period: usize,
+    smoothing: AtrSmoothing,
+) -> Option<AtrFilter> {
+    let client = ib_client.lock().await;
+    match client.calculate_filtered_atr(symbol, period, OutlierMethod::default(), smoothing, false).await {
+        Ok(result) => Some(AtrFilter {
+            lower_bound: result.lower_bound,
+            upper_bound: result.upper_bound,
+            excluded_dates: client.earnings_reaction_days(symbol).await.into_iter().collect(),
+        }),
+        Err(e) => {
+            wrn!("No filtered ATR for the {} chart: {}", symbol, e);
+            None
+        }
+    }
+}
+
+/// Redraw just the crosshair over the last rendered frame, or the whole chart without one
+async fn send_crosshair(state: &mut State) -> Result<(), crate::error::AppError> {
+    let Some((image, symbol)) = crosshair_image(state)? else {
+        return generate_and_send_chart(state, None).await;
+    };
+    state.send_message_to_ui(UIMessage::ChartImageUpdate { image, symbol });
+    
+    Ok(())
+}
+
+/// The active chart's last frame with the crosshair over it, placed in the grid, and the
+/// charted symbol; None without a frame
+fn crosshair_image(state: &State) -> Result<Option<(SharedPixelBuffer<Rgb8Pixel>, String)>, crate::error::AppError> {
+    let (Some(frame), Some((symbol, bars))) = (&state.chart_frame, &state.chart_data) else {
+        return Ok(None);
+    };
+    let buffer = chart_for(state, state.chart_theme.clone().unwrap_or_default(), active_size(state))
+        .render_crosshair(frame, bars)?;
+    Ok(Some((in_grid(state, buffer), symbol.clone())))
+}
+
+/// Make `slot` the active chart, parking the one that was; its replay is paused. Whether
+/// the active chart changed.
+fn select_slot(state: &mut State, slot: usize) -> bool {
+    let previous = state.chart_layout.active;
+    if slot == previous || slot >= state.chart_layout.slots.len() {
+        return false;
+    }
+    let mut chart = std::mem::take(&mut state.chart_layout.slots[slot]);
+    chart.swap_with(state);
+    if let Some(replay) = &mut chart.replay {
+        replay.pause();
+    }
+    state.chart_layout.slots[previous] = chart;
+    state.chart_layout.active = slot;
+    state.chart_cursor = None;
+    true
+}
+
+/// The active chart's cell: the whole view unless the grid has several
+fn active_cell(state: &State) -> ChartCell {
+    let size = state.chart_size;
+    state.chart_layout.grid.cells(size.width, size.height)
+        .get(state.chart_layout.active)
+        .copied()
+        .unwrap_or(ChartCell { x: 0, y: 0, width: size.width, height: size.height })
+}
+
+/// Size the active chart renders at
+fn active_size(state: &State) -> ChartSize {
+    let cell = active_cell(state);
+    ChartSize { width: cell.width, height: cell.height, ..state.chart_size }
+}
+
+/// A cursor position in the chart view, relative to the active chart; None off its cell
+fn active_cursor(state: &State, (x, y): (i32, i32)) -> Option<(i32, i32)> {
+    let cell = active_cell(state);
+    cell.contains(x, y).then(|| (x - cell.x as i32, y - cell.y as i32))
+}
+
+/// The active chart's image over its cell of the last grid render; as is without a grid
+fn in_grid(state: &State, image: SharedPixelBuffer<Rgb8Pixel>) -> SharedPixelBuffer<Rgb8Pixel> {
+    match &state.chart_layout.composite {
+        Some(composite) if state.chart_layout.grid != ChartGrid::Single => {
+            let mut grid = composite.clone();
+            blit_cell(&mut grid, &image, &active_cell(state));
+            grid
+        }
+        _ => image,
+    }
+}
+
+/// Render every chart of the grid into its cell, each parked chart taking the active one's
+/// place while it renders. Empty slots show the background, and gutters the axis color.
+async fn generate_and_send_grid(
+    state: &mut State,
     theme: Option<ChartTheme>,
 ) -> Result<(), crate::error::AppError> {
-    if let Some((symbol, bars)) = &state.chart_data {
-        if let Some(controller) = &state.viewport_controller {
-            let viewport = controller.lock().await.get_viewport();
-            
-            // Use provided theme or default
-            let chart_theme = theme.unwrap_or_else(|| {
-                state.chart_theme.as_ref()
-                    .cloned()
-                    .unwrap_or_default()
-            });
-            
-            // Use fixed size for now, but this could be made dynamic
-            let width = 800;
-            let height = 600;
-            
-            // Create chart
-            let chart = CandlestickChart::new(width, height, chart_theme);
-            
-            // Render to buffer (using bitmap for performance)
-            let buffer = chart.render_to_buffer(bars, &viewport)?;
-            
-            // Send to UI
-            state.send_message_to_ui(UIMessage::ChartImageUpdate {
-                image_data: buffer,
-                width,
-                height,
-                symbol: symbol.clone(),
-            });
+    let chart_theme = theme.unwrap_or_else(|| state.chart_theme.clone().unwrap_or_default());
+    let pixel = |color: &str| {
+        let rgba = ChartTheme::parse_color(color);
+        Rgb8Pixel { r: rgba.0, g: rgba.1, b: rgba.2 }
+    };
+    let (gutter, background) = (pixel(&chart_theme.colors.axis_line), pixel(&chart_theme.colors.background));
+    
+    let size = state.chart_size;
+    let mut composite = SharedPixelBuffer::<Rgb8Pixel>::new(size.width, size.height);
+    fill_cell(&mut composite, &ChartCell { x: 0, y: 0, width: size.width, height: size.height }, gutter);
+    let active = state.chart_layout.active;
+    for (index, cell) in state.chart_layout.grid.cells(size.width, size.height).iter().enumerate() {
+        let mut parked = (index != active).then(|| std::mem::take(&mut state.chart_layout.slots[index]));
+        if let Some(chart) = &mut parked {
+            chart.swap_with(state);
+        }
+        let rendered = render_cell(state, &chart_theme, cell).await;
+        if let Some(mut chart) = parked {
+            chart.swap_with(state);
+            state.chart_layout.slots[index] = chart;
         }
+        match rendered? {
+            Some(image) => blit_cell(&mut composite, &image, cell),
+            None => fill_cell(&mut composite, cell, background),
+        }
+    }
+    state.chart_layout.composite = Some(composite.clone());
+    
+    // The active chart's crosshair goes over its cell
+    let (image, symbol) = crosshair_image(state)?.unwrap_or((composite, String::new()));
+    state.send_message_to_ui(UIMessage::ChartImageUpdate { image, symbol });
+    Ok(())
+}
+
+/// Render the chart in the state's chart fields at `cell`'s size, keeping its frame; None
+/// when nothing is charted
+async fn render_cell(
+    state: &mut State,
+    theme: &ChartTheme,
+    cell: &ChartCell,
+) -> Result<Option<SharedPixelBuffer<Rgb8Pixel>>, crate::error::AppError> {
+    state.chart_frame = None;
+    let (Some((_, bars)), Some(controller)) = (&state.chart_data, &state.viewport_controller) else {
+        return Ok(None);
+    };
+    let viewport = controller.lock().await.get_viewport();
+    let size = ChartSize { width: cell.width, height: cell.height, ..state.chart_size };
+    let frame = chart_for(state, theme.clone(), size).render_frame(bars, &viewport)?;
+    let image = frame.image().clone();
+    state.chart_frame = Some(frame);
+    Ok(Some(image))
+}
+
+/// The price chart of `state`'s overlays, trades and cursor
+fn chart_for(state: &State, theme: ChartTheme, size: ChartSize) -> CandlestickChart {
+    CandlestickChart::new(size.width, size.height, theme.scaled(size.scale_factor))
+        .with_kind(state.chart_kind)
+        .with_indicators(state.chart_indicators.clone())
+        .with_trades(match &state.chart_replay {
+            Some(replay) => replay.markers(&state.chart_trades),
+            None => state.chart_trades.clone(),
+        })
+        .with_drawings(state.chart_drawings.clone())
+        .with_comparisons(state.chart_comparison_series.clone())
+        .with_atr_exclusions(state.chart_atr_exclusions.clone())
+        .with_crosshair(state.chart_cursor)
+        .with_countdown(countdown(state))
+}
+
+/// Time left in the chart's last bar while its symbol streams; None once the bar has
+/// closed, while replaying and on charts not built from time bars
+fn countdown(state: &State) -> Option<chrono::Duration> {
+    if state.chart_live.is_none() || state.chart_replay.is_some() || !state.chart_kind.is_time_based() {
+        return None;
+    }
+    let (_, bars) = state.chart_data.as_ref()?;
+    let left = bar_close_time(bars.last()?.timestamp, &state.chart_bar_size) - chrono::Utc::now();
+    (left > chrono::Duration::zero()).then_some(left)
+}
+
+/// Chart the replay's revealed bars, scrolled to keep the latest in view
+async fn show_replay(state: &mut State) -> Result<(), crate::error::AppError> {
+    let (Some(replay), Some((symbol, _))) = (&state.chart_replay, &state.chart_data) else {
+        return Ok(());
+    };
+    let symbol = symbol.clone();
+    let bars = replay.revealed_bars().to_vec();
+    let chart_length = state.chart_kind.transform(&bars).len();
+    state.chart_data = Some((symbol, bars));
+    
+    if let Some(controller) = &state.viewport_controller {
+        let mut controller = controller.lock().await;
+        controller.update_data_length(chart_length);
+        let viewport = controller.get_viewport();
+        let x_max = chart_length.saturating_sub(1) as f64;
+        let span = viewport.x_max - viewport.x_min;
+        controller.set_viewport(ChartViewport { x_min: (x_max - span).max(0.0), x_max, ..viewport });
+    }
+    generate_and_send_chart(state, None).await
+}
+
+/// Ask for the replay's next bar after `interval`
+/// Move the chart's viewport with `move_viewport`, given the bars as charted, then fit the
+/// price axis to the bars in view and redraw
+async fn navigate(state: &mut State, move_viewport: impl FnOnce(&mut ViewportController, &[HistoricalBar])) {
+    let (Some((_, bars)), Some(controller)) = (&state.chart_data, &state.viewport_controller) else {
+        return;
+    };
+    let bars = state.chart_kind.transform(bars);
+    {
+        let mut controller = controller.lock().await;
+        move_viewport(&mut controller, &bars);
+        let (start, end) = controller.get_visible_bar_range();
+        if let Some(visible) = bars.get(start..=end) && !visible.is_empty() {
+            let low = visible.iter().map(|bar| bar.low).fold(f64::INFINITY, f64::min);
+            let high = visible.iter().map(|bar| bar.high).fold(f64::NEG_INFINITY, f64::max);
+            controller.fit_y_axis(low, high);
+        }
+    }
+    if let Err(e) = generate_and_send_chart(state, None).await {
+        err!("Failed to update chart after moving the viewport: {}", e);
+    }
+}
+
+/// Stream `symbol`'s real-time bars to the chart as `LiveBar` messages, with a
+/// `CountdownTick` each second between them, returning the forwarding task's handle to stop
+/// it with. None when IB won't stream, which leaves the chart as fetched.
+async fn stream_live_bars(
+    state: &State,
+    ib_client: &Arc<tokio::sync::Mutex<IBClient>>,
+    symbol: &str,
+    use_rth: bool,
+) -> Option<tokio::task::AbortHandle> {
+    let runtime = state.runtime.clone()?;
+    let result = ib_client.lock().await.stream_realtime_bars(symbol, use_rth).await;
+    let mut bars = match result {
+        Ok(bars) => bars,
+        Err(e) => {
+            wrn!("No live bars for the {} chart: {}", symbol, e);
+            return None;
+        }
+    };
+    let symbol = symbol.to_string();
+    let task = tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(COUNTDOWN_TICK);
+        loop {
+            let msg = tokio::select! {
+                bar = bars.recv() => match bar {
+                    Some(bar) => ChartMessage::LiveBar { symbol: symbol.clone(), bar },
+                    None => break,
+                },
+                _ = ticks.tick() => ChartMessage::CountdownTick { symbol: symbol.clone() },
+            };
+            runtime.tell(RuntimeInMessage::Chart(msg));
+        }
+    });
+    Some(task.abort_handle())
+}
+
+/// Whether the chart in the state's chart fields is of `symbol` and counts down its bar
+fn shows_countdown(state: &State, symbol: &str) -> bool {
+    state.chart_data.as_ref().is_some_and(|(charted, _)| charted == symbol) && countdown(state).is_some()
+}
+
+/// Fold a streamed bar into the chart in the state's chart fields if it charts `symbol` and
+/// isn't replaying. A viewport showing the latest bar moves along as bars are added. Whether
+/// the chart changed.
+async fn apply_live_bar(state: &mut State, symbol: &str, bar: &HistoricalBar) -> bool {
+    if state.chart_replay.is_some() {
+        return false;
+    }
+    let Some((charted, bars)) = &mut state.chart_data else {
+        return false;
+    };
+    if charted != symbol {
+        return false;
+    }
+    let before = state.chart_kind.transform(bars).len();
+    if merge_live_bar(bars, bar, &state.chart_bar_size) == LiveBarUpdate::Unchanged {
+        return false;
+    }
+    let after = state.chart_kind.transform(bars).len();
+    if after != before && let Some(controller) = &state.viewport_controller {
+        let mut controller = controller.lock().await;
+        let mut viewport = controller.get_viewport();
+        let following = viewport.x_max >= before as f64 - 1.0;
+        controller.update_data_length(after);
+        if following && after > before {
+            let shift = (after - before) as f64;
+            viewport.x_min += shift;
+            viewport.x_max += shift;
+            controller.set_viewport(viewport);
+        }
+    }
+    true
+}
+
+fn schedule_replay_tick(state: &State, generation: u64, interval: std::time::Duration) {
+    let Some(runtime) = state.runtime.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        tokio::time::sleep(interval).await;
+        runtime.tell(RuntimeInMessage::Chart(ChartMessage::ReplayTick { generation }));
+    });
+}
+
+/// Render the charted symbol's current viewport to a file
+async fn export_chart(
+    state: &State,
+    path: &Path,
+    format: ExportFormat,
+    width: u32,
+    height: u32,
+) -> Result<(), crate::error::AppError> {
+    let (Some((symbol, bars)), Some(controller)) = (&state.chart_data, &state.viewport_controller) else {
+        return Err(crate::error::AppError::ChartError("No chart to export".to_string()));
+    };
+    if width == 0 || height == 0 {
+        return Err(crate::error::AppError::ChartError(format!("Can't export a {}x{} chart", width, height)));
+    }
+    let viewport = controller.lock().await.get_viewport();
+    
+    let caption = format!("{} · {} · {}", symbol, state.chart_bar_size, chrono::Local::now().format("%Y-%m-%d %H:%M"));
+    let size = ChartSize { width, height, scale_factor: 1.0 };
+    chart_for(state, state.chart_theme.clone().unwrap_or_default(), size)
+        .with_crosshair(None)
+        .with_caption(Some(caption))
+        .save(bars, &viewport, path, format)
+}
+
+async fn generate_and_send_chart(
+    state: &mut State,
+    theme: Option<ChartTheme>,
+) -> Result<(), crate::error::AppError> {
+    if state.chart_layout.grid != ChartGrid::Single {
+        return generate_and_send_grid(state, theme).await;
+    }
+    state.chart_frame = None;
+    if let (Some((symbol, bars)), Some(controller)) = (&state.chart_data, &state.viewport_controller) {
+        let viewport = controller.lock().await.get_viewport();
+        
+        // Use provided theme or default
+        let chart_theme = theme.unwrap_or_else(|| {
+            state.chart_theme.as_ref()
+                .cloned()
+                .unwrap_or_default()
+        });
+        
+        // Render to buffer (using bitmap for performance), keeping the frame for cursor moves
+        let chart = chart_for(state, chart_theme, state.chart_size);
+        let frame = chart.render_frame(bars, &viewport)?;
+        let buffer = chart.render_crosshair(&frame, bars)?;
+        
+        // Send to UI
+        state.send_message_to_ui(UIMessage::ChartImageUpdate {
+            image: buffer,
+            symbol: symbol.clone(),
+        });
+        state.chart_frame = Some(frame);
     }
     
     Ok(())
@@ -1,16 +1,23 @@
 use std::sync::Arc;
 
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
 
 use crate::{
+    db::import::{read_template_csv, ImportResult},
     err, inf, wrn,
-    ib::{messages::*, IBClient},
+    ib::{live_guard::{LiveAction, CONFIRMATION_TTL_SECS}, messages::*, risk::TradingLockChange, stop_management::StopAction, types::AuditSource, IBClient},
     system::{
         state::State,
-        types::{RuntimeOutMessage, UIMessage},
+        types::{RuntimeInMessage, RuntimeOutMessage, UIMessage},
     },
 };
 
+/// How often a market data stream's relative volume is measured again: one 5-minute bar
+const RVOL_REFRESH: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Least time between two stop-rule runs driven by one symbol's market data
+const STOP_RULE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
 // Helper macro for oneshot channels
 macro_rules! notify_oneshot {
     ($reply_channel:expr, $message:expr) => {
@@ -31,7 +38,11 @@ pub async fn handle_ib_message(
     let ib_client = if let Some(client) = &state_local.ib_client {
         client.clone()
     } else {
-        let client = Arc::new(tokio::sync::Mutex::new(IBClient::new()));
+        let client = match &state_local.db {
+            Some(db) => IBClient::with_database(db.clone()),
+            None => IBClient::new(),
+        };
+        let client = Arc::new(tokio::sync::Mutex::new(client));
         state_local.ib_client = Some(client.clone());
         client
     };
@@ -39,10 +50,13 @@ pub async fn handle_ib_message(
     match msg {
         IBMessage::ConnectPaper { response } => {
             inf!("Connecting to IB paper account...");
-            match ib_client.lock().await.connect_paper().await {
+            let result = ib_client.lock().await.connect_paper().await;
+            match result {
                 Ok(_) => {
                     state.send_message_to_ui(UIMessage::StatusMessage("Connected to paper account".to_string()));
                     update_connection_status(&state, &ib_client).await;
+                    adopt_orphans(&state, &ib_client).await;
+                    report_trading_lock(&state, &ib_client).await;
                     let _ = response.send(Ok(()));
                 }
                 Err(e) => {
@@ -55,10 +69,13 @@ pub async fn handle_ib_message(
         
         IBMessage::ConnectLive { response } => {
             wrn!("Connecting to IB LIVE account...");
-            match ib_client.lock().await.connect_live().await {
+            let result = ib_client.lock().await.connect_live().await;
+            match result {
                 Ok(_) => {
                     state.send_message_to_ui(UIMessage::StatusMessage("Connected to LIVE account".to_string()));
                     update_connection_status(&state, &ib_client).await;
+                    adopt_orphans(&state, &ib_client).await;
+                    report_trading_lock(&state, &ib_client).await;
                     let _ = response.send(Ok(()));
                 }
                 Err(e) => {
@@ -112,9 +129,9 @@ pub async fn handle_ib_message(
             let _ = response.send(status);
         }
         
-        IBMessage::CreateTemplate { name, symbol, side, quantity, limit_price, stop_price, time_in_force, model, response } => {
+        IBMessage::CreateTemplate { name, symbol, side, quantity, auto_size, sizing_strategy, limit_price, stop_price, time_in_force, entry_type, model, response } => {
             inf!("Creating order template: {}", name);
-            let template = crate::ib::OrderTemplate::new(
+            let mut template = crate::ib::OrderTemplate::new(
                 name.clone(),
                 symbol,
                 side,
@@ -124,8 +141,11 @@ pub async fn handle_ib_message(
                 time_in_force,
                 model,
             );
+            template.entry_type = entry_type;
+            template.sizing_strategy = sizing_strategy;
             
-            match ib_client.lock().await.create_template(template).await {
+            let result = create_sized_template(&state, &ib_client, template, auto_size || quantity <= 0.0).await;
+            match result {
                 Ok(template_id) => {
                     state.send_message_to_ui(UIMessage::StatusMessage(format!("Created template: {}", name)));
                     update_templates(&state, &ib_client).await;
@@ -169,6 +189,147 @@ pub async fn handle_ib_message(
             }
         }
         
+        IBMessage::GetAuditTrail { template_id, response } => {
+            let result = ib_client.lock().await.get_audit_trail(&template_id).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetAuditLog { filter, offset, limit, response } => {
+            let result = ib_client.lock().await.get_audit_page(&filter, offset, limit).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::AdoptOrphans { response } => {
+            let result = ib_client.lock().await.adopt_orphans().await;
+            if result.is_ok() {
+                update_templates(&state, &ib_client).await;
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::ClaimTemplate { template_id, response } => {
+            inf!("Claiming template: {}", template_id);
+            let result = ib_client.lock().await.claim_template(&template_id).await;
+            match result {
+                Ok(_) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage("Template claimed".to_string()));
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to claim template: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to claim template: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::ArchiveTemplate { template_id, response } => {
+            inf!("Archiving template: {}", template_id);
+            let result = ib_client.lock().await.archive_template(&template_id).await;
+            match result {
+                Ok(_) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage("Template archived".to_string()));
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to archive template: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to archive template: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::RestoreTemplate { template_id, response } => {
+            inf!("Restoring template: {}", template_id);
+            let result = ib_client.lock().await.restore_template(&template_id).await;
+            match result {
+                Ok(_) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage("Template restored".to_string()));
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to restore template: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to restore template: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::GetArchivedTemplates { response } => {
+            let result = ib_client.lock().await.get_archived_templates().await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::RepriceTemplate { template_id, response } => {
+            inf!("Re-pricing template: {}", template_id);
+            let result = ib_client.lock().await.reprice_template(&template_id).await;
+            if let Err(e) = &result {
+                err!("Failed to re-price template: {}", e);
+                state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to re-price template: {}", e)));
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetModelDefaults { model, response } => {
+            let result = ib_client.lock().await.get_model_defaults(model).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::SetModelDefaults { model, defaults, response } => {
+            let result = ib_client.lock().await.set_model_defaults(model, defaults).await;
+            match result {
+                Ok(_) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Saved {:?} defaults", model)));
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to save model defaults: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to save defaults: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::GetSettings { response } => {
+            let settings = ib_client.lock().await.settings().await;
+            let _ = response.send(Ok(settings));
+        }
+        
+        IBMessage::UpdateSettings { settings, response } => {
+            let result = ib_client.lock().await.update_settings(settings).await;
+            match result {
+                Ok(settings) => {
+                    state.send_message_to_ui(UIMessage::SettingsChanged(Box::new(settings.clone())));
+                    let _ = response.send(Ok(settings));
+                }
+                Err(e) => {
+                    err!("Failed to save settings: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to save settings: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::DuplicateTemplate { template_id, new_name, response } => {
+            inf!("Duplicating template: {}", template_id);
+            let result = ib_client.lock().await.duplicate_template(&template_id, new_name).await;
+            match result {
+                Ok(new_id) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage("Template duplicated".to_string()));
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(new_id));
+                }
+                Err(e) => {
+                    err!("Failed to duplicate template: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to duplicate template: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
         IBMessage::GetTemplate { template_id, response } => {
             let template = ib_client.lock().await.get_template(&template_id).await;
             let _ = response.send(template);
@@ -179,9 +340,54 @@ pub async fn handle_ib_message(
             let _ = response.send(templates);
         }
         
-        IBMessage::ActivateTemplate { template_id, response } => {
+        IBMessage::GetTemplateHistory { template_id, response } => {
+            match ib_client.lock().await.get_template_history(&template_id).await {
+                Ok(history) => {
+                    let _ = response.send(Ok(history));
+                }
+                Err(e) => {
+                    err!("Failed to get template history: {}", e);
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::ExportTemplates { path, response } => {
+            inf!("Exporting order templates to {}", path.display());
+            let templates = ib_client.lock().await.get_all_templates().await;
+            let count = templates.len();
+            let export = crate::ib::orders::OrderTemplateExport::from_templates(templates);
+            match export.write_to_file(&path).await {
+                Ok(_) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Exported {} templates", count)));
+                    let _ = response.send(Ok(count));
+                }
+                Err(e) => {
+                    err!("Failed to export templates: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to export templates: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::ActivateTemplate { template_id, override_price_band, override_limits, response } => {
             inf!("Activating template: {}", template_id);
-            match ib_client.lock().await.activate_template(&template_id).await {
+            if override_price_band {
+                wrn!("Price-band check overridden for template {}", template_id);
+            }
+            if override_limits {
+                wrn!("Position limits overridden for template {}", template_id);
+            }
+            let action = LiveAction::Activate { template_id: template_id.clone(), override_price_band, override_limits };
+            let confirmed = ib_client.lock().await.require_confirmation(&action).await;
+            let result = match confirmed {
+                Ok(()) => {
+                    warn_correlations(&state, &ib_client, &template_id).await;
+                    ib_client.lock().await.activate_template(&template_id, override_price_band, override_limits, AuditSource::UI).await
+                }
+                Err(e) => Err(e),
+            };
+            match result {
                 Ok(_) => {
                     state.send_message_to_ui(UIMessage::StatusMessage(format!("Template {} activated", template_id)));
                     update_templates(&state, &ib_client).await;
@@ -195,9 +401,86 @@ pub async fn handle_ib_message(
             }
         }
         
+        IBMessage::CreateOcoPair { first, second, response } => {
+            inf!("Creating OCO pair: {} / {}", first.name, second.name);
+            let result = ib_client.lock().await.create_oco_pair(first, second).await;
+            match result {
+                Ok(ids) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage("Created OCO pair".to_string()));
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(ids));
+                }
+                Err(e) => {
+                    err!("Failed to create OCO pair: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to create OCO pair: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::ActivateOcoPair { template_id, override_price_band, override_limits, response } => {
+            inf!("Activating OCO pair for template: {}", template_id);
+            let action = LiveAction::ActivateOcoPair { template_id: template_id.clone(), override_price_band, override_limits };
+            let confirmed = ib_client.lock().await.require_confirmation(&action).await;
+            let result = match confirmed {
+                Ok(()) => ib_client.lock().await.activate_oco_pair(&template_id, override_price_band, override_limits).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(ids) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("OCO pair activated ({} templates)", ids.len())));
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(ids));
+                }
+                Err(e) => {
+                    err!("Failed to activate OCO pair: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to activate OCO pair: {}", e)));
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::MoveStopToBreakeven { template_id, offset, response } => {
+            inf!("Moving stop to breakeven for template {} (offset {})", template_id, offset);
+            let result = ib_client.lock().await.move_stop_to_breakeven(&template_id, offset).await;
+            match result {
+                Ok(new_stop) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Stop moved to {:.2}", new_stop)));
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(new_stop));
+                }
+                Err(e) => {
+                    err!("Failed to move stop to breakeven: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to move stop: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::SetStopRules { template_id, rules, response } => {
+            inf!("Setting {} stop rule(s) on template {}", rules.len(), template_id);
+            let result = ib_client.lock().await.set_stop_rules(&template_id, rules).await;
+            match result {
+                Ok(()) => {
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to set stop rules: {}", e);
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
         IBMessage::DeactivateTemplate { template_id, response } => {
             inf!("Deactivating template: {}", template_id);
-            match ib_client.lock().await.deactivate_template(&template_id).await {
+            let confirmed = ib_client.lock().await.require_confirmation(&LiveAction::Deactivate { template_id: template_id.clone() }).await;
+            let result = match confirmed {
+                Ok(()) => ib_client.lock().await.deactivate_template(&template_id, AuditSource::UI).await,
+                Err(e) => Err(e),
+            };
+            match result {
                 Ok(_) => {
                     state.send_message_to_ui(UIMessage::StatusMessage(format!("Template {} deactivated", template_id)));
                     update_templates(&state, &ib_client).await;
@@ -211,10 +494,691 @@ pub async fn handle_ib_message(
             }
         }
         
+        IBMessage::SaveChecklistItem { id, model, text, position, response } => {
+            let result = ib_client.lock().await.save_checklist_item(id, model, text, position).await;
+            if let Err(e) = &result {
+                err!("Failed to save checklist item: {}", e);
+                state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to save checklist item: {}", e)));
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::DeleteChecklistItem { id, response } => {
+            let result = ib_client.lock().await.delete_checklist_item(&id).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetChecklistItems { model, response } => {
+            let result = ib_client.lock().await.get_checklist_items(model).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetChecklist { template_id, response } => {
+            let result = ib_client.lock().await.get_checklist(&template_id).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::SubmitChecklist { template_id, answers, response } => {
+            inf!("Submitting checklist for template {}", template_id);
+            let result = ib_client.lock().await.submit_checklist(&template_id, answers).await;
+            match result {
+                Ok(_) => {
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to submit checklist: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to submit checklist: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::PrepareActivation { action, response } => {
+            inf!("Preparing live action: {:?}", action);
+            let result = ib_client.lock().await.prepare_live_action(action).await;
+            match result {
+                Ok(confirmation) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Confirm within {}s: {}", CONFIRMATION_TTL_SECS, confirmation.summary)));
+                    let _ = response.send(Ok(confirmation));
+                }
+                Err(e) => {
+                    err!("Failed to prepare action: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to prepare: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::ConfirmActivation { token, response } => {
+            let confirmed = ib_client.lock().await.confirm_live_action(&token).await;
+            match confirmed {
+                Ok(action) => {
+                    inf!("Confirmed live action: {:?}", action);
+                    let result = run_confirmed(action, state_local.clone()).await;
+                    let _ = response.send(result);
+                }
+                Err(e) => {
+                    err!("Failed to confirm action: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to confirm: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::ScheduleActivation { template_id, activate_at, response } => {
+            inf!("Scheduling activation of template {}: {:?}", template_id, activate_at);
+            let result = ib_client.lock().await.schedule_activation(&template_id, activate_at).await;
+            match result {
+                Ok(_) => {
+                    let status = match activate_at {
+                        Some(at) => format!("Template scheduled for {}", at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")),
+                        None => "Template schedule cleared".to_string(),
+                    };
+                    state.send_message_to_ui(UIMessage::StatusMessage(status));
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to schedule template activation: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to schedule activation: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::ActivateScheduledTemplates => {
+            let results = ib_client.lock().await.activate_due_templates(chrono::Utc::now()).await;
+            if !results.is_empty() {
+                for (template, result) in &results {
+                    match result {
+                        Ok(_) => state.send_message_to_ui(UIMessage::StatusMessage(
+                            format!("Scheduled activation: {} is active", template.name)
+                        )),
+                        Err(e) => state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("Scheduled activation of {} failed: {}", template.name, e)
+                        )),
+                    }
+                }
+                update_templates(&state, &ib_client).await;
+            }
+        }
+        
+        IBMessage::DeactivateDayTemplates => {
+            let results = ib_client.lock().await.deactivate_day_templates(chrono::Utc::now()).await;
+            if !results.is_empty() {
+                for (template, result) in &results {
+                    match result {
+                        Ok(_) => state.send_message_to_ui(UIMessage::StatusMessage(
+                            format!("End of day: {} deactivated", template.name)
+                        )),
+                        Err(e) => state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("End of day: failed to deactivate {}: {}", template.name, e)
+                        )),
+                    }
+                }
+                update_templates(&state, &ib_client).await;
+            }
+        }
+        
+        IBMessage::SyncFills => {
+            let result = ib_client.lock().await.sync_fills().await;
+            match result {
+                Ok(changed) if !changed.is_empty() => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(
+                        format!("Fills updated for {} template(s)", changed.len())
+                    ));
+                    update_templates(&state, &ib_client).await;
+                }
+                Ok(_) => {}
+                Err(e) => wrn!("Failed to sync fills: {}", e),
+            }
+        }
+        
+        IBMessage::ApplyStopRules { symbol } => {
+            // The scheduler's run keeps a stream open for each managed symbol, whose updates run the price rules
+            if symbol.is_none() {
+                sync_stop_rule_streams(&mut state_local, &ib_client).await;
+            }
+            let results = ib_client.lock().await.apply_stop_rules(chrono::Utc::now(), symbol.as_deref()).await;
+            if !results.is_empty() {
+                for (template, action, result) in &results {
+                    match (action, result) {
+                        (StopAction::MoveStop { price, .. }, Ok(())) => state.send_message_to_ui(UIMessage::StatusMessage(
+                            format!("{}: stop moved to {:.2}", template.name, price)
+                        )),
+                        (StopAction::Exit { .. }, Ok(())) => state.send_message_to_ui(UIMessage::StatusMessage(
+                            format!("{}: time stop, exiting at market", template.name)
+                        )),
+                        (_, Err(e)) => state.send_message_to_ui(UIMessage::ErrorMessage(
+                            format!("{}: stop rule failed: {}", template.name, e)
+                        )),
+                    }
+                }
+                update_templates(&state, &ib_client).await;
+            }
+        }
+        
+        IBMessage::ExpireTemplates => {
+            let expired = ib_client.lock().await.expire_templates(chrono::Utc::now()).await;
+            if !expired.is_empty() {
+                for template in &expired {
+                    state.send_message_to_ui(UIMessage::StatusMessage(
+                        format!("{} expired; re-analyze before reactivating", template.name)
+                    ));
+                }
+                update_templates(&state, &ib_client).await;
+            }
+        }
+        
+        IBMessage::SaveSector { name, max_notional, max_risk, response } => {
+            inf!("Saving sector: {}", name);
+            let result = ib_client.lock().await.save_sector(name.clone(), max_notional, max_risk).await;
+            match result {
+                Ok(()) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Saved sector {}", name)));
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to save sector: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to save sector: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::DeleteSector { name, response } => {
+            let result = ib_client.lock().await.delete_sector(&name).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::AssignSymbolSector { symbol, sector, response } => {
+            let result = ib_client.lock().await.assign_symbol_sector(&symbol, sector.as_deref()).await;
+            if let Err(e) = &result {
+                state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to assign {}: {}", symbol, e)));
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetSectors { response } => {
+            let result = ib_client.lock().await.get_all_sectors().await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetSymbolSettings { symbol, response } => {
+            let result = ib_client.lock().await.get_symbol_settings(&symbol).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetAllSymbolSettings { response } => {
+            let result = ib_client.lock().await.get_all_symbol_settings().await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::SaveSymbolSettings { settings, response } => {
+            inf!("Saving settings for {}", settings.symbol);
+            let symbol = settings.symbol.trim().to_uppercase();
+            let result = ib_client.lock().await.save_symbol_settings(settings).await;
+            match result {
+                Ok(()) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Saved settings for {}", symbol)));
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to save settings for {}: {}", symbol, e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to save settings for {}: {}", symbol, e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::DeleteSymbolSettings { symbol, response } => {
+            let result = ib_client.lock().await.delete_symbol_settings(&symbol).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::CheckLossLimit => {
+            let result = ib_client.lock().await.check_loss_limit(chrono::Utc::now()).await;
+            match result {
+                Ok(Some(TradingLockChange::Locked { lock, cancelled, flattened, errors })) => {
+                    state.send_message_to_ui(UIMessage::TradingLockStatus(Some(lock.describe())));
+                    let mut summary = format!("Daily loss limit hit: cancelled {} pending template(s)", cancelled.len());
+                    if !flattened.is_empty() {
+                        summary.push_str(&format!(", flattened {}", flattened.join(", ")));
+                    }
+                    state.send_message_to_ui(UIMessage::ErrorMessage(summary));
+                    if !errors.is_empty() {
+                        state.send_message_to_ui(UIMessage::ErrorMessage(format!("Loss limit actions failed: {}", errors.join("; "))));
+                    }
+                    update_templates(&state, &ib_client).await;
+                }
+                Ok(Some(TradingLockChange::Released)) => {
+                    state.send_message_to_ui(UIMessage::TradingLockStatus(None));
+                    state.send_message_to_ui(UIMessage::StatusMessage("New session: trading unlocked".to_string()));
+                }
+                Ok(None) => {}
+                Err(e) => wrn!("Failed to check daily loss limit: {}", e),
+            }
+        }
+        
+        IBMessage::TrackDrawdown => {
+            let result = ib_client.lock().await.track_drawdown(chrono::Utc::now()).await;
+            match result {
+                Ok(Some((risk_scale, drawdown))) if risk_scale < 1.0 => {
+                    wrn!("Drawdown {:.1}%: risk per trade scaled to {:.0}%", drawdown.pct(), risk_scale * 100.0);
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!(
+                        "Drawdown {:.1}% from the high-water mark: risk per trade scaled to {:.0}%",
+                        drawdown.pct(), risk_scale * 100.0
+                    )));
+                }
+                Ok(Some(_)) => {
+                    inf!("Drawdown recovered: full risk per trade restored");
+                    state.send_message_to_ui(UIMessage::StatusMessage("Drawdown recovered: full risk per trade restored".to_string()));
+                }
+                Ok(None) => {}
+                Err(e) => wrn!("Failed to track drawdown: {}", e),
+            }
+        }
+        
+        IBMessage::CheckVolatilitySpikes => {
+            let result = ib_client.lock().await.check_volatility_spikes(chrono::Utc::now()).await;
+            match result {
+                Ok(spikes) => {
+                    for spike in spikes {
+                        wrn!("Volatility spike: {}", spike.describe());
+                        state.send_message_to_ui(UIMessage::StatusMessage(format!("Volatility spike: {}", spike.describe())));
+                    }
+                }
+                Err(e) => wrn!("Failed to check volatility spikes: {}", e),
+            }
+        }
+        
+        IBMessage::BackupIfDue => {
+            let result = ib_client.lock().await.backup_if_due(chrono::Utc::now()).await;
+            if let Err(e) = result {
+                err!("Automatic backup failed: {}", e);
+                state.send_message_to_ui(UIMessage::ErrorMessage(format!("Automatic backup failed: {}", e)));
+            }
+        }
+        
+        IBMessage::PurgeDeletedTemplates => {
+            let result = ib_client.lock().await.purge_deleted_templates(chrono::Utc::now()).await;
+            if let Err(e) = result {
+                err!("Failed to purge deleted templates: {}", e);
+            }
+        }
+        
+        IBMessage::ExportCsv { table, path, from, to, response } => {
+            let result = ib_client.lock().await.export_csv(table, &path, from, to).await;
+            match result {
+                Ok(rows) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Exported {} row(s) to {}", rows, path.display())));
+                    let _ = response.send(Ok(rows));
+                }
+                Err(e) => {
+                    err!("Failed to export {:?}: {}", table, e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to export: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::Search { query, limit, response } => {
+            let result = ib_client.lock().await.search(&query, limit).await;
+            if let Err(e) = &result {
+                err!("Search for {:?} failed: {}", query, e);
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::ImportTemplates { path, response } => {
+            match read_template_csv(&path) {
+                Ok(rows) => {
+                    let mut results = Vec::with_capacity(rows.len());
+                    for row in rows {
+                        let (symbol, result) = match row.plan {
+                            Ok(plan) => {
+                                let result = create_sized_template(&state, &ib_client, plan.to_template(), plan.quantity.is_none()).await;
+                                (plan.symbol, result.map_err(|e| e.to_string()))
+                            }
+                            Err(e) => (String::new(), Err(e)),
+                        };
+                        if let Err(e) = &result {
+                            wrn!("Import line {} skipped: {}", row.line, e);
+                        }
+                        results.push(ImportResult { line: row.line, symbol, result });
+                    }
+                    let imported = results.iter().filter(|r| r.result.is_ok()).count();
+                    if imported > 0 {
+                        update_templates(&state, &ib_client).await;
+                    }
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Imported {} of {} template(s) from {}", imported, results.len(), path.display())));
+                    let _ = response.send(Ok(results));
+                }
+                Err(e) => {
+                    err!("Failed to read {}: {}", path.display(), e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to import templates: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::BackupDatabase { path, response } => {
+            let result = ib_client.lock().await.backup_database(path).await;
+            match result {
+                Ok(path) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Database backed up to {}", path.display())));
+                    let _ = response.send(Ok(path));
+                }
+                Err(e) => {
+                    err!("Failed to back up the database: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to back up the database: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::GetTradingLock { response } => {
+            let lock = ib_client.lock().await.active_trading_lock(chrono::Utc::now()).await;
+            let _ = response.send(lock);
+        }
+        
+        IBMessage::GetRiskSummary { response } => {
+            let summary = ib_client.lock().await.get_risk_summary().await;
+            let _ = response.send(summary);
+        }
+        
+        IBMessage::CheckShortSale { template_id, response } => {
+            let result = ib_client.lock().await.check_short(&template_id).await;
+            if let Ok(Some(check)) = &result {
+                let max_margin_pct = ib_client.lock().await.get_short_max_margin_pct().await;
+                for issue in check.issues(max_margin_pct) {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Short sale warning: {}", issue)));
+                }
+                update_templates(&state, &ib_client).await;
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetTradeCosts { template_id, target_price, response } => {
+            let result = ib_client.lock().await.get_trade_costs(&template_id, target_price).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetRStats { model, symbol, window, response } => {
+            let result = ib_client.lock().await.get_r_stats(model, symbol.as_deref(), window).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetPerformanceStats { range, response } => {
+            let result = ib_client.lock().await.get_performance_stats(range).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::RunMonteCarlo { model, r_multiples, config, response } => {
+            let result = ib_client.lock().await.run_monte_carlo(model, r_multiples, config).await;
+            if let Err(e) = &result {
+                state.send_message_to_ui(UIMessage::ErrorMessage(format!("Monte Carlo failed: {}", e)));
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::SaveEarningsDate { event, response } => {
+            let description = event.describe();
+            let result = ib_client.lock().await.save_earnings_event(event).await;
+            match result {
+                Ok(()) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(description));
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to save earnings date: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to save earnings date: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::RemoveEarningsDate { symbol, date, response } => {
+            let result = ib_client.lock().await.remove_earnings_event(&symbol, date).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetEarningsDates { symbol, response } => {
+            let result = ib_client.lock().await.get_earnings_events(symbol.as_deref()).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::CheckEarnings { template_id, response } => {
+            let result = ib_client.lock().await.earnings_within_hold(&template_id).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::CreateWatchlist { name, response } => {
+            let result = ib_client.lock().await.create_watchlist(&name).await;
+            match result {
+                Ok(watchlist) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Created watchlist {}", watchlist.name)));
+                    let _ = response.send(Ok(watchlist));
+                }
+                Err(e) => {
+                    err!("Failed to create watchlist: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to create watchlist: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::RenameWatchlist { watchlist_id, name, response } => {
+            let result = ib_client.lock().await.rename_watchlist(&watchlist_id, &name).await;
+            if let Err(e) = &result {
+                state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to rename watchlist: {}", e)));
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::DeleteWatchlist { watchlist_id, response } => {
+            let result = ib_client.lock().await.delete_watchlist(&watchlist_id).await;
+            if let Err(e) = &result {
+                state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to delete watchlist: {}", e)));
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetWatchlists { response } => {
+            let result = ib_client.lock().await.get_watchlists().await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::AddToWatchlist { watchlist_id, symbols, note, response } => {
+            let result = ib_client.lock().await.add_to_watchlist(watchlist_id.as_deref(), symbols, note).await;
+            match result {
+                Ok(symbols) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Watching {}", symbols.join(", "))));
+                    let _ = response.send(Ok(symbols));
+                }
+                Err(e) => {
+                    err!("Failed to update watchlist: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to update watchlist: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::RemoveFromWatchlist { watchlist_id, symbol, response } => {
+            let result = ib_client.lock().await.remove_from_watchlist(watchlist_id.as_deref(), &symbol).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::ReorderWatchlist { watchlist_id, symbols, response } => {
+            let result = ib_client.lock().await.reorder_watchlist(&watchlist_id, symbols).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetWatchlist { watchlist_id, response } => {
+            let result = ib_client.lock().await.get_watchlist(watchlist_id.as_deref()).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::CalculateWatchlistATR { watchlist_id, period_days, method, smoothing, response } => {
+            let result = ib_client.lock().await.watchlist_atr(watchlist_id.as_deref(), period_days, method, smoothing).await;
+            match result {
+                Ok(results) => {
+                    let calculated = results.iter().filter(|(_, r)| r.is_ok()).count();
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("ATR for {} of {} watched symbol(s)", calculated, results.len())));
+                    let _ = response.send(Ok(results));
+                }
+                Err(e) => {
+                    err!("Failed to calculate watchlist ATR: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to calculate watchlist ATR: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::RunScan { criteria, symbols, watchlist_id, response } => {
+            inf!("Running scan: {:?}", criteria);
+            let result = ib_client.lock().await.run_scan(criteria, symbols, watchlist_id).await;
+            match result {
+                Ok(results) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Scan found {} symbol(s)", results.len())));
+                    let _ = response.send(Ok(results));
+                }
+                Err(e) => {
+                    err!("Scan failed: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Scan failed: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::CreateTemplateFromScan { symbol, side, model, response } => {
+            inf!("Creating template from scan: {}", symbol);
+            let template = ib_client.lock().await.template_from_scan(&symbol, side, model).await;
+            let result = match template {
+                Ok(template) => create_sized_template(&state, &ib_client, template, true).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(template_id) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Created template for {}", symbol)));
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(template_id));
+                }
+                Err(e) => {
+                    err!("Failed to create template from scan: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to create template for {}: {}", symbol, e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::CreateBasket { name, template_ids, response } => {
+            inf!("Creating basket: {}", name);
+            match ib_client.lock().await.create_basket(name.clone(), template_ids).await {
+                Ok(basket_id) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage(format!("Created basket: {}", name)));
+                    let _ = response.send(Ok(basket_id));
+                }
+                Err(e) => {
+                    err!("Failed to create basket: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to create basket: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::DeleteBasket { basket_id, response } => {
+            inf!("Deleting basket: {}", basket_id);
+            match ib_client.lock().await.delete_basket(&basket_id).await {
+                Ok(_) => {
+                    state.send_message_to_ui(UIMessage::StatusMessage("Basket deleted".to_string()));
+                    let _ = response.send(Ok(()));
+                }
+                Err(e) => {
+                    err!("Failed to delete basket: {}", e);
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::AddToBasket { basket_id, template_id, response } => {
+            let result = ib_client.lock().await.add_to_basket(&basket_id, &template_id).await;
+            if let Err(e) = &result {
+                err!("Failed to add template {} to basket {}: {}", template_id, basket_id, e);
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::RemoveFromBasket { basket_id, template_id, response } => {
+            let result = ib_client.lock().await.remove_from_basket(&basket_id, &template_id).await;
+            if let Err(e) = &result {
+                err!("Failed to remove template {} from basket {}: {}", template_id, basket_id, e);
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetAllBaskets { response } => {
+            let baskets = ib_client.lock().await.get_all_baskets().await;
+            let _ = response.send(baskets.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::ActivateBasket { basket_id, response } => {
+            inf!("Activating basket: {}", basket_id);
+            let confirmed = ib_client.lock().await.require_confirmation(&LiveAction::ActivateBasket { basket_id: basket_id.clone() }).await;
+            let result = match confirmed {
+                Ok(()) => ib_client.lock().await.activate_basket(&basket_id).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(result) => {
+                    report_basket_result(&state, "activated", &result);
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(result));
+                }
+                Err(e) => {
+                    err!("Failed to activate basket: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to activate basket: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
+        IBMessage::DeactivateBasket { basket_id, response } => {
+            inf!("Deactivating basket: {}", basket_id);
+            let confirmed = ib_client.lock().await.require_confirmation(&LiveAction::DeactivateBasket { basket_id: basket_id.clone() }).await;
+            let result = match confirmed {
+                Ok(()) => ib_client.lock().await.deactivate_basket(&basket_id).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(result) => {
+                    report_basket_result(&state, "deactivated", &result);
+                    update_templates(&state, &ib_client).await;
+                    let _ = response.send(Ok(result));
+                }
+                Err(e) => {
+                    err!("Failed to deactivate basket: {}", e);
+                    state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to deactivate basket: {}", e)));
+                    let _ = response.send(Err(e.to_string()));
+                }
+            }
+        }
+        
         IBMessage::SubscribeMarketData { symbol, response } => {
             inf!("Subscribing to market data for {}", symbol);
-            match ib_client.lock().await.subscribe_market_data(&symbol).await {
-                Ok(_) => {
+            // A stream opened for stop rules is taken over: it now stays until unsubscribed
+            let result = if state_local.stop_rule_streams.remove(&symbol) {
+                Ok(None)
+            } else {
+                ib_client.lock().await.subscribe_market_data(&symbol).await.map(Some)
+            };
+            match result {
+                Ok(updates) => {
+                    if let Some(updates) = updates {
+                        let forwarding = forward_market_data(&state, &ib_client, symbol.clone(), updates);
+                        state_local.market_data_streams.insert(symbol.clone(), forwarding);
+                    }
                     state.send_message_to_ui(UIMessage::StatusMessage(format!("Subscribed to {}", symbol)));
                     let _ = response.send(Ok(()));
                 }
@@ -227,6 +1191,9 @@ pub async fn handle_ib_message(
         
         IBMessage::UnsubscribeMarketData { symbol } => {
             inf!("Unsubscribing from market data for {}", symbol);
+            if let Some(forwarding) = state_local.market_data_streams.remove(&symbol) {
+                forwarding.abort();
+            }
             ib_client.lock().await.unsubscribe_market_data(&symbol).await;
             notify_oneshot!(reply_channel, RuntimeOutMessage::Ok);
         }
@@ -241,9 +1208,9 @@ pub async fn handle_ib_message(
             let _ = response.send(Err("Positions retrieval not yet implemented".to_string()));
         }
         
-        IBMessage::GetHistoricalData { symbol, duration_days, bar_size, response } => {
+        IBMessage::GetHistoricalData { symbol, duration_days, bar_size, use_rth, response } => {
             inf!("Getting historical data for {} - {} days of {} bars", symbol, duration_days, bar_size);
-            match ib_client.lock().await.get_historical_data(&symbol, duration_days, &bar_size).await {
+            match ib_client.lock().await.get_historical_data(&symbol, duration_days, &bar_size, use_rth).await {
                 Ok(historical_data) => {
                     state.send_message_to_ui(UIMessage::StatusMessage(
                         format!("Retrieved {} bars for {}", historical_data.bars.len(), symbol)
@@ -260,9 +1227,9 @@ pub async fn handle_ib_message(
             }
         }
         
-        IBMessage::CalculateFilteredATR { symbol, period_days, method, response } => {
+        IBMessage::CalculateFilteredATR { symbol, period_days, method, smoothing, force_refresh, response } => {
             inf!("Calculating filtered ATR for {} - {} days period", symbol, period_days);
-            match ib_client.lock().await.calculate_filtered_atr(&symbol, period_days, method).await {
+            match ib_client.lock().await.calculate_filtered_atr(&symbol, period_days, method, smoothing, force_refresh).await {
                 Ok(atr_result) => {
                     let msg = format!(
                         "ATR for {}: Filtered {:.2}, Regular {:.2}, Excluded {} bars ({}%)",
@@ -281,6 +1248,43 @@ pub async fn handle_ib_message(
                 }
             }
         }
+        
+        IBMessage::SuggestStops { symbol, side, entry_price, model, response } => {
+            let result = ib_client.lock().await.suggest_stops(&symbol, side, entry_price, model).await;
+            if let Err(e) = &result {
+                wrn!("Failed to suggest stops for {}: {}", symbol, e);
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetTemplateATRAnalysis { template_id, response } => {
+            let result = ib_client.lock().await.get_template_atr_analysis(&template_id).await;
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetVolatilityProfile { symbol, response } => {
+            let result = ib_client.lock().await.get_volatility_profile(&symbol).await;
+            if let Err(e) = &result {
+                wrn!("Failed to build volatility profile for {}: {}", symbol, e);
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetRelativeVolume { symbol, sessions, response } => {
+            let result = ib_client.lock().await.get_relative_volume(&symbol, sessions).await;
+            if let Err(e) = &result {
+                wrn!("Failed to measure relative volume for {}: {}", symbol, e);
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
+        
+        IBMessage::GetGapStats { symbol, lookback_days, min_gap_pct, response } => {
+            let result = ib_client.lock().await.get_gap_stats(&symbol, lookback_days, min_gap_pct).await;
+            if let Err(e) = &result {
+                wrn!("Failed to build gap statistics for {}: {}", symbol, e);
+            }
+            let _ = response.send(result.map_err(|e| e.to_string()));
+        }
     }
     
     state_local
@@ -295,6 +1299,191 @@ async fn update_connection_status(state: &State, ib_client: &Arc<tokio::sync::Mu
     });
 }
 
+fn report_basket_result(state: &State, action: &str, result: &crate::ib::types::BasketResult) {
+    state.send_message_to_ui(UIMessage::StatusMessage(format!(
+        "Basket {}: {}/{} templates",
+        action, result.succeeded.len(), result.total()
+    )));
+    if !result.is_complete_success() {
+        let failures: Vec<String> = result.failed.iter()
+            .map(|(template_id, e)| format!("{}: {}", template_id, e))
+            .collect();
+        state.send_message_to_ui(UIMessage::ErrorMessage(format!(
+            "Basket templates not {}: {}", action, failures.join("; ")
+        )));
+    }
+}
+
+/// Show a loss limit lock carried over from earlier in the session
+async fn report_trading_lock(state: &State, ib_client: &Arc<tokio::sync::Mutex<IBClient>>) {
+    let lock = ib_client.lock().await.active_trading_lock(chrono::Utc::now()).await;
+    if let Some(lock) = lock {
+        state.send_message_to_ui(UIMessage::TradingLockStatus(Some(lock.describe())));
+    }
+}
+
+/// Advisory only: activation goes ahead whether or not the candidate moves with held symbols
+async fn warn_correlations(state: &State, ib_client: &Arc<tokio::sync::Mutex<IBClient>>, template_id: &str) {
+    let result = ib_client.lock().await.correlation_warnings(template_id).await;
+    match result {
+        Ok(warnings) => {
+            for warning in warnings {
+                wrn!("{}", warning);
+                state.send_message_to_ui(UIMessage::StatusMessage(format!("Correlation warning: {}", warning)));
+            }
+        }
+        Err(e) => wrn!("Correlation check skipped for {}: {}", template_id, e),
+    }
+}
+
+/// Mirror orders and positions placed outside zakaz so dashboards see the whole account
+async fn adopt_orphans(state: &State, ib_client: &Arc<tokio::sync::Mutex<IBClient>>) {
+    let result = ib_client.lock().await.adopt_orphans().await;
+    match result {
+        Ok(0) => {}
+        Ok(count) => {
+            state.send_message_to_ui(UIMessage::StatusMessage(format!("Mirrored {} IB order(s)/position(s) as read-only", count)));
+            update_templates(state, ib_client).await;
+        }
+        Err(e) => {
+            err!("Failed to adopt orphan orders: {}", e);
+            state.send_message_to_ui(UIMessage::ErrorMessage(format!("Failed to load IB orders: {}", e)));
+        }
+    }
+}
+
+/// Run a confirmed action through its usual message so it is reported the same way
+async fn run_confirmed(action: LiveAction, state: State) -> Result<(), String> {
+    match action {
+        LiveAction::Activate { template_id, override_price_band, override_limits } => {
+            let (response, rx) = oneshot::channel();
+            dispatch(IBMessage::ActivateTemplate { template_id, override_price_band, override_limits, response }, state, rx).await
+        }
+        LiveAction::ActivateOcoPair { template_id, override_price_band, override_limits } => {
+            let (response, rx) = oneshot::channel();
+            dispatch(IBMessage::ActivateOcoPair { template_id, override_price_band, override_limits, response }, state, rx).await
+        }
+        LiveAction::Deactivate { template_id } => {
+            let (response, rx) = oneshot::channel();
+            dispatch(IBMessage::DeactivateTemplate { template_id, response }, state, rx).await
+        }
+        LiveAction::ActivateBasket { basket_id } => {
+            let (response, rx) = oneshot::channel();
+            dispatch(IBMessage::ActivateBasket { basket_id, response }, state, rx).await
+        }
+        LiveAction::DeactivateBasket { basket_id } => {
+            let (response, rx) = oneshot::channel();
+            dispatch(IBMessage::DeactivateBasket { basket_id, response }, state, rx).await
+        }
+    }
+}
+
+async fn dispatch<T>(msg: IBMessage, state: State, rx: oneshot::Receiver<Result<T, String>>) -> Result<(), String> {
+    Box::pin(handle_ib_message(msg, state, None)).await;
+    rx.await.map_err(|e| e.to_string())?.map(|_| ())
+}
+
+/// Fill model defaults, size from risk when asked and save; reports any size reductions
+async fn create_sized_template(
+    state: &State,
+    ib_client: &Arc<tokio::sync::Mutex<IBClient>>,
+    mut template: crate::ib::OrderTemplate,
+    auto_size: bool,
+) -> Result<String, crate::error::AppError> {
+    let ib_client_guard = ib_client.lock().await;
+    ib_client_guard.apply_model_defaults(&mut template).await?;
+    if auto_size {
+        let sizing = ib_client_guard.size_from_risk(&mut template).await?;
+        if sizing.was_reduced() {
+            let reasons: Vec<String> = sizing.reductions.iter().map(|r| r.to_string()).collect();
+            state.send_message_to_ui(UIMessage::StatusMessage(format!("{}: {}", template.name, reasons.join("; "))));
+        }
+    }
+    ib_client_guard.create_template(template).await
+}
+
+/// Send each of `symbol`'s market data updates to the UI, measuring its relative volume again
+/// every `RVOL_REFRESH`. Returns the forwarding task's handle; stopping it ends the stream.
+fn forward_market_data(
+    state: &State,
+    ib_client: &Arc<tokio::sync::Mutex<IBClient>>,
+    symbol: String,
+    mut updates: mpsc::UnboundedReceiver<MarketData>,
+) -> tokio::task::AbortHandle {
+    let state = state.clone();
+    let ib_client = ib_client.clone();
+    let task = tokio::spawn(async move {
+        let mut rvol_refresh = tokio::time::interval(RVOL_REFRESH);
+        rvol_refresh.tick().await; // Measured as the stream started
+        let mut last_price = 0.0;
+        let mut last_stop_check: Option<std::time::Instant> = None;
+        loop {
+            tokio::select! {
+                update = updates.recv() => {
+                    let Some(data) = update else {
+                        break;
+                    };
+                    let throttled = last_stop_check.is_some_and(|at| at.elapsed() < STOP_RULE_INTERVAL);
+                    if data.last > 0.0 && data.last != last_price && !throttled
+                        && let Some(runtime) = &state.runtime {
+                        last_price = data.last;
+                        last_stop_check = Some(std::time::Instant::now());
+                        runtime.tell(RuntimeInMessage::IB(IBMessage::ApplyStopRules { symbol: Some(symbol.clone()) }));
+                    }
+                    state.send_message_to_ui(UIMessage::IBMarketData {
+                        symbol: data.symbol,
+                        bid: data.bid,
+                        ask: data.ask,
+                        last: data.last,
+                        volume: data.volume,
+                        relative_volume: data.relative_volume,
+                    });
+                }
+                _ = rvol_refresh.tick() => {
+                    let result = ib_client.lock().await.refresh_relative_volume(&symbol).await;
+                    if let Err(e) = result {
+                        wrn!("Failed to refresh relative volume for {}: {}", symbol, e);
+                    }
+                }
+            }
+        }
+    });
+    task.abort_handle()
+}
+
+/// Stream market data for every symbol whose filled templates have stop rules, and end the
+/// streams opened here once no template needs them
+async fn sync_stop_rule_streams(state: &mut State, ib_client: &Arc<tokio::sync::Mutex<IBClient>>) {
+    let needed = ib_client.lock().await.stop_rule_symbols().await;
+    let idle: Vec<String> = state.stop_rule_streams.iter()
+        .filter(|symbol| !needed.contains(*symbol))
+        .cloned()
+        .collect();
+    for symbol in idle {
+        state.stop_rule_streams.remove(&symbol);
+        if let Some(forwarding) = state.market_data_streams.remove(&symbol) {
+            forwarding.abort();
+        }
+        ib_client.lock().await.unsubscribe_market_data(&symbol).await;
+    }
+    
+    for symbol in needed {
+        // A stream that ended on an IB error is opened again
+        if state.market_data_streams.get(&symbol).is_some_and(|forwarding| !forwarding.is_finished()) {
+            continue;
+        }
+        let result = ib_client.lock().await.subscribe_market_data(&symbol).await;
+        match result {
+            Ok(updates) => {
+                let forwarding = forward_market_data(state, ib_client, symbol.clone(), updates);
+                state.market_data_streams.insert(symbol.clone(), forwarding);
+                state.stop_rule_streams.insert(symbol);
+            }
+            Err(e) => wrn!("No market data for {} stop rules, checked on the scheduler only: {}", symbol, e),
+        }
+    }
+}
+
 async fn update_templates(state: &State, ib_client: &Arc<tokio::sync::Mutex<IBClient>>) {
     let templates = ib_client.lock().await.get_all_templates().await;
     state.send_message_to_ui(UIMessage::IBOrderTemplateUpdate { templates });
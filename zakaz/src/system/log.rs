@@ -54,7 +54,7 @@ impl Log {
     fn cleanup_old_logs(dir: &str, keep: usize) -> AppResult<()> {
         let mut logs: Vec<PathBuf> = fs::read_dir(dir)?
             .filter_map(Result::ok)
-            .filter(|e| e.path().extension().map_or(false, |ext| ext == "log"))
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "log"))
             .map(|e| e.path())
             .collect();
 
@@ -112,26 +112,26 @@ impl Log {
 #[macro_export]
 macro_rules! inf {
     ($msg:expr) => {
-        crate::system::log::Log::info($msg)
+        $crate::system::log::Log::info($msg)
     };
     ($( $arg:tt )*) => {
-        crate::system::log::Log::info(&format!($( $arg )*))
+        $crate::system::log::Log::info(&format!($( $arg )*))
     };
 }
 
 #[macro_export]
 macro_rules! wrn {
     ($( $arg:tt )*) => {
-        crate::system::log::Log::warn(&format!($( $arg )*))
+        $crate::system::log::Log::warn(&format!($( $arg )*))
     };
 }
 
 #[macro_export]
 macro_rules! err {
     ($msg:expr) => {
-        crate::system::log::Log::err($msg)
+        $crate::system::log::Log::err($msg)
     };
     ($( $arg:tt )*) => {
-        crate::system::log::Log::err(&format!($( $arg )*))
+        $crate::system::log::Log::err(&format!($( $arg )*))
     };
 }
\ No newline at end of file
@@ -126,7 +126,9 @@ impl Mailbox {
 
                     RuntimeInMessage::IB(ib_msg) => {
                         // Handle IB messages in a separate handler
-                        // Convert mpsc::Sender to oneshot::Sender by not passing reply_channel
+                        // Convert mpsc::Sender to oneshot::Sender by not passing reply_channel;
+                        // acknowledge here so `tell` callers don't see a closed response channel
+                        notify_channel!(reply_channel, RuntimeOutMessage::Ok);
                         crate::system::ib_handler::handle_ib_message(ib_msg, state, None).await
                     }
                     
@@ -134,6 +136,13 @@ impl Mailbox {
                         // Handle chart messages in a separate handler
                         crate::system::chart_handler::handle_chart_message(chart_msg, state, None).await
                     }
+                    
+                    RuntimeInMessage::Backtest { template_ids, from, config, response } => {
+                        notify_channel!(reply_channel, RuntimeOutMessage::Ok);
+                        // A replay fetches history for every symbol; keep the mailbox free meanwhile
+                        tokio::spawn(crate::system::backtest_handler::run_backtest(template_ids, from, config, response, state.clone()));
+                        state
+                    }
                 }
             }
         ).await;
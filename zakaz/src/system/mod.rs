@@ -6,4 +6,5 @@ pub mod event;
 pub mod log;
 pub mod macros;
 pub mod ib_handler;
-pub mod chart_handler;
\ No newline at end of file
+pub mod chart_handler;
+pub mod backtest_handler;
\ No newline at end of file
@@ -1,10 +1,13 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use mailbox_processor::MailboxProcessor;
 use tokio::sync::Mutex;
 
 use crate::{
     err, inf,
+    db::Database,
+    ib::{messages::IBMessage, IBClient},
     system::{
         event::Event,
         mailbox::Mailbox,
@@ -13,6 +16,9 @@ use crate::{
     },
 };
 
+/// How often the scheduler checks for scheduled activations and the end-of-day cutoff
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(15);
+
 #[derive(Debug)]
 pub struct Runtime {
     /// Internal message processing queue
@@ -35,6 +41,32 @@ impl Runtime {
         // Store a reference to the runtime in the state
         state.runtime = Some(runtime.clone());
 
+        // Open the database and restore persisted order templates
+        match Database::new().await {
+            Ok(db) => {
+                let ib_client = IBClient::with_database(db.clone());
+                if let Err(e) = ib_client.load_templates().await {
+                    err!("Failed to load order templates: {}", e);
+                }
+                // Templates first: trades take their model and planned risk from them
+                if let Err(e) = ib_client.rebuild_trades(None).await {
+                    err!("Failed to build trades: {}", e);
+                }
+                if let Err(e) = ib_client.load_trading_lock().await {
+                    err!("Failed to restore trading lock: {}", e);
+                }
+                match ib_client.selected_chart_theme().await {
+                    Ok((_, theme)) => state.chart_theme = Some(theme),
+                    Err(e) => err!("Failed to load chart theme: {}", e),
+                }
+                state.ib_client = Some(Arc::new(Mutex::new(ib_client)));
+                state.db = Some(db);
+            }
+            Err(e) => {
+                err!("Failed to open database, templates will not be persisted: {}", e);
+            }
+        }
+
         // Set new state to mailbox
         runtime.tell(RuntimeInMessage::NewState(state));
         runtime
@@ -58,6 +90,31 @@ impl Runtime {
                 }),
             );
         });
+
+        self.start_scheduler();
+    }
+
+    /// Periodically ask the IB handler to run time-based template actions
+    fn start_scheduler(self: &Arc<Self>) {
+        let rt = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                rt.tell(RuntimeInMessage::IB(IBMessage::ActivateScheduledTemplates));
+                rt.tell(RuntimeInMessage::IB(IBMessage::DeactivateDayTemplates));
+                rt.tell(RuntimeInMessage::IB(IBMessage::SyncFills));
+                rt.tell(RuntimeInMessage::IB(IBMessage::ApplyStopRules { symbol: None }));
+                rt.tell(RuntimeInMessage::IB(IBMessage::ExpireTemplates));
+                rt.tell(RuntimeInMessage::IB(IBMessage::CheckLossLimit));
+                rt.tell(RuntimeInMessage::IB(IBMessage::TrackDrawdown));
+                rt.tell(RuntimeInMessage::IB(IBMessage::CheckVolatilitySpikes));
+                rt.tell(RuntimeInMessage::IB(IBMessage::BackupIfDue));
+                rt.tell(RuntimeInMessage::IB(IBMessage::PurgeDeletedTemplates));
+            }
+        });
     }
 
     /// Send message without waiting for result
@@ -1,10 +1,82 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::system::{runtime::Runtime, types::UIMessage};
 
+/// Comparison symbols' chart bars by symbol, bar size and whether they are RTH only, with
+/// when they were fetched
+pub type ChartBarCache = HashMap<(String, String, bool), (DateTime<Utc>, Vec<crate::ib::types::HistoricalBar>)>;
+
+/// One chart of a grid while another is active: the state's per-chart fields, parked
+#[derive(Debug, Clone)]
+pub struct ChartSlot {
+    pub data: Option<(String, Vec<crate::ib::types::HistoricalBar>)>,
+    pub viewport_controller: Option<Arc<tokio::sync::Mutex<crate::charts::ViewportController>>>,
+    pub indicators: crate::charts::types::ChartIndicators,
+    pub bar_size: String,
+    pub kind: crate::charts::types::ChartKind,
+    pub trades: crate::charts::types::TradeMarkers,
+    pub drawings: Vec<crate::charts::types::Drawing>,
+    pub atr_exclusions: Vec<crate::ib::types::ExcludedBar>,
+    pub comparisons: Vec<String>,
+    pub comparison_series: Vec<crate::charts::types::ComparisonSeries>,
+    pub frame: Option<crate::charts::ChartFrame>,
+    pub replay: Option<crate::charts::ReplayController>,
+    pub live: Option<tokio::task::AbortHandle>,
+}
+
+impl Default for ChartSlot {
+    fn default() -> Self {
+        Self {
+            data: None,
+            viewport_controller: None,
+            indicators: Default::default(),
+            bar_size: "1 day".to_string(),
+            kind: Default::default(),
+            trades: Default::default(),
+            drawings: Vec::new(),
+            atr_exclusions: Vec::new(),
+            comparisons: Vec::new(),
+            comparison_series: Vec::new(),
+            frame: None,
+            replay: None,
+            live: None,
+        }
+    }
+}
+
+impl ChartSlot {
+    /// Trade this slot's chart for the one in `state`'s chart fields
+    pub fn swap_with(&mut self, state: &mut State) {
+        std::mem::swap(&mut self.data, &mut state.chart_data);
+        std::mem::swap(&mut self.viewport_controller, &mut state.viewport_controller);
+        std::mem::swap(&mut self.indicators, &mut state.chart_indicators);
+        std::mem::swap(&mut self.bar_size, &mut state.chart_bar_size);
+        std::mem::swap(&mut self.kind, &mut state.chart_kind);
+        std::mem::swap(&mut self.trades, &mut state.chart_trades);
+        std::mem::swap(&mut self.drawings, &mut state.chart_drawings);
+        std::mem::swap(&mut self.atr_exclusions, &mut state.chart_atr_exclusions);
+        std::mem::swap(&mut self.comparisons, &mut state.chart_comparisons);
+        std::mem::swap(&mut self.comparison_series, &mut state.chart_comparison_series);
+        std::mem::swap(&mut self.frame, &mut state.chart_frame);
+        std::mem::swap(&mut self.replay, &mut state.chart_replay);
+        std::mem::swap(&mut self.live, &mut state.chart_live);
+    }
+}
+
+/// Charts shown together in the chart view. The active slot's chart lives in the state's
+/// chart fields, where every chart message works on it; the others are parked in `slots`.
+#[derive(Debug, Clone, Default)]
+pub struct ChartLayout {
+    pub grid: crate::charts::ChartGrid,
+    pub active: usize,
+    pub slots: Vec<ChartSlot>, // One per slot; the active one's is left empty
+    pub composite: Option<slint::SharedPixelBuffer<slint::Rgb8Pixel>>, // Last grid render, without the crosshair
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct State {
     /// Version number for state tracking
@@ -21,6 +93,9 @@ pub struct State {
     /// IB client instance (not serialized)
     #[serde(skip)]
     pub ib_client: Option<Arc<tokio::sync::Mutex<crate::ib::IBClient>>>,
+    /// Database handle (not serialized)
+    #[serde(skip)]
+    pub db: Option<Arc<tokio::sync::Mutex<crate::db::Database>>>,
     /// Chart data (not serialized)
     #[serde(skip)]
     pub chart_data: Option<(String, Vec<crate::ib::types::HistoricalBar>)>,
@@ -30,6 +105,57 @@ pub struct State {
     /// Chart theme (not serialized)
     #[serde(skip)]
     pub chart_theme: Option<crate::charts::ChartTheme>,
+    /// Indicator overlays on the price chart (not serialized)
+    #[serde(skip)]
+    pub chart_indicators: crate::charts::types::ChartIndicators,
+    /// Bar size of the price chart (not serialized)
+    #[serde(skip)]
+    pub chart_bar_size: String,
+    /// How the price chart draws bars (not serialized)
+    #[serde(skip)]
+    pub chart_kind: crate::charts::types::ChartKind,
+    /// Crosshair position in chart pixels (not serialized)
+    #[serde(skip)]
+    pub chart_cursor: Option<(i32, i32)>,
+    /// Fills and open position for the charted symbol (not serialized)
+    #[serde(skip)]
+    pub chart_trades: crate::charts::types::TradeMarkers,
+    /// Drawings on the charted symbol (not serialized)
+    #[serde(skip)]
+    pub chart_drawings: Vec<crate::charts::types::Drawing>,
+    /// Daily bars the charted symbol's filtered ATR left out (not serialized)
+    #[serde(skip)]
+    pub chart_atr_exclusions: Vec<crate::ib::types::ExcludedBar>,
+    /// Symbols compared against the charted one (not serialized)
+    #[serde(skip)]
+    pub chart_comparisons: Vec<String>,
+    /// Bars of the comparison symbols, loaded with the chart (not serialized)
+    #[serde(skip)]
+    pub chart_comparison_series: Vec<crate::charts::types::ComparisonSeries>,
+    /// Recently fetched comparison bars (not serialized)
+    #[serde(skip)]
+    pub chart_bar_cache: ChartBarCache,
+    /// Last rendered chart without its crosshair, redrawn over on cursor moves (not serialized)
+    #[serde(skip)]
+    pub chart_frame: Option<crate::charts::ChartFrame>,
+    /// Size the chart is rendered at, following the chart view (not serialized)
+    #[serde(skip)]
+    pub chart_size: crate::charts::types::ChartSize,
+    /// Bar replay in progress on the chart, if any (not serialized)
+    #[serde(skip)]
+    pub chart_replay: Option<crate::charts::ReplayController>,
+    /// Task forwarding the charted symbol's streamed bars, if any (not serialized)
+    #[serde(skip)]
+    pub chart_live: Option<tokio::task::AbortHandle>,
+    /// Charts shown together and which one is active (not serialized)
+    #[serde(skip)]
+    pub chart_layout: ChartLayout,
+    /// Tasks forwarding each subscribed symbol's market data (not serialized)
+    #[serde(skip)]
+    pub market_data_streams: HashMap<String, tokio::task::AbortHandle>,
+    /// Symbols streamed only so their stop rules see the price (not serialized)
+    #[serde(skip)]
+    pub stop_rule_streams: HashSet<String>,
 }
 
 impl State {
@@ -41,9 +167,27 @@ impl State {
             is_running: false,
             runtime: None,
             ib_client: None,
+            db: None,
             chart_data: None,
             viewport_controller: None,
             chart_theme: None,
+            chart_indicators: Default::default(),
+            chart_bar_size: "1 day".to_string(),
+            chart_kind: Default::default(),
+            chart_cursor: None,
+            chart_trades: Default::default(),
+            chart_drawings: Vec::new(),
+            chart_atr_exclusions: Vec::new(),
+            chart_comparisons: Vec::new(),
+            chart_comparison_series: Vec::new(),
+            chart_bar_cache: HashMap::new(),
+            chart_frame: None,
+            chart_size: Default::default(),
+            chart_replay: None,
+            chart_live: None,
+            chart_layout: ChartLayout { slots: vec![ChartSlot::default()], ..Default::default() },
+            market_data_streams: HashMap::new(),
+            stop_rule_streams: HashSet::new(),
         }
     }
 
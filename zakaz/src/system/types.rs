@@ -2,11 +2,14 @@ use std::fmt;
 
 use chrono::{DateTime, Local, Utc};
 
+use tokio::sync::oneshot;
+
 use crate::charts::ChartViewport;
+use crate::ib::backtest::{BacktestConfig, BacktestReport};
 use crate::ib::messages::IBMessage;
 
 #[derive(Debug)]
-#[allow(dead_code)]
+#[allow(dead_code, clippy::large_enum_variant)]
 pub enum RuntimeInMessage<S> {
     Start,
     Stop,
@@ -26,10 +29,18 @@ pub enum RuntimeInMessage<S> {
     IB(IBMessage),
     /// Chart-related messages
     Chart(ChartMessage),
+    /// Replay templates over daily bars and report per-model statistics.
+    /// Runs in the background, posting `BacktestProgress` after each template.
+    Backtest {
+        template_ids: Vec<String>,      // Empty: every template in the working set
+        from: Option<DateTime<Utc>>,    // Replay each plan from here; None: from its creation
+        config: BacktestConfig,
+        response: oneshot::Sender<Result<BacktestReport, String>>,
+    },
 }
 
 #[derive(Debug)]
-#[allow(dead_code)]
+#[allow(dead_code, clippy::large_enum_variant)]
 pub enum RuntimeOutMessage<S> {
     /// Used to signify that the runtime has started
     Started(DateTime<Local>),
@@ -63,6 +74,8 @@ pub enum UIMessage {
         live_connected: bool,
         active_account: Option<crate::ib::AccountType>,
     },
+    /// Daily loss limit lock engaged (Some, with the reason) or released (None)
+    TradingLockStatus(Option<String>),
     /// IB order template update
     IBOrderTemplateUpdate {
         templates: Vec<crate::ib::OrderTemplate>,
@@ -74,12 +87,26 @@ pub enum UIMessage {
         ask: f64,
         last: f64,
         volume: i64,
+        relative_volume: Option<f64>,
     },
     /// Chart image update
     ChartImageUpdate {
-        image_data: Vec<u8>,
-        width: u32,
-        height: u32,
+        image: slint::SharedPixelBuffer<slint::Rgb8Pixel>, // Rendered in place, shown without a copy
+        symbol: String,
+    },
+    /// Chart themes to choose from, built-in first, and the one in use
+    ChartThemes {
+        names: Vec<String>,
+        selected: String,
+    },
+    /// Color scheme preference for the UI's palette
+    ColorScheme(crate::charts::theme::ColorSchemePreference),
+    /// Settings were saved; the client already reads the new values
+    SettingsChanged(Box<crate::db::settings::Settings>),
+    /// Backtest progress: `completed` of `total` templates replayed, the last on `symbol`
+    BacktestProgress {
+        completed: usize,
+        total: usize,
         symbol: String,
     },
 }
@@ -96,14 +123,24 @@ impl fmt::Display for UIMessage {
                 write!(f, "IB Status - Paper: {}, Live: {}, Active: {:?}", 
                     paper_connected, live_connected, active_account)
             },
+            UIMessage::TradingLockStatus(Some(reason)) => write!(f, "{}", reason),
+            UIMessage::TradingLockStatus(None) => write!(f, "Trading unlocked"),
             UIMessage::IBOrderTemplateUpdate { templates } => {
                 write!(f, "Order templates updated: {} templates", templates.len())
             },
             UIMessage::IBMarketData { symbol, last, .. } => {
                 write!(f, "Market data for {}: ${:.2}", symbol, last)
             },
-            UIMessage::ChartImageUpdate { symbol, width, height, .. } => {
-                write!(f, "Chart updated for {} ({}x{})", symbol, width, height)
+            UIMessage::ChartImageUpdate { symbol, image } => {
+                write!(f, "Chart updated for {} ({}x{})", symbol, image.width(), image.height())
+            },
+            UIMessage::ChartThemes { names, selected } => {
+                write!(f, "Chart themes: {} ({} in use)", names.join(", "), selected)
+            },
+            UIMessage::ColorScheme(preference) => write!(f, "Color scheme: {}", preference.as_str()),
+            UIMessage::SettingsChanged(_) => write!(f, "Settings changed"),
+            UIMessage::BacktestProgress { completed, total, symbol } => {
+                write!(f, "Backtest: {}/{} templates ({})", completed, total, symbol)
             },
         }
     }
@@ -115,6 +152,10 @@ pub enum ChartMessage {
     UpdateChart {
         symbol: String,
         theme: Option<crate::charts::ChartTheme>,
+        indicators: Option<crate::charts::types::ChartIndicators>, // None keeps the current overlays
+        bar_size: Option<String>, // "1 day", "1 hour" or "5 mins"; None keeps the current one
+        kind: Option<crate::charts::types::ChartKind>, // None keeps the current kind
+        comparisons: Option<Vec<String>>, // Symbols overlaid for comparison; None keeps the current ones
     },
     /// Pan the chart
     Pan {
@@ -129,6 +170,125 @@ pub enum ChartMessage {
     },
     /// Reset zoom
     ResetZoom,
+    /// Draw the crosshair at a cursor position, in chart image pixels
+    CursorMoved {
+        x: f64,
+        y: f64,
+    },
+    /// Hide the crosshair
+    CursorLeft,
     /// Set viewport directly
     SetViewport(ChartViewport),
+    /// Show a preset span of bars up to the latest, fitting the price axis to them
+    ApplyPreset {
+        preset: crate::charts::types::ViewportPreset,
+    },
+    /// Scroll one visible span towards older bars, fitting the price axis
+    PageLeft,
+    /// Scroll one visible span towards newer bars, fitting the price axis
+    PageRight,
+    /// Scroll to the latest bar at the same zoom, fitting the price axis
+    JumpToLatest,
+    /// Save the charted symbol as shown, without the crosshair, captioned with the symbol,
+    /// bar size and time
+    ExportChart {
+        path: std::path::PathBuf,
+        format: crate::charts::types::ExportFormat,
+        width: u32,
+        height: u32,
+    },
+    /// Save a custom chart theme under `name`, redrawing if it is the one in use
+    SaveTheme {
+        name: String,
+        theme: crate::charts::ChartTheme,
+    },
+    /// Send the chart themes to choose from to the UI
+    LoadThemes,
+    /// Follow the OS's light or dark preference, or stay on one, now and on later starts
+    SetColorScheme {
+        preference: crate::charts::theme::ColorSchemePreference,
+    },
+    /// Send the stored color scheme preference to the UI
+    LoadColorScheme,
+    /// The UI's palette turned dark or light, with the OS or by preference: swap to the
+    /// matching built-in chart theme if one is in use
+    UiColorScheme {
+        dark: bool,
+    },
+    /// Draw charts with the theme named `name`, now and on later starts
+    SelectTheme {
+        name: String,
+    },
+    /// The chart view was laid out at a new size, in physical pixels
+    Resize {
+        width: u32,
+        height: u32,
+        scale_factor: f64,
+    },
+    /// Replay the charted bars from `from`'s session, or the last few dozen without it,
+    /// paused on the bar before
+    ReplayStart {
+        from: Option<chrono::DateTime<chrono::Utc>>,
+    },
+    /// Reveal the replay's bars one after another at its speed
+    ReplayPlay,
+    ReplayPause,
+    /// Reveal the replay's next bar
+    ReplayStep,
+    /// Hide the replay's latest bar, dropping entries made on it
+    ReplayStepBack,
+    /// Bars revealed per second while the replay plays
+    ReplaySpeed {
+        bars_per_second: f64,
+    },
+    /// Time for the next bar of a playing replay; stale once `generation` is out of date
+    ReplayTick {
+        generation: u64,
+    },
+    /// Simulate a template as if activated at the replay's latest bar (daily bars only)
+    ReplayEnter {
+        template_id: String,
+    },
+    /// End the replay and chart all its bars again
+    ReplayStop,
+    /// A streamed bar for `symbol`, folded into the last bar of every chart of it
+    LiveBar {
+        symbol: String,
+        bar: crate::ib::types::HistoricalBar,
+    },
+    /// A second has passed on `symbol`'s stream: redraw its charts' bar countdowns
+    CountdownTick {
+        symbol: String,
+    },
+    /// Show one, two or four charts at once. The active chart is kept, moving to the first
+    /// slot if its slot goes; other charts in dropped slots are closed
+    SetChartGrid {
+        grid: crate::charts::ChartGrid,
+    },
+    /// Make a slot's chart the active one, which the other chart messages work on
+    SelectChartSlot {
+        slot: usize,
+    },
+    /// Chart `symbol` in a slot and make it active; None keeps the slot's bar size
+    AssignChartSlot {
+        slot: usize,
+        symbol: String,
+        bar_size: Option<String>,
+    },
+    /// Store a drawing and show it if its symbol is charted
+    AddDrawing(crate::charts::types::Drawing),
+    /// Give a stored drawing new anchors
+    MoveDrawing {
+        id: String,
+        shape: crate::charts::types::DrawingShape,
+    },
+    /// Remove a stored drawing
+    DeleteDrawing {
+        id: String,
+    },
+    /// Chart the daily equity curve of trades closed within `range`
+    UpdateEquityCurve {
+        range: crate::ib::performance::PerformanceRange,
+        theme: Option<crate::charts::ChartTheme>,
+    },
 }
\ No newline at end of file
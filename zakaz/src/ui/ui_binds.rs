@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
+use slint::ComponentHandle;
+
 use crate::{
     MainWindow,
+    ib::messages::IBMessage,
     system::{
         runtime::Runtime,
         types::{ChartMessage, RuntimeInMessage},
@@ -64,6 +67,57 @@ pub fn bind_ui_events(runtime: Arc<Runtime>, ui: Arc<MainWindow>) {
         }));
     });
     
+    // Bind chart crosshair
+    let rt = runtime.clone();
+    ui.on_chart_cursor_moved(move |x, y| {
+        rt.tell(RuntimeInMessage::Chart(ChartMessage::CursorMoved {
+            x: x as f64,
+            y: y as f64,
+        }));
+    });
+    
+    let rt = runtime.clone();
+    ui.on_chart_cursor_left(move || {
+        rt.tell(RuntimeInMessage::Chart(ChartMessage::CursorLeft));
+    });
+    
+    // Bind chart resize; the chart is rendered in physical pixels so it stays sharp
+    let rt = runtime.clone();
+    let ui_handle = ui.as_weak();
+    ui.on_chart_resized(move |width, height| {
+        let scale_factor = ui_handle.upgrade()
+            .map(|ui| ui.window().scale_factor() as f64)
+            .unwrap_or(1.0);
+        rt.tell(RuntimeInMessage::Chart(ChartMessage::Resize {
+            width: (width as f64 * scale_factor).round() as u32,
+            height: (height as f64 * scale_factor).round() as u32,
+            scale_factor,
+        }));
+    });
+    
+    // Follow the palette's color scheme with the chart theme, from its current one
+    let rt = runtime.clone();
+    ui.on_color_scheme_changed(move |dark| {
+        rt.tell(RuntimeInMessage::Chart(ChartMessage::UiColorScheme { dark }));
+    });
+    runtime.tell(RuntimeInMessage::Chart(ChartMessage::UiColorScheme { dark: ui.get_dark_palette() }));
+    runtime.tell(RuntimeInMessage::Chart(ChartMessage::LoadColorScheme));
+    
+    // Bind breakeven buttons; the IB handler reports the new stop or why it couldn't move
+    let rt = runtime.clone();
+    ui.on_move_stop_to_breakeven(move |template_id, offset| {
+        let Ok(offset) = offset.trim().parse::<f64>() else {
+            rt.tell(RuntimeInMessage::Error(format!("Invalid breakeven offset: {}", offset)));
+            return;
+        };
+        let (tx, _) = tokio::sync::oneshot::channel();
+        rt.tell(RuntimeInMessage::IB(IBMessage::MoveStopToBreakeven {
+            template_id: template_id.to_string(),
+            offset,
+            response: tx,
+        }));
+    });
+    
     // Bind chart reset zoom
     let rt = runtime.clone();
     ui.on_chart_reset_zoom(move || {
@@ -90,6 +144,10 @@ pub fn bind_ui_events(runtime: Arc<Runtime>, ui: Arc<MainWindow>) {
                     rt_inner.tell(RuntimeInMessage::Chart(ChartMessage::UpdateChart {
                         symbol: "AAPL".to_string(),
                         theme: None,
+                        indicators: None,
+                        bar_size: None,
+                        kind: None,
+                        comparisons: None,
                     }));
                 }
                 Ok(Err(e)) => {
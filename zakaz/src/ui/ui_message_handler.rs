@@ -1,9 +1,9 @@
-use crate::{inf, err};
+use crate::{inf, err, wrn};
 use crate::system::types::UIMessage;
-use crate::MainWindow;
-use slint::{Weak, SharedString};
+use crate::{FilledTemplate, MainWindow};
+use slint::{ModelRc, SharedString, VecModel, Weak};
 
-pub fn get_ui_message_handler(weak_handle: Weak<MainWindow>) -> impl Fn(UIMessage) -> () {
+pub fn get_ui_message_handler(weak_handle: Weak<MainWindow>) -> impl Fn(UIMessage) {
     let ui_handle = weak_handle.clone();
     inf!("UI message handler created");
 
@@ -73,26 +73,48 @@ pub fn get_ui_message_handler(weak_handle: Weak<MainWindow>) -> impl Fn(UIMessag
                     paper_connected, live_connected, active_account);
                 // TODO: Update UI with IB connection status
             }
+            UIMessage::TradingLockStatus(reason) => {
+                wrn!("Trading lock: {}", reason.as_deref().unwrap_or("released"));
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_handle.upgrade() {
+                        ui.set_trading_lock_message(SharedString::from(reason.unwrap_or_default()));
+                    } else {
+                        err!("Failed to get Window pointer");
+                    }
+                });
+            }
             UIMessage::IBOrderTemplateUpdate { templates } => {
                 inf!("Order templates updated: {} templates", templates.len());
-                // TODO: Update UI with order templates
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_handle.upgrade() {
+                        let mut filled: Vec<_> = templates.iter()
+                            .filter(|t| t.has_fills() && !t.is_read_only)
+                            .collect();
+                        filled.sort_by(|a, b| a.name.cmp(&b.name));
+                        let rows: Vec<FilledTemplate> = filled.into_iter()
+                            .map(|t| FilledTemplate {
+                                id: SharedString::from(t.id.as_str()),
+                                name: SharedString::from(t.name.as_str()),
+                                stop: SharedString::from(format!("{:.2}", t.stop_price)),
+                            })
+                            .collect();
+                        ui.set_filled_templates(ModelRc::new(VecModel::from(rows)));
+                    } else {
+                        err!("Failed to get Window pointer");
+                    }
+                });
             }
-            UIMessage::IBMarketData { symbol, bid, ask, last, volume } => {
-                inf!("Market data for {}: bid={}, ask={}, last={}, volume={}", 
-                    symbol, bid, ask, last, volume);
+            UIMessage::IBMarketData { symbol, bid, ask, last, volume, relative_volume } => {
+                inf!("Market data for {}: bid={}, ask={}, last={}, volume={}, rvol={:?}", 
+                    symbol, bid, ask, last, volume, relative_volume);
                 // TODO: Update UI with market data
             }
-            UIMessage::ChartImageUpdate { image_data, width, height, symbol } => {
-                inf!("Chart image update for {} ({}x{})", symbol, width, height);
+            UIMessage::ChartImageUpdate { image, symbol } => {
+                inf!("Chart image update for {} ({}x{})", symbol, image.width(), image.height());
                 let _ = slint::invoke_from_event_loop(move || {
                     if let Some(ui) = ui_handle.upgrade() {
-                        // Create Slint image from RGB data
-                        let pixel_buffer = slint::SharedPixelBuffer::<slint::Rgb8Pixel>::clone_from_slice(
-                            &image_data,
-                            width,
-                            height,
-                        );
-                        let image = slint::Image::from_rgb8(pixel_buffer);
+                        // The chart was rendered into the pixel buffer, so it is shown as is
+                        let image = slint::Image::from_rgb8(image);
                         
                         // Update UI
                         ui.set_chart_image(image);
@@ -102,6 +124,42 @@ pub fn get_ui_message_handler(weak_handle: Weak<MainWindow>) -> impl Fn(UIMessag
                     }
                 });
             }
+            UIMessage::ChartThemes { names, selected } => {
+                inf!("Chart themes: {} ({} in use)", names.join(", "), selected);
+                // TODO: Update UI with chart themes
+            }
+            UIMessage::ColorScheme(preference) => {
+                inf!("Color scheme: {}", preference.as_str());
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_handle.upgrade() {
+                        ui.set_color_scheme_preference(SharedString::from(preference.as_str()));
+                    } else {
+                        err!("Failed to get Window pointer");
+                    }
+                });
+            }
+            UIMessage::SettingsChanged(settings) => {
+                inf!("Settings changed");
+                let preference = settings.ui.color_scheme;
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_handle.upgrade() {
+                        ui.set_color_scheme_preference(SharedString::from(preference.as_str()));
+                        ui.set_status_message(SharedString::from("Settings saved"));
+                    } else {
+                        err!("Failed to get Window pointer");
+                    }
+                });
+            }
+            UIMessage::BacktestProgress { completed, total, symbol } => {
+                inf!("Backtest: {}/{} templates ({})", completed, total, symbol);
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_handle.upgrade() {
+                        ui.set_status_message(SharedString::from(format!("Backtest: {}/{} templates ({})", completed, total, symbol)));
+                    } else {
+                        err!("Failed to get Window pointer");
+                    }
+                });
+            }
         }
     }
 }
\ No newline at end of file